@@ -7,16 +7,22 @@ echo "🦀 Building UniFFI for Swift Package (workspace root -> ios/DialogPackag
 export IPHONEOS_DEPLOYMENT_TARGET=18.4
 export MACOSX_DEPLOYMENT_TARGET=14.0
 
+# QA builds: CHAOS=1 enables SetChaos fault injection
+CARGO_FEATURES=()
+if [ -n "$CHAOS" ]; then
+  CARGO_FEATURES=(--features chaos)
+fi
+
 if [ -z "$SKIP_RUST" ]; then
   # Build for iOS architectures
   echo "📱 Building for iOS device (arm64)..."
-cargo build -p dialog_uniffi --release --target aarch64-apple-ios
+cargo build -p dialog_uniffi --release --target aarch64-apple-ios "${CARGO_FEATURES[@]}"
 
   echo "📱 Building for iOS simulator (arm64)..."
-cargo build -p dialog_uniffi --release --target aarch64-apple-ios-sim
+cargo build -p dialog_uniffi --release --target aarch64-apple-ios-sim "${CARGO_FEATURES[@]}"
 
   echo "💻 Building for macOS (arm64)..."
-cargo build -p dialog_uniffi --release --target aarch64-apple-darwin
+cargo build -p dialog_uniffi --release --target aarch64-apple-darwin "${CARGO_FEATURES[@]}"
 else
   echo "⏭️  SKIP_RUST=1 set; skipping Rust compilation and using existing artifacts"
 fi
@@ -0,0 +1,119 @@
+//! How notes are printed by `list`, `search` and the watch feed
+
+use dialog_lib::{Note, NoteIdFormat, format_note_id, grapheme_len, truncate_graphemes};
+use nostr_sdk::prelude::*;
+
+/// Used when the terminal width can't be found, e.g. output is piped
+const DEFAULT_WIDTH: usize = 80;
+/// Hex characters of the id shown in a table row; `--long` shows it whole
+const SHORT_ID_CHARS: usize = 8;
+/// Text keeps at least this many columns, even if tags then wrap
+const MIN_TEXT_WIDTH: usize = 20;
+
+/// `$COLUMNS` if set, else the terminal's width
+pub fn terminal_width() -> usize {
+    std::env::var("COLUMNS")
+        .ok()
+        .and_then(|columns| columns.parse().ok())
+        .or_else(|| terminal_size::terminal_size().map(|(width, _)| width.0 as usize))
+        .unwrap_or(DEFAULT_WIDTH)
+}
+
+/// "just now", "5m ago", "2h ago", "3d ago", "6w ago" or "2y ago"
+pub fn relative_time(at: Timestamp, now: Timestamp) -> String {
+    const MINUTE: u64 = 60;
+    const HOUR: u64 = 60 * MINUTE;
+    const DAY: u64 = 24 * HOUR;
+    const WEEK: u64 = 7 * DAY;
+    const YEAR: u64 = 365 * DAY;
+    // Notes from a clock that runs ahead count as just written
+    let secs = now.as_u64().saturating_sub(at.as_u64());
+    match secs {
+        0..MINUTE => "just now".to_string(),
+        MINUTE..HOUR => format!("{}m ago", secs / MINUTE),
+        HOUR..DAY => format!("{}h ago", secs / HOUR),
+        DAY..WEEK => format!("{}d ago", secs / DAY),
+        WEEK..YEAR => format!("{}w ago", secs / WEEK),
+        _ => format!("{}y ago", secs / YEAR),
+    }
+}
+
+pub struct NotePrinter {
+    /// Full text, date and id instead of one row per note
+    long: bool,
+    width: usize,
+    id_format: NoteIdFormat,
+}
+
+impl NotePrinter {
+    pub fn new(long: bool, id_format: NoteIdFormat) -> Self {
+        Self {
+            long,
+            width: terminal_width(),
+            id_format,
+        }
+    }
+
+    pub fn print_all(&self, notes: &[Note]) {
+        for note in notes {
+            self.print(note);
+        }
+    }
+
+    pub fn print(&self, note: &Note) {
+        if self.long {
+            self.print_long(note);
+        } else {
+            println!("{}", self.row(note, Timestamp::now()));
+        }
+    }
+
+    fn print_long(&self, note: &Note) {
+        println!(
+            "\n[{}] {}",
+            note.created_at.to_human_datetime(),
+            format_note_id(&note.id, self.id_format)
+        );
+        if note.is_hydrated {
+            println!("{}", note.text);
+        } else {
+            println!("(not downloaded; evicted from the local cache)");
+        }
+        if !note.tags.is_empty() {
+            println!("Tags: #{}", note.tags.join(" #"));
+        }
+        if let Some(status) = note.task_status {
+            println!("Task: {status}");
+        }
+        if note.word_count > 0 {
+            println!(
+                "{} words, {} min read",
+                note.word_count,
+                note.reading_minutes()
+            );
+        }
+    }
+
+    /// Unread marker, short id, age, text on one line and tag chips,
+    /// fitted to the terminal width
+    fn row(&self, note: &Note, now: Timestamp) -> String {
+        let marker = if note.is_read { ' ' } else { '•' };
+        let id = &note.id.to_hex()[..SHORT_ID_CHARS];
+        let age = relative_time(note.created_at, now);
+        let status = note.task_status.map(|s| format!(" [{s}]"));
+        let tags = note.tags.iter().map(|tag| format!(" [#{tag}]"));
+        let chips: String = status.into_iter().chain(tags).collect();
+
+        let used = 2 + SHORT_ID_CHARS + 2 + 8 + 2 + grapheme_len(&chips);
+        let text_width = self.width.saturating_sub(used).max(MIN_TEXT_WIDTH);
+        let text = if note.is_hydrated {
+            note.text.split_whitespace().collect::<Vec<_>>().join(" ")
+        } else {
+            "(not downloaded)".to_string()
+        };
+        let text = truncate_graphemes(&text, text_width - 1);
+        let padding = text_width.saturating_sub(grapheme_len(&text));
+        let row = format!("{marker} {id}  {age:>8}  {text}{:padding$}{chips}", "");
+        row.trim_end().to_string()
+    }
+}
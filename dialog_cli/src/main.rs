@@ -1,7 +1,22 @@
-use clap::{Parser, Subcommand};
-use dialog_lib::Dialog;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use dialog_lib::query::SEARCH_SCAN_LIMIT;
+use dialog_lib::{
+    CancellationToken, CaptureSource, DEFAULT_MAX_RELAY_PAYMENT_SATS, DEFAULT_NOTE_KIND,
+    DEFAULT_RELAY_PAYMENT_BUDGET_SATS, Dialog, DialogError, DigestFormat, DuplicatePolicy,
+    FrameDirection, GroupShare, ImportFormat, LintWarning, NoteIdFormat, PublishOptions,
+    RelayPolicy, RuleMatch, SyncMode, TagDefaults, TagRule, TaskStatus, WatchItem, WatchSpec,
+    add_hashtags, format_note_id, normalize_tag, parse_note_id, strip_hashtags, truncate_graphemes,
+};
 use nostr_sdk::prelude::*;
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
 use thiserror::Error;
+use tokio::sync::broadcast::error::RecvError;
+
+mod format;
+
+use format::NotePrinter;
 
 #[derive(Error, Debug)]
 enum CliError {
@@ -11,12 +26,29 @@ enum CliError {
     Bech32(#[from] nostr_sdk::nips::nip19::Error),
     #[error("Key parse error: {0}")]
     Keys(#[from] nostr_sdk::key::Error),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
     #[error("Missing environment variable: {0}")]
     MissingEnv(String),
+    #[error("Invalid date: {0} (expected YYYY-MM-DD or YYYY-MM-DDTHH:MM)")]
+    InvalidDate(String),
+    #[error("Invalid duration: {0} (expected e.g. 30m, 12h, 7d or 2w)")]
+    InvalidDuration(String),
+    #[error("Invalid hours: {0} (expected e.g. 9-17)")]
+    InvalidHours(String),
+    #[error("Invalid relay URL: {0}")]
+    InvalidRelay(String),
+    #[error("Editor failed: {0}")]
+    Editor(String),
+    #[error("Notification failed: {0}")]
+    Notify(String),
 }
 
 type Result<T> = std::result::Result<T, CliError>;
 
+/// Characters of a note shown in a desktop notification
+const NOTIFICATION_CHARS: usize = 200;
+
 #[derive(Parser)]
 #[command(name = "dialog")]
 #[command(about = "A privacy-first note-taking system on Nostr", long_about = None)]
@@ -33,10 +65,72 @@ struct Cli {
     #[arg(short, long)]
     data_dir: Option<String>,
 
+    /// Nostr Wallet Connect URI used to pay relays that ask for payment
+    #[arg(long, env = "DIALOG_NWC", hide_env_values = true)]
+    nwc: Option<String>,
+
+    /// Largest relay invoice paid automatically, in sats
+    #[arg(long, default_value_t = DEFAULT_MAX_RELAY_PAYMENT_SATS)]
+    max_relay_payment: u64,
+
+    /// Relay whose invoices may be paid automatically (repeatable); other
+    /// relays asking for payment are never paid
+    #[arg(long)]
+    pay_relay: Vec<String>,
+
+    /// Total paid to relays automatically, in sats, across runs
+    #[arg(long, default_value_t = DEFAULT_RELAY_PAYMENT_BUDGET_SATS)]
+    relay_payment_budget: u64,
+
+    /// Event kind new notes are written with; notes under the default and
+    /// legacy (1059) kinds are read either way
+    #[arg(long, env = "DIALOG_NOTE_KIND", default_value_t = DEFAULT_NOTE_KIND)]
+    note_kind: u16,
+
+    /// Publish new notes without public hashtags or p-tag and with a
+    /// fuzzed timestamp. Kept with the profile, so later runs don't need
+    /// it; `--max-privacy false` turns it off again
+    #[arg(long, env = "DIALOG_MAX_PRIVACY", num_args = 0..=1, default_missing_value = "true")]
+    max_privacy: Option<bool>,
+
+    /// Name other devices see for this one in `devices`
+    #[arg(long, env = "DIALOG_DEVICE_NAME", default_value = "dialog_cli")]
+    device_name: String,
+
+    /// Events each relay may hold for this key, for relays that cap them;
+    /// `status` warns within 10% of it
+    #[arg(long, env = "DIALOG_EVENT_BUDGET")]
+    event_budget: Option<usize>,
+
+    /// How note ids are printed; any of these forms is accepted as input
+    #[arg(long, env = "DIALOG_ID_FORMAT", value_enum, default_value_t = IdFormatArg::Note)]
+    id_format: IdFormatArg,
+
     #[command(subcommand)]
     command: Option<Commands>,
 }
 
+/// Flags shared by `create` and `compose`
+#[derive(Args)]
+struct CreateOptions {
+    /// Trim trailing whitespace and extra blank lines, normalize Unicode
+    #[arg(long)]
+    normalize: bool,
+
+    /// Create the note even if it looks like it contains a private key
+    #[arg(long)]
+    allow_secrets: bool,
+
+    /// Show what would be published (kind, tags relays can see, sizes and
+    /// relays) without sending or storing anything
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Print each relay's answer after sending
+    #[arg(short, long, conflicts_with = "dry_run")]
+    verbose: bool,
+}
+
 #[derive(Subcommand)]
 enum Commands {
     /// Create a new note
@@ -44,6 +138,24 @@ enum Commands {
     Create {
         /// Note text (hashtags will be parsed automatically)
         text: String,
+
+        #[command(flatten)]
+        options: CreateOptions,
+    },
+
+    /// Write a note in $VISUAL or $EDITOR; it is created when you save
+    /// and quit
+    Compose {
+        /// Tag to start the note with (repeatable)
+        #[arg(long)]
+        tag: Vec<String>,
+
+        /// File whose text the note starts from
+        #[arg(long)]
+        template: Option<PathBuf>,
+
+        #[command(flatten)]
+        options: CreateOptions,
     },
 
     /// List notes
@@ -59,10 +171,650 @@ enum Commands {
         /// Watch for new notes in real-time
         #[arg(long)]
         watch: bool,
+
+        /// Show the list as it was at this date (YYYY-MM-DD or
+        /// YYYY-MM-DDTHH:MM), before later notes and edits
+        #[arg(long, conflicts_with = "watch")]
+        as_of: Option<String>,
+
+        /// Only notes with at least this many words, e.g. 300 for long-form
+        #[arg(long, conflicts_with_all = ["watch", "as_of"])]
+        min_words: Option<usize>,
+
+        /// Full text, date and id instead of one row per note
+        #[arg(long)]
+        long: bool,
+    },
+
+    /// Print new notes with a tag as they arrive, until Ctrl+C
+    #[command(arg_required_else_help = true)]
+    Follow {
+        tag: String,
+
+        /// Also show a desktop notification (notify-send, or osascript on
+        /// macOS)
+        #[arg(long)]
+        notify: bool,
+
+        /// Full text, date and id instead of one row per note
+        #[arg(long)]
+        long: bool,
+    },
+
+    /// Search note text and transcripts
+    #[command(arg_required_else_help = true)]
+    Search {
+        query: String,
+
+        /// Maximum number of notes to display
+        #[arg(short, long, default_value = "10")]
+        limit: usize,
+
+        /// Full text, date and id instead of one row per note
+        #[arg(long)]
+        long: bool,
+    },
+
+    /// Add or remove tags on many notes at once. Changed notes are
+    /// replaced by copies with new ids.
+    #[command(arg_required_else_help = true)]
+    Bulk {
+        /// Note ids (note1... or hex)
+        #[arg(required = true)]
+        ids: Vec<String>,
+
+        /// Tag to add (repeatable)
+        #[arg(long, required_unless_present = "untag")]
+        tag: Vec<String>,
+
+        /// Tag to remove (repeatable)
+        #[arg(long)]
+        untag: Vec<String>,
+    },
+
+    /// Publish a note publicly as a kind-1 post
+    #[command(arg_required_else_help = true)]
+    Publish {
+        /// Note id (note1... or hex)
+        id: String,
+
+        /// Remove hashtags from the public post
+        #[arg(long)]
+        strip_tags: bool,
+
+        /// Skip the confirmation prompt
+        #[arg(short, long)]
+        yes: bool,
+    },
+
+    /// Import files as notes, keeping their original dates
+    #[command(arg_required_else_help = true)]
+    Import {
+        /// Format of the files to import
+        #[arg(short, long, value_enum)]
+        format: ImportFormatArg,
+
+        /// What to do with notes that look like ones already stored
+        #[arg(long, value_enum, default_value = "skip")]
+        on_duplicate: DuplicatePolicyArg,
+
+        /// Files or directories to import
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+    },
+
+    /// Set or clear when a note is due
+    #[command(arg_required_else_help = true)]
+    Due {
+        /// Note id (note1... or hex)
+        id: String,
+
+        /// Due date as YYYY-MM-DD or YYYY-MM-DDTHH:MM (local time)
+        #[arg(required_unless_present = "clear")]
+        when: Option<String>,
+
+        /// Remove the due date
+        #[arg(long, conflicts_with = "when")]
+        clear: bool,
+    },
+
+    /// Make a note a task, move it along, or make it a plain note again
+    #[command(arg_required_else_help = true)]
+    Task {
+        /// Note id (note1... or hex)
+        id: String,
+
+        #[arg(value_enum, required_unless_present = "clear")]
+        status: Option<TaskStatusArg>,
+
+        /// Make it a plain note again
+        #[arg(long, conflicts_with = "status")]
+        clear: bool,
+    },
+
+    /// List notes that are tasks, newest first
+    Tasks {
+        /// Only tasks with this status
+        #[arg(short, long, value_enum)]
+        status: Option<TaskStatusArg>,
+
+        #[arg(short, long, default_value = "20")]
+        limit: usize,
+
+        /// Full text, date and id instead of one row per note
+        #[arg(long)]
+        long: bool,
+    },
+
+    /// Export notes, e.g. due notes as a calendar feed
+    #[command(arg_required_else_help = true)]
+    Export {
+        /// Output format
+        #[arg(short, long, value_enum)]
+        format: ExportFormatArg,
+
+        /// Only notes due on or after this date (YYYY-MM-DD)
+        #[arg(long)]
+        from: Option<String>,
+
+        /// Only notes due before this date (YYYY-MM-DD)
+        #[arg(long)]
+        to: Option<String>,
+
+        /// File to write (default: stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Print a digest of recent notes, grouped by day and tag
+    Digest {
+        /// Cover the last 7 days
+        #[arg(long, conflicts_with = "days")]
+        week: bool,
+
+        /// Cover the last N days (default: 1)
+        #[arg(long)]
+        days: Option<u64>,
+
+        /// Only notes with this tag (repeatable)
+        #[arg(short, long)]
+        tag: Vec<String>,
+
+        /// Print HTML instead of markdown
+        #[arg(long, conflicts_with = "email_stdout")]
+        html: bool,
+
+        /// Print a complete email (text and HTML) for `sendmail -t`
+        #[arg(long)]
+        email_stdout: bool,
+
+        /// Recipient for --email-stdout
+        #[arg(long, requires = "email_stdout")]
+        to: Option<String>,
+    },
+
+    /// Show the log of local changes (creates, flag changes, syncs)
+    Audit {
+        /// Only entries from this date (YYYY-MM-DD or YYYY-MM-DDTHH:MM)
+        #[arg(long)]
+        since: Option<String>,
+
+        /// Only entries before this date
+        #[arg(long)]
+        until: Option<String>,
+    },
+
+    /// Keep a directory of decrypted markdown files in step with your notes
+    #[command(arg_required_else_help = true)]
+    Mirror {
+        /// Directory to write notes into (they are stored unencrypted)
+        #[arg(long)]
+        dir: PathBuf,
+
+        /// Write the files once and exit instead of watching for changes
+        #[arg(long)]
+        once: bool,
+    },
+
+    /// Republish notes stored under older kinds under --note-kind
+    MigrateKind,
+
+    /// Pay a lightning invoice, e.g. a relay's admission fee, with the
+    /// wallet from --nwc
+    #[command(arg_required_else_help = true)]
+    Pay {
+        /// BOLT11 invoice (lnbc...)
+        invoice: String,
+    },
+
+    /// Print one note, by id or `nostr:` link; edited notes show their
+    /// latest version
+    #[command(arg_required_else_help = true)]
+    Show {
+        /// Note id or `nostr:nevent1...` link
+        id: String,
+    },
+
+    /// List notes that refer to a note
+    #[command(arg_required_else_help = true)]
+    Backlinks {
+        /// Note ID
+        id: String,
+    },
+
+    /// Print a `nostr:` link to a note, e.g. for Things or OmniFocus
+    #[command(arg_required_else_help = true)]
+    Ref {
+        /// Note ID
+        id: String,
+    },
+
+    /// Show your public key
+    Pubkey,
+
+    /// Show or publish the name and picture others see for your key
+    Profile {
+        #[command(subcommand)]
+        command: ProfileCommands,
+    },
+
+    /// Show notes you haven't opened in a long time
+    Review {
+        #[arg(short, long, default_value = "5")]
+        limit: usize,
+
+        /// Don't count the notes shown as viewed
+        #[arg(long)]
+        peek: bool,
+    },
+
+    /// Show relay connections, database size and sync state
+    Status {
+        /// Sync first, so the report includes a sync result
+        #[arg(long)]
+        sync: bool,
+    },
+
+    /// Ask relays to delete notes replaced by edits, and events deleted
+    /// earlier that they still hold, to stay under an event cap
+    Compact,
+
+    /// Drop the oldest note bodies until stored notes fit in MAX_BYTES.
+    /// They stay listed and relays keep them; pinned notes are kept.
+    #[command(arg_required_else_help = true)]
+    Evict {
+        /// Size the stored notes may take up, in bytes
+        max_bytes: u64,
+    },
+
+    /// Rebuild the search index and local caches from the stored events,
+    /// for when lists or flags look wrong. Notes are never deleted.
+    Recover {
+        /// Fetch our events from the relays first
+        #[arg(long)]
+        from_relays: bool,
+    },
+
+    /// Inspect the relays in use
+    Relay {
+        #[command(subcommand)]
+        command: RelayCommands,
+    },
+
+    /// Protocol diagnostics
+    Debug {
+        #[command(subcommand)]
+        command: DebugCommands,
+    },
+
+    /// List and rename tags
+    Tags {
+        #[command(subcommand)]
+        command: TagsCommands,
+    },
+
+    /// Manage rules that tag new notes automatically
+    Rules {
+        #[command(subcommand)]
+        command: RulesCommands,
+    },
+
+    /// Devices writing with this key, and hiding notes from old ones
+    Devices {
+        #[command(subcommand)]
+        command: DevicesCommands,
+    },
+
+    /// Move relays and tag metadata to another device
+    Settings {
+        #[command(subcommand)]
+        command: SettingsCommands,
+    },
+
+    /// Share a note through an expiring, encrypted link or privately
+    /// with other people
+    Share {
+        #[command(subcommand)]
+        command: ShareCommands,
+    },
+
+    /// Serve read-only note search and listing on localhost, e.g. for a
+    /// local AI assistant or MCP server
+    ServeApi {
+        /// Port to listen on (127.0.0.1 only)
+        #[arg(short, long, default_value = "7756")]
+        port: u16,
+
+        /// Token clients must send as `Authorization: Bearer <token>`
+        #[arg(long, env = "DIALOG_API_TOKEN", hide_env_values = true)]
+        token: String,
+    },
+
+    /// Web clipper for browser extensions
+    Clipper {
+        #[command(subcommand)]
+        command: ClipperCommands,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ImportFormatArg {
+    /// Email messages (.eml), tagged #email
+    Eml,
+    /// Google Keep from a Takeout .zip (labels become tags)
+    Keep,
+    /// Simplenote export .zip or its notes.json
+    Simplenote,
+}
+
+impl From<ImportFormatArg> for ImportFormat {
+    fn from(format: ImportFormatArg) -> Self {
+        match format {
+            ImportFormatArg::Eml => ImportFormat::Eml,
+            ImportFormatArg::Keep => ImportFormat::Keep,
+            ImportFormatArg::Simplenote => ImportFormat::Simplenote,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum DuplicatePolicyArg {
+    /// Leave them out
+    Skip,
+    /// Add their tags and pin/archive state to the stored note
+    Merge,
+    /// Import them anyway
+    Duplicate,
+}
+
+impl From<DuplicatePolicyArg> for DuplicatePolicy {
+    fn from(policy: DuplicatePolicyArg) -> Self {
+        match policy {
+            DuplicatePolicyArg::Skip => DuplicatePolicy::Skip,
+            DuplicatePolicyArg::Merge => DuplicatePolicy::Merge,
+            DuplicatePolicyArg::Duplicate => DuplicatePolicy::Duplicate,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum TaskStatusArg {
+    Todo,
+    Doing,
+    Done,
+}
+
+impl From<TaskStatusArg> for TaskStatus {
+    fn from(status: TaskStatusArg) -> Self {
+        match status {
+            TaskStatusArg::Todo => TaskStatus::Todo,
+            TaskStatusArg::Doing => TaskStatus::Doing,
+            TaskStatusArg::Done => TaskStatus::Done,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum IdFormatArg {
+    /// 64 hex characters
+    Hex,
+    /// note1...
+    Note,
+    /// nevent1...
+    Nevent,
+}
+
+impl From<IdFormatArg> for NoteIdFormat {
+    fn from(format: IdFormatArg) -> Self {
+        match format {
+            IdFormatArg::Hex => NoteIdFormat::Hex,
+            IdFormatArg::Note => NoteIdFormat::Note,
+            IdFormatArg::Nevent => NoteIdFormat::Nevent,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ExportFormatArg {
+    /// iCalendar feed of notes with due dates
+    Ics,
+}
+
+#[derive(Subcommand)]
+enum RelayCommands {
+    /// List relays with what their NIP-11 documents say they support
+    List,
+    /// Show or set which event kinds are published to a relay; without a
+    /// URL, list every relay's policy
+    Policy {
+        /// Relay URL
+        url: Option<String>,
+
+        /// Kinds the relay is sent, as numbers or names (notes, app-data,
+        /// messages, profile, contacts, relays, public, deletions)
+        #[arg(
+            long,
+            value_delimiter = ',',
+            requires = "url",
+            conflicts_with = "clear"
+        )]
+        kinds: Vec<String>,
+
+        /// Send the relay every kind again
+        #[arg(long, requires = "url")]
+        clear: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum DebugCommands {
+    /// Print relay messages as they are sent and received while watching
+    /// for notes. Event content and signatures are redacted.
+    Tail {
+        /// Only show messages to and from this relay
+        #[arg(long)]
+        relay: Option<String>,
+
+        /// Sync once at the start, to see that traffic too
+        #[arg(long)]
+        sync: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum TagsCommands {
+    /// Tags with their note counts, under their current names
+    List,
+    /// Rename a tag. Notes keep their hashtags; the old name is an alias
+    /// for the new one from now on.
+    #[command(arg_required_else_help = true)]
+    Rename { from: String, to: String },
+    /// Show or set what new notes with a tag get, e.g. an expiry
+    #[command(arg_required_else_help = true)]
+    Config {
+        tag: String,
+
+        /// New notes expire after this many days
+        #[arg(long)]
+        expire_after_days: Option<u32>,
+
+        /// Pin new notes
+        #[arg(long)]
+        pin: bool,
+
+        /// Only pin notes written between these hours, e.g. 9-17
+        #[arg(long, requires = "pin")]
+        pin_hours: Option<String>,
+
+        /// Remove the tag's defaults
+        #[arg(long, conflicts_with_all = ["expire_after_days", "pin"])]
+        clear: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum RulesCommands {
+    /// List rules, numbered for `rules remove`
+    List,
+    /// Tag notes containing a keyword (or matching a regex)
+    #[command(arg_required_else_help = true)]
+    Add {
+        /// Text to look for, ignoring case
+        pattern: String,
+
+        /// Tag to add, with or without '#'
+        tag: String,
+
+        /// Treat the pattern as a regular expression
+        #[arg(long)]
+        regex: bool,
+    },
+    /// Remove a rule by its number in `rules list`
+    #[command(arg_required_else_help = true)]
+    Remove { number: usize },
+    /// Apply the rules to existing notes
+    Apply {
+        /// Only show which notes would be tagged
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum ProfileCommands {
+    /// Print your published profile
+    Show,
+    /// Publish your profile; fields not given keep their current value
+    /// and an empty value removes the field
+    Set {
+        #[arg(long)]
+        name: Option<String>,
+        #[arg(long)]
+        about: Option<String>,
+        /// Picture URL
+        #[arg(long)]
+        picture: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum DevicesCommands {
+    /// List devices with their last activity
+    List {
+        /// Sync first, to hear from the other devices
+        #[arg(long)]
+        sync: bool,
+    },
+    /// Hide the notes a device wrote
+    #[command(arg_required_else_help = true)]
+    Ignore {
+        /// Device id, as shown by `devices list`
+        id: String,
+    },
+    /// Show a device's notes again
+    #[command(arg_required_else_help = true)]
+    Unignore { id: String },
+}
+
+#[derive(Subcommand)]
+enum SettingsCommands {
+    /// Print settings as a blob only this key can import
+    Export {
+        /// File to write (default: stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Restore settings from `settings export`
+    #[command(arg_required_else_help = true)]
+    Import {
+        /// File written by `settings export`
+        path: PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+enum ShareCommands {
+    /// Publish an encrypted copy of a note and print a link to it
+    #[command(arg_required_else_help = true)]
+    Create {
+        /// Note ID to share
+        id: String,
+
+        /// How long the link works, e.g. 30m, 12h, 7d
+        #[arg(long, default_value = "1d")]
+        ttl: String,
+    },
+    /// Send a note privately (NIP-17) to one or more people
+    #[command(arg_required_else_help = true)]
+    Send {
+        /// Note ID to share
+        id: String,
+
+        /// Recipients' public keys (npub or hex)
+        #[arg(required = true)]
+        recipients: Vec<String>,
+    },
+    /// Send a note again to people it didn't reach
+    #[command(arg_required_else_help = true)]
+    Resend {
+        /// Note ID
+        id: String,
+
+        /// When it was shared, as shown by `share list`
+        shared_at: u64,
+    },
+    /// List the links made for a note and who it was sent to
+    #[command(arg_required_else_help = true)]
+    List {
+        /// Note ID
+        id: String,
+    },
+    /// Delete a shared copy before it expires
+    #[command(arg_required_else_help = true)]
+    Revoke {
+        /// ID of the shared copy, as shown by `share list`
+        id: String,
+    },
+    /// Print the note behind a share link
+    #[command(arg_required_else_help = true)]
+    Open {
+        /// Link as printed by `share create` (note1...#...)
+        url: String,
     },
+}
 
-    /// Show your public key
-    Pubkey,
+#[derive(Subcommand)]
+enum ClipperCommands {
+    /// Accept clips on localhost and save them as #clipped notes
+    Serve {
+        /// Port to listen on (127.0.0.1 only)
+        #[arg(short, long, default_value = "7755")]
+        port: u16,
+
+        /// Token the extension must send as `Authorization: Bearer <token>`
+        #[arg(long, env = "DIALOG_CLIPPER_TOKEN", hide_env_values = true)]
+        token: String,
+    },
 }
 
 fn get_nsec() -> Result<String> {
@@ -76,6 +828,224 @@ fn get_nsec() -> Result<String> {
     })
 }
 
+/// Parse a local date or date-time into a timestamp; dates mean midnight
+fn parse_local_time(input: &str) -> Result<Timestamp> {
+    use chrono::{Local, NaiveDate, NaiveDateTime, TimeZone};
+
+    let naive = NaiveDateTime::parse_from_str(input, "%Y-%m-%dT%H:%M")
+        .or_else(|_| {
+            NaiveDate::parse_from_str(input, "%Y-%m-%d").map(|d| d.and_time(Default::default()))
+        })
+        .map_err(|_| CliError::InvalidDate(input.to_string()))?;
+    let local = Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .ok_or_else(|| CliError::InvalidDate(input.to_string()))?;
+    u64::try_from(local.timestamp())
+        .map(Timestamp::from)
+        .map_err(|_| CliError::InvalidDate(input.to_string()))
+}
+
+/// Local hours such as `9-17`, start included and end not
+fn parse_hours(input: &str) -> Result<(u8, u8)> {
+    let invalid = || CliError::InvalidHours(input.to_string());
+    let (start, end) = input.trim().split_once('-').ok_or_else(invalid)?;
+    let start = start.trim().parse().map_err(|_| invalid())?;
+    let end = end.trim().parse().map_err(|_| invalid())?;
+    Ok((start, end))
+}
+
+/// Durations such as `90s`, `30m`, `12h`, `7d` or `2w`
+fn parse_duration(input: &str) -> Result<std::time::Duration> {
+    let input = input.trim();
+    let split = input
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(input.len());
+    let (amount, unit) = input.split_at(split);
+    let amount: u64 = amount
+        .parse()
+        .map_err(|_| CliError::InvalidDuration(input.to_string()))?;
+    let unit_secs = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3_600,
+        "d" => 86_400,
+        "w" => 604_800,
+        _ => return Err(CliError::InvalidDuration(input.to_string())),
+    };
+    Ok(std::time::Duration::from_secs(amount * unit_secs))
+}
+
+/// Open $VISUAL or $EDITOR (vi if neither is set) on a scratch file
+/// holding `initial`, and return what was saved
+fn edit_in_editor(initial: &str) -> Result<String> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    // The editor may come with arguments, e.g. "code --wait"
+    let mut words = editor.split_whitespace();
+    let program = words
+        .next()
+        .ok_or_else(|| CliError::Editor("$EDITOR is empty".to_string()))?;
+
+    let path = std::env::temp_dir().join(format!("dialog-note-{}.md", std::process::id()));
+    // Only readable by us; the draft is removed once the editor exits
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(&path)?.write_all(initial.as_bytes())?;
+    let status = std::process::Command::new(program)
+        .args(words)
+        .arg(&path)
+        .status();
+    let text = std::fs::read_to_string(&path);
+    let _ = std::fs::remove_file(&path);
+
+    let status = status.map_err(|e| CliError::Editor(format!("{program}: {e}")))?;
+    if !status.success() {
+        return Err(CliError::Editor(format!("{program} exited with {status}")));
+    }
+    Ok(text?)
+}
+
+/// Show a desktop notification with the start of `text`
+fn notify_desktop(title: &str, text: &str) -> Result<()> {
+    let body = truncate_graphemes(text.trim(), NOTIFICATION_CHARS);
+    let status = if cfg!(target_os = "macos") {
+        let quote = |s: &str| format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""));
+        let script = format!(
+            "display notification {} with title {}",
+            quote(&body),
+            quote(title)
+        );
+        std::process::Command::new("osascript")
+            .args(["-e", &script])
+            .status()
+    } else {
+        std::process::Command::new("notify-send")
+            .args([title, &body])
+            .status()
+    };
+    match status {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(CliError::Notify(format!("exited with {status}"))),
+        Err(e) => Err(CliError::Notify(e.to_string())),
+    }
+}
+
+fn note_too_large(max: usize, actual: usize) -> ! {
+    eprintln!("Note is too large: {actual} bytes (max {max} bytes).");
+    eprintln!("Split it into several smaller notes.");
+    std::process::exit(1);
+}
+
+fn print_profile(profile: &Metadata) {
+    println!("  Name: {}", profile.name.as_deref().unwrap_or("-"));
+    if let Some(about) = &profile.about {
+        println!("  About: {about}");
+    }
+    if let Some(picture) = &profile.picture {
+        println!("  Picture: {picture}");
+    }
+}
+
+fn format_kinds(policy: &RelayPolicy) -> String {
+    let kinds: Vec<String> = policy.kinds.iter().map(u16::to_string).collect();
+    kinds.join(", ")
+}
+
+fn print_group_share(share: &GroupShare) {
+    let delivered = share.recipients.iter().filter(|r| r.is_delivered()).count();
+    println!(
+        "Sent {} ({}): delivered to {delivered}/{}",
+        share.shared_at.to_human_datetime(),
+        share.shared_at,
+        share.recipients.len()
+    );
+    for recipient in &share.recipients {
+        let pubkey = recipient
+            .pubkey
+            .to_bech32()
+            .unwrap_or_else(|_| recipient.pubkey.to_hex());
+        let status = if recipient.is_delivered() {
+            format!("delivered ({} relays)", recipient.acked_by.len())
+        } else if let Some(error) = &recipient.error {
+            format!("failed: {error}")
+        } else {
+            "not sent".to_string()
+        };
+        println!("  {pubkey} {status}");
+    }
+}
+
+async fn create_note(
+    dialog: &Dialog,
+    text: &str,
+    options: &CreateOptions,
+    id_format: NoteIdFormat,
+) -> Result<()> {
+    let secrets = dialog.lint_note(text).await;
+    if !secrets.is_empty() && !options.allow_secrets {
+        for secret in &secrets {
+            let LintWarning::PotentialSecretDetected { masked, .. } = secret;
+            eprintln!("This looks like a private key: {masked}");
+        }
+        eprintln!("Not created. Remove it, or pass --allow-secrets to keep it.");
+        std::process::exit(1);
+    }
+    dialog.set_normalize_notes(options.normalize);
+    let (text, warnings) = dialog.link_note_refs(text).await;
+    for warning in &warnings {
+        eprintln!("Warning: {warning}; left as written");
+    }
+    if options.dry_run {
+        let plan = match dialog.plan_note(&text).await {
+            Err(DialogError::NoteTooLarge { max, actual }) => note_too_large(max, actual),
+            result => result?,
+        };
+        println!("Dry run, nothing sent or stored. Relays would see:");
+        println!("  Kind: {}", plan.kind.as_u16());
+        println!("  Date: {}", plan.created_at.to_human_datetime());
+        println!(
+            "  Content: {} bytes, encrypted ({} bytes signed)",
+            plan.content_bytes, plan.event_bytes
+        );
+        println!("  Tags:");
+        for tag in &plan.tags {
+            println!("    {}", tag.join(" "));
+        }
+        if plan.relays.is_empty() {
+            println!("  Relays: none connected");
+        } else {
+            println!("  Relays:");
+            for url in &plan.relays {
+                println!("    {url}");
+            }
+        }
+        return Ok(());
+    }
+
+    let (note, report) = match dialog.create_note_reported(&text).await {
+        Err(DialogError::NoteTooLarge { max, actual }) => note_too_large(max, actual),
+        result => result?,
+    };
+    println!("Created note: {}", format_note_id(&note.id, id_format));
+    if options.verbose {
+        for url in &report.accepted {
+            println!("  OK    {url}");
+        }
+        for (url, reason) in &report.rejected {
+            println!("  ERROR {url}: {reason}");
+        }
+    }
+
+    if !note.tags.is_empty() {
+        println!("Tags: {}", note.tags.join(", "));
+    }
+    Ok(())
+}
+
 fn get_relay_url(cli_override: Option<String>) -> String {
     cli_override
         .or_else(|| std::env::var("DIALOG_RELAY").ok())
@@ -99,6 +1069,11 @@ async fn main() -> Result<()> {
         println!("Config:");
         println!("  Pubkey: {}", pubkey.to_bech32()?);
         println!("  Relay:  {relay_url}");
+        println!("  Note kind: {}", cli.note_kind);
+        match cli.max_privacy {
+            Some(enabled) => println!("  Max privacy: {enabled}"),
+            None => println!("  Max privacy: <profile setting>"),
+        }
         match data_dir_env {
             Some(ref dir) => println!("  DataDir: {dir}"),
             None => println!("  DataDir: <OS default>"),
@@ -115,6 +1090,21 @@ async fn main() -> Result<()> {
 
     // Create dialog instance
     let dialog = Dialog::new(&nsec).await?;
+    dialog.set_max_relay_payment(cli.max_relay_payment);
+    let pay_relays: Vec<&str> = cli.pay_relay.iter().map(String::as_str).collect();
+    dialog.set_payable_relays(&pay_relays)?;
+    dialog.set_relay_payment_budget(cli.relay_payment_budget)?;
+    dialog.set_note_kind(cli.note_kind)?;
+    if let Some(enabled) = cli.max_privacy {
+        dialog.set_max_privacy(enabled)?;
+    }
+    dialog.set_device_name(Some(cli.device_name.clone()));
+    dialog.set_app_version(Some(format!("dialog-cli {}", env!("CARGO_PKG_VERSION"))));
+    dialog.set_capture_source(Some(CaptureSource::Cli));
+    dialog.set_event_budget(cli.event_budget);
+    if let Some(uri) = &cli.nwc {
+        dialog.configure_nwc(uri)?;
+    }
 
     // Connect to relay
     eprintln!("Using relay: {relay_url}");
@@ -123,29 +1113,55 @@ async fn main() -> Result<()> {
         eprintln!("Running in offline mode.");
     }
 
+    let id_format = NoteIdFormat::from(cli.id_format);
+    let show_id = |id: &EventId| format_note_id(id, id_format);
+
     // Handle commands
     let Some(command) = cli.command else {
         eprintln!("No command provided. Try --help for usage.");
         return Ok(());
     };
     match command {
-        Commands::Create { text } => {
-            let id = dialog.create_note(&text).await?;
-            println!("Created note: {}", id.to_bech32()?);
-
-            // Parse and display tags
-            let tags: Vec<_> = text
-                .split_whitespace()
-                .filter(|w| w.starts_with('#') && w.len() > 1)
-                .map(|t| &t[1..])
-                .collect();
+        Commands::Create { text, options } => {
+            create_note(&dialog, &text, &options, id_format).await?;
+        }
 
+        Commands::Compose {
+            tag,
+            template,
+            options,
+        } => {
+            let mut initial = match &template {
+                Some(path) => std::fs::read_to_string(path)?,
+                None => String::new(),
+            };
+            let tags: Vec<String> = tag.iter().filter_map(|t| normalize_tag(t)).collect();
             if !tags.is_empty() {
-                println!("Tags: {}", tags.join(", "));
+                // Tags go below a blank line for the text
+                if initial.trim().is_empty() {
+                    initial = format!("\n\n{}", add_hashtags("", &tags));
+                } else {
+                    initial = add_hashtags(&initial, &tags);
+                }
+                initial.push('\n');
             }
+            let text = edit_in_editor(&initial)?;
+            if strip_hashtags(&text).trim().is_empty() {
+                eprintln!("Empty note, nothing created.");
+                return Ok(());
+            }
+            create_note(&dialog, &text, &options, id_format).await?;
         }
 
-        Commands::List { limit, tag, watch } => {
+        Commands::List {
+            limit,
+            tag,
+            watch,
+            as_of,
+            min_words,
+            long,
+        } => {
+            let printer = NotePrinter::new(long, id_format);
             if watch {
                 // Watch mode - show existing notes first, then subscribe to new ones
                 println!("Entering watch mode. Press Ctrl+C to exit.\n");
@@ -162,33 +1178,43 @@ async fn main() -> Result<()> {
                 if existing_notes.is_empty() {
                     println!("No existing notes found.");
                 } else {
-                    for note in &existing_notes {
-                        println!("\n[{}]", note.created_at.to_human_datetime());
-                        println!("{}", note.text);
-                        if !note.tags.is_empty() {
-                            println!("Tags: #{}", note.tags.join(" #"));
-                        }
-                    }
+                    printer.print_all(&existing_notes);
                     println!("\n---");
                 }
 
                 // Now watch for notes using subscribe - runs forever
                 println!("\nWatching for new notes...");
-                let mut receiver = dialog.watch_notes().await?;
+                let spec = match tag {
+                    Some(tag) => WatchSpec::Tag(tag),
+                    None => WatchSpec::Notes,
+                };
+                let mut watch = dialog.watch("list", spec).await?;
 
                 // Handle incoming notes
-                while let Some(note) = receiver.recv().await {
-                    println!("\n🆕 [{}]", note.created_at.to_human_datetime());
-                    println!("{}", note.text);
-                    if !note.tags.is_empty() {
-                        println!("Tags: #{}", note.tags.join(" #"));
-                    }
+                while let Some(WatchItem::Note(note)) = watch.recv().await {
+                    printer.print(&note);
                 }
             } else {
                 // Regular list mode
-                let notes = if let Some(tag) = tag {
+                let notes = if let Some(as_of) = as_of {
+                    let at = parse_local_time(&as_of)?;
+                    println!("Notes as of {}", at.to_human_datetime());
+                    let mut notes = dialog.notes_as_of(at, tag.as_deref()).await?;
+                    notes.truncate(limit);
+                    notes
+                } else if let Some(tag) = tag {
                     println!("Listing notes with tag: #{tag}");
-                    dialog.list_by_tag(&tag, limit).await?
+                    match min_words {
+                        Some(min_words) => {
+                            let mut notes = dialog.list_by_tag(&tag, SEARCH_SCAN_LIMIT).await?;
+                            notes.retain(|note| note.word_count >= min_words);
+                            notes.truncate(limit);
+                            notes
+                        }
+                        None => dialog.list_by_tag(&tag, limit).await?,
+                    }
+                } else if let Some(min_words) = min_words {
+                    dialog.list_notes_by_length(min_words, None, limit).await?
                 } else {
                     dialog.list_notes(limit).await?
                 };
@@ -196,21 +1222,888 @@ async fn main() -> Result<()> {
                 if notes.is_empty() {
                     println!("No notes found.");
                 } else {
-                    for note in &notes {
-                        println!("\n[{}]", note.created_at.to_human_datetime());
-                        println!("{}", note.text);
-                        if !note.tags.is_empty() {
-                            println!("Tags: #{}", note.tags.join(" #"));
-                        }
-                    }
+                    printer.print_all(&notes);
                     println!("\nTotal: {} note(s)", notes.len());
                 }
             }
         }
 
+        Commands::Follow { tag, notify, long } => {
+            let Some(tag) = normalize_tag(&tag) else {
+                eprintln!("#{tag} isn't a tag name.");
+                std::process::exit(1);
+            };
+            let printer = NotePrinter::new(long, id_format);
+            // Named per tag so follows of other tags, and list --watch,
+            // keep their own subscriptions
+            let mut watch = dialog
+                .watch(&format!("follow:{tag}"), WatchSpec::Tag(tag.clone()))
+                .await?;
+            println!("Following #{tag}. Press Ctrl+C to exit.\n");
+            let mut notify = notify;
+            while let Some(item) = watch.recv().await {
+                let WatchItem::Note(note) = item else {
+                    continue;
+                };
+                printer.print(&note);
+                if notify && let Err(e) = notify_desktop(&format!("#{tag}"), &note.text) {
+                    eprintln!("Desktop notifications off: {e}");
+                    notify = false;
+                }
+            }
+        }
+
+        Commands::Search { query, limit, long } => {
+            let notes = dialog.search_notes(&query, limit).await?;
+            if notes.is_empty() {
+                println!("No notes match \"{query}\".");
+            } else {
+                NotePrinter::new(long, id_format).print_all(&notes);
+                println!("\nFound: {} note(s)", notes.len());
+            }
+        }
+
+        Commands::Bulk { ids, tag, untag } => {
+            let mut ids = ids
+                .iter()
+                .map(|id| parse_note_id(id))
+                .collect::<std::result::Result<Vec<_>, _>>()?;
+            for (tags, adding) in [(tag, true), (untag, false)] {
+                if tags.is_empty() {
+                    continue;
+                }
+                let report = if adding {
+                    dialog.add_tags(&ids, &tags).await
+                } else {
+                    dialog.remove_tags(&ids, &tags).await
+                };
+                for (old_id, note) in &report.updated {
+                    println!("{} -> {}", show_id(old_id), show_id(&note.id));
+                    // --untag works on the copies --tag made
+                    if let Some(id) = ids.iter_mut().find(|id| **id == *old_id) {
+                        *id = note.id;
+                    }
+                }
+                for (id, error) in &report.failed {
+                    eprintln!("Failed to update {}: {error}", show_id(id));
+                }
+            }
+        }
+
+        Commands::Publish {
+            id,
+            strip_tags,
+            yes,
+        } => {
+            let note_id = parse_note_id(&id)?;
+            let Some(note) = dialog.get_note(&note_id).await? else {
+                eprintln!("Note not found: {id}");
+                std::process::exit(1);
+            };
+            let text = if strip_tags {
+                dialog_lib::strip_hashtags(&note.text)
+            } else {
+                note.text.clone()
+            };
+
+            if !yes && note.public_id.is_none() {
+                println!("This will be posted PUBLICLY and cannot be undone:\n");
+                println!("{text}\n");
+                print!("Publish? [y/N] ");
+                std::io::stdout().flush()?;
+                let mut answer = String::new();
+                std::io::stdin().lock().read_line(&mut answer)?;
+                if !matches!(answer.trim(), "y" | "Y" | "yes") {
+                    println!("Cancelled.");
+                    return Ok(());
+                }
+            }
+
+            let public_id = dialog
+                .publish_public(&note_id, PublishOptions { strip_tags })
+                .await?;
+            println!("Published: {}", show_id(&public_id));
+        }
+
+        Commands::Import {
+            format,
+            on_duplicate,
+            paths,
+        } => {
+            let (mut created, mut skipped, mut merged, mut failed) = (0, 0, 0, 0);
+            let cancel = CancellationToken::new();
+            for path in paths {
+                let report = dialog
+                    .import_path_cancellable(&path, format.into(), on_duplicate.into(), &cancel)
+                    .await?;
+                created += report.created.len();
+                for duplicate in &report.skipped {
+                    eprintln!(
+                        "Skipped {}: {} as {}",
+                        duplicate.source.display(),
+                        duplicate.reason,
+                        show_id(&duplicate.existing)
+                    );
+                }
+                skipped += report.skipped.len();
+                for (duplicate, note) in &report.merged {
+                    eprintln!(
+                        "Merged {} into {}",
+                        duplicate.source.display(),
+                        show_id(&note.id)
+                    );
+                }
+                merged += report.merged.len();
+                for (file, reason) in &report.failed {
+                    eprintln!("Failed {}: {reason}", file.display());
+                }
+                failed += report.failed.len();
+            }
+            println!(
+                "Imported {created} notes ({skipped} skipped as duplicates, {merged} merged, \
+                 {failed} failed)"
+            );
+        }
+
+        Commands::Due { id, when, .. } => {
+            let note_id = parse_note_id(&id)?;
+            // `--clear` conflicts with `when`, so no date means clear it
+            let due = when.as_deref().map(parse_local_time).transpose()?;
+            dialog.set_due(&note_id, due).await?;
+            match due {
+                Some(due) => println!("Due: {}", due.to_human_datetime()),
+                None => println!("Due date cleared"),
+            }
+        }
+
+        Commands::Task { id, status, .. } => {
+            let note_id = parse_note_id(&id)?;
+            // `--clear` conflicts with `status`, so no status means clear it
+            let status = status.map(TaskStatus::from);
+            dialog.set_task_status(&note_id, status).await?;
+            match status {
+                Some(status) => println!("Task: {status}"),
+                None => println!("No longer a task"),
+            }
+        }
+
+        Commands::Tasks {
+            status,
+            limit,
+            long,
+        } => {
+            let notes = dialog.list_tasks(status.map(Into::into), limit).await?;
+            if notes.is_empty() {
+                println!("No tasks.");
+            } else {
+                NotePrinter::new(long, id_format).print_all(&notes);
+            }
+        }
+
+        Commands::Export {
+            format: ExportFormatArg::Ics,
+            from,
+            to,
+            output,
+        } => {
+            let from = from.as_deref().map(parse_local_time).transpose()?;
+            let to = to.as_deref().map(parse_local_time).transpose()?;
+            let range = (
+                from.map_or(std::ops::Bound::Unbounded, std::ops::Bound::Included),
+                to.map_or(std::ops::Bound::Unbounded, std::ops::Bound::Excluded),
+            );
+            let exported = match &output {
+                Some(path) => {
+                    dialog
+                        .export_ics(std::fs::File::create(path)?, range)
+                        .await?
+                }
+                None => dialog.export_ics(std::io::stdout().lock(), range).await?,
+            };
+            eprintln!("Exported {exported} due notes");
+        }
+
+        Commands::Digest {
+            week,
+            days,
+            tag,
+            html,
+            email_stdout,
+            to,
+        } => {
+            let days = if week { 7 } else { days.unwrap_or(1) };
+            let since = Timestamp::from(Timestamp::now().as_u64().saturating_sub(days * 86_400));
+            let tags: Vec<String> = tag
+                .iter()
+                .filter_map(|t| dialog_lib::normalize_tag(t))
+                .collect();
+            let markdown = dialog
+                .generate_digest(since.., &tags, DigestFormat::Markdown)
+                .await?;
+            if email_stdout {
+                let html = dialog
+                    .generate_digest(since.., &tags, DigestFormat::Html)
+                    .await?;
+                let subject = match days {
+                    7 => "Your week in notes".to_string(),
+                    1 => "Your day in notes".to_string(),
+                    n => format!("Your last {n} days in notes"),
+                };
+                print!(
+                    "{}",
+                    dialog_lib::digest_email(&subject, &markdown, &html, to.as_deref())
+                );
+            } else if html {
+                print!(
+                    "{}",
+                    dialog
+                        .generate_digest(since.., &tags, DigestFormat::Html)
+                        .await?
+                );
+            } else {
+                print!("{markdown}");
+            }
+        }
+
+        Commands::Audit { since, until } => {
+            let since = since.as_deref().map(parse_local_time).transpose()?;
+            let until = until.as_deref().map(parse_local_time).transpose()?;
+            let range = (
+                since.map_or(std::ops::Bound::Unbounded, std::ops::Bound::Included),
+                until.map_or(std::ops::Bound::Unbounded, std::ops::Bound::Excluded),
+            );
+            let entries = dialog.audit_log(range)?;
+            if entries.is_empty() {
+                println!("No audit entries.");
+            }
+            for entry in entries {
+                let note = entry
+                    .note_id
+                    .map(|id| format!(" {}", show_id(&id)))
+                    .unwrap_or_default();
+                println!(
+                    "{} {:?}{note} {}",
+                    entry.at.to_human_datetime(),
+                    entry.action,
+                    entry.detail
+                );
+            }
+        }
+
+        Commands::Mirror { dir, once } => {
+            if once {
+                let report = dialog.write_mirror(&dir).await?;
+                println!(
+                    "Mirrored to {}: {} written, {} removed, {} unchanged",
+                    dir.display(),
+                    report.written,
+                    report.removed,
+                    report.unchanged
+                );
+            } else {
+                let mirror = Arc::new(dialog).enable_mirror(dir).await?;
+                println!(
+                    "Mirroring notes to {}. Press Ctrl+C to exit.",
+                    mirror.dir().display()
+                );
+                mirror.join().await;
+            }
+        }
+
+        Commands::MigrateKind => {
+            let report = dialog.migrate_note_kind().await?;
+            println!(
+                "Migrated {} notes to kind {} ({} already migrated)",
+                report.migrated,
+                dialog.note_kind(),
+                report.skipped
+            );
+            for (id, error) in &report.failed {
+                eprintln!("Failed to migrate {}: {error}", show_id(id));
+            }
+        }
+
+        Commands::Pay { invoice } => {
+            dialog.pay_invoice(&invoice).await?;
+            println!("Invoice paid");
+        }
+
+        Commands::Show { id } => {
+            let note = dialog.resolve_note_ref(&id).await?;
+            println!(
+                "[{}] {}",
+                note.created_at.to_human_datetime(),
+                show_id(&note.id)
+            );
+            println!("{}", note.text);
+            if !note.tags.is_empty() {
+                println!("Tags: #{}", note.tags.join(" #"));
+            }
+            if let Some(provenance) = dialog.note_provenance(&note.id).await? {
+                println!("From: {provenance}");
+            }
+            if note.edited {
+                let versions = dialog.edit_history(&note.id).await?.len();
+                println!("Edited ({} earlier versions)", versions.saturating_sub(1));
+            }
+        }
+
+        Commands::Backlinks { id } => {
+            let notes = dialog.backlinks(&parse_note_id(&id)?).await?;
+            if notes.is_empty() {
+                println!("No notes refer to it.");
+            }
+            for note in &notes {
+                println!(
+                    "\n[{}] {}",
+                    note.created_at.to_human_datetime(),
+                    show_id(&note.id)
+                );
+                println!("{}", note.text);
+            }
+        }
+
+        Commands::Ref { id } => {
+            println!("{}", dialog.external_ref(&parse_note_id(&id)?).await?);
+        }
+
         Commands::Pubkey => {
             println!("Your public key: {}", dialog.public_key().to_bech32()?);
         }
+
+        Commands::Profile { command } => match command {
+            ProfileCommands::Show => match dialog.get_my_profile().await? {
+                Some(profile) => print_profile(&profile),
+                None => println!("No profile published yet."),
+            },
+            ProfileCommands::Set {
+                name,
+                about,
+                picture,
+            } => {
+                let current = dialog.get_my_profile().await?.unwrap_or_default();
+                let profile = dialog
+                    .publish_profile(
+                        name.as_deref().or(current.name.as_deref()),
+                        about.as_deref().or(current.about.as_deref()),
+                        picture.as_deref().or(current.picture.as_deref()),
+                    )
+                    .await?;
+                println!("Published profile:");
+                print_profile(&profile);
+            }
+        },
+
+        Commands::Review { limit, peek } => {
+            let notes = dialog.review_queue(limit).await?;
+            if notes.is_empty() {
+                println!("Nothing to review.");
+            }
+            for note in notes {
+                println!(
+                    "{} {}",
+                    note.created_at.to_human_datetime(),
+                    show_id(&note.id)
+                );
+                println!("{}\n", note.text);
+                if !peek {
+                    dialog.record_view(&note.id).await?;
+                }
+            }
+        }
+
+        Commands::Status { sync } => {
+            if sync {
+                // The outcome is reported below
+                let _ = dialog.sync().await;
+            }
+            if dialog.event_budget().is_some() {
+                dialog.count_remote_events().await?;
+            }
+            let status = dialog.status().await?;
+            println!("Relays:");
+            if status.relays.is_empty() {
+                println!("  (none)");
+            }
+            for relay in &status.relays {
+                println!("  {} {}", relay.url, relay.status);
+            }
+            match status.db_size_bytes {
+                Some(bytes) => println!("Database: {:.1} MB", bytes as f64 / 1_000_000.0),
+                None => println!("Database: in memory"),
+            }
+            match &status.last_sync {
+                Some(last) => {
+                    let when = last.finished_at.to_human_datetime();
+                    match &last.result {
+                        Ok(report) => {
+                            println!(
+                                "Last sync: {when} ({:?}, received {}, sent {})",
+                                report.mode, report.received, report.sent
+                            );
+                            for relay in &report.relays {
+                                match &relay.error {
+                                    Some(e) => println!("  {}: failed: {e}", relay.url),
+                                    None => println!(
+                                        "  {}: {} notes, {} found nowhere else",
+                                        relay.url, relay.fetched, relay.exclusive
+                                    ),
+                                }
+                            }
+                        }
+                        Err(e) => println!("Last sync: {when} failed: {e}"),
+                    }
+                }
+                None => println!("Last sync: not run (use --sync)"),
+            }
+            if status.pending_outbox > 0 {
+                println!("Outbox: {} pending", status.pending_outbox);
+            }
+            if !status.watches.is_empty() {
+                println!("Watches: {}", status.watches.join(", "));
+            }
+            if !status.event_counts.is_empty() {
+                println!("Events stored:");
+            }
+            for count in &status.event_counts {
+                let budget = count.budget.map(|b| format!("/{b}")).unwrap_or_default();
+                let marker = if count.near_limit() {
+                    " (near limit)"
+                } else {
+                    ""
+                };
+                println!("  {} {}{budget}{marker}", count.url, count.count);
+            }
+            if status.event_counts.iter().any(|count| count.near_limit()) {
+                println!("Run `dialog compact` to free up room on relays");
+            }
+        }
+
+        Commands::Compact => {
+            let report = dialog.compact_remote().await?;
+            if report.superseded.is_empty() && report.tombstoned.is_empty() {
+                println!("Nothing to compact");
+            } else {
+                println!(
+                    "Asked {} relay(s) to delete {} replaced note(s) and {} deleted event(s)",
+                    report.relays.len(),
+                    report.superseded.len(),
+                    report.tombstoned.len()
+                );
+            }
+        }
+
+        Commands::Evict { max_bytes } => {
+            let report = dialog.set_local_cache_limit(Some(max_bytes)).await?;
+            println!(
+                "Evicted {} note(s): {} -> {} bytes",
+                report.evicted.len(),
+                report.bytes_before,
+                report.bytes_after
+            );
+            if report.not_on_relay > 0 {
+                println!(
+                    "Kept {} note(s) no relay has a copy of; sync and try again",
+                    report.not_on_relay
+                );
+            }
+        }
+
+        Commands::Recover { from_relays } => {
+            let report = dialog.rebuild_index(from_relays).await?;
+            if from_relays {
+                println!("Fetched {} missing event(s)", report.fetched);
+            }
+            println!(
+                "Rebuilt: {} note(s), {} indexed, {} with read/pin/archive flags",
+                report.notes, report.indexed, report.notes_with_state
+            );
+            if report.evictions_cleared > 0 {
+                println!(
+                    "{} evicted note(s) are stored again",
+                    report.evictions_cleared
+                );
+            }
+            if report.attachments_dropped > 0 {
+                println!(
+                    "Forgot {} cached attachment(s) whose files were missing",
+                    report.attachments_dropped
+                );
+            }
+        }
+
+        Commands::Relay {
+            command: RelayCommands::List,
+        } => {
+            let policies = dialog.relay_policies()?;
+            for url in dialog.relay_urls().await {
+                println!("{url}");
+                if let Some(policy) = policies.get(&url.to_string()) {
+                    println!("  Kinds:    {}", format_kinds(policy));
+                }
+                match dialog.relay_capabilities(url.as_str()).await {
+                    Ok(info) => {
+                        if let Some(name) = &info.name {
+                            println!("  Name:     {name}");
+                        }
+                        if let Some(software) = &info.software {
+                            let version = info.version.as_deref().unwrap_or("");
+                            println!("  Software: {software} {version}");
+                        }
+                        let nips: Vec<String> =
+                            info.supported_nips.iter().map(u16::to_string).collect();
+                        println!("  NIPs:     {}", nips.join(", "));
+                        let sync = match info.sync_mode() {
+                            Some(SyncMode::Negentropy) => "negentropy",
+                            Some(SyncMode::Plain) => "plain",
+                            None => "negentropy, falling back to plain",
+                        };
+                        println!("  Sync:     {sync}");
+                        if info.payment_required {
+                            println!("  Requires payment");
+                        }
+                    }
+                    Err(e) => println!("  No NIP-11 document: {e}"),
+                }
+            }
+        }
+
+        Commands::Relay {
+            command: RelayCommands::Policy { url, kinds, clear },
+        } => {
+            if let Some(url) = &url {
+                if clear {
+                    dialog.set_relay_policy(url, None)?;
+                } else if !kinds.is_empty() {
+                    let mut allowed = Vec::new();
+                    for name in &kinds {
+                        allowed.extend(dialog.kinds_named(name)?);
+                    }
+                    dialog.set_relay_policy(url, Some(RelayPolicy::new(allowed)))?;
+                }
+            }
+            let policies = dialog.relay_policies()?;
+            let wanted = url
+                .map(|url| {
+                    RelayUrl::parse(&url).map_err(|e| CliError::InvalidRelay(format!("{url}: {e}")))
+                })
+                .transpose()?;
+            match wanted {
+                Some(url) => match policies.get(&url.to_string()) {
+                    Some(policy) => println!("{url}: {}", format_kinds(policy)),
+                    None => println!("{url}: all kinds"),
+                },
+                None if policies.is_empty() => println!("No relay policies; relays get all kinds"),
+                None => {
+                    for (url, policy) in &policies {
+                        println!("{url}: {}", format_kinds(policy));
+                    }
+                }
+            }
+        }
+
+        Commands::Debug {
+            command: DebugCommands::Tail { relay, sync },
+        } => {
+            let relay = relay
+                .map(|url| {
+                    RelayUrl::parse(&url).map_err(|e| CliError::InvalidRelay(format!("{url}: {e}")))
+                })
+                .transpose()?;
+            let mut frames = dialog.debug_messages();
+            let _watch = dialog.watch("debug", WatchSpec::Notes).await?;
+            eprintln!("Tailing relay messages. Press Ctrl+C to exit.\n");
+
+            let sync_once = async {
+                if sync {
+                    if let Err(e) = dialog.sync().await {
+                        eprintln!("Sync failed: {e}");
+                    }
+                }
+            };
+            let tail = async {
+                loop {
+                    let frame = match frames.recv().await {
+                        Ok(frame) => frame,
+                        Err(RecvError::Lagged(missed)) => {
+                            println!("... {missed} messages missed");
+                            continue;
+                        }
+                        Err(RecvError::Closed) => break,
+                    };
+                    // Messages sent to every relay concern the filtered one too
+                    if relay.is_some() && frame.relay.is_some() && frame.relay != relay {
+                        continue;
+                    }
+                    let arrow = match frame.direction {
+                        FrameDirection::Sent => "→",
+                        FrameDirection::Received => "←",
+                    };
+                    let url = frame.relay.as_ref().map_or("*".into(), RelayUrl::to_string);
+                    let time = chrono::DateTime::from_timestamp(frame.at.as_u64() as i64, 0)
+                        .map(|at| {
+                            at.with_timezone(&chrono::Local)
+                                .format("%H:%M:%S")
+                                .to_string()
+                        })
+                        .unwrap_or_default();
+                    println!("{time} {arrow} {url} {} {}", frame.kind, frame.json);
+                }
+            };
+            tokio::join!(sync_once, tail);
+        }
+
+        Commands::Tags { command } => match command {
+            TagsCommands::List => {
+                let aliases = dialog.tag_aliases().await?;
+                for (tag, count) in dialog.tag_counts().await? {
+                    let old: Vec<String> = aliases
+                        .iter()
+                        .filter(|(_, new)| **new == tag)
+                        .map(|(old, _)| format!("#{old}"))
+                        .collect();
+                    if old.is_empty() {
+                        println!("#{tag} ({count})");
+                    } else {
+                        println!("#{tag} ({count}, was {})", old.join(", "));
+                    }
+                }
+            }
+            TagsCommands::Config {
+                tag,
+                expire_after_days,
+                pin,
+                pin_hours,
+                clear,
+            } => {
+                if clear || expire_after_days.is_some() || pin {
+                    let defaults = TagDefaults {
+                        expire_after_days,
+                        pin,
+                        pin_hours: pin_hours.as_deref().map(parse_hours).transpose()?,
+                    };
+                    dialog.set_tag_defaults(&tag, defaults).await?;
+                }
+                let tag = normalize_tag(&tag).unwrap_or(tag);
+                match dialog.tag_defaults().await?.remove(&tag) {
+                    None => println!("#{tag}: no defaults"),
+                    Some(defaults) => {
+                        println!("#{tag}:");
+                        if let Some(days) = defaults.expire_after_days {
+                            println!("  Expires after {days} days");
+                        }
+                        match defaults.pin_hours {
+                            Some((start, end)) if defaults.pin => {
+                                println!("  Pinned when written {start}:00-{end}:00")
+                            }
+                            _ if defaults.pin => println!("  Pinned"),
+                            _ => {}
+                        }
+                    }
+                }
+            }
+            TagsCommands::Rename { from, to } => {
+                dialog.rename_tag(&from, &to).await?;
+                println!(
+                    "Renamed #{} to #{}",
+                    from.trim_start_matches('#'),
+                    to.trim_start_matches('#')
+                );
+            }
+        },
+
+        Commands::Rules { command } => match command {
+            RulesCommands::List => {
+                let rules = dialog.tag_rules().await?;
+                if rules.is_empty() {
+                    println!("No rules. Add one with `rules add <pattern> <tag>`.");
+                }
+                for (i, rule) in rules.iter().enumerate() {
+                    let kind = match rule.matcher {
+                        RuleMatch::Keyword => "contains",
+                        RuleMatch::Regex => "matches",
+                    };
+                    println!("{}. {kind} \"{}\" → #{}", i + 1, rule.pattern, rule.tag);
+                }
+            }
+            RulesCommands::Add {
+                pattern,
+                tag,
+                regex,
+            } => {
+                let matcher = if regex {
+                    RuleMatch::Regex
+                } else {
+                    RuleMatch::Keyword
+                };
+                let rule = TagRule::new(&pattern, matcher, &tag)?;
+                let mut rules = dialog.tag_rules().await?;
+                println!("Added rule {}: #{}", rules.len() + 1, rule.tag);
+                rules.push(rule);
+                dialog.set_tag_rules(rules).await?;
+            }
+            RulesCommands::Remove { number } => {
+                let mut rules = dialog.tag_rules().await?;
+                if number == 0 || number > rules.len() {
+                    return Err(DialogError::Config(format!("no rule {number}")).into());
+                }
+                let rule = rules.remove(number - 1);
+                dialog.set_tag_rules(rules).await?;
+                println!("Removed \"{}\" → #{}", rule.pattern, rule.tag);
+            }
+            RulesCommands::Apply { dry_run } => {
+                let report = dialog.apply_rules_to_notes(dry_run).await?;
+                let verb = if dry_run { "Would tag" } else { "Tagged" };
+                for (id, tags) in &report.tagged {
+                    let tags: Vec<String> = tags.iter().map(|tag| format!("#{tag}")).collect();
+                    println!("{verb} {} with {}", show_id(id), tags.join(" "));
+                }
+                for (id, error) in &report.failed {
+                    eprintln!("Failed to tag {}: {error}", show_id(id));
+                }
+                println!("{verb} {} notes", report.tagged.len());
+            }
+        },
+
+        Commands::Devices { command } => match command {
+            DevicesCommands::List { sync } => {
+                if sync {
+                    if let Err(e) = dialog.sync().await {
+                        eprintln!("Sync failed: {e}");
+                    }
+                }
+                for device in dialog.devices().await? {
+                    let name = device.name.as_deref().unwrap_or("unnamed");
+                    let mut flags = Vec::new();
+                    if device.is_current {
+                        flags.push("this device");
+                    }
+                    if device.ignored {
+                        flags.push("ignored");
+                    }
+                    let flags = if flags.is_empty() {
+                        String::new()
+                    } else {
+                        format!(" ({})", flags.join(", "))
+                    };
+                    println!("{} {name}{flags}", device.id);
+                    if device.last_active.as_u64() > 0 {
+                        println!(
+                            "  Last active: {}, {} notes",
+                            device.last_active.to_human_datetime(),
+                            device.notes
+                        );
+                    }
+                }
+            }
+            DevicesCommands::Ignore { id } => {
+                dialog.set_device_ignored(&id, true).await?;
+                println!("Notes from {id} are hidden");
+            }
+            DevicesCommands::Unignore { id } => {
+                dialog.set_device_ignored(&id, false).await?;
+                println!("Notes from {id} are shown again");
+            }
+        },
+
+        Commands::Settings { command } => match command {
+            SettingsCommands::Export { output } => {
+                let blob = dialog.export_settings().await?;
+                match output {
+                    Some(path) => std::fs::write(path, blob)?,
+                    None => println!("{blob}"),
+                }
+            }
+            SettingsCommands::Import { path } => {
+                let settings = dialog
+                    .import_settings(&std::fs::read_to_string(path)?)
+                    .await?;
+                println!(
+                    "Imported {} relays, {} relay policies, {} tags, {} saved searches, {} retention rules",
+                    settings.relays.len(),
+                    settings.relay_policies.len(),
+                    settings.tags.len(),
+                    settings.saved_searches.len(),
+                    settings.retention.len()
+                );
+            }
+        },
+
+        Commands::Share { command } => match command {
+            ShareCommands::Create { id, ttl } => {
+                let note_id = parse_note_id(&id)?;
+                let link = dialog
+                    .create_share_link(&note_id, parse_duration(&ttl)?)
+                    .await?;
+                println!("{}", link.url);
+                eprintln!("Expires {}", link.expires_at.to_human_datetime());
+            }
+            ShareCommands::Send { id, recipients } => {
+                let note_id = parse_note_id(&id)?;
+                let recipients = recipients
+                    .iter()
+                    .map(|recipient| PublicKey::parse(recipient))
+                    .collect::<std::result::Result<Vec<_>, _>>()?;
+                let share = dialog.share_note_with(&note_id, recipients).await?;
+                print_group_share(&share);
+            }
+            ShareCommands::Resend { id, shared_at } => {
+                let note_id = parse_note_id(&id)?;
+                let share = dialog
+                    .resend_group_share(&note_id, Timestamp::from(shared_at))
+                    .await?;
+                print_group_share(&share);
+            }
+            ShareCommands::List { id } => {
+                let note_id = parse_note_id(&id)?;
+                let links = dialog.share_links(&note_id).await?;
+                let shares = dialog.group_shares(&note_id).await?;
+                if links.is_empty() && shares.is_empty() {
+                    println!("No shares of this note");
+                }
+                for share in &shares {
+                    print_group_share(share);
+                }
+                let now = Timestamp::now();
+                for link in links {
+                    let status = if link.revoked {
+                        "revoked".to_string()
+                    } else if link.is_expired(now) {
+                        "expired".to_string()
+                    } else {
+                        format!("expires {}", link.expires_at.to_human_datetime())
+                    };
+                    println!("{} ({status})\n  {}", show_id(&link.event_id), link.url);
+                }
+            }
+            ShareCommands::Revoke { id } => {
+                dialog.revoke_share_link(&parse_note_id(&id)?).await?;
+                println!("Share link revoked");
+            }
+            ShareCommands::Open { url } => {
+                println!("{}", dialog.open_share_link(&url).await?);
+            }
+        },
+
+        Commands::ServeApi { port, token } => {
+            let server = Arc::new(dialog).start_query_api(port, token).await?;
+            println!(
+                "Query API listening on http://{} (GET /notes, /notes/<id>, /search). Press Ctrl+C to exit.",
+                server.local_addr()
+            );
+            server.join().await;
+        }
+
+        Commands::Clipper {
+            command: ClipperCommands::Serve { port, token },
+        } => {
+            let server = Arc::new(dialog).start_clipper_server(port, token).await?;
+            println!(
+                "Clipper listening on http://{}/clip. Press Ctrl+C to exit.",
+                server.local_addr()
+            );
+            server.join().await;
+        }
     }
 
     Ok(())
@@ -0,0 +1,89 @@
+//! The public Rust API of Dialog, for apps other than the CLI and the iOS
+//! app (a GTK client, a bot...).
+//!
+//! Everything here follows semver: names exported from this crate are only
+//! removed or changed in a breaking release. The rest of `dialog_lib`
+//! (uniffi and CLI helpers, debug feeds, importers) may change in any
+//! release. Caveats:
+//! - [`DialogError`] and [`Note`] are `#[non_exhaustive]`: errors gain
+//!   variants and notes gain fields in minor releases, so match errors
+//!   with a wildcard arm and read notes through their fields.
+//! - The other structs here (reports, options, statuses) may also gain
+//!   public fields in minor releases. Build options with `Default` and
+//!   struct update syntax, and don't destructure them exhaustively.
+//! - Importers, link previews and the attachment cache pull in HTTP and
+//!   zip dependencies, so `dialog_lib` builds them only with its `import`,
+//!   `enrich` and `attachments` features. This crate leaves them off.
+//!
+//! ```no_run
+//! use dialog_core::{Dialog, Keys, StoreBackend};
+//!
+//! # async fn example() -> dialog_core::Result<()> {
+//! let dialog = Dialog::builder()
+//!     .signer(Keys::generate())
+//!     .store(StoreBackend::Memory)
+//!     .relay("wss://relay.damus.io")
+//!     .build()
+//!     .await?;
+//! dialog.create_note("Hello from Rust #bot").await?;
+//! for note in dialog.list_notes(10).await? {
+//!     println!("{}", note.text);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+// Dialog, its builder and errors
+pub use dialog_lib::{
+    validate_nsec, CancellationToken, Clock, Dialog, DialogBuilder, DialogError, ManualClock,
+    Result, StoreBackend, SystemClock, DEFAULT_FETCH_TIMEOUT,
+};
+
+// Notes and queries
+pub use dialog_lib::{
+    add_hashtags, format_note_id, normalize_tag, parse_hashtags, parse_note_id, remove_hashtags,
+    sort_notes, strip_hashtags, AppState, Attachment, CaptureSource, Note, NoteIdFormat, NoteState,
+    Provenance, SortKey, SortOrder, TagDefaults, TagMetadata, TaskStatus, DEFAULT_NOTE_KIND,
+    LEGACY_NOTE_KIND, MAX_NOTE_BYTES,
+};
+
+// Publishing, sync and watching
+pub use dialog_lib::{
+    DialogStatus, LastSync, PublishOptions, PublishPlan, PublishReport, RateLimit, RelayHealth,
+    RelaySyncStats, SyncMode, SyncReport, WatchHandle, WatchItem, WatchSpec,
+};
+
+// Devices, settings and the account
+pub use dialog_lib::{AccountDeletionReport, DeleteAccountOptions, Device, Settings};
+
+/// The nostr-sdk version Dialog is built on, for types not re-exported here
+pub use nostr_sdk;
+pub use nostr_sdk::{EventId, Keys, PublicKey, RelayUrl, Timestamp};
+
+#[cfg(feature = "keyring")]
+pub use dialog_lib::keychain;
+
+#[cfg(feature = "testing")]
+pub use dialog_lib::{clean_test_storage, testing::TestRelay};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_notes_through_the_public_api() {
+        let dialog = Dialog::builder()
+            .signer(Keys::generate())
+            .store(StoreBackend::Memory)
+            .build()
+            .await
+            .unwrap();
+        let note = dialog.save_note("Written by a bot #bot").await.unwrap();
+        assert_eq!(note.tags, vec!["bot".to_string()]);
+
+        let id = format_note_id(&note.id, NoteIdFormat::Note);
+        let notes = dialog.list_notes(10).await.unwrap();
+        assert_eq!(notes.len(), 1);
+        assert_eq!(notes[0].id, parse_note_id(&id).unwrap());
+    }
+}
@@ -0,0 +1,286 @@
+//! Leaving for good: local data and the stored key are removed, then
+//! relays are asked to delete everything this key published
+
+use crate::{Dialog, DialogError, Result};
+use nostr_sdk::prelude::*;
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+
+/// Event ids named per deletion request, to stay under relays' size limits
+pub(crate) const DELETION_BATCH_SIZE: usize = 500;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DeleteAccountOptions {
+    /// Ask relays (NIP-09) to delete every event this key published
+    pub delete_remote: bool,
+    /// Keychain entry holding the nsec, as (service, account). Needs the
+    /// `keyring` feature.
+    pub keychain: Option<(String, String)>,
+}
+
+/// What [`Dialog::delete_account`] did
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AccountDeletionReport {
+    /// Events named in deletion requests
+    pub remote_requested: usize,
+    /// Relays that accepted every deletion request
+    pub relays: Vec<RelayUrl>,
+    /// Relays that refused at least one, with the last reason given
+    pub failed_relays: Vec<(RelayUrl, String)>,
+    pub local_events_removed: usize,
+    /// The profile's data directory, now deleted; None for in-memory storage
+    pub data_dir_removed: Option<PathBuf>,
+    /// Files written outside the data directory, now deleted
+    pub files_removed: Vec<PathBuf>,
+    pub keychain_cleared: bool,
+}
+
+impl Dialog {
+    /// Delete this account: optionally ask relays to delete every event of
+    /// this key, and remove all local events, the profile's data
+    /// directory, the files Dialog wrote outside it (the quick-capture
+    /// outbox and the widget snapshot) and the keychain entry. Relays may
+    /// keep copies anyway; deletion requests are requests.
+    ///
+    /// Everything local goes first, so a local failure stops the deletion
+    /// before relays are asked for anything. If relays were to be asked and
+    /// none is connected, nothing is touched; if none accepts, the error
+    /// comes after the local data is gone, and retrying with the same key
+    /// asks again for whatever the relays still hold.
+    ///
+    /// The Dialog is disconnected afterwards and shouldn't be used again.
+    pub async fn delete_account(
+        &self,
+        options: DeleteAccountOptions,
+    ) -> Result<AccountDeletionReport> {
+        if options.keychain.is_some() && !cfg!(feature = "keyring") {
+            return Err(DialogError::Config(
+                "can't clear the keychain: built without the keyring feature".into(),
+            ));
+        }
+        let mut report = AccountDeletionReport::default();
+        let ids = if options.delete_remote {
+            self.account_event_ids().await?
+        } else {
+            Vec::new()
+        };
+
+        report.local_events_removed = self
+            .client
+            .database()
+            .query(vec![Filter::new()])
+            .await
+            .map_err(|e| DialogError::Database(e.to_string()))?
+            .len();
+        // nostrdb can't delete events; closing it lets its directory go
+        self.local_store.close();
+        if let Some(dir) = self.db_path.as_ref().and_then(|path| path.parent()) {
+            if remove_path(dir)? {
+                report.data_dir_removed = Some(dir.to_path_buf());
+            }
+        }
+        let outside = [self.outbox_path(), self.widget_path.read().unwrap().clone()];
+        for path in outside.into_iter().flatten() {
+            if remove_path(&path)? {
+                report.files_removed.push(path);
+            }
+        }
+        #[cfg(feature = "keyring")]
+        if let Some((service, account)) = &options.keychain {
+            crate::keychain::delete_nsec(service, account)?;
+            report.keychain_cleared = true;
+        }
+
+        let requested = if options.delete_remote {
+            self.request_account_deletion(&ids, &mut report).await
+        } else {
+            Ok(())
+        };
+        self.client.disconnect().await;
+        requested?;
+        eprintln!(
+            "[lib] delete_account: {} events requested from {} relays, {} removed locally",
+            report.remote_requested,
+            report.relays.len(),
+            report.local_events_removed
+        );
+        Ok(report)
+    }
+
+    /// Ids of every event of ours stored locally or on a relay, for
+    /// deletion requests. Fails when no relay is connected to ask.
+    async fn account_event_ids(&self) -> Result<Vec<EventId>> {
+        let relays = self
+            .client
+            .pool()
+            .relays_with_flag(RelayServiceFlags::WRITE, FlagCheck::All)
+            .await;
+        if relays.is_empty() {
+            return Err(DialogError::NotPublished("no relays connected".into()));
+        }
+        let filter = Filter::new().author(self.keys.public_key());
+        let mut events: Vec<Event> = self
+            .client
+            .database()
+            .query(vec![filter.clone()])
+            .await
+            .map_err(|e| DialogError::Database(e.to_string()))?
+            .into_iter()
+            .collect();
+        match self
+            .client
+            .fetch_events(vec![filter], Some(self.fetch_timeout))
+            .await
+        {
+            Ok(remote) => events.extend(remote),
+            Err(e) => eprintln!("[lib] delete_account: relay events not fetched: {e}"),
+        }
+        // Deleting a deletion request has no effect (NIP-09)
+        let ids: BTreeSet<EventId> = events
+            .iter()
+            .filter(|event| event.kind != Kind::EventDeletion)
+            .map(|event| event.id)
+            .collect();
+        Ok(ids.into_iter().collect())
+    }
+
+    /// Send deletion requests naming `ids`
+    async fn request_account_deletion(
+        &self,
+        ids: &[EventId],
+        report: &mut AccountDeletionReport,
+    ) -> Result<()> {
+        report.remote_requested = ids.len();
+
+        let mut accepted: HashMap<RelayUrl, usize> = HashMap::new();
+        let mut refused: HashMap<RelayUrl, String> = HashMap::new();
+        let batches = ids.chunks(DELETION_BATCH_SIZE);
+        let batch_count = batches.len();
+        for batch in batches {
+            let request = self.deletion_request(batch).await?;
+            // Not stored: the local database is gone by now
+            let output = match self.send_to_relays(request).await {
+                Ok(output) => output,
+                Err(DialogError::NotPublished(reason)) => {
+                    eprintln!("[lib] delete_account: deletion request refused: {reason}");
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            for url in output.success {
+                *accepted.entry(url).or_insert(0) += 1;
+            }
+            for (url, reason) in output.failed {
+                refused.insert(url, reason.unwrap_or_else(|| "unknown".to_string()));
+            }
+        }
+        if batch_count > 0 && accepted.is_empty() {
+            return Err(DialogError::NotPublished(
+                "no relay accepted the deletion requests".into(),
+            ));
+        }
+
+        report.relays = accepted
+            .into_iter()
+            .filter(|(url, count)| *count == batch_count && !refused.contains_key(url))
+            .map(|(url, _)| url)
+            .collect();
+        report.relays.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+        report.failed_relays = refused.into_iter().collect();
+        report
+            .failed_relays
+            .sort_by(|a, b| a.0.as_str().cmp(b.0.as_str()));
+        Ok(())
+    }
+
+    /// Deletion request (NIP-09) naming `ids`; callers keep batches to
+    /// [`DELETION_BATCH_SIZE`]
+    pub(crate) async fn deletion_request(&self, ids: &[EventId]) -> Result<Event> {
+        Ok(EventBuilder::new(Kind::EventDeletion, "")
+            .tags(ids.iter().map(|id| Tag::event(*id)))
+            .custom_created_at(self.now())
+            .sign(&self.keys)
+            .await?)
+    }
+}
+
+/// Remove a file or directory; false if there was nothing to remove
+fn remove_path(path: &Path) -> Result<bool> {
+    let result = if path.is_dir() {
+        std::fs::remove_dir_all(path)
+    } else {
+        std::fs::remove_file(path)
+    };
+    match result {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::memory_dialog;
+
+    #[tokio::test]
+    async fn test_delete_account_wipes_local_data() {
+        let dialog = memory_dialog();
+        dialog.save_note("First").await.unwrap();
+        dialog.save_note("Second #tag").await.unwrap();
+
+        // No relay to ask, so nothing is wiped
+        let options = DeleteAccountOptions {
+            delete_remote: true,
+            ..Default::default()
+        };
+        assert!(dialog.delete_account(options).await.is_err());
+        assert_eq!(dialog.list_notes(10).await.unwrap().len(), 2);
+
+        let dir = std::env::temp_dir().join(format!("dialog-account-{}", std::process::id()));
+        let outbox = dir.join("outbox.jsonl");
+        let widget = dir.join("widget.json");
+        crate::append_to_outbox(&outbox, "Captured").unwrap();
+        dialog.set_outbox_path(Some(outbox.clone()));
+        dialog.write_widget_snapshot(&widget).await.unwrap();
+
+        let report = dialog
+            .delete_account(DeleteAccountOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(report.local_events_removed, 2);
+        assert_eq!(report.data_dir_removed, None);
+        assert_eq!(report.files_removed, vec![outbox.clone(), widget.clone()]);
+        assert!(!report.keychain_cleared);
+        assert!(dialog.list_notes(10).await.unwrap().is_empty());
+        assert!(!outbox.exists() && !widget.exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_delete_account_on_nostrdb() {
+        let base = std::env::temp_dir().join(format!("dialog-leave-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&base);
+        let dialog = Dialog::builder()
+            .signer(Keys::generate())
+            .data_dir(&base)
+            .build()
+            .await
+            .unwrap();
+        dialog.save_note("First").await.unwrap();
+        // nostrdb ingests in the background
+        while dialog.list_notes(10).await.unwrap().is_empty() {
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+
+        let report = dialog
+            .delete_account(DeleteAccountOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(report.local_events_removed, 1);
+        let dir = report.data_dir_removed.unwrap();
+        assert!(!dir.exists());
+        assert!(dialog.list_notes(10).await.unwrap().is_empty());
+        let _ = std::fs::remove_dir_all(&base);
+    }
+}
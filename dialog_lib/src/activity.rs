@@ -0,0 +1,115 @@
+use crate::migrate::migrated_from;
+use crate::{Dialog, DialogError, Result};
+use chrono::{DateTime, Local, NaiveDate};
+use nostr_sdk::prelude::*;
+use std::collections::{BTreeMap, HashSet};
+use std::ops::RangeInclusive;
+
+/// Notes written on one local day
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DayActivity {
+    pub date: NaiveDate,
+    pub notes: usize,
+}
+
+/// Writing activity for a streak or a weekly chart
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Activity {
+    /// Every day of the requested range, oldest first, empty days included
+    pub days: Vec<DayActivity>,
+    pub today_count: usize,
+    /// Days in a row with at least one note, ending today, or yesterday
+    /// while nothing has been written today
+    pub streak_days: u32,
+}
+
+fn local_date(timestamp: Timestamp) -> Option<NaiveDate> {
+    let date = DateTime::from_timestamp(timestamp.as_u64() as i64, 0)?;
+    Some(date.with_timezone(&Local).date_naive())
+}
+
+impl Dialog {
+    /// Notes written per day over `range`, plus today's count and the
+    /// current streak, which may reach back before `range`.
+    ///
+    /// Counted from event timestamps, so nothing is decrypted. Notes
+    /// written with [`Dialog::set_max_privacy`] can count up to
+    /// [`MAX_TIMESTAMP_FUZZ_SECS`](crate::MAX_TIMESTAMP_FUZZ_SECS) early.
+    pub async fn activity(&self, range: RangeInclusive<NaiveDate>) -> Result<Activity> {
+        let per_day = self.notes_per_day().await?;
+        let count = |date: &NaiveDate| per_day.get(date).copied().unwrap_or(0);
+
+        let days = range
+            .start()
+            .iter_days()
+            .take_while(|date| date <= range.end())
+            .map(|date| DayActivity {
+                date,
+                notes: count(&date),
+            })
+            .collect();
+
+        let today = local_date(self.now()).unwrap_or_else(|| Local::now().date_naive());
+        let today_count = count(&today);
+        let mut day = if today_count > 0 {
+            Some(today)
+        } else {
+            today.pred_opt()
+        };
+        let mut streak_days = 0;
+        while let Some(date) = day.filter(|date| count(date) > 0) {
+            streak_days += 1;
+            day = date.pred_opt();
+        }
+
+        Ok(Activity {
+            days,
+            today_count,
+            streak_days,
+        })
+    }
+
+    async fn notes_per_day(&self) -> Result<BTreeMap<NaiveDate, usize>> {
+        let events = self
+            .client
+            .database()
+            .query(vec![self.notes_filter()])
+            .await
+            .map_err(|e| DialogError::Database(e.to_string()))?;
+        // A retagged or migrated note is one note, not two
+        let superseded: HashSet<EventId> = events.iter().filter_map(migrated_from).collect();
+        let mut per_day = BTreeMap::new();
+        for event in events.iter().filter(|e| !superseded.contains(&e.id)) {
+            if let Some(date) = local_date(event.created_at) {
+                *per_day.entry(date).or_default() += 1;
+            }
+        }
+        Ok(per_day)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::memory_dialog;
+
+    #[tokio::test]
+    async fn test_activity_counts_days_and_streak() {
+        let dialog = memory_dialog();
+        let today = Local::now().date_naive();
+        let week = today - chrono::Days::new(6)..=today;
+        assert_eq!(dialog.activity(week.clone()).await.unwrap().streak_days, 0);
+
+        let first = dialog.save_note("One").await.unwrap();
+        dialog.save_note("Two").await.unwrap();
+        // Retagging replaces the note; it still counts once
+        dialog.add_tags(&[first.id], &["todo".into()]).await;
+
+        let activity = dialog.activity(week).await.unwrap();
+        assert_eq!(activity.days.len(), 7);
+        assert_eq!(activity.days[6].date, today);
+        assert_eq!(activity.days[6].notes, 2);
+        assert_eq!(activity.today_count, 2);
+        assert_eq!(activity.streak_days, 1);
+    }
+}
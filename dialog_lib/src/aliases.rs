@@ -0,0 +1,130 @@
+//! Tag renames. Notes keep the hashtags they were written with; queries
+//! resolve old names through an alias table kept in app state, so a rename
+//! doesn't republish every note.
+
+use crate::{normalize_tag, Dialog, DialogError, Result};
+use std::collections::BTreeMap;
+
+/// The name `tag` goes by now, following renames in `aliases` (old name to
+/// new). A loop, e.g. from renames made on two devices, stops before it
+/// comes back around.
+pub fn resolve_tag(aliases: &BTreeMap<String, String>, tag: &str) -> String {
+    let mut current = tag;
+    for _ in 0..aliases.len() {
+        match aliases.get(current) {
+            Some(next) if next != tag => current = next,
+            _ => break,
+        }
+    }
+    current.to_string()
+}
+
+/// `tag` followed by every older name that resolves to it
+fn names_for(aliases: &BTreeMap<String, String>, tag: &str) -> Vec<String> {
+    let mut names = vec![tag.to_string()];
+    names.extend(
+        aliases
+            .keys()
+            .filter(|old| *old != tag && resolve_tag(aliases, old) == tag)
+            .cloned(),
+    );
+    names
+}
+
+impl Dialog {
+    /// Rename `from` to `to` on every device syncing app state. Notes keep
+    /// their hashtags; [`Dialog::list_by_tag`] and [`Dialog::tag_counts`]
+    /// count `#from` notes as `#to` from now on. The tag's color and
+    /// notification rule carry over unless `to` has its own.
+    pub async fn rename_tag(&self, from: &str, to: &str) -> Result<()> {
+        let (Some(from), Some(to)) = (normalize_tag(from), normalize_tag(to)) else {
+            return Err(DialogError::Config(format!(
+                "can't rename #{from} to #{to}: not a tag name"
+            )));
+        };
+        if from == to {
+            return Ok(());
+        }
+        let state = self.app_state().await?;
+        let mut aliases = state.tag_aliases.map(|a| a.value).unwrap_or_default();
+        // A name renamed away earlier is in use again
+        aliases.remove(&to);
+        aliases.insert(from.clone(), to.clone());
+        self.store_tag_aliases(aliases).await?;
+
+        if let Some(metadata) = state.tags.get(&from) {
+            if !state.tags.contains_key(&to) {
+                self.set_tag_metadata(&to, metadata.value.clone()).await?;
+            }
+        }
+        eprintln!("[lib] rename_tag: #{from} -> #{to}");
+        Ok(())
+    }
+
+    /// Renamed tags, old name to new
+    pub async fn tag_aliases(&self) -> Result<BTreeMap<String, String>> {
+        let state = self.app_state().await?;
+        Ok(state.tag_aliases.map(|a| a.value).unwrap_or_default())
+    }
+
+    /// `tag` and the names it had before being renamed
+    pub(crate) async fn tag_names(&self, tag: &str) -> Result<Vec<String>> {
+        let aliases = self.tag_aliases().await?;
+        Ok(names_for(&aliases, &resolve_tag(&aliases, tag)))
+    }
+
+    /// Number of notes per tag, under their current names. A note tagged
+    /// with a tag's old and new name counts once.
+    pub async fn tag_counts(&self) -> Result<BTreeMap<String, usize>> {
+        let aliases = self.tag_aliases().await?;
+        let mut counts = BTreeMap::new();
+        for note in self.list_notes(usize::MAX).await? {
+            let mut tags: Vec<String> = note
+                .tags
+                .iter()
+                .map(|tag| resolve_tag(&aliases, tag))
+                .collect();
+            tags.sort();
+            tags.dedup();
+            for tag in tags {
+                *counts.entry(tag).or_insert(0) += 1;
+            }
+        }
+        Ok(counts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::memory_dialog;
+    use crate::TagMetadata;
+
+    #[tokio::test]
+    async fn test_renamed_tags_resolve() {
+        let dialog = memory_dialog();
+        dialog.save_note("Old #work note").await.unwrap();
+        dialog.save_note("Both #work #job").await.unwrap();
+        dialog.save_note("New #job note").await.unwrap();
+        let red = TagMetadata {
+            color: Some("red".into()),
+            ..Default::default()
+        };
+        dialog.set_tag_metadata("work", red.clone()).await.unwrap();
+
+        dialog.rename_tag("#Work", "job").await.unwrap();
+        assert_eq!(dialog.list_by_tag("job", 10).await.unwrap().len(), 3);
+        assert_eq!(dialog.list_by_tag("work", 10).await.unwrap().len(), 3);
+        assert_eq!(dialog.tag_counts().await.unwrap()["job"], 3);
+        assert_eq!(dialog.app_state().await.unwrap().tags["job"].value, red);
+
+        // Renames chain, and renaming back doesn't loop
+        dialog.rename_tag("job", "career").await.unwrap();
+        assert_eq!(dialog.tag_counts().await.unwrap()["career"], 3);
+        dialog.rename_tag("career", "work").await.unwrap();
+        let counts = dialog.tag_counts().await.unwrap();
+        assert_eq!(counts.len(), 1);
+        assert_eq!(counts["work"], 3);
+        assert!(dialog.rename_tag("work", "#").await.is_err());
+    }
+}
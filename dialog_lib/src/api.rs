@@ -0,0 +1,273 @@
+use crate::local_http::{read_request, write_response, Request, Response};
+use crate::{parse_note_id, Dialog, DialogError, Note, Result};
+use nostr_sdk::prelude::*;
+use serde_json::{json, Value};
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+
+const MIN_TOKEN_LEN: usize = 16;
+/// Notes returned when the request doesn't pass `limit`
+const DEFAULT_LIMIT: usize = 20;
+const MAX_LIMIT: usize = 500;
+/// Requests are read-only, so bodies are never needed
+const MAX_BODY_BYTES: usize = 0;
+
+/// Running query API server; stops when dropped.
+///
+/// A read-only localhost endpoint over the decrypted notes for local tools
+/// such as an AI assistant or MCP server:
+///
+/// - `GET /notes?limit=&tag=` newest notes, optionally with a tag
+/// - `GET /notes/<id>` one note, by hex or `note1` id
+/// - `GET /search?q=&limit=` notes containing the query
+///
+/// Every request must send `Authorization: Bearer <token>`.
+pub struct ApiHandle {
+    addr: SocketAddr,
+    task: JoinHandle<()>,
+}
+
+impl ApiHandle {
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Wait for the server to stop (it only stops if the task is aborted)
+    pub async fn join(mut self) {
+        let _ = (&mut self.task).await;
+    }
+}
+
+impl Drop for ApiHandle {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+impl Dialog {
+    /// Start the query API on `127.0.0.1:port` (0 picks a free port).
+    ///
+    /// Anything holding the token can read every note in plaintext, so only
+    /// hand it to tools you trust.
+    pub async fn start_query_api(self: Arc<Self>, port: u16, token: String) -> Result<ApiHandle> {
+        if token.len() < MIN_TOKEN_LEN {
+            return Err(DialogError::Api(format!(
+                "token must be at least {MIN_TOKEN_LEN} characters"
+            )));
+        }
+
+        // Never listen beyond this machine
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port)).await?;
+        let addr = listener.local_addr()?;
+        eprintln!("[lib] api: listening on http://{addr}");
+
+        let token = Arc::new(token);
+        let task = tokio::spawn(async move {
+            loop {
+                let (stream, peer) = match listener.accept().await {
+                    Ok(accepted) => accepted,
+                    Err(e) => {
+                        eprintln!("[lib] api: accept failed: {e}");
+                        continue;
+                    }
+                };
+                let dialog = self.clone();
+                let token = token.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = dialog.serve_query(stream, &token).await {
+                        eprintln!("[lib] api: {peer}: {e}");
+                    }
+                });
+            }
+        });
+        Ok(ApiHandle { addr, task })
+    }
+
+    async fn serve_query(&self, mut stream: TcpStream, token: &str) -> Result<()> {
+        let response = match read_request(&mut stream, MAX_BODY_BYTES).await {
+            Ok(request) => self.handle_query(request, token).await,
+            Err(response) => response,
+        };
+        write_response(&mut stream, response).await?;
+        Ok(())
+    }
+
+    async fn handle_query(&self, request: Request, token: &str) -> Response {
+        if !request.is_authorized(token) {
+            return Response::error("401 Unauthorized", "invalid token");
+        }
+        if request.method != "GET" {
+            return Response::error("405 Method Not Allowed", "the API is read-only; use GET");
+        }
+        let (path, query) = request
+            .path
+            .split_once('?')
+            .unwrap_or((request.path.as_str(), ""));
+        let Some(params) = parse_query(query) else {
+            return Response::error("400 Bad Request", "malformed query string");
+        };
+        let limit = match param(&params, "limit").map(str::parse::<usize>) {
+            None => DEFAULT_LIMIT,
+            Some(Ok(limit)) => limit.min(MAX_LIMIT),
+            Some(Err(_)) => return Response::error("400 Bad Request", "invalid limit"),
+        };
+
+        let result = match path.trim_end_matches('/') {
+            "/notes" => match param(&params, "tag") {
+                Some(tag) => self.list_by_tag(tag, limit).await,
+                None => self.list_notes(limit).await,
+            },
+            "/search" => match param(&params, "q").map(str::trim) {
+                Some(q) if !q.is_empty() => self.search_notes(q, limit).await,
+                _ => return Response::error("400 Bad Request", "missing q"),
+            },
+            other => {
+                let Some(id) = other.strip_prefix("/notes/") else {
+                    return Response::error("404 Not Found", "not found");
+                };
+                let Ok(id) = parse_note_id(id) else {
+                    return Response::error("400 Bad Request", "invalid note id");
+                };
+                return match self.get_note(&id).await {
+                    Ok(Some(note)) => Response::new("200 OK", note_json(&note).to_string()),
+                    Ok(None) => Response::error("404 Not Found", "note not found"),
+                    Err(e) => Response::error("500 Internal Server Error", &e.to_string()),
+                };
+            }
+        };
+
+        match result {
+            Ok(notes) => {
+                let notes: Vec<Value> = notes.iter().map(note_json).collect();
+                Response::new("200 OK", json!({ "notes": notes }).to_string())
+            }
+            Err(e) => Response::error("500 Internal Server Error", &e.to_string()),
+        }
+    }
+}
+
+fn note_json(note: &Note) -> Value {
+    json!({
+        "id": note.id.to_hex(),
+        "text": note.text,
+        "tags": note.tags,
+        "created_at": note.created_at.as_u64(),
+        "is_read": note.is_read,
+        "is_pinned": note.is_pinned,
+        "is_archived": note.is_archived,
+        "transcript": note.transcript,
+    })
+}
+
+/// `a=1&b=two+words` into decoded pairs; None if anything is malformed
+fn parse_query(query: &str) -> Option<Vec<(String, String)>> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            Some((percent_decode(key)?, percent_decode(value)?))
+        })
+        .collect()
+}
+
+fn param<'a>(params: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    params
+        .iter()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.as_str())
+}
+
+fn percent_decode(input: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(input.len());
+    let mut iter = input.bytes();
+    while let Some(byte) = iter.next() {
+        match byte {
+            b'+' => bytes.push(b' '),
+            b'%' => {
+                let hex = [iter.next()?, iter.next()?];
+                let hex = std::str::from_utf8(&hex).ok()?;
+                bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            }
+            _ => bytes.push(byte),
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::memory_dialog;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    const TOKEN: &str = "0123456789abcdef";
+
+    async fn get(addr: SocketAddr, path: &str, token: &str) -> String {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let request = format!("GET {path} HTTP/1.1\r\nAuthorization: Bearer {token}\r\n\r\n");
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    fn body(response: &str) -> Value {
+        let (_, body) = response.split_once("\r\n\r\n").unwrap();
+        serde_json::from_str(body).unwrap()
+    }
+
+    #[test]
+    fn test_parse_query() {
+        assert_eq!(
+            parse_query("q=team+sync%21&limit=5").unwrap(),
+            vec![
+                ("q".to_string(), "team sync!".to_string()),
+                ("limit".to_string(), "5".to_string())
+            ]
+        );
+        assert!(parse_query("").unwrap().is_empty());
+        assert!(parse_query("q=%zz").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_query_api_lists_and_searches() {
+        let dialog = Arc::new(memory_dialog());
+        assert!(dialog
+            .clone()
+            .start_query_api(0, "short".into())
+            .await
+            .is_err());
+
+        let note = dialog.save_note("Quarterly plan #work").await.unwrap();
+        dialog.save_note("Groceries #home").await.unwrap();
+        let server = dialog.start_query_api(0, TOKEN.into()).await.unwrap();
+        let addr = server.local_addr();
+        assert!(addr.ip().is_loopback());
+
+        let unauthorized = get(addr, "/notes", "wrong-token-0000").await;
+        assert!(unauthorized.starts_with("HTTP/1.1 401"), "{unauthorized}");
+
+        let all = body(&get(addr, "/notes", TOKEN).await);
+        assert_eq!(all["notes"].as_array().unwrap().len(), 2);
+
+        let work = body(&get(addr, "/notes?tag=work", TOKEN).await);
+        assert_eq!(work["notes"][0]["id"], note.id.to_hex());
+
+        let found = body(&get(addr, "/search?q=quarterly", TOKEN).await);
+        assert_eq!(found["notes"].as_array().unwrap().len(), 1);
+
+        let one = get(
+            addr,
+            &format!("/notes/{}", note.id.to_bech32().unwrap()),
+            TOKEN,
+        )
+        .await;
+        assert_eq!(body(&one)["text"], "Quarterly plan #work");
+
+        let missing = get(addr, "/search", TOKEN).await;
+        assert!(missing.starts_with("HTTP/1.1 400"), "{missing}");
+    }
+}
@@ -0,0 +1,949 @@
+use crate::audit::AuditAction;
+use crate::kinds::{KindFeature, APP_DATA_KIND};
+use crate::notify::NotifyRule;
+use crate::rules::TagRule;
+use crate::tag_defaults::TagDefaults;
+use crate::tasks::TaskStatus;
+use crate::{Dialog, DialogError, Result};
+use nostr_sdk::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::mpsc;
+
+/// Prefix of the d-tags app state is stored under. State is split into one
+/// event per leading hex digit of the note id (plus one for tags) so each
+/// event stays below the NIP-44 plaintext limit; see [`ShardLayout`].
+const APP_STATE_D_TAG: &str = "dialog_app_state";
+const TAGS_SHARD: &str = "tags";
+const HEX_DIGITS: &str = "0123456789abcdef";
+/// A digit's notes are split by their first two hex digits once their JSON
+/// passes this, well under the 65535 byte NIP-44 limit. Two digits keep
+/// shards under it past 100,000 notes.
+const SHARD_SPLIT_BYTES: usize = 32 * 1024;
+/// d-tag of the old, never-published read status events
+const LEGACY_D_TAG: &str = "dialog_local_state";
+const LEGACY_LIMIT: usize = 1000;
+
+/// A value stamped with the time it last changed; merges keep the newest
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Lww<T> {
+    // Short field names keep the encrypted state events small
+    #[serde(rename = "v")]
+    pub value: T,
+    #[serde(rename = "t")]
+    pub updated_at: u64,
+}
+
+impl<T: Clone + Ord> Lww<T> {
+    pub fn new(value: T, updated_at: u64) -> Self {
+        Self { value, updated_at }
+    }
+
+    // Equal timestamps fall back to comparing values so every device
+    // settles on the same winner
+    fn wins_over(&self, other: &Self) -> bool {
+        (self.updated_at, &self.value) > (other.updated_at, &other.value)
+    }
+}
+
+fn merge_field<T: Clone + Ord>(mine: &mut Option<Lww<T>>, theirs: &Option<Lww<T>>) -> bool {
+    let Some(theirs) = theirs else {
+        return false;
+    };
+    if mine.as_ref().is_some_and(|mine| !theirs.wins_over(mine)) {
+        return false;
+    }
+    *mine = Some(theirs.clone());
+    true
+}
+
+/// Per-note flags; unset fields have never been changed on any device
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NoteState {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub read: Option<Lww<bool>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pinned: Option<Lww<bool>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub archived: Option<Lww<bool>>,
+    /// Hex id of the public kind-1 this note was published as
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub published: Option<Lww<Option<String>>>,
+    /// When the note is due, in unix seconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub due: Option<Lww<Option<u64>>>,
+    /// When the note was last opened, in unix seconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub viewed: Option<Lww<u64>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub task: Option<Lww<Option<TaskStatus>>>,
+}
+
+impl NoteState {
+    pub fn is_read(&self) -> bool {
+        self.read.as_ref().is_some_and(|r| r.value)
+    }
+
+    pub fn is_pinned(&self) -> bool {
+        self.pinned.as_ref().is_some_and(|p| p.value)
+    }
+
+    pub fn is_archived(&self) -> bool {
+        self.archived.as_ref().is_some_and(|a| a.value)
+    }
+
+    pub fn public_id(&self) -> Option<EventId> {
+        let id = self.published.as_ref()?.value.as_deref()?;
+        EventId::from_hex(id).ok()
+    }
+
+    pub fn due(&self) -> Option<Timestamp> {
+        self.due.as_ref()?.value.map(Timestamp::from)
+    }
+
+    pub fn last_viewed(&self) -> Option<Timestamp> {
+        self.viewed.as_ref().map(|v| Timestamp::from(v.value))
+    }
+
+    pub fn task(&self) -> Option<TaskStatus> {
+        self.task.as_ref()?.value
+    }
+
+    fn merge(&mut self, other: &NoteState) -> bool {
+        let read = merge_field(&mut self.read, &other.read);
+        let pinned = merge_field(&mut self.pinned, &other.pinned);
+        let archived = merge_field(&mut self.archived, &other.archived);
+        let published = merge_field(&mut self.published, &other.published);
+        let due = merge_field(&mut self.due, &other.due);
+        let viewed = merge_field(&mut self.viewed, &other.viewed);
+        let task = merge_field(&mut self.task, &other.task);
+        read || pinned || archived || published || due || viewed || task
+    }
+}
+
+/// User-assigned presentation data for a tag
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct TagMetadata {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    #[serde(default, skip_serializing_if = "NotifyRule::is_off")]
+    pub notify: NotifyRule,
+    #[serde(default, skip_serializing_if = "TagDefaults::is_empty")]
+    pub defaults: TagDefaults,
+}
+
+/// Read/pin/archive flags and tag metadata, merged last-write-wins
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AppState {
+    /// Keyed by note id (hex)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub notes: BTreeMap<String, NoteState>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub tags: BTreeMap<String, Lww<TagMetadata>>,
+    /// Auto-tagging rules, replaced as a whole when edited
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag_rules: Option<Lww<Vec<TagRule>>>,
+    /// Devices whose notes are hidden, by id
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ignored_devices: Option<Lww<Vec<String>>>,
+    /// Renamed tags, old name to new; see [`Dialog::rename_tag`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag_aliases: Option<Lww<BTreeMap<String, String>>>,
+}
+
+impl AppState {
+    pub fn note(&self, note_id: &EventId) -> NoteState {
+        self.notes
+            .get(&note_id.to_hex())
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Merge another device's state into this one.
+    /// Returns true if anything here changed.
+    pub fn merge(&mut self, other: &AppState) -> bool {
+        let mut changed = false;
+        for (id, theirs) in &other.notes {
+            changed |= self.notes.entry(id.clone()).or_default().merge(theirs);
+        }
+        for (tag, theirs) in &other.tags {
+            let mut mine = self.tags.get(tag).cloned();
+            if merge_field(&mut mine, &Some(theirs.clone())) {
+                self.tags.insert(tag.clone(), theirs.clone());
+                changed = true;
+            }
+        }
+        changed |= merge_field(&mut self.tag_rules, &other.tag_rules);
+        changed |= merge_field(&mut self.ignored_devices, &other.ignored_devices);
+        changed |= merge_field(&mut self.tag_aliases, &other.tag_aliases);
+        changed
+    }
+
+    /// The part of the state stored under `shard` with notes laid out by
+    /// `layout`. Shards the layout doesn't use come back empty.
+    fn shard(&self, shard: &str, layout: &ShardLayout) -> AppState {
+        if shard == TAGS_SHARD {
+            return AppState {
+                notes: BTreeMap::new(),
+                tags: self.tags.clone(),
+                tag_rules: self.tag_rules.clone(),
+                ignored_devices: self.ignored_devices.clone(),
+                tag_aliases: self.tag_aliases.clone(),
+            };
+        }
+        AppState {
+            notes: self
+                .notes
+                .iter()
+                .filter(|(id, _)| layout.shard_for(id) == shard)
+                .map(|(id, state)| (id.clone(), state.clone()))
+                .collect(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Which leading hex digits have their notes split into 16 shards by the
+/// second digit. Worked out from the state alone, so devices that merged
+/// the same state store it the same way; a shard the layout stops using is
+/// stored empty so relays drop its old contents.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct ShardLayout {
+    split: BTreeSet<char>,
+}
+
+impl ShardLayout {
+    fn of(state: &AppState) -> Self {
+        let mut sizes: BTreeMap<char, usize> = BTreeMap::new();
+        for (id, note) in &state.notes {
+            let Some(digit) = id.chars().next() else {
+                continue;
+            };
+            // The value plus its quoted key, colon and comma
+            let json = serde_json::to_string(note).map_or(0, |json| json.len());
+            *sizes.entry(digit).or_default() += id.len() + 4 + json;
+        }
+        Self {
+            split: sizes
+                .into_iter()
+                .filter(|(_, size)| *size > SHARD_SPLIT_BYTES)
+                .map(|(digit, _)| digit)
+                .collect(),
+        }
+    }
+
+    fn shard_for<'a>(&self, note_id: &'a str) -> &'a str {
+        let len = match note_id.chars().next() {
+            Some(digit) if self.split.contains(&digit) => 2,
+            _ => 1,
+        };
+        note_id.get(..len).unwrap_or(note_id)
+    }
+
+    fn is_split(&self, note_id: &str) -> bool {
+        note_id
+            .chars()
+            .next()
+            .is_some_and(|digit| self.split.contains(&digit))
+    }
+}
+
+fn shard_d_tag(shard: &str) -> String {
+    format!("{APP_STATE_D_TAG}:{shard}")
+}
+
+/// The shard of a digit and the 16 it splits into
+fn digit_shards(digit: &str) -> Vec<String> {
+    std::iter::once(digit.to_string())
+        .chain(HEX_DIGITS.chars().map(|c| format!("{digit}{c}")))
+        .collect()
+}
+
+/// Every shard state may be stored under, for any layout
+fn all_shards() -> Vec<String> {
+    HEX_DIGITS
+        .chars()
+        .flat_map(|c| digit_shards(&c.to_string()))
+        .chain([TAGS_SHARD.to_string()])
+        .collect()
+}
+
+/// A note's flags after a change made on another device
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateChange {
+    pub note_id: EventId,
+    pub state: NoteState,
+}
+
+/// Cached app state plus the opt-in relay sync flag
+#[derive(Debug, Default)]
+pub(crate) struct AppStateStore {
+    // Loaded from the local database on first use
+    cache: tokio::sync::Mutex<Option<AppState>>,
+    sync_enabled: AtomicBool,
+    // created_at of the newest state event, so replacements always sort later
+    last_saved: AtomicU64,
+}
+
+impl AppStateStore {
+    /// Merge a shard received from a relay into the cache, returning the
+    /// notes whose flags changed. Nothing is merged before the cache is
+    /// loaded; loading picks the event up from the database instead.
+    async fn merge_remote(&self, shard: &AppState) -> Vec<StateChange> {
+        let mut cache = self.cache.lock().await;
+        let Some(state) = cache.as_mut() else {
+            return Vec::new();
+        };
+        let mut changes = Vec::new();
+        for (id, theirs) in &shard.notes {
+            let mine = state.notes.entry(id.clone()).or_default();
+            if !mine.merge(theirs) {
+                continue;
+            }
+            if let Ok(note_id) = EventId::from_hex(id) {
+                changes.push(StateChange {
+                    note_id,
+                    state: mine.clone(),
+                });
+            }
+        }
+        state.merge(&AppState {
+            notes: BTreeMap::new(),
+            ..shard.clone()
+        });
+        changes
+    }
+}
+
+impl Dialog {
+    /// Opt in to (or out of) publishing app state to relays as encrypted
+    /// NIP-78 data. When off, state is only kept in the local database.
+    pub fn set_app_state_sync(&self, enabled: bool) {
+        self.app_state.sync_enabled.store(enabled, Ordering::SeqCst);
+    }
+
+    pub fn app_state_sync_enabled(&self) -> bool {
+        self.app_state.sync_enabled.load(Ordering::SeqCst)
+    }
+
+    /// Snapshot of the current app state
+    pub async fn app_state(&self) -> Result<AppState> {
+        let mut cache = self.app_state.cache.lock().await;
+        if cache.is_none() {
+            *cache = Some(self.load_app_state().await?);
+        }
+        Ok(cache.clone().unwrap_or_default())
+    }
+
+    /// Drop the cached state and merge it again from the stored events
+    pub(crate) async fn reload_app_state(&self) -> Result<AppState> {
+        let mut cache = self.app_state.cache.lock().await;
+        let state = self.load_app_state().await?;
+        *cache = Some(state.clone());
+        Ok(state)
+    }
+
+    pub async fn note_state(&self, note_id: &EventId) -> Result<NoteState> {
+        Ok(self.app_state().await?.note(note_id))
+    }
+
+    /// Mark a note as read
+    pub async fn mark_as_read(&self, note_id: &EventId) -> Result<()> {
+        self.set_read(note_id, true).await
+    }
+
+    /// Get read status for a note; unknown notes are unread
+    pub async fn get_read_status(&self, note_id: &EventId) -> bool {
+        self.note_state(note_id)
+            .await
+            .map(|state| state.is_read())
+            .unwrap_or(false)
+    }
+
+    pub async fn set_read(&self, note_id: &EventId, read: bool) -> Result<()> {
+        self.update_note_state(note_id, |state, now| state.read = Some(Lww::new(read, now)))
+            .await?;
+        self.audit(AuditAction::SetRead, Some(note_id), read.to_string());
+        Ok(())
+    }
+
+    pub async fn set_pinned(&self, note_id: &EventId, pinned: bool) -> Result<()> {
+        self.update_note_state(note_id, |state, now| {
+            state.pinned = Some(Lww::new(pinned, now))
+        })
+        .await?;
+        self.audit(AuditAction::SetPinned, Some(note_id), pinned.to_string());
+        Ok(())
+    }
+
+    pub async fn set_archived(&self, note_id: &EventId, archived: bool) -> Result<()> {
+        self.update_note_state(note_id, |state, now| {
+            state.archived = Some(Lww::new(archived, now))
+        })
+        .await?;
+        self.audit(
+            AuditAction::SetArchived,
+            Some(note_id),
+            archived.to_string(),
+        );
+        Ok(())
+    }
+
+    pub(crate) async fn set_public_id(&self, note_id: &EventId, public_id: EventId) -> Result<()> {
+        self.update_note_state(note_id, |state, now| {
+            state.published = Some(Lww::new(Some(public_id.to_hex()), now))
+        })
+        .await?;
+        self.audit(
+            AuditAction::PublishPublic,
+            Some(note_id),
+            public_id.to_hex(),
+        );
+        Ok(())
+    }
+
+    /// Set or clear the time a note is due
+    pub async fn set_due(&self, note_id: &EventId, due: Option<Timestamp>) -> Result<()> {
+        self.update_note_state(note_id, |state, now| {
+            state.due = Some(Lww::new(due.map(|d| d.as_u64()), now))
+        })
+        .await?;
+        let detail = due.map_or_else(|| "cleared".to_string(), |d| d.as_u64().to_string());
+        self.audit(AuditAction::SetDue, Some(note_id), detail);
+        Ok(())
+    }
+
+    /// Give `to` the flags `from` has, e.g. when a note is republished
+    /// under a new id
+    pub(crate) async fn copy_note_state(&self, from: &EventId, to: &EventId) -> Result<()> {
+        let state = self.note_state(from).await?;
+        if state == NoteState::default() {
+            return Ok(());
+        }
+        self.update_note_state(to, |target, _| {
+            target.merge(&state);
+        })
+        .await
+    }
+
+    pub async fn set_tag_metadata(&self, tag: &str, metadata: TagMetadata) -> Result<()> {
+        let Some(tag) = crate::normalize_tag(tag) else {
+            return Ok(());
+        };
+        self.update_app_state(None, |state, now| {
+            state.tags.insert(tag, Lww::new(metadata, now));
+        })
+        .await
+    }
+
+    /// Merge tag metadata from elsewhere, e.g. imported settings; newer
+    /// local edits win
+    pub(crate) async fn merge_tag_metadata(
+        &self,
+        tags: &BTreeMap<String, Lww<TagMetadata>>,
+    ) -> Result<()> {
+        self.update_app_state(None, |state, _| {
+            state.merge(&AppState {
+                tags: tags.clone(),
+                ..Default::default()
+            });
+        })
+        .await
+    }
+
+    pub(crate) async fn store_tag_rules(&self, rules: Vec<TagRule>) -> Result<()> {
+        self.update_app_state(None, |state, now| {
+            state.tag_rules = Some(Lww::new(rules, now));
+        })
+        .await
+    }
+
+    pub(crate) async fn store_tag_aliases(&self, aliases: BTreeMap<String, String>) -> Result<()> {
+        self.update_app_state(None, |state, now| {
+            state.tag_aliases = Some(Lww::new(aliases, now));
+        })
+        .await
+    }
+
+    pub(crate) async fn store_ignored_devices(&self, devices: Vec<String>) -> Result<()> {
+        self.update_app_state(None, |state, now| {
+            state.ignored_devices = Some(Lww::new(devices, now));
+        })
+        .await
+    }
+
+    /// Pull app state published by other devices, merge it and publish the
+    /// result if it differs from what the relays hold. Does nothing unless
+    /// [`Dialog::set_app_state_sync`] is on. Returns whether local state changed.
+    pub async fn sync_app_state(&self) -> Result<bool> {
+        if !self.app_state_sync_enabled() {
+            return Ok(false);
+        }
+
+        let events = self
+            .client
+            .fetch_events(vec![self.app_state_filter()], Some(self.fetch_timeout))
+            .await?;
+        let mut remote = AppState::default();
+        // What the relays hold under each d-tag, newest version only
+        let mut remote_shards: BTreeMap<String, AppState> = BTreeMap::new();
+        for (shard, state) in self.decode_state_events(events) {
+            remote.merge(&state);
+            remote_shards.entry(shard).or_insert(state);
+        }
+
+        let mut cache = self.app_state.cache.lock().await;
+        let mut state = match cache.take() {
+            Some(state) => state,
+            None => self.load_app_state().await?,
+        };
+        let changed = state.merge(&remote);
+        eprintln!("[lib] sync_app_state: merged remote state; changed={changed}");
+
+        let layout = ShardLayout::of(&state);
+        let mut result = Ok(());
+        for shard in all_shards() {
+            let local = state.shard(&shard, &layout);
+            // An unused shard only needs storing to clear what relays hold
+            let stale = match remote_shards.get(&shard) {
+                Some(remote) => *remote != local,
+                None => local != AppState::default(),
+            };
+            if stale {
+                result = result.and(self.store_app_state(&shard, &local).await);
+            }
+        }
+        *cache = Some(state);
+        result.map(|()| changed)
+    }
+
+    /// Stream flag changes (read, pinned, archived...) made on other
+    /// devices as their app state reaches the relays. Only changes that
+    /// differ from what this device already has are sent; the stream ends
+    /// when the receiver is dropped.
+    pub async fn watch_state_changes(&self) -> Result<mpsc::Receiver<StateChange>> {
+        self.require_feature(KindFeature::AppData)?;
+        let sub_id = SubscriptionId::generate();
+        let filter = self.app_state_filter().since(self.now());
+        self.client
+            .subscribe_with_id(sub_id.clone(), vec![filter], None)
+            .await?;
+
+        let (tx, rx) = mpsc::channel(100);
+        let client = self.client.clone();
+        let keys = self.keys.clone();
+        let store = self.app_state.clone();
+        tokio::spawn(async move {
+            let mut notifications = client.notifications();
+            loop {
+                let notification = tokio::select! {
+                    _ = tx.closed() => break,
+                    notification = notifications.recv() => notification,
+                };
+                let event = match notification {
+                    Ok(RelayPoolNotification::Event {
+                        subscription_id,
+                        event,
+                        ..
+                    }) if subscription_id == sub_id => event,
+                    Ok(RelayPoolNotification::Shutdown) | Err(RecvError::Closed) => break,
+                    // Missed shards are merged by the next sync
+                    Ok(_) | Err(RecvError::Lagged(_)) => continue,
+                };
+                store
+                    .last_saved
+                    .fetch_max(event.created_at.as_u64(), Ordering::SeqCst);
+                let shard = match decode_state_event(&keys, &event) {
+                    Ok(shard) => shard,
+                    Err(e) => {
+                        eprintln!("[lib] app state event {} unreadable: {e}", event.id);
+                        continue;
+                    }
+                };
+                for change in store.merge_remote(&shard).await {
+                    if tx.send(change).await.is_err() {
+                        break;
+                    }
+                }
+            }
+            let _ = client.unsubscribe(sub_id).await;
+        });
+        Ok(rx)
+    }
+
+    pub(crate) async fn update_note_state(
+        &self,
+        note_id: &EventId,
+        update: impl FnOnce(&mut NoteState, u64),
+    ) -> Result<()> {
+        let id = note_id.to_hex();
+        self.update_app_state(Some(&id), |state, now| {
+            update(state.notes.entry(id.clone()).or_default(), now)
+        })
+        .await
+    }
+
+    /// Change the state and store the shard holding `note_id`'s flags, or
+    /// the tags shard for None. When the change splits or joins the note's
+    /// digit, all its shards are stored.
+    async fn update_app_state(
+        &self,
+        note_id: Option<&str>,
+        update: impl FnOnce(&mut AppState, u64),
+    ) -> Result<()> {
+        let mut cache = self.app_state.cache.lock().await;
+        let mut state = match cache.take() {
+            Some(state) => state,
+            None => self.load_app_state().await?,
+        };
+        let before = note_id.map(|id| ShardLayout::of(&state).is_split(id));
+        update(&mut state, self.now().as_u64());
+        let layout = ShardLayout::of(&state);
+        let shards = match note_id {
+            None => vec![TAGS_SHARD.to_string()],
+            Some(id) if before != Some(layout.is_split(id)) => digit_shards(&id[..1]),
+            Some(id) => vec![layout.shard_for(id).to_string()],
+        };
+        let mut result = Ok(());
+        for shard in shards {
+            let stored = self.store_app_state(&shard, &state.shard(&shard, &layout));
+            result = result.and(stored.await);
+        }
+        *cache = Some(state);
+        result
+    }
+
+    pub(crate) fn app_state_filter(&self) -> Filter {
+        Filter::new()
+            .author(self.keys.public_key())
+            .kind(Kind::from(APP_DATA_KIND))
+            .identifiers(all_shards().iter().map(|shard| shard_d_tag(shard)))
+    }
+
+    /// Merge every stored state event; read status from the legacy local
+    /// events is folded in so upgrading doesn't lose it
+    async fn load_app_state(&self) -> Result<AppState> {
+        let database = self.client.database();
+        let events = database
+            .query(vec![self.app_state_filter()])
+            .await
+            .map_err(|e| DialogError::Database(e.to_string()))?;
+        let mut state = self.merge_state_events(events);
+
+        let legacy_filter = Filter::new()
+            .author(self.keys.public_key())
+            .kind(Kind::from(APP_DATA_KIND))
+            .identifier(LEGACY_D_TAG)
+            .limit(LEGACY_LIMIT);
+        let legacy = database
+            .query(vec![legacy_filter])
+            .await
+            .map_err(|e| DialogError::Database(e.to_string()))?;
+        for event in legacy {
+            if let Some(read) = legacy_read_status(&event) {
+                state.merge(&read);
+            }
+        }
+        Ok(state)
+    }
+
+    fn merge_state_events(&self, events: Events) -> AppState {
+        let mut state = AppState::default();
+        for (_, shard) in self.decode_state_events(events) {
+            state.merge(&shard);
+        }
+        state
+    }
+
+    /// Readable state events in the order given (newest first from
+    /// [`Events`]), with the shard each was stored under
+    fn decode_state_events(&self, events: Events) -> Vec<(String, AppState)> {
+        let prefix = format!("{APP_STATE_D_TAG}:");
+        let mut decoded = Vec::new();
+        for event in events {
+            self.app_state
+                .last_saved
+                .fetch_max(event.created_at.as_u64(), Ordering::SeqCst);
+            let shard = event
+                .tags
+                .identifier()
+                .and_then(|d| d.strip_prefix(&prefix))
+                .unwrap_or_default()
+                .to_string();
+            match decode_state_event(&self.keys, &event) {
+                Ok(state) => decoded.push((shard, state)),
+                Err(e) => eprintln!("[lib] app state event {} unreadable: {e}", event.id),
+            }
+        }
+        decoded
+    }
+
+    /// Save one shard locally, publishing it too when relay sync is on
+    async fn store_app_state(&self, shard: &str, state: &AppState) -> Result<()> {
+        let json =
+            serde_json::to_string(state).map_err(|e| DialogError::Database(e.to_string()))?;
+        let encrypted = nip44::encrypt(
+            self.keys.secret_key(),
+            &self.keys.public_key(),
+            json,
+            nip44::Version::default(),
+        )?;
+
+        let now = self.now().as_u64();
+        let previous = self.app_state.last_saved.load(Ordering::SeqCst);
+        let created_at = now.max(previous + 1);
+        self.app_state
+            .last_saved
+            .fetch_max(created_at, Ordering::SeqCst);
+
+        let event = EventBuilder::new(Kind::from(APP_DATA_KIND), encrypted)
+            .tag(Tag::identifier(shard_d_tag(shard)))
+            .tag(self.device_tag()?)
+            .custom_created_at(Timestamp::from(created_at))
+            .sign(&self.keys)
+            .await?;
+
+        if !self.app_state_sync_enabled() {
+            // Local only: never leaves this device
+            return self
+                .client
+                .database()
+                .save_event(&event)
+                .await
+                .map(|_| ())
+                .map_err(|e| DialogError::Database(e.to_string()));
+        }
+
+        match self.publish(event).await {
+            Ok(_) => Ok(()),
+            // Already saved locally; the next sync publishes it again
+            Err(DialogError::NotPublished(reason)) => {
+                eprintln!("[lib] app state not published: {reason}");
+                Ok(())
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+fn decode_state_event(keys: &Keys, event: &Event) -> std::result::Result<AppState, String> {
+    let json = nip44::decrypt(keys.secret_key(), &keys.public_key(), &event.content)
+        .map_err(|e| e.to_string())?;
+    serde_json::from_str(&json).map_err(|e| e.to_string())
+}
+
+fn legacy_read_status(event: &Event) -> Option<AppState> {
+    let data: serde_json::Value = serde_json::from_str(&event.content).ok()?;
+    if data["type"] != "read_status" {
+        return None;
+    }
+    let note_id = data["note_id"].as_str()?.to_string();
+    let read = Lww::new(
+        data["is_read"].as_bool()?,
+        data["timestamp"]
+            .as_u64()
+            .unwrap_or(event.created_at.as_u64()),
+    );
+    let mut state = AppState::default();
+    state.notes.insert(
+        note_id,
+        NoteState {
+            read: Some(read),
+            ..Default::default()
+        },
+    );
+    Some(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::memory_dialog;
+
+    fn note_id(n: u8) -> EventId {
+        EventId::from_byte_array([n; 32])
+    }
+
+    fn with_read(id: &EventId, read: bool, at: u64) -> AppState {
+        let mut state = AppState::default();
+        state.notes.insert(
+            id.to_hex(),
+            NoteState {
+                read: Some(Lww::new(read, at)),
+                ..Default::default()
+            },
+        );
+        state
+    }
+
+    #[test]
+    fn test_merge_last_write_wins() {
+        let id = note_id(1);
+        let mut phone = with_read(&id, true, 200);
+        let laptop = with_read(&id, false, 100);
+
+        assert!(!phone.merge(&laptop), "older write must not win");
+        assert!(phone.note(&id).is_read());
+
+        let mut laptop = laptop;
+        assert!(laptop.merge(&phone));
+        assert_eq!(laptop, phone);
+    }
+
+    #[test]
+    fn test_merge_ties_converge() {
+        let id = note_id(2);
+        let mut a = with_read(&id, true, 100);
+        let mut b = with_read(&id, false, 100);
+        let a_before = a.clone();
+        a.merge(&b);
+        b.merge(&a_before);
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn test_shard_splits_by_note_id() {
+        let mut state = with_read(&note_id(0x11), true, 1);
+        state.merge(&with_read(&note_id(0xab), true, 1));
+        state
+            .tags
+            .insert("work".into(), Lww::new(TagMetadata::default(), 1));
+
+        let layout = ShardLayout::of(&state);
+        let shard = state.shard(layout.shard_for(&note_id(0xab).to_hex()), &layout);
+        assert_eq!(shard.notes.len(), 1);
+        assert!(shard.tags.is_empty());
+        assert_eq!(state.shard(TAGS_SHARD, &layout).tags.len(), 1);
+    }
+
+    /// A note id under the `a` digit
+    fn a_note(n: u16) -> EventId {
+        let mut bytes = [0; 32];
+        bytes[0] = 0xa0 | (n % 16) as u8;
+        bytes[1..3].copy_from_slice(&n.to_be_bytes());
+        EventId::from_byte_array(bytes)
+    }
+
+    fn flagged(state: &mut AppState, id: &EventId) {
+        let at = 1_700_000_000;
+        state.notes.insert(
+            id.to_hex(),
+            NoteState {
+                read: Some(Lww::new(true, at)),
+                pinned: Some(Lww::new(true, at)),
+                viewed: Some(Lww::new(at, at)),
+                ..Default::default()
+            },
+        );
+    }
+
+    #[test]
+    fn test_large_digit_splits_below_nip44_limit() {
+        let mut state = AppState::default();
+        for n in 0..4000 {
+            flagged(&mut state, &a_note(n));
+        }
+        flagged(&mut state, &note_id(0x11));
+
+        let layout = ShardLayout::of(&state);
+        assert!(layout.is_split("a0"));
+        assert!(!layout.is_split("11"));
+        assert!(state.shard("a", &layout).notes.is_empty());
+        let mut stored = 0;
+        for shard in all_shards() {
+            let part = state.shard(&shard, &layout);
+            assert!(serde_json::to_string(&part).unwrap().len() < 65535);
+            stored += part.notes.len();
+        }
+        assert_eq!(stored, state.notes.len());
+    }
+
+    #[tokio::test]
+    async fn test_split_clears_the_parent_shard() {
+        let dialog = memory_dialog();
+        // Fill the digit up to just below the split
+        let mut state = AppState::default();
+        let mut n = 0;
+        while !ShardLayout::of(&state).is_split("a") {
+            flagged(&mut state, &a_note(n));
+            n += 1;
+        }
+        let last = a_note(n - 1);
+        state.notes.remove(&last.to_hex());
+        let layout = ShardLayout::of(&state);
+        dialog
+            .store_app_state("a", &state.shard("a", &layout))
+            .await
+            .unwrap();
+        *dialog.app_state.cache.lock().await = Some(state);
+
+        dialog.set_pinned(&last, true).await.unwrap();
+
+        let events = dialog
+            .client
+            .database()
+            .query(vec![dialog.app_state_filter()])
+            .await
+            .unwrap();
+        let shards: BTreeMap<String, AppState> =
+            dialog.decode_state_events(events).into_iter().collect();
+        assert!(shards["a"].notes.is_empty());
+        let stored: usize = shards.values().map(|shard| shard.notes.len()).sum();
+        assert_eq!(stored, n as usize);
+        assert!(dialog.note_state(&last).await.unwrap().is_pinned());
+    }
+
+    #[tokio::test]
+    async fn test_state_persists_locally() {
+        let dialog = memory_dialog();
+        let id = note_id(3);
+        dialog.set_pinned(&id, true).await.unwrap();
+        dialog.mark_as_read(&id).await.unwrap();
+        dialog.set_archived(&id, true).await.unwrap();
+        dialog.set_archived(&id, false).await.unwrap();
+
+        // Drop the cache so state comes back from the database
+        *dialog.app_state.cache.lock().await = None;
+        let state = dialog.note_state(&id).await.unwrap();
+        assert!(state.is_read() && state.is_pinned() && !state.is_archived());
+    }
+
+    #[tokio::test]
+    async fn test_remote_changes_are_reported_once() {
+        let dialog = memory_dialog();
+        let id = note_id(5);
+        let far_future = Timestamp::now().as_u64() + 1000;
+        let remote = with_read(&id, true, far_future);
+        assert!(dialog.app_state.merge_remote(&remote).await.is_empty());
+
+        dialog.set_pinned(&id, true).await.unwrap();
+        let changes = dialog.app_state.merge_remote(&remote).await;
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].note_id, id);
+        assert!(changes[0].state.is_read() && changes[0].state.is_pinned());
+        assert!(dialog.note_state(&id).await.unwrap().is_read());
+        assert!(dialog.app_state.merge_remote(&remote).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_legacy_read_status_is_imported() {
+        let dialog = memory_dialog();
+        let id = note_id(4);
+        let content = serde_json::json!({
+            "type": "read_status",
+            "note_id": id.to_hex(),
+            "is_read": true,
+            "timestamp": 1_700_000_000u64,
+        })
+        .to_string();
+        let legacy = EventBuilder::new(Kind::from(APP_DATA_KIND), content)
+            .tag(Tag::identifier(LEGACY_D_TAG))
+            .sign_with_keys(&dialog.keys)
+            .unwrap();
+        dialog.client.database().save_event(&legacy).await.unwrap();
+
+        assert!(dialog.get_read_status(&id).await);
+    }
+}
@@ -0,0 +1,21 @@
+use serde::{Deserialize, Serialize};
+
+/// A file stored outside the note, e.g. a recording uploaded to a
+/// Blossom or NIP-96 server. The note only keeps this reference.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Attachment {
+    pub url: String,
+    /// MIME type, e.g. `audio/m4a`
+    pub mime_type: String,
+    /// Size in bytes
+    pub size: u64,
+    /// Hex SHA-256 of the file, if the upload reported one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+}
+
+impl Attachment {
+    pub fn is_audio(&self) -> bool {
+        self.mime_type.starts_with("audio/")
+    }
+}
@@ -0,0 +1,366 @@
+//! Attachment files kept on this device. Files are stored under their
+//! SHA-256, so one shared by several notes is stored once. Notes marked
+//! "keep offline" hold on to their files; the rest are evicted, least
+//! recently used first, once the cache goes over its budget. The choices
+//! are per device and never synced.
+
+use crate::enrich::http_client;
+use crate::file_store::{write_atomic, JsonStore};
+use crate::{Attachment, Dialog, DialogError, Result};
+use nostr_sdk::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Directory of cached files inside the profile's data directory
+pub(crate) const ATTACHMENT_CACHE_DIR: &str = "attachments";
+const INDEX_FILE: &str = "index.json";
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// What [`Dialog::attachment_cache_stats`] reports
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AttachmentCacheStats {
+    pub files: usize,
+    pub bytes: u64,
+    /// Files of notes kept offline, which are never evicted
+    pub pinned_files: usize,
+    pub pinned_bytes: u64,
+    /// None keeps every file
+    pub budget: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedFile {
+    size: u64,
+    last_used: Timestamp,
+    /// Notes the file is attached to
+    notes: BTreeSet<EventId>,
+    /// Where it was downloaded from, for attachments without a sha256
+    urls: BTreeSet<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct CacheIndex {
+    #[serde(default)]
+    files: BTreeMap<String, CachedFile>,
+    #[serde(default)]
+    keep_offline: BTreeSet<EventId>,
+    #[serde(default)]
+    budget: Option<u64>,
+}
+
+impl CacheIndex {
+    fn is_pinned(&self, file: &CachedFile) -> bool {
+        !file.notes.is_disjoint(&self.keep_offline)
+    }
+
+    fn hash_for(&self, attachment: &Attachment) -> Option<String> {
+        match &attachment.sha256 {
+            Some(hash) => Some(hash.to_lowercase()),
+            None => self
+                .files
+                .iter()
+                .find(|(_, file)| file.urls.contains(&attachment.url))
+                .map(|(hash, _)| hash.clone()),
+        }
+    }
+
+    /// Drop unpinned files, least recently used first, until the cache
+    /// fits the budget. Returns their hashes.
+    fn evict(&mut self) -> Vec<String> {
+        let Some(budget) = self.budget else {
+            return Vec::new();
+        };
+        let mut total: u64 = self.files.values().map(|file| file.size).sum();
+        let mut candidates: Vec<(Timestamp, String, u64)> = self
+            .files
+            .iter()
+            .filter(|(_, file)| !self.is_pinned(file))
+            .map(|(hash, file)| (file.last_used, hash.clone(), file.size))
+            .collect();
+        candidates.sort();
+        let mut evicted = Vec::new();
+        for (_, hash, size) in candidates {
+            if total <= budget {
+                break;
+            }
+            self.files.remove(&hash);
+            total -= size;
+            evicted.push(hash);
+        }
+        evicted
+    }
+}
+
+/// The cache on disk in the data directory, or in memory for tests
+#[derive(Debug, Default)]
+pub(crate) struct AttachmentCache {
+    dir: Option<PathBuf>,
+    index: JsonStore<CacheIndex>,
+    memory: Mutex<HashMap<String, Vec<u8>>>,
+}
+
+impl AttachmentCache {
+    pub(crate) fn at(dir: PathBuf) -> Self {
+        Self {
+            index: JsonStore::at(dir.join(INDEX_FILE)),
+            dir: Some(dir),
+            ..Default::default()
+        }
+    }
+
+    /// Run `f` on the index and save it, then delete the files it evicted
+    fn update<T>(&self, f: impl FnOnce(&mut CacheIndex) -> T) -> Result<T> {
+        let (value, evicted) = self.index.update(|index| {
+            let value = f(index);
+            ((value, index.evict()), true)
+        })?;
+        for hash in &evicted {
+            self.remove_file(hash);
+        }
+        if !evicted.is_empty() {
+            eprintln!("[lib] attachment cache: evicted {} files", evicted.len());
+        }
+        Ok(value)
+    }
+
+    /// Drop index entries whose file is gone. Returns how many.
+    pub(crate) fn prune_missing(&self) -> Result<usize> {
+        let exists = |hash: &str| match &self.dir {
+            Some(dir) => dir.join(hash).is_file(),
+            None => self.memory.lock().unwrap().contains_key(hash),
+        };
+        self.update(|index| {
+            let before = index.files.len();
+            index.files.retain(|hash, _| exists(hash));
+            before - index.files.len()
+        })
+    }
+
+    fn write_file(&self, hash: &str, bytes: &[u8]) -> Result<()> {
+        match &self.dir {
+            Some(dir) => {
+                fs::create_dir_all(dir)?;
+                write_atomic(&dir.join(hash), bytes)?;
+            }
+            None => {
+                self.memory
+                    .lock()
+                    .unwrap()
+                    .insert(hash.to_string(), bytes.to_vec());
+            }
+        }
+        Ok(())
+    }
+
+    fn read_file(&self, hash: &str) -> Result<Option<Vec<u8>>> {
+        match &self.dir {
+            Some(dir) => match fs::read(dir.join(hash)) {
+                Ok(bytes) => Ok(Some(bytes)),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+                Err(e) => Err(e.into()),
+            },
+            None => Ok(self.memory.lock().unwrap().get(hash).cloned()),
+        }
+    }
+
+    fn remove_file(&self, hash: &str) {
+        match &self.dir {
+            Some(dir) => {
+                if let Err(e) = fs::remove_file(dir.join(hash)) {
+                    eprintln!("[lib] attachment cache: couldn't remove {hash}: {e}");
+                }
+            }
+            None => {
+                self.memory.lock().unwrap().remove(hash);
+            }
+        }
+    }
+}
+
+impl Dialog {
+    /// Store `bytes` as the file of `note_id`'s attachment, e.g. right
+    /// after recording or uploading it. Fails if the attachment names a
+    /// sha256 the bytes don't have. Returns the file's hash.
+    pub async fn cache_attachment(
+        &self,
+        note_id: &EventId,
+        attachment: &Attachment,
+        bytes: &[u8],
+    ) -> Result<String> {
+        let hash = hashes::sha256::Hash::hash(bytes).to_string();
+        if let Some(expected) = &attachment.sha256 {
+            if !expected.eq_ignore_ascii_case(&hash) {
+                return Err(DialogError::Http(format!(
+                    "{} doesn't match its sha256",
+                    attachment.url
+                )));
+            }
+        }
+        self.attachment_cache.write_file(&hash, bytes)?;
+        let now = self.now();
+        self.attachment_cache.update(|index| {
+            let file = index
+                .files
+                .entry(hash.clone())
+                .or_insert_with(|| CachedFile {
+                    size: bytes.len() as u64,
+                    last_used: now,
+                    notes: BTreeSet::new(),
+                    urls: BTreeSet::new(),
+                });
+            file.last_used = now;
+            file.notes.insert(*note_id);
+            file.urls.insert(attachment.url.clone());
+        })?;
+        Ok(hash)
+    }
+
+    /// The attachment's file if it is on this device
+    pub async fn cached_attachment(&self, attachment: &Attachment) -> Result<Option<Vec<u8>>> {
+        let now = self.now();
+        let hash = self.attachment_cache.update(|index| {
+            let hash = index.hash_for(attachment)?;
+            index.files.get_mut(&hash)?.last_used = now;
+            Some(hash)
+        })?;
+        match hash {
+            Some(hash) => self.attachment_cache.read_file(&hash),
+            None => Ok(None),
+        }
+    }
+
+    /// The attachment's file, downloaded and cached unless it already is.
+    /// Downloads go through the proxy in [`Dialog::enrich_settings`].
+    pub async fn fetch_attachment(
+        &self,
+        note_id: &EventId,
+        attachment: &Attachment,
+    ) -> Result<Vec<u8>> {
+        if let Some(bytes) = self.cached_attachment(attachment).await? {
+            return Ok(bytes);
+        }
+        let http = http_client(&self.enrich_settings(), DOWNLOAD_TIMEOUT)?;
+        let bytes = http
+            .get(&attachment.url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| DialogError::Http(e.to_string()))?
+            .bytes()
+            .await
+            .map_err(|e| DialogError::Http(e.to_string()))?;
+        self.cache_attachment(note_id, attachment, &bytes).await?;
+        Ok(bytes.to_vec())
+    }
+
+    /// Keep a note's attachments on this device whatever the budget,
+    /// downloading them now; or let them be evicted again
+    pub async fn set_keep_offline(&self, note_id: &EventId, keep: bool) -> Result<()> {
+        self.attachment_cache.update(|index| {
+            if keep {
+                index.keep_offline.insert(*note_id);
+            } else {
+                index.keep_offline.remove(note_id);
+            }
+        })?;
+        if keep {
+            let audio = self.voice_data(note_id).await?.and_then(|data| data.audio);
+            if let Some(audio) = audio {
+                self.fetch_attachment(note_id, &audio).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Notes whose attachments are kept offline
+    pub fn kept_offline(&self) -> Result<Vec<EventId>> {
+        self.attachment_cache
+            .update(|index| index.keep_offline.iter().copied().collect())
+    }
+
+    /// Keep unpinned files under `bytes`, evicting the least recently used
+    /// right away and whenever a file is added. None keeps everything.
+    pub fn set_attachment_cache_budget(&self, bytes: Option<u64>) -> Result<()> {
+        self.attachment_cache.update(|index| index.budget = bytes)
+    }
+
+    pub fn attachment_cache_stats(&self) -> Result<AttachmentCacheStats> {
+        self.attachment_cache.update(|index| {
+            let mut stats = AttachmentCacheStats {
+                budget: index.budget,
+                ..Default::default()
+            };
+            for file in index.files.values() {
+                stats.files += 1;
+                stats.bytes += file.size;
+                if index.is_pinned(file) {
+                    stats.pinned_files += 1;
+                    stats.pinned_bytes += file.size;
+                }
+            }
+            stats
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::memory_dialog;
+
+    fn attachment(url: &str) -> Attachment {
+        Attachment {
+            url: url.into(),
+            mime_type: "audio/m4a".into(),
+            size: 4,
+            sha256: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unpinned_files_are_evicted() {
+        let dialog = memory_dialog();
+        let kept = dialog.save_note("Voice memo").await.unwrap().id;
+        let other = dialog.save_note("Another memo").await.unwrap().id;
+        let first = attachment("https://example.com/a.m4a");
+        let second = attachment("https://example.com/b.m4a");
+
+        dialog
+            .cache_attachment(&kept, &first, b"aaaa")
+            .await
+            .unwrap();
+        dialog
+            .cache_attachment(&other, &second, b"bbbb")
+            .await
+            .unwrap();
+        // Same bytes under another url are stored once
+        let copy = attachment("https://mirror.example.com/a.m4a");
+        dialog
+            .cache_attachment(&other, &copy, b"aaaa")
+            .await
+            .unwrap();
+        assert_eq!(dialog.attachment_cache_stats().unwrap().bytes, 8);
+
+        let mut wrong = attachment("https://example.com/c.m4a");
+        wrong.sha256 = Some("00".repeat(32));
+        assert!(dialog
+            .cache_attachment(&kept, &wrong, b"cccc")
+            .await
+            .is_err());
+
+        dialog.set_keep_offline(&kept, true).await.unwrap();
+        dialog.set_attachment_cache_budget(Some(0)).unwrap();
+        let stats = dialog.attachment_cache_stats().unwrap();
+        assert_eq!((stats.files, stats.pinned_files), (1, 1));
+        assert!(dialog.cached_attachment(&first).await.unwrap().is_some());
+        assert!(dialog.cached_attachment(&second).await.unwrap().is_none());
+
+        dialog.set_keep_offline(&kept, false).await.unwrap();
+        assert_eq!(dialog.attachment_cache_stats().unwrap().files, 0);
+        assert!(dialog.kept_offline().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,228 @@
+use crate::file_store::read_jsonl;
+use crate::{Dialog, DialogError, Result};
+use nostr_sdk::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::ops::RangeBounds;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// File name of the audit log inside the profile's data directory
+pub(crate) const AUDIT_LOG_FILE: &str = "audit.jsonl";
+/// Size at which the log is moved aside to `audit.jsonl.1`, replacing the
+/// previous one, so at most about twice this is kept
+pub const AUDIT_LOG_MAX_BYTES: u64 = 1024 * 1024;
+
+/// A local change recorded in the audit log
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    /// A note was written, whether or not a relay took it
+    Create,
+    SetRead,
+    SetPinned,
+    SetArchived,
+    SetDue,
+    /// A note's task status was set or cleared
+    SetTask,
+    SetTranscript,
+    /// A public copy was posted
+    PublishPublic,
+    /// A note was republished under a new kind
+    Migrate,
+    /// A note was replaced by a passphrase-locked copy
+    Lock,
+    /// A note was replaced by a copy with tags added or removed
+    Retag,
+    /// A note was replaced by a copy with new text
+    Edit,
+    /// A note was deleted on request
+    Delete,
+    /// A note was deleted by the retention policy
+    Purge,
+    /// A sync pulled or pushed notes, or merged another device's state
+    Sync,
+    /// Local indexes and caches were rebuilt from the stored events
+    Recover,
+}
+
+/// One line of the audit log
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub at: Timestamp,
+    pub action: AuditAction,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note_id: Option<EventId>,
+    /// The new value or a short summary, e.g. `true` or `received 3, sent 1`
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub detail: String,
+}
+
+/// Append-only log of local mutations, one JSON object per line. Dialogs
+/// without a data directory (tests) keep it in memory.
+#[derive(Debug, Default)]
+pub(crate) struct AuditLog {
+    path: Option<PathBuf>,
+    memory: Mutex<Vec<AuditEntry>>,
+    max_bytes: u64,
+}
+
+impl AuditLog {
+    pub(crate) fn at(path: PathBuf) -> Self {
+        Self {
+            path: Some(path),
+            memory: Mutex::default(),
+            max_bytes: AUDIT_LOG_MAX_BYTES,
+        }
+    }
+
+    fn append(&self, entry: AuditEntry) -> Result<()> {
+        // The lock also keeps concurrent appends from interleaving
+        let mut memory = self.memory.lock().unwrap();
+        let Some(path) = &self.path else {
+            memory.push(entry);
+            return Ok(());
+        };
+        let mut line =
+            serde_json::to_string(&entry).map_err(|e| DialogError::Database(e.to_string()))?;
+        line.push('\n');
+        if fs::metadata(path).is_ok_and(|metadata| metadata.len() >= self.max_bytes) {
+            fs::rename(path, rotated_path(path))?;
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        file.write_all(line.as_bytes())?;
+        Ok(())
+    }
+
+    fn entries(&self) -> Result<Vec<AuditEntry>> {
+        let memory = self.memory.lock().unwrap();
+        let Some(path) = &self.path else {
+            return Ok(memory.clone());
+        };
+        let mut entries = read_jsonl(&rotated_path(path), "audit")?;
+        entries.extend(read_jsonl(path, "audit")?);
+        Ok(entries)
+    }
+}
+
+fn rotated_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".1");
+    path.with_file_name(name)
+}
+
+impl Dialog {
+    /// Audit entries recorded in `range`, oldest first
+    pub fn audit_log(&self, range: impl RangeBounds<Timestamp>) -> Result<Vec<AuditEntry>> {
+        let mut entries: Vec<AuditEntry> = self
+            .audit
+            .entries()?
+            .into_iter()
+            .filter(|entry| range.contains(&entry.at))
+            .collect();
+        // Appends are in order already; a stable sort only fixes clock jumps
+        entries.sort_by_key(|entry| entry.at);
+        Ok(entries)
+    }
+
+    /// Record a mutation. Failing to write the log never fails the change
+    /// itself.
+    pub(crate) fn audit(
+        &self,
+        action: AuditAction,
+        note_id: Option<&EventId>,
+        detail: impl Into<String>,
+    ) {
+        let entry = AuditEntry {
+            at: self.now(),
+            action,
+            note_id: note_id.copied(),
+            detail: detail.into(),
+        };
+        if let Err(e) = self.audit.append(entry) {
+            eprintln!("[lib] audit: could not record {action:?}: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::memory_dialog;
+
+    #[tokio::test]
+    async fn test_mutations_are_audited() {
+        let dialog = memory_dialog();
+        let note = dialog.save_note("Audit me").await.unwrap();
+        dialog.set_pinned(&note.id, true).await.unwrap();
+        dialog.mark_as_read(&note.id).await.unwrap();
+
+        let entries = dialog.audit_log(..).unwrap();
+        let actions: Vec<AuditAction> = entries.iter().map(|e| e.action).collect();
+        assert_eq!(
+            actions,
+            vec![
+                AuditAction::Create,
+                AuditAction::SetPinned,
+                AuditAction::SetRead
+            ]
+        );
+        assert!(entries.iter().all(|e| e.note_id == Some(note.id)));
+        assert_eq!(entries[1].detail, "true");
+
+        let future = Timestamp::from(Timestamp::now().as_u64() + 60);
+        assert!(dialog.audit_log(future..).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_audit_log_file_round_trip() {
+        let path = std::env::temp_dir().join(format!(
+            "dialog-audit-{}-{AUDIT_LOG_FILE}",
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&path);
+        let log = AuditLog::at(path.clone());
+        let entry = AuditEntry {
+            at: Timestamp::from(1_700_000_000),
+            action: AuditAction::Sync,
+            note_id: None,
+            detail: "received 3, sent 1".into(),
+        };
+        log.append(entry.clone()).unwrap();
+        log.append(entry.clone()).unwrap();
+        assert_eq!(log.entries().unwrap(), vec![entry.clone(), entry]);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_audit_log_rotates() {
+        let path = std::env::temp_dir().join(format!(
+            "dialog-audit-rotate-{}-{AUDIT_LOG_FILE}",
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&path);
+        let _ = fs::remove_file(rotated_path(&path));
+        let mut log = AuditLog::at(path.clone());
+        log.max_bytes = 1;
+        for at in 1..=3 {
+            log.append(AuditEntry {
+                at: Timestamp::from(at),
+                action: AuditAction::Edit,
+                note_id: None,
+                detail: String::new(),
+            })
+            .unwrap();
+        }
+        // The oldest entry went with the first rotated file
+        let kept: Vec<u64> = log
+            .entries()
+            .unwrap()
+            .iter()
+            .map(|entry| entry.at.as_u64())
+            .collect();
+        assert_eq!(kept, vec![2, 3]);
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(rotated_path(&path)).unwrap();
+    }
+}
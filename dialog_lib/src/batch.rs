@@ -0,0 +1,335 @@
+use crate::audit::AuditAction;
+use crate::lock::is_locked_text;
+use crate::note::local_note;
+use crate::privacy::open_note;
+use crate::{Dialog, DialogError, Note, Result};
+use nostr_sdk::prelude::*;
+
+enum Staged {
+    Create {
+        text: String,
+        created_at: Option<Timestamp>,
+    },
+    Edit {
+        note_id: EventId,
+        text: String,
+    },
+    Delete(EventId),
+}
+
+/// Changes staged inside [`Dialog::with_batch`]. Nothing happens until
+/// the closure returns.
+#[derive(Default)]
+pub struct Batch {
+    staged: Vec<Staged>,
+}
+
+impl Batch {
+    /// A new note, like [`Dialog::create_note`]; `created_at` backdates it
+    pub fn create(&mut self, text: impl Into<String>, created_at: Option<Timestamp>) {
+        self.staged.push(Staged::Create {
+            text: text.into(),
+            created_at,
+        });
+    }
+
+    /// Replace a stored note's text. The note is re-issued under a new id
+    /// keeping its date, flags and voice data, as retagging does.
+    pub fn edit(&mut self, note_id: EventId, text: impl Into<String>) {
+        self.staged.push(Staged::Edit {
+            note_id,
+            text: text.into(),
+        });
+    }
+
+    /// Remove a note locally and ask relays to delete it
+    pub fn delete(&mut self, note_id: EventId) {
+        self.staged.push(Staged::Delete(note_id));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.staged.is_empty()
+    }
+}
+
+/// What [`Dialog::with_batch`] committed
+#[derive(Debug, Default)]
+pub struct BatchReport {
+    pub created: Vec<Note>,
+    /// The new copies of edited notes, in staging order
+    pub edited: Vec<Note>,
+    pub deleted: Vec<EventId>,
+    /// Committed locally but not accepted by any relay; a sync sends them
+    pub unpublished: Vec<(EventId, String)>,
+}
+
+impl Dialog {
+    /// Stage creates, edits and deletes in `stage`, then commit them to
+    /// the local database together: if any note can't be built or stored,
+    /// nothing is written. Publishing follows the commit, and relay
+    /// failures don't undo it.
+    ///
+    /// ```ignore
+    /// let report = dialog
+    ///     .with_batch(|batch| {
+    ///         batch.create("Imported #keep", Some(created_at));
+    ///         batch.edit(note_id, "Fixed typo");
+    ///         batch.delete(duplicate_id);
+    ///     })
+    ///     .await?;
+    /// ```
+    pub async fn with_batch<F>(&self, stage: F) -> Result<BatchReport>
+    where
+        F: FnOnce(&mut Batch),
+    {
+        self.run_batch(stage, true).await
+    }
+
+    /// [`Dialog::with_batch`] without contacting relays, e.g. while
+    /// offline. Created and edited notes are all listed as unpublished;
+    /// send them later with [`Dialog::publish_stored_note`]. Relays aren't
+    /// asked to delete anything.
+    pub async fn with_batch_offline<F>(&self, stage: F) -> Result<BatchReport>
+    where
+        F: FnOnce(&mut Batch),
+    {
+        self.run_batch(stage, false).await
+    }
+
+    async fn run_batch<F>(&self, stage: F, publish: bool) -> Result<BatchReport>
+    where
+        F: FnOnce(&mut Batch),
+    {
+        let mut batch = Batch::default();
+        stage(&mut batch);
+
+        // Build and sign everything first; errors here leave no trace
+        let mut events = Vec::new();
+        let mut notes = Vec::new();
+        let mut edits = Vec::new();
+        let mut deletes = Vec::new();
+        for staged in batch.staged {
+            match staged {
+                Staged::Create { text, created_at } => {
+                    let text = self.prepare_text(&text).await;
+                    let event = match created_at {
+                        Some(created_at) => self.build_note_event_at(&text, created_at).await?,
+                        None => self.build_note_event(&text).await?,
+                    };
+                    notes.push(local_note(&self.keys, &event, &text));
+                    events.push(event);
+                }
+                Staged::Edit { note_id, text } => {
+                    let original = open_note(&self.keys, &self.note_event(&note_id).await?)?;
+                    if is_locked_text(&original.text) {
+                        return Err(DialogError::Lock("locked notes can't be edited".into()));
+                    }
+                    let event = self
+                        .build_note_event_tagged(
+                            &text,
+                            original.created_at,
+                            [self.supersede_tag(&note_id)],
+                        )
+                        .await?;
+                    edits.push((note_id, notes.len()));
+                    notes.push(local_note(&self.keys, &event, &text));
+                    events.push(event);
+                }
+                Staged::Delete(note_id) => deletes.push(note_id),
+            }
+        }
+
+        self.commit_batch(&events, &deletes).await?;
+        eprintln!(
+            "[lib] batch: committed {} notes, {} deletions",
+            events.len(),
+            deletes.len()
+        );
+
+        let mut report = BatchReport {
+            deleted: deletes.clone(),
+            ..Default::default()
+        };
+        for (original, index) in &edits {
+            let copy = &notes[*index];
+            self.copy_note_state(original, &copy.id).await?;
+            if let Some(data) = self.voice_data(original).await? {
+                self.store_voice_data(&copy.id, &data, None).await?;
+            }
+            self.audit(
+                AuditAction::Edit,
+                Some(&copy.id),
+                format!("replaces {original}"),
+            );
+        }
+        for note_id in &deletes {
+            self.audit(AuditAction::Delete, Some(note_id), "batch");
+            if !publish {
+                continue;
+            }
+            if let Err(e) = self.client.delete_event(*note_id).await {
+                eprintln!("[lib] batch: deletion request for {note_id} not sent: {e}");
+            }
+        }
+
+        for (index, (event, note)) in events.into_iter().zip(notes).enumerate() {
+            let note = if publish {
+                match self.publish_note(event, &note.text).await {
+                    Ok(published) => published,
+                    Err(e) => {
+                        report.unpublished.push((note.id, e.to_string()));
+                        note
+                    }
+                }
+            } else {
+                self.audit(AuditAction::Create, Some(&note.id), "saved locally");
+                report.unpublished.push((note.id, "offline".to_string()));
+                note
+            };
+            if edits.iter().any(|(_, i)| *i == index) {
+                // Read back so the copy shows the flags it took over
+                report
+                    .edited
+                    .push(self.get_note(&note.id).await?.unwrap_or(note));
+            } else {
+                report.created.push(note);
+            }
+        }
+        Ok(report)
+    }
+
+    /// Remove `deletes` and save `events` locally, or neither. The store
+    /// only records removals until it's next opened, so they can be undone
+    /// if a save fails; nothing relies on the backend deleting events.
+    async fn commit_batch(&self, events: &[Event], deletes: &[EventId]) -> Result<()> {
+        let database = self.client.database();
+        if !deletes.is_empty() {
+            database
+                .delete(Filter::new().ids(deletes.to_vec()))
+                .await
+                .map_err(|e| DialogError::Database(e.to_string()))?;
+        }
+        let mut saved = Vec::new();
+        for event in events {
+            if let Err(e) = database.save_event(event).await {
+                self.rollback_batch(deletes, saved).await;
+                return Err(DialogError::Database(e.to_string()));
+            }
+            saved.push(event.id);
+        }
+        Ok(())
+    }
+
+    /// Bring back the notes a failed commit removed and hide the new events
+    /// it saved, which restores the old state
+    async fn rollback_batch(&self, deletes: &[EventId], saved: Vec<EventId>) {
+        if let Err(e) = self.local_store.restore(deletes) {
+            eprintln!("[lib] batch: rollback failed: {e}");
+        }
+        if saved.is_empty() {
+            return;
+        }
+        if let Err(e) = self.local_store.delete(Filter::new().ids(saved)).await {
+            eprintln!("[lib] batch: rollback failed: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::memory_dialog;
+
+    #[tokio::test]
+    async fn test_batch_commits_all_or_nothing() {
+        let dialog = memory_dialog();
+        let keep = dialog.save_note("Keep me").await.unwrap();
+        let typo = dialog.save_note("Teh plan").await.unwrap();
+        let drop = dialog.save_note("Duplicate").await.unwrap();
+        dialog.set_pinned(&typo.id, true).await.unwrap();
+
+        // One edit targets a missing note, so nothing is written
+        let missing = EventId::all_zeros();
+        let failed = dialog
+            .with_batch(|batch| {
+                batch.create("Imported", None);
+                batch.edit(missing, "Nope");
+                batch.delete(drop.id);
+            })
+            .await;
+        assert!(matches!(failed, Err(DialogError::NoteNotFound(_))));
+        assert_eq!(dialog.list_notes(10).await.unwrap().len(), 3);
+
+        // No relays: committed locally, publishing reported as failed
+        let report = dialog
+            .with_batch(|batch| {
+                batch.create("Imported #keep", Some(Timestamp::from(1_600_000_000)));
+                batch.edit(typo.id, "The plan");
+                batch.delete(drop.id);
+            })
+            .await
+            .unwrap();
+        assert_eq!(report.created.len(), 1);
+        assert_eq!(report.edited[0].text, "The plan");
+        assert!(report.edited[0].is_pinned);
+        assert_eq!(report.deleted, vec![drop.id]);
+        assert_eq!(report.unpublished.len(), 2);
+
+        let mut texts: Vec<String> = dialog
+            .list_notes(10)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|note| note.text)
+            .collect();
+        texts.sort();
+        assert_eq!(texts, ["Imported #keep", "Keep me", "The plan"]);
+        assert!(dialog.get_note(&keep.id).await.unwrap().is_some());
+
+        let offline = dialog
+            .with_batch_offline(|batch| batch.edit(keep.id, "Keep me too"))
+            .await
+            .unwrap();
+        assert_eq!(offline.unpublished[0].0, offline.edited[0].id);
+    }
+
+    #[tokio::test]
+    async fn test_batch_on_nostrdb() {
+        let dir = std::env::temp_dir().join(format!("dialog-batch-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let keys = Keys::generate();
+        let open = || {
+            Dialog::builder()
+                .signer(keys.clone())
+                .data_dir(&dir)
+                .build()
+        };
+
+        let dialog = open().await.unwrap();
+        let duplicate = dialog.save_note("Duplicate").await.unwrap();
+        // nostrdb ingests in the background
+        while dialog.list_notes(10).await.unwrap().is_empty() {
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        dialog
+            .with_batch_offline(|batch| {
+                batch.create("Imported", None);
+                batch.delete(duplicate.id);
+            })
+            .await
+            .unwrap();
+        while dialog.list_notes(10).await.unwrap().is_empty() {
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        let notes = dialog.list_notes(10).await.unwrap();
+        assert_eq!(notes.len(), 1);
+        assert_eq!(notes[0].text, "Imported");
+        drop(dialog);
+
+        let dialog = open().await.unwrap();
+        assert!(dialog.get_note(&duplicate.id).await.unwrap().is_none());
+        assert_eq!(dialog.list_notes(10).await.unwrap().len(), 1);
+        drop(dialog);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
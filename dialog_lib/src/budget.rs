@@ -0,0 +1,351 @@
+//! Event counts per relay, for relays that cap how many events a pubkey
+//! may store
+
+use crate::account::DELETION_BATCH_SIZE;
+use crate::audit::AuditAction;
+use crate::migrate::migrated_from;
+use crate::{Dialog, DialogError, Result};
+use nostr_sdk::prelude::*;
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use tokio::sync::watch;
+use tokio::task::JoinSet;
+
+/// Share of the budget from which a relay counts as near its limit
+pub const BUDGET_WARNING_RATIO: f64 = 0.9;
+
+/// How many of this key's events a relay holds
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RelayEventCount {
+    pub url: RelayUrl,
+    pub count: usize,
+    /// Set with [`Dialog::set_event_budget`]
+    pub budget: Option<usize>,
+}
+
+impl RelayEventCount {
+    /// Within 10% of the budget, or over it
+    pub fn near_limit(&self) -> bool {
+        self.budget
+            .is_some_and(|budget| self.count as f64 >= budget as f64 * BUDGET_WARNING_RATIO)
+    }
+}
+
+/// What [`Dialog::compact_remote`] asked relays to delete
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CompactionReport {
+    /// Notes replaced by an edited, locked or retagged copy
+    pub superseded: Vec<EventId>,
+    /// Events deleted earlier that a relay still returned
+    pub tombstoned: Vec<EventId>,
+    /// Relays that accepted every deletion request
+    pub relays: Vec<RelayUrl>,
+}
+
+/// Counts from [`Dialog::count_remote_events`], kept current as events
+/// are published
+#[derive(Debug)]
+pub(crate) struct EventCounter {
+    budget: Mutex<Option<usize>>,
+    counts: Mutex<HashMap<RelayUrl, usize>>,
+    near_limit: watch::Sender<Vec<RelayEventCount>>,
+}
+
+impl Default for EventCounter {
+    fn default() -> Self {
+        Self {
+            budget: Mutex::new(None),
+            counts: Mutex::new(HashMap::new()),
+            near_limit: watch::channel(Vec::new()).0,
+        }
+    }
+}
+
+impl EventCounter {
+    fn snapshot(&self) -> Vec<RelayEventCount> {
+        let budget = *self.budget.lock().unwrap();
+        let mut counts: Vec<RelayEventCount> = self
+            .counts
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(url, count)| RelayEventCount {
+                url: url.clone(),
+                count: *count,
+                budget,
+            })
+            .collect();
+        counts.sort_by(|a, b| a.url.as_str().cmp(b.url.as_str()));
+        counts
+    }
+
+    // Tell watchers only when the set of relays near the limit changes
+    fn notify(&self) {
+        let near: Vec<RelayEventCount> = self
+            .snapshot()
+            .into_iter()
+            .filter(RelayEventCount::near_limit)
+            .collect();
+        self.near_limit.send_if_modified(|current| {
+            let changed = current
+                .iter()
+                .map(|c| &c.url)
+                .ne(near.iter().map(|c| &c.url));
+            *current = near;
+            changed
+        });
+    }
+
+    fn set(&self, url: RelayUrl, count: usize) {
+        self.counts.lock().unwrap().insert(url, count);
+        self.notify();
+    }
+
+    /// One more event on each of `urls`. Relays never counted stay unknown.
+    pub(crate) fn published<'a>(&self, urls: impl IntoIterator<Item = &'a RelayUrl>) {
+        {
+            let mut counts = self.counts.lock().unwrap();
+            for url in urls {
+                if let Some(count) = counts.get_mut(url) {
+                    *count += 1;
+                }
+            }
+        }
+        self.notify();
+    }
+
+    // `removed` events gone from each of `urls`, and the deletion request added
+    fn compacted<'a>(&self, urls: impl IntoIterator<Item = &'a RelayUrl>, removed: usize) {
+        {
+            let mut counts = self.counts.lock().unwrap();
+            for url in urls {
+                if let Some(count) = counts.get_mut(url) {
+                    *count = count.saturating_sub(removed) + 1;
+                }
+            }
+        }
+        self.notify();
+    }
+}
+
+/// Originals replaced by a copy of the same kind, and ids named by this
+/// key's deletion requests. Copies under another kind come from a kind
+/// migration; their originals stay for devices that don't read the new kind.
+fn compactable(events: &[Event]) -> (Vec<EventId>, Vec<EventId>) {
+    let kinds: HashMap<EventId, Kind> = events.iter().map(|event| (event.id, event.kind)).collect();
+    let superseded: HashSet<EventId> = events
+        .iter()
+        .filter_map(|copy| {
+            let original = migrated_from(copy)?;
+            (kinds.get(&original) == Some(&copy.kind)).then_some(original)
+        })
+        .collect();
+    let tombstoned: HashSet<EventId> = events
+        .iter()
+        .filter(|event| event.kind == Kind::EventDeletion)
+        .flat_map(|event| event.tags.iter())
+        .filter_map(|tag| match tag.as_slice() {
+            [name, id, ..] if name == "e" => EventId::from_hex(id).ok(),
+            _ => None,
+        })
+        .filter(|id| !superseded.contains(id))
+        .collect();
+    let mut superseded: Vec<EventId> = superseded.into_iter().collect();
+    let mut tombstoned: Vec<EventId> = tombstoned.into_iter().collect();
+    superseded.sort();
+    tombstoned.sort();
+    (superseded, tombstoned)
+}
+
+impl Dialog {
+    /// Events each relay may hold for this key; None for no limit. Relays
+    /// within 10% of it show up in [`Dialog::watch_event_budget`].
+    pub fn set_event_budget(&self, budget: Option<usize>) {
+        *self.event_counter.budget.lock().unwrap() = budget;
+        self.event_counter.notify();
+    }
+
+    pub fn event_budget(&self) -> Option<usize> {
+        *self.event_counter.budget.lock().unwrap()
+    }
+
+    /// Counts known so far, without asking relays. Empty until
+    /// [`Dialog::count_remote_events`] has run.
+    pub fn event_counts(&self) -> Vec<RelayEventCount> {
+        self.event_counter.snapshot()
+    }
+
+    /// Receiver holding the relays within 10% of the event budget; empty
+    /// while every relay is comfortably under it. A good moment to suggest
+    /// [`Dialog::compact_remote`].
+    pub fn watch_event_budget(&self) -> watch::Receiver<Vec<RelayEventCount>> {
+        self.event_counter.near_limit.subscribe()
+    }
+
+    /// Ask every relay for all of this key's events and count them.
+    /// Relays that cap query results may report fewer than they hold.
+    /// Relays that don't answer are left out.
+    pub async fn count_remote_events(&self) -> Result<Vec<RelayEventCount>> {
+        let filter = Filter::new().author(self.keys.public_key());
+        let mut tasks = JoinSet::new();
+        for url in self.client.relays().await.into_keys() {
+            let client = self.client.clone();
+            let filter = filter.clone();
+            let timeout = self.fetch_timeout;
+            tasks.spawn(async move {
+                let result = client
+                    .fetch_events_from([url.clone()], vec![filter], Some(timeout))
+                    .await;
+                (url, result)
+            });
+        }
+        while let Some(joined) = tasks.join_next().await {
+            match joined {
+                Ok((url, Ok(events))) => self.event_counter.set(url, events.len()),
+                Ok((url, Err(e))) => eprintln!("[lib] count_remote_events: {url} failed: {e}"),
+                Err(e) => eprintln!("[lib] count_remote_events: relay task failed: {e}"),
+            }
+        }
+        Ok(self.event_counts())
+    }
+
+    /// Free up room on relays: ask them in deletion requests (NIP-09) to
+    /// drop notes that were replaced by edited copies, and events deleted
+    /// before that they still hold. Requests are split like
+    /// [`Dialog::delete_account`]'s to stay under relays' size limits.
+    /// Replaced notes are removed locally once a relay accepts the request
+    /// naming them.
+    pub async fn compact_remote(&self) -> Result<CompactionReport> {
+        let database = self.client.database();
+        let events: Vec<Event> = database
+            .query(vec![Filter::new().author(self.keys.public_key())])
+            .await
+            .map_err(|e| DialogError::Database(e.to_string()))?
+            .into_iter()
+            .collect();
+        let (superseded, deleted) = compactable(&events);
+
+        let tombstoned: Vec<EventId> = if deleted.is_empty() {
+            Vec::new()
+        } else {
+            let filter = Filter::new().author(self.keys.public_key()).ids(deleted);
+            let mut ids: Vec<EventId> = self
+                .client
+                .fetch_events(vec![filter], Some(self.fetch_timeout))
+                .await?
+                .into_iter()
+                .map(|event| event.id)
+                .collect();
+            ids.sort();
+            ids
+        };
+
+        let mut report = CompactionReport {
+            superseded,
+            tombstoned,
+            relays: Vec::new(),
+        };
+        let ids: Vec<EventId> = report
+            .superseded
+            .iter()
+            .chain(&report.tombstoned)
+            .copied()
+            .collect();
+        if ids.is_empty() {
+            return Ok(report);
+        }
+
+        let superseded: HashSet<EventId> = report.superseded.iter().copied().collect();
+        let mut accepted: HashMap<RelayUrl, usize> = HashMap::new();
+        let batches = ids.chunks(DELETION_BATCH_SIZE);
+        let batch_count = batches.len();
+        for batch in batches {
+            let request = self.deletion_request(batch).await?;
+            let output = self.publish(request).await?;
+            self.event_counter.compacted(&output.success, batch.len());
+            for url in &output.success {
+                *accepted.entry(url.clone()).or_insert(0) += 1;
+            }
+            if output.success.is_empty() {
+                continue;
+            }
+            let removed: Vec<EventId> = batch
+                .iter()
+                .filter(|id| superseded.contains(id))
+                .copied()
+                .collect();
+            if removed.is_empty() {
+                continue;
+            }
+            database
+                .delete(Filter::new().ids(removed.clone()))
+                .await
+                .map_err(|e| DialogError::Database(e.to_string()))?;
+            for note_id in &removed {
+                self.audit(AuditAction::Delete, Some(note_id), "compaction");
+            }
+        }
+        report.relays = accepted
+            .into_iter()
+            .filter(|(_, count)| *count == batch_count)
+            .map(|(url, _)| url)
+            .collect();
+        report.relays.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+
+        eprintln!(
+            "[lib] compact_remote: {} superseded, {} tombstoned, {} relays",
+            report.superseded.len(),
+            report.tombstoned.len(),
+            report.relays.len()
+        );
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::memory_dialog;
+
+    #[tokio::test]
+    async fn test_budget_warning_and_compactable() {
+        let dialog = memory_dialog();
+        let url = RelayUrl::parse("wss://relay.example.com").unwrap();
+        let mut near = dialog.watch_event_budget();
+        dialog.event_counter.set(url.clone(), 89);
+        dialog.set_event_budget(Some(100));
+        assert!(near.borrow_and_update().is_empty());
+
+        dialog.event_counter.published([&url]);
+        assert!(near.has_changed().unwrap());
+        let warning = near.borrow_and_update().clone();
+        assert_eq!(warning[0].count, 90);
+        assert!(warning[0].near_limit());
+
+        dialog.event_counter.compacted([&url], 10);
+        assert!(near.borrow_and_update().is_empty());
+        assert_eq!(dialog.event_counts()[0].count, 81);
+
+        let keys = Keys::generate();
+        let note = |kind: u16, tags: Vec<Tag>| {
+            EventBuilder::new(Kind::Custom(kind), "")
+                .tags(tags)
+                .sign_with_keys(&keys)
+                .unwrap()
+        };
+        let edited = note(1000, vec![]);
+        let edit = note(1000, vec![dialog.supersede_tag(&edited.id)]);
+        let legacy = note(1059, vec![]);
+        let migrated = note(1000, vec![dialog.supersede_tag(&legacy.id)]);
+        let gone = EventId::all_zeros();
+        let deletion = EventBuilder::new(Kind::EventDeletion, "")
+            .tags([Tag::event(gone)])
+            .sign_with_keys(&keys)
+            .unwrap();
+
+        let (superseded, tombstoned) =
+            compactable(&[edited.clone(), edit, legacy, migrated, deletion]);
+        assert_eq!(superseded, vec![edited.id]);
+        assert_eq!(tombstoned, vec![gone]);
+    }
+}
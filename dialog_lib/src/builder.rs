@@ -0,0 +1,270 @@
+use crate::file_store::JsonStore;
+use crate::local_store::{self, LocalStore};
+use crate::sync::SyncMode;
+use crate::{
+    audit, cache, devices, get_data_dir, offline_queue, payments, privacy, relay_policy, retention,
+    schema, search_index, Clock, Dialog, DialogError, KindSet, Result,
+};
+use nostr_sdk::prelude::*;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Relay queries give up after this unless configured otherwise
+pub const DEFAULT_FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Where notes are stored locally
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StoreBackend {
+    /// nostrdb in the data directory
+    #[default]
+    Ndb,
+    /// Nothing written to disk; gone when the Dialog is dropped
+    Memory,
+}
+
+/// Configures a [`Dialog`] without environment variables.
+///
+/// ```ignore
+/// let dialog = Dialog::builder()
+///     .signer(Keys::parse(nsec)?)
+///     .data_dir("/var/lib/dialog")
+///     .relay("wss://relay.damus.io")
+///     .proxy("127.0.0.1:9050".parse()?)
+///     .build()
+///     .await?;
+/// ```
+#[derive(Debug, Default)]
+pub struct DialogBuilder {
+    keys: Option<Keys>,
+    data_dir: Option<PathBuf>,
+    relays: Vec<String>,
+    proxy: Option<SocketAddr>,
+    store: StoreBackend,
+    sync_mode: Option<SyncMode>,
+    note_kind: Option<u16>,
+    connection_timeout: Option<Duration>,
+    fetch_timeout: Option<Duration>,
+    outbox: Option<PathBuf>,
+    device_name: Option<String>,
+    kind_set: Option<KindSet>,
+    defer_schema_migration: bool,
+    clock: Option<Arc<dyn Clock>>,
+}
+
+impl Dialog {
+    pub fn builder() -> DialogBuilder {
+        DialogBuilder::default()
+    }
+}
+
+impl DialogBuilder {
+    /// The key notes are signed and encrypted with. Required.
+    pub fn signer(mut self, keys: Keys) -> Self {
+        self.keys = Some(keys);
+        self
+    }
+
+    /// Directory holding one folder per key. Defaults to `DIALOG_DATA_DIR`,
+    /// then the platform's data directory.
+    pub fn data_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.data_dir = Some(dir.into());
+        self
+    }
+
+    /// Connect to `url` once built. Can be called more than once.
+    pub fn relay(mut self, url: impl Into<String>) -> Self {
+        self.relays.push(url.into());
+        self
+    }
+
+    /// SOCKS5 proxy for relay connections and link previews, e.g. Tor
+    pub fn proxy(mut self, addr: SocketAddr) -> Self {
+        self.proxy = Some(addr);
+        self
+    }
+
+    pub fn store(mut self, store: StoreBackend) -> Self {
+        self.store = store;
+        self
+    }
+
+    /// Sync every relay with `mode` instead of asking it over NIP-11.
+    /// Negentropy failures still fall back to a plain fetch.
+    pub fn sync_mode(mut self, mode: SyncMode) -> Self {
+        self.sync_mode = Some(mode);
+        self
+    }
+
+    /// Kind new notes are written with; see [`Dialog::set_note_kind`]
+    pub fn note_kind(mut self, kind: u16) -> Self {
+        self.note_kind = Some(kind);
+        self
+    }
+
+    /// How long connecting to relays may block. Without it, connecting
+    /// returns right away and relays keep retrying in the background.
+    pub fn connection_timeout(mut self, timeout: Duration) -> Self {
+        self.connection_timeout = Some(timeout);
+        self
+    }
+
+    /// How long relay queries (contacts, messages, plain sync...) may take,
+    /// and how long a relay gets to answer a negentropy sync. Defaults to
+    /// [`DEFAULT_FETCH_TIMEOUT`]. Use [`Dialog::sync_cancellable`] to stop
+    /// a sync at any point.
+    pub fn fetch_timeout(mut self, timeout: Duration) -> Self {
+        self.fetch_timeout = Some(timeout);
+        self
+    }
+
+    /// Outbox file other processes queue notes in; see [`Dialog::status`]
+    pub fn outbox(mut self, path: impl Into<PathBuf>) -> Self {
+        self.outbox = Some(path.into());
+        self
+    }
+
+    /// Name shown for this device in [`Dialog::devices`] on the others
+    pub fn device_name(mut self, name: impl Into<String>) -> Self {
+        self.device_name = Some(name.into());
+        self
+    }
+
+    /// Features that sync and watch; see [`Dialog::set_kind_set`]
+    pub fn kind_set(mut self, kinds: KindSet) -> Self {
+        self.kind_set = Some(kinds);
+        self
+    }
+
+    /// Don't migrate local storage in [`DialogBuilder::build`]; call
+    /// [`Dialog::migrate_schema_with_progress`] instead, e.g. to show
+    /// progress. Nothing else should run until it has.
+    pub fn defer_schema_migration(mut self) -> Self {
+        self.defer_schema_migration = true;
+        self
+    }
+
+    /// Time source, e.g. a [`ManualClock`](crate::ManualClock) in tests.
+    /// Defaults to the system clock.
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+
+    pub async fn build(self) -> Result<Dialog> {
+        let keys = self
+            .keys
+            .ok_or_else(|| DialogError::Config("a signer is required".into()))?;
+
+        let mut options = Options::new();
+        if let Some(addr) = self.proxy {
+            options = options.connection(Connection::new().proxy(addr));
+        }
+        if let Some(timeout) = self.connection_timeout {
+            options = options.connection_timeout(Some(timeout));
+        }
+        let builder = Client::builder().signer(keys.clone()).opts(options);
+
+        let mut dialog = match self.store {
+            StoreBackend::Memory => Dialog::from_store(builder, LocalStore::memory(), keys),
+            StoreBackend::Ndb => {
+                // Use pubkey in path for isolation
+                let db_path = get_data_dir(self.data_dir.as_deref(), &keys.public_key().to_hex())?;
+                let dir = db_path.parent().unwrap_or(&db_path).to_path_buf();
+                let store =
+                    LocalStore::open_ndb(&db_path, &dir.join(local_store::REMOVED_EVENTS_FILE))
+                        .await?;
+                let mut dialog = Dialog::from_store(builder, store, keys);
+                dialog.audit = audit::AuditLog::at(dir.join(audit::AUDIT_LOG_FILE));
+                dialog.search_journal =
+                    search_index::SearchJournal::at(dir.join(search_index::SEARCH_JOURNAL_FILE));
+                dialog.device = std::sync::RwLock::new(devices::DeviceInfo::load_or_create(
+                    &dir.join(devices::DEVICE_ID_FILE),
+                ));
+                dialog.evicted_notes = cache::EvictedNotes::at(dir.join(cache::EVICTED_NOTES_FILE));
+                dialog.schema_version =
+                    schema::SchemaVersion::at(dir.join(schema::SCHEMA_VERSION_FILE));
+                dialog.relay_policies =
+                    relay_policy::RelayPolicies::at(dir.join(relay_policy::RELAY_POLICY_FILE));
+                dialog.wallet.payments = JsonStore::at(dir.join(payments::RELAY_PAYMENTS_FILE));
+                #[cfg(feature = "attachments")]
+                {
+                    use crate::attachment_cache::{AttachmentCache, ATTACHMENT_CACHE_DIR};
+                    dialog.attachment_cache = AttachmentCache::at(dir.join(ATTACHMENT_CACHE_DIR));
+                }
+                dialog.offline_queue =
+                    offline_queue::OfflineQueue::at(dir.join(offline_queue::OFFLINE_QUEUE_FILE));
+                dialog.max_privacy = JsonStore::at(dir.join(privacy::MAX_PRIVACY_FILE));
+                dialog.retention = JsonStore::at(dir.join(retention::RETENTION_FILE));
+                // Read now so a broken file fails here, not on every note
+                dialog.max_privacy.get()?;
+                dialog.retention.get()?;
+                dialog.db_path = Some(db_path);
+                dialog
+            }
+        };
+
+        if let Some(clock) = self.clock {
+            dialog.clock = clock;
+        }
+        if let Some(kind) = self.note_kind {
+            dialog.set_note_kind(kind)?;
+        }
+        dialog.set_device_name(self.device_name);
+        if let Some(kinds) = self.kind_set {
+            dialog.set_kind_set(kinds);
+        }
+        dialog.sync_mode = self.sync_mode;
+        dialog.set_outbox_path(self.outbox);
+        if let Some(timeout) = self.fetch_timeout {
+            dialog.fetch_timeout = timeout;
+        }
+        if !self.defer_schema_migration {
+            dialog.migrate_schema().await?;
+        }
+        if let Some(addr) = self.proxy {
+            let mut settings = dialog.enrich_settings();
+            settings.proxy = Some(format!("socks5h://{addr}"));
+            dialog.set_enrich_settings(settings);
+        }
+        for url in &self.relays {
+            dialog.connect_relay(url).await?;
+        }
+        Ok(dialog)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_builder_configures_dialog() {
+        let keys = Keys::generate();
+        let dialog = Dialog::builder()
+            .signer(keys.clone())
+            .store(StoreBackend::Memory)
+            .note_kind(4242)
+            .sync_mode(SyncMode::Plain)
+            .fetch_timeout(Duration::from_secs(3))
+            .build()
+            .await
+            .unwrap();
+        assert_eq!(dialog.public_key(), keys.public_key());
+        assert_eq!(dialog.note_kind(), Kind::from(4242));
+        assert_eq!(dialog.sync_mode, Some(SyncMode::Plain));
+        assert_eq!(dialog.fetch_timeout, Duration::from_secs(3));
+        assert_eq!(dialog.schema_version().unwrap(), crate::SCHEMA_VERSION);
+
+        let missing = Dialog::builder().store(StoreBackend::Memory).build().await;
+        assert!(matches!(missing, Err(DialogError::Config(_))));
+        let bad_kind = Dialog::builder()
+            .signer(keys)
+            .store(StoreBackend::Memory)
+            .note_kind(1)
+            .build()
+            .await;
+        assert!(matches!(bad_kind, Err(DialogError::NoteKind(1))));
+    }
+}
@@ -0,0 +1,138 @@
+use crate::audit::AuditAction;
+use crate::lock::is_locked_text;
+use crate::privacy::open_note;
+use crate::tags::{add_hashtags, normalize_tag, remove_hashtags};
+use crate::{Dialog, DialogError, Note, Result};
+use nostr_sdk::prelude::*;
+
+/// Outcome of [`Dialog::add_tags`] and [`Dialog::remove_tags`]
+#[derive(Debug, Default)]
+pub struct BulkTagReport {
+    /// Changed notes as (old id, the copy that replaces it)
+    pub updated: Vec<(EventId, Note)>,
+    /// Notes that already had, or never had, the tags
+    pub unchanged: Vec<EventId>,
+    pub failed: Vec<(EventId, String)>,
+}
+
+impl Dialog {
+    /// Add hashtags to many notes at once, e.g. from a multi-select.
+    ///
+    /// Tags live in a note's text, so each note that changes is re-issued:
+    /// a copy with the tags appended replaces it in lists, keeping its
+    /// created_at, flags and voice data. Locked notes can't be retagged.
+    pub async fn add_tags(&self, ids: &[EventId], tags: &[String]) -> BulkTagReport {
+        let tags = normalize_tags(tags);
+        self.retag(ids, |text| add_hashtags(text, &tags)).await
+    }
+
+    /// Remove hashtags from many notes at once, re-issuing them like
+    /// [`Dialog::add_tags`]
+    pub async fn remove_tags(&self, ids: &[EventId], tags: &[String]) -> BulkTagReport {
+        let tags = normalize_tags(tags);
+        self.retag(ids, |text| remove_hashtags(text, &tags)).await
+    }
+
+    pub(crate) async fn retag(
+        &self,
+        ids: &[EventId],
+        edit: impl Fn(&str) -> String,
+    ) -> BulkTagReport {
+        let mut report = BulkTagReport::default();
+        for id in ids {
+            match self.retag_note(id, &edit).await {
+                Ok(Some(note)) => report.updated.push((*id, note)),
+                Ok(None) => report.unchanged.push(*id),
+                Err(e) => {
+                    eprintln!("[lib] retag: {id} failed: {e}");
+                    report.failed.push((*id, e.to_string()));
+                }
+            }
+        }
+        eprintln!(
+            "[lib] retag: {} updated, {} unchanged, {} failed",
+            report.updated.len(),
+            report.unchanged.len(),
+            report.failed.len()
+        );
+        report
+    }
+
+    async fn retag_note(
+        &self,
+        note_id: &EventId,
+        edit: impl Fn(&str) -> String,
+    ) -> Result<Option<Note>> {
+        let event = self.note_event(note_id).await?;
+        let opened = open_note(&self.keys, &event)?;
+        if is_locked_text(&opened.text) {
+            return Err(DialogError::Lock("locked notes can't be retagged".into()));
+        }
+        let text = edit(&opened.text);
+        if text == opened.text {
+            return Ok(None);
+        }
+
+        let mut note = self.reissue_note(note_id, &text, opened.created_at).await?;
+        self.audit(
+            AuditAction::Retag,
+            Some(&note.id),
+            format!("replaces {note_id}"),
+        );
+        if let Some(data) = self.voice_data(note_id).await? {
+            self.store_voice_data(&note.id, &data, None).await?;
+            note.audio = data.audio;
+            note.transcript = data.transcript;
+        }
+        Ok(Some(note))
+    }
+}
+
+pub(crate) fn normalize_tags(tags: &[String]) -> Vec<String> {
+    let mut normalized = Vec::new();
+    for tag in tags.iter().filter_map(|tag| normalize_tag(tag)) {
+        if !normalized.contains(&tag) {
+            normalized.push(tag);
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::memory_dialog;
+
+    #[tokio::test]
+    async fn test_bulk_tags() {
+        let dialog = memory_dialog();
+        let first = dialog.save_note("Plan sprint #work").await.unwrap();
+        let second = dialog.save_note("Buy milk").await.unwrap();
+        dialog.set_pinned(&second.id, true).await.unwrap();
+
+        let ids = [first.id, second.id];
+        let report = dialog.add_tags(&ids, &["#Q3".into(), "work".into()]).await;
+        assert!(report.failed.is_empty());
+        assert_eq!(report.updated.len(), 2);
+        let (old_id, copy) = &report.updated[1];
+        assert_eq!(*old_id, second.id);
+        assert_eq!(copy.text, "Buy milk\n#q3 #work");
+        assert_eq!(copy.created_at, second.created_at);
+        assert!(copy.is_pinned);
+
+        let notes = dialog.list_notes(10).await.unwrap();
+        assert_eq!(notes.len(), 2, "originals are hidden behind their copies");
+        assert!(notes.iter().all(|n| n.tags.contains(&"q3".to_string())));
+
+        let ids: Vec<EventId> = notes.iter().map(|n| n.id).collect();
+        let report = dialog.remove_tags(&ids, &["work".into()]).await;
+        assert_eq!(report.updated.len(), 2);
+
+        let notes = dialog.list_notes(10).await.unwrap();
+        let texts: Vec<&str> = notes.iter().map(|n| n.text.as_str()).collect();
+        assert_eq!(texts, vec!["Buy milk\n#q3", "Plan sprint\n#q3"]);
+        let ids: Vec<EventId> = notes.iter().map(|n| n.id).collect();
+        let again = dialog.remove_tags(&ids, &["work".into()]).await;
+        assert_eq!(again.unchanged, ids);
+    }
+}
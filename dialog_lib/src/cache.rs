@@ -0,0 +1,457 @@
+use crate::file_store::JsonStore;
+use crate::query::sort_newest_first;
+use crate::{Dialog, DialogError, Note, Result};
+use nostr_sdk::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// File name of the evicted note list inside the profile's data directory
+pub(crate) const EVICTED_NOTES_FILE: &str = "evicted_notes.json";
+
+/// What [`Dialog::enforce_cache_limit`] did
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EvictionReport {
+    /// Notes whose bodies were dropped locally, oldest first
+    pub evicted: Vec<EventId>,
+    /// Size of the stored notes, as in [`Dialog::local_cache_size`]
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+    /// Notes over the limit that no relay had a copy of, so they were kept
+    pub not_on_relay: usize,
+}
+
+/// What is kept of a note once its body is evicted
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct EvictedNote {
+    id: EventId,
+    created_at: Timestamp,
+    tags: Vec<String>,
+    // Missing from lists written before word counts were kept
+    #[serde(default)]
+    word_count: usize,
+    /// Lets imports spot the note as a duplicate; None for locked notes
+    /// and lists written before hashes were kept
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    content_hash: Option<String>,
+}
+
+/// Hash of a note's trimmed text. Kept for evicted notes, whose text is
+/// gone, so imports can still spot them as duplicates.
+pub(crate) fn content_hash(text: &str) -> String {
+    hashes::sha256::Hash::hash(text.trim().as_bytes()).to_string()
+}
+
+/// Notes evicted by [`Dialog::enforce_cache_limit`], on disk in the data
+/// directory or in memory for tests
+pub(crate) type EvictedNotes = JsonStore<Vec<EvictedNote>>;
+
+impl EvictedNotes {
+    fn add(&self, evicted: Vec<EvictedNote>) -> Result<()> {
+        self.update(|notes| {
+            notes.retain(|note| !evicted.iter().any(|e| e.id == note.id));
+            notes.extend(evicted);
+            ((), true)
+        })
+    }
+
+    /// Forget `id` once its body is back
+    fn remove(&self, id: &EventId) -> Result<()> {
+        self.update(|notes| {
+            let before = notes.len();
+            notes.retain(|note| note.id != *id);
+            ((), notes.len() < before)
+        })
+    }
+
+    fn contains(&self, id: &EventId) -> bool {
+        self.get()
+            .is_ok_and(|notes| notes.iter().any(|note| note.id == *id))
+    }
+}
+
+/// Bytes an event takes up, counted as its JSON. The database files only
+/// ever grow, so they can't tell how much eviction freed.
+fn event_size(event: &Event) -> u64 {
+    event.as_json().len() as u64
+}
+
+impl Dialog {
+    /// Size of the notes stored locally, counted the same way
+    /// [`Dialog::set_local_cache_limit`] does
+    pub async fn local_cache_size(&self) -> Result<u64> {
+        Ok(self
+            .stored_note_events()
+            .await?
+            .iter()
+            .map(event_size)
+            .sum())
+    }
+
+    pub fn local_cache_limit(&self) -> Option<u64> {
+        *self.cache_limit.read().unwrap()
+    }
+
+    /// Keep stored notes under `bytes`, evicting the oldest unpinned note
+    /// bodies right away and after every sync. Evicted notes stay listed
+    /// with their tags and state, but with empty text and `is_hydrated`
+    /// false. Only notes a relay still has are evicted. `None` lifts the
+    /// limit; evicted notes stay evicted.
+    pub async fn set_local_cache_limit(&self, bytes: Option<u64>) -> Result<EvictionReport> {
+        *self.cache_limit.write().unwrap() = bytes;
+        self.enforce_cache_limit().await
+    }
+
+    /// Evict notes until the cache is under [`Dialog::local_cache_limit`]
+    pub async fn enforce_cache_limit(&self) -> Result<EvictionReport> {
+        let events = self.stored_note_events().await?;
+        let sizes: HashMap<EventId, u64> = events.iter().map(|e| (e.id, event_size(e))).collect();
+        let bytes_before: u64 = sizes.values().sum();
+        let mut report = EvictionReport {
+            bytes_before,
+            bytes_after: bytes_before,
+            ..Default::default()
+        };
+        let Some(limit) = self.local_cache_limit() else {
+            return Ok(report);
+        };
+        if bytes_before <= limit {
+            return Ok(report);
+        }
+
+        let mut over = bytes_before - limit;
+        let mut candidates = Vec::new();
+        // Oldest first
+        for note in self.notes_from_events(events).await.into_iter().rev() {
+            if over == 0 {
+                break;
+            }
+            if note.is_pinned {
+                continue;
+            }
+            over = over.saturating_sub(sizes.get(&note.id).copied().unwrap_or(0));
+            candidates.push(note);
+        }
+
+        let on_relay = self.on_relay(&candidates).await?;
+        let (confirmed, missing): (Vec<Note>, Vec<Note>) = candidates
+            .into_iter()
+            .partition(|note| on_relay.contains(&note.id));
+        report.not_on_relay = missing.len();
+        self.evict(&confirmed).await?;
+        for note in confirmed {
+            report.bytes_after -= sizes.get(&note.id).copied().unwrap_or(0);
+            report.evicted.push(note.id);
+        }
+        eprintln!(
+            "[lib] cache: evicted {} notes, {} -> {} bytes",
+            report.evicted.len(),
+            report.bytes_before,
+            report.bytes_after
+        );
+        Ok(report)
+    }
+
+    /// Fetch a note's body from the relays and store it again, e.g. when an
+    /// evicted note or a search result from another device is opened. Notes
+    /// that are stored already come back as they are.
+    pub async fn hydrate_note(&self, note_id: &EventId) -> Result<Note> {
+        if !self.is_evicted(note_id) {
+            if let Some(note) = self.get_note(note_id).await? {
+                return Ok(note);
+            }
+        }
+        let filter = self.notes_filter().id(*note_id);
+        let events = self
+            .client
+            .fetch_events(vec![filter], Some(self.fetch_timeout))
+            .await?;
+        let event = events
+            .into_iter()
+            .next()
+            .ok_or(DialogError::NoteNotFound(*note_id))?;
+        self.rehydrate(event).await
+    }
+
+    /// Store a fetched note event and stop treating it as evicted
+    async fn rehydrate(&self, event: Event) -> Result<Note> {
+        let id = event.id;
+        self.local_store.restore(&[id])?;
+        self.save_events(vec![event]).await?;
+        // Read back before forgetting the stub, so an event that fails
+        // validation doesn't make the note vanish from the list
+        let filter = self.notes_filter().id(id);
+        let events = self
+            .client
+            .database()
+            .query(vec![filter])
+            .await
+            .map_err(|e| DialogError::Database(e.to_string()))?;
+        let note = self
+            .notes_from_events(events)
+            .await
+            .into_iter()
+            .next()
+            .ok_or(DialogError::NoteNotFound(id))?;
+        self.evicted_notes.remove(&id)?;
+        Ok(note)
+    }
+
+    /// Content hashes of evicted notes, for spotting duplicates on import
+    pub(crate) fn evicted_content_hashes(&self) -> Vec<(String, EventId)> {
+        let evicted = self.evicted_notes.get().unwrap_or_default();
+        evicted
+            .into_iter()
+            .filter_map(|note| Some((note.content_hash?, note.id)))
+            .collect()
+    }
+
+    /// Whether `note_id` was evicted and its body isn't stored
+    pub fn is_evicted(&self, note_id: &EventId) -> bool {
+        self.evicted_notes.contains(note_id)
+    }
+
+    /// Forget evictions of notes whose bodies are stored again, e.g. after
+    /// a recovery fetched them from relays. Returns how many.
+    pub(crate) async fn prune_evicted(&self) -> Result<usize> {
+        let ids: Vec<EventId> = self.evicted_notes.get()?.iter().map(|n| n.id).collect();
+        if ids.is_empty() {
+            return Ok(0);
+        }
+        let stored = self
+            .client
+            .database()
+            .query(vec![self.notes_filter().ids(ids)])
+            .await
+            .map_err(|e| DialogError::Database(e.to_string()))?;
+        let mut pruned = 0;
+        for event in stored {
+            self.evicted_notes.remove(&event.id)?;
+            pruned += 1;
+        }
+        Ok(pruned)
+    }
+
+    async fn stored_note_events(&self) -> Result<Events> {
+        let filter = self.notes_filter();
+        self.client
+            .database()
+            .query(vec![filter])
+            .await
+            .map_err(|e| DialogError::Database(e.to_string()))
+    }
+
+    /// Which of `notes` a relay has a copy of
+    async fn on_relay(&self, notes: &[Note]) -> Result<HashSet<EventId>> {
+        if notes.is_empty() {
+            return Ok(HashSet::new());
+        }
+        let filter = Filter::new().ids(notes.iter().map(|note| note.id));
+        let events = self
+            .client
+            .fetch_events(vec![filter], Some(self.fetch_timeout))
+            .await?;
+        Ok(events.into_iter().map(|event| event.id).collect())
+    }
+
+    /// Drop the bodies of `notes`, remembering their ids, times and tags.
+    /// The store keeps them out of later syncs until they are hydrated.
+    async fn evict(&self, notes: &[Note]) -> Result<()> {
+        if notes.is_empty() {
+            return Ok(());
+        }
+        // Recorded first, so a crash in between never loses a note entirely
+        self.evicted_notes.add(
+            notes
+                .iter()
+                .map(|note| EvictedNote {
+                    id: note.id,
+                    created_at: note.created_at,
+                    tags: note.tags.clone(),
+                    word_count: note.word_count,
+                    content_hash: (!note.is_locked).then(|| content_hash(&note.text)),
+                })
+                .collect(),
+        )?;
+        self.client
+            .database()
+            .delete(Filter::new().ids(notes.iter().map(|note| note.id)))
+            .await
+            .map_err(|e| DialogError::Database(e.to_string()))
+    }
+
+    /// Add evicted notes (those with one of `tags`, if given) to `notes`, keeping
+    /// the newest `limit`
+    pub(crate) async fn add_evicted(
+        &self,
+        notes: &mut Vec<Note>,
+        tags: Option<&[String]>,
+        limit: usize,
+    ) {
+        let evicted = match self.evicted_notes.get() {
+            Ok(evicted) => evicted,
+            Err(e) => {
+                eprintln!("[lib] cache: evicted notes unavailable: {e}");
+                Vec::new()
+            }
+        };
+        let state = if evicted.is_empty() {
+            Default::default()
+        } else {
+            self.app_state().await.unwrap_or_default()
+        };
+        for stub in evicted {
+            if tags.is_some_and(|tags| !stub.tags.iter().any(|t| tags.contains(t)))
+                || notes.iter().any(|note| note.id == stub.id)
+            {
+                continue;
+            }
+            let note_state = state.note(&stub.id);
+            notes.push(Note {
+                id: stub.id,
+                text: String::new(),
+                tags: stub.tags,
+                created_at: stub.created_at,
+                is_read: note_state.is_read(),
+                is_pinned: note_state.is_pinned(),
+                is_archived: note_state.is_archived(),
+                public_id: note_state.public_id(),
+                audio: None,
+                transcript: None,
+                is_locked: false,
+                is_synced: true,
+                is_hydrated: false,
+                edited: false,
+                edit_of: None,
+                task_status: note_state.task(),
+                word_count: stub.word_count,
+            });
+        }
+        sort_newest_first(notes);
+        notes.truncate(limit);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::memory_dialog;
+
+    #[tokio::test]
+    async fn test_evicted_notes_stay_listed() {
+        let dialog = memory_dialog();
+        let old = dialog.save_note("Old receipt #finance").await.unwrap();
+        let new = dialog.save_note("New thought").await.unwrap();
+        let before = dialog.local_cache_size().await.unwrap();
+
+        dialog.evict(&[old.clone()]).await.unwrap();
+        assert!(dialog.is_evicted(&old.id));
+        assert!(dialog.local_cache_size().await.unwrap() < before);
+
+        let notes = dialog.list_notes(10).await.unwrap();
+        assert_eq!(notes.len(), 2);
+        assert_eq!(notes[0].id, new.id);
+        assert!(notes[0].is_hydrated);
+        assert_eq!(notes[1].id, old.id);
+        assert!(!notes[1].is_hydrated);
+        assert!(notes[1].text.is_empty());
+        let tagged = dialog.list_by_tag("finance", 10).await.unwrap();
+        assert_eq!(tagged.len(), 1);
+        assert!(!tagged[0].is_hydrated);
+
+        // A sync offering the body again doesn't store it
+        let event = dialog.note_event(&new.id).await.unwrap();
+        dialog.evict(&[new.clone()]).await.unwrap();
+        assert_eq!(dialog.save_events(vec![event]).await.unwrap(), 0);
+        let report = dialog.enforce_cache_limit().await.unwrap();
+        assert!(report.evicted.is_empty());
+        assert_eq!(report.bytes_after, 0);
+    }
+
+    #[cfg(feature = "import")]
+    #[tokio::test]
+    async fn test_import_spots_evicted_duplicates() {
+        let dialog = memory_dialog();
+        let old = dialog.save_note("Call the bank").await.unwrap();
+        dialog.evict(&[old.clone()]).await.unwrap();
+
+        let dir = std::env::temp_dir().join(format!("dialog-evict-dedupe-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("notes.json");
+        std::fs::write(
+            &path,
+            r#"{"activeNotes": [{"content": "Call the bank"}, {"content": "Pay rent"}]}"#,
+        )
+        .unwrap();
+        let report = dialog
+            .import_path(&path, crate::ImportFormat::Simplenote)
+            .await
+            .unwrap();
+        // Matched by the hash kept at eviction, since the text is gone
+        assert_eq!(report.created.len(), 1);
+        assert_eq!(report.skipped.len(), 1);
+        assert_eq!(report.skipped[0].existing, old.id);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_eviction_on_nostrdb() {
+        let dir = std::env::temp_dir().join(format!("dialog-evict-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let keys = Keys::generate();
+        let open = || {
+            Dialog::builder()
+                .signer(keys.clone())
+                .data_dir(&dir)
+                .build()
+        };
+
+        let dialog = open().await.unwrap();
+        let old = dialog.save_note("Old receipt #finance").await.unwrap();
+        dialog.save_note("New thought").await.unwrap();
+        // nostrdb ingests in the background
+        while dialog.list_notes(10).await.unwrap().len() < 2 {
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        let event = dialog.note_event(&old.id).await.unwrap();
+        let before = dialog.local_cache_size().await.unwrap();
+        dialog.evict(&[old.clone()]).await.unwrap();
+        assert!(dialog.local_cache_size().await.unwrap() < before);
+        assert_eq!(dialog.save_events(vec![event.clone()]).await.unwrap(), 0);
+        let items = dialog
+            .local_store
+            .negentropy_items(dialog.notes_filter())
+            .await
+            .unwrap();
+        assert!(items.iter().any(|(id, _)| *id == old.id));
+        drop(dialog);
+
+        // Reopening rewrites the database without the evicted body
+        let dialog = open().await.unwrap();
+        let notes = dialog.list_notes(10).await.unwrap();
+        assert_eq!(notes.len(), 2);
+        assert!(!notes[1].is_hydrated);
+        assert!(dialog.note_event(&old.id).await.is_err());
+
+        let hydrated = dialog.rehydrate(event).await.unwrap();
+        assert_eq!(hydrated.text, "Old receipt #finance");
+        assert!(!dialog.is_evicted(&old.id));
+        drop(dialog);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_hydrated_notes_are_kept() {
+        let dialog = memory_dialog();
+        let note = dialog.save_note("Read me later").await.unwrap();
+        let event = dialog.note_event(&note.id).await.unwrap();
+        dialog.evict(&[note.clone()]).await.unwrap();
+
+        let hydrated = dialog.rehydrate(event).await.unwrap();
+        assert!(hydrated.is_hydrated);
+        assert_eq!(hydrated.text, "Read me later");
+        assert!(!dialog.is_evicted(&note.id));
+        dialog.enforce_cache_limit().await.unwrap();
+        let stored = dialog.hydrate_note(&note.id).await.unwrap();
+        assert_eq!(stored.text, "Read me later");
+    }
+}
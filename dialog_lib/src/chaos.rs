@@ -0,0 +1,141 @@
+//! Fault injection for testing error and retry handling in apps: relay
+//! latency, dropped publishes and notes that fail to decrypt. Only
+//! [`Dialog::set_chaos`] turns it on, and that needs the `chaos` feature.
+
+use crate::Clock;
+#[cfg(feature = "chaos")]
+use crate::{Dialog, DialogError, Result};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Reason given for a publish dropped on purpose
+pub const CHAOS_DROP_REASON: &str = "chaos: publish dropped";
+
+/// What to break, and how often
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ChaosConfig {
+    /// Added before every publish to a relay and every sync
+    pub latency: Duration,
+    /// Up to this much more latency, picked at random each time
+    pub jitter: Duration,
+    /// Share of publishes to a relay (0.0 to 1.0) that fail as if refused
+    pub drop_rate: f64,
+    /// Share of stored notes (0.0 to 1.0) that fail to decrypt when read
+    pub decrypt_failure_rate: f64,
+    /// Same seed, same sequence of failures
+    pub seed: u64,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Chaos {
+    // The config and the state of its random number generator
+    state: Mutex<Option<(ChaosConfig, u64)>>,
+}
+
+impl Chaos {
+    /// xorshift64*; good enough to pick failures, and reproducible
+    fn roll(&self) -> Option<(ChaosConfig, f64)> {
+        let mut state = self.state.lock().unwrap();
+        let (config, x) = state.as_mut()?;
+        *x ^= *x >> 12;
+        *x ^= *x << 25;
+        *x ^= *x >> 27;
+        let random = x.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 11;
+        Some((config.clone(), random as f64 / (1u64 << 53) as f64))
+    }
+
+    /// Wait out the configured relay latency
+    pub(crate) async fn delay(&self, clock: &dyn Clock) {
+        let Some((config, random)) = self.roll() else {
+            return;
+        };
+        let delay = config.latency + config.jitter.mul_f64(random);
+        if !delay.is_zero() {
+            clock.sleep(delay).await;
+        }
+    }
+
+    /// Before a publish to one relay: wait, then maybe drop it
+    pub(crate) async fn publish(&self, clock: &dyn Clock) -> std::result::Result<(), String> {
+        self.delay(clock).await;
+        match self.roll() {
+            Some((config, random)) if random < config.drop_rate => {
+                Err(CHAOS_DROP_REASON.to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether the next note read should fail to decrypt
+    pub(crate) fn fail_decrypt(&self) -> bool {
+        self.roll()
+            .is_some_and(|(config, random)| random < config.decrypt_failure_rate)
+    }
+}
+
+#[cfg(feature = "chaos")]
+impl Dialog {
+    /// Inject faults until called with None. For testing apps only: notes
+    /// that "fail to decrypt" are just hidden, but dropped publishes really
+    /// don't reach the relay.
+    pub fn set_chaos(&self, config: Option<ChaosConfig>) -> Result<()> {
+        if let Some(config) = &config {
+            let rates = [config.drop_rate, config.decrypt_failure_rate];
+            if !rates.iter().all(|rate| (0.0..=1.0).contains(rate)) {
+                return Err(DialogError::Config(
+                    "chaos rates must be between 0 and 1".into(),
+                ));
+            }
+            eprintln!("[lib] chaos enabled: {config:?}");
+        }
+        // xorshift gets stuck at zero
+        *self.chaos.state.lock().unwrap() = config.map(|config| {
+            let seed = config.seed.max(1);
+            (config, seed)
+        });
+        Ok(())
+    }
+
+    pub fn chaos(&self) -> Option<ChaosConfig> {
+        let state = self.chaos.state.lock().unwrap();
+        state.as_ref().map(|(config, _)| config.clone())
+    }
+}
+
+#[cfg(all(test, feature = "chaos"))]
+mod tests {
+    use super::*;
+    use crate::test_support::memory_dialog;
+
+    #[tokio::test]
+    async fn test_chaos_hides_notes_until_turned_off() {
+        let dialog = memory_dialog();
+        dialog.save_note("Fragile").await.unwrap();
+        dialog.save_note("Also fragile").await.unwrap();
+
+        let all_fail = ChaosConfig {
+            decrypt_failure_rate: 1.0,
+            ..Default::default()
+        };
+        dialog.set_chaos(Some(all_fail.clone())).unwrap();
+        assert_eq!(dialog.chaos(), Some(all_fail));
+        assert!(dialog.list_notes(10).await.unwrap().is_empty());
+        assert!(dialog.chaos.publish(&*dialog.clock).await.is_ok());
+
+        let drop_all = ChaosConfig {
+            drop_rate: 1.0,
+            ..Default::default()
+        };
+        dialog.set_chaos(Some(drop_all)).unwrap();
+        let dropped = dialog.chaos.publish(&*dialog.clock).await;
+        assert_eq!(dropped.unwrap_err(), CHAOS_DROP_REASON);
+
+        dialog.set_chaos(None).unwrap();
+        assert_eq!(dialog.list_notes(10).await.unwrap().len(), 2);
+        let invalid = ChaosConfig {
+            drop_rate: 1.5,
+            ..Default::default()
+        };
+        assert!(dialog.set_chaos(Some(invalid)).is_err());
+    }
+}
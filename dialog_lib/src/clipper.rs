@@ -0,0 +1,237 @@
+use crate::local_http::{read_request, write_response, Request, Response};
+use crate::{Dialog, DialogError, Result};
+use nostr_sdk::prelude::*;
+use serde::Deserialize;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+
+/// Tag added to every clipped note
+pub const CLIPPED_TAG: &str = "clipped";
+const MIN_TOKEN_LEN: usize = 16;
+const MAX_BODY_BYTES: usize = crate::MAX_NOTE_BYTES;
+
+/// Running web clipper server; stops when dropped.
+///
+/// The server is a localhost endpoint for a browser extension and is kept
+/// deliberately tiny: one route (`POST /clip`), bearer-token auth, and one
+/// request per connection.
+pub struct ClipperHandle {
+    addr: SocketAddr,
+    task: JoinHandle<()>,
+}
+
+impl ClipperHandle {
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Wait for the server to stop (it only stops if the task is aborted)
+    pub async fn join(mut self) {
+        let _ = (&mut self.task).await;
+    }
+}
+
+impl Drop for ClipperHandle {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// JSON body accepted by `POST /clip`; plain-text bodies are taken as `text`
+#[derive(Debug, Default, Deserialize, PartialEq)]
+struct Clip {
+    #[serde(default)]
+    text: Option<String>,
+    #[serde(default)]
+    url: Option<String>,
+    #[serde(default)]
+    title: Option<String>,
+}
+
+impl Clip {
+    /// Note text for the clip: title, selection and URL on their own lines,
+    /// followed by the `#clipped` tag. None if the clip is empty.
+    fn note_text(&self) -> Option<String> {
+        let parts: Vec<&str> = [&self.title, &self.text, &self.url]
+            .into_iter()
+            .filter_map(|part| part.as_deref().map(str::trim))
+            .filter(|part| !part.is_empty())
+            .collect();
+        if parts.is_empty() {
+            return None;
+        }
+        Some(format!("{}\n\n#{CLIPPED_TAG}", parts.join("\n\n")))
+    }
+}
+
+impl Dialog {
+    /// Start the web clipper server on `127.0.0.1:port` (0 picks a free
+    /// port). Requests must send `Authorization: Bearer <token>`.
+    pub async fn start_clipper_server(
+        self: Arc<Self>,
+        port: u16,
+        token: String,
+    ) -> Result<ClipperHandle> {
+        if token.len() < MIN_TOKEN_LEN {
+            return Err(DialogError::Clipper(format!(
+                "token must be at least {MIN_TOKEN_LEN} characters"
+            )));
+        }
+
+        // Never listen beyond this machine
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port)).await?;
+        let addr = listener.local_addr()?;
+        eprintln!("[lib] clipper: listening on http://{addr}");
+
+        let token = Arc::new(token);
+        let task = tokio::spawn(async move {
+            loop {
+                let (stream, peer) = match listener.accept().await {
+                    Ok(accepted) => accepted,
+                    Err(e) => {
+                        eprintln!("[lib] clipper: accept failed: {e}");
+                        continue;
+                    }
+                };
+                let dialog = self.clone();
+                let token = token.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = dialog.serve_clip(stream, &token).await {
+                        eprintln!("[lib] clipper: {peer}: {e}");
+                    }
+                });
+            }
+        });
+        Ok(ClipperHandle { addr, task })
+    }
+
+    async fn serve_clip(&self, mut stream: TcpStream, token: &str) -> Result<()> {
+        let response = match read_request(&mut stream, MAX_BODY_BYTES).await {
+            Ok(request) => self.handle_clip(request, token).await,
+            Err(response) => response,
+        };
+        write_response(&mut stream, response).await?;
+        Ok(())
+    }
+
+    async fn handle_clip(&self, request: Request, token: &str) -> Response {
+        if request.path != "/clip" {
+            return Response::error("404 Not Found", "not found");
+        }
+        if request.method != "POST" {
+            return Response::error("405 Method Not Allowed", "use POST");
+        }
+        if !request.is_authorized(token) {
+            return Response::error("401 Unauthorized", "invalid token");
+        }
+
+        let clip = match parse_clip(request.content_type.as_deref(), &request.body) {
+            Ok(clip) => clip,
+            Err(message) => return Response::error("400 Bad Request", &message),
+        };
+        let Some(text) = clip.note_text() else {
+            return Response::error("400 Bad Request", "clip is empty");
+        };
+
+        match self.create_note(&text).await {
+            Ok(note) => {
+                eprintln!("[lib] clipper: created note {}", note.id);
+                Response::new(
+                    "201 Created",
+                    serde_json::json!({ "id": note.id.to_hex() }).to_string(),
+                )
+            }
+            Err(e @ DialogError::NoteTooLarge { .. }) => {
+                Response::error("413 Payload Too Large", &e.to_string())
+            }
+            Err(e) => Response::error("500 Internal Server Error", &e.to_string()),
+        }
+    }
+}
+
+fn parse_clip(content_type: Option<&str>, body: &[u8]) -> std::result::Result<Clip, String> {
+    if content_type.is_some_and(|ct| ct.starts_with("application/json")) {
+        return serde_json::from_slice(body).map_err(|e| format!("invalid JSON: {e}"));
+    }
+    let text = std::str::from_utf8(body).map_err(|_| "body is not UTF-8".to_string())?;
+    Ok(Clip {
+        text: Some(text.to_string()),
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::memory_dialog;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    const TOKEN: &str = "0123456789abcdef";
+
+    async fn send(addr: SocketAddr, request: &str) -> String {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    #[test]
+    fn test_clip_note_text() {
+        let clip = Clip {
+            text: Some(" quoted bit ".into()),
+            url: Some("https://example.com".into()),
+            title: Some("Example".into()),
+        };
+        assert_eq!(
+            clip.note_text().unwrap(),
+            "Example\n\nquoted bit\n\nhttps://example.com\n\n#clipped"
+        );
+        assert_eq!(
+            crate::parse_hashtags(&clip.note_text().unwrap()),
+            vec!["clipped"]
+        );
+        assert!(Clip::default().note_text().is_none());
+    }
+
+    #[test]
+    fn test_parse_clip() {
+        let json = parse_clip(Some("application/json"), br#"{"url":"https://a.b"}"#).unwrap();
+        assert_eq!(json.url.as_deref(), Some("https://a.b"));
+        let plain = parse_clip(Some("text/plain"), b"hello").unwrap();
+        assert_eq!(plain.text.as_deref(), Some("hello"));
+        assert!(parse_clip(Some("application/json"), b"{").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_clipper_rejects_bad_requests() {
+        let dialog = Arc::new(memory_dialog());
+        assert!(dialog
+            .clone()
+            .start_clipper_server(0, "short".into())
+            .await
+            .is_err());
+
+        let server = dialog.start_clipper_server(0, TOKEN.into()).await.unwrap();
+        assert!(server.local_addr().ip().is_loopback());
+
+        let unauthorized = send(
+            server.local_addr(),
+            "POST /clip HTTP/1.1\r\nAuthorization: Bearer nope\r\nContent-Length: 2\r\n\r\nhi",
+        )
+        .await;
+        assert!(unauthorized.starts_with("HTTP/1.1 401"), "{unauthorized}");
+
+        let empty = send(
+            server.local_addr(),
+            &format!("POST /clip HTTP/1.1\r\nAuthorization: Bearer {TOKEN}\r\nContent-Type: application/json\r\nContent-Length: 2\r\n\r\n{{}}"),
+        )
+        .await;
+        assert!(empty.starts_with("HTTP/1.1 400"), "{empty}");
+
+        let wrong_route = send(server.local_addr(), "GET / HTTP/1.1\r\n\r\n").await;
+        assert!(wrong_route.starts_with("HTTP/1.1 404"), "{wrong_route}");
+    }
+}
@@ -0,0 +1,171 @@
+use crate::Dialog;
+use nostr_sdk::prelude::*;
+use std::fmt::Debug;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+
+/// How far into the future an event may be dated before we assume the
+/// clocks of the devices involved disagree
+pub const CLOCK_SKEW_TOLERANCE_SECS: u64 = 5 * 60;
+
+/// What [`Clock::sleep`] returns
+pub type Sleep = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// Where a [`Dialog`](crate::Dialog) gets the time: for created_at,
+/// retention and expiry checks, and for pacing and backoff. Set with
+/// [`DialogBuilder::clock`](crate::DialogBuilder::clock).
+pub trait Clock: Send + Sync + Debug {
+    /// Wall-clock time
+    fn now(&self) -> Timestamp;
+    /// Monotonic time, for measuring how long something has waited
+    fn instant(&self) -> Instant;
+    fn sleep(&self, duration: Duration) -> Sleep;
+}
+
+/// The system clock and tokio timers; the default
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Timestamp {
+        Timestamp::now()
+    }
+
+    fn instant(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep(&self, duration: Duration) -> Sleep {
+        Box::pin(tokio::time::sleep(duration))
+    }
+}
+
+/// A clock that only moves when told to, so tests can fast-forward
+/// through expiry, backoff and sync intervals. Sleeps finish as soon as
+/// [`ManualClock::advance`] passes their deadline.
+#[derive(Debug)]
+pub struct ManualClock {
+    start: Timestamp,
+    start_instant: Instant,
+    elapsed: watch::Sender<Duration>,
+}
+
+impl ManualClock {
+    pub fn new(start: Timestamp) -> Self {
+        Self {
+            start,
+            start_instant: Instant::now(),
+            elapsed: watch::channel(Duration::ZERO).0,
+        }
+    }
+
+    pub fn advance(&self, by: Duration) {
+        self.elapsed.send_modify(|elapsed| *elapsed += by);
+    }
+
+    /// Move to `at`; the clock never goes back, so earlier times are ignored
+    pub fn set(&self, at: Timestamp) {
+        let target = Duration::from_secs(at.as_u64().saturating_sub(self.start.as_u64()));
+        self.elapsed
+            .send_modify(|elapsed| *elapsed = (*elapsed).max(target));
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new(Timestamp::now())
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Timestamp {
+        Timestamp::from(self.start.as_u64() + self.elapsed.borrow().as_secs())
+    }
+
+    fn instant(&self) -> Instant {
+        self.start_instant + *self.elapsed.borrow()
+    }
+
+    fn sleep(&self, duration: Duration) -> Sleep {
+        let mut elapsed = self.elapsed.subscribe();
+        let deadline = *elapsed.borrow() + duration;
+        Box::pin(async move {
+            // Err means the clock is gone, and with it any reason to wait
+            let _ = elapsed.wait_for(|elapsed| *elapsed >= deadline).await;
+        })
+    }
+}
+
+/// Hands out created_at values that never go backwards, even if the wall
+/// clock does, so notes created in sequence keep their order.
+#[derive(Debug, Default)]
+pub(crate) struct MonotonicTimestamps {
+    last: AtomicU64,
+}
+
+impl MonotonicTimestamps {
+    pub(crate) fn next(&self, now: Timestamp) -> Timestamp {
+        let now = now.as_u64();
+        let previous = self.last.fetch_max(now, Ordering::SeqCst);
+        Timestamp::from(previous.max(now))
+    }
+}
+
+impl Dialog {
+    /// The current time by the configured [`Clock`]
+    pub(crate) fn now(&self) -> Timestamp {
+        self.clock.now()
+    }
+}
+
+/// Seconds by which `created_at` is ahead of `now`, when that exceeds
+/// [`CLOCK_SKEW_TOLERANCE_SECS`]. Past timestamps are never treated as
+/// skew since old notes arrive during every sync.
+pub fn detect_clock_skew(created_at: Timestamp, now: Timestamp) -> Option<u64> {
+    let ahead = created_at.as_u64().saturating_sub(now.as_u64());
+    (ahead > CLOCK_SKEW_TOLERANCE_SECS).then_some(ahead)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_monotonic_timestamps_never_go_backwards() {
+        let timestamps = MonotonicTimestamps::default();
+        assert_eq!(timestamps.next(Timestamp::from(100)), Timestamp::from(100));
+        // Wall clock jumped back
+        assert_eq!(timestamps.next(Timestamp::from(90)), Timestamp::from(100));
+        assert_eq!(timestamps.next(Timestamp::from(120)), Timestamp::from(120));
+    }
+
+    #[tokio::test]
+    async fn test_manual_clock_sleeps_until_advanced() {
+        let clock = std::sync::Arc::new(ManualClock::new(Timestamp::from(1_000)));
+        let sleep = tokio::spawn(clock.sleep(Duration::from_secs(60)));
+
+        clock.advance(Duration::from_secs(59));
+        tokio::task::yield_now().await;
+        assert!(!sleep.is_finished());
+        clock.set(Timestamp::from(1_060));
+        sleep.await.unwrap();
+
+        assert_eq!(clock.now(), Timestamp::from(1_060));
+        clock.set(Timestamp::from(500));
+        assert_eq!(clock.now(), Timestamp::from(1_060));
+    }
+
+    #[test]
+    fn test_detect_clock_skew() {
+        let now = Timestamp::from(1_000_000);
+        assert_eq!(detect_clock_skew(Timestamp::from(999_000), now), None);
+        assert_eq!(detect_clock_skew(Timestamp::from(1_000_060), now), None);
+        assert_eq!(
+            detect_clock_skew(Timestamp::from(1_003_600), now),
+            Some(3600)
+        );
+    }
+}
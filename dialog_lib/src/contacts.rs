@@ -0,0 +1,279 @@
+use crate::{fold_for_search, Dialog, DialogError, Result};
+use nostr_sdk::prelude::*;
+use std::collections::{BTreeSet, HashMap};
+
+/// Someone on our follow list (kind 3), with their profile when known
+#[derive(Debug, Clone)]
+pub struct Contact {
+    pub pubkey: PublicKey,
+    /// Petname from the follow list entry
+    pub alias: Option<String>,
+    pub profile: Option<Metadata>,
+}
+
+impl Contact {
+    /// Best name to show: petname, then display name, then name
+    pub fn display_name(&self) -> Option<&str> {
+        self.alias.as_deref().or_else(|| {
+            let profile = self.profile.as_ref()?;
+            profile.display_name.as_deref().or(profile.name.as_deref())
+        })
+    }
+}
+
+impl Dialog {
+    /// Our follow list with profiles, sorted by display name.
+    ///
+    /// Refreshes the list from relays and caches it locally; when relays
+    /// can't be reached the cached copy is used.
+    pub async fn fetch_contacts(&self) -> Result<Vec<Contact>> {
+        let filter = Filter::new()
+            .author(self.keys.public_key())
+            .kind(Kind::ContactList)
+            .limit(1);
+
+        match self
+            .client
+            .fetch_events(vec![filter.clone()], Some(self.fetch_timeout))
+            .await
+        {
+            Ok(fetched) => {
+                self.save_events(fetched).await?;
+            }
+            Err(e) => eprintln!("[lib] fetch_contacts: using cached list ({e})"),
+        }
+
+        let stored = self
+            .client
+            .database()
+            .query(vec![filter])
+            .await
+            .map_err(|e| DialogError::Database(e.to_string()))?;
+        let Some(list) = stored.into_iter().max_by_key(|e| e.created_at) else {
+            return Ok(Vec::new());
+        };
+
+        let mut contacts: Vec<Contact> = list
+            .tags
+            .iter()
+            .filter_map(|tag| match tag.as_standardized() {
+                Some(TagStandard::PublicKey {
+                    public_key, alias, ..
+                }) => Some(Contact {
+                    pubkey: *public_key,
+                    alias: alias.clone().filter(|a| !a.is_empty()),
+                    profile: None,
+                }),
+                _ => None,
+            })
+            .collect();
+
+        let mut profiles = self
+            .fetch_profiles(contacts.iter().map(|c| c.pubkey))
+            .await?;
+        for contact in &mut contacts {
+            contact.profile = profiles.remove(&contact.pubkey);
+        }
+        // Named contacts first, alphabetically ignoring case and accents
+        contacts.sort_by_cached_key(|c| {
+            let name = c.display_name().map(fold_for_search);
+            (name.is_none(), name, c.pubkey)
+        });
+        eprintln!("[lib] fetch_contacts: {} contacts", contacts.len());
+        Ok(contacts)
+    }
+
+    /// Kind-0 profile for a single pubkey; see [`Dialog::fetch_profiles`]
+    pub async fn fetch_profile(&self, pubkey: PublicKey) -> Result<Option<Metadata>> {
+        Ok(self.fetch_profiles([pubkey]).await?.remove(&pubkey))
+    }
+
+    /// Kind-0 profiles for `pubkeys`, from the local database first and
+    /// then from relays for any that are missing
+    pub async fn fetch_profiles(
+        &self,
+        pubkeys: impl IntoIterator<Item = PublicKey>,
+    ) -> Result<HashMap<PublicKey, Metadata>> {
+        let pubkeys: BTreeSet<PublicKey> = pubkeys.into_iter().collect();
+        if pubkeys.is_empty() {
+            return Ok(HashMap::new());
+        }
+
+        let filter = Filter::new().kind(Kind::Metadata).authors(pubkeys.clone());
+        let stored = self
+            .client
+            .database()
+            .query(vec![filter])
+            .await
+            .map_err(|e| DialogError::Database(e.to_string()))?;
+        let mut profiles = newest_profiles(stored);
+
+        let missing: Vec<PublicKey> = pubkeys
+            .into_iter()
+            .filter(|pk| !profiles.contains_key(pk))
+            .collect();
+        if missing.is_empty() {
+            return Ok(profiles);
+        }
+
+        let filter = Filter::new().kind(Kind::Metadata).authors(missing);
+        match self
+            .client
+            .fetch_events(vec![filter], Some(self.fetch_timeout))
+            .await
+        {
+            Ok(fetched) => {
+                let fetched: Vec<Event> = fetched.into_iter().collect();
+                self.save_events(fetched.clone()).await?;
+                profiles.extend(newest_profiles(fetched));
+            }
+            Err(e) => eprintln!("[lib] fetch_profiles: relay fetch failed: {e}"),
+        }
+        Ok(profiles)
+    }
+
+    /// Our own kind-0 profile; see [`Dialog::fetch_profiles`]
+    pub async fn get_my_profile(&self) -> Result<Option<Metadata>> {
+        self.fetch_profile(self.keys.public_key()).await
+    }
+
+    /// Publish our kind-0 profile so others see a name instead of a pubkey.
+    ///
+    /// `name` is used as both name and display name. None or blank fields
+    /// are removed; fields set by other clients (nip05, lud16, ...) are
+    /// kept. The profile is saved locally even when no relay takes it, in
+    /// which case the error says so and publishing can be retried.
+    pub async fn publish_profile(
+        &self,
+        name: Option<&str>,
+        about: Option<&str>,
+        picture: Option<&str>,
+    ) -> Result<Metadata> {
+        let field = |value: Option<&str>| {
+            value
+                .map(str::trim)
+                .filter(|v| !v.is_empty())
+                .map(str::to_string)
+        };
+        let picture = match field(picture) {
+            Some(picture) => Some(
+                Url::parse(&picture)
+                    .map_err(|e| DialogError::Config(format!("invalid picture URL: {e}")))?
+                    .to_string(),
+            ),
+            None => None,
+        };
+
+        let mut metadata = self.get_my_profile().await?.unwrap_or_default();
+        metadata.name = field(name);
+        metadata.display_name = field(name);
+        metadata.about = field(about);
+        metadata.picture = picture;
+
+        let event = EventBuilder::metadata(&metadata)
+            .custom_created_at(self.created_at.next(self.now()))
+            .sign(&self.keys)
+            .await?;
+        let id = event.id;
+        self.publish(event).await?;
+        eprintln!("[lib] publish_profile: published {id}");
+        Ok(metadata)
+    }
+}
+
+fn newest_profiles(events: impl IntoIterator<Item = Event>) -> HashMap<PublicKey, Metadata> {
+    let mut newest: HashMap<PublicKey, Event> = HashMap::new();
+    for event in events {
+        match newest.get(&event.pubkey) {
+            Some(existing) if existing.created_at >= event.created_at => {}
+            _ => {
+                newest.insert(event.pubkey, event);
+            }
+        }
+    }
+    newest
+        .into_iter()
+        .filter_map(|(pubkey, event)| Some((pubkey, Metadata::from_json(&event.content).ok()?)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::memory_dialog;
+
+    fn metadata_event(keys: &Keys, name: &str, created_at: u64) -> Event {
+        EventBuilder::metadata(&Metadata::new().name(name))
+            .custom_created_at(Timestamp::from(created_at))
+            .sign_with_keys(keys)
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_fetch_profiles_uses_newest_local_metadata() {
+        let dialog = memory_dialog();
+        let alice = Keys::generate();
+        dialog
+            .save_events([
+                metadata_event(&alice, "old", 1_700_000_000),
+                metadata_event(&alice, "alice", 1_700_000_100),
+            ])
+            .await
+            .unwrap();
+
+        let profile = dialog.fetch_profile(alice.public_key()).await.unwrap();
+        assert_eq!(profile.unwrap().name.as_deref(), Some("alice"));
+    }
+
+    #[tokio::test]
+    async fn test_publish_profile_keeps_other_fields() {
+        let dialog = memory_dialog();
+        assert!(dialog.get_my_profile().await.unwrap().is_none());
+        let elsewhere = Metadata::new().name("old").nip05("me@example.com");
+        let event = EventBuilder::metadata(&elsewhere)
+            .custom_created_at(Timestamp::from(1_700_000_000))
+            .sign_with_keys(&dialog.keys)
+            .unwrap();
+        dialog.save_events([event]).await.unwrap();
+
+        let err = dialog
+            .publish_profile(Some("alice"), None, Some("not a url"))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, DialogError::Config(_)));
+
+        // No relays are connected, but the profile is saved locally
+        let _ = dialog
+            .publish_profile(Some(" alice "), Some("Notes to self"), None)
+            .await;
+        let profile = dialog.get_my_profile().await.unwrap().unwrap();
+        assert_eq!(profile.name.as_deref(), Some("alice"));
+        assert_eq!(profile.display_name.as_deref(), Some("alice"));
+        assert_eq!(profile.about.as_deref(), Some("Notes to self"));
+        assert_eq!(profile.picture, None);
+        assert_eq!(profile.nip05.as_deref(), Some("me@example.com"));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_contacts_from_cache() {
+        let dialog = memory_dialog();
+        let (alice, bob, carol) = (Keys::generate(), Keys::generate(), Keys::generate());
+        let list = EventBuilder::contact_list([
+            nostr_sdk::Contact::new::<String>(carol.public_key(), None, None),
+            nostr_sdk::Contact::new(bob.public_key(), None, Some("Bobby")),
+            nostr_sdk::Contact::new::<String>(alice.public_key(), None, None),
+        ])
+        .sign_with_keys(&dialog.keys)
+        .unwrap();
+        dialog
+            .save_events([list, metadata_event(&alice, "alice", 1_700_000_000)])
+            .await
+            .unwrap();
+
+        // No relays are connected, so this exercises the local cache
+        let contacts = dialog.fetch_contacts().await.unwrap();
+        let names: Vec<Option<&str>> = contacts.iter().map(|c| c.display_name()).collect();
+        assert_eq!(names, vec![Some("alice"), Some("Bobby"), None]);
+        assert_eq!(contacts[2].pubkey, carol.public_key());
+    }
+}
@@ -0,0 +1,183 @@
+//! Raw relay traffic for diagnosing protocol problems with a relay
+
+use crate::Dialog;
+use nostr_sdk::prelude::*;
+use serde_json::Value;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+/// Frames kept for a slow reader before it starts missing them
+const DEBUG_FEED_CAPACITY: usize = 512;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameDirection {
+    Sent,
+    Received,
+}
+
+/// One protocol message to or from a relay
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RelayFrame {
+    pub at: Timestamp,
+    pub direction: FrameDirection,
+    /// None for messages sent through the pool to every relay
+    pub relay: Option<RelayUrl>,
+    /// Message type, e.g. "REQ", "EVENT", "OK" or "NOTICE"
+    pub kind: String,
+    /// The message as JSON, with event content and signatures redacted
+    pub json: String,
+}
+
+/// Replace event content and signatures, which are all a frame could leak
+fn redact(value: &mut Value) {
+    match value {
+        Value::Array(items) => items.iter_mut().for_each(redact),
+        Value::Object(object) => {
+            if let Some(Value::String(content)) = object.get_mut("content") {
+                if !content.is_empty() {
+                    *content = format!("<{} bytes redacted>", content.len());
+                }
+            }
+            if let Some(sig) = object.get_mut("sig") {
+                *sig = Value::String("<redacted>".into());
+            }
+        }
+        _ => {}
+    }
+}
+
+fn frame(
+    at: Timestamp,
+    direction: FrameDirection,
+    relay: Option<RelayUrl>,
+    json: &str,
+) -> RelayFrame {
+    let Ok(mut value) = serde_json::from_str::<Value>(json) else {
+        return RelayFrame {
+            at,
+            direction,
+            relay,
+            kind: "?".into(),
+            json: "<unparseable>".into(),
+        };
+    };
+    let kind = value[0].as_str().unwrap_or("?").to_string();
+    redact(&mut value);
+    RelayFrame {
+        at,
+        direction,
+        relay,
+        kind,
+        json: value.to_string(),
+    }
+}
+
+/// Fans frames out to [`Dialog::debug_messages`] receivers. Costs nothing
+/// while nobody is listening.
+#[derive(Debug)]
+pub(crate) struct DebugFeed {
+    tx: broadcast::Sender<RelayFrame>,
+    // The task forwarding received frames has been started
+    listening: AtomicBool,
+}
+
+impl Default for DebugFeed {
+    fn default() -> Self {
+        Self {
+            tx: broadcast::channel(DEBUG_FEED_CAPACITY).0,
+            listening: AtomicBool::new(false),
+        }
+    }
+}
+
+impl DebugFeed {
+    pub(crate) fn sent(&self, at: Timestamp, relay: Option<&RelayUrl>, message: &ClientMessage) {
+        if self.tx.receiver_count() > 0 {
+            let frame = frame(at, FrameDirection::Sent, relay.cloned(), &message.as_json());
+            let _ = self.tx.send(frame);
+        }
+    }
+
+    fn received(&self, at: Timestamp, relay: RelayUrl, message: &RelayMessage) {
+        if self.tx.receiver_count() > 0 {
+            let frame = frame(
+                at,
+                FrameDirection::Received,
+                Some(relay),
+                &message.as_json(),
+            );
+            let _ = self.tx.send(frame);
+        }
+    }
+}
+
+impl Dialog {
+    /// Stream of relay messages from now on: everything relays send, and
+    /// the EVENTs and subscription REQs this Dialog sends. Fetches and
+    /// negentropy syncs show up through the relays' replies. Frames are
+    /// dropped for a receiver that falls too far behind.
+    pub fn debug_messages(&self) -> broadcast::Receiver<RelayFrame> {
+        let rx = self.debug_feed.tx.subscribe();
+        if !self.debug_feed.listening.swap(true, Ordering::SeqCst) {
+            let feed = self.debug_feed.clone();
+            let clock = self.clock.clone();
+            let mut notifications = self.client.notifications();
+            tokio::spawn(async move {
+                loop {
+                    match notifications.recv().await {
+                        Ok(RelayPoolNotification::Message {
+                            relay_url, message, ..
+                        }) => feed.received(clock.now(), relay_url, &message),
+                        Ok(RelayPoolNotification::Shutdown) | Err(RecvError::Closed) => break,
+                        Ok(_) | Err(RecvError::Lagged(_)) => {}
+                    }
+                }
+                feed.listening.store(false, Ordering::SeqCst);
+            });
+        }
+        rx
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_frames_are_redacted() {
+        let keys = Keys::generate();
+        let event = EventBuilder::text_note("my secret plans")
+            .sign_with_keys(&keys)
+            .unwrap();
+        let url = RelayUrl::parse("wss://relay.example.com").unwrap();
+        let feed = DebugFeed::default();
+
+        // Nobody listening: nothing is serialized or kept
+        feed.sent(
+            event.created_at,
+            Some(&url),
+            &ClientMessage::event(event.clone()),
+        );
+        let mut rx = feed.tx.subscribe();
+        feed.sent(
+            event.created_at,
+            Some(&url),
+            &ClientMessage::event(event.clone()),
+        );
+        let notice = RelayMessage::notice("slow down");
+        feed.received(event.created_at, url.clone(), &notice);
+
+        let sent = rx.recv().await.unwrap();
+        assert_eq!(sent.direction, FrameDirection::Sent);
+        assert_eq!(sent.kind, "EVENT");
+        assert!(!sent.json.contains("my secret plans"));
+        assert!(!sent.json.contains(&event.sig.to_string()));
+        assert!(sent.json.contains(&event.id.to_hex()));
+
+        let received = rx.recv().await.unwrap();
+        assert_eq!(received.relay, Some(url));
+        assert_eq!(received.kind, "NOTICE");
+        assert!(received.json.contains("slow down"));
+        assert!(rx.try_recv().is_err());
+    }
+}
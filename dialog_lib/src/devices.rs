@@ -0,0 +1,236 @@
+use crate::{CaptureSource, Dialog, DialogError, Result};
+use nostr_sdk::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// Next to the database; holds this installation's device id
+pub(crate) const DEVICE_ID_FILE: &str = "device_id";
+/// Tag carrying the encrypted [`DeviceInfo`] on notes and state events
+const DEVICE_TAG: &str = "dialog_device";
+
+/// Who wrote an event, encrypted to our own key like the note itself
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct DeviceInfo {
+    pub(crate) id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) app_version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) source: Option<CaptureSource>,
+}
+
+/// A device that has written notes or app state with this key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Device {
+    pub id: String,
+    /// As last reported by the device, e.g. "Paul's iPad"
+    pub name: Option<String>,
+    /// Newest note or state change from it
+    pub last_active: Timestamp,
+    pub notes: usize,
+    /// This Dialog
+    pub is_current: bool,
+    /// Its notes are hidden; see [`Dialog::set_device_ignored`]
+    pub ignored: bool,
+}
+
+impl DeviceInfo {
+    /// A new random id, for storage that doesn't outlive the process
+    pub(crate) fn generate() -> Self {
+        // Any 16 random hex characters do; a throwaway key is a handy source
+        let id = Keys::generate().public_key().to_hex()[..16].to_string();
+        Self {
+            id,
+            ..Default::default()
+        }
+    }
+
+    /// The id kept at `path`, created on first use
+    pub(crate) fn load_or_create(path: &Path) -> Self {
+        if let Ok(id) = fs::read_to_string(path) {
+            let id = id.trim();
+            if !id.is_empty() {
+                return Self {
+                    id: id.to_string(),
+                    ..Default::default()
+                };
+            }
+        }
+        let device = Self::generate();
+        if let Err(e) = fs::write(path, &device.id) {
+            eprintln!("[lib] device id not saved: {e}");
+        }
+        device
+    }
+}
+
+/// The device recorded on `event`, if any
+pub(crate) fn event_device(keys: &Keys, event: &Event) -> Option<DeviceInfo> {
+    let tag = event
+        .tags
+        .iter()
+        .find(|tag| tag.kind() == TagKind::custom(DEVICE_TAG))?;
+    let json = nip44::decrypt(keys.secret_key(), &keys.public_key(), tag.content()?).ok()?;
+    serde_json::from_str(&json).ok()
+}
+
+impl Dialog {
+    /// Id of this installation, as shown in [`Dialog::devices`]
+    pub fn device_id(&self) -> String {
+        self.device.read().unwrap().id.clone()
+    }
+
+    /// Name other devices see for this one in [`Dialog::devices`]
+    pub fn set_device_name(&self, name: Option<String>) {
+        self.device.write().unwrap().name = name;
+    }
+
+    /// Tag naming this device, added to every note and state event. It is
+    /// encrypted, so relays can't tell devices apart.
+    pub(crate) fn device_tag(&self) -> Result<Tag> {
+        self.device_tag_from(None)
+    }
+
+    /// [`Dialog::device_tag`], naming `source` instead of the capture
+    /// source set for this Dialog
+    pub(crate) fn device_tag_from(&self, source: Option<CaptureSource>) -> Result<Tag> {
+        let mut device = self.device.read().unwrap().clone();
+        if source.is_some() {
+            device.source = source;
+        }
+        let json =
+            serde_json::to_string(&device).map_err(|e| DialogError::Database(e.to_string()))?;
+        let encrypted = nip44::encrypt(
+            self.keys.secret_key(),
+            &self.keys.public_key(),
+            json,
+            nip44::Version::default(),
+        )?;
+        Ok(Tag::custom(TagKind::custom(DEVICE_TAG), [encrypted]))
+    }
+
+    /// Every device seen in the local database, most recently active
+    /// first. Sync first to hear from the others.
+    pub async fn devices(&self) -> Result<Vec<Device>> {
+        let notes = self.notes_filter();
+        let events = self
+            .client
+            .database()
+            .query(vec![notes, self.app_state_filter()])
+            .await
+            .map_err(|e| DialogError::Database(e.to_string()))?;
+        let ignored = self.ignored_devices().await?;
+        let current = self.device.read().unwrap().clone();
+        let note_kinds = self.note_kinds();
+
+        let mut devices: HashMap<String, Device> = HashMap::new();
+        devices.insert(
+            current.id.clone(),
+            Device {
+                id: current.id.clone(),
+                name: current.name,
+                last_active: Timestamp::from(0),
+                notes: 0,
+                is_current: true,
+                ignored: false,
+            },
+        );
+        for event in events {
+            let Some(info) = event_device(&self.keys, &event) else {
+                continue;
+            };
+            let device = devices.entry(info.id.clone()).or_insert_with(|| Device {
+                ignored: ignored.contains(&info.id),
+                id: info.id,
+                name: None,
+                last_active: Timestamp::from(0),
+                notes: 0,
+                is_current: false,
+            });
+            if event.created_at >= device.last_active {
+                device.last_active = event.created_at;
+                if !device.is_current {
+                    device.name = info.name;
+                }
+            }
+            if note_kinds.contains(&event.kind) {
+                device.notes += 1;
+            }
+        }
+
+        let mut devices: Vec<Device> = devices.into_values().collect();
+        devices.sort_by(|a, b| b.last_active.cmp(&a.last_active).then(a.id.cmp(&b.id)));
+        Ok(devices)
+    }
+
+    /// Hide (or show again) the notes a device wrote, e.g. a lost tablet.
+    /// Synced with the rest of the app state; this device can't be ignored.
+    pub async fn set_device_ignored(&self, device_id: &str, ignored: bool) -> Result<()> {
+        if ignored && device_id == self.device_id() {
+            return Err(DialogError::Config("this device can't be ignored".into()));
+        }
+        let mut devices = self.ignored_devices().await?;
+        devices.retain(|id| id != device_id);
+        if ignored {
+            devices.push(device_id.to_string());
+        }
+        self.store_ignored_devices(devices).await
+    }
+
+    pub async fn ignored_devices(&self) -> Result<Vec<String>> {
+        Ok(self
+            .app_state()
+            .await?
+            .ignored_devices
+            .map(|devices| devices.value)
+            .unwrap_or_default())
+    }
+
+    /// Whether `event` was written by one of `ignored`
+    pub(crate) fn from_ignored_device(&self, event: &Event, ignored: &[String]) -> bool {
+        !ignored.is_empty()
+            && event_device(&self.keys, event).is_some_and(|info| ignored.contains(&info.id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::memory_dialog;
+
+    #[tokio::test]
+    async fn test_devices_are_listed_and_ignored() {
+        let phone = memory_dialog();
+        phone.set_device_name(Some("Phone".into()));
+        phone.save_note("From the phone").await.unwrap();
+
+        // An old iPad with the same key, whose note reached this database
+        let ipad = Dialog::in_memory(phone.keys.clone());
+        ipad.set_device_name(Some("iPad".into()));
+        let event = ipad
+            .build_note_event_at("From the iPad", Timestamp::now())
+            .await
+            .unwrap();
+        phone.save_events(vec![event]).await.unwrap();
+
+        let devices = phone.devices().await.unwrap();
+        assert_eq!(devices.len(), 2);
+        let old = devices.iter().find(|d| !d.is_current).unwrap();
+        assert_eq!(old.id, ipad.device_id());
+        assert_eq!(old.name.as_deref(), Some("iPad"));
+        assert_eq!(old.notes, 1);
+
+        phone.set_device_ignored(&old.id, true).await.unwrap();
+        let notes = phone.list_notes(10).await.unwrap();
+        assert_eq!(notes.len(), 1);
+        assert_eq!(notes[0].text, "From the phone");
+        assert!(phone.devices().await.unwrap().iter().any(|d| d.ignored));
+        assert!(phone
+            .set_device_ignored(&phone.device_id(), true)
+            .await
+            .is_err());
+    }
+}
@@ -0,0 +1,253 @@
+use crate::tags::strip_hashtags;
+use crate::{Dialog, Note, Result};
+use chrono::{DateTime, NaiveDate, Utc};
+use nostr_sdk::prelude::*;
+use std::ops::RangeBounds;
+
+/// Notes scanned for a digest; a week of notes is far below this
+const DIGEST_NOTE_LIMIT: usize = 2000;
+/// Heading for notes without tags
+const UNTAGGED: &str = "Untagged";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DigestFormat {
+    Markdown,
+    /// A standalone HTML page, e.g. for printing or email
+    Html,
+}
+
+/// Notes of one day, grouped under their first (matching) tag
+struct DigestDay {
+    date: NaiveDate,
+    groups: Vec<(String, Vec<Note>)>,
+}
+
+impl Dialog {
+    /// A readable digest of the notes created in `range`, grouped by day
+    /// and then by tag. With `tags` non-empty only notes carrying one of
+    /// them are included. Days are UTC.
+    pub async fn generate_digest(
+        &self,
+        range: impl RangeBounds<Timestamp>,
+        tags: &[String],
+        format: DigestFormat,
+    ) -> Result<String> {
+        let mut notes: Vec<Note> = self
+            .list_notes(DIGEST_NOTE_LIMIT)
+            .await?
+            .into_iter()
+            .filter(|note| range.contains(&note.created_at))
+            .filter(|note| tags.is_empty() || note.tags.iter().any(|t| tags.contains(t)))
+            .collect();
+        notes.sort_by_key(|note| note.created_at);
+
+        let days = group(notes, tags);
+        Ok(match format {
+            DigestFormat::Markdown => render_markdown(&days),
+            DigestFormat::Html => render_html(&days),
+        })
+    }
+}
+
+fn group(notes: Vec<Note>, tags: &[String]) -> Vec<DigestDay> {
+    let mut days: Vec<DigestDay> = Vec::new();
+    for note in notes {
+        let date = date_time(note.created_at).date_naive();
+        let heading = note
+            .tags
+            .iter()
+            .find(|tag| tags.is_empty() || tags.contains(tag))
+            .map_or_else(|| UNTAGGED.to_string(), |tag| format!("#{tag}"));
+
+        if days.last().is_none_or(|day| day.date != date) {
+            days.push(DigestDay {
+                date,
+                groups: Vec::new(),
+            });
+        }
+        let day = days.last_mut().expect("pushed above");
+        match day.groups.iter_mut().find(|(h, _)| *h == heading) {
+            Some((_, notes)) => notes.push(note),
+            None => day.groups.push((heading, vec![note])),
+        }
+    }
+    // Tagged groups alphabetically, untagged notes last
+    for day in &mut days {
+        day.groups
+            .sort_by_key(|(heading, _)| (heading == UNTAGGED, heading.clone()));
+    }
+    days
+}
+
+fn title(days: &[DigestDay]) -> String {
+    match (days.first(), days.last()) {
+        (Some(first), Some(last)) if first.date != last.date => {
+            format!("Notes digest: {} to {}", first.date, last.date)
+        }
+        (Some(day), _) => format!("Notes digest: {}", day.date),
+        _ => "Notes digest".to_string(),
+    }
+}
+
+fn render_markdown(days: &[DigestDay]) -> String {
+    let mut out = format!("# {}\n", title(days));
+    if days.is_empty() {
+        out.push_str("\nNo notes in this period.\n");
+    }
+    for day in days {
+        out.push_str(&format!("\n## {}\n", day.date.format("%A, %-d %B %Y")));
+        for (heading, notes) in &day.groups {
+            out.push_str(&format!("\n### {heading}\n\n"));
+            for note in notes {
+                let text = strip_hashtags(&note.text);
+                let mut lines = text.lines();
+                let first = lines.next().unwrap_or_default();
+                out.push_str(&format!("- {} {first}\n", time(note)));
+                for line in lines {
+                    if line.is_empty() {
+                        out.push('\n');
+                    } else {
+                        out.push_str(&format!("  {line}\n"));
+                    }
+                }
+            }
+        }
+    }
+    out
+}
+
+fn render_html(days: &[DigestDay]) -> String {
+    let title = escape_html(&title(days));
+    let mut out = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n\
+         <style>body{{font-family:sans-serif;max-width:40em;margin:auto}}\
+         .time{{color:#888;margin-right:.5em}}</style>\n</head>\n<body>\n<h1>{title}</h1>\n"
+    );
+    if days.is_empty() {
+        out.push_str("<p>No notes in this period.</p>\n");
+    }
+    for day in days {
+        out.push_str(&format!("<h2>{}</h2>\n", day.date.format("%A, %-d %B %Y")));
+        for (heading, notes) in &day.groups {
+            out.push_str(&format!("<h3>{}</h3>\n<ul>\n", escape_html(heading)));
+            for note in notes {
+                let text = escape_html(&strip_hashtags(&note.text)).replace('\n', "<br>\n");
+                out.push_str(&format!(
+                    "<li><span class=\"time\">{}</span>{text}</li>\n",
+                    time(note)
+                ));
+            }
+            out.push_str("</ul>\n");
+        }
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+/// A MIME email carrying the digest as text and HTML alternatives, ready
+/// to pipe into `sendmail -t`
+pub fn digest_email(subject: &str, markdown: &str, html: &str, to: Option<&str>) -> String {
+    let boundary = format!("dialog-digest-{}", Timestamp::now().as_u64());
+    let mut out = String::new();
+    if let Some(to) = to {
+        out.push_str(&format!("To: {to}\r\n"));
+    }
+    out.push_str(&format!("Subject: {}\r\n", encode_header(subject)));
+    out.push_str(&format!("Date: {}\r\n", Utc::now().to_rfc2822()));
+    out.push_str("MIME-Version: 1.0\r\n");
+    out.push_str(&format!(
+        "Content-Type: multipart/alternative; boundary=\"{boundary}\"\r\n\r\n"
+    ));
+    for (content_type, body) in [("text/plain", markdown), ("text/html", html)] {
+        out.push_str(&format!("--{boundary}\r\n"));
+        out.push_str(&format!("Content-Type: {content_type}; charset=utf-8\r\n"));
+        out.push_str("Content-Transfer-Encoding: 8bit\r\n\r\n");
+        out.push_str(&body.replace("\r\n", "\n").replace('\n', "\r\n"));
+        out.push_str("\r\n");
+    }
+    out.push_str(&format!("--{boundary}--\r\n"));
+    out
+}
+
+/// RFC 2047 base64 encoded-word when the header isn't plain ASCII
+fn encode_header(value: &str) -> String {
+    use base64::Engine;
+
+    if value.is_ascii() {
+        return value.to_string();
+    }
+    let encoded = base64::engine::general_purpose::STANDARD.encode(value);
+    format!("=?UTF-8?B?{encoded}?=")
+}
+
+fn date_time(timestamp: Timestamp) -> DateTime<Utc> {
+    DateTime::from_timestamp(timestamp.as_u64() as i64, 0).unwrap_or_default()
+}
+
+fn time(note: &Note) -> String {
+    date_time(note.created_at).format("%H:%M").to_string()
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::memory_dialog;
+
+    #[tokio::test]
+    async fn test_generate_digest_groups_by_day_and_tag() {
+        let dialog = memory_dialog();
+        let at = |secs| Timestamp::from(1_709_283_600 + secs);
+        // 2024-03-01 09:00 UTC and the day after
+        dialog.create_note_at("Plan sprint #work", at(0)).await.ok();
+        dialog.create_note_at("Milk\neggs #home", at(60)).await.ok();
+        dialog.create_note_at("No tags", at(120)).await.ok();
+        dialog
+            .create_note_at("Retro <notes> #work", at(86_400))
+            .await
+            .ok();
+
+        let markdown = dialog
+            .generate_digest(.., &[], DigestFormat::Markdown)
+            .await
+            .unwrap();
+        assert_eq!(
+            markdown,
+            "# Notes digest: 2024-03-01 to 2024-03-02\n\
+             \n## Friday, 1 March 2024\n\
+             \n### #home\n\n- 09:01 Milk\n  eggs\n\
+             \n### #work\n\n- 09:00 Plan sprint\n\
+             \n### Untagged\n\n- 09:02 No tags\n\
+             \n## Saturday, 2 March 2024\n\
+             \n### #work\n\n- 09:00 Retro <notes>\n"
+        );
+
+        let html = dialog
+            .generate_digest(at(86_400).., &["work".to_string()], DigestFormat::Html)
+            .await
+            .unwrap();
+        assert!(html.contains("<h1>Notes digest: 2024-03-02</h1>"));
+        assert!(html.contains("Retro &lt;notes&gt;</li>"));
+        assert!(!html.contains("Plan sprint"));
+    }
+
+    #[test]
+    fn test_digest_email() {
+        let email = digest_email(
+            "Week in notes ✓",
+            "# Hi",
+            "<h1>Hi</h1>",
+            Some("me@example.com"),
+        );
+        assert!(email.starts_with("To: me@example.com\r\nSubject: =?UTF-8?B?"));
+        assert!(email.contains("Content-Type: text/plain; charset=utf-8\r\n"));
+        assert!(email.contains("\r\n\r\n<h1>Hi</h1>\r\n"));
+        assert!(email.ends_with("--\r\n"));
+    }
+}
@@ -0,0 +1,182 @@
+//! Page fetches for link previews, behind the `enrich` feature
+
+use super::{EnrichSettings, LinkPreview};
+use crate::{Clock, DialogError, Result};
+use std::time::Duration;
+
+/// Only the start of a page is read; metadata lives in <head>
+const MAX_PAGE_BYTES: usize = 512 * 1024;
+const MAX_FIELD_CHARS: usize = 300;
+const FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub(crate) fn http_client(settings: &EnrichSettings, timeout: Duration) -> Result<reqwest::Client> {
+    let mut builder = reqwest::Client::builder()
+        .timeout(timeout)
+        .redirect(reqwest::redirect::Policy::limited(5));
+    if let Some(proxy) = &settings.proxy {
+        let proxy = reqwest::Proxy::all(proxy).map_err(|e| DialogError::Http(e.to_string()))?;
+        builder = builder.proxy(proxy);
+    }
+    builder
+        .build()
+        .map_err(|e| DialogError::Http(e.to_string()))
+}
+
+/// Fetch the pages behind `urls`, skipping any that fail
+pub(super) async fn fetch_previews(
+    settings: &EnrichSettings,
+    urls: &[String],
+    clock: &dyn Clock,
+) -> Result<Vec<LinkPreview>> {
+    let http = http_client(settings, FETCH_TIMEOUT)?;
+    let mut previews = Vec::new();
+    for url in urls {
+        match fetch_preview(&http, url, clock).await {
+            Ok(preview) => previews.push(preview),
+            Err(e) => eprintln!("[lib] enrich: {url}: {e}"),
+        }
+    }
+    Ok(previews)
+}
+
+async fn fetch_preview(
+    http: &reqwest::Client,
+    url: &str,
+    clock: &dyn Clock,
+) -> Result<LinkPreview> {
+    let mut response = http
+        .get(url)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| DialogError::Http(e.to_string()))?;
+
+    let is_html = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|ct| ct.to_str().ok())
+        .is_some_and(|ct| ct.contains("html"));
+    let mut page = Vec::new();
+    if is_html {
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .map_err(|e| DialogError::Http(e.to_string()))?
+        {
+            page.extend_from_slice(&chunk);
+            if page.len() >= MAX_PAGE_BYTES {
+                break;
+            }
+        }
+    }
+
+    let (title, description) = parse_page_metadata(&String::from_utf8_lossy(&page));
+    Ok(LinkPreview {
+        url: url.to_string(),
+        title,
+        description,
+        fetched_at: clock.now(),
+    })
+}
+
+/// Title and description from OpenGraph tags, falling back to <title> and
+/// the description meta tag
+fn parse_page_metadata(html: &str) -> (Option<String>, Option<String>) {
+    let mut og_title = None;
+    let mut og_description = None;
+    let mut description = None;
+
+    let lower = html.to_ascii_lowercase();
+    let mut offset = 0;
+    while let Some(start) = lower[offset..].find("<meta") {
+        let start = offset + start;
+        let Some(len) = lower[start..].find('>') else {
+            break;
+        };
+        let tag = &html[start..start + len];
+        offset = start + len;
+
+        let key = attribute(tag, "property").or_else(|| attribute(tag, "name"));
+        let Some(content) = attribute(tag, "content") else {
+            continue;
+        };
+        match key.map(|k| k.to_ascii_lowercase()).as_deref() {
+            Some("og:title") => og_title = og_title.or(Some(content)),
+            Some("og:description") => og_description = og_description.or(Some(content)),
+            Some("description") => description = description.or(Some(content)),
+            _ => {}
+        }
+    }
+
+    let title = og_title.or_else(|| {
+        let start = lower.find("<title")?;
+        let open = start + lower[start..].find('>')? + 1;
+        let close = open + lower[open..].find("</title")?;
+        Some(html[open..close].to_string())
+    });
+    (
+        title.and_then(clean_field),
+        og_description.or(description).and_then(clean_field),
+    )
+}
+
+/// Value of `name="..."` (or single-quoted) inside a tag
+fn attribute(tag: &str, name: &str) -> Option<String> {
+    let lower = tag.to_ascii_lowercase();
+    let mut offset = 0;
+    while let Some(found) = lower[offset..].find(name) {
+        let at = offset + found;
+        offset = at + name.len();
+        // Must be a whole attribute name, not e.g. "og:name"
+        if at > 0 && !lower.as_bytes()[at - 1].is_ascii_whitespace() {
+            continue;
+        }
+        let rest = tag[offset..].trim_start().strip_prefix('=')?.trim_start();
+        let quote = rest.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+        let value = &rest[1..];
+        return Some(value[..value.find(quote)?].to_string());
+    }
+    None
+}
+
+fn clean_field(value: String) -> Option<String> {
+    let decoded = value
+        .replace("&amp;", "&")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#x27;", "'");
+    let collapsed = decoded.split_whitespace().collect::<Vec<_>>().join(" ");
+    if collapsed.is_empty() {
+        return None;
+    }
+    Some(collapsed.chars().take(MAX_FIELD_CHARS).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_page_metadata() {
+        let html = r#"<html><head>
+            <title>Fallback  Title</title>
+            <meta name="description" content="Plain description">
+            <meta property='og:title' content='Tom &amp; Jerry'>
+        </head></html>"#;
+        assert_eq!(
+            parse_page_metadata(html),
+            (
+                Some("Tom & Jerry".to_string()),
+                Some("Plain description".to_string())
+            )
+        );
+
+        let bare = "<TITLE>\n  Only a title\n</TITLE>";
+        assert_eq!(
+            parse_page_metadata(bare),
+            (Some("Only a title".into()), None)
+        );
+    }
+}
@@ -0,0 +1,212 @@
+use crate::kinds::APP_DATA_KIND;
+use crate::{Clock, Dialog, DialogError, Result};
+use nostr_sdk::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "enrich")]
+mod fetch;
+
+#[cfg(feature = "enrich")]
+use fetch::fetch_previews;
+#[cfg(feature = "enrich")]
+pub(crate) use fetch::http_client;
+
+/// d-tag prefix for the locally stored, encrypted previews of one note
+const PREVIEW_D_TAG: &str = "dialog_link_preview";
+/// Links fetched per note; the rest are ignored
+const MAX_LINKS_PER_NOTE: usize = 3;
+
+/// Title and description of a linked page, stored so previews work offline
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LinkPreview {
+    pub url: String,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub fetched_at: Timestamp,
+}
+
+/// Link enrichment is off by default since fetching a page tells its
+/// server that someone looked at the link
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EnrichSettings {
+    /// Fetch previews for links in new notes automatically
+    pub auto_enrich: bool,
+    /// Proxy for page fetches, e.g. `socks5h://127.0.0.1:9050` for Tor
+    pub proxy: Option<String>,
+}
+
+impl Dialog {
+    pub fn set_enrich_settings(&self, settings: EnrichSettings) {
+        *self.enrich_settings.write().unwrap() = settings;
+    }
+
+    pub fn enrich_settings(&self) -> EnrichSettings {
+        self.enrich_settings.read().unwrap().clone()
+    }
+
+    /// Fetch and store previews for the links in a note. Previews already
+    /// stored are returned without touching the network.
+    pub async fn enrich_note(&self, note_id: &EventId) -> Result<Vec<LinkPreview>> {
+        let stored = self.link_previews(note_id).await?;
+        if !stored.is_empty() {
+            return Ok(stored);
+        }
+        let note = self
+            .get_note(note_id)
+            .await?
+            .ok_or(DialogError::NoteNotFound(*note_id))?;
+        enrich(
+            &self.client,
+            &self.keys,
+            self.clock.as_ref(),
+            &self.enrich_settings(),
+            note_id,
+            &note.text,
+        )
+        .await
+    }
+
+    /// Previews stored for a note by an earlier [`Dialog::enrich_note`]
+    pub async fn link_previews(&self, note_id: &EventId) -> Result<Vec<LinkPreview>> {
+        let filter = Filter::new()
+            .author(self.keys.public_key())
+            .kind(Kind::from(APP_DATA_KIND))
+            .identifier(preview_d_tag(note_id));
+        let events = self
+            .client
+            .database()
+            .query(vec![filter])
+            .await
+            .map_err(|e| DialogError::Database(e.to_string()))?;
+
+        let Some(event) = events.into_iter().max_by_key(|e| e.created_at) else {
+            return Ok(Vec::new());
+        };
+        let json = self.decrypt_event(&event)?;
+        serde_json::from_str(&json).map_err(|e| DialogError::Database(e.to_string()))
+    }
+
+    /// Enrich a freshly created note in the background when auto-enrich is on
+    pub(crate) fn maybe_auto_enrich(&self, note_id: EventId, text: &str) {
+        let settings = self.enrich_settings();
+        if !cfg!(feature = "enrich") || !settings.auto_enrich || extract_urls(text).is_empty() {
+            return;
+        }
+        let client = self.client.clone();
+        let keys = self.keys.clone();
+        let clock = self.clock.clone();
+        let text = text.to_string();
+        tokio::spawn(async move {
+            let result = enrich(&client, &keys, clock.as_ref(), &settings, &note_id, &text).await;
+            if let Err(e) = result {
+                eprintln!("[lib] auto-enrich {note_id} failed: {e}");
+            }
+        });
+    }
+}
+
+/// Fetch previews for the links in `text` and store them encrypted in the
+/// local database. Takes the pieces it needs so it can run in a spawned task.
+async fn enrich(
+    client: &Client,
+    keys: &Keys,
+    clock: &dyn Clock,
+    settings: &EnrichSettings,
+    note_id: &EventId,
+    text: &str,
+) -> Result<Vec<LinkPreview>> {
+    let urls = extract_urls(text);
+    if urls.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let urls = &urls[..urls.len().min(MAX_LINKS_PER_NOTE)];
+    let previews = fetch_previews(settings, urls, clock).await?;
+    if previews.is_empty() {
+        return Ok(previews);
+    }
+
+    let json =
+        serde_json::to_string(&previews).map_err(|e| DialogError::Database(e.to_string()))?;
+    let encrypted = nip44::encrypt(
+        keys.secret_key(),
+        &keys.public_key(),
+        json,
+        nip44::Version::default(),
+    )?;
+    // Local only: previews are cheap to refetch on other devices
+    let event = EventBuilder::new(Kind::from(APP_DATA_KIND), encrypted)
+        .tag(Tag::identifier(preview_d_tag(note_id)))
+        .sign(keys)
+        .await?;
+    client
+        .database()
+        .save_event(&event)
+        .await
+        .map_err(|e| DialogError::Database(e.to_string()))?;
+    eprintln!(
+        "[lib] enrich: stored {} previews for {note_id}",
+        previews.len()
+    );
+    Ok(previews)
+}
+
+/// Without the `enrich` feature there is no HTTP client to fetch with
+#[cfg(not(feature = "enrich"))]
+async fn fetch_previews(
+    _settings: &EnrichSettings,
+    _urls: &[String],
+    _clock: &dyn Clock,
+) -> Result<Vec<LinkPreview>> {
+    Err(DialogError::Http(
+        "built without the enrich feature".to_string(),
+    ))
+}
+
+fn preview_d_tag(note_id: &EventId) -> String {
+    format!("{PREVIEW_D_TAG}:{}", note_id.to_hex())
+}
+
+/// http(s) links in note text, de-duplicated, without trailing punctuation
+pub fn extract_urls(text: &str) -> Vec<String> {
+    let mut urls: Vec<String> = Vec::new();
+    for word in text.split_whitespace() {
+        let word = word.trim_start_matches(['(', '<', '"', '\'']);
+        if !(word.starts_with("https://") || word.starts_with("http://")) {
+            continue;
+        }
+        let url = word.trim_end_matches(['.', ',', ';', ':', '!', '?', ')', '>', '"', '\'']);
+        let has_host = url
+            .split_once("://")
+            .is_some_and(|(_, rest)| !rest.is_empty());
+        if has_host && !urls.iter().any(|u| u == url) {
+            urls.push(url.to_string());
+        }
+    }
+    urls
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::memory_dialog;
+
+    #[test]
+    fn test_extract_urls() {
+        let urls = extract_urls(
+            "Read (https://example.com/a?b=1). Also http://x.org, https://example.com/a?b=1 and ftp://no",
+        );
+        assert_eq!(urls, vec!["https://example.com/a?b=1", "http://x.org"]);
+        assert!(extract_urls("https:// nothing").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_enrich_without_links_is_a_no_op() {
+        let dialog = memory_dialog();
+        let event = dialog.build_note_event("no links here").await.unwrap();
+        dialog.save_events([event.clone()]).await.unwrap();
+
+        assert!(dialog.enrich_note(&event.id).await.unwrap().is_empty());
+        assert!(dialog.link_previews(&event.id).await.unwrap().is_empty());
+    }
+}
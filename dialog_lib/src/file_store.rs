@@ -0,0 +1,126 @@
+use crate::{DialogError, Result};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Write `contents` to `path` through a temporary file next to it and a
+/// rename, so a crash never leaves half a file behind and readers (widgets,
+/// apps watching a mirror) never see one.
+pub(crate) fn write_atomic(path: &Path, contents: impl AsRef<[u8]>) -> Result<()> {
+    let name = path
+        .file_name()
+        .ok_or_else(|| DialogError::Database(format!("not a file: {}", path.display())))?;
+    let tmp = path.with_file_name(format!(".{}.tmp", name.to_string_lossy()));
+    fs::write(&tmp, contents)?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// Entries of a JSON Lines file, one object per line, skipping lines that
+/// don't parse. A missing file has none. `what` names the file in logs.
+pub(crate) fn read_jsonl<T: DeserializeOwned>(path: &Path, what: &str) -> Result<Vec<T>> {
+    match fs::read_to_string(path) {
+        Ok(contents) => Ok(parse_jsonl(&contents, what)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub(crate) fn parse_jsonl<T: DeserializeOwned>(contents: &str, what: &str) -> Vec<T> {
+    contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| match serde_json::from_str(line) {
+            Ok(entry) => Some(entry),
+            Err(e) => {
+                eprintln!("[lib] {what}: skipping unreadable entry: {e}");
+                None
+            }
+        })
+        .collect()
+}
+
+/// A value kept as JSON in one file of the profile's data directory, or
+/// only in memory without a path (in-memory profiles and tests). Read on
+/// first use; a missing file reads as `T::default()`.
+#[derive(Debug, Default)]
+pub(crate) struct JsonStore<T> {
+    path: Option<PathBuf>,
+    memory: Mutex<Option<T>>,
+}
+
+impl<T: Serialize + DeserializeOwned + Default + Clone> JsonStore<T> {
+    pub(crate) fn at(path: PathBuf) -> Self {
+        Self {
+            path: Some(path),
+            memory: Mutex::default(),
+        }
+    }
+
+    fn load(&self) -> Result<T> {
+        let Some(path) = &self.path else {
+            return Ok(T::default());
+        };
+        match fs::read_to_string(path) {
+            Ok(json) => {
+                serde_json::from_str(&json).map_err(|e| DialogError::Database(e.to_string()))
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(T::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn save(&self, value: &T) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let json =
+            serde_json::to_string(value).map_err(|e| DialogError::Database(e.to_string()))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        write_atomic(path, json)
+    }
+
+    /// Run `f` on the value, saving it afterwards if `f` returns true
+    pub(crate) fn update<R>(&self, f: impl FnOnce(&mut T) -> (R, bool)) -> Result<R> {
+        let mut memory = self.memory.lock().unwrap();
+        let value = match memory.as_mut() {
+            Some(value) => value,
+            None => memory.insert(self.load()?),
+        };
+        let (result, changed) = f(value);
+        if changed {
+            self.save(value)?;
+        }
+        Ok(result)
+    }
+
+    pub(crate) fn get(&self) -> Result<T> {
+        self.update(|value| (value.clone(), false))
+    }
+
+    pub(crate) fn set(&self, value: T) -> Result<()> {
+        self.update(|current| {
+            *current = value;
+            ((), true)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::OutboxEntry;
+    use nostr_sdk::Timestamp;
+
+    #[test]
+    fn test_parse_jsonl_skips_garbage() {
+        let contents = "{\"text\":\"ok\",\"created_at\":1700000000}\nnot json\n\n";
+        let entries: Vec<OutboxEntry> = parse_jsonl(contents, "outbox");
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].created_at, Timestamp::from(1_700_000_000));
+    }
+}
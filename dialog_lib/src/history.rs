@@ -0,0 +1,190 @@
+use crate::audit::AuditAction;
+use crate::migrate::{migrated_from, superseded_at};
+use crate::{normalize_tag, Dialog, DialogError, Note, Result};
+use nostr_sdk::prelude::*;
+use std::collections::{HashMap, HashSet};
+
+/// A note's read/pin/archive flags as recorded at some time; None where
+/// nothing was recorded
+#[derive(Debug, Default, Clone, Copy)]
+struct FlagsAt {
+    read: Option<bool>,
+    pinned: Option<bool>,
+    archived: Option<bool>,
+}
+
+impl Dialog {
+    /// The note list as it was at `at`, newest first, optionally only notes
+    /// tagged `tag`.
+    ///
+    /// Notes written later are left out, and notes retagged since show the
+    /// version current at `at`. Notes locked since show the locked copy, as
+    /// locking removes the original. Flags come from the local audit
+    /// log, so changes made on other devices only count once they are the
+    /// latest value and older than `at`.
+    pub async fn notes_as_of(&self, at: Timestamp, tag: Option<&str>) -> Result<Vec<Note>> {
+        let tag = match tag {
+            Some(tag) => match normalize_tag(tag) {
+                Some(tag) => Some(tag),
+                None => return Ok(Vec::new()),
+            },
+            None => None,
+        };
+        let filter = self.notes_filter();
+        let stored = self
+            .client
+            .database()
+            .query(vec![filter])
+            .await
+            .map_err(|e| DialogError::Database(e.to_string()))?;
+        let stored_ids: HashSet<EventId> = stored.iter().map(|event| event.id).collect();
+        let events: Vec<Event> = stored
+            .into_iter()
+            // Copies made after `at` don't hide their originals yet, unless
+            // the original is gone
+            .filter(|event| {
+                superseded_at(event).unwrap_or(event.created_at) <= at
+                    || migrated_from(event).is_some_and(|original| !stored_ids.contains(&original))
+            })
+            .collect();
+        let replaces: HashMap<EventId, EventId> = events
+            .iter()
+            .filter_map(|event| Some((event.id, migrated_from(event)?)))
+            .collect();
+
+        let flags = self.flags_as_of(at)?;
+        let state = self.app_state().await?;
+        let mut notes = self.notes_from_events(events).await;
+        // Private notes' real times are only known once decrypted
+        notes.retain(|note| note.created_at <= at);
+        if let Some(tag) = &tag {
+            notes.retain(|note| note.tags.contains(tag));
+        }
+        for note in &mut notes {
+            // Copies inherit their original's flags, so look back through
+            // what they replace
+            let mut recorded = FlagsAt::default();
+            let mut id = Some(note.id);
+            while let Some(current) = id {
+                let found = flags.get(&current).copied().unwrap_or_default();
+                recorded.read = recorded.read.or(found.read);
+                recorded.pinned = recorded.pinned.or(found.pinned);
+                recorded.archived = recorded.archived.or(found.archived);
+                id = replaces.get(&current).copied();
+            }
+            // Fall back to the synced value if it was set before `at`
+            let note_state = state.note(&note.id);
+            let before = |value: &Option<crate::app_state::Lww<bool>>| {
+                value
+                    .as_ref()
+                    .filter(|lww| lww.updated_at <= at.as_u64())
+                    .is_some_and(|lww| lww.value)
+            };
+            note.is_read = recorded.read.unwrap_or_else(|| before(&note_state.read));
+            note.is_pinned = recorded
+                .pinned
+                .unwrap_or_else(|| before(&note_state.pinned));
+            note.is_archived = recorded
+                .archived
+                .unwrap_or_else(|| before(&note_state.archived));
+        }
+        Ok(notes)
+    }
+
+    /// Every version of a note, from `note_id` back through the notes it
+    /// replaced, newest first. Kind migrations are left out, since their
+    /// text is the same as the version before.
+    pub async fn edit_history(&self, note_id: &EventId) -> Result<Vec<Note>> {
+        let notes = self.list_notes_with(usize::MAX, true).await?;
+        let by_id: HashMap<EventId, &Note> = notes.iter().map(|note| (note.id, note)).collect();
+        let mut history: Vec<Note> = Vec::new();
+        let mut next = Some(*note_id);
+        while let Some(id) = next {
+            let Some(note) = by_id.get(&id) else {
+                break;
+            };
+            if history.last().is_none_or(|newer| newer.text != note.text) {
+                history.push((*note).clone());
+            }
+            next = note.edit_of;
+        }
+        if history.is_empty() {
+            return Err(DialogError::NoteNotFound(*note_id));
+        }
+        Ok(history)
+    }
+
+    /// The last flag values the audit log recorded for each note up to `at`
+    fn flags_as_of(&self, at: Timestamp) -> Result<HashMap<EventId, FlagsAt>> {
+        let mut flags: HashMap<EventId, FlagsAt> = HashMap::new();
+        for entry in self.audit_log(..=at)? {
+            let Some(note_id) = entry.note_id else {
+                continue;
+            };
+            let Ok(value) = entry.detail.parse::<bool>() else {
+                continue;
+            };
+            let note = flags.entry(note_id).or_default();
+            match entry.action {
+                AuditAction::SetRead => note.read = Some(value),
+                AuditAction::SetPinned => note.pinned = Some(value),
+                AuditAction::SetArchived => note.archived = Some(value),
+                _ => {}
+            }
+        }
+        Ok(flags)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::memory_dialog;
+
+    const DAY: u64 = 24 * 60 * 60;
+
+    #[tokio::test]
+    async fn test_notes_as_of() {
+        let dialog = memory_dialog();
+        let now = Timestamp::now().as_u64();
+        let old = dialog
+            .build_note_event_at("Quarterly plan #work", Timestamp::from(now - 30 * DAY))
+            .await
+            .unwrap();
+        let recent = dialog
+            .build_note_event_at("Yesterday's errand", Timestamp::from(now - DAY))
+            .await
+            .unwrap();
+        dialog
+            .save_events([old.clone(), recent.clone()])
+            .await
+            .unwrap();
+        dialog.set_pinned(&old.id, true).await.unwrap();
+        let report = dialog.add_tags(&[old.id], &["q3".into()]).await;
+        assert_eq!(report.updated.len(), 1);
+
+        let two_weeks_ago = Timestamp::from(now - 14 * DAY);
+        let then = dialog.notes_as_of(two_weeks_ago, None).await.unwrap();
+        assert_eq!(then.len(), 1);
+        assert_eq!(then[0].id, old.id, "the retagged copy didn't exist yet");
+        assert_eq!(then[0].text, "Quarterly plan #work");
+        assert!(!then[0].is_pinned, "it was pinned later");
+        assert!(dialog
+            .notes_as_of(two_weeks_ago, Some("q3"))
+            .await
+            .unwrap()
+            .is_empty());
+
+        let current = dialog.notes_as_of(Timestamp::now(), None).await.unwrap();
+        let texts: Vec<&str> = current.iter().map(|n| n.text.as_str()).collect();
+        assert_eq!(
+            texts,
+            vec!["Yesterday's errand", "Quarterly plan #work\n#q3"]
+        );
+        assert!(current[1].is_pinned);
+
+        let history = dialog.edit_history(&current[1].id).await.unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[1].id, old.id);
+    }
+}
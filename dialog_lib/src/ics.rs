@@ -0,0 +1,191 @@
+use crate::tags::strip_hashtags;
+use crate::{Dialog, Note, Provenance, Result};
+use nostr_sdk::prelude::*;
+use std::io::Write;
+use std::ops::RangeBounds;
+
+/// Lines are folded at this many octets (RFC 5545 section 3.1)
+const MAX_LINE_OCTETS: usize = 75;
+/// Longest SUMMARY taken from a note's first line
+const MAX_SUMMARY_CHARS: usize = 80;
+const PRODUCT_ID: &str = "-//dialog//notes//EN";
+
+impl Dialog {
+    /// Write an iCalendar feed with one event per note whose due date falls
+    /// in `range`, each with an alert at the due time and where the note
+    /// came from. Returns how many notes were exported.
+    pub async fn export_ics<W: Write>(
+        &self,
+        mut writer: W,
+        range: impl RangeBounds<Timestamp>,
+    ) -> Result<usize> {
+        let state = self.app_state().await?;
+        let mut due: Vec<(EventId, Timestamp)> = state
+            .notes
+            .iter()
+            .filter_map(|(id, note_state)| Some((EventId::from_hex(id).ok()?, note_state.due()?)))
+            .filter(|(_, due)| range.contains(due))
+            .collect();
+        due.sort_by_key(|(id, due)| (*due, *id));
+
+        let mut notes = Vec::with_capacity(due.len());
+        for (id, due) in due {
+            match self.get_note(&id).await? {
+                Some(note) => notes.push((note, due, self.note_provenance(&id).await?)),
+                None => eprintln!("[lib] export_ics: due note {id} not stored locally"),
+            }
+        }
+
+        writer.write_all(calendar(&notes, self.now()).as_bytes())?;
+        eprintln!("[lib] export_ics: exported {} notes", notes.len());
+        Ok(notes.len())
+    }
+}
+
+fn calendar(notes: &[(Note, Timestamp, Option<Provenance>)], now: Timestamp) -> String {
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        format!("PRODID:{PRODUCT_ID}"),
+        "CALSCALE:GREGORIAN".to_string(),
+    ];
+    for (note, due, provenance) in notes {
+        let summary = summary(&note.text, MAX_SUMMARY_CHARS);
+        lines.extend([
+            "BEGIN:VEVENT".to_string(),
+            format!("UID:{}@dialog", note.id.to_hex()),
+            format!("DTSTAMP:{}", format_time(now)),
+            format!("DTSTART:{}", format_time(*due)),
+            format!("SUMMARY:{}", escape_text(&summary)),
+            format!("DESCRIPTION:{}", escape_text(&note.text)),
+        ]);
+        if !note.tags.is_empty() {
+            let tags: Vec<String> = note.tags.iter().map(|t| escape_text(t)).collect();
+            lines.push(format!("CATEGORIES:{}", tags.join(",")));
+        }
+        if let Some(provenance) = provenance {
+            lines.push(format!(
+                "X-DIALOG-PROVENANCE:{}",
+                escape_text(&provenance.to_string())
+            ));
+        }
+        lines.extend([
+            "BEGIN:VALARM".to_string(),
+            "ACTION:DISPLAY".to_string(),
+            format!("DESCRIPTION:{}", escape_text(&summary)),
+            "TRIGGER:PT0S".to_string(),
+            "END:VALARM".to_string(),
+            "END:VEVENT".to_string(),
+        ]);
+    }
+    lines.push("END:VCALENDAR".to_string());
+
+    lines.iter().map(|line| fold_line(line)).collect()
+}
+
+/// First non-empty line without hashtags, shortened to `max_chars`, for
+/// calendar and search titles
+pub(crate) fn summary(text: &str, max_chars: usize) -> String {
+    let stripped = strip_hashtags(text);
+    let first = stripped
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .unwrap_or("Note");
+    if first.chars().count() <= max_chars {
+        return first.to_string();
+    }
+    let short: String = first.chars().take(max_chars - 1).collect();
+    format!("{}…", short.trim_end())
+}
+
+/// UTC date-time form, e.g. `20240301T090000Z`
+fn format_time(timestamp: Timestamp) -> String {
+    chrono::DateTime::from_timestamp(timestamp.as_u64() as i64, 0)
+        .unwrap_or_default()
+        .format("%Y%m%dT%H%M%SZ")
+        .to_string()
+}
+
+fn escape_text(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+/// Fold a content line into CRLF-terminated chunks of at most 75 octets,
+/// never splitting a UTF-8 character
+fn fold_line(line: &str) -> String {
+    let mut out = String::with_capacity(line.len() + 8);
+    let mut width = 0;
+    for ch in line.chars() {
+        // Continuation lines start with a space, which counts towards the limit
+        if width + ch.len_utf8() > MAX_LINE_OCTETS {
+            out.push_str("\r\n ");
+            width = 1;
+        }
+        out.push(ch);
+        width += ch.len_utf8();
+    }
+    out.push_str("\r\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::memory_dialog;
+
+    #[test]
+    fn test_fold_line() {
+        let line = format!("DESCRIPTION:{}", "é".repeat(50));
+        let folded = fold_line(&line);
+        assert!(folded.split("\r\n").all(|l| l.len() <= MAX_LINE_OCTETS));
+        assert_eq!(folded.replace("\r\n ", "").trim_end(), line);
+    }
+
+    #[test]
+    fn test_summary_and_escaping() {
+        assert_eq!(
+            summary("#work\nCall Bob; then, email\nmore", MAX_SUMMARY_CHARS),
+            "Call Bob; then, email"
+        );
+        assert_eq!(escape_text("a;b,c\\d\ne"), "a\\;b\\,c\\\\d\\ne");
+        assert_eq!(
+            format_time(Timestamp::from(1_709_283_600)),
+            "20240301T090000Z"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_export_ics_only_due_notes_in_range() {
+        let dialog = memory_dialog();
+        dialog.set_device_name(Some("Phone".into()));
+        let due = dialog.save_note("Renew passport #admin").await.unwrap();
+        let later = dialog.save_note("Dentist").await.unwrap();
+        dialog.save_note("No due date").await.unwrap();
+        dialog
+            .set_due(&due.id, Some(Timestamp::from(1_709_283_600)))
+            .await
+            .unwrap();
+        dialog
+            .set_due(&later.id, Some(Timestamp::from(1_800_000_000)))
+            .await
+            .unwrap();
+
+        let mut out = Vec::new();
+        let range = Timestamp::from(1_700_000_000)..Timestamp::from(1_750_000_000);
+        assert_eq!(dialog.export_ics(&mut out, range).await.unwrap(), 1);
+
+        let ics = String::from_utf8(out).unwrap();
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\n"));
+        assert!(ics.ends_with("END:VCALENDAR\r\n"));
+        assert!(ics.contains("DTSTART:20240301T090000Z\r\n"));
+        assert!(ics.contains("SUMMARY:Renew passport\r\n"));
+        assert!(ics.contains("CATEGORIES:admin\r\n"));
+        assert!(ics.contains("X-DIALOG-PROVENANCE:Phone\r\n"));
+        assert!(!ics.contains("Dentist"));
+    }
+}
@@ -0,0 +1,126 @@
+use crate::{Dialog, Note, Result};
+use std::collections::HashMap;
+use std::future::Future;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::OnceCell;
+
+/// How long a send is remembered. Repeats within this window return the
+/// first note instead of creating another.
+pub const IDEMPOTENCY_WINDOW: Duration = Duration::from_secs(30);
+
+/// Identifies one intended send, so a double tap or a retry after a
+/// timeout doesn't create the note twice
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct IdempotencyKey(String);
+
+impl IdempotencyKey {
+    /// Key for sending `text` from the client identified by `nonce`, e.g.
+    /// a random id chosen when the client starts
+    pub fn new(text: &str, nonce: &str) -> Self {
+        // Fixed keys, so the same send hashes the same across runs
+        let mut hasher = DefaultHasher::new();
+        (nonce, text).hash(&mut hasher);
+        Self(format!("{:016x}", hasher.finish()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+/// A key supplied by the caller as is, e.g. a UUID made per compose sheet
+impl From<String> for IdempotencyKey {
+    fn from(key: String) -> Self {
+        Self(key)
+    }
+}
+
+struct RecentSend {
+    started: Instant,
+    // Set once the first attempt succeeds; later callers wait on it
+    note: Arc<OnceCell<Note>>,
+}
+
+/// Sends seen within the last [`IDEMPOTENCY_WINDOW`]
+#[derive(Default)]
+pub(crate) struct RecentSends {
+    sends: Mutex<HashMap<IdempotencyKey, RecentSend>>,
+}
+
+impl Dialog {
+    /// [`Dialog::create_note`], unless a note was already created with
+    /// `key` in the last [`IDEMPOTENCY_WINDOW`], in which case that note
+    /// is returned. Concurrent calls with one key create a single note; a
+    /// failed attempt can be retried with the same key.
+    pub async fn create_note_idempotent(&self, text: &str, key: &IdempotencyKey) -> Result<Note> {
+        self.idempotent(key, || self.create_note(text)).await
+    }
+
+    /// [`Dialog::save_note`] with the same duplicate check, for notes
+    /// written while offline
+    pub async fn save_note_idempotent(&self, text: &str, key: &IdempotencyKey) -> Result<Note> {
+        self.idempotent(key, || self.save_note(text)).await
+    }
+
+    pub(crate) async fn idempotent<F, Fut>(&self, key: &IdempotencyKey, create: F) -> Result<Note>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<Note>>,
+    {
+        let cell = {
+            let now = self.clock.instant();
+            let mut sends = self.recent_sends.sends.lock().unwrap();
+            sends.retain(|_, send| now.duration_since(send.started) < IDEMPOTENCY_WINDOW);
+            sends
+                .entry(key.clone())
+                .or_insert_with(|| RecentSend {
+                    started: now,
+                    note: Arc::default(),
+                })
+                .note
+                .clone()
+        };
+        if cell.initialized() {
+            eprintln!("[lib] create_note: duplicate send {} ignored", key.as_str());
+        }
+        cell.get_or_try_init(create).await.cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::memory_dialog;
+    use crate::DialogError;
+
+    #[tokio::test]
+    async fn test_duplicate_sends_create_one_note() {
+        let dialog = memory_dialog();
+        let key = IdempotencyKey::new("Buy milk", "client-1");
+        assert_eq!(key, IdempotencyKey::new("Buy milk", "client-1"));
+        assert_ne!(key, IdempotencyKey::new("Buy milk", "client-2"));
+
+        let failed = dialog
+            .idempotent(&key, || async {
+                Err(DialogError::NotPublished("offline".into()))
+            })
+            .await;
+        assert!(failed.is_err());
+
+        let (first, second) = tokio::join!(
+            dialog.idempotent(&key, || dialog.save_note("Buy milk")),
+            dialog.idempotent(&key, || dialog.save_note("Buy milk")),
+        );
+        assert_eq!(first.unwrap().id, second.unwrap().id);
+        assert_eq!(dialog.list_notes(10).await.unwrap().len(), 1);
+
+        let other = IdempotencyKey::new("Buy milk", "client-2");
+        dialog
+            .idempotent(&other, || dialog.save_note("Buy milk"))
+            .await
+            .unwrap();
+        assert_eq!(dialog.list_notes(10).await.unwrap().len(), 2);
+    }
+}
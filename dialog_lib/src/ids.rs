@@ -0,0 +1,131 @@
+use crate::migrate::migrated_from;
+use crate::{Dialog, DialogError, Note, Result};
+use nostr_sdk::prelude::*;
+use std::collections::HashMap;
+
+/// How note ids are written out for people to copy
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NoteIdFormat {
+    /// 64 hex characters, as stored
+    #[default]
+    Hex,
+    /// NIP-19 `note1...`
+    Note,
+    /// NIP-19 `nevent1...`, which other clients can open directly
+    Nevent,
+}
+
+/// Parse a note id written as hex, `note1...` or `nevent1...`, with or
+/// without a `nostr:` prefix
+pub fn parse_note_id(input: &str) -> Result<EventId> {
+    let input = input.trim();
+    let id = input.strip_prefix("nostr:").unwrap_or(input);
+    if let Ok(id) = EventId::from_hex(id) {
+        return Ok(id);
+    }
+    match Nip19::from_bech32(id) {
+        Ok(Nip19::EventId(id)) => Ok(id),
+        Ok(Nip19::Event(event)) => Ok(event.event_id),
+        _ => Err(DialogError::InvalidNoteId(input.to_string())),
+    }
+}
+
+pub fn format_note_id(id: &EventId, format: NoteIdFormat) -> String {
+    let bech32 = match format {
+        NoteIdFormat::Hex => return id.to_hex(),
+        NoteIdFormat::Note => id.to_bech32(),
+        NoteIdFormat::Nevent => Nip19Event::new(*id, Vec::<String>::new()).to_bech32(),
+    };
+    // Encoding a valid id can't fail, but don't panic if it somehow does
+    bech32.unwrap_or_else(|_| id.to_hex())
+}
+
+impl Dialog {
+    /// A `nostr:nevent1...` URI for one of our notes, e.g. to paste into a
+    /// task manager. The same note always gives the same URI: it names the
+    /// author and kind but no relays, which change over time.
+    pub async fn external_ref(&self, note_id: &EventId) -> Result<String> {
+        let event = self.note_event(note_id).await?;
+        let nevent = Nip19Event::new(event.id, Vec::<String>::new())
+            .author(event.pubkey)
+            .kind(event.kind);
+        let bech32 = nevent
+            .to_bech32()
+            .map_err(|_| DialogError::InvalidNoteId(event.id.to_hex()))?;
+        Ok(format!("nostr:{bech32}"))
+    }
+
+    /// The note behind an id or URI in any form [`parse_note_id`] takes.
+    /// Notes retagged, locked or migrated since the reference was made
+    /// resolve to the copy that replaced them.
+    pub async fn resolve_note_ref(&self, input: &str) -> Result<Note> {
+        let mut id = parse_note_id(input)?;
+        let filter = self.notes_filter();
+        let events = self
+            .client
+            .database()
+            .query(vec![filter])
+            .await
+            .map_err(|e| DialogError::Database(e.to_string()))?;
+        let replaced_by: HashMap<EventId, EventId> = events
+            .iter()
+            .filter_map(|event| Some((migrated_from(event)?, event.id)))
+            .collect();
+        // Bounded, in case replacements somehow form a loop
+        for _ in 0..replaced_by.len() {
+            match replaced_by.get(&id) {
+                Some(copy) => id = *copy,
+                None => break,
+            }
+        }
+        self.get_note(&id)
+            .await?
+            .ok_or(DialogError::NoteNotFound(id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::memory_dialog;
+
+    #[test]
+    fn test_note_id_round_trip() {
+        let id = EventId::all_zeros();
+        for format in [NoteIdFormat::Hex, NoteIdFormat::Note, NoteIdFormat::Nevent] {
+            let formatted = format_note_id(&id, format);
+            assert_eq!(parse_note_id(&formatted).unwrap(), id, "{format:?}");
+            assert_eq!(
+                parse_note_id(&format!(" nostr:{formatted}\n")).unwrap(),
+                id,
+                "{format:?} with prefix"
+            );
+        }
+        assert!(format_note_id(&id, NoteIdFormat::Note).starts_with("note1"));
+        assert!(format_note_id(&id, NoteIdFormat::Nevent).starts_with("nevent1"));
+
+        let npub = Keys::generate().public_key().to_bech32().unwrap();
+        for bad in ["", "abc", npub.as_str()] {
+            assert!(matches!(
+                parse_note_id(bad),
+                Err(DialogError::InvalidNoteId(_))
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_external_ref_round_trips_through_edits() {
+        let dialog = memory_dialog();
+        let note = dialog.save_note("Call the bank").await.unwrap();
+        let uri = dialog.external_ref(&note.id).await.unwrap();
+        assert!(uri.starts_with("nostr:nevent1"));
+        assert_eq!(dialog.external_ref(&note.id).await.unwrap(), uri);
+        assert_eq!(parse_note_id(&uri).unwrap(), note.id);
+
+        let report = dialog.add_tags(&[note.id], &["todo".into()]).await;
+        let (_, copy) = &report.updated[0];
+        let resolved = dialog.resolve_note_ref(&uri).await.unwrap();
+        assert_eq!(resolved.id, copy.id);
+        assert_eq!(resolved.tags, vec!["todo"]);
+    }
+}
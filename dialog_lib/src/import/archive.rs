@@ -0,0 +1,42 @@
+use crate::{DialogError, Result};
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+/// Largest archive entry read; export files are far smaller, so anything
+/// bigger is not what we're looking for
+const MAX_ENTRY_BYTES: u64 = 16 * 1024 * 1024;
+
+/// Names and contents of the files in a zip archive that `wanted` accepts
+pub(super) fn read_entries(
+    path: &Path,
+    wanted: fn(&str) -> bool,
+) -> Result<Vec<(String, Vec<u8>)>> {
+    let mut archive = zip::ZipArchive::new(File::open(path)?)
+        .map_err(|e| DialogError::Import(format!("not a zip archive: {e}")))?;
+
+    let mut entries = Vec::new();
+    for index in 0..archive.len() {
+        let mut entry = archive
+            .by_index(index)
+            .map_err(|e| DialogError::Import(e.to_string()))?;
+        let name = entry.name().to_string();
+        if entry.is_dir() || !wanted(&name) {
+            continue;
+        }
+        if entry.size() > MAX_ENTRY_BYTES {
+            eprintln!("[lib] import: skipping oversized entry {name}");
+            continue;
+        }
+        let mut bytes = Vec::with_capacity(entry.size() as usize);
+        entry
+            .by_ref()
+            .take(MAX_ENTRY_BYTES)
+            .read_to_end(&mut bytes)?;
+        entries.push((name, bytes));
+    }
+    if entries.is_empty() {
+        return Err(DialogError::Import("no notes found in archive".into()));
+    }
+    Ok(entries)
+}
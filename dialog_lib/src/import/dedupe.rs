@@ -0,0 +1,115 @@
+//! Spotting notes an import would create twice, e.g. when the same export
+//! is imported again or two exports overlap
+
+use crate::cache::content_hash;
+use crate::tags::strip_hashtags;
+use crate::Note;
+use nostr_sdk::prelude::*;
+use std::collections::HashMap;
+use std::fmt;
+
+/// Notes with the same words count as duplicates when dated this close
+pub const DUPLICATE_WINDOW_SECS: u64 = 24 * 60 * 60;
+
+/// What to do with an imported note that looks like one already stored
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DuplicatePolicy {
+    /// Leave the stored note alone and don't import it
+    #[default]
+    Skip,
+    /// Add the import's tags and pin/archive state to the stored note
+    Merge,
+    /// Import it anyway
+    Duplicate,
+}
+
+/// Why an imported note was taken for a duplicate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicateReason {
+    /// Exactly the same text, whatever the date
+    SameContent,
+    /// The same words, ignoring hashtags, case and spacing, dated within
+    /// [`DUPLICATE_WINDOW_SECS`]
+    SameText,
+}
+
+impl fmt::Display for DuplicateReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            DuplicateReason::SameContent => "same content",
+            DuplicateReason::SameText => "same text at about the same time",
+        })
+    }
+}
+
+/// Stored notes (and notes imported so far) by content hash and by words
+#[derive(Debug, Default)]
+pub(super) struct DuplicateIndex {
+    by_hash: HashMap<String, EventId>,
+    by_words: HashMap<String, Vec<(Timestamp, EventId)>>,
+}
+
+fn words(text: &str) -> String {
+    strip_hashtags(text)
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+impl DuplicateIndex {
+    /// Index stored notes by their text, and evicted ones by the content
+    /// hash kept when their text was dropped. Locked notes only show a
+    /// placeholder, so they can't be matched.
+    pub(super) fn new(
+        notes: &[Note],
+        evicted: impl IntoIterator<Item = (String, EventId)>,
+    ) -> Self {
+        let mut index = Self::default();
+        for note in notes {
+            if note.is_hydrated && !note.is_locked {
+                index.insert(note);
+            }
+        }
+        for (hash, id) in evicted {
+            index.by_hash.entry(hash).or_insert(id);
+        }
+        index
+    }
+
+    pub(super) fn insert(&mut self, note: &Note) {
+        self.by_hash.insert(content_hash(&note.text), note.id);
+        self.by_words
+            .entry(words(&note.text))
+            .or_default()
+            .push((note.created_at, note.id));
+    }
+
+    /// Forget `id`, e.g. once a merge has replaced it
+    pub(super) fn remove(&mut self, id: &EventId) {
+        self.by_hash.retain(|_, stored| stored != id);
+        for notes in self.by_words.values_mut() {
+            notes.retain(|(_, stored)| stored != id);
+        }
+    }
+
+    /// The stored note `text` written at `created_at` would duplicate
+    pub(super) fn find(
+        &self,
+        text: &str,
+        created_at: Timestamp,
+    ) -> Option<(EventId, DuplicateReason)> {
+        if let Some(id) = self.by_hash.get(&content_hash(text)) {
+            return Some((*id, DuplicateReason::SameContent));
+        }
+        let words = words(text);
+        if words.is_empty() {
+            return None;
+        }
+        self.by_words
+            .get(&words)?
+            .iter()
+            .find(|(at, _)| at.as_u64().abs_diff(created_at.as_u64()) <= DUPLICATE_WINDOW_SECS)
+            .map(|(_, id)| (*id, DuplicateReason::SameText))
+    }
+}
@@ -0,0 +1,445 @@
+use super::ExportedNote;
+use crate::{CaptureSource, Dialog, DialogError, Note, Result};
+use base64::Engine;
+use nostr_sdk::prelude::*;
+use std::path::Path;
+
+/// Tag added to every note imported from an email
+pub const EMAIL_TAG: &str = "email";
+/// Nested multipart levels followed when looking for the text body
+const MAX_MIME_DEPTH: usize = 8;
+
+/// The parts of an email that become a note
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParsedEmail {
+    pub subject: Option<String>,
+    pub body: String,
+    pub date: Option<Timestamp>,
+}
+
+impl ParsedEmail {
+    /// Parse a raw RFC 5322 message
+    pub fn parse(raw: &[u8]) -> Result<Self> {
+        let (headers, body) = split_message(raw);
+        if headers.is_empty() {
+            return Err(DialogError::Import("no email headers found".into()));
+        }
+        let subject = header(&headers, "subject")
+            .map(|s| decode_encoded_words(&s))
+            .filter(|s| !s.trim().is_empty());
+        let date = header(&headers, "date").and_then(|d| parse_date(&d));
+        let body = text_body(&headers, body, 0).unwrap_or_default();
+        Ok(Self {
+            subject,
+            body: body.trim().to_string(),
+            date,
+        })
+    }
+
+    /// Subject as the first line, then the body, then `#email`
+    pub fn note_text(&self) -> String {
+        let mut parts: Vec<&str> = Vec::new();
+        if let Some(subject) = &self.subject {
+            parts.push(subject.trim());
+        }
+        if !self.body.is_empty() {
+            parts.push(&self.body);
+        }
+        parts.push("");
+        format!("{}#{EMAIL_TAG}", parts.join("\n\n"))
+    }
+}
+
+/// An email message as an export holding one note, for
+/// [`Dialog::import_path`]
+pub(super) fn parse_eml_export(raw: &[u8]) -> Result<Vec<ExportedNote>> {
+    let email = ParsedEmail::parse(raw)?;
+    Ok(vec![ExportedNote {
+        text: email.note_text(),
+        created_at: email.date,
+        pinned: false,
+        archived: false,
+    }])
+}
+
+impl Dialog {
+    /// Import an email message as a note tagged `#email`, keeping its date
+    pub async fn import_eml(&self, raw: &[u8]) -> Result<Note> {
+        let email = ParsedEmail::parse(raw)?;
+        let created_at = email.date.unwrap_or_else(Timestamp::now);
+        let text = &self.prepare_text(&email.note_text()).await;
+        let event = self
+            .build_note_event_with(text, created_at, [], Some(CaptureSource::Import))
+            .await?;
+        self.publish_note(event, text).await
+    }
+
+    pub async fn import_eml_file(&self, path: &Path) -> Result<Note> {
+        let raw = std::fs::read(path)?;
+        self.import_eml(&raw).await
+    }
+}
+
+type Headers = Vec<(String, String)>;
+
+/// Split into unfolded headers (lowercased names) and the raw body
+fn split_message(raw: &[u8]) -> (Headers, &[u8]) {
+    let (head, body) = match find(raw, b"\r\n\r\n") {
+        Some(at) => (&raw[..at], &raw[at + 4..]),
+        None => match find(raw, b"\n\n") {
+            Some(at) => (&raw[..at], &raw[at + 2..]),
+            None => (raw, &raw[raw.len()..]),
+        },
+    };
+
+    let mut headers: Headers = Vec::new();
+    for line in String::from_utf8_lossy(head).lines() {
+        if line.starts_with([' ', '\t']) {
+            // Folded continuation of the previous header
+            if let Some((_, value)) = headers.last_mut() {
+                value.push(' ');
+                value.push_str(line.trim());
+            }
+        } else if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+        }
+    }
+    (headers, body)
+}
+
+fn header(headers: &Headers, name: &str) -> Option<String> {
+    headers
+        .iter()
+        .find(|(n, _)| n == name)
+        .map(|(_, v)| v.clone())
+}
+
+/// `param` from a header value like `text/plain; charset="utf-8"`
+fn header_param(value: &str, param: &str) -> Option<String> {
+    value.split(';').skip(1).find_map(|part| {
+        let (name, value) = part.split_once('=')?;
+        name.trim()
+            .eq_ignore_ascii_case(param)
+            .then(|| value.trim().trim_matches('"').to_string())
+    })
+}
+
+/// Find the best text body: text/plain preferred, HTML stripped as fallback
+fn text_body(headers: &Headers, body: &[u8], depth: usize) -> Option<String> {
+    let content_type = header(headers, "content-type").unwrap_or_else(|| "text/plain".into());
+    let mime = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+
+    if mime.starts_with("multipart/") {
+        if depth >= MAX_MIME_DEPTH {
+            return None;
+        }
+        let boundary = header_param(&content_type, "boundary")?;
+        let parts = split_multipart(body, &boundary);
+        let mut html = None;
+        for part in parts {
+            let (part_headers, part_body) = split_message(part);
+            let part_type = header(&part_headers, "content-type").unwrap_or_default();
+            if let Some(text) = text_body(&part_headers, part_body, depth + 1) {
+                if part_type.to_ascii_lowercase().contains("text/html") {
+                    html = html.or(Some(text));
+                } else {
+                    return Some(text);
+                }
+            }
+        }
+        return html;
+    }
+
+    if !mime.starts_with("text/") {
+        return None;
+    }
+    let disposition = header(headers, "content-disposition").unwrap_or_default();
+    if disposition.to_ascii_lowercase().starts_with("attachment") {
+        return None;
+    }
+
+    let encoding = header(headers, "content-transfer-encoding")
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    let bytes = match encoding.as_str() {
+        "base64" => {
+            let compact: Vec<u8> = body
+                .iter()
+                .copied()
+                .filter(|b| !b.is_ascii_whitespace())
+                .collect();
+            base64::engine::general_purpose::STANDARD
+                .decode(compact)
+                .ok()?
+        }
+        "quoted-printable" => decode_quoted_printable(body, false),
+        _ => body.to_vec(),
+    };
+    let charset = header_param(&content_type, "charset").unwrap_or_default();
+    let text = decode_charset(&bytes, &charset);
+
+    if mime == "text/html" {
+        Some(strip_html(&text))
+    } else {
+        Some(text)
+    }
+}
+
+fn split_multipart<'a>(body: &'a [u8], boundary: &str) -> Vec<&'a [u8]> {
+    let delimiter = format!("--{boundary}");
+    let delimiter = delimiter.as_bytes();
+    let mut parts = Vec::new();
+    let mut rest = body;
+    // Skip the preamble
+    let Some(start) = find(rest, delimiter) else {
+        return parts;
+    };
+    rest = &rest[start + delimiter.len()..];
+    while !rest.starts_with(b"--") {
+        let Some(end) = find(rest, delimiter) else {
+            break;
+        };
+        let part = &rest[..end];
+        // Drop the line break after the delimiter and the one before the next
+        let part = part
+            .strip_prefix(b"\r\n")
+            .or_else(|| part.strip_prefix(b"\n"))
+            .unwrap_or(part);
+        let part = part
+            .strip_suffix(b"\r\n")
+            .or_else(|| part.strip_suffix(b"\n"))
+            .unwrap_or(part);
+        parts.push(part);
+        rest = &rest[end + delimiter.len()..];
+    }
+    parts
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// Quoted-printable decoding; `header` mode (RFC 2047 `Q`) also maps `_` to space
+fn decode_quoted_printable(input: &[u8], header: bool) -> Vec<u8> {
+    let mut out = Vec::with_capacity(input.len());
+    let mut i = 0;
+    while i < input.len() {
+        match input[i] {
+            b'=' if input[i + 1..].starts_with(b"\r\n") => i += 3,
+            b'=' if input[i + 1..].starts_with(b"\n") => i += 2,
+            b'=' if i + 2 < input.len() => {
+                let hex = std::str::from_utf8(&input[i + 1..i + 3]).ok();
+                match hex.and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                    Some(byte) => {
+                        out.push(byte);
+                        i += 3;
+                    }
+                    None => {
+                        out.push(b'=');
+                        i += 1;
+                    }
+                }
+            }
+            b'_' if header => {
+                out.push(b' ');
+                i += 1;
+            }
+            byte => {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
+/// UTF-8 (lossy), with Latin-1 and Windows-1252 mapped byte for byte
+fn decode_charset(bytes: &[u8], charset: &str) -> String {
+    match charset.to_ascii_lowercase().as_str() {
+        "iso-8859-1" | "latin1" | "windows-1252" | "us-ascii" => {
+            bytes.iter().map(|&b| b as char).collect()
+        }
+        _ => String::from_utf8_lossy(bytes).into_owned(),
+    }
+}
+
+/// Decode RFC 2047 `=?charset?B|Q?text?=` words in a header value
+fn decode_encoded_words(value: &str) -> String {
+    let mut out = String::new();
+    let mut rest = value;
+    let mut last_was_word = false;
+    while let Some(start) = rest.find("=?") {
+        let decoded = rest[start + 2..]
+            .split_once('?')
+            .and_then(|(charset, tail)| {
+                let (encoding, tail) = tail.split_once('?')?;
+                let end = tail.find("?=")?;
+                let bytes = match encoding.to_ascii_uppercase().as_str() {
+                    "B" => base64::engine::general_purpose::STANDARD
+                        .decode(&tail[..end])
+                        .ok()?,
+                    "Q" => decode_quoted_printable(tail[..end].as_bytes(), true),
+                    _ => return None,
+                };
+                let consumed = start + 2 + charset.len() + 1 + encoding.len() + 1 + end + 2;
+                Some((decode_charset(&bytes, charset), consumed))
+            });
+        let Some((text, consumed)) = decoded else {
+            out.push_str(&rest[..start + 2]);
+            rest = &rest[start + 2..];
+            last_was_word = false;
+            continue;
+        };
+        // Whitespace between adjacent encoded words is not part of the text
+        let between = &rest[..start];
+        if !(last_was_word && between.trim().is_empty()) {
+            out.push_str(between);
+        }
+        out.push_str(&text);
+        rest = &rest[consumed..];
+        last_was_word = true;
+    }
+    out.push_str(rest);
+    out
+}
+
+fn parse_date(value: &str) -> Option<Timestamp> {
+    // Drop trailing comments such as "(UTC)"
+    let value = value.split('(').next().unwrap_or(value).trim();
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    u64::try_from(date.timestamp()).ok().map(Timestamp::from)
+}
+
+/// Crude HTML to text: drop tags, script/style contents and decode common entities
+fn strip_html(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let lower = html.to_ascii_lowercase();
+    let mut i = 0;
+    while i < html.len() {
+        if lower[i..].starts_with("<script") || lower[i..].starts_with("<style") {
+            let close = if lower[i..].starts_with("<script") {
+                "</script>"
+            } else {
+                "</style>"
+            };
+            i = lower[i..]
+                .find(close)
+                .map_or(html.len(), |end| i + end + close.len());
+            continue;
+        }
+        if html[i..].starts_with('<') {
+            let tag_end = html[i..].find('>').map_or(html.len(), |end| i + end + 1);
+            let tag = &lower[i..tag_end];
+            if ["<br", "<p", "</p", "<div", "</div", "<li", "<tr"]
+                .iter()
+                .any(|t| tag.starts_with(t))
+            {
+                text.push('\n');
+            }
+            i = tag_end;
+            continue;
+        }
+        let ch = html[i..].chars().next().unwrap_or_default();
+        text.push(ch);
+        i += ch.len_utf8();
+    }
+
+    let text = text
+        .replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&");
+    let lines: Vec<&str> = text.lines().map(str::trim).collect();
+    let mut collapsed = lines.join("\n");
+    while collapsed.contains("\n\n\n") {
+        collapsed = collapsed.replace("\n\n\n", "\n\n");
+    }
+    collapsed.trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::memory_dialog;
+
+    const SIMPLE: &[u8] = b"From: Me <me@example.com>\r\n\
+Subject: =?UTF-8?B?Q2Fmw6k=?= plans\r\n\
+Date: Tue, 14 Mar 2023 09:30:00 +0000 (UTC)\r\n\
+Content-Type: text/plain; charset=utf-8\r\n\
+Content-Transfer-Encoding: quoted-printable\r\n\
+\r\n\
+Meet at the caf=C3=A9 at 10, it's a long line that gets soft=\r\n\
+ wrapped.\r\n";
+
+    const MULTIPART: &[u8] = b"Subject: Folded\r\n subject line\r\n\
+Content-Type: multipart/alternative; boundary=\"XYZ\"\r\n\
+\r\n\
+preamble\r\n\
+--XYZ\r\n\
+Content-Type: text/html\r\n\
+\r\n\
+<p>Hello <b>html</b></p>\r\n\
+--XYZ\r\n\
+Content-Type: text/plain\r\n\
+Content-Transfer-Encoding: base64\r\n\
+\r\n\
+SGVsbG8gcGxhaW4=\r\n\
+--XYZ--\r\n";
+
+    #[test]
+    fn test_parse_simple_email() {
+        let email = ParsedEmail::parse(SIMPLE).unwrap();
+        assert_eq!(email.subject.as_deref(), Some("Café plans"));
+        assert_eq!(
+            email.body,
+            "Meet at the café at 10, it's a long line that gets soft wrapped."
+        );
+        assert_eq!(email.date, Some(Timestamp::from(1_678_786_200)));
+        assert_eq!(
+            email.note_text(),
+            format!("Café plans\n\n{}\n\n#email", email.body)
+        );
+    }
+
+    #[test]
+    fn test_parse_multipart_prefers_plain_text() {
+        let email = ParsedEmail::parse(MULTIPART).unwrap();
+        assert_eq!(email.subject.as_deref(), Some("Folded subject line"));
+        assert_eq!(email.body, "Hello plain");
+        assert_eq!(email.date, None);
+    }
+
+    #[test]
+    fn test_strip_html() {
+        assert_eq!(
+            strip_html("<style>p{}</style><p>One &amp; two</p><br>Three"),
+            "One & two\n\nThree"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_import_eml_keeps_date_and_tag() {
+        let dialog = memory_dialog();
+        let event = dialog
+            .build_note_event_at(
+                &ParsedEmail::parse(SIMPLE).unwrap().note_text(),
+                Timestamp::from(1_678_786_200),
+            )
+            .await
+            .unwrap();
+        dialog.save_events([event.clone()]).await.unwrap();
+
+        let note = dialog.get_note(&event.id).await.unwrap().unwrap();
+        assert_eq!(note.created_at, Timestamp::from(1_678_786_200));
+        assert_eq!(note.tags, vec![EMAIL_TAG]);
+    }
+}
@@ -0,0 +1,124 @@
+use super::{note_text, ExportedNote};
+use crate::{DialogError, Result};
+use nostr_sdk::prelude::*;
+use serde::Deserialize;
+
+/// One note from a Google Takeout `Keep/*.json` file
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct KeepNote {
+    #[serde(default)]
+    title: String,
+    #[serde(default)]
+    text_content: String,
+    /// Checklist notes have items instead of text
+    #[serde(default)]
+    list_content: Vec<KeepListItem>,
+    #[serde(default)]
+    labels: Vec<KeepLabel>,
+    #[serde(default)]
+    is_trashed: bool,
+    #[serde(default)]
+    is_pinned: bool,
+    #[serde(default)]
+    is_archived: bool,
+    created_timestamp_usec: Option<u64>,
+    user_edited_timestamp_usec: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct KeepListItem {
+    #[serde(default)]
+    text: String,
+    #[serde(default)]
+    is_checked: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct KeepLabel {
+    name: String,
+}
+
+/// Whether a Takeout archive entry is a Keep note
+pub(super) fn is_keep_entry(name: &str) -> bool {
+    name.contains("Keep/") && name.ends_with(".json")
+}
+
+/// Parse one Keep note. Trashed notes are skipped.
+pub(super) fn parse_keep_note(json: &[u8]) -> Result<Vec<ExportedNote>> {
+    let note: KeepNote = serde_json::from_slice(json)
+        .map_err(|e| DialogError::Import(format!("not a Keep note: {e}")))?;
+    if note.is_trashed {
+        return Ok(Vec::new());
+    }
+
+    let body = if note.list_content.is_empty() {
+        note.text_content.trim().to_string()
+    } else {
+        note.list_content
+            .iter()
+            .map(|item| {
+                let mark = if item.is_checked { "x" } else { " " };
+                format!("- [{mark}] {}", item.text.trim())
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    let labels: Vec<&str> = note
+        .labels
+        .iter()
+        .map(|label| label.name.as_str())
+        .collect();
+    let text = note_text(&[note.title.trim(), &body], &labels)
+        .ok_or_else(|| DialogError::Import("empty note".into()))?;
+
+    let created_at = note
+        .created_timestamp_usec
+        .or(note.user_edited_timestamp_usec)
+        .map(|usec| Timestamp::from(usec / 1_000_000));
+    Ok(vec![ExportedNote {
+        text,
+        created_at,
+        pinned: note.is_pinned,
+        archived: note.is_archived,
+    }])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_keep_note() {
+        let json = br#"{
+            "title": "Trip",
+            "textContent": "",
+            "listContent": [
+                {"text": "Passport", "isChecked": true},
+                {"text": "Charger", "isChecked": false}
+            ],
+            "labels": [{"name": "Travel Plans"}],
+            "isPinned": true,
+            "isArchived": false,
+            "isTrashed": false,
+            "createdTimestampUsec": 1678786200000000
+        }"#;
+        let notes = parse_keep_note(json).unwrap();
+        assert_eq!(
+            notes,
+            vec![ExportedNote {
+                text: "Trip\n\n- [x] Passport\n- [ ] Charger\n\n#travel-plans".into(),
+                created_at: Some(Timestamp::from(1_678_786_200)),
+                pinned: true,
+                archived: false,
+            }]
+        );
+
+        let trashed = br#"{"textContent": "gone", "isTrashed": true}"#;
+        assert!(parse_keep_note(trashed).unwrap().is_empty());
+        assert!(parse_keep_note(br#"{"title": " "}"#).is_err());
+        assert!(is_keep_entry("Takeout/Keep/Trip.json"));
+        assert!(!is_keep_entry("Takeout/Keep/Labels.txt"));
+    }
+}
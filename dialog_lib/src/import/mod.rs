@@ -0,0 +1,454 @@
+mod archive;
+mod dedupe;
+mod eml;
+mod keep;
+mod simplenote;
+
+pub use dedupe::{DuplicatePolicy, DuplicateReason, DUPLICATE_WINDOW_SECS};
+pub use eml::ParsedEmail;
+
+use crate::note::local_note;
+use crate::tags::{normalize_tag, parse_hashtags};
+use crate::{CancellationToken, CaptureSource, Dialog, DialogError, Note, Result};
+use dedupe::DuplicateIndex;
+use nostr_sdk::prelude::*;
+use std::path::{Path, PathBuf};
+
+/// File formats `Dialog::import_path` understands
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportFormat {
+    /// RFC 5322 email messages (`.eml`)
+    Eml,
+    /// Google Keep from a Takeout `.zip`, or its extracted `.json` files
+    Keep,
+    /// Simplenote export `.zip`, or its extracted `notes.json`
+    Simplenote,
+}
+
+impl ImportFormat {
+    /// File extensions used to pick files out of a directory
+    fn extensions(self) -> &'static [&'static str] {
+        match self {
+            ImportFormat::Eml => &["eml"],
+            ImportFormat::Keep | ImportFormat::Simplenote => &["json", "zip"],
+        }
+    }
+}
+
+/// An imported note that looked like one already stored
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportDuplicate {
+    /// File it came from; notes inside an archive are `<archive>/<entry>`
+    pub source: PathBuf,
+    /// The stored note it matched
+    pub existing: EventId,
+    pub reason: DuplicateReason,
+}
+
+/// Outcome of a bulk import
+#[derive(Debug, Default)]
+pub struct ImportReport {
+    pub created: Vec<Note>,
+    /// Duplicates left out under [`DuplicatePolicy::Skip`]
+    pub skipped: Vec<ImportDuplicate>,
+    /// Duplicates merged under [`DuplicatePolicy::Merge`], with the note
+    /// that now stands for the stored one
+    pub merged: Vec<(ImportDuplicate, Note)>,
+    /// Files that could not be imported, with the reason. Notes inside an
+    /// archive are reported as `<archive>/<entry>`.
+    pub failed: Vec<(PathBuf, String)>,
+    /// Stopped early by the cancellation token
+    pub cancelled: bool,
+}
+
+/// What became of one imported note
+enum Imported {
+    Created(Note),
+    Skipped(EventId, DuplicateReason),
+    Merged(EventId, DuplicateReason, Note),
+}
+
+impl ImportReport {
+    fn record(&mut self, source: PathBuf, result: Result<Imported>) {
+        match result {
+            Ok(Imported::Created(note)) => self.created.push(note),
+            Ok(Imported::Skipped(existing, reason)) => self.skipped.push(ImportDuplicate {
+                source,
+                existing,
+                reason,
+            }),
+            Ok(Imported::Merged(existing, reason, note)) => {
+                let duplicate = ImportDuplicate {
+                    source,
+                    existing,
+                    reason,
+                };
+                self.merged.push((duplicate, note));
+            }
+            Err(e) => {
+                eprintln!("[lib] import: {} failed: {e}", source.display());
+                self.failed.push((source, e.to_string()));
+            }
+        }
+    }
+}
+
+/// A note read from another app's export, before it is saved
+#[derive(Debug, Clone, PartialEq)]
+struct ExportedNote {
+    text: String,
+    created_at: Option<Timestamp>,
+    pinned: bool,
+    archived: bool,
+}
+
+impl Dialog {
+    /// Import a file, or every matching file in a directory (not recursive).
+    /// Individual failures are collected in the report rather than aborting.
+    /// Likely duplicates of stored notes, or of notes earlier in the same
+    /// import, are skipped.
+    pub async fn import_path(&self, path: &Path, format: ImportFormat) -> Result<ImportReport> {
+        let policy = DuplicatePolicy::default();
+        self.import_path_cancellable(path, format, policy, &CancellationToken::new())
+            .await
+    }
+
+    /// Like [`Dialog::import_path`], handling duplicates by `policy` and
+    /// stopping between notes once `cancel` fires. Notes imported so far
+    /// are kept and listed in the report.
+    pub async fn import_path_cancellable(
+        &self,
+        path: &Path,
+        format: ImportFormat,
+        policy: DuplicatePolicy,
+        cancel: &CancellationToken,
+    ) -> Result<ImportReport> {
+        let mut files = Vec::new();
+        if path.is_dir() {
+            for entry in std::fs::read_dir(path)? {
+                let file = entry?.path();
+                if file.extension().is_some_and(|ext| {
+                    format
+                        .extensions()
+                        .iter()
+                        .any(|wanted| ext.eq_ignore_ascii_case(wanted))
+                }) {
+                    files.push(file);
+                }
+            }
+            files.sort();
+        } else {
+            files.push(path.to_path_buf());
+        }
+
+        let notes = self.list_notes(usize::MAX).await?;
+        let mut import = Import {
+            policy,
+            duplicates: DuplicateIndex::new(&notes, self.evicted_content_hashes()),
+            cancel,
+            report: ImportReport::default(),
+        };
+        for file in files {
+            if cancel.is_cancelled() {
+                import.report.cancelled = true;
+                break;
+            }
+            let (is_entry, parse): (fn(&str) -> bool, Parse) = match format {
+                ImportFormat::Eml => (|_| false, eml::parse_eml_export),
+                ImportFormat::Keep => (keep::is_keep_entry, keep::parse_keep_note),
+                ImportFormat::Simplenote => (
+                    simplenote::is_simplenote_entry,
+                    simplenote::parse_simplenote_export,
+                ),
+            };
+            self.import_export(&file, is_entry, parse, &mut import)
+                .await;
+        }
+        let report = import.report;
+        eprintln!(
+            "[lib] import: {} created, {} skipped, {} merged, {} failed",
+            report.created.len(),
+            report.skipped.len(),
+            report.merged.len(),
+            report.failed.len()
+        );
+        Ok(report)
+    }
+
+    /// Import the notes in an export file, or in the entries of an export
+    /// `.zip` picked by `is_entry`
+    async fn import_export(
+        &self,
+        file: &Path,
+        is_entry: fn(&str) -> bool,
+        parse: Parse,
+        import: &mut Import<'_>,
+    ) {
+        let sources = if file
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("zip"))
+        {
+            archive::read_entries(file, is_entry)
+        } else {
+            std::fs::read(file)
+                .map(|bytes| vec![(String::new(), bytes)])
+                .map_err(DialogError::from)
+        };
+        let sources = match sources {
+            Ok(sources) => sources,
+            Err(e) => return import.report.record(file.to_path_buf(), Err(e)),
+        };
+
+        for (entry, bytes) in sources {
+            let source = if entry.is_empty() {
+                file.to_path_buf()
+            } else {
+                file.join(entry)
+            };
+            match parse(&bytes) {
+                Ok(notes) => {
+                    for note in notes {
+                        if import.cancel.is_cancelled() {
+                            import.report.cancelled = true;
+                            return;
+                        }
+                        let result = self.import_exported_note(note, import).await;
+                        import.report.record(source.clone(), result);
+                    }
+                }
+                Err(e) => import.report.record(source, Err(e)),
+            }
+        }
+    }
+
+    /// Save an exported note with its original date and pin/archive state,
+    /// unless it duplicates one and the policy says otherwise. Notes that
+    /// are saved but don't reach a relay still count as imported.
+    async fn import_exported_note(
+        &self,
+        exported: ExportedNote,
+        import: &mut Import<'_>,
+    ) -> Result<Imported> {
+        let created_at = exported.created_at.unwrap_or_else(Timestamp::now);
+        let text = self.prepare_text(&exported.text).await;
+        let duplicate = match import.policy {
+            DuplicatePolicy::Duplicate => None,
+            _ => import.duplicates.find(&text, created_at),
+        };
+        if let Some((existing, reason)) = duplicate {
+            if import.policy == DuplicatePolicy::Skip {
+                return Ok(Imported::Skipped(existing, reason));
+            }
+            let note = self.merge_into(&existing, &text, &exported).await?;
+            import.duplicates.remove(&existing);
+            import.duplicates.insert(&note);
+            return Ok(Imported::Merged(existing, reason, note));
+        }
+
+        let event = self
+            .build_note_event_with(&text, created_at, [], Some(CaptureSource::Import))
+            .await?;
+        let note = match self.publish_note(event.clone(), &text).await {
+            Ok(note) => note,
+            Err(e @ DialogError::Database(_)) => return Err(e),
+            Err(e) => {
+                eprintln!("[lib] import: {} not published: {e}", event.id);
+                local_note(&self.keys, &event, &text)
+            }
+        };
+        if exported.pinned {
+            self.set_pinned(&note.id, true).await?;
+        }
+        if exported.archived {
+            self.set_archived(&note.id, true).await?;
+        }
+        let note = Note {
+            is_pinned: exported.pinned,
+            is_archived: exported.archived,
+            ..note
+        };
+        import.duplicates.insert(&note);
+        Ok(Imported::Created(note))
+    }
+
+    /// Give the stored note `existing` the hashtags in `text` it lacks, and
+    /// pin or archive it if the exported note was
+    async fn merge_into(
+        &self,
+        existing: &EventId,
+        text: &str,
+        exported: &ExportedNote,
+    ) -> Result<Note> {
+        // Only its content hash is stored; retagging needs the text
+        if self.is_evicted(existing) {
+            self.hydrate_note(existing).await?;
+        }
+        let mut report = self.add_tags(&[*existing], &parse_hashtags(text)).await;
+        if let Some((_, reason)) = report.failed.pop() {
+            return Err(DialogError::Import(reason));
+        }
+        let mut note = match report.updated.pop() {
+            Some((_, note)) => note,
+            None => self
+                .get_note(existing)
+                .await?
+                .ok_or(DialogError::NoteNotFound(*existing))?,
+        };
+        if exported.pinned && !note.is_pinned {
+            self.set_pinned(&note.id, true).await?;
+            note.is_pinned = true;
+        }
+        if exported.archived && !note.is_archived {
+            self.set_archived(&note.id, true).await?;
+            note.is_archived = true;
+        }
+        Ok(note)
+    }
+}
+
+type Parse = fn(&[u8]) -> Result<Vec<ExportedNote>>;
+
+/// State of one [`Dialog::import_path_cancellable`] run
+struct Import<'a> {
+    policy: DuplicatePolicy,
+    duplicates: DuplicateIndex,
+    cancel: &'a CancellationToken,
+    report: ImportReport,
+}
+
+/// Join the non-empty `parts` with blank lines and append the `labels` as
+/// hashtags (spaces become dashes). None if there is no text at all.
+fn note_text(parts: &[&str], labels: &[&str]) -> Option<String> {
+    let parts: Vec<&str> = parts
+        .iter()
+        .map(|part| part.trim())
+        .filter(|part| !part.is_empty())
+        .collect();
+    if parts.is_empty() {
+        return None;
+    }
+    let mut text = parts.join("\n\n");
+
+    let mut present = parse_hashtags(&text);
+    let mut hashtags = Vec::new();
+    for label in labels {
+        let label = label.split_whitespace().collect::<Vec<_>>().join("-");
+        let Some(tag) = normalize_tag(&label) else {
+            continue;
+        };
+        if !present.contains(&tag) {
+            hashtags.push(format!("#{tag}"));
+            present.push(tag);
+        }
+    }
+    if !hashtags.is_empty() {
+        text.push_str("\n\n");
+        text.push_str(&hashtags.join(" "));
+    }
+    Some(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::memory_dialog;
+
+    #[test]
+    fn test_note_text_appends_missing_labels() {
+        assert_eq!(
+            note_text(&["Title", "", "Body #work"], &["Work", "Big Ideas", "!!"]),
+            Some("Title\n\nBody #work\n\n#big-ideas".to_string())
+        );
+        assert_eq!(note_text(&[" ", ""], &["work"]), None);
+    }
+
+    #[tokio::test]
+    async fn test_import_simplenote_file() {
+        let dialog = memory_dialog();
+        let dir = std::env::temp_dir().join(format!("dialog-import-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("notes.json");
+        std::fs::write(
+            &path,
+            r#"{"activeNotes": [{"content": "Pinned idea", "tags": ["ideas"], "pinned": true,
+                "creationDate": "2023-03-14T09:30:00Z"}]}"#,
+        )
+        .unwrap();
+
+        let report = dialog
+            .import_path(&path, ImportFormat::Simplenote)
+            .await
+            .unwrap();
+        assert!(report.failed.is_empty(), "{:?}", report.failed);
+        let note = &report.created[0];
+        assert_eq!(note.created_at, Timestamp::from(1_678_786_200));
+        assert_eq!(note.tags, vec!["ideas"]);
+
+        let stored = dialog.get_note(&note.id).await.unwrap().unwrap();
+        assert!(stored.is_pinned);
+    }
+
+    #[tokio::test]
+    async fn test_import_detects_duplicates() {
+        let dialog = memory_dialog();
+        let dir = std::env::temp_dir().join(format!("dialog-dedupe-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("notes.json");
+        std::fs::write(
+            &path,
+            r#"{"activeNotes": [{"content": "Call the bank", "tags": ["errands"],
+                "creationDate": "2023-03-14T09:30:00Z"}]}"#,
+        )
+        .unwrap();
+        let first = dialog
+            .import_path(&path, ImportFormat::Simplenote)
+            .await
+            .unwrap();
+        let original = first.created[0].id;
+
+        // Same file again: the same content
+        let again = dialog
+            .import_path(&path, ImportFormat::Simplenote)
+            .await
+            .unwrap();
+        assert!(again.created.is_empty());
+        assert_eq!(again.skipped[0].existing, original);
+        assert_eq!(again.skipped[0].reason, DuplicateReason::SameContent);
+
+        // Same words an hour later with another tag: merged
+        std::fs::write(
+            &path,
+            r#"{"activeNotes": [{"content": "call the  bank", "tags": ["money"],
+                "creationDate": "2023-03-14T10:30:00Z"}]}"#,
+        )
+        .unwrap();
+        let cancel = CancellationToken::new();
+        let merged = dialog
+            .import_path_cancellable(
+                &path,
+                ImportFormat::Simplenote,
+                DuplicatePolicy::Merge,
+                &cancel,
+            )
+            .await
+            .unwrap();
+        let (duplicate, note) = &merged.merged[0];
+        assert_eq!(duplicate.existing, original);
+        assert_eq!(duplicate.reason, DuplicateReason::SameText);
+        assert!(note.tags.contains(&"errands".to_string()));
+        assert!(note.tags.contains(&"money".to_string()));
+        assert_eq!(dialog.list_notes(10).await.unwrap().len(), 1);
+
+        let duplicated = dialog
+            .import_path_cancellable(
+                &path,
+                ImportFormat::Simplenote,
+                DuplicatePolicy::Duplicate,
+                &cancel,
+            )
+            .await
+            .unwrap();
+        assert_eq!(duplicated.created.len(), 1);
+        assert_eq!(dialog.list_notes(10).await.unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,90 @@
+use super::{note_text, ExportedNote};
+use crate::{DialogError, Result};
+use nostr_sdk::prelude::*;
+use serde::Deserialize;
+
+/// `source/notes.json` from a Simplenote export
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SimplenoteExport {
+    /// Trashed notes are listed separately and not imported
+    #[serde(default)]
+    active_notes: Vec<SimplenoteNote>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SimplenoteNote {
+    #[serde(default)]
+    content: String,
+    creation_date: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    pinned: bool,
+}
+
+/// Whether an export archive entry is the Simplenote notes file
+pub(super) fn is_simplenote_entry(name: &str) -> bool {
+    name == "notes.json" || name.ends_with("/notes.json")
+}
+
+/// Parse every active note in a Simplenote export, skipping empty ones
+pub(super) fn parse_simplenote_export(json: &[u8]) -> Result<Vec<ExportedNote>> {
+    let export: SimplenoteExport = serde_json::from_slice(json)
+        .map_err(|e| DialogError::Import(format!("not a Simplenote export: {e}")))?;
+    Ok(export
+        .active_notes
+        .into_iter()
+        .filter_map(|note| {
+            let tags: Vec<&str> = note.tags.iter().map(String::as_str).collect();
+            Some(ExportedNote {
+                text: note_text(&[note.content.trim()], &tags)?,
+                created_at: note.creation_date.as_deref().and_then(parse_date),
+                pinned: note.pinned,
+                archived: false,
+            })
+        })
+        .collect())
+}
+
+/// ISO 8601 dates such as `2023-03-14T09:30:00.000Z`
+fn parse_date(value: &str) -> Option<Timestamp> {
+    let date = chrono::DateTime::parse_from_rfc3339(value).ok()?;
+    u64::try_from(date.timestamp()).ok().map(Timestamp::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_simplenote_export() {
+        let json = br#"{
+            "activeNotes": [
+                {
+                    "id": "a1",
+                    "content": "Groceries\neggs, milk",
+                    "creationDate": "2023-03-14T09:30:00.000Z",
+                    "tags": ["home", "Errands"],
+                    "pinned": true
+                },
+                {"id": "a2", "content": "   "}
+            ],
+            "trashedNotes": [{"id": "t1", "content": "old"}]
+        }"#;
+        let notes = parse_simplenote_export(json).unwrap();
+        assert_eq!(
+            notes,
+            vec![ExportedNote {
+                text: "Groceries\neggs, milk\n\n#home #errands".into(),
+                created_at: Some(Timestamp::from(1_678_786_200)),
+                pinned: true,
+                archived: false,
+            }]
+        );
+        assert!(parse_simplenote_export(b"[]").is_err());
+        assert!(is_simplenote_entry("source/notes.json"));
+        assert!(!is_simplenote_entry("source/mynotes.json"));
+    }
+}
@@ -0,0 +1,50 @@
+//! The nsec kept in the OS credential store: the Keychain on Apple
+//! platforms, Credential Manager on Windows and the kernel keyring on
+//! Linux. Enabled with the `keyring` feature.
+
+use crate::{DialogError, Result};
+use keyring::Entry;
+use nostr_sdk::prelude::*;
+
+fn entry(service: &str, account: &str) -> Result<Entry> {
+    Entry::new(service, account).map_err(|e| DialogError::Keychain(e.to_string()))
+}
+
+/// Check `nsec` (nsec1... or hex) and store it under `service`/`account`,
+/// replacing any key there. Returns the key's public key.
+pub fn store_nsec(service: &str, account: &str, nsec: &str) -> Result<PublicKey> {
+    let keys = Keys::parse(nsec)?;
+    let bech32 = keys
+        .secret_key()
+        .to_bech32()
+        .map_err(|e| DialogError::Keychain(e.to_string()))?;
+    entry(service, account)?
+        .set_password(&bech32)
+        .map_err(|e| DialogError::Keychain(e.to_string()))?;
+    Ok(keys.public_key())
+}
+
+/// The keys stored by [`store_nsec`]
+pub fn load_keys(service: &str, account: &str) -> Result<Keys> {
+    let nsec = entry(service, account)?
+        .get_password()
+        .map_err(|e| match e {
+            keyring::Error::NoEntry => {
+                DialogError::Keychain(format!("no key stored for {service}/{account}"))
+            }
+            e => DialogError::Keychain(e.to_string()),
+        })?;
+    Ok(Keys::parse(&nsec)?)
+}
+
+pub fn has_nsec(service: &str, account: &str) -> bool {
+    entry(service, account).is_ok_and(|entry| entry.get_password().is_ok())
+}
+
+/// Remove the stored key, e.g. on sign-out. Succeeds if there was none.
+pub fn delete_nsec(service: &str, account: &str) -> Result<()> {
+    match entry(service, account)?.delete_credential() {
+        Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+        Err(e) => Err(DialogError::Keychain(e.to_string())),
+    }
+}
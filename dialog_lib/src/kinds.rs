@@ -0,0 +1,122 @@
+use crate::{Dialog, DialogError, Result};
+use nostr_sdk::prelude::*;
+use std::collections::BTreeSet;
+
+/// NIP-78 application data kind: app state, voice data, link previews and
+/// share links
+pub(crate) const APP_DATA_KIND: u16 = 30078;
+
+/// A feature with event kinds of its own
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum KindFeature {
+    /// Notes, under the configured kind plus the default and legacy ones
+    Notes,
+    /// Read/pin/archive state, voice data and other NIP-78 app data
+    AppData,
+    /// Private messages from other people (NIP-17 gift wraps)
+    Messages,
+}
+
+impl KindFeature {
+    pub const ALL: [KindFeature; 3] = [
+        KindFeature::Notes,
+        KindFeature::AppData,
+        KindFeature::Messages,
+    ];
+}
+
+/// Which features sync and watch their kinds. Everything is on by default;
+/// turning a feature off leaves what is already stored readable.
+///
+/// ```ignore
+/// dialog.set_kind_set(KindSet::default().without(KindFeature::Messages));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KindSet {
+    disabled: BTreeSet<KindFeature>,
+}
+
+impl KindSet {
+    pub fn with(mut self, feature: KindFeature) -> Self {
+        self.disabled.remove(&feature);
+        self
+    }
+
+    pub fn without(mut self, feature: KindFeature) -> Self {
+        self.disabled.insert(feature);
+        self
+    }
+
+    pub fn contains(&self, feature: KindFeature) -> bool {
+        !self.disabled.contains(&feature)
+    }
+}
+
+impl Dialog {
+    pub fn kind_set(&self) -> KindSet {
+        self.kind_set.read().unwrap().clone()
+    }
+
+    /// Choose which features sync and watch. Takes effect for the next
+    /// sync and newly started watches.
+    pub fn set_kind_set(&self, kinds: KindSet) {
+        *self.kind_set.write().unwrap() = kinds;
+    }
+
+    /// Every kind `feature` reads and writes, whether or not it is enabled
+    pub fn kinds_for(&self, feature: KindFeature) -> Vec<Kind> {
+        match feature {
+            KindFeature::Notes => self.note_kinds(),
+            KindFeature::AppData => vec![Kind::from(APP_DATA_KIND)],
+            KindFeature::Messages => vec![Kind::GiftWrap],
+        }
+    }
+
+    /// Kinds sync and watches follow, across every enabled feature
+    pub fn synced_kinds(&self) -> Vec<Kind> {
+        let kinds = self.kind_set();
+        KindFeature::ALL
+            .into_iter()
+            .filter(|feature| kinds.contains(*feature))
+            .flat_map(|feature| self.kinds_for(feature))
+            .collect()
+    }
+
+    /// Fails if `feature` was turned off with [`Dialog::set_kind_set`]
+    pub(crate) fn require_feature(&self, feature: KindFeature) -> Result<()> {
+        if self.kind_set().contains(feature) {
+            Ok(())
+        } else {
+            Err(DialogError::Config(format!(
+                "{feature:?} kinds are turned off"
+            )))
+        }
+    }
+
+    /// Our notes, in every kind notes are read from
+    pub(crate) fn notes_filter(&self) -> Filter {
+        Filter::new()
+            .author(self.keys.public_key())
+            .kinds(self.kinds_for(KindFeature::Notes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::memory_dialog;
+
+    #[tokio::test]
+    async fn test_features_can_be_turned_off() {
+        let dialog = memory_dialog();
+        assert!(dialog.synced_kinds().contains(&Kind::GiftWrap));
+
+        dialog.set_kind_set(KindSet::default().without(KindFeature::Messages));
+        assert!(!dialog.synced_kinds().contains(&Kind::GiftWrap));
+        assert!(dialog.synced_kinds().contains(&dialog.note_kind()));
+        assert!(dialog.watch_messages().await.is_err());
+
+        dialog.set_kind_set(dialog.kind_set().with(KindFeature::Messages));
+        assert_eq!(dialog.kind_set(), KindSet::default());
+    }
+}
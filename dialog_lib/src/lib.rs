@@ -1,14 +1,172 @@
 use nostr_sdk::prelude::*;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use thiserror::Error;
 
+pub mod account;
+pub mod activity;
+pub mod aliases;
+#[cfg(feature = "api")]
+pub mod api;
+pub mod app_state;
+pub mod attachment;
+#[cfg(feature = "attachments")]
+pub mod attachment_cache;
+pub mod audit;
+pub mod batch;
+pub mod budget;
+pub mod builder;
+pub mod bulk;
+pub mod cache;
+pub mod chaos;
+#[cfg(feature = "clipper")]
+pub mod clipper;
+pub mod clock;
+pub mod contacts;
+pub mod debug;
+pub mod devices;
+pub mod digest;
+pub mod enrich;
+mod file_store;
+pub mod history;
+pub mod ics;
+pub mod idempotency;
+pub mod ids;
+#[cfg(feature = "import")]
+pub mod import;
+#[cfg(feature = "keyring")]
+pub mod keychain;
+pub mod kinds;
+pub mod lint;
+#[cfg(any(feature = "clipper", feature = "api"))]
+mod local_http;
+mod local_store;
+pub mod lock;
+pub mod messages;
+pub mod migrate;
+pub mod mirror;
 pub mod note;
+pub mod notify;
+pub mod offline_queue;
+pub mod outbox;
+pub mod payments;
+pub mod plan;
+pub mod privacy;
+mod private_record;
+pub mod profile;
+pub mod provenance;
+pub mod public;
+pub mod publish;
 pub mod query;
+pub mod recover;
+pub mod references;
+pub mod relay_info;
+pub mod relay_policy;
+pub mod retention;
+pub mod review;
+pub mod rules;
+pub mod runtime_stats;
+pub mod schema;
+pub mod search_index;
+pub mod settings;
+pub mod share;
+pub mod status;
+pub mod store;
+pub mod suggest;
+pub mod summarize;
+pub mod sync;
+pub mod tag_defaults;
+pub mod tags;
+pub mod tasks;
+#[cfg(feature = "testing")]
+pub mod testing;
+pub mod text;
+pub mod validate;
+pub mod voice;
 pub mod watch;
+pub mod widget;
 
-pub use note::Note;
+pub use account::{AccountDeletionReport, DeleteAccountOptions};
+pub use activity::{Activity, DayActivity};
+pub use aliases::resolve_tag;
+pub use app_state::{AppState, NoteState, StateChange, TagMetadata};
+pub use attachment::Attachment;
+#[cfg(feature = "attachments")]
+pub use attachment_cache::AttachmentCacheStats;
+pub use audit::{AuditAction, AuditEntry, AUDIT_LOG_MAX_BYTES};
+pub use batch::{Batch, BatchReport};
+pub use budget::{CompactionReport, RelayEventCount, BUDGET_WARNING_RATIO};
+pub use builder::{DialogBuilder, StoreBackend, DEFAULT_FETCH_TIMEOUT};
+pub use bulk::BulkTagReport;
+pub use cache::EvictionReport;
+pub use chaos::{ChaosConfig, CHAOS_DROP_REASON};
+pub use clock::{detect_clock_skew, Clock, ManualClock, SystemClock};
+pub use contacts::Contact;
+pub use debug::{FrameDirection, RelayFrame};
+pub use devices::Device;
+pub use digest::{digest_email, DigestFormat};
+pub use enrich::{EnrichSettings, LinkPreview};
+pub use idempotency::{IdempotencyKey, IDEMPOTENCY_WINDOW};
+pub use ids::{format_note_id, parse_note_id, NoteIdFormat};
+#[cfg(feature = "import")]
+pub use import::{
+    DuplicatePolicy, DuplicateReason, ImportDuplicate, ImportFormat, ImportReport,
+    DUPLICATE_WINDOW_SECS,
+};
+pub use kinds::{KindFeature, KindSet};
+pub use lint::{
+    lint_text, normalize_text, LintWarning, SecretKind, ValidationReport, MAX_BLANK_LINES,
+};
+pub use lock::LOCKED_PLACEHOLDER;
+pub use messages::DirectMessage;
+pub use migrate::MigrationReport;
+pub use mirror::{MirrorHandle, MirrorReport};
+pub use note::{Note, DEFAULT_NOTE_KIND, LEGACY_NOTE_KIND, MAX_NOTE_BYTES};
+pub use notify::NotifyRule;
+pub use offline_queue::{QueuedWrite, QueuedWriteKind};
+pub use outbox::{append_to_outbox, OutboxEntry};
+pub use payments::{DEFAULT_MAX_RELAY_PAYMENT_SATS, DEFAULT_RELAY_PAYMENT_BUDGET_SATS};
+pub use plan::{PublishPlan, PublishReport};
+pub use privacy::MAX_TIMESTAMP_FUZZ_SECS;
+pub use profile::{OpTiming, DEFAULT_SLOW_THRESHOLD};
+pub use provenance::{CaptureSource, Provenance};
+pub use public::PublishOptions;
+pub use publish::RateLimit;
+pub use query::{sort_notes, SortKey, SortOrder};
+pub use recover::RecoveryReport;
+pub use references::{RefProblem, RefWarning};
+pub use relay_info::RelayCapabilities;
+pub use relay_policy::{RelayPolicy, KIND_NAMES};
+pub use retention::{
+    RetentionAction, RetentionHandle, RetentionPolicy, RetentionReport, RetentionRule,
+    RETENTION_INTERVAL,
+};
+pub use review::REVIEW_AFTER_DAYS;
+pub use rules::{RuleMatch, RulesReport, TagRule};
+pub use runtime_stats::RuntimeStats;
+pub use schema::{SchemaProgress, SchemaReport, SCHEMA_VERSION};
+pub use search_index::{SearchIndexChanges, SearchIndexEntry};
+pub use settings::Settings;
+pub use share::{parse_share_url, GroupShare, ShareLink, ShareRecipient};
+pub use status::{DialogStatus, LastSync, RelayHealth};
+pub use summarize::{NoopSummarizer, Summarizer, SummaryFuture};
+pub use sync::{RelaySyncStats, SyncMode, SyncReport};
+pub use tag_defaults::TagDefaults;
+pub use tags::{add_hashtags, normalize_tag, parse_hashtags, remove_hashtags, strip_hashtags};
+pub use tasks::TaskStatus;
+pub use text::{
+    fold_for_search, grapheme_len, preview, reading_minutes, truncate_graphemes, word_count,
+    SearchQuery, PREVIEW_GRAPHEMES, WORDS_PER_MINUTE,
+};
+pub use tokio_util::sync::CancellationToken;
+pub use validate::{QuarantinedEvent, Rejection};
+pub use voice::VoiceData;
+pub use watch::{WatchHandle, WatchItem, WatchSpec};
+pub use widget::{read_widget_snapshot, WidgetKind, WidgetNote, WidgetSnapshot};
 
+/// New variants are added in minor releases
 #[derive(Error, Debug)]
+#[non_exhaustive]
 pub enum DialogError {
     #[error("Nostr error: {0}")]
     Nostr(#[from] nostr_sdk::client::Error),
@@ -24,36 +182,165 @@ pub enum DialogError {
     EventBuilder(#[from] nostr_sdk::event::builder::Error),
     #[error("Failed to get project directories")]
     ProjectDirs,
+    #[error("Sync failed: {0}")]
+    Sync(String),
+    #[error("Event not published: {0}")]
+    NotPublished(String),
+    #[error("Clipper error: {0}")]
+    Clipper(String),
+    #[error("HTTP error: {0}")]
+    Http(String),
+    #[error("Import failed: {0}")]
+    Import(String),
+    #[error("Note not found: {0}")]
+    NoteNotFound(EventId),
+    #[error("Invalid note id: {0} (expected hex, note1... or nevent1...)")]
+    InvalidNoteId(String),
+    #[error("Note too large: {actual} bytes (max {max})")]
+    NoteTooLarge { max: usize, actual: usize },
+    #[error("Query API error: {0}")]
+    Api(String),
+    #[error("Share link error: {0}")]
+    Share(String),
+    #[error("Summarizer failed: {0}")]
+    Summarize(String),
+    #[error("Payment failed: {0}")]
+    Payment(String),
+    #[error("Unsupported note kind: {0} (expected a regular kind from 1000 to 9999)")]
+    NoteKind(u16),
+    #[error("Locked note error: {0}")]
+    Lock(String),
+    #[error("Wrong passphrase")]
+    WrongPassphrase,
+    #[error("Invalid configuration: {0}")]
+    Config(String),
+    #[error("Keychain error: {0}")]
+    Keychain(String),
+    #[error("Cancelled")]
+    Cancelled,
 }
 
 pub type Result<T> = std::result::Result<T, DialogError>;
 
 pub struct Dialog {
-    pub client: Client,
-    pub keys: Keys,
+    pub(crate) client: Client,
+    pub(crate) keys: Keys,
+    // The client's database, for removing and restoring events
+    local_store: Arc<local_store::LocalStore>,
+    clock: Arc<dyn clock::Clock>,
+    created_at: clock::MonotonicTimestamps,
+    quarantine: Arc<validate::Quarantine>,
+    sync_state: sync::SyncState,
+    rate_limiter: Arc<publish::RateLimiter>,
+    app_state: Arc<app_state::AppStateStore>,
+    enrich_settings: std::sync::RwLock<enrich::EnrichSettings>,
+    wallet: payments::Wallet,
+    summarizer: std::sync::RwLock<Arc<dyn summarize::Summarizer>>,
+    note_kind: std::sync::atomic::AtomicU16,
+    max_privacy: file_store::JsonStore<bool>,
+    normalize_notes: std::sync::atomic::AtomicBool,
+    relay_info: relay_info::RelayInfoCache,
+    watches: Arc<watch::WatchRegistry>,
+    audit: audit::AuditLog,
+    // Text of notes unlocked this session
+    unlocked: std::sync::RwLock<std::collections::HashMap<EventId, String>>,
+    retention: file_store::JsonStore<retention::RetentionPolicy>,
+    search_journal: search_index::SearchJournal,
+    // Set by the builder; None probes each relay over NIP-11
+    sync_mode: Option<sync::SyncMode>,
+    fetch_timeout: std::time::Duration,
+    // None for in-memory storage
+    db_path: Option<PathBuf>,
+    outbox_path: std::sync::RwLock<Option<PathBuf>>,
+    // Last written by write_widget_snapshot; removed with the account
+    widget_path: std::sync::RwLock<Option<PathBuf>>,
+    recent_sends: idempotency::RecentSends,
+    saved_searches: std::sync::RwLock<std::collections::BTreeMap<String, String>>,
+    device: std::sync::RwLock<devices::DeviceInfo>,
+    // Bytes of notes kept locally; None keeps everything
+    cache_limit: std::sync::RwLock<Option<u64>>,
+    evicted_notes: cache::EvictedNotes,
+    kind_set: std::sync::RwLock<kinds::KindSet>,
+    relay_policies: relay_policy::RelayPolicies,
+    schema_version: schema::SchemaVersion,
+    debug_feed: Arc<debug::DebugFeed>,
+    event_counter: budget::EventCounter,
+    profiler: profile::Profiler,
+    #[cfg(feature = "attachments")]
+    attachment_cache: attachment_cache::AttachmentCache,
+    chaos: Arc<chaos::Chaos>,
+    offline_queue: offline_queue::OfflineQueue,
 }
 
 impl Dialog {
     pub async fn new(nsec: &str) -> Result<Self> {
-        let keys = Keys::parse(nsec)?;
-
-        // Use pubkey in path for isolation
-        let db_path = get_data_dir(&keys.public_key().to_hex())?;
-        let database = NdbDatabase::open(db_path.to_string_lossy())
-            .map_err(|e| DialogError::Database(e.to_string()))?;
+        Self::builder().signer(Keys::parse(nsec)?).build().await
+    }
 
-        let client = Client::builder()
-            .signer(keys.clone())
-            .database(database)
-            .build();
+    pub(crate) fn from_store(
+        builder: ClientBuilder,
+        local_store: local_store::LocalStore,
+        keys: Keys,
+    ) -> Self {
+        let local_store = Arc::new(local_store);
+        Self {
+            client: builder.database(local_store.clone()).build(),
+            keys,
+            local_store,
+            clock: Arc::new(clock::SystemClock),
+            created_at: clock::MonotonicTimestamps::default(),
+            quarantine: Arc::new(validate::Quarantine::default()),
+            sync_state: sync::SyncState::default(),
+            rate_limiter: Arc::new(publish::RateLimiter::default()),
+            app_state: Arc::new(app_state::AppStateStore::default()),
+            enrich_settings: Default::default(),
+            wallet: payments::Wallet::default(),
+            summarizer: std::sync::RwLock::new(Arc::new(summarize::NoopSummarizer)),
+            note_kind: std::sync::atomic::AtomicU16::new(note::DEFAULT_NOTE_KIND),
+            max_privacy: Default::default(),
+            normalize_notes: std::sync::atomic::AtomicBool::new(false),
+            relay_info: relay_info::RelayInfoCache::default(),
+            watches: Arc::default(),
+            audit: audit::AuditLog::default(),
+            unlocked: Default::default(),
+            retention: Default::default(),
+            search_journal: Default::default(),
+            sync_mode: None,
+            fetch_timeout: builder::DEFAULT_FETCH_TIMEOUT,
+            db_path: None,
+            outbox_path: Default::default(),
+            widget_path: Default::default(),
+            recent_sends: Default::default(),
+            saved_searches: Default::default(),
+            device: std::sync::RwLock::new(devices::DeviceInfo::generate()),
+            cache_limit: Default::default(),
+            evicted_notes: Default::default(),
+            kind_set: Default::default(),
+            relay_policies: Default::default(),
+            schema_version: Default::default(),
+            debug_feed: Arc::default(),
+            event_counter: Default::default(),
+            profiler: Default::default(),
+            #[cfg(feature = "attachments")]
+            attachment_cache: Default::default(),
+            chaos: Arc::default(),
+            offline_queue: Default::default(),
+        }
+    }
 
-        Ok(Self { client, keys })
+    /// Dialog backed by an in-memory event store, for tests
+    #[cfg(any(test, feature = "testing"))]
+    pub(crate) fn in_memory(keys: Keys) -> Self {
+        let builder = Client::builder().signer(keys.clone());
+        Self::from_store(builder, local_store::LocalStore::memory(), keys)
     }
 
     pub async fn new_with_relay(nsec: &str, relay_url: &str) -> Result<Self> {
-        let dialog = Self::new(nsec).await?;
-        dialog.connect_relay(relay_url).await?;
-        Ok(dialog)
+        Self::builder()
+            .signer(Keys::parse(nsec)?)
+            .relay(relay_url)
+            .build()
+            .await
     }
 
     pub async fn connect_relay(&self, url: &str) -> Result<()> {
@@ -65,12 +352,24 @@ impl Dialog {
         Ok(())
     }
 
+    /// Relays added with [`Dialog::connect_relay`] or the builder
+    pub async fn relay_urls(&self) -> Vec<RelayUrl> {
+        self.client.relays().await.into_keys().collect()
+    }
+
     pub fn public_key(&self) -> PublicKey {
         self.keys.public_key()
     }
 }
 
-fn get_data_dir(pubkey: &str) -> Result<PathBuf> {
+fn get_data_dir(base: Option<&Path>, pubkey: &str) -> Result<PathBuf> {
+    // 0) Configured through the builder
+    if let Some(base) = base {
+        let p = base.join(pubkey);
+        std::fs::create_dir_all(&p)?;
+        return Ok(p.join("nostrdb"));
+    }
+
     // 1) CI / user override
     if let Ok(p) = std::env::var("DIALOG_DATA_DIR") {
         let p = PathBuf::from(p).join(pubkey);
@@ -113,18 +412,17 @@ pub fn validate_nsec(nsec: &str) -> Result<()> {
 }
 
 impl Dialog {
-    /// Mark a note as read - stores in local database only
-    pub async fn mark_as_read(&self, note_id: &EventId) -> Result<()> {
-        // Create a local-only Kind 30078 event for app data
+    /// Mark a note as synced locally
+    pub async fn mark_as_synced(&self, note_id: &EventId) -> Result<()> {
         let content = serde_json::json!({
-            "type": "read_status",
+            "type": "sync_status",
             "note_id": note_id.to_hex(),
-            "is_read": true,
-            "timestamp": Timestamp::now().as_u64()
+            "is_synced": true,
+            "timestamp": self.now().as_u64()
         })
         .to_string();
 
-        let event = EventBuilder::new(Kind::from(30078), content)
+        let event = EventBuilder::new(Kind::from(kinds::APP_DATA_KIND), content)
             .tag(Tag::custom(
                 TagKind::SingleLetter(SingleLetterTag::lowercase(Alphabet::D)),
                 vec!["dialog_local_state"],
@@ -132,7 +430,6 @@ impl Dialog {
             .sign(&self.keys)
             .await?;
 
-        // Save to database but NEVER publish to relays
         self.client
             .database()
             .save_event(&event)
@@ -141,60 +438,38 @@ impl Dialog {
 
         Ok(())
     }
+}
 
-    /// Get read status for a note from local state
-    pub async fn get_read_status(&self, note_id: &EventId) -> bool {
-        // Query local database for read status
-        let filter = Filter::new()
-            .author(self.keys.public_key())
-            .kind(Kind::from(30078))
-            .custom_tag(
-                SingleLetterTag::lowercase(Alphabet::D),
-                vec!["dialog_local_state"],
-            )
-            .limit(1000); // Get recent local state events
-
-        if let Ok(events) = self.client.database().query(vec![filter]).await {
-            // Convert to vec first to use rev()
-            let events_vec: Vec<_> = events.into_iter().collect();
-            // Look through events for this note's read status (most recent first)
-            for event in events_vec.iter().rev() {
-                // Parse content to check if it's for this note
-                if let Ok(data) = serde_json::from_str::<serde_json::Value>(&event.content) {
-                    if data["type"] == "read_status" && data["note_id"] == note_id.to_hex() {
-                        return data["is_read"].as_bool().unwrap_or(false);
-                    }
-                }
-            }
-        }
+#[cfg(test)]
+pub(crate) mod test_support {
+    use super::*;
 
-        false // Default to unread
+    /// Dialog backed by an in-memory event store with a fresh key
+    pub(crate) fn memory_dialog() -> Dialog {
+        Dialog::in_memory(Keys::generate())
     }
 
-    /// Mark a note as synced locally
-    pub async fn mark_as_synced(&self, note_id: &EventId) -> Result<()> {
-        let content = serde_json::json!({
-            "type": "sync_status",
-            "note_id": note_id.to_hex(),
-            "is_synced": true,
-            "timestamp": Timestamp::now().as_u64()
-        })
-        .to_string();
-
-        let event = EventBuilder::new(Kind::from(30078), content)
-            .tag(Tag::custom(
-                TagKind::SingleLetter(SingleLetterTag::lowercase(Alphabet::D)),
-                vec!["dialog_local_state"],
-            ))
-            .sign(&self.keys)
-            .await?;
-
-        self.client
-            .database()
-            .save_event(&event)
-            .await
-            .map_err(|e| DialogError::Database(e.to_string()))?;
+    /// Arbitrary note text: unicode, emoji and newline-heavy bodies followed
+    /// by a few hashtags (including emoji-only and accented ones)
+    pub(crate) fn note_text() -> impl proptest::strategy::Strategy<Value = String> {
+        use proptest::prelude::*;
 
-        Ok(())
+        let body = prop_oneof![
+            "\\PC{1,200}",
+            "[a-z ]{0,10}(\n{1,8}[a-z ]{0,10}){1,20}",
+            "[😀🔥🎉🚀✨🇯🇵 ]{1,20}",
+        ];
+        let tag = prop_oneof![
+            "[a-zA-Z][a-zA-Z0-9_]{0,15}",
+            "[😀🔥🎉🚀✨]{1,4}",
+            "[à-öø-ÿ]{1,8}",
+        ];
+        (body, prop::collection::vec(tag, 0..5)).prop_map(|(mut text, tags)| {
+            for tag in tags {
+                text.push_str(" #");
+                text.push_str(&tag);
+            }
+            text
+        })
     }
 }
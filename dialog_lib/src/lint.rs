@@ -0,0 +1,259 @@
+use crate::tags::parse_hashtags;
+use crate::{Dialog, Result, MAX_NOTE_BYTES};
+use nostr_sdk::prelude::*;
+use regex::Regex;
+use std::sync::atomic::Ordering;
+use std::sync::OnceLock;
+use unicode_normalization::UnicodeNormalization;
+
+/// Blank lines [`normalize_text`] keeps in a row
+pub const MAX_BLANK_LINES: usize = 1;
+
+/// What a [`LintWarning::PotentialSecretDetected`] looks like
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SecretKind {
+    /// A bech32 `nsec1...` private key
+    Nsec,
+    /// 64 hex characters, the raw form of a private key
+    HexKey,
+}
+
+/// Something in a note's text worth a second look before it is saved
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LintWarning {
+    /// Looks like a pasted private key. Notes are encrypted, but they are
+    /// encrypted to that same kind of key and kept on relays indefinitely.
+    PotentialSecretDetected {
+        kind: SecretKind,
+        /// Byte offset in the text
+        start: usize,
+        /// The first characters only, safe to show
+        masked: String,
+    },
+}
+
+/// What sending a draft would run into; see [`Dialog::validate_note`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationReport {
+    /// Size of the relay message carrying the encrypted note
+    pub estimated_bytes: usize,
+    /// Over [`MAX_NOTE_BYTES`]; creating the note would fail
+    pub too_large: bool,
+    /// Connected relays whose advertised message limit it exceeds
+    pub rejected_by: Vec<RelayUrl>,
+    /// Hashtags the note will have, including ones added by tag rules
+    pub tag_count: usize,
+    pub warnings: Vec<LintWarning>,
+}
+
+/// Length of a NIP-44 v2 payload for `len` bytes of text, base64 included
+fn encrypted_len(len: usize) -> usize {
+    let padded = if len <= 32 {
+        32
+    } else {
+        let next_power = (len - 1).next_power_of_two().max(64);
+        let chunk = if next_power <= 256 {
+            32
+        } else {
+            next_power / 8
+        };
+        chunk * ((len - 1) / chunk + 1)
+    };
+    // Version byte, nonce, length prefix, padded text and MAC
+    let payload = 1 + 32 + 2 + padded + 32;
+    payload.div_ceil(3) * 4
+}
+
+/// `text` with Unicode in NFC form, trailing whitespace removed from every
+/// line and the end, and runs of blank lines cut to [`MAX_BLANK_LINES`]
+pub fn normalize_text(text: &str) -> String {
+    let text: String = text.nfc().collect();
+    let mut lines = Vec::new();
+    let mut blank = 0;
+    for line in text.lines().map(str::trim_end) {
+        blank = if line.is_empty() { blank + 1 } else { 0 };
+        if blank <= MAX_BLANK_LINES {
+            lines.push(line);
+        }
+    }
+    lines.join("\n").trim_end().to_string()
+}
+
+/// Warnings for `text`, in order of appearance. Any 64 hex characters
+/// count, including note ids; [`Dialog::lint_note`] leaves out the ones
+/// it knows to be public.
+pub fn lint_text(text: &str) -> Vec<LintWarning> {
+    static SECRETS: OnceLock<Regex> = OnceLock::new();
+    let secrets = SECRETS.get_or_init(|| {
+        Regex::new(r"\b(?:nsec1[0-9a-z]{58}|[0-9a-fA-F]{64})\b").expect("valid regex")
+    });
+    secrets
+        .find_iter(text)
+        .map(|m| LintWarning::PotentialSecretDetected {
+            kind: if m.as_str().starts_with("nsec1") {
+                SecretKind::Nsec
+            } else {
+                SecretKind::HexKey
+            },
+            start: m.start(),
+            masked: format!("{}…", &m.as_str()[..8]),
+        })
+        .collect()
+}
+
+impl Dialog {
+    /// Run new notes through [`normalize_text`] before they are saved.
+    /// Off by default; text is kept exactly as typed.
+    pub fn set_normalize_notes(&self, enabled: bool) {
+        self.normalize_notes.store(enabled, Ordering::SeqCst);
+    }
+
+    pub fn normalize_notes(&self) -> bool {
+        self.normalize_notes.load(Ordering::SeqCst)
+    }
+
+    /// [`lint_text`] warnings for a draft, without hex that names a stored
+    /// event (a referenced note id, say) or this profile's public key
+    pub async fn lint_note(&self, text: &str) -> Vec<LintWarning> {
+        let mut warnings = Vec::new();
+        for warning in lint_text(text) {
+            let LintWarning::PotentialSecretDetected { kind, start, .. } = &warning;
+            if *kind == SecretKind::HexKey && self.is_public_hex(&text[*start..*start + 64]).await {
+                continue;
+            }
+            warnings.push(warning);
+        }
+        warnings
+    }
+
+    async fn is_public_hex(&self, hex: &str) -> bool {
+        if hex.eq_ignore_ascii_case(&self.keys.public_key().to_hex()) {
+            return true;
+        }
+        let Ok(id) = EventId::from_hex(hex) else {
+            return false;
+        };
+        self.client
+            .database()
+            .count(vec![Filter::new().id(id)])
+            .await
+            .is_ok_and(|count| count > 0)
+    }
+
+    /// Check a draft before it is sent: its size against our limit and
+    /// the limits relays have advertised (as last fetched; none are asked
+    /// now), its tag count and [`Dialog::lint_note`] warnings.
+    pub async fn validate_note(&self, text: &str) -> Result<ValidationReport> {
+        let text = self.prepare_text(text).await;
+        let too_large = text.len() > MAX_NOTE_BYTES;
+        let estimated_bytes = if too_large {
+            // Can't be built; the content alone is most of it anyway
+            encrypted_len(text.len())
+        } else {
+            let event = self.build_note_event_at(&text, self.now()).await?;
+            // Sent as ["EVENT",<event>]
+            event.as_json().len() + r#"["EVENT",]"#.len()
+        };
+
+        let mut rejected_by = Vec::new();
+        for url in self.client.relays().await.into_keys() {
+            let limit = self
+                .cached_capabilities(&url)
+                .await
+                .and_then(|capabilities| capabilities.max_message_length);
+            if limit.is_some_and(|limit| estimated_bytes as u64 > limit) {
+                rejected_by.push(url);
+            }
+        }
+
+        Ok(ValidationReport {
+            estimated_bytes,
+            too_large,
+            rejected_by,
+            tag_count: parse_hashtags(&text).len(),
+            warnings: self.lint_note(&text).await,
+        })
+    }
+
+    /// Text of a new note as it will be stored: normalized if enabled, then
+    /// tagged by the tag rules
+    pub(crate) async fn prepare_text(&self, text: &str) -> String {
+        if self.normalize_notes() {
+            self.auto_tag(&normalize_text(text)).await
+        } else {
+            self.auto_tag(text).await
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::memory_dialog;
+    use nostr_sdk::prelude::*;
+
+    #[tokio::test]
+    async fn test_notes_are_normalized_and_linted() {
+        // "é" as e + combining accent becomes the single precomposed char
+        let messy = "Cafe\u{301}  \n\n\n\nmenu\t\n\n";
+        assert_eq!(normalize_text(messy), "Caf\u{e9}\n\nmenu");
+
+        let nsec = Keys::generate().secret_key().to_bech32().unwrap();
+        let warnings = lint_text(&format!("my key: {nsec}"));
+        assert_eq!(
+            warnings,
+            vec![LintWarning::PotentialSecretDetected {
+                kind: SecretKind::Nsec,
+                start: 8,
+                masked: format!("{}…", &nsec[..8]),
+            }]
+        );
+        assert!(lint_text("nothing to see in note1abc").is_empty());
+
+        let dialog = memory_dialog();
+        assert_eq!(dialog.save_note(messy).await.unwrap().text, messy);
+        dialog.set_normalize_notes(true);
+        let note = dialog.save_note(messy).await.unwrap();
+        assert_eq!(note.text, "Caf\u{e9}\n\nmenu");
+    }
+
+    #[tokio::test]
+    async fn test_validate_note_reports_size_tags_and_secrets() {
+        let dialog = memory_dialog();
+        let report = dialog.validate_note("Plan the trip #travel").await.unwrap();
+        assert!(!report.too_large);
+        assert_eq!(report.tag_count, 1);
+        assert!(report.warnings.is_empty());
+        assert!(report.estimated_bytes > encrypted_len("Plan the trip #travel".len()));
+
+        let hex = Keys::generate().secret_key().to_secret_hex();
+        let report = dialog.validate_note(&hex).await.unwrap();
+        assert_eq!(report.warnings.len(), 1);
+
+        let report = dialog
+            .validate_note(&"x".repeat(MAX_NOTE_BYTES + 1))
+            .await
+            .unwrap();
+        assert!(report.too_large);
+        assert!(report.estimated_bytes > MAX_NOTE_BYTES);
+        // Nothing was saved along the way
+        assert!(dialog.list_notes(10).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_note_ids_are_not_secrets() {
+        let dialog = memory_dialog();
+        let note = dialog.save_note("Plan the trip").await.unwrap();
+        let text = format!(
+            "Follow-up to {} by {}",
+            note.id.to_hex(),
+            dialog.public_key().to_hex()
+        );
+        assert_eq!(lint_text(&text).len(), 2);
+        assert!(dialog.lint_note(&text).await.is_empty());
+
+        // Hex that names nothing stored still looks like a key
+        let hex = Keys::generate().secret_key().to_secret_hex();
+        assert_eq!(dialog.lint_note(&format!("{text} {hex}")).await.len(), 1);
+    }
+}
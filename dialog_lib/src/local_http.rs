@@ -0,0 +1,168 @@
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+
+const MAX_HEADER_BYTES: usize = 16 * 1024;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// One HTTP/1.1 request to a localhost server (web clipper, query API).
+/// Connections carry a single request and get a JSON reply.
+pub(crate) struct Request {
+    pub method: String,
+    pub path: String,
+    pub authorization: Option<String>,
+    pub content_type: Option<String>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    /// Whether the request carries `Authorization: Bearer <token>`
+    pub fn is_authorized(&self, token: &str) -> bool {
+        self.authorization
+            .as_deref()
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .is_some_and(|presented| tokens_match(presented, token))
+    }
+}
+
+pub(crate) struct Response {
+    pub status: &'static str,
+    pub body: String,
+}
+
+impl Response {
+    pub fn new(status: &'static str, body: impl Into<String>) -> Self {
+        Self {
+            status,
+            body: body.into(),
+        }
+    }
+
+    pub fn error(status: &'static str, message: &str) -> Self {
+        Self::new(status, serde_json::json!({ "error": message }).to_string())
+    }
+}
+
+/// Read one request, giving up after [`REQUEST_TIMEOUT`]. Protocol problems
+/// come back as the response to send.
+pub(crate) async fn read_request(
+    stream: &mut TcpStream,
+    max_body_bytes: usize,
+) -> std::result::Result<Request, Response> {
+    match tokio::time::timeout(REQUEST_TIMEOUT, read_request_inner(stream, max_body_bytes)).await {
+        Ok(result) => result,
+        Err(_) => Err(Response::error("408 Request Timeout", "request timed out")),
+    }
+}
+
+async fn read_request_inner(
+    stream: &mut TcpStream,
+    max_body_bytes: usize,
+) -> std::result::Result<Request, Response> {
+    let bad_request = |message: &str| Response::error("400 Bad Request", message);
+    let mut reader = BufReader::new(stream);
+
+    let mut head = Vec::new();
+    loop {
+        let before = head.len();
+        // Bound each read so one endless header line can't grow the buffer
+        let remaining = (MAX_HEADER_BYTES + 1).saturating_sub(head.len()) as u64;
+        let read = (&mut reader)
+            .take(remaining)
+            .read_until(b'\n', &mut head)
+            .await
+            .map_err(|e| bad_request(&e.to_string()))?;
+        if read == 0 {
+            return Err(bad_request("connection closed mid-request"));
+        }
+        if head.len() > MAX_HEADER_BYTES {
+            return Err(Response::error(
+                "431 Request Header Fields Too Large",
+                "headers too large",
+            ));
+        }
+        // A bare CRLF (or LF) ends the header block
+        if matches!(&head[before..], b"\r\n" | b"\n") {
+            break;
+        }
+    }
+
+    let head = String::from_utf8(head).map_err(|_| bad_request("headers are not UTF-8"))?;
+    let mut lines = head.lines();
+    let mut request_line = lines.next().unwrap_or_default().split_whitespace();
+    let (Some(method), Some(path)) = (request_line.next(), request_line.next()) else {
+        return Err(bad_request("malformed request line"));
+    };
+
+    let mut request = Request {
+        method: method.to_string(),
+        path: path.to_string(),
+        authorization: None,
+        content_type: None,
+        body: Vec::new(),
+    };
+    let mut content_length = 0;
+    for line in lines {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        match name.trim().to_ascii_lowercase().as_str() {
+            "authorization" => request.authorization = Some(value.to_string()),
+            "content-type" => request.content_type = Some(value.to_ascii_lowercase()),
+            "content-length" => {
+                content_length = value
+                    .parse()
+                    .map_err(|_| bad_request("invalid Content-Length"))?
+            }
+            _ => {}
+        }
+    }
+
+    if content_length > max_body_bytes {
+        return Err(Response::error("413 Payload Too Large", "body too large"));
+    }
+    request.body = vec![0; content_length];
+    reader
+        .read_exact(&mut request.body)
+        .await
+        .map_err(|e| bad_request(&e.to_string()))?;
+    Ok(request)
+}
+
+pub(crate) async fn write_response(
+    stream: &mut TcpStream,
+    response: Response,
+) -> std::io::Result<()> {
+    let head = format!(
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        response.status,
+        response.body.len()
+    );
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(response.body.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+/// Compare without short-circuiting so response timing doesn't leak the token
+pub(crate) fn tokens_match(presented: &str, expected: &str) -> bool {
+    presented.len() == expected.len()
+        && presented
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokens_match() {
+        let token = "0123456789abcdef";
+        assert!(tokens_match(token, token));
+        assert!(!tokens_match("0123456789abcdeX", token));
+        assert!(!tokens_match("short", token));
+    }
+}
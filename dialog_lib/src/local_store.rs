@@ -0,0 +1,506 @@
+use crate::file_store::JsonStore;
+use crate::{DialogError, Result};
+use nostr_sdk::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+/// Next to the database; events removed locally
+pub(crate) const REMOVED_EVENTS_FILE: &str = "removed_events.json";
+
+/// Events read per query while rewriting the database
+const REWRITE_PAGE: usize = 1000;
+
+/// How long a rewrite waits for nostrdb to ingest the copied events
+const REWRITE_INGEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// What is kept of an event removed locally
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct RemovedEvent {
+    created_at: Timestamp,
+    kind: Kind,
+    author: PublicKey,
+    /// Still in the underlying database, waiting for the next rewrite
+    stored: bool,
+}
+
+impl RemovedEvent {
+    fn matches(&self, id: &EventId, filter: &Filter) -> bool {
+        filter.ids.as_ref().is_none_or(|ids| ids.contains(id))
+            && filter.authors.as_ref().is_none_or(|a| a.contains(&self.author))
+            && filter.kinds.as_ref().is_none_or(|k| k.contains(&self.kind))
+            && filter.since.is_none_or(|since| self.created_at >= since)
+            && filter.until.is_none_or(|until| self.created_at <= until)
+            // Tags aren't kept, so tag and search filters never match
+            && filter.generic_tags.is_empty()
+            && filter.search.is_none()
+    }
+}
+
+/// The event store every [`crate::Dialog`] reads and writes through.
+///
+/// nostrdb can't delete events, so removing them (eviction, purges,
+/// batches) is done here: removed ids are recorded next to the database
+/// and hidden from every query at once, and the database is rewritten
+/// without them the next time it is opened. Until then a removal can be
+/// undone with [`LocalStore::restore`].
+///
+/// Removed events stay removed: saving them again is a no-op, so a sync
+/// can't bring them back, and they are offered to negentropy as present, so
+/// relays don't send them in the first place. Restoring lets them back in.
+#[derive(Debug)]
+pub(crate) struct LocalStore {
+    // Swapped for an empty in-memory database by `close`
+    inner: RwLock<Arc<dyn NostrDatabase>>,
+    removed: JsonStore<BTreeMap<EventId, RemovedEvent>>,
+    // Ids of `removed`, checked on every read
+    hidden: RwLock<HashSet<EventId>>,
+}
+
+impl LocalStore {
+    pub(crate) fn memory() -> Self {
+        Self::new(Arc::new(memory_database()), JsonStore::default())
+    }
+
+    fn new(
+        inner: Arc<dyn NostrDatabase>,
+        removed: JsonStore<BTreeMap<EventId, RemovedEvent>>,
+    ) -> Self {
+        let hidden = removed
+            .get()
+            .map(|removed| removed.keys().copied().collect())
+            .unwrap_or_else(|e| {
+                eprintln!("[lib] store: removed events unavailable: {e}");
+                HashSet::new()
+            });
+        Self {
+            inner: RwLock::new(inner),
+            removed,
+            hidden: RwLock::new(hidden),
+        }
+    }
+
+    /// Open the nostrdb database at `path`, first rewriting it without the
+    /// events removed since it was last opened
+    pub(crate) async fn open_ndb(path: &Path, removed_file: &Path) -> Result<Self> {
+        let removed = JsonStore::at(removed_file.to_path_buf());
+        finish_swap(path)?;
+        let pending: HashSet<EventId> = removed
+            .get()?
+            .into_iter()
+            .filter(|(_, event)| event.stored)
+            .map(|(id, _)| id)
+            .collect();
+        if !pending.is_empty() {
+            // The events stay hidden either way; the rewrite is tried again
+            // on the next open
+            match rewrite_without(path, &pending).await {
+                Ok(kept) => {
+                    eprintln!(
+                        "[lib] store: rewrote database without {} events, {kept} kept",
+                        pending.len()
+                    );
+                    removed.update(|removed| {
+                        for event in removed.values_mut() {
+                            event.stored = false;
+                        }
+                        ((), true)
+                    })?;
+                }
+                Err(e) => eprintln!("[lib] store: rewrite failed: {e}"),
+            }
+        }
+        let database = NdbDatabase::open(path.to_string_lossy())
+            .map_err(|e| DialogError::Database(e.to_string()))?;
+        Ok(Self::new(Arc::new(database), removed))
+    }
+
+    fn inner(&self) -> Arc<dyn NostrDatabase> {
+        self.inner.read().unwrap().clone()
+    }
+
+    /// Let go of the database, e.g. before its directory is removed. Reads
+    /// and writes go to an empty in-memory store afterwards.
+    pub(crate) fn close(&self) {
+        *self.inner.write().unwrap() = Arc::new(memory_database());
+        self.hidden.write().unwrap().clear();
+    }
+
+    fn is_hidden(&self, id: &EventId) -> bool {
+        self.hidden.read().unwrap().contains(id)
+    }
+
+    /// Let removed events be saved and listed again, e.g. when an evicted
+    /// note is fetched back. Events not yet rewritten away reappear as they
+    /// were.
+    pub(crate) fn restore(&self, ids: &[EventId]) -> Result<()> {
+        self.removed.update(|removed| {
+            let before = removed.len();
+            removed.retain(|id, _| !ids.contains(id));
+            ((), removed.len() < before)
+        })?;
+        let mut hidden = self.hidden.write().unwrap();
+        for id in ids {
+            hidden.remove(id);
+        }
+        Ok(())
+    }
+}
+
+fn memory_database() -> MemoryDatabase {
+    MemoryDatabase::with_opts(MemoryDatabaseOptions {
+        events: true,
+        ..Default::default()
+    })
+}
+
+fn backend_error(e: impl std::fmt::Display) -> DatabaseError {
+    DatabaseError::backend(std::io::Error::other(e.to_string()))
+}
+
+#[async_trait]
+impl NostrEventsDatabase for LocalStore {
+    async fn save_event(&self, event: &Event) -> std::result::Result<bool, DatabaseError> {
+        if self.is_hidden(&event.id) {
+            return Ok(false);
+        }
+        self.inner().save_event(event).await
+    }
+
+    async fn check_id(
+        &self,
+        event_id: &EventId,
+    ) -> std::result::Result<DatabaseEventStatus, DatabaseError> {
+        if self.is_hidden(event_id) {
+            return Ok(DatabaseEventStatus::Deleted);
+        }
+        self.inner().check_id(event_id).await
+    }
+
+    async fn has_coordinate_been_deleted(
+        &self,
+        coordinate: &Coordinate,
+        timestamp: &Timestamp,
+    ) -> std::result::Result<bool, DatabaseError> {
+        self.inner()
+            .has_coordinate_been_deleted(coordinate, timestamp)
+            .await
+    }
+
+    async fn event_id_seen(
+        &self,
+        event_id: EventId,
+        relay_url: RelayUrl,
+    ) -> std::result::Result<(), DatabaseError> {
+        self.inner().event_id_seen(event_id, relay_url).await
+    }
+
+    async fn event_seen_on_relays(
+        &self,
+        event_id: &EventId,
+    ) -> std::result::Result<Option<HashSet<RelayUrl>>, DatabaseError> {
+        self.inner().event_seen_on_relays(event_id).await
+    }
+
+    async fn event_by_id(
+        &self,
+        event_id: &EventId,
+    ) -> std::result::Result<Option<Event>, DatabaseError> {
+        if self.is_hidden(event_id) {
+            return Ok(None);
+        }
+        self.inner().event_by_id(event_id).await
+    }
+
+    async fn count(&self, filters: Vec<Filter>) -> std::result::Result<usize, DatabaseError> {
+        if self.hidden.read().unwrap().is_empty() {
+            return self.inner().count(filters).await;
+        }
+        Ok(self.query(filters).await?.len())
+    }
+
+    async fn query(&self, filters: Vec<Filter>) -> std::result::Result<Events, DatabaseError> {
+        let hidden = self.hidden.read().unwrap().clone();
+        if hidden.is_empty() {
+            return self.inner().query(filters).await;
+        }
+        // Ask for enough extra that hidden events can't push visible ones
+        // past a limit
+        let widened = filters
+            .iter()
+            .cloned()
+            .map(|mut filter| {
+                filter.limit = filter.limit.map(|limit| limit + hidden.len());
+                filter
+            })
+            .collect();
+        let mut events = Events::new(&filters);
+        events.extend(
+            self.inner()
+                .query(widened)
+                .await?
+                .into_iter()
+                .filter(|event| !hidden.contains(&event.id)),
+        );
+        Ok(events)
+    }
+
+    async fn negentropy_items(
+        &self,
+        filter: Filter,
+    ) -> std::result::Result<Vec<(EventId, Timestamp)>, DatabaseError> {
+        let mut items: HashMap<EventId, Timestamp> = self
+            .inner()
+            .negentropy_items(filter.clone())
+            .await?
+            .into_iter()
+            .collect();
+        // Removed events count as present, so relays don't send them again
+        for (id, event) in self.removed.get().map_err(backend_error)? {
+            if event.matches(&id, &filter) {
+                items.insert(id, event.created_at);
+            }
+        }
+        Ok(items.into_iter().collect())
+    }
+
+    async fn delete(&self, filter: Filter) -> std::result::Result<(), DatabaseError> {
+        let events = self.inner().query(vec![filter.clone()]).await?;
+        if events.is_empty() {
+            return Ok(());
+        }
+        let removed: Vec<(EventId, RemovedEvent)> = events
+            .iter()
+            .map(|event| {
+                let stub = RemovedEvent {
+                    created_at: event.created_at,
+                    kind: event.kind,
+                    author: event.pubkey,
+                    stored: true,
+                };
+                (event.id, stub)
+            })
+            .collect();
+        // Only recorded: the events are dropped from the database the next
+        // time it's opened, so until then `restore` undoes this
+        self.removed
+            .update(|stubs| {
+                stubs.extend(removed.iter().copied());
+                ((), true)
+            })
+            .map_err(backend_error)?;
+        self.hidden
+            .write()
+            .unwrap()
+            .extend(removed.iter().map(|(id, _)| *id));
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl NostrDatabase for LocalStore {
+    fn backend(&self) -> Backend {
+        self.inner().backend()
+    }
+
+    async fn wipe(&self) -> std::result::Result<(), DatabaseError> {
+        self.inner().wipe().await?;
+        self.removed.set(BTreeMap::new()).map_err(backend_error)?;
+        self.hidden.write().unwrap().clear();
+        Ok(())
+    }
+}
+
+/// Sibling of the database directory used during a rewrite
+fn sibling(path: &Path, suffix: &str) -> std::path::PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("{name}.{suffix}"))
+}
+
+/// Clean up after a rewrite that was interrupted: put the old database back
+/// if the new one never made it into place, and drop leftovers
+fn finish_swap(path: &Path) -> Result<()> {
+    let old = sibling(path, "old");
+    let new = sibling(path, "rewrite");
+    if old.exists() {
+        if path.exists() {
+            fs::remove_dir_all(&old)?;
+        } else {
+            fs::rename(&old, path)?;
+        }
+    }
+    if new.exists() {
+        fs::remove_dir_all(&new)?;
+    }
+    Ok(())
+}
+
+/// Copy every event not in `removed` into a fresh database and swap it in for
+/// the one at `path`. Returns how many events were kept.
+async fn rewrite_without(path: &Path, removed: &HashSet<EventId>) -> Result<usize> {
+    if !path.exists() {
+        return Ok(0);
+    }
+    let events = {
+        let old = NdbDatabase::open(path.to_string_lossy())
+            .map_err(|e| DialogError::Database(e.to_string()))?;
+        all_events(&old).await?
+    };
+    let kept: Vec<Event> = events
+        .into_iter()
+        .filter(|event| !removed.contains(&event.id))
+        .collect();
+
+    let new_path = sibling(path, "rewrite");
+    fs::create_dir_all(&new_path)?;
+    {
+        let new = NdbDatabase::open(new_path.to_string_lossy())
+            .map_err(|e| DialogError::Database(e.to_string()))?;
+        for event in &kept {
+            new.save_event(event)
+                .await
+                .map_err(|e| DialogError::Database(e.to_string()))?;
+        }
+        // nostrdb ingests in the background; only swap once it has everything
+        let started = std::time::Instant::now();
+        while all_events(&new).await?.len() < kept.len() {
+            if started.elapsed() > REWRITE_INGEST_TIMEOUT {
+                drop_dir(&new_path);
+                return Err(DialogError::Database(format!(
+                    "rewrite stored fewer than {} events",
+                    kept.len()
+                )));
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    }
+
+    let old_path = sibling(path, "old");
+    fs::rename(path, &old_path)?;
+    fs::rename(&new_path, path)?;
+    fs::remove_dir_all(&old_path)?;
+    Ok(kept.len())
+}
+
+fn drop_dir(path: &Path) {
+    if let Err(e) = fs::remove_dir_all(path) {
+        eprintln!("[lib] store: failed to remove {}: {e}", path.display());
+    }
+}
+
+/// Every event in `database`, paged newest first since nostrdb caps results
+async fn all_events(database: &NdbDatabase) -> Result<Vec<Event>> {
+    let mut events: HashMap<EventId, Event> = HashMap::new();
+    let mut until = None;
+    let mut limit = REWRITE_PAGE;
+    loop {
+        let mut filter = Filter::new().limit(limit);
+        if let Some(until) = until {
+            filter = filter.until(until);
+        }
+        let page = database
+            .query(vec![filter])
+            .await
+            .map_err(|e| DialogError::Database(e.to_string()))?;
+        let full = page.len() >= limit;
+        let oldest = page.iter().map(|event| event.created_at).min();
+        let mut added = 0;
+        for event in page {
+            if events.insert(event.id, event).is_none() {
+                added += 1;
+            }
+        }
+        if !full {
+            break;
+        }
+        if added == 0 {
+            // A whole page shares one timestamp; widen until it fits
+            limit *= 2;
+        } else {
+            until = oldest;
+        }
+    }
+    Ok(events.into_values().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("dialog-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[tokio::test]
+    async fn test_removed_events_are_hidden_until_restored() {
+        let keys = Keys::generate();
+        let store = LocalStore::memory();
+        let kept = EventBuilder::text_note("kept")
+            .sign_with_keys(&keys)
+            .unwrap();
+        let gone = EventBuilder::text_note("gone")
+            .sign_with_keys(&keys)
+            .unwrap();
+        store.save_event(&kept).await.unwrap();
+        store.save_event(&gone).await.unwrap();
+
+        store.delete(Filter::new().id(gone.id)).await.unwrap();
+        let events = store.query(vec![Filter::new().limit(1)]).await.unwrap();
+        assert_eq!(events.first().map(|e| e.id), Some(kept.id));
+        assert!(!store.save_event(&gone).await.unwrap());
+        assert_eq!(
+            store.check_id(&gone.id).await.unwrap(),
+            DatabaseEventStatus::Deleted
+        );
+        let items = store
+            .negentropy_items(Filter::new().author(keys.public_key()))
+            .await;
+        assert!(items.unwrap().iter().any(|(id, _)| *id == gone.id));
+
+        // Not dropped yet, so restoring brings it straight back
+        store.restore(&[gone.id]).unwrap();
+        assert!(store.event_by_id(&gone.id).await.unwrap().is_some());
+        assert_eq!(store.count(vec![Filter::new()]).await.unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_ndb_rewrite_drops_removed_events() {
+        let dir = temp_dir("local-store");
+        let path = dir.join("nostrdb");
+        let removed = dir.join(REMOVED_EVENTS_FILE);
+        let keys = Keys::generate();
+        let kept = EventBuilder::text_note("kept")
+            .sign_with_keys(&keys)
+            .unwrap();
+        let gone = EventBuilder::text_note("gone")
+            .sign_with_keys(&keys)
+            .unwrap();
+
+        {
+            let store = LocalStore::open_ndb(&path, &removed).await.unwrap();
+            store.save_event(&kept).await.unwrap();
+            store.save_event(&gone).await.unwrap();
+            while store.count(vec![Filter::new()]).await.unwrap() < 2 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            // nostrdb can't delete, but the event is gone from reads at once
+            store.delete(Filter::new().id(gone.id)).await.unwrap();
+            assert!(store.event_by_id(&gone.id).await.unwrap().is_none());
+            assert_eq!(store.count(vec![Filter::new()]).await.unwrap(), 1);
+        }
+
+        let store = LocalStore::open_ndb(&path, &removed).await.unwrap();
+        assert!(store.inner().event_by_id(&gone.id).await.unwrap().is_none());
+        assert!(store.event_by_id(&kept.id).await.unwrap().is_some());
+        assert!(!store.save_event(&gone).await.unwrap());
+        assert!(!store.removed.get().unwrap()[&gone.id].stored);
+        drop(store);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
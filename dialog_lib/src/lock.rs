@@ -0,0 +1,252 @@
+use crate::audit::AuditAction;
+use crate::privacy::open_note;
+use crate::tags::parse_hashtags;
+use crate::voice::VoiceData;
+use crate::{Dialog, DialogError, Note, Result};
+use nostr_sdk::nips::nip49::{EncryptedSecretKey, KeySecurity};
+use nostr_sdk::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Text shown instead of a locked note until it is unlocked
+pub const LOCKED_PLACEHOLDER: &str = "🔒 Locked note";
+
+/// Marks note text that is a locked envelope rather than the note itself
+const LOCKED_PREFIX: &str = "dialog-locked:v1:";
+
+/// scrypt cost (2^n) for the passphrase, as in NIP-49. Tests use a cheap
+/// one so they don't spend seconds per note.
+#[cfg(not(test))]
+const LOCK_LOG_N: u8 = 16;
+#[cfg(test)]
+const LOCK_LOG_N: u8 = 4;
+
+/// The inner layer of a locked note. The text is encrypted to a random
+/// key, and that key is stored NIP-49 encrypted under the passphrase. The
+/// envelope then goes through the usual note encryption.
+#[derive(Debug, Serialize, Deserialize)]
+struct LockedEnvelope {
+    /// `ncryptsec` of the content key
+    key: String,
+    content: String,
+}
+
+impl Dialog {
+    /// Create a note whose text can only be read with `passphrase`.
+    /// Lists show it as [`LOCKED_PLACEHOLDER`] until
+    /// [`Dialog::unlock_note`] is called.
+    pub async fn create_locked_note(&self, text: &str, passphrase: &str) -> Result<Note> {
+        let envelope = seal(text, passphrase)?;
+        let event = self.build_note_event(&envelope).await?;
+        self.publish_note(event, &envelope).await
+    }
+
+    /// Lock an existing note. Notes can't be changed in place, so this
+    /// writes a locked copy keeping its created_at, flags and audio, then
+    /// removes the original and its transcript locally and asks relays to
+    /// delete them (NIP-09). Relays that ignore the request keep the
+    /// original's ciphertext; write sensitive notes with
+    /// [`Dialog::create_locked_note`] instead when possible.
+    pub async fn lock_note(&self, note_id: &EventId, passphrase: &str) -> Result<Note> {
+        let event = self.note_event(note_id).await?;
+        let opened = open_note(&self.keys, &event)?;
+        if is_locked_text(&opened.text) {
+            return Err(DialogError::Lock("note is already locked".into()));
+        }
+
+        let envelope = seal(&opened.text, passphrase)?;
+        let mut note = self
+            .reissue_note(note_id, &envelope, opened.created_at)
+            .await?;
+        self.audit(
+            AuditAction::Lock,
+            Some(&note.id),
+            format!("replaces {note_id}"),
+        );
+        // The transcript would give the text away, so only the audio moves
+        if let Some(VoiceData {
+            audio: Some(audio), ..
+        }) = self.voice_data(note_id).await?
+        {
+            let data = VoiceData {
+                audio: Some(audio),
+                transcript: None,
+            };
+            self.store_voice_data(&note.id, &data, None).await?;
+            note.audio = data.audio;
+        }
+
+        // The original holds the text in the clear, so it mustn't linger
+        // in history views or on other devices
+        self.remove_voice_data(note_id).await?;
+        self.client
+            .database()
+            .delete(Filter::new().id(*note_id))
+            .await
+            .map_err(|e| DialogError::Database(e.to_string()))?;
+        if let Err(e) = self.client.delete_event(*note_id).await {
+            eprintln!("[lib] lock_note: deletion request for {note_id} not sent: {e}");
+        }
+        Ok(note)
+    }
+
+    /// Reveal a locked note until [`Dialog::relock_notes`] or the end of
+    /// the session. Returns the note with its text and tags.
+    pub async fn unlock_note(&self, note_id: &EventId, passphrase: &str) -> Result<Note> {
+        let event = self.note_event(note_id).await?;
+        let opened = open_note(&self.keys, &event)?;
+        if is_locked_text(&opened.text) {
+            let text = unseal(&opened.text, passphrase)?;
+            self.unlocked.write().unwrap().insert(*note_id, text);
+        }
+        self.get_note(note_id)
+            .await?
+            .ok_or(DialogError::NoteNotFound(*note_id))
+    }
+
+    /// Hide every unlocked note again
+    pub fn relock_notes(&self) {
+        self.unlocked.write().unwrap().clear();
+    }
+
+    /// Text and tags to show for a note, and whether it is locked
+    pub(crate) fn reveal(
+        &self,
+        note_id: &EventId,
+        text: String,
+        tags: Vec<String>,
+    ) -> (String, Vec<String>, bool) {
+        if is_locked_text(&text) {
+            if let Some(text) = self.unlocked.read().unwrap().get(note_id) {
+                return (text.clone(), parse_hashtags(text), true);
+            }
+        }
+        mask_locked(text, tags)
+    }
+}
+
+pub(crate) fn is_locked_text(text: &str) -> bool {
+    text.starts_with(LOCKED_PREFIX)
+}
+
+/// Text and tags to show for a note that hasn't been unlocked, and whether
+/// it is locked
+pub(crate) fn mask_locked(text: String, tags: Vec<String>) -> (String, Vec<String>, bool) {
+    if is_locked_text(&text) {
+        (LOCKED_PLACEHOLDER.to_string(), Vec::new(), true)
+    } else {
+        (text, tags, false)
+    }
+}
+
+fn seal(text: &str, passphrase: &str) -> Result<String> {
+    if passphrase.is_empty() {
+        return Err(DialogError::Lock("passphrase is empty".into()));
+    }
+    let content_keys = Keys::generate();
+    let content = nip44::encrypt(
+        content_keys.secret_key(),
+        &content_keys.public_key(),
+        text,
+        nip44::Version::default(),
+    )?;
+    let key = EncryptedSecretKey::new(
+        content_keys.secret_key(),
+        passphrase,
+        LOCK_LOG_N,
+        KeySecurity::Unknown,
+    )
+    .map_err(|e| DialogError::Lock(e.to_string()))?
+    .to_bech32()
+    .map_err(|e| DialogError::Lock(e.to_string()))?;
+    let envelope = serde_json::to_string(&LockedEnvelope { key, content })
+        .map_err(|e| DialogError::Lock(e.to_string()))?;
+    Ok(format!("{LOCKED_PREFIX}{envelope}"))
+}
+
+fn unseal(text: &str, passphrase: &str) -> Result<String> {
+    let envelope: LockedEnvelope = text
+        .strip_prefix(LOCKED_PREFIX)
+        .and_then(|json| serde_json::from_str(json).ok())
+        .ok_or_else(|| DialogError::Lock("unreadable locked note".into()))?;
+    let content_key = EncryptedSecretKey::from_bech32(&envelope.key)
+        .map_err(|e| DialogError::Lock(e.to_string()))?
+        .to_secret_key(passphrase)
+        .map_err(|_| DialogError::WrongPassphrase)?;
+    let content_keys = Keys::new(content_key);
+    Ok(nip44::decrypt(
+        content_keys.secret_key(),
+        &content_keys.public_key(),
+        &envelope.content,
+    )?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::memory_dialog;
+
+    #[test]
+    fn test_seal_round_trip() {
+        let sealed = seal("bank pin 1234 #private", "hunter2").unwrap();
+        assert!(is_locked_text(&sealed));
+        assert!(!sealed.contains("1234"));
+        assert!(parse_hashtags(&sealed).is_empty());
+        assert_eq!(
+            unseal(&sealed, "hunter2").unwrap(),
+            "bank pin 1234 #private"
+        );
+        assert!(matches!(
+            unseal(&sealed, "wrong"),
+            Err(DialogError::WrongPassphrase)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_lock_and_unlock_note() {
+        let dialog = memory_dialog();
+        let note = dialog.save_note("Diary #private").await.unwrap();
+        dialog.set_pinned(&note.id, true).await.unwrap();
+
+        let locked = dialog.lock_note(&note.id, "hunter2").await.unwrap();
+        assert!(locked.is_locked);
+        assert_eq!(locked.text, LOCKED_PLACEHOLDER);
+
+        let notes = dialog.list_notes(10).await.unwrap();
+        assert_eq!(notes.len(), 1, "the original is hidden behind the copy");
+        assert_eq!(notes[0].id, locked.id);
+        assert_eq!(notes[0].text, LOCKED_PLACEHOLDER);
+        assert!(notes[0].tags.is_empty());
+        assert!(notes[0].is_pinned);
+        assert_eq!(notes[0].created_at, note.created_at);
+
+        assert!(dialog.unlock_note(&locked.id, "wrong").await.is_err());
+        let unlocked = dialog.unlock_note(&locked.id, "hunter2").await.unwrap();
+        assert_eq!(unlocked.text, "Diary #private");
+        assert_eq!(unlocked.tags, vec!["private"]);
+        assert!(unlocked.is_locked);
+
+        dialog.relock_notes();
+        let notes = dialog.list_notes(10).await.unwrap();
+        assert_eq!(notes[0].text, LOCKED_PLACEHOLDER);
+    }
+
+    #[tokio::test]
+    async fn test_locked_text_leaves_no_readable_copy() {
+        let dialog = memory_dialog();
+        let note = dialog.save_note("Bank PIN is 4321").await.unwrap();
+        dialog
+            .set_transcript(&note.id, "bank pin is four three two one")
+            .await
+            .unwrap();
+
+        let locked = dialog.lock_note(&note.id, "hunter2").await.unwrap();
+        let history = dialog.list_notes_with(10, true).await.unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].id, locked.id);
+        assert!(history
+            .iter()
+            .all(|n| !n.text.contains("4321") && n.transcript.is_none()));
+        assert!(dialog.get_note(&note.id).await.unwrap().is_none());
+        assert!(dialog.voice_data(&note.id).await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,147 @@
+use crate::kinds::KindFeature;
+use crate::validate::{Quarantine, Rejection};
+use crate::{Dialog, DialogError, Result};
+use nostr_sdk::nips::nip59::UnwrappedGift;
+use nostr_sdk::prelude::*;
+
+/// NIP-59 gift wraps are backdated by up to two days, so live
+/// subscriptions have to look back at least this far
+pub(crate) const GIFT_WRAP_BACKDATE_SECS: u64 = 2 * 24 * 60 * 60;
+const MESSAGE_SYNC_LIMIT: usize = 500;
+
+/// A NIP-17 private message someone else sent us
+#[derive(Debug, Clone)]
+pub struct DirectMessage {
+    /// Id of the gift wrap the message arrived in
+    pub id: EventId,
+    pub sender: PublicKey,
+    pub text: String,
+    /// When the sender wrote it (the gift wrap's own timestamp is randomized)
+    pub created_at: Timestamp,
+}
+
+impl Dialog {
+    /// Private messages from other people, newest first.
+    /// Only reads the local database; see [`Dialog::sync_messages`].
+    pub async fn list_messages(&self, limit: usize) -> Result<Vec<DirectMessage>> {
+        // No limit on the query: our own legacy notes and sent copies share
+        // the kind and would use it up, so stop once enough are unwrapped
+        let events = self
+            .client
+            .database()
+            .query(vec![gift_wrap_filter(&self.keys)])
+            .await
+            .map_err(|e| DialogError::Database(e.to_string()))?;
+
+        let mut messages = Vec::new();
+        for event in events {
+            if messages.len() == limit {
+                break;
+            }
+            if let Some(message) =
+                unwrap_message(&self.keys, &self.quarantine, &event, self.now()).await
+            {
+                messages.push(message);
+            }
+        }
+        messages.sort_by_key(|m| std::cmp::Reverse((m.created_at, m.id)));
+        eprintln!("[lib] list_messages: returning {} messages", messages.len());
+        Ok(messages)
+    }
+
+    /// Fetch gift wraps addressed to us from relays and store them locally.
+    /// Returns the number that were new.
+    pub async fn sync_messages(&self) -> Result<usize> {
+        self.require_feature(KindFeature::Messages)?;
+        let events = self
+            .client
+            .fetch_events(
+                vec![gift_wrap_filter(&self.keys).limit(MESSAGE_SYNC_LIMIT)],
+                Some(self.fetch_timeout),
+            )
+            .await?;
+        eprintln!("[lib] sync_messages: fetched {} gift wraps", events.len());
+        self.save_events(events).await
+    }
+}
+
+/// Gift wraps addressed to us
+pub(crate) fn gift_wrap_filter(keys: &Keys) -> Filter {
+    Filter::new().kind(Kind::GiftWrap).pubkey(keys.public_key())
+}
+
+/// Unwrap a gift wrap into a message from someone else. Our legacy notes share
+/// the kind, and NIP-17 also wraps copies of messages we sent, so both are
+/// skipped; wraps that fail to open are quarantined.
+pub(crate) async fn unwrap_message(
+    keys: &Keys,
+    quarantine: &Quarantine,
+    event: &Event,
+    now: Timestamp,
+) -> Option<DirectMessage> {
+    if event.kind != Kind::GiftWrap || event.pubkey == keys.public_key() {
+        return None;
+    }
+    let gift = match UnwrappedGift::from_gift_wrap(keys, event).await {
+        Ok(gift) => gift,
+        Err(e) => {
+            quarantine.add(event, Rejection::Undecryptable(e.to_string()), now);
+            return None;
+        }
+    };
+    if gift.rumor.kind != Kind::PrivateDirectMessage || gift.sender == keys.public_key() {
+        return None;
+    }
+    Some(DirectMessage {
+        id: event.id,
+        sender: gift.sender,
+        text: gift.rumor.content,
+        created_at: gift.rumor.created_at,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::memory_dialog;
+
+    #[tokio::test]
+    async fn test_list_messages_skips_own_notes() {
+        let dialog = memory_dialog();
+        let alice = Keys::generate();
+        let dm = EventBuilder::private_msg(&alice, dialog.public_key(), "hi there", [])
+            .await
+            .unwrap();
+        let note = dialog.build_note_event("just a note").await.unwrap();
+        dialog.save_events([dm.clone(), note]).await.unwrap();
+
+        let messages = dialog.list_messages(10).await.unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].id, dm.id);
+        assert_eq!(messages[0].sender, alice.public_key());
+        assert_eq!(messages[0].text, "hi there");
+    }
+
+    #[tokio::test]
+    async fn test_list_messages_limit_skips_own_wraps() {
+        let dialog = memory_dialog();
+        let alice = Keys::generate();
+        let dm = EventBuilder::private_msg(&alice, dialog.public_key(), "hi there", [])
+            .await
+            .unwrap();
+        // Gift wraps are backdated, so these legacy notes are all newer
+        let mut events = vec![dm.clone()];
+        for n in 0..5 {
+            let legacy = EventBuilder::new(Kind::GiftWrap, format!("note {n}"))
+                .tag(Tag::public_key(dialog.public_key()))
+                .sign_with_keys(&dialog.keys)
+                .unwrap();
+            events.push(legacy);
+        }
+        dialog.save_events(events).await.unwrap();
+
+        let messages = dialog.list_messages(2).await.unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].id, dm.id);
+    }
+}
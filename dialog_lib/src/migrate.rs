@@ -0,0 +1,243 @@
+use crate::audit::AuditAction;
+use crate::{CancellationToken, Dialog, DialogError, Result};
+use nostr_sdk::prelude::*;
+use std::collections::HashSet;
+
+/// Tag on a migrated or locked copy naming the note it replaces
+const MIGRATED_FROM_TAG: &str = "migrated_from";
+
+/// Outcome of [`Dialog::migrate_note_kind`]
+#[derive(Debug, Default)]
+pub struct MigrationReport {
+    /// Notes republished under the configured kind
+    pub migrated: usize,
+    /// Notes that already had a migrated copy
+    pub skipped: usize,
+    pub failed: Vec<(EventId, String)>,
+    /// Stopped early by the cancellation token; run again to finish
+    pub cancelled: bool,
+}
+
+impl Dialog {
+    /// Republish every note stored under another kind under
+    /// [`Dialog::note_kind`].
+    ///
+    /// A copy keeps the note's ciphertext, tags and created_at, and names
+    /// the original in a `migrated_from` tag so readers list only the copy.
+    /// Flags and voice data move to the copy. Originals are left in place
+    /// for devices that don't read the new kind yet. Copies that can't
+    /// reach a relay are saved locally and sent by the next sync.
+    pub async fn migrate_note_kind(&self) -> Result<MigrationReport> {
+        self.migrate_note_kind_cancellable(&CancellationToken::new())
+            .await
+    }
+
+    /// Like [`Dialog::migrate_note_kind`], stopping between notes once
+    /// `cancel` fires. Notes already migrated stay migrated.
+    pub async fn migrate_note_kind_cancellable(
+        &self,
+        cancel: &CancellationToken,
+    ) -> Result<MigrationReport> {
+        let kind = self.note_kind();
+        let filter = self.notes_filter();
+        let events: Vec<Event> = self
+            .client
+            .database()
+            .query(vec![filter])
+            .await
+            .map_err(|e| DialogError::Database(e.to_string()))?
+            .into_iter()
+            .collect();
+        let superseded: HashSet<EventId> = events.iter().filter_map(migrated_from).collect();
+
+        let mut report = MigrationReport::default();
+        for event in events.iter().filter(|event| event.kind != kind) {
+            if cancel.is_cancelled() {
+                report.cancelled = true;
+                break;
+            }
+            if superseded.contains(&event.id) {
+                report.skipped += 1;
+                continue;
+            }
+            match self.migrate_note(event, kind).await {
+                Ok(()) => report.migrated += 1,
+                Err(e) => {
+                    eprintln!("[lib] migrate_note_kind: {} failed: {e}", event.id);
+                    report.failed.push((event.id, e.to_string()));
+                }
+            }
+        }
+        eprintln!(
+            "[lib] migrate_note_kind: {} migrated to kind {kind}, {} skipped, {} failed",
+            report.migrated,
+            report.skipped,
+            report.failed.len()
+        );
+        Ok(report)
+    }
+
+    async fn migrate_note(&self, event: &Event, kind: Kind) -> Result<()> {
+        let copy = EventBuilder::new(kind, event.content.clone())
+            .tags(
+                event
+                    .tags
+                    .iter()
+                    .cloned()
+                    .chain([self.supersede_tag(&event.id)]),
+            )
+            .custom_created_at(event.created_at)
+            .sign(&self.keys)
+            .await?;
+
+        match self.publish(copy.clone()).await {
+            Ok(_) => {}
+            Err(e @ DialogError::Database(_)) => return Err(e),
+            Err(e) => eprintln!("[lib] migrate_note_kind: {} not published: {e}", copy.id),
+        }
+        self.audit(
+            AuditAction::Migrate,
+            Some(&copy.id),
+            format!("from {} (kind {})", event.id, event.kind),
+        );
+        self.copy_note_state(&event.id, &copy.id).await?;
+        if let Some(data) = self.voice_data(&event.id).await? {
+            self.store_voice_data(&copy.id, &data, None).await?;
+        }
+        Ok(())
+    }
+
+    /// Marks a copy as replacing `note_id` from now on; readers then skip
+    /// the original
+    pub(crate) fn supersede_tag(&self, note_id: &EventId) -> Tag {
+        Tag::custom(
+            TagKind::custom(MIGRATED_FROM_TAG),
+            [note_id.to_hex(), self.now().as_u64().to_string()],
+        )
+    }
+}
+
+/// The note a migrated or locked copy replaces. A migrated copy of a copy
+/// carries its original's tag too; the last one is its own.
+pub(crate) fn migrated_from(event: &Event) -> Option<EventId> {
+    event
+        .tags
+        .iter()
+        .rev()
+        .find_map(|tag| match tag.as_slice() {
+            [name, id, ..] if name == MIGRATED_FROM_TAG => EventId::from_hex(id).ok(),
+            _ => None,
+        })
+}
+
+/// When a copy replaced its note. None for copies that don't record it,
+/// which were written before the time was added.
+pub(crate) fn superseded_at(event: &Event) -> Option<Timestamp> {
+    event
+        .tags
+        .iter()
+        .rev()
+        .find_map(|tag| match tag.as_slice() {
+            [name, _, at, ..] if name == MIGRATED_FROM_TAG => {
+                at.parse::<u64>().ok().map(Timestamp::from)
+            }
+            _ => None,
+        })
+}
+
+/// Whether `event`, or a copy before it, changed the note's text. Kind
+/// migrations keep the ciphertext, while edits, retags and locks always
+/// encrypt anew. `stored` looks up earlier copies; a copy whose original
+/// isn't known counts as an edit.
+pub(crate) fn is_edited<'a>(event: &Event, stored: impl Fn(&EventId) -> Option<&'a Event>) -> bool {
+    let mut current = event;
+    while let Some(original_id) = migrated_from(current) {
+        let Some(original) = stored(&original_id) else {
+            return true;
+        };
+        if original.content != current.content {
+            return true;
+        }
+        current = original;
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::note::{DEFAULT_NOTE_KIND, LEGACY_NOTE_KIND};
+    use crate::test_support::memory_dialog;
+
+    #[tokio::test]
+    async fn test_migrate_note_kind() {
+        let dialog = memory_dialog();
+        dialog.set_note_kind(LEGACY_NOTE_KIND).unwrap();
+        let old = dialog.save_note("Old note #work").await.unwrap();
+        dialog.set_pinned(&old.id, true).await.unwrap();
+
+        // Legacy notes stay readable under the new kind
+        dialog.set_note_kind(DEFAULT_NOTE_KIND).unwrap();
+        dialog.save_note("New note").await.unwrap();
+        assert_eq!(dialog.list_notes(10).await.unwrap().len(), 2);
+
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+        let cancelled = dialog.migrate_note_kind_cancellable(&cancel).await.unwrap();
+        assert!(cancelled.cancelled);
+        assert_eq!(cancelled.migrated, 0);
+        assert!(matches!(
+            dialog.sync_cancellable(&cancel).await,
+            Err(DialogError::Cancelled)
+        ));
+
+        let report = dialog.migrate_note_kind().await.unwrap();
+        assert_eq!((report.migrated, report.skipped), (1, 0));
+        assert!(!report.cancelled);
+        assert!(report.failed.is_empty());
+
+        let notes = dialog.list_notes(10).await.unwrap();
+        assert_eq!(notes.len(), 2, "the original is hidden behind its copy");
+        let copy = notes.iter().find(|n| n.text == "Old note #work").unwrap();
+        assert_ne!(copy.id, old.id);
+        assert_eq!(copy.created_at, old.created_at);
+        assert_eq!(copy.tags, vec!["work"]);
+        assert!(copy.is_pinned);
+        assert_eq!(copy.edit_of, Some(old.id));
+        assert!(!copy.edited, "a kind migration isn't an edit");
+
+        let again = dialog.migrate_note_kind().await.unwrap();
+        assert_eq!((again.migrated, again.skipped), (0, 1));
+        assert!(dialog.set_note_kind(30078).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_edit_chains_collapse() {
+        let dialog = memory_dialog();
+        dialog.set_note_kind(LEGACY_NOTE_KIND).unwrap();
+        let first = dialog.save_note("Draft #work").await.unwrap();
+        assert!(!first.edited);
+        let report = dialog
+            .with_batch_offline(|batch| batch.edit(first.id, "Final #work"))
+            .await
+            .unwrap();
+        let edit = &report.edited[0];
+        assert!(edit.edited);
+        assert_eq!(edit.edit_of, Some(first.id));
+
+        // Migrating the edited copy keeps it edited and hides both before it
+        dialog.set_note_kind(DEFAULT_NOTE_KIND).unwrap();
+        dialog.migrate_note_kind().await.unwrap();
+        let notes = dialog.list_notes(10).await.unwrap();
+        assert_eq!(notes.len(), 1);
+        assert_eq!(notes[0].text, "Final #work");
+        assert_eq!(notes[0].edit_of, Some(edit.id));
+        assert!(notes[0].edited);
+
+        let all = dialog.list_notes_with(10, true).await.unwrap();
+        assert_eq!(all.len(), 3);
+        let original = all.iter().find(|note| note.id == first.id).unwrap();
+        assert!(!original.edited);
+        assert_eq!(original.edit_of, None);
+    }
+}
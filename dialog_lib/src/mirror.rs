@@ -0,0 +1,227 @@
+use crate::file_store::write_atomic;
+use crate::{Dialog, Note, Result};
+use nostr_sdk::prelude::*;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+/// Notes written per pass; older notes beyond this are left alone
+const MIRROR_NOTE_LIMIT: usize = 10_000;
+/// Full rescan interval, which also picks up pin/archive changes and
+/// notes that disappeared
+const RESCAN_INTERVAL: Duration = Duration::from_secs(60);
+/// Hex characters of the note id used in file names
+const ID_PREFIX_LEN: usize = 12;
+
+/// Outcome of one mirror pass
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MirrorReport {
+    pub written: usize,
+    pub removed: usize,
+    pub unchanged: usize,
+}
+
+/// Running plain-text mirror; stops when dropped
+pub struct MirrorHandle {
+    dir: PathBuf,
+    task: JoinHandle<()>,
+}
+
+impl MirrorHandle {
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Wait for the mirror to stop (it only stops if the task is aborted)
+    pub async fn join(mut self) {
+        let _ = (&mut self.task).await;
+    }
+}
+
+impl Drop for MirrorHandle {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+impl Dialog {
+    /// Keep `dir` in step with the notes as decrypted markdown files, one
+    /// per note. Files are rewritten only when a note changes and removed
+    /// when the note is gone, so the directory diffs cleanly under git.
+    ///
+    /// The files are plaintext: point this at a directory you trust.
+    pub async fn enable_mirror(self: Arc<Self>, dir: impl Into<PathBuf>) -> Result<MirrorHandle> {
+        let dir = dir.into();
+        self.write_mirror(&dir).await?;
+
+        // Without a relay there is nothing to watch; rescans still run
+        let mut receiver = match self.watch_notes().await {
+            Ok(receiver) => Some(receiver),
+            Err(e) => {
+                eprintln!("[lib] mirror: not watching for new notes: {e}");
+                None
+            }
+        };
+
+        let task_dir = dir.clone();
+        let task = tokio::spawn(async move {
+            let mut rescan = self.clock.sleep(RESCAN_INTERVAL);
+            loop {
+                let watch_alive = tokio::select! {
+                    note = async { receiver.as_mut()?.recv().await }, if receiver.is_some() => {
+                        note.is_some()
+                    }
+                    _ = &mut rescan => {
+                        rescan = self.clock.sleep(RESCAN_INTERVAL);
+                        true
+                    }
+                };
+                if !watch_alive {
+                    eprintln!("[lib] mirror: watch ended; relying on rescans");
+                    receiver = None;
+                    continue;
+                }
+                if let Err(e) = self.write_mirror(&task_dir).await {
+                    eprintln!("[lib] mirror: pass failed: {e}");
+                }
+            }
+        });
+        Ok(MirrorHandle { dir, task })
+    }
+
+    /// Write every note to `dir` once and remove files of notes that no
+    /// longer exist. Files not written by the mirror are never touched.
+    pub async fn write_mirror(&self, dir: &Path) -> Result<MirrorReport> {
+        fs::create_dir_all(dir)?;
+        let notes = self.list_notes(MIRROR_NOTE_LIMIT).await?;
+
+        let mut report = MirrorReport::default();
+        let mut keep = HashSet::new();
+        for note in &notes {
+            let name = file_name(note);
+            let path = dir.join(&name);
+            let contents = render(note);
+            if fs::read_to_string(&path).is_ok_and(|existing| existing == contents) {
+                report.unchanged += 1;
+            } else {
+                write_atomic(&path, contents)?;
+                report.written += 1;
+            }
+            keep.insert(name);
+        }
+
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if is_mirror_file(&name) && !keep.contains(&name) {
+                fs::remove_file(entry.path())?;
+                report.removed += 1;
+            }
+        }
+        eprintln!("[lib] mirror: {report:?} in {}", dir.display());
+        Ok(report)
+    }
+}
+
+/// `2024-03-01-0123456789ab.md`: sorts by date and never changes for a note
+fn file_name(note: &Note) -> String {
+    let date = chrono::DateTime::from_timestamp(note.created_at.as_u64() as i64, 0)
+        .unwrap_or_default()
+        .format("%Y-%m-%d");
+    format!("{date}-{}.md", &note.id.to_hex()[..ID_PREFIX_LEN])
+}
+
+fn is_mirror_file(name: &str) -> bool {
+    let Some(stem) = name.strip_suffix(".md") else {
+        return false;
+    };
+    let Some((date, id)) = stem.rsplit_once('-') else {
+        return false;
+    };
+    id.len() == ID_PREFIX_LEN
+        && id.chars().all(|c| c.is_ascii_hexdigit())
+        && chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d").is_ok()
+}
+
+/// Markdown with a small front matter block for the metadata
+fn render(note: &Note) -> String {
+    let created_at = chrono::DateTime::from_timestamp(note.created_at.as_u64() as i64, 0)
+        .unwrap_or_default()
+        .format("%Y-%m-%dT%H:%M:%SZ");
+    let mut out = String::from("---\n");
+    out.push_str(&format!("id: {}\n", note.id.to_hex()));
+    out.push_str(&format!("created_at: {created_at}\n"));
+    if !note.tags.is_empty() {
+        out.push_str(&format!("tags: [{}]\n", note.tags.join(", ")));
+    }
+    if note.is_pinned {
+        out.push_str("pinned: true\n");
+    }
+    if note.is_archived {
+        out.push_str("archived: true\n");
+    }
+    out.push_str("---\n\n");
+    out.push_str(note.text.trim_end());
+    out.push('\n');
+    if let Some(transcript) = &note.transcript {
+        out.push_str(&format!("\n## Transcript\n\n{}\n", transcript.trim_end()));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::memory_dialog;
+
+    fn mirror_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("dialog-mirror-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn test_is_mirror_file() {
+        assert!(is_mirror_file("2024-03-01-0123456789ab.md"));
+        assert!(!is_mirror_file("README.md"));
+        assert!(!is_mirror_file("2024-03-01-notes.md"));
+        assert!(!is_mirror_file(".2024-03-01-0123456789ab.md.tmp"));
+    }
+
+    #[tokio::test]
+    async fn test_write_mirror_updates_and_removes() {
+        let dialog = memory_dialog();
+        let dir = mirror_dir("pass");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("README.md"), "mine").unwrap();
+        fs::write(dir.join("2020-01-01-aaaaaaaaaaaa.md"), "stale").unwrap();
+
+        let note = dialog.save_note("Mirror me #backup").await.unwrap();
+        let report = dialog.write_mirror(&dir).await.unwrap();
+        assert_eq!(
+            report,
+            MirrorReport {
+                written: 1,
+                removed: 1,
+                unchanged: 0
+            }
+        );
+
+        let contents = fs::read_to_string(dir.join(file_name(&note))).unwrap();
+        assert!(contents.contains(&format!("id: {}\n", note.id.to_hex())));
+        assert!(contents.contains("tags: [backup]\n"));
+        assert!(contents.ends_with("---\n\nMirror me #backup\n"));
+        assert!(dir.join("README.md").exists());
+
+        // Nothing changed, nothing rewritten
+        let again = dialog.write_mirror(&dir).await.unwrap();
+        assert_eq!(again.unchanged, 1);
+        assert_eq!(again.written, 0);
+
+        dialog.set_pinned(&note.id, true).await.unwrap();
+        assert_eq!(dialog.write_mirror(&dir).await.unwrap().written, 1);
+    }
+}
@@ -1,19 +1,316 @@
-use crate::{Dialog, Result};
+use crate::audit::AuditAction;
+use crate::bulk::normalize_tags;
+use crate::lock::{mask_locked, LOCKED_PLACEHOLDER};
+use crate::migrate::migrated_from;
+use crate::privacy::{fuzz_timestamp, is_private, open_note, private_content};
+use crate::tags::{add_hashtags, parse_hashtags};
+use crate::text::{reading_minutes, word_count};
+use crate::{Attachment, CaptureSource, Dialog, DialogError, Result, TaskStatus};
 use nostr_sdk::prelude::*;
+use std::sync::atomic::Ordering;
 
+/// Largest note text (in bytes) that can be stored. NIP-44 caps plaintext at
+/// 65536 - 128 bytes; the resulting event stays well under common relay limits.
+pub const MAX_NOTE_BYTES: usize = 65_408;
+
+/// Kind new notes are written with. It sits in the regular (stored) range
+/// and isn't assigned by any NIP, so our notes don't mix with gift wraps.
+pub const DEFAULT_NOTE_KIND: u16 = 4059;
+/// Kind notes were written with before the kind became configurable. It is
+/// NIP-59's gift wrap kind; notes under it are still read until migrated
+/// with [`Dialog::migrate_note_kind`].
+pub const LEGACY_NOTE_KIND: u16 = 1059;
+
+/// Fields are added in minor releases, so code outside this crate reads
+/// notes but can't build or exhaustively destructure them.
 #[derive(Debug, Clone)]
+#[non_exhaustive]
 pub struct Note {
     pub id: EventId,
     pub text: String,
     pub tags: Vec<String>,
     pub created_at: Timestamp,
     pub is_read: bool,
+    pub is_pinned: bool,
+    pub is_archived: bool,
+    /// Id of the public kind-1 copy, if this note has been published
+    pub public_id: Option<EventId>,
+    /// Recording for voice notes
+    pub audio: Option<Attachment>,
+    /// Set once the app has transcribed `audio`; searched like the text
+    pub transcript: Option<String>,
+    /// Needs a passphrase to read; `text` is a placeholder until unlocked
+    pub is_locked: bool,
+    /// False while the note waits in the offline queue for a relay
     pub is_synced: bool,
+    /// False once evicted by [`Dialog::set_local_cache_limit`]; `text` is
+    /// empty until the note is fetched again
+    pub is_hydrated: bool,
+    /// The text was changed since the note was written, e.g. to show an
+    /// "edited" marker
+    pub edited: bool,
+    /// The note this copy replaced, for a history view. Set for kind
+    /// migrations too, which aren't edits.
+    pub edit_of: Option<EventId>,
+    /// Set once the note was made a task with [`Dialog::set_task_status`]
+    pub task_status: Option<TaskStatus>,
+    /// Words in the text, hashtags left out; kept for evicted notes, 0 for
+    /// locked ones
+    pub word_count: usize,
+}
+
+impl Note {
+    /// Estimated minutes to read the note, see [`reading_minutes`]
+    pub fn reading_minutes(&self) -> usize {
+        reading_minutes(self.word_count)
+    }
+}
+
+/// [`word_count`] of a note's shown text, leaving out the placeholder of
+/// a locked note
+pub(crate) fn shown_word_count(text: &str) -> usize {
+    if text == LOCKED_PLACEHOLDER {
+        0
+    } else {
+        word_count(text)
+    }
 }
 
 impl Dialog {
-    pub async fn create_note(&self, text: &str) -> Result<EventId> {
+    /// Write new notes with `kind`. It must be a regular (stored) kind from
+    /// 1000 to 9999, or [`LEGACY_NOTE_KIND`]. Notes under the default and
+    /// legacy kinds stay readable whatever is configured.
+    pub fn set_note_kind(&self, kind: u16) -> Result<()> {
+        if kind != LEGACY_NOTE_KIND && !(1000..10_000).contains(&kind) {
+            return Err(DialogError::NoteKind(kind));
+        }
+        self.note_kind.store(kind, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Kind new notes are written with
+    pub fn note_kind(&self) -> Kind {
+        Kind::from(self.note_kind.load(Ordering::SeqCst))
+    }
+
+    /// Every kind notes are read from: the configured one plus the default
+    /// and legacy kinds, so nothing disappears while notes are migrated
+    pub fn note_kinds(&self) -> Vec<Kind> {
+        let mut kinds = vec![self.note_kind()];
+        for kind in [DEFAULT_NOTE_KIND, LEGACY_NOTE_KIND].map(Kind::from) {
+            if !kinds.contains(&kind) {
+                kinds.push(kind);
+            }
+        }
+        kinds
+    }
+
+    /// Encrypt, sign and publish a note, returning it as stored.
+    /// `created_at` is the signed event's timestamp, so callers can show the
+    /// note optimistically without it moving once the relay echoes it back.
+    ///
+    /// Defaults of the note's tags apply (see [`Dialog::set_tag_defaults`]).
+    pub async fn create_note(&self, text: &str) -> Result<Note> {
+        let text = &self.prepare_text(text).await;
         eprintln!("[lib] create_note: building event (len={})", text.len());
+        let created_at = self.created_at.next(self.now());
+        let (event, is_pinned) = self.build_new_note_event(text, created_at).await?;
+        let note = self.publish_note(event, text).await?;
+        self.maybe_auto_enrich(note.id, text);
+        Ok(Note { is_pinned, ..note })
+    }
+
+    /// [`Dialog::create_note`] with `tags` besides those written in the
+    /// text, e.g. from a Shortcut. Tags in the text keep their place; the
+    /// others are appended as hashtags in the order given, so the same
+    /// input always gives the same note. `created_at` backdates an
+    /// imported item.
+    pub async fn create_note_with_tags(
+        &self,
+        text: &str,
+        tags: &[String],
+        created_at: Option<Timestamp>,
+    ) -> Result<Note> {
+        let text = add_hashtags(text, &normalize_tags(tags));
+        match created_at {
+            Some(created_at) => self.create_note_at(&text, created_at).await,
+            None => self.create_note(&text).await,
+        }
+    }
+
+    /// Create a note with an explicit timestamp, e.g. an imported email's date
+    pub(crate) async fn create_note_at(&self, text: &str, created_at: Timestamp) -> Result<Note> {
+        let text = &self.prepare_text(text).await;
+        let (event, is_pinned) = self.build_new_note_event(text, created_at).await?;
+        let note = self.publish_note(event, text).await?;
+        Ok(Note { is_pinned, ..note })
+    }
+
+    /// Encrypt, sign and store a note locally without contacting relays.
+    /// Send it later with [`Dialog::publish_stored_note`]; a negentropy sync
+    /// uploads it too.
+    pub async fn save_note(&self, text: &str) -> Result<Note> {
+        let text = &self.prepare_text(text).await;
+        let created_at = self.created_at.next(self.now());
+        let (event, is_pinned) = self.build_new_note_event(text, created_at).await?;
+        self.client
+            .database()
+            .save_event(&event)
+            .await
+            .map_err(|e| DialogError::Database(e.to_string()))?;
+        eprintln!("[lib] save_note: stored {} locally", event.id);
+        self.audit(AuditAction::Create, Some(&event.id), "saved locally");
+        Ok(Note {
+            is_pinned,
+            ..local_note(&self.keys, &event, text)
+        })
+    }
+
+    /// Send a note that is already in the local database to the relays
+    pub async fn publish_stored_note(&self, note_id: &EventId) -> Result<()> {
+        let event = self.note_event(note_id).await?;
+        self.publish(event).await?;
+        Ok(())
+    }
+
+    /// The stored event of one of our notes
+    pub(crate) async fn note_event(&self, note_id: &EventId) -> Result<Event> {
+        let filter = self.notes_filter().id(*note_id);
+        self.client
+            .database()
+            .query(vec![filter])
+            .await
+            .map_err(|e| DialogError::Database(e.to_string()))?
+            .into_iter()
+            .next()
+            .ok_or(DialogError::NoteNotFound(*note_id))
+    }
+
+    /// Write `text` as a copy of `original` that replaces it in lists,
+    /// keeping its created_at and flags. Notes can't be edited in place, so
+    /// this is how their text changes. A copy that can't reach a relay is
+    /// saved locally and sent by the next sync. Voice data is left to the
+    /// caller.
+    pub(crate) async fn reissue_note(
+        &self,
+        original: &EventId,
+        text: &str,
+        created_at: Timestamp,
+    ) -> Result<Note> {
+        let copy = self
+            .build_note_event_tagged(text, created_at, [self.supersede_tag(original)])
+            .await?;
+        let note = match self.publish_note(copy.clone(), text).await {
+            Ok(note) => note,
+            Err(e @ DialogError::Database(_)) => return Err(e),
+            Err(e) => {
+                eprintln!("[lib] reissue_note: {} not published: {e}", copy.id);
+                local_note(&self.keys, &copy, text)
+            }
+        };
+        self.copy_note_state(original, &copy.id).await?;
+        let state = self.note_state(&copy.id).await?;
+        Ok(Note {
+            is_read: state.is_read(),
+            is_pinned: state.is_pinned(),
+            is_archived: state.is_archived(),
+            public_id: state.public_id(),
+            task_status: state.task(),
+            ..note
+        })
+    }
+
+    pub(crate) async fn publish_note(&self, event: Event, text: &str) -> Result<Note> {
+        Ok(self.publish_note_output(event, text).await?.0)
+    }
+
+    /// [`Dialog::publish_note`] along with how each relay answered
+    pub(crate) async fn publish_note_output(
+        &self,
+        event: Event,
+        text: &str,
+    ) -> Result<(Note, Output<EventId>)> {
+        // Send the event (this also saves to local db)
+        let output = match self.publish(event.clone()).await {
+            Ok(output) => output,
+            Err(e) => {
+                // Anything but a database error leaves the note saved locally
+                if !matches!(e, DialogError::Database(_)) {
+                    self.audit(AuditAction::Create, Some(&event.id), "not published");
+                }
+                return Err(e);
+            }
+        };
+        eprintln!("[lib] create_note: sent; id={}", output.id());
+        self.audit(AuditAction::Create, Some(&event.id), "published");
+
+        let note = Note {
+            is_synced: !output.success.is_empty(),
+            is_hydrated: true,
+            ..local_note(&self.keys, &event, text)
+        };
+        Ok((note, output))
+    }
+
+    /// Build and sign the encrypted event for a note without publishing it
+    pub(crate) async fn build_note_event(&self, text: &str) -> Result<Event> {
+        let created_at = self.created_at.next(self.now());
+        self.build_note_event_at(text, created_at).await
+    }
+
+    pub(crate) async fn build_note_event_at(
+        &self,
+        text: &str,
+        created_at: Timestamp,
+    ) -> Result<Event> {
+        self.build_note_event_tagged(text, created_at, []).await
+    }
+
+    /// Build a note event carrying `extra` tags besides the usual ones
+    pub(crate) async fn build_note_event_tagged(
+        &self,
+        text: &str,
+        created_at: Timestamp,
+        extra: impl IntoIterator<Item = Tag>,
+    ) -> Result<Event> {
+        self.build_note_event_with(text, created_at, extra, None)
+            .await
+    }
+
+    /// [`Dialog::build_note_event_tagged`] for a note that came in some
+    /// other way than this Dialog's capture source, e.g. an import
+    pub(crate) async fn build_note_event_with(
+        &self,
+        text: &str,
+        created_at: Timestamp,
+        extra: impl IntoIterator<Item = Tag>,
+        source: Option<CaptureSource>,
+    ) -> Result<Event> {
+        // Check up front so oversized notes get a clear error instead of a
+        // generic encryption failure
+        if text.len() > MAX_NOTE_BYTES {
+            return Err(DialogError::NoteTooLarge {
+                max: MAX_NOTE_BYTES,
+                actual: text.len(),
+            });
+        }
+
+        if self.max_privacy() {
+            // Tags and the real time only exist inside the ciphertext
+            let encrypted = nip44::encrypt(
+                self.keys.secret_key(),
+                &self.keys.public_key(),
+                private_content(text, created_at)?,
+                nip44::Version::default(),
+            )?;
+            return Ok(EventBuilder::new(self.note_kind(), encrypted)
+                .tag(self.device_tag_from(source)?)
+                .tags(extra)
+                .custom_created_at(fuzz_timestamp(created_at))
+                .sign(&self.keys)
+                .await?);
+        }
+
         // Parse hashtags from text
         let tags = parse_hashtags(text);
 
@@ -26,21 +323,21 @@ impl Dialog {
         )?;
 
         // Build event with NIP-44 encrypted content
-        // Using Kind 1059 for encrypted direct messages
-        let mut builder = EventBuilder::new(Kind::from(1059), encrypted);
+        let mut builder =
+            EventBuilder::new(self.note_kind(), encrypted).custom_created_at(created_at);
 
-        // Add t tags for topics (lowercase)
+        // Add t tags for topics (already lowercased by the parser)
         for tag in &tags {
-            builder = builder.tag(Tag::hashtag(tag.to_lowercase()));
+            builder = builder.tag(Tag::hashtag(tag));
         }
 
         // Add p tag pointing to self (for self-DM)
-        builder = builder.tag(Tag::public_key(self.keys.public_key()));
+        builder = builder
+            .tag(Tag::public_key(self.keys.public_key()))
+            .tag(self.device_tag_from(source)?)
+            .tags(extra);
 
-        // Send the event (this also saves to local db)
-        let output = self.client.send_event_builder(builder).await?;
-        eprintln!("[lib] create_note: sent; id={}", output.id());
-        Ok(*output.id())
+        Ok(builder.sign(&self.keys).await?)
     }
 
     pub(crate) fn decrypt_event(&self, event: &Event) -> Result<String> {
@@ -53,35 +350,130 @@ impl Dialog {
     }
 }
 
-fn parse_hashtags(text: &str) -> Vec<String> {
-    text.split_whitespace()
-        .filter(|word| word.starts_with('#') && word.len() > 1)
-        .map(|tag| tag[1..].to_lowercase())
-        .collect()
+/// A freshly built note event as a `Note`, before any relay has accepted it
+pub(crate) fn local_note(keys: &Keys, event: &Event, text: &str) -> Note {
+    // Private notes keep their real time inside the ciphertext
+    let created_at = if is_private(event) {
+        open_note(keys, event).map_or(event.created_at, |opened| opened.created_at)
+    } else {
+        event.created_at
+    };
+    let (text, tags, is_locked) = mask_locked(text.to_string(), parse_hashtags(text));
+    let word_count = shown_word_count(&text);
+    // Only reissued copies are built here, never migrated ones
+    let edit_of = migrated_from(event);
+    Note {
+        id: event.id,
+        text,
+        tags,
+        created_at,
+        is_read: false,
+        is_pinned: false,
+        is_archived: false,
+        public_id: None,
+        audio: None,
+        transcript: None,
+        is_locked,
+        is_synced: false,
+        is_hydrated: true,
+        edited: edit_of.is_some(),
+        edit_of,
+        task_status: None,
+        word_count,
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_support::{memory_dialog, note_text};
+    use proptest::prelude::*;
 
-    #[test]
-    fn test_parse_hashtags() {
-        let text = "This is a #Test note with #Multiple #TAGS";
-        let tags = parse_hashtags(text);
-        assert_eq!(tags, vec!["test", "multiple", "tags"]);
+    /// Build a note event, store it and read it back through list_notes
+    async fn round_trip(dialog: &Dialog, text: &str) -> Note {
+        let event = dialog.build_note_event(text).await.unwrap();
+        dialog.save_events([event.clone()]).await.unwrap();
+        let notes = dialog.list_notes(10).await.unwrap();
+        notes
+            .into_iter()
+            .find(|n| n.id == event.id)
+            .expect("stored note should be listed")
     }
 
-    #[test]
-    fn test_parse_hashtags_empty() {
-        let text = "This has no hashtags";
-        let tags = parse_hashtags(text);
-        assert!(tags.is_empty());
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(64))]
+
+        #[test]
+        fn prop_note_round_trips(text in note_text()) {
+            let rt = tokio::runtime::Runtime::new().unwrap();
+            rt.block_on(async {
+                let dialog = memory_dialog();
+                let note = round_trip(&dialog, &text).await;
+                assert_eq!(note.text, text);
+                assert_eq!(note.tags, parse_hashtags(&text));
+
+                for tag in &note.tags {
+                    let tagged = dialog.list_by_tag(tag, 10).await.unwrap();
+                    assert!(tagged.iter().any(|n| n.id == note.id), "missing from #{tag}");
+                }
+            });
+        }
     }
 
-    #[test]
-    fn test_parse_hashtags_just_hash() {
-        let text = "This has just # and nothing else";
-        let tags = parse_hashtags(text);
-        assert!(tags.is_empty());
+    #[tokio::test]
+    async fn test_largest_note_round_trips() {
+        let dialog = memory_dialog();
+        let text = "a".repeat(MAX_NOTE_BYTES);
+        let note = round_trip(&dialog, &text).await;
+        assert_eq!(note.text, text);
+
+        // One byte more is rejected with a typed error before encrypting
+        let err = dialog
+            .build_note_event(&"a".repeat(MAX_NOTE_BYTES + 1))
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            DialogError::NoteTooLarge {
+                max: MAX_NOTE_BYTES,
+                actual
+            } if actual == MAX_NOTE_BYTES + 1
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_save_note_stays_local() {
+        let dialog = memory_dialog();
+        let note = dialog.save_note("offline thought #later").await.unwrap();
+        assert!(!note.is_synced);
+        assert_eq!(note.tags, vec!["later"]);
+
+        let stored = dialog.get_note(&note.id).await.unwrap().unwrap();
+        assert_eq!(stored.text, "offline thought #later");
+
+        // No relays to send it to
+        assert!(dialog.publish_stored_note(&note.id).await.is_err());
+        let missing = EventId::all_zeros();
+        assert!(matches!(
+            dialog.publish_stored_note(&missing).await,
+            Err(DialogError::NoteNotFound(id)) if id == missing
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_create_note_with_tags_merges_and_backdates() {
+        let dialog = memory_dialog();
+        let tags = ["#Inbox".to_string(), "work".into(), "inbox".into()];
+        let created_at = Timestamp::from(1_600_000_000);
+        // No relays, so sending fails after the note is stored
+        let result = dialog
+            .create_note_with_tags("Call Sam #work", &tags, Some(created_at))
+            .await;
+        assert!(result.is_err());
+
+        let notes = dialog.list_notes(10).await.unwrap();
+        assert_eq!(notes[0].text, "Call Sam #work\n#inbox");
+        assert_eq!(notes[0].tags, vec!["work", "inbox"]);
+        assert_eq!(notes[0].created_at, created_at);
     }
 }
@@ -0,0 +1,118 @@
+use crate::{Dialog, Note, Result, SearchQuery};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Whether a new note under a tag is worth a notification. Stored with the
+/// tag's metadata, so it syncs with the rest of the app state.
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotifyRule {
+    /// Watch only
+    #[default]
+    Off,
+    All,
+    /// Only notes mentioning the keyword, ignoring case
+    Keyword(String),
+}
+
+impl NotifyRule {
+    pub fn is_off(&self) -> bool {
+        *self == NotifyRule::Off
+    }
+
+    pub fn matches(&self, note: &Note) -> bool {
+        match self {
+            NotifyRule::Off => false,
+            NotifyRule::All => true,
+            NotifyRule::Keyword(keyword) => {
+                let keyword = SearchQuery::new(keyword);
+                !keyword.is_empty() && keyword.matches(&note.text)
+            }
+        }
+    }
+}
+
+impl Dialog {
+    /// Set when notes tagged `tag` should notify. Other tag metadata is kept.
+    pub async fn set_notify_rule(&self, tag: &str, rule: NotifyRule) -> Result<()> {
+        let Some(tag) = crate::normalize_tag(tag) else {
+            return Ok(());
+        };
+        let mut metadata = self
+            .app_state()
+            .await?
+            .tags
+            .get(&tag)
+            .map(|lww| lww.value.clone())
+            .unwrap_or_default();
+        metadata.notify = rule;
+        self.set_tag_metadata(&tag, metadata).await
+    }
+
+    /// Tags with a rule other than [`NotifyRule::Off`]
+    pub async fn notify_rules(&self) -> Result<BTreeMap<String, NotifyRule>> {
+        Ok(self
+            .app_state()
+            .await?
+            .tags
+            .into_iter()
+            .filter(|(_, lww)| !lww.value.notify.is_off())
+            .map(|(tag, lww)| (tag, lww.value.notify))
+            .collect())
+    }
+
+    /// The first of the note's tags whose rule wants a notification for it
+    pub async fn notification_rule(&self, note: &Note) -> Result<Option<(String, NotifyRule)>> {
+        let rules = self.notify_rules().await?;
+        Ok(note.tags.iter().find_map(|tag| {
+            let rule = rules.get(tag)?;
+            rule.matches(note).then(|| (tag.clone(), rule.clone()))
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::memory_dialog;
+    use crate::TagMetadata;
+
+    #[tokio::test]
+    async fn test_notification_rules() {
+        let dialog = memory_dialog();
+        let metadata = TagMetadata {
+            color: Some("#ff0000".into()),
+            ..Default::default()
+        };
+        dialog.set_tag_metadata("urgent", metadata).await.unwrap();
+        dialog
+            .set_notify_rule("#Urgent", NotifyRule::All)
+            .await
+            .unwrap();
+        dialog
+            .set_notify_rule("deploys", NotifyRule::Keyword("Failed".into()))
+            .await
+            .unwrap();
+
+        let state = dialog.app_state().await.unwrap();
+        assert_eq!(state.tags["urgent"].value.color.as_deref(), Some("#ff0000"));
+        assert_eq!(dialog.notify_rules().await.unwrap().len(), 2);
+
+        let urgent = dialog.save_note("Call back #urgent").await.unwrap();
+        let (tag, rule) = dialog.notification_rule(&urgent).await.unwrap().unwrap();
+        assert_eq!((tag.as_str(), rule), ("urgent", NotifyRule::All));
+
+        let failed = dialog.save_note("Build failed #deploys").await.unwrap();
+        assert!(dialog.notification_rule(&failed).await.unwrap().is_some());
+        let passed = dialog.save_note("Build passed #deploys").await.unwrap();
+        assert!(dialog.notification_rule(&passed).await.unwrap().is_none());
+        let untagged = dialog.save_note("Nothing here").await.unwrap();
+        assert!(dialog.notification_rule(&untagged).await.unwrap().is_none());
+
+        dialog
+            .set_notify_rule("urgent", NotifyRule::Off)
+            .await
+            .unwrap();
+        assert_eq!(dialog.notify_rules().await.unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,298 @@
+use crate::file_store::JsonStore;
+use crate::{Dialog, DialogError, Result};
+use nostr_sdk::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+pub(crate) const OFFLINE_QUEUE_FILE: &str = "offline_queue.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum QueuedWriteKind {
+    Create,
+    Edit,
+    /// The note is already removed locally; relays are asked to delete it
+    Delete,
+}
+
+/// A change made on this device that relays haven't received yet
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QueuedWrite {
+    /// For an edit, the new copy
+    pub note_id: EventId,
+    pub kind: QueuedWriteKind,
+    /// For an edit, the note relays already have that the copy replaces.
+    /// Deleting an edited note deletes it too.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub replaces: Option<EventId>,
+    pub queued_at: Timestamp,
+}
+
+/// Writes waiting for a relay, oldest first. Kept in the data directory so
+/// notes saved while offline still go out after the app is killed.
+pub(crate) type OfflineQueue = JsonStore<Vec<QueuedWrite>>;
+
+impl Dialog {
+    pub fn offline_queue(&self) -> Result<Vec<QueuedWrite>> {
+        self.offline_queue.get()
+    }
+
+    /// Add a write to the end of the queue and return the queue.
+    ///
+    /// Writes to a note that hasn't gone out yet are folded into its entry
+    /// instead, so relays only get the end result and a create still comes
+    /// before anything made after it: an edit of `replaces` takes its place,
+    /// and deleting a note relays never got drops the entry. Deleting an
+    /// unsent edit turns it into a delete of the note it replaces, which is
+    /// removed locally too so it doesn't take the copy's place again.
+    pub async fn queue_write(
+        &self,
+        note_id: EventId,
+        kind: QueuedWriteKind,
+        replaces: Option<&EventId>,
+    ) -> Result<Vec<QueuedWrite>> {
+        let queued_at = self.now();
+        let (queue, original) = self.offline_queue.update(|queue| {
+            let target = replaces.unwrap_or(&note_id);
+            let queued = queue.iter().position(|write| write.note_id == *target);
+            let mut original = None;
+            let changed = match (queued, kind) {
+                (Some(index), QueuedWriteKind::Delete) => match queue[index].kind {
+                    // Relays never got the note, so there's nothing to ask
+                    QueuedWriteKind::Create => {
+                        queue.remove(index);
+                        true
+                    }
+                    QueuedWriteKind::Edit => {
+                        queue[index].kind = QueuedWriteKind::Delete;
+                        original = queue[index].replaces;
+                        true
+                    }
+                    QueuedWriteKind::Delete => false,
+                },
+                (Some(index), _) => {
+                    let changed = queue[index].note_id != note_id;
+                    queue[index].note_id = note_id;
+                    changed
+                }
+                (None, _) => {
+                    queue.push(QueuedWrite {
+                        note_id,
+                        kind,
+                        replaces: replaces.filter(|_| kind == QueuedWriteKind::Edit).copied(),
+                        queued_at,
+                    });
+                    true
+                }
+            };
+            ((queue.clone(), original), changed)
+        })?;
+        if let Some(original) = original {
+            self.client
+                .database()
+                .delete(Filter::new().id(original))
+                .await
+                .map_err(|e| DialogError::Database(e.to_string()))?;
+        }
+        Ok(queue)
+    }
+
+    /// Notes stored locally whose create or edit no relay has accepted yet
+    pub(crate) fn unsent_note_ids(&self) -> HashSet<EventId> {
+        match self.offline_queue.get() {
+            Ok(queue) => queue
+                .into_iter()
+                .filter(|write| write.kind != QueuedWriteKind::Delete)
+                .map(|write| write.note_id)
+                .collect(),
+            Err(e) => {
+                eprintln!("[lib] offline queue unreadable: {e}");
+                HashSet::new()
+            }
+        }
+    }
+
+    /// Take a write off the queue, e.g. once it was sent, and return the
+    /// queue
+    pub fn dequeue_write(&self, note_id: &EventId) -> Result<Vec<QueuedWrite>> {
+        self.offline_queue.update(|queue| {
+            let before = queue.len();
+            queue.retain(|write| write.note_id != *note_id);
+            (queue.clone(), queue.len() < before)
+        })
+    }
+
+    /// Send one queued write to the relays. The caller dequeues it once
+    /// this succeeds.
+    pub async fn send_queued_write(&self, write: &QueuedWrite) -> Result<()> {
+        match write.kind {
+            QueuedWriteKind::Create | QueuedWriteKind::Edit => {
+                self.publish_stored_note(&write.note_id).await
+            }
+            QueuedWriteKind::Delete => {
+                let ids: Vec<EventId> = std::iter::once(write.note_id)
+                    .chain(write.replaces)
+                    .collect();
+                let request = self.deletion_request(&ids).await?;
+                self.publish(request).await?;
+                Ok(())
+            }
+        }
+    }
+
+    /// Bring back a note removed locally, e.g. to undo a delete. Only works
+    /// until the profile is next opened. Returns whether its deletion
+    /// request was still queued, in which case relays never heard of it;
+    /// one already sent can't be taken back. A queued delete of an unsent
+    /// edit goes back to being the edit.
+    pub fn restore_note(&self, note_id: &EventId) -> Result<bool> {
+        self.local_store.restore(&[*note_id])?;
+        let (unsent, original) = self.offline_queue.update(|queue| {
+            let Some(index) = queue.iter().position(|write| {
+                write.note_id == *note_id && write.kind == QueuedWriteKind::Delete
+            }) else {
+                return ((false, None), false);
+            };
+            let original = queue[index].replaces;
+            if original.is_some() {
+                queue[index].kind = QueuedWriteKind::Edit;
+            } else {
+                queue.remove(index);
+            }
+            ((true, original), true)
+        })?;
+        if let Some(original) = original {
+            self.local_store.restore(&[original])?;
+        }
+        Ok(unsent)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::memory_dialog;
+
+    #[tokio::test]
+    async fn test_queue_folds_writes_to_unsent_notes() {
+        let dialog = memory_dialog();
+        let note = dialog.save_note("Draft").await.unwrap();
+        let sent = dialog.save_note("Sent earlier").await.unwrap();
+        let copy = EventId::all_zeros();
+
+        dialog
+            .queue_write(note.id, QueuedWriteKind::Create, None)
+            .await
+            .unwrap();
+        let queue = dialog
+            .queue_write(copy, QueuedWriteKind::Edit, Some(&note.id))
+            .await
+            .unwrap();
+        assert_eq!(queue.len(), 1);
+        assert_eq!(queue[0].note_id, copy);
+        assert_eq!(queue[0].kind, QueuedWriteKind::Create);
+
+        // Deleting a note relays never got needs no request
+        assert!(dialog
+            .queue_write(copy, QueuedWriteKind::Delete, None)
+            .await
+            .unwrap()
+            .is_empty());
+        let queue = dialog
+            .queue_write(sent.id, QueuedWriteKind::Delete, None)
+            .await
+            .unwrap();
+        assert_eq!(queue[0].kind, QueuedWriteKind::Delete);
+    }
+
+    #[tokio::test]
+    async fn test_queue_survives_reopening() {
+        let dir = std::env::temp_dir().join(format!("dialog-offline-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = dir.join(OFFLINE_QUEUE_FILE);
+        let mut dialog = memory_dialog();
+        dialog.offline_queue = OfflineQueue::at(path.clone());
+        let note = dialog.save_note("Written on a plane").await.unwrap();
+        dialog
+            .queue_write(note.id, QueuedWriteKind::Create, None)
+            .await
+            .unwrap();
+
+        let mut reopened = memory_dialog();
+        reopened.offline_queue = OfflineQueue::at(path);
+        let queue = reopened.offline_queue().unwrap();
+        assert_eq!(queue.len(), 1);
+        assert_eq!(queue[0].note_id, note.id);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_restore_drops_queued_delete() {
+        let dialog = memory_dialog();
+        let note = dialog.save_note("Oops").await.unwrap();
+        dialog
+            .with_batch_offline(|batch| batch.delete(note.id))
+            .await
+            .unwrap();
+        dialog
+            .queue_write(note.id, QueuedWriteKind::Delete, None)
+            .await
+            .unwrap();
+        assert!(dialog.get_note(&note.id).await.unwrap().is_none());
+
+        assert!(dialog.restore_note(&note.id).unwrap());
+        assert!(dialog.offline_queue().unwrap().is_empty());
+        assert!(dialog.get_note(&note.id).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_deleting_unsent_edit_deletes_the_synced_original() {
+        let dialog = memory_dialog();
+        // Already on relays, so not queued
+        let synced = dialog.save_note("Synced long ago").await.unwrap();
+        let report = dialog
+            .with_batch_offline(|batch| batch.edit(synced.id, "Edited offline"))
+            .await
+            .unwrap();
+        let copy = report.edited[0].id;
+        dialog
+            .queue_write(copy, QueuedWriteKind::Edit, Some(&synced.id))
+            .await
+            .unwrap();
+
+        dialog
+            .with_batch_offline(|batch| batch.delete(copy))
+            .await
+            .unwrap();
+        let queue = dialog
+            .queue_write(copy, QueuedWriteKind::Delete, None)
+            .await
+            .unwrap();
+        assert_eq!(queue.len(), 1);
+        assert_eq!(queue[0].kind, QueuedWriteKind::Delete);
+        assert_eq!(queue[0].replaces, Some(synced.id));
+        // The original doesn't come back in the copy's place
+        assert!(dialog.list_notes(10).await.unwrap().is_empty());
+
+        // Undo brings back the copy and its pending edit
+        assert!(dialog.restore_note(&copy).unwrap());
+        let queue = dialog.offline_queue().unwrap();
+        assert_eq!(queue[0].kind, QueuedWriteKind::Edit);
+        let notes = dialog.list_notes(10).await.unwrap();
+        assert_eq!(notes.len(), 1);
+        assert_eq!(notes[0].id, copy);
+    }
+
+    #[tokio::test]
+    async fn test_queued_notes_list_as_unsynced() {
+        let dialog = memory_dialog();
+        let note = dialog.save_note("Saved on the train").await.unwrap();
+        dialog
+            .queue_write(note.id, QueuedWriteKind::Create, None)
+            .await
+            .unwrap();
+        assert!(!dialog.list_notes(10).await.unwrap()[0].is_synced);
+
+        dialog.dequeue_write(&note.id).unwrap();
+        assert!(dialog.list_notes(10).await.unwrap()[0].is_synced);
+    }
+}
@@ -0,0 +1,217 @@
+use crate::file_store::{read_jsonl, write_atomic};
+use crate::note::local_note;
+use crate::{CaptureSource, Dialog, DialogError, Note, QueuedWriteKind, Result, MAX_NOTE_BYTES};
+use nostr_sdk::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Suffix of the file an outbox is moved to while it is being drained
+const DRAINING_SUFFIX: &str = "draining";
+
+/// A note captured by another process (e.g. the iOS share extension) that
+/// hasn't been turned into a real note yet
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OutboxEntry {
+    pub text: String,
+    /// When it was captured; the note keeps this date
+    pub created_at: Timestamp,
+}
+
+/// Append a note to the outbox file at `path`, one JSON object per line.
+///
+/// This never touches the note database, so it is safe to call from a
+/// process that must not open nostrdb while the app has it open.
+pub fn append_to_outbox(path: &Path, text: &str) -> Result<OutboxEntry> {
+    if text.trim().is_empty() {
+        return Err(DialogError::Import("empty note".into()));
+    }
+    if text.len() > MAX_NOTE_BYTES {
+        return Err(DialogError::NoteTooLarge {
+            max: MAX_NOTE_BYTES,
+            actual: text.len(),
+        });
+    }
+
+    let entry = OutboxEntry {
+        text: text.to_string(),
+        created_at: Timestamp::now(),
+    };
+    let mut line = serde_json::to_string(&entry).map_err(|e| DialogError::Import(e.to_string()))?;
+    line.push('\n');
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    // A single append-mode write per entry, so a concurrent drain never
+    // sees half a line
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(line.as_bytes())?;
+    file.sync_data()?;
+    Ok(entry)
+}
+
+/// Entries waiting in an outbox file, without draining it
+pub fn read_outbox(path: &Path) -> Result<Vec<OutboxEntry>> {
+    read_jsonl(path, "outbox")
+}
+
+/// Replace the outbox file at `path` with `entries`
+fn write_outbox(path: &Path, entries: &[OutboxEntry]) -> Result<()> {
+    let mut contents = String::new();
+    for entry in entries {
+        contents.push_str(
+            &serde_json::to_string(entry).map_err(|e| DialogError::Import(e.to_string()))?,
+        );
+        contents.push('\n');
+    }
+    write_atomic(path, contents)
+}
+
+fn draining_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".");
+    name.push(DRAINING_SUFFIX);
+    path.with_file_name(name)
+}
+
+impl Dialog {
+    /// Turn everything in an outbox file into notes, oldest first.
+    ///
+    /// The file is renamed before it is read so captures made while
+    /// draining go to a fresh outbox. Entries that fail to save are put
+    /// back for the next drain. Each entry leaves the renamed file once it
+    /// is handled, so a drain interrupted midway resumes where it stopped
+    /// instead of saving notes twice. Notes that are saved but couldn't
+    /// reach a relay come back with `is_synced` false and are queued (see
+    /// [`Dialog::queue_write`]).
+    pub async fn drain_outbox(&self, path: &Path) -> Result<Vec<Note>> {
+        self.set_outbox_path(Some(path.to_path_buf()));
+        let draining = draining_path(path);
+        if !draining.exists() {
+            match fs::rename(path, &draining) {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        let mut entries = read_outbox(&draining)?;
+        entries.sort_by_key(|entry| entry.created_at);
+
+        let mut notes = Vec::new();
+        let mut failed = Vec::new();
+        while !entries.is_empty() {
+            let entry = entries.remove(0);
+            let event = match self
+                .build_note_event_with(
+                    &entry.text,
+                    entry.created_at,
+                    [],
+                    Some(CaptureSource::ShareExtension),
+                )
+                .await
+            {
+                Ok(event) => event,
+                Err(e) => {
+                    // Retrying wouldn't help, so the entry is dropped
+                    eprintln!(
+                        "[lib] outbox: dropping entry from {}: {e}",
+                        entry.created_at
+                    );
+                    write_outbox(&draining, &[failed.as_slice(), &entries].concat())?;
+                    continue;
+                }
+            };
+            match self.publish_note(event.clone(), &entry.text).await {
+                Ok(note) => notes.push(note),
+                Err(DialogError::Database(e)) => {
+                    eprintln!(
+                        "[lib] outbox: entry from {} not saved: {e}",
+                        entry.created_at
+                    );
+                    failed.push(entry);
+                }
+                // Saved locally; only the relays missed it, so it waits in
+                // the offline queue
+                Err(e) => {
+                    eprintln!("[lib] outbox: {} not published: {e}", event.id);
+                    if let Err(e) = self
+                        .queue_write(event.id, QueuedWriteKind::Create, None)
+                        .await
+                    {
+                        eprintln!("[lib] outbox: {} not queued: {e}", event.id);
+                    }
+                    notes.push(local_note(&self.keys, &event, &entry.text));
+                }
+            }
+            write_outbox(&draining, &[failed.as_slice(), &entries].concat())?;
+        }
+
+        if !failed.is_empty() {
+            let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+            for entry in &failed {
+                let line =
+                    serde_json::to_string(entry).map_err(|e| DialogError::Import(e.to_string()))?;
+                writeln!(file, "{line}")?;
+            }
+        }
+        fs::remove_file(&draining)?;
+        eprintln!(
+            "[lib] outbox: drained {} notes ({} kept for retry)",
+            notes.len(),
+            failed.len()
+        );
+        Ok(notes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::memory_dialog;
+
+    fn outbox_path(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("dialog-outbox-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir.join("outbox.jsonl")
+    }
+
+    #[test]
+    fn test_append_and_read_outbox() {
+        let path = outbox_path("append");
+        assert!(read_outbox(&path).unwrap().is_empty());
+
+        append_to_outbox(&path, "first #shared").unwrap();
+        append_to_outbox(&path, "second\nwith a newline").unwrap();
+        assert!(append_to_outbox(&path, "   ").is_err());
+
+        let texts: Vec<String> = read_outbox(&path)
+            .unwrap()
+            .into_iter()
+            .map(|e| e.text)
+            .collect();
+        assert_eq!(texts, vec!["first #shared", "second\nwith a newline"]);
+    }
+
+    #[tokio::test]
+    async fn test_drain_outbox_empties_file() {
+        let dialog = memory_dialog();
+        let path = outbox_path("drain");
+        assert!(dialog.drain_outbox(&path).await.unwrap().is_empty());
+
+        let entry = append_to_outbox(&path, "captured #shared").unwrap();
+        // No relays: the note is only saved locally
+        let notes = dialog.drain_outbox(&path).await.unwrap();
+        assert_eq!(notes.len(), 1);
+        assert!(!notes[0].is_synced);
+        assert_eq!(notes[0].created_at, entry.created_at);
+        assert!(read_outbox(&path).unwrap().is_empty());
+        assert!(!draining_path(&path).exists());
+
+        let stored = dialog.get_note(&notes[0].id).await.unwrap().unwrap();
+        assert_eq!(stored.tags, vec!["shared"]);
+        assert!(!stored.is_synced);
+    }
+}
@@ -0,0 +1,359 @@
+use crate::file_store::JsonStore;
+use crate::{Dialog, DialogError, Result};
+#[cfg(feature = "nwc")]
+use nostr_sdk::nips::nip47::NostrWalletConnectURI;
+#[cfg(feature = "nwc")]
+use nostr_sdk::nwc::NWC;
+use nostr_sdk::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::sync::atomic::{AtomicU64, Ordering};
+#[cfg(feature = "nwc")]
+use std::sync::{Arc, RwLock};
+
+/// Next to the database; relays that may be paid and what was spent
+pub(crate) const RELAY_PAYMENTS_FILE: &str = "relay_payments.json";
+
+/// Prefix relays use in OK messages when an event needs a paid
+/// subscription first (NIP-01)
+const PAYMENT_REQUIRED_PREFIX: &str = "payment-required:";
+/// What the relay pool puts in front of a relay's OK message
+const NOT_PUBLISHED_PREFIX: &str = "event not published: ";
+/// Largest invoice paid without asking, unless changed with
+/// [`Dialog::set_max_relay_payment`]
+pub const DEFAULT_MAX_RELAY_PAYMENT_SATS: u64 = 10_000;
+/// Total paid to relays without asking, unless changed with
+/// [`Dialog::set_relay_payment_budget`]
+pub const DEFAULT_RELAY_PAYMENT_BUDGET_SATS: u64 = 50_000;
+/// BOLT11 human-readable prefixes: mainnet, testnet, signet and regtest
+const INVOICE_PREFIXES: [&str; 4] = ["lnbcrt", "lntbs", "lntb", "lnbc"];
+
+/// Which relays may be paid automatically and how much has been, kept
+/// across launches so the budget covers every payment
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct RelayPayments {
+    /// Relay URLs the user opted into paying
+    relays: BTreeSet<String>,
+    budget_sats: u64,
+    spent_sats: u64,
+}
+
+impl Default for RelayPayments {
+    fn default() -> Self {
+        Self {
+            relays: BTreeSet::new(),
+            budget_sats: DEFAULT_RELAY_PAYMENT_BUDGET_SATS,
+            spent_sats: 0,
+        }
+    }
+}
+
+/// Nostr Wallet Connect (NIP-47) wallet used to pay relay invoices
+pub(crate) struct Wallet {
+    #[cfg(feature = "nwc")]
+    nwc: RwLock<Option<Arc<NWC>>>,
+    max_sats: AtomicU64,
+    pub(crate) payments: JsonStore<RelayPayments>,
+}
+
+impl Default for Wallet {
+    fn default() -> Self {
+        Self {
+            #[cfg(feature = "nwc")]
+            nwc: RwLock::new(None),
+            max_sats: AtomicU64::new(DEFAULT_MAX_RELAY_PAYMENT_SATS),
+            payments: JsonStore::default(),
+        }
+    }
+}
+
+impl Dialog {
+    /// Connect a wallet from a `nostr+walletconnect://` URI. When a relay
+    /// opted into with [`Dialog::set_payable_relays`] answers a publish with
+    /// `payment-required` and an invoice, the invoice is paid through this
+    /// wallet, within [`Dialog::relay_payment_budget`], and the publish
+    /// retried once.
+    #[cfg(feature = "nwc")]
+    pub fn configure_nwc(&self, uri: &str) -> Result<()> {
+        let uri: NostrWalletConnectURI = uri
+            .parse()
+            .map_err(|e| DialogError::Payment(format!("invalid wallet connect URI: {e}")))?;
+        eprintln!("[lib] configure_nwc: wallet relay {}", uri.relay_url);
+        *self.wallet.nwc.write().unwrap() = Some(Arc::new(NWC::new(uri)));
+        Ok(())
+    }
+
+    /// Forget the configured wallet; payment-required relays then just fail
+    #[cfg(feature = "nwc")]
+    pub fn clear_nwc(&self) {
+        *self.wallet.nwc.write().unwrap() = None;
+    }
+
+    #[cfg(feature = "nwc")]
+    pub fn has_nwc(&self) -> bool {
+        self.wallet.nwc.read().unwrap().is_some()
+    }
+
+    /// Cap on a single relay invoice; larger invoices are refused
+    pub fn set_max_relay_payment(&self, sats: u64) {
+        self.wallet.max_sats.store(sats, Ordering::Relaxed);
+    }
+
+    pub fn max_relay_payment(&self) -> u64 {
+        self.wallet.max_sats.load(Ordering::Relaxed)
+    }
+
+    /// Relays whose invoices may be paid without asking; any other relay
+    /// asking for payment just fails the publish
+    pub fn set_payable_relays(&self, urls: &[&str]) -> Result<()> {
+        let relays = urls
+            .iter()
+            .map(|url| {
+                RelayUrl::parse(url)
+                    .map(|url| url.to_string())
+                    .map_err(|e| DialogError::Config(e.to_string()))
+            })
+            .collect::<Result<BTreeSet<String>>>()?;
+        self.wallet.payments.update(|payments| {
+            payments.relays = relays;
+            ((), true)
+        })
+    }
+
+    pub fn payable_relays(&self) -> Result<Vec<String>> {
+        Ok(self.wallet.payments.get()?.relays.into_iter().collect())
+    }
+
+    /// Cap on everything paid to relays automatically, across launches.
+    /// Once spent, relay invoices are refused until it is raised.
+    pub fn set_relay_payment_budget(&self, sats: u64) -> Result<()> {
+        self.wallet.payments.update(|payments| {
+            payments.budget_sats = sats;
+            ((), true)
+        })
+    }
+
+    pub fn relay_payment_budget(&self) -> Result<u64> {
+        Ok(self.wallet.payments.get()?.budget_sats)
+    }
+
+    /// Sats paid to relays automatically so far
+    pub fn relay_payments_spent(&self) -> Result<u64> {
+        Ok(self.wallet.payments.get()?.spent_sats)
+    }
+
+    /// Set `sats` aside from the budget for a payment to `url`, or say why
+    /// not. Handed back with `refund_relay_payment` if the payment fails.
+    #[cfg(any(feature = "nwc", test))]
+    fn reserve_relay_payment(&self, url: &RelayUrl, sats: u64) -> Result<()> {
+        self.wallet
+            .payments
+            .update(|payments| {
+                if !payments.relays.contains(&url.to_string()) {
+                    return (
+                        Err(format!("{url} isn't a relay payments were allowed for")),
+                        false,
+                    );
+                }
+                let left = payments.budget_sats.saturating_sub(payments.spent_sats);
+                if sats > left {
+                    return (
+                        Err(format!(
+                            "{sats} sats is over the {left} sats left in the budget"
+                        )),
+                        false,
+                    );
+                }
+                payments.spent_sats += sats;
+                (Ok(()), true)
+            })?
+            .map_err(DialogError::Payment)
+    }
+
+    #[cfg(any(feature = "nwc", test))]
+    fn refund_relay_payment(&self, sats: u64) {
+        let refunded = self.wallet.payments.update(|payments| {
+            payments.spent_sats = payments.spent_sats.saturating_sub(sats);
+            ((), true)
+        });
+        if let Err(e) = refunded {
+            eprintln!("[lib] payments: refund of {sats} sats not recorded: {e}");
+        }
+    }
+
+    /// Pay a BOLT11 invoice with the configured wallet, returning the
+    /// preimage. Invoices without an amount or above the payment cap are
+    /// refused.
+    #[cfg(feature = "nwc")]
+    pub async fn pay_invoice(&self, invoice: &str) -> Result<String> {
+        let nwc = self
+            .wallet
+            .nwc
+            .read()
+            .unwrap()
+            .clone()
+            .ok_or_else(|| DialogError::Payment("no wallet configured".into()))?;
+        let msats = invoice_amount_msats(invoice)
+            .ok_or_else(|| DialogError::Payment("invoice has no amount".into()))?;
+        let sats = msats.div_ceil(1000);
+        let max = self.max_relay_payment();
+        if sats > max {
+            return Err(DialogError::Payment(format!(
+                "invoice for {sats} sats is over the {max} sat limit"
+            )));
+        }
+
+        eprintln!("[lib] pay_invoice: paying {sats} sats");
+        let preimage = nwc
+            .pay_invoice(invoice)
+            .await
+            .map_err(|e| DialogError::Payment(e.to_string()))?;
+        eprintln!("[lib] pay_invoice: paid");
+        Ok(preimage)
+    }
+
+    /// Pay the invoice in a relay's `payment-required` message if the user
+    /// opted into paying `url` and the budget allows. Returns whether it
+    /// was paid, i.e. whether retrying the publish makes sense.
+    pub(crate) async fn pay_relay(&self, url: &RelayUrl, message: &str) -> bool {
+        if !is_payment_required(message) {
+            return false;
+        }
+        let Some(invoice) = find_invoice(message) else {
+            eprintln!("[lib] payments: {url} wants payment but sent no invoice: {message}");
+            return false;
+        };
+        self.pay_relay_invoice(url, &invoice).await
+    }
+
+    #[cfg(feature = "nwc")]
+    async fn pay_relay_invoice(&self, url: &RelayUrl, invoice: &str) -> bool {
+        let Some(sats) = invoice_amount_msats(invoice).map(|msats| msats.div_ceil(1000)) else {
+            eprintln!("[lib] payments: not paying {url}: invoice has no amount");
+            return false;
+        };
+        if let Err(e) = self.reserve_relay_payment(url, sats) {
+            eprintln!("[lib] payments: not paying {url}: {e}");
+            return false;
+        }
+        match self.pay_invoice(invoice).await {
+            Ok(_) => {
+                eprintln!("[lib] payments: paid {url}");
+                true
+            }
+            Err(e) => {
+                self.refund_relay_payment(sats);
+                eprintln!("[lib] payments: not paying {url}: {e}");
+                false
+            }
+        }
+    }
+
+    #[cfg(not(feature = "nwc"))]
+    async fn pay_relay_invoice(&self, url: &RelayUrl, invoice: &str) -> bool {
+        eprintln!("[lib] payments: {url} wants payment; pay {invoice} to continue");
+        false
+    }
+}
+
+/// Whether the relay's message starts with `payment-required:`. Other
+/// messages that merely mention it (e.g. `blocked: no payment-required:
+/// invoices accepted`) don't count.
+fn is_payment_required(message: &str) -> bool {
+    message
+        .strip_prefix(NOT_PUBLISHED_PREFIX)
+        .unwrap_or(message)
+        .starts_with(PAYMENT_REQUIRED_PREFIX)
+}
+
+/// First BOLT11 invoice in free text, e.g. `lightning:lnbc...`
+fn find_invoice(message: &str) -> Option<String> {
+    message
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .map(|word| word.to_ascii_lowercase())
+        .find(|word| {
+            INVOICE_PREFIXES
+                .iter()
+                .any(|prefix| word.starts_with(prefix))
+                && word.contains('1')
+        })
+}
+
+/// Amount encoded in a BOLT11 invoice's human-readable part, in millisats
+fn invoice_amount_msats(invoice: &str) -> Option<u64> {
+    let invoice = invoice.to_ascii_lowercase();
+    let hrp = &invoice[..invoice.rfind('1')?];
+    let prefix = INVOICE_PREFIXES
+        .iter()
+        .find(|prefix| hrp.starts_with(*prefix))?;
+    let amount = &hrp[prefix.len()..];
+
+    let (digits, multiplier) = match amount.chars().last()? {
+        'm' | 'u' | 'n' | 'p' => amount.split_at(amount.len() - 1),
+        _ => (amount, ""),
+    };
+    let value: u64 = digits.parse().ok()?;
+    // One bitcoin is 10^11 millisats
+    match multiplier {
+        "" => value.checked_mul(100_000_000_000),
+        "m" => value.checked_mul(100_000_000),
+        "u" => value.checked_mul(100_000),
+        "n" => value.checked_mul(100),
+        // Pico-bitcoin amounts must be whole millisats
+        "p" if value % 10 == 0 => Some(value / 10),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_invoice_amount_msats() {
+        assert_eq!(invoice_amount_msats("lnbc2500u1pvjluez"), Some(250_000_000));
+        assert_eq!(invoice_amount_msats("lnbc20m1pvjluez"), Some(2_000_000_000));
+        assert_eq!(invoice_amount_msats("LNTB1500N1PVJLUEZ"), Some(150_000));
+        assert_eq!(invoice_amount_msats("lnbcrt10p1pvjluez"), Some(1));
+        assert_eq!(invoice_amount_msats("lnbc1pvjluez"), None);
+        assert_eq!(invoice_amount_msats("lnbc15p1pvjluez"), None);
+    }
+
+    #[test]
+    fn test_find_invoice_in_relay_message() {
+        let message =
+            "event not published: payment-required: pay lightning:lnbc10u1pvjluez to join";
+        assert!(is_payment_required(message));
+        assert_eq!(find_invoice(message).as_deref(), Some("lnbc10u1pvjluez"));
+        assert_eq!(
+            find_invoice("payment-required: see https://relay.example/join"),
+            None
+        );
+        assert!(!is_payment_required(
+            "event not published: rate-limited: slow down"
+        ));
+        assert!(!is_payment_required(
+            "event not published: blocked: no payment-required: lnbc10u1pvjluez"
+        ));
+        assert!(is_payment_required("payment-required: lnbc10u1pvjluez"));
+    }
+
+    #[tokio::test]
+    async fn test_relay_payments_need_opt_in_and_budget() {
+        let dialog = crate::test_support::memory_dialog();
+        let paid = RelayUrl::parse("wss://paid.example.com").unwrap();
+        let other = RelayUrl::parse("wss://other.example.com").unwrap();
+        dialog
+            .set_payable_relays(&["wss://paid.example.com"])
+            .unwrap();
+        dialog.set_relay_payment_budget(1_500).unwrap();
+
+        assert!(dialog.reserve_relay_payment(&other, 10).is_err());
+        dialog.reserve_relay_payment(&paid, 1_000).unwrap();
+        assert!(dialog.reserve_relay_payment(&paid, 1_000).is_err());
+        assert_eq!(dialog.relay_payments_spent().unwrap(), 1_000);
+        dialog.refund_relay_payment(1_000);
+        dialog.reserve_relay_payment(&paid, 1_500).unwrap();
+        assert_eq!(dialog.relay_payments_spent().unwrap(), 1_500);
+    }
+}
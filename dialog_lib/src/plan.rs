@@ -0,0 +1,110 @@
+//! What creating a note puts on relays, so privacy assumptions can be
+//! checked before and after sending
+
+use crate::{Dialog, Note, Result};
+use nostr_sdk::prelude::*;
+
+/// The event [`Dialog::plan_note`] would publish. Everything here is
+/// visible to relays; the text is not.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PublishPlan {
+    pub kind: Kind,
+    /// Tags as sent, e.g. `["t", "work"]` for a hashtag
+    pub tags: Vec<Vec<String>>,
+    /// As relays see it; fuzzed under max privacy
+    pub created_at: Timestamp,
+    /// Size of the encrypted content
+    pub content_bytes: usize,
+    /// Size of the whole signed event as JSON
+    pub event_bytes: usize,
+    /// Write relays it would go to
+    pub relays: Vec<RelayUrl>,
+}
+
+/// How each relay answered a publish
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PublishReport {
+    pub accepted: Vec<RelayUrl>,
+    /// Relays that refused it, with their reason
+    pub rejected: Vec<(RelayUrl, String)>,
+}
+
+impl From<&Output<EventId>> for PublishReport {
+    fn from(output: &Output<EventId>) -> Self {
+        let mut accepted: Vec<RelayUrl> = output.success.iter().cloned().collect();
+        let mut rejected: Vec<(RelayUrl, String)> = output
+            .failed
+            .iter()
+            .map(|(url, reason)| {
+                let reason = reason.clone().unwrap_or_else(|| "unknown".to_string());
+                (url.clone(), reason)
+            })
+            .collect();
+        accepted.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+        rejected.sort_by(|a, b| a.0.as_str().cmp(b.0.as_str()));
+        Self { accepted, rejected }
+    }
+}
+
+impl Dialog {
+    /// Build and sign the event [`Dialog::create_note`] would publish for
+    /// `text`, without storing or sending it
+    pub async fn plan_note(&self, text: &str) -> Result<PublishPlan> {
+        let text = self.prepare_text(text).await;
+        let event = self.build_note_event(&text).await?;
+        let mut relays: Vec<RelayUrl> = self
+            .client
+            .pool()
+            .relays_with_flag(RelayServiceFlags::WRITE, FlagCheck::All)
+            .await
+            .into_keys()
+            .filter(|url| self.relay_allows(url, event.kind))
+            .collect();
+        relays.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+        Ok(PublishPlan {
+            kind: event.kind,
+            tags: event
+                .tags
+                .iter()
+                .map(|tag| tag.as_slice().to_vec())
+                .collect(),
+            created_at: event.created_at,
+            content_bytes: event.content.len(),
+            event_bytes: event.as_json().len(),
+            relays,
+        })
+    }
+
+    /// [`Dialog::create_note`], also returning each relay's answer
+    pub async fn create_note_reported(&self, text: &str) -> Result<(Note, PublishReport)> {
+        let text = &self.prepare_text(text).await;
+        let event = self.build_note_event(text).await?;
+        let (note, output) = self.publish_note_output(event, text).await?;
+        self.maybe_auto_enrich(note.id, text);
+        Ok((note, PublishReport::from(&output)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::memory_dialog;
+
+    #[tokio::test]
+    async fn test_plan_shows_what_relays_see() {
+        let dialog = memory_dialog();
+        let plan = dialog.plan_note("Secret plans #work").await.unwrap();
+        assert_eq!(plan.kind, dialog.note_kind());
+        assert!(plan
+            .tags
+            .contains(&vec!["t".to_string(), "work".to_string()]));
+        assert!(plan.content_bytes > "Secret plans #work".len());
+        assert!(plan.relays.is_empty());
+        // Nothing was stored
+        assert!(dialog.list_notes(10).await.unwrap().is_empty());
+
+        dialog.set_max_privacy(true).unwrap();
+        let plan = dialog.plan_note("Secret plans #work").await.unwrap();
+        assert!(plan.tags.iter().all(|tag| tag[0] != "t"));
+    }
+}
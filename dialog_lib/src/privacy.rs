@@ -0,0 +1,142 @@
+use crate::query::extract_tags;
+use crate::tags::parse_hashtags;
+use crate::{Dialog, DialogError, Result};
+use nostr_sdk::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+
+pub(crate) const MAX_PRIVACY_FILE: &str = "max_privacy.json";
+
+/// Furthest a private note's public created_at is moved into the past
+pub const MAX_TIMESTAMP_FUZZ_SECS: u64 = 60 * 60;
+
+/// Encrypted content of a private note: the text plus the real creation
+/// time, which the event itself doesn't reveal
+#[derive(Debug, Serialize, Deserialize)]
+struct PrivatePayload {
+    text: String,
+    created_at: u64,
+}
+
+/// A note event's decrypted text, tags and real creation time
+pub(crate) struct OpenedNote {
+    pub text: String,
+    pub tags: Vec<String>,
+    pub created_at: Timestamp,
+}
+
+impl Dialog {
+    /// Maximum privacy: new notes are published without hashtags or the
+    /// p-tag and with a created_at fuzzed up to
+    /// [`MAX_TIMESTAMP_FUZZ_SECS`] into the past. Tags and the real time
+    /// travel inside the encrypted content instead. Notes written either
+    /// way are read and searched the same.
+    /// The setting belongs to the profile and is kept in its data
+    /// directory, so it holds until changed again.
+    pub fn set_max_privacy(&self, enabled: bool) -> Result<()> {
+        self.max_privacy.set(enabled)
+    }
+
+    pub fn max_privacy(&self) -> bool {
+        // Read when the profile opens, so this only fails if that did; err
+        // on the private side then
+        self.max_privacy.get().unwrap_or(true)
+    }
+}
+
+/// Decrypt a note event written in either mode
+pub(crate) fn open_note(keys: &Keys, event: &Event) -> Result<OpenedNote> {
+    let decrypted = nip44::decrypt(keys.secret_key(), &keys.public_key(), &event.content)?;
+    if is_private(event) {
+        // Anything else without a p-tag is read as a plain note
+        if let Ok(payload) = serde_json::from_str::<PrivatePayload>(&decrypted) {
+            return Ok(OpenedNote {
+                tags: parse_hashtags(&payload.text),
+                text: payload.text,
+                created_at: Timestamp::from(payload.created_at),
+            });
+        }
+    }
+    Ok(OpenedNote {
+        text: decrypted,
+        tags: extract_tags(event),
+        created_at: event.created_at,
+    })
+}
+
+/// Normal notes p-tag their author; private ones carry no tags in the clear
+pub(crate) fn is_private(event: &Event) -> bool {
+    !event
+        .tags
+        .iter()
+        .any(|tag| tag.as_slice().first().is_some_and(|name| name == "p"))
+}
+
+pub(crate) fn private_content(text: &str, created_at: Timestamp) -> Result<String> {
+    let payload = PrivatePayload {
+        text: text.to_string(),
+        created_at: created_at.as_u64(),
+    };
+    serde_json::to_string(&payload).map_err(|e| DialogError::Database(e.to_string()))
+}
+
+/// A random time up to [`MAX_TIMESTAMP_FUZZ_SECS`] before `created_at`
+pub(crate) fn fuzz_timestamp(created_at: Timestamp) -> Timestamp {
+    let jitter = RandomState::new().hash_one(created_at.as_u64()) % (MAX_TIMESTAMP_FUZZ_SECS + 1);
+    Timestamp::from(created_at.as_u64().saturating_sub(jitter))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::memory_dialog;
+
+    #[tokio::test]
+    async fn test_private_notes_hide_metadata() {
+        let dialog = memory_dialog();
+        dialog.set_max_privacy(true).unwrap();
+        let created_at = Timestamp::from(1_700_000_000);
+        let event = dialog
+            .build_note_event_at("Plan trip #travel", created_at)
+            .await
+            .unwrap();
+        assert!(event.tags.is_empty());
+        assert!(event.created_at <= created_at);
+        assert!(event.created_at.as_u64() >= created_at.as_u64() - MAX_TIMESTAMP_FUZZ_SECS);
+
+        let opened = open_note(&dialog.keys, &event).unwrap();
+        assert_eq!(opened.text, "Plan trip #travel");
+        assert_eq!(opened.tags, vec!["travel"]);
+        assert_eq!(opened.created_at, created_at);
+
+        dialog.save_events([event.clone()]).await.unwrap();
+        dialog.set_max_privacy(false).unwrap();
+        dialog.save_note("Pack bags #travel").await.unwrap();
+        let tagged = dialog.list_by_tag("travel", 10).await.unwrap();
+        let texts: Vec<&str> = tagged.iter().map(|n| n.text.as_str()).collect();
+        assert_eq!(texts, vec!["Pack bags #travel", "Plan trip #travel"]);
+        assert_eq!(tagged[1].created_at, created_at);
+    }
+
+    #[tokio::test]
+    async fn test_max_privacy_is_kept_with_the_profile() {
+        let dir = std::env::temp_dir().join(format!("dialog-privacy-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let keys = Keys::generate();
+        let open = || {
+            Dialog::builder()
+                .signer(keys.clone())
+                .data_dir(&dir)
+                .build()
+        };
+
+        let dialog = open().await.unwrap();
+        assert!(!dialog.max_privacy());
+        dialog.set_max_privacy(true).unwrap();
+        drop(dialog);
+
+        assert!(open().await.unwrap().max_privacy());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,109 @@
+use crate::kinds::APP_DATA_KIND;
+use crate::{Dialog, DialogError, Result};
+use nostr_sdk::prelude::*;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Encrypted-to-self NIP-78 records keyed by d-tag: share records, voice
+/// data and the like. Replacing one bumps created_at past the record it
+/// replaces, so the newest copy wins even within the same second.
+impl Dialog {
+    /// Sign a record without saving or publishing it. `previous` is the
+    /// created_at of the record being replaced.
+    pub(crate) async fn private_record_event<T: Serialize>(
+        &self,
+        d_tag: String,
+        value: &T,
+        previous: Option<Timestamp>,
+    ) -> Result<Event> {
+        let json =
+            serde_json::to_string(value).map_err(|e| DialogError::Database(e.to_string()))?;
+        let encrypted = nip44::encrypt(
+            self.keys.secret_key(),
+            &self.keys.public_key(),
+            json,
+            nip44::Version::default(),
+        )?;
+        let mut created_at = self.created_at.next(self.now());
+        if let Some(previous) = previous {
+            created_at = created_at.max(Timestamp::from(previous.as_u64() + 1));
+        }
+        Ok(EventBuilder::new(Kind::from(APP_DATA_KIND), encrypted)
+            .tag(Tag::identifier(d_tag))
+            .custom_created_at(created_at)
+            .sign(&self.keys)
+            .await?)
+    }
+
+    /// Save a record on this device only. Returns its created_at.
+    pub(crate) async fn save_private_record<T: Serialize>(
+        &self,
+        d_tag: String,
+        value: &T,
+        previous: Option<Timestamp>,
+    ) -> Result<Timestamp> {
+        let event = self.private_record_event(d_tag, value, previous).await?;
+        self.client
+            .database()
+            .save_event(&event)
+            .await
+            .map_err(|e| DialogError::Database(e.to_string()))?;
+        Ok(event.created_at)
+    }
+
+    /// The newest record under `d_tag` and its created_at
+    pub(crate) async fn private_record<T: DeserializeOwned>(
+        &self,
+        d_tag: &str,
+    ) -> Result<Option<(T, Timestamp)>> {
+        let filter = Filter::new()
+            .author(self.keys.public_key())
+            .kind(Kind::from(APP_DATA_KIND))
+            .identifier(d_tag);
+        let events = self
+            .client
+            .database()
+            .query(vec![filter])
+            .await
+            .map_err(|e| DialogError::Database(e.to_string()))?;
+        let Some(event) = events.into_iter().max_by_key(|e| e.created_at) else {
+            return Ok(None);
+        };
+        Ok(Some((
+            self.decode_private_record(&event)?,
+            event.created_at,
+        )))
+    }
+
+    /// Every record whose d-tag starts with `prefix`, skipping unreadable
+    /// ones
+    pub(crate) async fn private_records<T: DeserializeOwned>(
+        &self,
+        prefix: &str,
+    ) -> Result<Vec<T>> {
+        let filter = Filter::new()
+            .author(self.keys.public_key())
+            .kind(Kind::from(APP_DATA_KIND));
+        let events = self
+            .client
+            .database()
+            .query(vec![filter])
+            .await
+            .map_err(|e| DialogError::Database(e.to_string()))?;
+        Ok(events
+            .into_iter()
+            .filter(|event| {
+                event
+                    .tags
+                    .identifier()
+                    .is_some_and(|d| d.starts_with(prefix))
+            })
+            .filter_map(|event| self.decode_private_record(&event).ok())
+            .collect())
+    }
+
+    pub(crate) fn decode_private_record<T: DeserializeOwned>(&self, event: &Event) -> Result<T> {
+        let json = self.decrypt_event(event)?;
+        serde_json::from_str(&json).map_err(|e| DialogError::Database(e.to_string()))
+    }
+}
@@ -0,0 +1,157 @@
+//! Timings of the operations users wait on, so slow ones can be reported
+//! from the field
+
+use crate::Dialog;
+use nostr_sdk::prelude::*;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+
+/// Timings kept for [`Dialog::last_op_timings`]
+pub const OP_TIMING_HISTORY: usize = 64;
+
+/// Threshold until [`Dialog::set_slow_threshold`] says otherwise
+pub const DEFAULT_SLOW_THRESHOLD: Duration = Duration::from_millis(500);
+
+/// Slow operations kept for a receiver that isn't keeping up
+const SLOW_FEED_CAPACITY: usize = 32;
+
+/// How long one operation took
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpTiming {
+    /// "list", "search", "sync" or "decrypt"
+    pub name: &'static str,
+    pub duration: Duration,
+    /// When it finished
+    pub at: Timestamp,
+}
+
+#[derive(Debug)]
+pub(crate) struct Profiler {
+    recent: Mutex<VecDeque<OpTiming>>,
+    // None reports nothing as slow
+    threshold: Mutex<Option<Duration>>,
+    slow: broadcast::Sender<OpTiming>,
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Self {
+            recent: Mutex::new(VecDeque::with_capacity(OP_TIMING_HISTORY)),
+            threshold: Mutex::new(Some(DEFAULT_SLOW_THRESHOLD)),
+            slow: broadcast::channel(SLOW_FEED_CAPACITY).0,
+        }
+    }
+}
+
+impl Profiler {
+    fn record(&self, timing: OpTiming) {
+        let slow = self
+            .threshold
+            .lock()
+            .unwrap()
+            .is_some_and(|threshold| timing.duration >= threshold);
+        if slow {
+            eprintln!(
+                "[lib] slow operation: {} took {}ms",
+                timing.name,
+                timing.duration.as_millis()
+            );
+            let _ = self.slow.send(timing.clone());
+        }
+        let mut recent = self.recent.lock().unwrap();
+        if recent.len() == OP_TIMING_HISTORY {
+            recent.pop_back();
+        }
+        recent.push_front(timing);
+    }
+}
+
+/// Times an operation from creation until dropped, so early returns are
+/// timed too
+pub(crate) struct Span<'a> {
+    dialog: &'a Dialog,
+    name: &'static str,
+    started: Instant,
+}
+
+impl Drop for Span<'_> {
+    fn drop(&mut self) {
+        self.dialog.profiler.record(OpTiming {
+            name: self.name,
+            duration: self.dialog.clock.instant().duration_since(self.started),
+            at: self.dialog.now(),
+        });
+    }
+}
+
+impl Dialog {
+    pub(crate) fn span(&self, name: &'static str) -> Span<'_> {
+        Span {
+            dialog: self,
+            name,
+            started: self.clock.instant(),
+        }
+    }
+
+    /// Recent timings of listing, searching, syncing and decrypting notes,
+    /// newest first
+    pub fn last_op_timings(&self) -> Vec<OpTiming> {
+        self.profiler
+            .recent
+            .lock()
+            .unwrap()
+            .iter()
+            .cloned()
+            .collect()
+    }
+
+    /// Operations taking at least `threshold` are logged and sent to
+    /// [`Dialog::watch_slow_operations`]; None turns that off
+    pub fn set_slow_threshold(&self, threshold: Option<Duration>) {
+        *self.profiler.threshold.lock().unwrap() = threshold;
+    }
+
+    pub fn slow_threshold(&self) -> Option<Duration> {
+        *self.profiler.threshold.lock().unwrap()
+    }
+
+    /// Stream of operations that went over the slow threshold from now on
+    pub fn watch_slow_operations(&self) -> broadcast::Receiver<OpTiming> {
+        self.profiler.slow.subscribe()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::memory_dialog;
+
+    #[tokio::test]
+    async fn test_slow_operations_are_reported() {
+        let dialog = memory_dialog();
+        let mut slow = dialog.watch_slow_operations();
+        dialog.set_slow_threshold(None);
+        dialog.save_note("Profile me").await.unwrap();
+        dialog.list_notes(10).await.unwrap();
+
+        let timings = dialog.last_op_timings();
+        assert_eq!(timings[0].name, "list");
+        assert!(timings.iter().any(|timing| timing.name == "decrypt"));
+        assert!(slow.try_recv().is_err());
+
+        dialog.set_slow_threshold(Some(Duration::ZERO));
+        dialog.search_notes("profile", 10).await.unwrap();
+        let reported: Vec<&str> = std::iter::from_fn(|| slow.try_recv().ok())
+            .map(|timing| timing.name)
+            .collect();
+        assert!(reported.contains(&"search"));
+        assert_eq!(dialog.last_op_timings()[0].name, "search");
+
+        for _ in 0..OP_TIMING_HISTORY {
+            dialog.list_notes(1).await.unwrap();
+        }
+        assert_eq!(dialog.last_op_timings().len(), OP_TIMING_HISTORY);
+    }
+}
@@ -0,0 +1,132 @@
+//! Where a note came from: the device, app and capture path that wrote it,
+//! recorded in the note's encrypted device tag
+
+use crate::devices::event_device;
+use crate::{Dialog, DialogError, Result};
+use nostr_sdk::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// How a note was captured
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CaptureSource {
+    App,
+    Cli,
+    /// Queued in an outbox by another process, e.g. the share extension
+    ShareExtension,
+    Import,
+}
+
+impl fmt::Display for CaptureSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            CaptureSource::App => "app",
+            CaptureSource::Cli => "cli",
+            CaptureSource::ShareExtension => "share-extension",
+            CaptureSource::Import => "import",
+        })
+    }
+}
+
+/// What a note's device tag says about where it came from. Notes written
+/// before provenance was recorded only have the device id.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Provenance {
+    /// As in [`Dialog::devices`]
+    pub device_id: String,
+    pub device_name: Option<String>,
+    /// E.g. "dialog-cli 0.1.0"
+    pub app_version: Option<String>,
+    pub source: Option<CaptureSource>,
+}
+
+impl fmt::Display for Provenance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.device_name.as_deref().unwrap_or(&self.device_id))?;
+        if let Some(app) = &self.app_version {
+            write!(f, ", {app}")?;
+        }
+        if let Some(source) = self.source {
+            write!(f, " via {source}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Dialog {
+    /// App name and version recorded on notes from now on
+    pub fn set_app_version(&self, version: Option<String>) {
+        self.device.write().unwrap().app_version = version;
+    }
+
+    /// Capture source recorded on notes from now on. Imports and outbox
+    /// drains record theirs regardless.
+    pub fn set_capture_source(&self, source: Option<CaptureSource>) {
+        self.device.write().unwrap().source = source;
+    }
+
+    /// Where the stored note `note_id` came from. None if it isn't stored
+    /// locally or has no device tag.
+    pub async fn note_provenance(&self, note_id: &EventId) -> Result<Option<Provenance>> {
+        let events = self
+            .client
+            .database()
+            .query(vec![self.notes_filter().id(*note_id)])
+            .await
+            .map_err(|e| DialogError::Database(e.to_string()))?;
+        let Some(info) = events
+            .into_iter()
+            .next()
+            .and_then(|event| event_device(&self.keys, &event))
+        else {
+            return Ok(None);
+        };
+        Ok(Some(Provenance {
+            device_id: info.id,
+            device_name: info.name,
+            app_version: info.app_version,
+            source: info.source,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::memory_dialog;
+
+    #[tokio::test]
+    async fn test_notes_record_provenance() {
+        let dialog = memory_dialog();
+        dialog.set_device_name(Some("Laptop".into()));
+        dialog.set_app_version(Some("dialog-cli 1.2.3".into()));
+        dialog.set_capture_source(Some(CaptureSource::Cli));
+        let note = dialog.save_note("Where did this come from?").await.unwrap();
+
+        let provenance = dialog.note_provenance(&note.id).await.unwrap().unwrap();
+        assert_eq!(provenance.device_id, dialog.device_id());
+        assert_eq!(provenance.source, Some(CaptureSource::Cli));
+        assert_eq!(provenance.to_string(), "Laptop, dialog-cli 1.2.3 via cli");
+
+        // An override applies to that event only
+        let event = dialog
+            .build_note_event_with(
+                "Imported",
+                Timestamp::now(),
+                [],
+                Some(CaptureSource::Import),
+            )
+            .await
+            .unwrap();
+        dialog.save_events(vec![event.clone()]).await.unwrap();
+        let imported = dialog.note_provenance(&event.id).await.unwrap().unwrap();
+        assert_eq!(imported.source, Some(CaptureSource::Import));
+        assert_eq!(imported.app_version.as_deref(), Some("dialog-cli 1.2.3"));
+        assert!(dialog
+            .note_provenance(&EventId::all_zeros())
+            .await
+            .unwrap()
+            .is_none());
+    }
+}
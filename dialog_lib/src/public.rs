@@ -0,0 +1,90 @@
+use crate::tags::{parse_hashtags, strip_hashtags};
+use crate::{Dialog, DialogError, Result};
+use nostr_sdk::prelude::*;
+
+/// How a private note is turned into a public post
+#[derive(Debug, Clone, Default)]
+pub struct PublishOptions {
+    /// Drop hashtags from the public text (and don't tag the post with them)
+    pub strip_tags: bool,
+}
+
+impl Dialog {
+    /// Publish a private note's text as a public kind-1 post.
+    ///
+    /// The public event id is recorded in the note's app state, so the note
+    /// reports it as `public_id`. Publishing an already published note
+    /// returns the existing id instead of posting it twice. Callers are
+    /// expected to have confirmed with the user: the post can't be unpublished.
+    pub async fn publish_public(
+        &self,
+        note_id: &EventId,
+        options: PublishOptions,
+    ) -> Result<EventId> {
+        let note = self
+            .get_note(note_id)
+            .await?
+            .ok_or(DialogError::NoteNotFound(*note_id))?;
+        if let Some(public_id) = note.public_id {
+            eprintln!("[lib] publish_public: {note_id} already published as {public_id}");
+            return Ok(public_id);
+        }
+
+        let event = self.build_public_event(&note.text, &options).await?;
+        let public_id = event.id;
+        self.publish(event).await?;
+        eprintln!("[lib] publish_public: {note_id} published as {public_id}");
+
+        self.set_public_id(note_id, public_id).await?;
+        Ok(public_id)
+    }
+
+    async fn build_public_event(&self, text: &str, options: &PublishOptions) -> Result<Event> {
+        let mut builder = if options.strip_tags {
+            EventBuilder::text_note(strip_hashtags(text))
+        } else {
+            EventBuilder::text_note(text).tags(parse_hashtags(text).into_iter().map(Tag::hashtag))
+        };
+        builder = builder.custom_created_at(self.created_at.next(self.now()));
+        Ok(builder.sign(&self.keys).await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query::extract_tags;
+    use crate::test_support::memory_dialog;
+
+    #[tokio::test]
+    async fn test_build_public_event() {
+        let dialog = memory_dialog();
+        let text = "Finally shipped #work";
+
+        let tagged = dialog
+            .build_public_event(text, &PublishOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(tagged.kind, Kind::TextNote);
+        assert_eq!(tagged.content, text);
+        assert_eq!(extract_tags(&tagged), vec!["work"]);
+
+        let stripped = dialog
+            .build_public_event(text, &PublishOptions { strip_tags: true })
+            .await
+            .unwrap();
+        assert_eq!(stripped.content, "Finally shipped");
+        assert!(extract_tags(&stripped).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_publish_public_requires_note() {
+        let dialog = memory_dialog();
+        let missing = EventId::all_zeros();
+        let err = dialog
+            .publish_public(&missing, PublishOptions::default())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, DialogError::NoteNotFound(id) if id == missing));
+    }
+}
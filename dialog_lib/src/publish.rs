@@ -0,0 +1,346 @@
+use crate::debug::DebugFeed;
+use crate::{Clock, Dialog, DialogError, Result};
+use nostr_sdk::prelude::*;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+use tokio::task::JoinSet;
+
+/// Prefix relays use in OK messages when they throttle us (NIP-01)
+const RATE_LIMITED_PREFIX: &str = "rate-limited";
+/// Attempts per relay before giving up on a rate-limited publish
+const MAX_PUBLISH_ATTEMPTS: u32 = 5;
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Publish rate allowed per relay
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+    /// Sustained events per second
+    pub events_per_sec: f64,
+    /// Events that may be sent back-to-back before throttling kicks in
+    pub burst: u32,
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        Self {
+            events_per_sec: 5.0,
+            burst: 10,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct TokenBucket {
+    tokens: f64,
+    refilled_at: Instant,
+    // Set when the relay told us to slow down
+    blocked_until: Option<Instant>,
+    backoff: Duration,
+}
+
+impl TokenBucket {
+    fn new(limit: RateLimit, now: Instant) -> Self {
+        Self {
+            tokens: limit.burst as f64,
+            refilled_at: now,
+            blocked_until: None,
+            backoff: INITIAL_BACKOFF,
+        }
+    }
+
+    /// Take a token, returning how long the caller must wait before sending
+    fn reserve(&mut self, limit: RateLimit, now: Instant) -> Duration {
+        let elapsed = now
+            .saturating_duration_since(self.refilled_at)
+            .as_secs_f64();
+        self.tokens = (self.tokens + elapsed * limit.events_per_sec).min(limit.burst as f64);
+        self.refilled_at = now;
+
+        let blocked = self
+            .blocked_until
+            .map(|until| until.saturating_duration_since(now))
+            .unwrap_or_default();
+
+        // Going negative reserves a slot in the queue for this caller
+        self.tokens -= 1.0;
+        let refill = if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / limit.events_per_sec.max(0.001))
+        };
+        blocked.max(refill)
+    }
+
+    fn back_off(&mut self, now: Instant) {
+        self.blocked_until = Some(now + self.backoff);
+        self.backoff = (self.backoff * 2).min(MAX_BACKOFF);
+    }
+
+    fn succeeded(&mut self) {
+        self.backoff = INITIAL_BACKOFF;
+    }
+}
+
+/// Per-relay token buckets shared by every publish path
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+    limit: Mutex<RateLimit>,
+    buckets: Mutex<HashMap<RelayUrl, TokenBucket>>,
+    // Publishes currently waiting on a bucket; drives the throttled flag
+    waiting: AtomicUsize,
+    throttled: watch::Sender<bool>,
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self {
+            limit: Mutex::new(RateLimit::default()),
+            buckets: Mutex::new(HashMap::new()),
+            waiting: AtomicUsize::new(0),
+            throttled: watch::channel(false).0,
+        }
+    }
+}
+
+impl RateLimiter {
+    async fn acquire(&self, url: &RelayUrl, clock: &dyn Clock) {
+        let limit = *self.limit.lock().unwrap();
+        let wait = {
+            let now = clock.instant();
+            let mut buckets = self.buckets.lock().unwrap();
+            buckets
+                .entry(url.clone())
+                .or_insert_with(|| TokenBucket::new(limit, now))
+                .reserve(limit, now)
+        };
+        if wait.is_zero() {
+            return;
+        }
+
+        if self.waiting.fetch_add(1, Ordering::SeqCst) == 0 {
+            self.throttled.send_replace(true);
+        }
+        eprintln!("[lib] publish: throttling {url} for {wait:?}");
+        clock.sleep(wait).await;
+        if self.waiting.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.throttled.send_replace(false);
+        }
+    }
+
+    fn update(&self, url: &RelayUrl, rate_limited: bool, clock: &dyn Clock) {
+        let mut buckets = self.buckets.lock().unwrap();
+        if let Some(bucket) = buckets.get_mut(url) {
+            if rate_limited {
+                bucket.back_off(clock.instant());
+            } else {
+                bucket.succeeded();
+            }
+        }
+    }
+}
+
+impl Dialog {
+    /// Change the per-relay publish rate (applies to subsequent publishes)
+    pub fn set_publish_rate(&self, limit: RateLimit) {
+        *self.rate_limiter.limit.lock().unwrap() = limit;
+    }
+
+    /// Receiver that flips to `true` while publishes are being held back by
+    /// rate limiting, so UIs can show that uploads are going slowly
+    pub fn watch_throttling(&self) -> watch::Receiver<bool> {
+        self.rate_limiter.throttled.subscribe()
+    }
+
+    /// Store an event locally and send it to every write relay, respecting
+    /// the per-relay rate limit and backing off when a relay reports
+    /// `rate-limited`. Relays that answer `payment-required` with an invoice
+    /// are paid through the configured wallet and tried once more.
+    pub(crate) async fn publish(&self, event: Event) -> Result<Output<EventId>> {
+        self.client
+            .database()
+            .save_event(&event)
+            .await
+            .map_err(|e| DialogError::Database(e.to_string()))?;
+        self.send_to_relays(event).await
+    }
+
+    /// [`Dialog::publish`] without storing the event locally. Relays whose
+    /// policy leaves out the event's kind are skipped.
+    pub(crate) async fn send_to_relays(&self, event: Event) -> Result<Output<EventId>> {
+        let mut relays = self
+            .client
+            .pool()
+            .relays_with_flag(RelayServiceFlags::WRITE, FlagCheck::All)
+            .await;
+        if relays.is_empty() {
+            // Let the client produce its usual "no relays" error
+            return Ok(self.client.send_event(event).await?);
+        }
+        relays.retain(|url, _| self.relay_allows(url, event.kind));
+        if relays.is_empty() {
+            return Err(DialogError::NotPublished(format!(
+                "no relay's policy allows kind {}",
+                event.kind
+            )));
+        }
+
+        let mut tasks = JoinSet::new();
+        for (url, relay) in relays.clone() {
+            let limiter = self.rate_limiter.clone();
+            let clock = self.clock.clone();
+            let debug = self.debug_feed.clone();
+            let chaos = self.chaos.clone();
+            let event = event.clone();
+            tasks.spawn(async move {
+                if let Err(message) = chaos.publish(&*clock).await {
+                    return (url, Err(message));
+                }
+                let result =
+                    send_with_backoff(&limiter, &*clock, &debug, &url, &relay, event).await;
+                (url, result)
+            });
+        }
+
+        let mut output = Output {
+            val: event.id,
+            success: HashSet::new(),
+            failed: HashMap::new(),
+        };
+        while let Some(joined) = tasks.join_next().await {
+            match joined {
+                Ok((url, Ok(()))) => {
+                    output.success.insert(url);
+                }
+                Ok((url, Err(message))) => {
+                    output.failed.insert(url, Some(message));
+                }
+                Err(e) => eprintln!("[lib] publish: relay task failed: {e}"),
+            }
+        }
+
+        let unpaid: Vec<(RelayUrl, String)> = output
+            .failed
+            .iter()
+            .filter_map(|(url, reason)| Some((url.clone(), reason.clone()?)))
+            .collect();
+        for (url, reason) in unpaid {
+            let Some(relay) = relays.get(&url) else {
+                continue;
+            };
+            if !self.pay_relay(&url, &reason).await {
+                continue;
+            }
+            let (clock, debug) = (&*self.clock, &self.debug_feed);
+            let result =
+                send_with_backoff(&self.rate_limiter, clock, debug, &url, relay, event.clone())
+                    .await;
+            match result {
+                Ok(()) => {
+                    output.failed.remove(&url);
+                    output.success.insert(url);
+                }
+                Err(message) => {
+                    output.failed.insert(url, Some(message));
+                }
+            }
+        }
+
+        if output.success.is_empty() {
+            let reasons: Vec<String> = output
+                .failed
+                .iter()
+                .map(|(url, reason)| format!("{url}: {}", reason.as_deref().unwrap_or("unknown")))
+                .collect();
+            return Err(DialogError::NotPublished(reasons.join("; ")));
+        }
+        self.event_counter.published(&output.success);
+        Ok(output)
+    }
+}
+
+async fn send_with_backoff(
+    limiter: &RateLimiter,
+    clock: &dyn Clock,
+    debug: &DebugFeed,
+    url: &RelayUrl,
+    relay: &Relay,
+    event: Event,
+) -> std::result::Result<(), String> {
+    let mut last_error = String::new();
+    for _ in 0..MAX_PUBLISH_ATTEMPTS {
+        limiter.acquire(url, clock).await;
+        debug.sent(clock.now(), Some(url), &ClientMessage::event(event.clone()));
+        match relay.send_event(event.clone()).await {
+            Ok(_) => {
+                limiter.update(url, false, clock);
+                return Ok(());
+            }
+            Err(e) => {
+                last_error = e.to_string();
+                if !is_rate_limited(&last_error) {
+                    return Err(last_error);
+                }
+                eprintln!("[lib] publish: {url} rate-limited us: {last_error}");
+                limiter.update(url, true, clock);
+            }
+        }
+    }
+    Err(last_error)
+}
+
+fn is_rate_limited(message: &str) -> bool {
+    message.contains(RATE_LIMITED_PREFIX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_bucket_allows_burst_then_paces() {
+        let limit = RateLimit {
+            events_per_sec: 2.0,
+            burst: 3,
+        };
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(limit, start);
+
+        for _ in 0..3 {
+            assert_eq!(bucket.reserve(limit, start), Duration::ZERO);
+        }
+        assert_eq!(bucket.reserve(limit, start), Duration::from_millis(500));
+        assert_eq!(bucket.reserve(limit, start), Duration::from_secs(1));
+
+        // Two seconds later the queue has drained and a token is free again
+        let later = start + Duration::from_secs(2);
+        assert_eq!(bucket.reserve(limit, later), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_token_bucket_backs_off_exponentially() {
+        let limit = RateLimit::default();
+        let now = Instant::now();
+        let mut bucket = TokenBucket::new(limit, now);
+
+        bucket.back_off(now);
+        assert_eq!(bucket.reserve(limit, now), INITIAL_BACKOFF);
+        bucket.back_off(now);
+        assert_eq!(bucket.reserve(limit, now), INITIAL_BACKOFF * 2);
+
+        bucket.succeeded();
+        bucket.back_off(now);
+        assert_eq!(bucket.reserve(limit, now), INITIAL_BACKOFF);
+    }
+
+    #[test]
+    fn test_is_rate_limited() {
+        assert!(is_rate_limited(
+            "event not published: rate-limited: slow down"
+        ));
+        assert!(!is_rate_limited("event not published: blocked: spam"));
+    }
+}
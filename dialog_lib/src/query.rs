@@ -1,18 +1,92 @@
-use crate::{Dialog, DialogError, Note, Result};
+use crate::migrate::{is_edited, migrated_from};
+use crate::note::shown_word_count;
+use crate::privacy::{is_private, open_note};
+use crate::tag_defaults::expires_at;
+use crate::validate::{validate_event, Rejection};
+use crate::{normalize_tag, Dialog, DialogError, Note, Result, SearchQuery};
 use nostr_sdk::prelude::*;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+
+/// Number of recent notes [`Dialog::search_notes`] looks through
+pub const SEARCH_SCAN_LIMIT: usize = 1000;
+
+/// Order notes are listed in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SortOrder {
+    #[default]
+    NewestFirst,
+    OldestFirst,
+    /// Pinned notes, then unread ones, then the rest, newest first in each
+    Smart,
+}
+
+/// What a [`SortOrder`] compares. Clients with their own note type build
+/// one per note; `id` only breaks ties, so any id form that sorts like
+/// the event id works, e.g. hex.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SortKey<I> {
+    pub is_pinned: bool,
+    pub is_read: bool,
+    pub created_at: Timestamp,
+    pub id: I,
+}
+
+impl SortOrder {
+    pub fn compare<I: Ord>(self, a: &SortKey<I>, b: &SortKey<I>) -> Ordering {
+        // Ties broken by id so notes from the same second keep their order
+        let newest = b
+            .created_at
+            .cmp(&a.created_at)
+            .then_with(|| b.id.cmp(&a.id));
+        match self {
+            Self::NewestFirst => newest,
+            Self::OldestFirst => newest.reverse(),
+            Self::Smart => b
+                .is_pinned
+                .cmp(&a.is_pinned)
+                .then(a.is_read.cmp(&b.is_read))
+                .then(newest),
+        }
+    }
+}
+
+impl Note {
+    pub fn sort_key(&self) -> SortKey<EventId> {
+        SortKey {
+            is_pinned: self.is_pinned,
+            is_read: self.is_read,
+            created_at: self.created_at,
+            id: self.id,
+        }
+    }
+}
+
+pub fn sort_notes(notes: &mut [Note], order: SortOrder) {
+    notes.sort_by(|a, b| order.compare(&a.sort_key(), &b.sort_key()));
+}
 
 impl Dialog {
     pub async fn list_notes(&self, limit: usize) -> Result<Vec<Note>> {
+        self.list_notes_with(limit, false).await
+    }
+
+    /// [`Dialog::list_notes`], also listing notes replaced by an edit when
+    /// `include_superseded` is set, e.g. for a history view. Copies name
+    /// the note they replace in [`Note::edit_of`].
+    pub async fn list_notes_with(
+        &self,
+        limit: usize,
+        include_superseded: bool,
+    ) -> Result<Vec<Note>> {
+        let _span = self.span("list");
         eprintln!(
             "[lib] list_notes: limit={} for pubkey={}",
             limit,
             self.keys.public_key()
         );
         // Query from local database
-        let filter = Filter::new()
-            .author(self.keys.public_key())
-            .kind(Kind::from(1059))
-            .limit(limit);
+        let filter = self.notes_filter().limit(limit);
 
         let events = self
             .client
@@ -21,35 +95,166 @@ impl Dialog {
             .await
             .map_err(|e| DialogError::Database(e.to_string()))?;
 
-        // Decrypt and convert to Notes
+        let mut notes = self
+            .notes_from_events_with(events, include_superseded)
+            .await;
+        self.add_evicted(&mut notes, None, limit).await;
+        eprintln!("[lib] list_notes: returning {} notes", notes.len());
+        Ok(notes)
+    }
+
+    /// `limit` notes in `order`: the most recent ones, except that
+    /// [`SortOrder::Smart`] puts every pinned note first however old it is
+    pub async fn list_notes_sorted(&self, limit: usize, order: SortOrder) -> Result<Vec<Note>> {
+        let mut notes = self.list_notes(limit).await?;
+        if order == SortOrder::Smart && self.pinned_outside(&notes).await? {
+            // Which stored notes an old pinned one was replaced by is only
+            // known from the whole set
+            notes = self.list_notes(usize::MAX).await?;
+        }
+        sort_notes(&mut notes, order);
+        notes.truncate(limit);
+        Ok(notes)
+    }
+
+    /// Whether a stored pinned note is missing from `notes`
+    async fn pinned_outside(&self, notes: &[Note]) -> Result<bool> {
+        let listed: HashSet<EventId> = notes
+            .iter()
+            .flat_map(|note| [Some(note.id), note.edit_of])
+            .flatten()
+            .collect();
+        let missing: Vec<EventId> = self
+            .app_state()
+            .await?
+            .notes
+            .iter()
+            .filter(|(_, state)| state.is_pinned())
+            .filter_map(|(id, _)| EventId::from_hex(id).ok())
+            .filter(|id| !listed.contains(id))
+            .collect();
+        if missing.is_empty() {
+            return Ok(false);
+        }
+        // Pins of deleted notes linger in the app state
+        let stored = self
+            .client
+            .database()
+            .count(vec![self.notes_filter().ids(missing)])
+            .await
+            .map_err(|e| DialogError::Database(e.to_string()))?;
+        Ok(stored > 0)
+    }
+
+    /// Validate, decrypt and convert stored events into notes, newest first.
+    /// Events that fail either step are quarantined instead of dropped.
+    /// Notes that have a migrated or edited copy among `events` are left
+    /// out.
+    pub(crate) async fn notes_from_events(
+        &self,
+        events: impl IntoIterator<Item = Event>,
+    ) -> Vec<Note> {
+        self.notes_from_events_with(events, false).await
+    }
+
+    /// [`Dialog::notes_from_events`], keeping notes an edit replaced when
+    /// `include_superseded` is set
+    pub(crate) async fn notes_from_events_with(
+        &self,
+        events: impl IntoIterator<Item = Event>,
+        include_superseded: bool,
+    ) -> Vec<Note> {
+        let _span = self.span("decrypt");
+        let events: Vec<Event> = events.into_iter().collect();
+        let by_id: HashMap<EventId, &Event> =
+            events.iter().map(|event| (event.id, event)).collect();
+        let superseded: HashSet<EventId> = events.iter().filter_map(migrated_from).collect();
+        let now = self.now();
+        let state = self.app_state().await.unwrap_or_else(|e| {
+            eprintln!("[lib] app state unavailable: {e}");
+            Default::default()
+        });
+        let ignored = state
+            .ignored_devices
+            .as_ref()
+            .map(|devices| devices.value.clone())
+            .unwrap_or_default();
+        let unsent = self.unsent_note_ids();
         let mut notes = Vec::new();
-        for event in events {
-            if let Ok(decrypted) = self.decrypt_event(&event) {
-                let is_read = self.get_read_status(&event.id).await;
-                notes.push(Note {
-                    id: event.id,
-                    text: decrypted,
-                    tags: extract_tags(&event),
-                    created_at: event.created_at,
-                    is_read,
-                    is_synced: true, // If it's in DB, it was synced
-                });
+        for event in &events {
+            if (superseded.contains(&event.id) && !include_superseded)
+                || self.from_ignored_device(event, &ignored)
+            {
+                continue;
+            }
+            // Expired by a tag default (NIP-40); relays drop these too
+            if expires_at(event).is_some_and(|at| at <= now) {
+                continue;
+            }
+            if let Err(reason) = validate_event(event, &self.keys.public_key(), now) {
+                self.quarantine.add(event, reason, now);
+                continue;
+            }
+            if self.chaos.fail_decrypt() {
+                eprintln!("[lib] chaos: {} failed to decrypt", event.id);
+                continue;
+            }
+            match open_note(&self.keys, event) {
+                Ok(opened) => {
+                    let note_state = state.note(&event.id);
+                    let (text, tags, is_locked) = self.reveal(&event.id, opened.text, opened.tags);
+                    let word_count = shown_word_count(&text);
+                    notes.push(Note {
+                        id: event.id,
+                        text,
+                        tags,
+                        created_at: opened.created_at,
+                        is_read: note_state.is_read(),
+                        is_pinned: note_state.is_pinned(),
+                        is_archived: note_state.is_archived(),
+                        public_id: note_state.public_id(),
+                        audio: None,
+                        transcript: None,
+                        is_locked,
+                        is_synced: !unsent.contains(&event.id),
+                        is_hydrated: true,
+                        edited: is_edited(event, |id| by_id.get(id).copied()),
+                        edit_of: migrated_from(event),
+                        task_status: note_state.task(),
+                        word_count,
+                    });
+                }
+                Err(e) => self
+                    .quarantine
+                    .add(event, Rejection::Undecryptable(e.to_string()), now),
             }
         }
 
-        // Sort by created_at descending (newest first)
-        notes.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+        self.attach_voice_data(&mut notes).await;
+        sort_newest_first(&mut notes);
+        notes
+    }
 
-        eprintln!("[lib] list_notes: returning {} notes", notes.len());
-        Ok(notes)
+    async fn attach_voice_data(&self, notes: &mut [Note]) {
+        let ids: Vec<EventId> = notes.iter().map(|n| n.id).collect();
+        let mut voice = match self.voice_data_for(&ids).await {
+            Ok(voice) => voice,
+            Err(e) => {
+                eprintln!("[lib] voice data unavailable: {e}");
+                return;
+            }
+        };
+        for note in notes {
+            if let Some(data) = voice.remove(&note.id) {
+                note.audio = data.audio;
+                note.transcript = data.transcript;
+            }
+        }
     }
 
-    pub async fn list_by_tag(&self, tag: &str, limit: usize) -> Result<Vec<Note>> {
-        let filter = Filter::new()
-            .author(self.keys.public_key())
-            .kind(Kind::from(1059))
-            .hashtag(tag.to_lowercase())
-            .limit(limit);
+    /// Look up a single note in the local database
+    pub async fn get_note(&self, note_id: &EventId) -> Result<Option<Note>> {
+        let filter = self.notes_filter().id(*note_id);
 
         let events = self
             .client
@@ -58,40 +263,88 @@ impl Dialog {
             .await
             .map_err(|e| DialogError::Database(e.to_string()))?;
 
-        // Decrypt and convert to Notes
-        let mut notes = Vec::new();
-        for event in events {
-            if let Ok(decrypted) = self.decrypt_event(&event) {
-                let is_read = self.get_read_status(&event.id).await;
-                notes.push(Note {
-                    id: event.id,
-                    text: decrypted,
-                    tags: extract_tags(&event),
-                    created_at: event.created_at,
-                    is_read,
-                    is_synced: true, // If it's in DB, it was synced
-                });
-            }
+        let mut notes = self.notes_from_events(events).await;
+        if notes.is_empty() && self.is_evicted(note_id) {
+            self.add_evicted(&mut notes, None, usize::MAX).await;
+            notes.retain(|note| note.id == *note_id);
         }
+        Ok(notes.into_iter().next())
+    }
 
-        // Sort by created_at descending (newest first)
-        notes.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+    /// Notes whose text or transcript contains `query` (ignoring case and
+    /// accents, see [`SearchQuery`]), newest first.
+    /// Only the most recent [`SEARCH_SCAN_LIMIT`] notes are searched.
+    pub async fn search_notes(&self, query: &str, limit: usize) -> Result<Vec<Note>> {
+        let _span = self.span("search");
+        let query = SearchQuery::new(query);
+        let notes = self.list_notes(SEARCH_SCAN_LIMIT).await?;
+        Ok(notes
+            .into_iter()
+            .filter(|note| {
+                query.matches(&note.text)
+                    || note.transcript.as_ref().is_some_and(|t| query.matches(t))
+            })
+            .take(limit)
+            .collect())
+    }
 
-        Ok(notes)
+    /// Notes of `min_words` words or more, and no more than `max_words`,
+    /// newest first, e.g. long-form only for a reading mode. Only the most
+    /// recent [`SEARCH_SCAN_LIMIT`] notes are looked through.
+    pub async fn list_notes_by_length(
+        &self,
+        min_words: usize,
+        max_words: Option<usize>,
+        limit: usize,
+    ) -> Result<Vec<Note>> {
+        let notes = self.list_notes(SEARCH_SCAN_LIMIT).await?;
+        Ok(notes
+            .into_iter()
+            .filter(|note| note.word_count >= min_words)
+            .filter(|note| max_words.is_none_or(|max| note.word_count <= max))
+            .take(limit)
+            .collect())
     }
 
-    pub async fn sync_notes(&self) -> Result<()> {
-        // Sync with relay using negentropy
-        let filter = Filter::new()
-            .author(self.keys.public_key())
-            .kind(Kind::from(1059));
+    /// Notes tagged `tag`, or any name it had before being renamed
+    /// (see [`Dialog::rename_tag`])
+    pub async fn list_by_tag(&self, tag: &str, limit: usize) -> Result<Vec<Note>> {
+        let Some(tag) = normalize_tag(tag) else {
+            return Ok(Vec::new());
+        };
+        let names = self.tag_names(&tag).await?;
+        let tagged = self.notes_filter().hashtags(names.clone()).limit(limit);
+        // Private notes have no t-tags to match, so recent ones are searched
+        let recent = self.notes_filter().limit(SEARCH_SCAN_LIMIT);
 
-        self.client.sync(filter, &SyncOptions::default()).await?;
-        Ok(())
+        let database = self.client.database();
+        let mut events: Vec<Event> = database
+            .query(vec![tagged])
+            .await
+            .map_err(|e| DialogError::Database(e.to_string()))?
+            .into_iter()
+            .collect();
+        events.extend(
+            database
+                .query(vec![recent])
+                .await
+                .map_err(|e| DialogError::Database(e.to_string()))?
+                .into_iter()
+                .filter(is_private),
+        );
+
+        let mut notes = self.notes_from_events(events).await;
+        notes.retain(|note| note.tags.iter().any(|t| names.contains(t)));
+        self.add_evicted(&mut notes, Some(&names), limit).await;
+        Ok(notes)
     }
 }
 
-fn extract_tags(event: &Event) -> Vec<String> {
+pub(crate) fn sort_newest_first(notes: &mut [Note]) {
+    sort_notes(notes, SortOrder::NewestFirst);
+}
+
+pub(crate) fn extract_tags(event: &Event) -> Vec<String> {
     event
         .tags
         .iter()
@@ -108,6 +361,7 @@ fn extract_tags(event: &Event) -> Vec<String> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_support::memory_dialog;
 
     #[test]
     fn test_extract_tags() {
@@ -133,4 +387,88 @@ mod tests {
         let extracted = extract_tags(&event);
         assert_eq!(extracted, vec!["test", "example"]);
     }
+
+    #[tokio::test]
+    async fn test_search_notes() {
+        let dialog = memory_dialog();
+        for text in ["Buy MILK", "call mom", "milkshake recipe #food"] {
+            dialog.save_note(text).await.unwrap();
+        }
+
+        let found = dialog.search_notes(" milk ", 10).await.unwrap();
+        let mut texts: Vec<&str> = found.iter().map(|n| n.text.as_str()).collect();
+        texts.sort();
+        assert_eq!(texts, vec!["Buy MILK", "milkshake recipe #food"]);
+        assert_eq!(dialog.search_notes("milk", 1).await.unwrap().len(), 1);
+        assert!(dialog.search_notes("bread", 10).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_list_notes_by_length() {
+        let dialog = memory_dialog();
+        let essay = "word ".repeat(450);
+        dialog.save_note(&format!("{essay}#essay")).await.unwrap();
+        dialog.save_note("Short one #essay #draft").await.unwrap();
+
+        let long = dialog.list_notes_by_length(300, None, 10).await.unwrap();
+        assert_eq!(long.len(), 1);
+        assert_eq!(long[0].word_count, 450);
+        assert_eq!(long[0].reading_minutes(), 3);
+        let short = dialog.list_notes_by_length(0, Some(10), 10).await.unwrap();
+        assert_eq!(short.len(), 1);
+        assert_eq!(short[0].word_count, 2);
+    }
+
+    #[tokio::test]
+    async fn test_smart_order() {
+        let dialog = memory_dialog();
+        let mut ids = Vec::new();
+        for (text, at) in [("Old", 100), ("Middle", 200), ("New", 300)] {
+            let event = dialog
+                .build_note_event_at(text, Timestamp::from(at))
+                .await
+                .unwrap();
+            ids.push(event.id);
+            dialog.save_events([event]).await.unwrap();
+        }
+        dialog.set_pinned(&ids[1], true).await.unwrap();
+        dialog.mark_as_read(&ids[2]).await.unwrap();
+
+        let texts =
+            |notes: Vec<Note>| -> Vec<String> { notes.into_iter().map(|n| n.text).collect() };
+        let smart = dialog
+            .list_notes_sorted(10, SortOrder::Smart)
+            .await
+            .unwrap();
+        assert_eq!(texts(smart), ["Middle", "Old", "New"]);
+        let newest = dialog
+            .list_notes_sorted(10, SortOrder::NewestFirst)
+            .await
+            .unwrap();
+        assert_eq!(texts(newest), ["New", "Middle", "Old"]);
+        let oldest = dialog
+            .list_notes_sorted(10, SortOrder::OldestFirst)
+            .await
+            .unwrap();
+        assert_eq!(texts(oldest), ["Old", "Middle", "New"]);
+    }
+
+    #[tokio::test]
+    async fn test_smart_order_finds_old_pinned_notes() {
+        let dialog = memory_dialog();
+        let mut ids = Vec::new();
+        for (text, at) in [("Pinned long ago", 100), ("Recent", 200), ("Newest", 300)] {
+            let event = dialog
+                .build_note_event_at(text, Timestamp::from(at))
+                .await
+                .unwrap();
+            ids.push(event.id);
+            dialog.save_events([event]).await.unwrap();
+        }
+        dialog.set_pinned(&ids[0], true).await.unwrap();
+
+        let smart = dialog.list_notes_sorted(2, SortOrder::Smart).await.unwrap();
+        let texts: Vec<&str> = smart.iter().map(|n| n.text.as_str()).collect();
+        assert_eq!(texts, ["Pinned long ago", "Newest"]);
+    }
 }
@@ -0,0 +1,111 @@
+use crate::audit::AuditAction;
+use crate::kinds::KindFeature;
+use crate::messages::gift_wrap_filter;
+use crate::{Dialog, DialogError, Result};
+use nostr_sdk::prelude::*;
+
+/// What [`Dialog::rebuild_index`] did
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RecoveryReport {
+    /// Events fetched from relays that weren't stored yet
+    pub fetched: usize,
+    /// Notes readable from the stored events
+    pub notes: usize,
+    /// Notes with read/pin/archive flags once app state was merged again
+    pub notes_with_state: usize,
+    /// Entries in the rebuilt search index
+    pub indexed: usize,
+    /// Evicted notes whose bodies turned out to be stored
+    pub evictions_cleared: usize,
+    /// Attachment cache entries whose files were missing
+    pub attachments_dropped: usize,
+}
+
+impl Dialog {
+    /// Throw away what is derived from the raw events (search index, app
+    /// state and relay info caches, eviction and attachment bookkeeping)
+    /// and rebuild it from the events in the database. With `from_relays`,
+    /// our events are fetched from the relays first, so ones missing
+    /// locally are back too.
+    ///
+    /// For when local state is wrong but the events are fine; notes and
+    /// the events themselves are never deleted. Spotlight-style clients
+    /// get a full reset on their next [`Dialog::search_index_changes`].
+    pub async fn rebuild_index(&self, from_relays: bool) -> Result<RecoveryReport> {
+        let mut report = RecoveryReport::default();
+        if from_relays {
+            let mut filters = vec![Filter::new()
+                .author(self.keys.public_key())
+                .kinds(self.synced_kinds())];
+            if self.kind_set().contains(KindFeature::Messages) {
+                filters.push(gift_wrap_filter(&self.keys));
+            }
+            let events = self
+                .client
+                .fetch_events(filters, Some(self.fetch_timeout))
+                .await?;
+            eprintln!("[lib] rebuild_index: fetched {} events", events.len());
+            report.fetched = self.save_events(events).await?;
+        }
+
+        self.relay_info.clear().await;
+        report.evictions_cleared = self.prune_evicted().await?;
+        #[cfg(feature = "attachments")]
+        {
+            report.attachments_dropped = self.attachment_cache.prune_missing()?;
+        }
+        report.notes_with_state = self.reload_app_state().await?.notes.len();
+
+        let events = self
+            .client
+            .database()
+            .query(vec![self.notes_filter()])
+            .await
+            .map_err(|e| DialogError::Database(e.to_string()))?;
+        report.notes = self.notes_from_events(events).await.len();
+        self.search_journal.reset(self.now())?;
+        report.indexed = self.search_index_changes(None).await?.entries.len();
+
+        eprintln!("[lib] rebuild_index: {report:?}");
+        let detail = format!(
+            "{} notes, {} indexed, {} fetched",
+            report.notes, report.indexed, report.fetched
+        );
+        self.audit(AuditAction::Recover, None, detail);
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::memory_dialog;
+
+    #[tokio::test]
+    async fn test_rebuild_index_from_stored_events() {
+        let dialog = memory_dialog();
+        let first = dialog.save_note("First #work").await.unwrap();
+        dialog.save_note("Second").await.unwrap();
+        dialog.set_pinned(&first.id, true).await.unwrap();
+        let before = dialog.search_index_changes(None).await.unwrap();
+        assert_eq!(before.entries.len(), 2);
+
+        let report = dialog.rebuild_index(false).await.unwrap();
+        assert_eq!(report.notes, 2);
+        assert_eq!(report.indexed, 2);
+        assert!(report.notes_with_state >= 1);
+        assert_eq!(report.fetched, 0);
+        assert!(dialog.note_state(&first.id).await.unwrap().is_pinned());
+
+        // A cursor from before the rebuild is too old to build on
+        let after = dialog
+            .search_index_changes(Some(before.cursor))
+            .await
+            .unwrap();
+        assert!(after.reset);
+        assert_eq!(after.entries.len(), 2);
+
+        // Without relays there is nothing to fetch from
+        assert!(dialog.rebuild_index(true).await.is_err());
+    }
+}
@@ -0,0 +1,175 @@
+use crate::ids::parse_note_id;
+use crate::migrate::migrated_from;
+use crate::query::SEARCH_SCAN_LIMIT;
+use crate::{Dialog, Note, Result};
+use nostr_sdk::prelude::*;
+use regex::Regex;
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::OnceLock;
+
+/// A reference in a note's text that doesn't point at one of our notes.
+/// The text is kept as written; only the reference is left unlinked.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RefWarning {
+    /// As written, e.g. `note1...`
+    pub reference: String,
+    pub problem: RefProblem,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RefProblem {
+    /// Looks like a note id but doesn't decode
+    Malformed,
+    /// Decodes, but no note of ours has that id
+    NotFound,
+}
+
+impl fmt::Display for RefWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.problem {
+            RefProblem::Malformed => write!(f, "{} is not a valid note id", self.reference),
+            RefProblem::NotFound => write!(f, "{} is not one of your notes", self.reference),
+        }
+    }
+}
+
+/// `note1...` and `nevent1...` ids in `text`, with their `nostr:` prefix if
+/// any, and where they start
+fn find_refs(text: &str) -> Vec<(usize, &str)> {
+    static REFS: OnceLock<Regex> = OnceLock::new();
+    let refs = REFS.get_or_init(|| {
+        Regex::new(r"\b(?:nostr:)?(?:note1|nevent1)[0-9a-z]+").expect("valid regex")
+    });
+    refs.find_iter(text)
+        .map(|m| (m.start(), m.as_str()))
+        .collect()
+}
+
+/// Ids of the notes `text` refers to; malformed references are skipped
+fn referenced_ids(text: &str) -> HashSet<EventId> {
+    find_refs(text)
+        .into_iter()
+        .filter_map(|(_, reference)| parse_note_id(reference).ok())
+        .collect()
+}
+
+impl Dialog {
+    /// Check the note references in `text` and write each valid one as the
+    /// `nostr:` URI of the note it resolves to (see
+    /// [`Dialog::resolve_note_ref`]), so it survives later edits. The rest
+    /// are left as written and reported.
+    ///
+    /// References live only in the encrypted text; relays never see which
+    /// notes point at which.
+    pub async fn link_note_refs(&self, text: &str) -> (String, Vec<RefWarning>) {
+        let mut linked = text.to_string();
+        let mut warnings = Vec::new();
+        // From the end, so earlier offsets stay valid
+        for (start, reference) in find_refs(text).into_iter().rev() {
+            let problem = if parse_note_id(reference).is_err() {
+                RefProblem::Malformed
+            } else {
+                match self.resolve_note_ref(reference).await {
+                    Ok(note) => match self.external_ref(&note.id).await {
+                        Ok(uri) => {
+                            linked.replace_range(start..start + reference.len(), &uri);
+                            continue;
+                        }
+                        Err(_) => RefProblem::NotFound,
+                    },
+                    Err(_) => RefProblem::NotFound,
+                }
+            };
+            warnings.push(RefWarning {
+                reference: reference.to_string(),
+                problem,
+            });
+        }
+        warnings.reverse();
+        (linked, warnings)
+    }
+
+    /// [`Dialog::create_note`] with the references in `text` linked by
+    /// [`Dialog::link_note_refs`]
+    pub async fn create_note_with_refs(&self, text: &str) -> Result<(Note, Vec<RefWarning>)> {
+        let (text, warnings) = self.link_note_refs(text).await;
+        for warning in &warnings {
+            eprintln!("[lib] create_note: {warning}");
+        }
+        Ok((self.create_note(&text).await?, warnings))
+    }
+
+    /// Notes that refer to `note_id` or to a version it replaced, newest
+    /// first. Only the most recent [`SEARCH_SCAN_LIMIT`] notes are searched.
+    pub async fn backlinks(&self, note_id: &EventId) -> Result<Vec<Note>> {
+        let mut targets = HashSet::from([*note_id]);
+        let mut id = *note_id;
+        // Bounded, in case replacements somehow form a loop
+        for _ in 0..SEARCH_SCAN_LIMIT {
+            let Some(previous) = self
+                .note_event(&id)
+                .await
+                .ok()
+                .and_then(|e| migrated_from(&e))
+            else {
+                break;
+            };
+            if !targets.insert(previous) {
+                break;
+            }
+            id = previous;
+        }
+
+        let notes = self.list_notes(SEARCH_SCAN_LIMIT).await?;
+        Ok(notes
+            .into_iter()
+            .filter(|note| {
+                !targets.contains(&note.id) && !referenced_ids(&note.text).is_disjoint(&targets)
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::memory_dialog;
+
+    #[tokio::test]
+    async fn test_references_link_and_backlink() {
+        let dialog = memory_dialog();
+        let plan = dialog.save_note("Kitchen plan").await.unwrap();
+        let report = dialog.add_tags(&[plan.id], &["home".into()]).await;
+        let (_, plan_v2) = &report.updated[0];
+
+        let old_ref = plan.id.to_bech32().unwrap();
+        let unknown = EventId::all_zeros().to_bech32().unwrap();
+        let text = format!("Follow-up to {old_ref}, see {unknown} and note1zzz");
+        let (linked, warnings) = dialog.link_note_refs(&text).await;
+        assert_eq!(
+            warnings,
+            vec![
+                RefWarning {
+                    reference: unknown.clone(),
+                    problem: RefProblem::NotFound
+                },
+                RefWarning {
+                    reference: "note1zzz".into(),
+                    problem: RefProblem::Malformed
+                },
+            ]
+        );
+        let uri = dialog.external_ref(&plan_v2.id).await.unwrap();
+        assert_eq!(
+            linked,
+            format!("Follow-up to {uri}, see {unknown} and note1zzz")
+        );
+
+        let follow_up = dialog.save_note(&linked).await.unwrap();
+        let backlinks = dialog.backlinks(&plan_v2.id).await.unwrap();
+        assert_eq!(backlinks.len(), 1);
+        assert_eq!(backlinks[0].id, follow_up.id);
+        assert!(dialog.backlinks(&follow_up.id).await.unwrap().is_empty());
+    }
+}
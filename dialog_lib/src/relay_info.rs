@@ -0,0 +1,243 @@
+use crate::{Dialog, DialogError, Result, SyncMode};
+use nostr_sdk::prelude::*;
+use serde::Deserialize;
+use std::collections::HashMap;
+#[cfg(feature = "enrich")]
+use std::time::Duration;
+
+#[cfg(feature = "enrich")]
+const NIP11_TIMEOUT: Duration = Duration::from_secs(5);
+/// How long a fetched NIP-11 document is trusted
+const CAPABILITIES_TTL_SECS: u64 = 24 * 60 * 60;
+/// Relays whose document couldn't be fetched aren't asked again for this long
+const FAILED_PROBE_TTL_SECS: u64 = 60 * 60;
+/// NIP-77: negentropy syncing
+const NEGENTROPY_NIP: u16 = 77;
+
+/// What a relay says about itself in its NIP-11 information document
+#[derive(Debug, Clone, PartialEq)]
+pub struct RelayCapabilities {
+    pub url: RelayUrl,
+    pub name: Option<String>,
+    pub software: Option<String>,
+    pub version: Option<String>,
+    pub supported_nips: Vec<u16>,
+    /// The relay wants payment before accepting events
+    pub payment_required: bool,
+    /// Largest websocket message the relay accepts, in bytes
+    pub max_message_length: Option<u64>,
+    pub fetched_at: Timestamp,
+}
+
+impl RelayCapabilities {
+    pub fn supports_nip(&self, nip: u16) -> bool {
+        self.supported_nips.contains(&nip)
+    }
+
+    /// How to sync with this relay. None when the document doesn't list
+    /// its NIPs, in which case negentropy is tried with a plain fallback.
+    pub fn sync_mode(&self) -> Option<SyncMode> {
+        if self.supported_nips.is_empty() {
+            None
+        } else if self.supports_nip(NEGENTROPY_NIP) {
+            Some(SyncMode::Negentropy)
+        } else {
+            Some(SyncMode::Plain)
+        }
+    }
+}
+
+/// The subset of NIP-11 we read
+#[derive(Debug, Default, Deserialize)]
+struct InformationDocument {
+    name: Option<String>,
+    software: Option<String>,
+    version: Option<String>,
+    #[serde(default)]
+    supported_nips: Vec<serde_json::Value>,
+    #[serde(default)]
+    limitation: Limitation,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Limitation {
+    #[serde(default)]
+    payment_required: bool,
+    max_message_length: Option<u64>,
+}
+
+/// Probe results per relay; None marks a failed probe
+#[derive(Debug, Default)]
+pub(crate) struct RelayInfoCache {
+    entries: tokio::sync::RwLock<HashMap<RelayUrl, (Timestamp, Option<RelayCapabilities>)>>,
+}
+
+impl RelayInfoCache {
+    pub(crate) async fn clear(&self) {
+        self.entries.write().await.clear();
+    }
+
+    pub(crate) async fn cached(&self) -> usize {
+        self.entries.read().await.len()
+    }
+}
+
+impl Dialog {
+    /// NIP-11 capabilities of a relay, fetched once and cached for a day
+    pub async fn relay_capabilities(&self, url: &str) -> Result<RelayCapabilities> {
+        let url = RelayUrl::parse(url).map_err(|e| DialogError::Http(e.to_string()))?;
+        let now = self.now();
+        if let Some((_, Some(capabilities))) = self.relay_info.entries.read().await.get(&url) {
+            if !is_stale(capabilities.fetched_at, CAPABILITIES_TTL_SECS, now) {
+                return Ok(capabilities.clone());
+            }
+        }
+        let result = fetch_capabilities(&url, now).await;
+        self.relay_info
+            .entries
+            .write()
+            .await
+            .insert(url, (now, result.as_ref().ok().cloned()));
+        result
+    }
+
+    /// Capabilities already fetched, without asking the relay
+    pub(crate) async fn cached_capabilities(&self, url: &RelayUrl) -> Option<RelayCapabilities> {
+        let entries = self.relay_info.entries.read().await;
+        entries
+            .get(url)
+            .and_then(|(_, capabilities)| capabilities.clone())
+    }
+
+    /// Cached capabilities for sync decisions; relays that recently failed
+    /// to answer aren't probed again
+    pub(crate) async fn probed_capabilities(&self, url: &RelayUrl) -> Option<RelayCapabilities> {
+        if let Some((probed_at, capabilities)) = self.relay_info.entries.read().await.get(url) {
+            let ttl = match capabilities {
+                Some(_) => CAPABILITIES_TTL_SECS,
+                None => FAILED_PROBE_TTL_SECS,
+            };
+            if !is_stale(*probed_at, ttl, self.now()) {
+                return capabilities.clone();
+            }
+        }
+        match self.relay_capabilities(url.as_str()).await {
+            Ok(capabilities) => Some(capabilities),
+            Err(e) => {
+                eprintln!("[lib] relay_capabilities: {url} gave no NIP-11 document: {e}");
+                None
+            }
+        }
+    }
+}
+
+fn is_stale(at: Timestamp, ttl_secs: u64, now: Timestamp) -> bool {
+    at.as_u64() + ttl_secs < now.as_u64()
+}
+
+async fn fetch_capabilities(url: &RelayUrl, now: Timestamp) -> Result<RelayCapabilities> {
+    let document = fetch_document(&nip11_url(url.as_str())).await?;
+    parse_document(url.clone(), &document, now)
+}
+
+#[cfg(feature = "enrich")]
+async fn fetch_document(url: &str) -> Result<String> {
+    let http = reqwest::Client::builder()
+        .timeout(NIP11_TIMEOUT)
+        .build()
+        .map_err(|e| DialogError::Http(e.to_string()))?;
+    http.get(url)
+        .header("Accept", "application/nostr+json")
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| DialogError::Http(e.to_string()))?
+        .text()
+        .await
+        .map_err(|e| DialogError::Http(e.to_string()))
+}
+
+/// Without the `enrich` feature there is no HTTP client to probe with
+#[cfg(not(feature = "enrich"))]
+async fn fetch_document(_url: &str) -> Result<String> {
+    Err(DialogError::Http(
+        "built without the enrich feature".to_string(),
+    ))
+}
+
+/// The document is served over HTTP(S) at the relay's own address
+fn nip11_url(relay_url: &str) -> String {
+    if let Some(rest) = relay_url.strip_prefix("wss://") {
+        format!("https://{rest}")
+    } else if let Some(rest) = relay_url.strip_prefix("ws://") {
+        format!("http://{rest}")
+    } else {
+        relay_url.to_string()
+    }
+}
+
+fn parse_document(url: RelayUrl, json: &str, fetched_at: Timestamp) -> Result<RelayCapabilities> {
+    let document: InformationDocument = serde_json::from_str(json)
+        .map_err(|e| DialogError::Http(format!("invalid NIP-11 document: {e}")))?;
+    // Some relays list NIPs as strings
+    let mut supported_nips: Vec<u16> = document
+        .supported_nips
+        .iter()
+        .filter_map(|nip| match nip {
+            serde_json::Value::Number(n) => n.as_u64().and_then(|n| u16::try_from(n).ok()),
+            serde_json::Value::String(s) => s.trim().parse().ok(),
+            _ => None,
+        })
+        .collect();
+    supported_nips.sort_unstable();
+    supported_nips.dedup();
+    Ok(RelayCapabilities {
+        url,
+        name: document.name,
+        software: document.software,
+        version: document.version,
+        supported_nips,
+        payment_required: document.limitation.payment_required,
+        max_message_length: document.limitation.max_message_length,
+        fetched_at,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_document() {
+        let url = RelayUrl::parse("wss://relay.example.com").unwrap();
+        let now = Timestamp::from(1_700_000_000);
+        let capabilities = parse_document(
+            url.clone(),
+            r#"{"name": "Example", "software": "git+https://github.com/hoytech/strfry.git",
+                "supported_nips": [1, 11, "77", 77, 9000000],
+                "limitation": {"payment_required": true, "max_message_length": 131072}}"#,
+            now,
+        )
+        .unwrap();
+        assert_eq!(capabilities.name.as_deref(), Some("Example"));
+        assert_eq!(capabilities.fetched_at, now);
+        assert_eq!(capabilities.supported_nips, vec![1, 11, 77]);
+        assert!(capabilities.payment_required);
+        assert_eq!(capabilities.max_message_length, Some(131_072));
+        assert_eq!(capabilities.sync_mode(), Some(SyncMode::Negentropy));
+
+        let plain = parse_document(url.clone(), r#"{"supported_nips": [1, 11]}"#, now).unwrap();
+        assert_eq!(plain.sync_mode(), Some(SyncMode::Plain));
+        let unknown = parse_document(url, "{}", now).unwrap();
+        assert_eq!(unknown.sync_mode(), None);
+    }
+
+    #[test]
+    fn test_nip11_url() {
+        assert_eq!(
+            nip11_url("wss://relay.example.com/"),
+            "https://relay.example.com/"
+        );
+        assert_eq!(nip11_url("ws://localhost:10548"), "http://localhost:10548");
+    }
+}
@@ -0,0 +1,154 @@
+use crate::file_store::JsonStore;
+use crate::kinds::KindFeature;
+use crate::{Dialog, DialogError, Result};
+use nostr_sdk::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+pub(crate) const RELAY_POLICY_FILE: &str = "relay_policy.json";
+
+/// Names accepted by [`Dialog::kinds_named`] besides plain kind numbers
+pub const KIND_NAMES: [&str; 8] = [
+    "notes",
+    "app-data",
+    "messages",
+    "profile",
+    "contacts",
+    "relays",
+    "public",
+    "deletions",
+];
+
+/// The event kinds a relay is sent. Relays without a policy get every
+/// event; deletion requests go to every relay regardless, so whatever was
+/// published anywhere can be deleted.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RelayPolicy {
+    pub kinds: BTreeSet<u16>,
+}
+
+impl RelayPolicy {
+    pub fn new(kinds: impl IntoIterator<Item = Kind>) -> Self {
+        Self {
+            kinds: kinds.into_iter().map(|kind| kind.as_u16()).collect(),
+        }
+    }
+
+    pub fn allows(&self, kind: Kind) -> bool {
+        kind == Kind::EventDeletion || self.kinds.contains(&kind.as_u16())
+    }
+}
+
+/// Policies by relay URL, on disk in the data directory or in memory for
+/// tests
+pub(crate) type RelayPolicies = JsonStore<BTreeMap<String, RelayPolicy>>;
+
+impl Dialog {
+    /// Only send `url` events of the kinds in `policy`, e.g. notes to a
+    /// private relay and the profile and relay list to public ones. None
+    /// sends it everything again. Applies to every publish from now on.
+    pub fn set_relay_policy(&self, url: &str, policy: Option<RelayPolicy>) -> Result<()> {
+        let url = RelayUrl::parse(url).map_err(|e| DialogError::Config(e.to_string()))?;
+        if policy
+            .as_ref()
+            .is_some_and(|policy| policy.kinds.is_empty())
+        {
+            return Err(DialogError::Config(
+                "a relay policy needs at least one kind".into(),
+            ));
+        }
+        let key = url.to_string();
+        self.relay_policies.update(|policies| {
+            match policy {
+                Some(policy) => policies.insert(key, policy),
+                None => policies.remove(&key),
+            };
+            ((), true)
+        })
+    }
+
+    /// Policies set with [`Dialog::set_relay_policy`], by relay URL
+    pub fn relay_policies(&self) -> Result<BTreeMap<String, RelayPolicy>> {
+        self.relay_policies.get()
+    }
+
+    /// Whether `url`'s policy lets it be sent an event of `kind`. Relays
+    /// without one, or whose policies can't be read, get everything.
+    pub(crate) fn relay_allows(&self, url: &RelayUrl, kind: Kind) -> bool {
+        match self.relay_policies.get() {
+            Ok(policies) => policies
+                .get(&url.to_string())
+                .is_none_or(|policy| policy.allows(kind)),
+            Err(e) => {
+                eprintln!("[lib] relay policies unavailable: {e}");
+                true
+            }
+        }
+    }
+
+    /// The kinds behind a name from [`KIND_NAMES`], or a kind number
+    pub fn kinds_named(&self, name: &str) -> Result<Vec<Kind>> {
+        let kinds = match name.trim().to_lowercase().as_str() {
+            "notes" => self.kinds_for(KindFeature::Notes),
+            "app-data" => self.kinds_for(KindFeature::AppData),
+            "messages" => self.kinds_for(KindFeature::Messages),
+            "profile" => vec![Kind::Metadata],
+            "contacts" => vec![Kind::ContactList],
+            "relays" => vec![Kind::RelayList],
+            "public" => vec![Kind::TextNote],
+            "deletions" => vec![Kind::EventDeletion],
+            other => {
+                let number = other.parse::<u16>().map_err(|_| {
+                    DialogError::Config(format!(
+                        "unknown kind {other:?}; use a number or one of {}",
+                        KIND_NAMES.join(", ")
+                    ))
+                })?;
+                vec![Kind::from(number)]
+            }
+        };
+        Ok(kinds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::memory_dialog;
+
+    #[tokio::test]
+    async fn test_relay_policy_filters_kinds() {
+        let dialog = memory_dialog();
+        let private = RelayUrl::parse("wss://private.example.com").unwrap();
+        let public = RelayUrl::parse("wss://public.example.com").unwrap();
+
+        let notes = RelayPolicy::new(dialog.kinds_named("notes").unwrap());
+        dialog
+            .set_relay_policy("wss://private.example.com", Some(notes))
+            .unwrap();
+        let mut profile = dialog.kinds_named("profile").unwrap();
+        profile.extend(dialog.kinds_named("10002").unwrap());
+        dialog
+            .set_relay_policy("wss://public.example.com/", Some(RelayPolicy::new(profile)))
+            .unwrap();
+        assert_eq!(dialog.relay_policies().unwrap().len(), 2);
+
+        let note = dialog.note_kind();
+        assert!(dialog.relay_allows(&private, note));
+        assert!(!dialog.relay_allows(&private, Kind::Metadata));
+        assert!(dialog.relay_allows(&public, Kind::RelayList));
+        assert!(!dialog.relay_allows(&public, note));
+        assert!(dialog.relay_allows(&public, Kind::EventDeletion));
+        let other = RelayUrl::parse("wss://other.example.com").unwrap();
+        assert!(dialog.relay_allows(&other, note));
+
+        assert!(dialog.kinds_named("gossip").is_err());
+        assert!(dialog
+            .set_relay_policy("wss://public.example.com", Some(RelayPolicy::default()))
+            .is_err());
+        dialog
+            .set_relay_policy("wss://public.example.com", None)
+            .unwrap();
+        assert!(dialog.relay_allows(&public, note));
+    }
+}
@@ -0,0 +1,315 @@
+use crate::audit::AuditAction;
+use crate::{normalize_tag, Dialog, DialogError, Note, Result};
+use nostr_sdk::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, watch};
+use tokio::task::JoinHandle;
+
+/// File name of the policy inside the profile's data directory
+pub(crate) const RETENTION_FILE: &str = "retention.json";
+
+/// How often the maintenance task applies the policy
+pub const RETENTION_INTERVAL: Duration = Duration::from_secs(60 * 60);
+const DAY_SECS: u64 = 24 * 60 * 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RetentionAction {
+    Archive,
+    /// Delete the note locally and ask relays to delete it (NIP-09)
+    Purge,
+}
+
+/// One rule of a [`RetentionPolicy`], e.g. "archive notes older than 365
+/// days unless pinned" or "purge #fleeting notes after 30 days"
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetentionRule {
+    /// Only notes with this tag; None for every note
+    pub tag: Option<String>,
+    pub older_than_days: u32,
+    pub action: RetentionAction,
+    /// Leave pinned (starred) notes alone
+    pub keep_pinned: bool,
+}
+
+impl RetentionRule {
+    fn applies_to(&self, note: &Note, now: Timestamp) -> bool {
+        let cutoff = now
+            .as_u64()
+            .saturating_sub(u64::from(self.older_than_days) * DAY_SECS);
+        if note.created_at.as_u64() > cutoff || (self.keep_pinned && note.is_pinned) {
+            return false;
+        }
+        if self.action == RetentionAction::Archive && note.is_archived {
+            return false;
+        }
+        match &self.tag {
+            Some(tag) => normalize_tag(tag).is_some_and(|tag| note.tags.contains(&tag)),
+            None => true,
+        }
+    }
+}
+
+/// Rules applied in order; a note is handled by the first that matches.
+/// Empty by default, so nothing is archived or purged unless asked for.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetentionPolicy {
+    pub rules: Vec<RetentionRule>,
+}
+
+/// What a retention pass did, or with `dry_run` would have done
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RetentionReport {
+    pub dry_run: bool,
+    pub archived: Vec<EventId>,
+    pub purged: Vec<EventId>,
+    pub failed: Vec<(EventId, String)>,
+}
+
+/// Running retention task; stops when dropped
+pub struct RetentionHandle {
+    task: JoinHandle<()>,
+    passes: watch::Receiver<u64>,
+    reports: mpsc::Receiver<Result<RetentionReport>>,
+}
+
+impl RetentionHandle {
+    /// Wait until the task has finished `count` passes since it started,
+    /// e.g. after moving a [`ManualClock`](crate::ManualClock) forward
+    pub async fn wait_for_passes(&mut self, count: u64) {
+        // Err means the task is gone and no more passes will come
+        let _ = self.passes.wait_for(|passes| *passes >= count).await;
+    }
+
+    /// Wait for the outcome of the next pass, e.g. to show archived notes
+    /// as such. A report not taken before the following pass ends is
+    /// dropped.
+    pub async fn next_report(&mut self) -> Option<Result<RetentionReport>> {
+        self.reports.recv().await
+    }
+}
+
+impl Drop for RetentionHandle {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+impl Dialog {
+    /// Kept in the profile's data directory, so after a restart the app
+    /// only has to call [`Dialog::start_retention`] again
+    pub fn set_retention(&self, policy: RetentionPolicy) -> Result<()> {
+        self.retention.set(policy)
+    }
+
+    pub fn retention(&self) -> RetentionPolicy {
+        // Read when the profile opens, so this only fails if that did; no
+        // rules then, so nothing is archived or purged by mistake
+        self.retention.get().unwrap_or_default()
+    }
+
+    /// Apply the retention policy to every stored note. With `dry_run`
+    /// nothing changes and the report lists what would.
+    pub async fn enforce_retention(&self, dry_run: bool) -> Result<RetentionReport> {
+        let policy = self.retention();
+        let mut report = RetentionReport {
+            dry_run,
+            ..Default::default()
+        };
+        if policy.rules.is_empty() {
+            return Ok(report);
+        }
+
+        let filter = self.notes_filter();
+        let events = self
+            .client
+            .database()
+            .query(vec![filter])
+            .await
+            .map_err(|e| DialogError::Database(e.to_string()))?;
+        let now = self.now();
+        for note in self.notes_from_events(events).await {
+            let Some(rule) = policy.rules.iter().find(|rule| rule.applies_to(&note, now)) else {
+                continue;
+            };
+            let result = match (rule.action, dry_run) {
+                (_, true) => Ok(()),
+                (RetentionAction::Archive, false) => self.set_archived(&note.id, true).await,
+                (RetentionAction::Purge, false) => self.purge_note(&note.id).await,
+            };
+            match (result, rule.action) {
+                (Ok(()), RetentionAction::Archive) => report.archived.push(note.id),
+                (Ok(()), RetentionAction::Purge) => report.purged.push(note.id),
+                (Err(e), _) => {
+                    eprintln!("[lib] retention: {} failed: {e}", note.id);
+                    report.failed.push((note.id, e.to_string()));
+                }
+            }
+        }
+        eprintln!(
+            "[lib] retention{}: {} archived, {} purged, {} failed",
+            if dry_run { " (dry run)" } else { "" },
+            report.archived.len(),
+            report.purged.len(),
+            report.failed.len()
+        );
+        Ok(report)
+    }
+
+    /// Apply the policy now and then every hour until the handle is dropped.
+    /// Changes to the policy take effect on the next pass.
+    pub fn start_retention(self: Arc<Self>) -> RetentionHandle {
+        let (passed, passes) = watch::channel(0);
+        let (report_tx, reports) = mpsc::channel(1);
+        let task = tokio::spawn(async move {
+            loop {
+                let result = self.enforce_retention(false).await;
+                if let Err(e) = &result {
+                    eprintln!("[lib] retention: pass failed: {e}");
+                }
+                // Subscribe before the handle's waiter can see this pass, so
+                // a clock moved right after it still wakes the sleep
+                let sleep = self.clock.sleep(RETENTION_INTERVAL);
+                // Full while the last report hasn't been taken
+                let _ = report_tx.try_send(result);
+                passed.send_modify(|passes| *passes += 1);
+                sleep.await;
+            }
+        });
+        RetentionHandle {
+            task,
+            passes,
+            reports,
+        }
+    }
+
+    /// Remove a note from the local database, where it stays removed even
+    /// if a relay still serves it, then ask relays to delete it
+    async fn purge_note(&self, note_id: &EventId) -> Result<()> {
+        self.client
+            .database()
+            .delete(Filter::new().id(*note_id))
+            .await
+            .map_err(|e| DialogError::Database(e.to_string()))?;
+        if let Err(e) = self.client.delete_event(*note_id).await {
+            eprintln!("[lib] retention: deletion request for {note_id} not sent: {e}");
+        }
+        self.audit(AuditAction::Purge, Some(note_id), "retention");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::memory_dialog;
+
+    #[tokio::test]
+    async fn test_enforce_retention() {
+        let dialog = memory_dialog();
+        let now = Timestamp::now().as_u64();
+        let mut events = Vec::new();
+        for (text, age_days) in [
+            ("Old plan", 400),
+            ("Old but starred", 401),
+            ("Passing thought #fleeting", 45),
+            ("Fresh thought #fleeting", 2),
+        ] {
+            let created_at = Timestamp::from(now - age_days * DAY_SECS);
+            events.push(dialog.build_note_event_at(text, created_at).await.unwrap());
+        }
+        dialog.save_events(events.clone()).await.unwrap();
+        dialog.set_pinned(&events[1].id, true).await.unwrap();
+
+        dialog
+            .set_retention(RetentionPolicy {
+                rules: vec![
+                    RetentionRule {
+                        tag: Some("#fleeting".into()),
+                        older_than_days: 30,
+                        action: RetentionAction::Purge,
+                        keep_pinned: false,
+                    },
+                    RetentionRule {
+                        tag: None,
+                        older_than_days: 365,
+                        action: RetentionAction::Archive,
+                        keep_pinned: true,
+                    },
+                ],
+            })
+            .unwrap();
+
+        let preview = dialog.enforce_retention(true).await.unwrap();
+        assert_eq!(preview.archived, vec![events[0].id]);
+        assert_eq!(preview.purged, vec![events[2].id]);
+        assert_eq!(dialog.list_notes(10).await.unwrap().len(), 4);
+
+        let report = dialog.enforce_retention(false).await.unwrap();
+        assert!(!report.dry_run);
+        assert!(report.failed.is_empty());
+        let notes = dialog.list_notes(10).await.unwrap();
+        let texts: Vec<&str> = notes.iter().map(|n| n.text.as_str()).collect();
+        assert_eq!(
+            texts,
+            vec!["Fresh thought #fleeting", "Old plan", "Old but starred"]
+        );
+        assert!(notes[1].is_archived);
+        assert!(!notes[2].is_archived);
+
+        let again = dialog.enforce_retention(false).await.unwrap();
+        assert_eq!(again, RetentionReport::default());
+    }
+
+    #[tokio::test]
+    async fn test_purge_on_nostrdb() {
+        let dir = std::env::temp_dir().join(format!("dialog-retention-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let keys = Keys::generate();
+        let open = || {
+            Dialog::builder()
+                .signer(keys.clone())
+                .data_dir(&dir)
+                .build()
+        };
+
+        let dialog = open().await.unwrap();
+        let created_at = Timestamp::from(Timestamp::now().as_u64() - 45 * DAY_SECS);
+        let event = dialog
+            .build_note_event_at("Passing thought #fleeting", created_at)
+            .await
+            .unwrap();
+        dialog.save_events(vec![event.clone()]).await.unwrap();
+        // nostrdb ingests in the background
+        while dialog.list_notes(10).await.unwrap().is_empty() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        dialog
+            .set_retention(RetentionPolicy {
+                rules: vec![RetentionRule {
+                    tag: Some("#fleeting".into()),
+                    older_than_days: 30,
+                    action: RetentionAction::Purge,
+                    keep_pinned: false,
+                }],
+            })
+            .unwrap();
+
+        let report = dialog.enforce_retention(false).await.unwrap();
+        assert_eq!(report.purged, vec![event.id]);
+        assert!(report.failed.is_empty());
+        assert!(dialog.list_notes(10).await.unwrap().is_empty());
+        drop(dialog);
+
+        // Still gone after reopening, even when a sync offers it again, and
+        // the policy is still there to keep it that way
+        let dialog = open().await.unwrap();
+        assert_eq!(dialog.retention().rules.len(), 1);
+        assert_eq!(dialog.save_events(vec![event]).await.unwrap(), 0);
+        assert!(dialog.list_notes(10).await.unwrap().is_empty());
+        drop(dialog);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,89 @@
+use crate::app_state::Lww;
+use crate::{Dialog, DialogError, Note, Result};
+use nostr_sdk::prelude::*;
+
+/// Notes not opened for this long come up for review
+pub const REVIEW_AFTER_DAYS: u64 = 30;
+const DAY_SECS: u64 = 24 * 60 * 60;
+
+impl Dialog {
+    /// Remember that a note was just opened. Views sync across devices
+    /// with the rest of the app state.
+    pub async fn record_view(&self, note_id: &EventId) -> Result<()> {
+        self.update_note_state(note_id, |state, now| {
+            state.viewed = Some(Lww::new(now, now))
+        })
+        .await
+    }
+
+    /// Up to `limit` notes not opened in [`REVIEW_AFTER_DAYS`] days,
+    /// longest-forgotten first. Notes never opened count from when they
+    /// were written; archived notes are left out.
+    pub async fn review_queue(&self, limit: usize) -> Result<Vec<Note>> {
+        let filter = self.notes_filter();
+        let events = self
+            .client
+            .database()
+            .query(vec![filter])
+            .await
+            .map_err(|e| DialogError::Database(e.to_string()))?;
+        let state = self.app_state().await?;
+        let cutoff = self
+            .now()
+            .as_u64()
+            .saturating_sub(REVIEW_AFTER_DAYS * DAY_SECS);
+
+        let mut due: Vec<(Timestamp, Note)> = self
+            .notes_from_events(events)
+            .await
+            .into_iter()
+            .filter(|note| !note.is_archived)
+            .filter_map(|note| {
+                let seen = state
+                    .note(&note.id)
+                    .last_viewed()
+                    .unwrap_or(note.created_at);
+                (seen.as_u64() < cutoff).then_some((seen, note))
+            })
+            .collect();
+        due.sort_by_key(|(seen, note)| (*seen, note.id));
+        Ok(due.into_iter().take(limit).map(|(_, note)| note).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::memory_dialog;
+
+    #[tokio::test]
+    async fn test_review_queue() {
+        let dialog = memory_dialog();
+        let now = Timestamp::now().as_u64();
+        let mut events = Vec::new();
+        for (text, days_ago) in [("Oldest", 90), ("Older", 60), ("Archived", 70)] {
+            let at = Timestamp::from(now - days_ago * DAY_SECS);
+            events.push(dialog.build_note_event_at(text, at).await.unwrap());
+        }
+        let ids: Vec<EventId> = events.iter().map(|e| e.id).collect();
+        dialog.save_events(events).await.unwrap();
+        dialog.set_archived(&ids[2], true).await.unwrap();
+        dialog.save_note("Fresh").await.unwrap();
+
+        let texts = |notes: Vec<Note>| notes.into_iter().map(|n| n.text).collect::<Vec<_>>();
+        assert_eq!(
+            texts(dialog.review_queue(10).await.unwrap()),
+            vec!["Oldest", "Older"]
+        );
+        assert_eq!(texts(dialog.review_queue(1).await.unwrap()), vec!["Oldest"]);
+
+        dialog.record_view(&ids[0]).await.unwrap();
+        assert_eq!(texts(dialog.review_queue(10).await.unwrap()), vec!["Older"]);
+        assert!(dialog
+            .note_state(&ids[0])
+            .await
+            .unwrap()
+            .last_viewed()
+            .is_some());
+    }
+}
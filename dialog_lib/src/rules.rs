@@ -0,0 +1,206 @@
+use crate::tags::{add_hashtags, normalize_tag, parse_hashtags};
+use crate::{Dialog, DialogError, Result, SearchQuery};
+use nostr_sdk::prelude::*;
+use regex::RegexBuilder;
+use serde::{Deserialize, Serialize};
+
+/// How a [`TagRule`] looks for its pattern in a note
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleMatch {
+    /// The text contains the pattern, ignoring case
+    Keyword,
+    /// The pattern is a regular expression, matched ignoring case
+    Regex,
+}
+
+/// Adds `tag` to notes matching `pattern`, e.g. anything containing
+/// "invoice" gets #finance
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct TagRule {
+    pub pattern: String,
+    #[serde(rename = "match")]
+    pub matcher: RuleMatch,
+    /// Without the leading '#'
+    pub tag: String,
+}
+
+/// What [`Dialog::apply_rules_to_notes`] did, or with `dry_run` would do
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RulesReport {
+    pub dry_run: bool,
+    /// Notes (by their id before retagging) and the tags they gained
+    pub tagged: Vec<(EventId, Vec<String>)>,
+    pub failed: Vec<(EventId, String)>,
+}
+
+impl TagRule {
+    /// Checks the pattern and normalizes the tag
+    pub fn new(pattern: &str, matcher: RuleMatch, tag: &str) -> Result<Self> {
+        let tag = normalize_tag(tag)
+            .ok_or_else(|| DialogError::Config(format!("invalid tag for rule: {tag}")))?;
+        if pattern.trim().is_empty() {
+            return Err(DialogError::Config("rule pattern is empty".into()));
+        }
+        let rule = Self {
+            pattern: pattern.to_string(),
+            matcher,
+            tag,
+        };
+        if matcher == RuleMatch::Regex {
+            rule.regex()?;
+        }
+        Ok(rule)
+    }
+
+    pub fn matches(&self, text: &str) -> bool {
+        match self.matcher {
+            RuleMatch::Keyword => SearchQuery::new(&self.pattern).matches(text),
+            RuleMatch::Regex => self.regex().is_ok_and(|re| re.is_match(text)),
+        }
+    }
+
+    fn regex(&self) -> Result<regex::Regex> {
+        RegexBuilder::new(&self.pattern)
+            .case_insensitive(true)
+            .build()
+            .map_err(|e| DialogError::Config(format!("invalid rule pattern: {e}")))
+    }
+}
+
+/// Tags from `rules` that match `text` and aren't in it yet
+fn matching_tags(rules: &[TagRule], text: &str) -> Vec<String> {
+    let existing = parse_hashtags(text);
+    let mut tags: Vec<String> = Vec::new();
+    for rule in rules {
+        if rule.matches(text) && !existing.contains(&rule.tag) && !tags.contains(&rule.tag) {
+            tags.push(rule.tag.clone());
+        }
+    }
+    tags
+}
+
+impl Dialog {
+    /// Auto-tagging rules, synced with the rest of the app state
+    pub async fn tag_rules(&self) -> Result<Vec<TagRule>> {
+        Ok(self
+            .app_state()
+            .await?
+            .tag_rules
+            .map(|rules| rules.value)
+            .unwrap_or_default())
+    }
+
+    /// Replace the rules. Each is checked as in [`TagRule::new`].
+    pub async fn set_tag_rules(&self, rules: Vec<TagRule>) -> Result<()> {
+        let rules = rules
+            .into_iter()
+            .map(|rule| TagRule::new(&rule.pattern, rule.matcher, &rule.tag))
+            .collect::<Result<Vec<_>>>()?;
+        self.store_tag_rules(rules).await
+    }
+
+    /// Tags the rules would add to `text`; empty if none match or the rules
+    /// can't be read
+    pub async fn apply_rules(&self, text: &str) -> Vec<String> {
+        match self.tag_rules().await {
+            Ok(rules) => matching_tags(&rules, text),
+            Err(e) => {
+                eprintln!("[lib] apply_rules: rules unavailable: {e}");
+                Vec::new()
+            }
+        }
+    }
+
+    /// `text` with the hashtags from [`Dialog::apply_rules`] appended
+    pub(crate) async fn auto_tag(&self, text: &str) -> String {
+        add_hashtags(text, &self.apply_rules(text).await)
+    }
+
+    /// Run the rules over every stored note. Matching notes are retagged
+    /// like [`Dialog::add_tags`] does; with `dry_run` nothing changes and
+    /// the report lists what would. Locked notes are skipped.
+    pub async fn apply_rules_to_notes(&self, dry_run: bool) -> Result<RulesReport> {
+        let rules = self.tag_rules().await?;
+        let filter = self.notes_filter();
+        let events = self
+            .client
+            .database()
+            .query(vec![filter])
+            .await
+            .map_err(|e| DialogError::Database(e.to_string()))?;
+
+        let mut report = RulesReport {
+            dry_run,
+            ..Default::default()
+        };
+        for note in self.notes_from_events(events).await {
+            if note.is_locked {
+                continue;
+            }
+            let tags = matching_tags(&rules, &note.text);
+            if !tags.is_empty() {
+                report.tagged.push((note.id, tags));
+            }
+        }
+        if dry_run || report.tagged.is_empty() {
+            return Ok(report);
+        }
+
+        let ids: Vec<EventId> = report.tagged.iter().map(|(id, _)| *id).collect();
+        let retagged = self
+            .retag(&ids, |text| {
+                add_hashtags(text, &matching_tags(&rules, text))
+            })
+            .await;
+        report
+            .tagged
+            .retain(|(id, _)| retagged.updated.iter().any(|(old, _)| old == id));
+        report.failed = retagged.failed;
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::memory_dialog;
+
+    #[test]
+    fn test_rules_match() {
+        let invoice = TagRule::new("Invoice", RuleMatch::Keyword, "#Finance").unwrap();
+        assert_eq!(invoice.tag, "finance");
+        let call = TagRule::new(r"\bcall \w+", RuleMatch::Regex, "todo").unwrap();
+        let rules = vec![invoice, call];
+
+        assert_eq!(
+            matching_tags(&rules, "Pay the INVOICE and call Bob"),
+            vec!["finance", "todo"]
+        );
+        assert_eq!(
+            matching_tags(&rules, "invoice sent #finance"),
+            Vec::<String>::new()
+        );
+        assert!(matching_tags(&rules, "recall").is_empty());
+        assert!(TagRule::new("(", RuleMatch::Regex, "broken").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_rules_tag_new_and_existing_notes() {
+        let dialog = memory_dialog();
+        let old = dialog.save_note("Invoice from the plumber").await.unwrap();
+        dialog
+            .set_tag_rules(vec![
+                TagRule::new("invoice", RuleMatch::Keyword, "finance").unwrap()
+            ])
+            .await
+            .unwrap();
+
+        let text = dialog.auto_tag("Another invoice").await;
+        assert_eq!(text, "Another invoice\n#finance");
+
+        let preview = dialog.apply_rules_to_notes(true).await.unwrap();
+        assert_eq!(preview.tagged, vec![(old.id, vec!["finance".to_string()])]);
+        assert!(dialog.list_by_tag("finance", 10).await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,43 @@
+use crate::Dialog;
+use std::sync::atomic::Ordering;
+
+/// Sizes of what a long-running client keeps in memory, for spotting leaks
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RuntimeStats {
+    /// Relay subscriptions open in the client's pool
+    pub subscriptions: usize,
+    /// Named watches running
+    pub watches: usize,
+    /// Event ids all running watches remember to drop duplicates
+    pub seen_ids: usize,
+    /// Notes unlocked this session, held decrypted
+    pub unlocked_notes: usize,
+    /// Relays with NIP-11 documents cached
+    pub relay_info_cached: usize,
+}
+
+impl Dialog {
+    /// Cheap to call; nothing is read from disk or relays
+    pub async fn runtime_stats(&self) -> RuntimeStats {
+        RuntimeStats {
+            subscriptions: self.client.subscriptions().await.len(),
+            watches: self.watches().len(),
+            seen_ids: self.watches.seen_ids.load(Ordering::Relaxed),
+            unlocked_notes: self.unlocked.read().unwrap().len(),
+            relay_info_cached: self.relay_info.cached().await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::memory_dialog;
+
+    #[tokio::test]
+    async fn test_runtime_stats_of_idle_dialog() {
+        let dialog = memory_dialog();
+        dialog.save_note("Nothing running").await.unwrap();
+        assert_eq!(dialog.runtime_stats().await, RuntimeStats::default());
+    }
+}
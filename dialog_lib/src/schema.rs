@@ -0,0 +1,164 @@
+use crate::file_store::JsonStore;
+use crate::{Dialog, DialogError, Result};
+use std::future::Future;
+use std::pin::Pin;
+
+/// Next to the database; holds the local storage schema version
+pub(crate) const SCHEMA_VERSION_FILE: &str = "schema_version";
+
+type SchemaStepFuture<'a> = Pin<Box<dyn Future<Output = Result<()>> + Send + 'a>>;
+
+/// One forward migration of local storage, run once per installation
+struct SchemaStep {
+    name: &'static str,
+    run: for<'a> fn(&'a Dialog) -> SchemaStepFuture<'a>,
+}
+
+/// Every step in order; step `n` (from 1) upgrades version `n - 1` to `n`.
+/// Only ever append: installations record how far down the list they are.
+const SCHEMA_STEPS: &[SchemaStep] = &[SchemaStep {
+    name: "baseline",
+    run: baseline,
+}];
+
+/// Storage as it was before versioning; nothing to change
+fn baseline(_: &Dialog) -> SchemaStepFuture<'_> {
+    Box::pin(async { Ok(()) })
+}
+
+/// Local storage version this build writes
+pub const SCHEMA_VERSION: u32 = SCHEMA_STEPS.len() as u32;
+
+/// Reported before each step of [`Dialog::migrate_schema_with_progress`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SchemaProgress {
+    /// Version the step upgrades to
+    pub version: u32,
+    /// Version the migration ends at
+    pub target: u32,
+    pub name: &'static str,
+}
+
+/// Outcome of [`Dialog::migrate_schema`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SchemaReport {
+    pub from: u32,
+    pub to: u32,
+    /// Names of the steps run, in order
+    pub applied: Vec<&'static str>,
+}
+
+/// The stored version, on disk in the data directory or in memory for tests.
+/// A bare number, so it reads the same as before it went through JSON.
+pub(crate) type SchemaVersion = JsonStore<u32>;
+
+impl Dialog {
+    /// Version local storage is at; 0 before the first migration
+    pub fn schema_version(&self) -> Result<u32> {
+        self.schema_version.get()
+    }
+
+    /// Bring local storage up to [`SCHEMA_VERSION`]. Run by
+    /// [`DialogBuilder::build`](crate::DialogBuilder::build) unless
+    /// deferred.
+    pub async fn migrate_schema(&self) -> Result<SchemaReport> {
+        self.migrate_schema_with_progress(|progress| {
+            eprintln!(
+                "[lib] migrate_schema: {}/{} {}",
+                progress.version, progress.target, progress.name
+            )
+        })
+        .await
+    }
+
+    /// Like [`Dialog::migrate_schema`], calling `progress` before each
+    /// step. The version is saved after every step, so an interrupted
+    /// migration resumes at the step that didn't finish.
+    pub async fn migrate_schema_with_progress(
+        &self,
+        progress: impl FnMut(SchemaProgress),
+    ) -> Result<SchemaReport> {
+        self.run_schema_steps(SCHEMA_STEPS, progress).await
+    }
+
+    async fn run_schema_steps(
+        &self,
+        steps: &[SchemaStep],
+        mut progress: impl FnMut(SchemaProgress),
+    ) -> Result<SchemaReport> {
+        let from = self.schema_version()?;
+        let target = steps.len() as u32;
+        if from > target {
+            return Err(DialogError::Database(format!(
+                "local storage is at schema version {from}, newer than this build's {target}"
+            )));
+        }
+        let mut report = SchemaReport {
+            from,
+            to: from,
+            applied: Vec::new(),
+        };
+        for (version, step) in (1..).zip(steps).skip(from as usize) {
+            progress(SchemaProgress {
+                version,
+                target,
+                name: step.name,
+            });
+            (step.run)(self).await.map_err(|e| {
+                DialogError::Database(format!("schema step {version} ({}): {e}", step.name))
+            })?;
+            self.schema_version.set(version)?;
+            report.to = version;
+            report.applied.push(step.name);
+        }
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::memory_dialog;
+
+    fn broken(_: &Dialog) -> SchemaStepFuture<'_> {
+        Box::pin(async { Err(DialogError::Cancelled) })
+    }
+
+    #[tokio::test]
+    async fn test_schema_steps_resume_after_failure() {
+        let dialog = memory_dialog();
+        let mut seen = Vec::new();
+        let steps = [
+            SchemaStep {
+                name: "first",
+                run: baseline,
+            },
+            SchemaStep {
+                name: "second",
+                run: broken,
+            },
+        ];
+        let failed = dialog
+            .run_schema_steps(&steps, |p| seen.push((p.version, p.name)))
+            .await;
+        assert!(failed.is_err());
+        assert_eq!(seen, vec![(1, "first"), (2, "second")]);
+        // The finished step stays done
+        assert_eq!(dialog.schema_version().unwrap(), 1);
+
+        let fixed = [
+            SchemaStep {
+                name: "first",
+                run: broken,
+            },
+            SchemaStep {
+                name: "second",
+                run: baseline,
+            },
+        ];
+        let report = dialog.run_schema_steps(&fixed, |_| {}).await.unwrap();
+        assert_eq!((report.from, report.to), (1, 2));
+        assert_eq!(report.applied, vec!["second"]);
+        assert!(dialog.migrate_schema().await.is_err());
+    }
+}
@@ -0,0 +1,282 @@
+use crate::file_store::JsonStore;
+use crate::ics::summary;
+use crate::{Dialog, DialogError, Note, Result};
+use nostr_sdk::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+/// File name of the search journal inside the profile's data directory
+pub(crate) const SEARCH_JOURNAL_FILE: &str = "search_journal.json";
+const MAX_TITLE_CHARS: usize = 80;
+/// How long removals are remembered. Clients that last synced before that
+/// get a full reset.
+const TOMBSTONE_TTL_SECS: u64 = 90 * 24 * 60 * 60;
+
+/// A note as a system search index (e.g. Core Spotlight) needs it: a short
+/// title instead of the whole body
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchIndexEntry {
+    pub id: EventId,
+    pub title: String,
+    pub tags: Vec<String>,
+    pub created_at: Timestamp,
+    /// When this entry last changed as far as this device knows
+    pub modified_at: Timestamp,
+}
+
+/// What changed in the index since a cursor from an earlier call
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SearchIndexChanges {
+    /// Entries to add or replace
+    pub entries: Vec<SearchIndexEntry>,
+    /// Entries to drop: deleted, replaced by a copy, or locked notes
+    pub removed: Vec<EventId>,
+    /// The cursor was unknown or too old; drop everything indexed before
+    /// applying `entries`, which then hold every note
+    pub reset: bool,
+    /// Pass to the next call
+    pub cursor: Timestamp,
+}
+
+/// What was last reported for each note. Only a hash of each entry is
+/// kept, so the journal holds no note text.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub(crate) struct Journal {
+    /// Changes before this time are not known
+    history_from: u64,
+    /// Keyed by note id (hex)
+    notes: BTreeMap<String, JournalNote>,
+    /// Removal time by note id (hex)
+    removed: BTreeMap<String, u64>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct JournalNote {
+    fingerprint: u64,
+    changed_at: u64,
+}
+
+/// The journal, on disk in the data directory or in memory for tests
+pub(crate) type SearchJournal = JsonStore<Journal>;
+
+impl SearchJournal {
+    /// Forget everything reported. Cursors handed out up to `now`, even
+    /// within the same second, get a full reset.
+    pub(crate) fn reset(&self, now: Timestamp) -> Result<()> {
+        self.set(Journal {
+            history_from: now.as_u64() + 1,
+            ..Default::default()
+        })
+    }
+
+    /// Record the current entries and return the changes since `since`
+    fn record(
+        &self,
+        entries: Vec<SearchIndexEntry>,
+        since: Option<Timestamp>,
+        now: Timestamp,
+    ) -> Result<SearchIndexChanges> {
+        self.update(|journal| (record_in(journal, entries, since, now), true))
+    }
+}
+
+fn record_in(
+    journal: &mut Journal,
+    entries: Vec<SearchIndexEntry>,
+    since: Option<Timestamp>,
+    now: Timestamp,
+) -> SearchIndexChanges {
+    let now_secs = now.as_u64();
+    if journal.history_from == 0 {
+        journal.history_from = now_secs;
+    }
+
+    let mut current = HashSet::new();
+    let mut entries: Vec<SearchIndexEntry> = entries
+        .into_iter()
+        .map(|mut entry| {
+            let id = entry.id.to_hex();
+            let fingerprint = fingerprint(&entry);
+            let known = journal.notes.get(&id).copied();
+            let changed_at = match known {
+                Some(known) if known.fingerprint == fingerprint => known.changed_at,
+                _ => now_secs,
+            };
+            journal.notes.insert(
+                id.clone(),
+                JournalNote {
+                    fingerprint,
+                    changed_at,
+                },
+            );
+            journal.removed.remove(&id);
+            current.insert(id);
+            entry.modified_at = Timestamp::from(changed_at);
+            entry
+        })
+        .collect();
+
+    let gone: Vec<String> = journal
+        .notes
+        .keys()
+        .filter(|id| !current.contains(*id))
+        .cloned()
+        .collect();
+    for id in gone {
+        journal.notes.remove(&id);
+        journal.removed.insert(id, now_secs);
+    }
+    let cutoff = now_secs.saturating_sub(TOMBSTONE_TTL_SECS);
+    if journal.removed.values().any(|at| *at < cutoff) {
+        journal.removed.retain(|_, at| *at >= cutoff);
+        journal.history_from = journal.history_from.max(cutoff);
+    }
+
+    let since = since
+        .map(|since| since.as_u64())
+        .filter(|since| *since >= journal.history_from);
+    let reset = since.is_none();
+    let mut removed = Vec::new();
+    if let Some(since) = since {
+        // Same-second changes are repeated rather than risk missing one
+        entries.retain(|entry| entry.modified_at.as_u64() >= since);
+        removed = journal
+            .removed
+            .iter()
+            .filter(|(_, at)| **at >= since)
+            .filter_map(|(id, _)| EventId::from_hex(id).ok())
+            .collect();
+    }
+    SearchIndexChanges {
+        entries,
+        removed,
+        reset,
+        cursor: now,
+    }
+}
+
+fn fingerprint(entry: &SearchIndexEntry) -> u64 {
+    // Fixed keys, so the same entry hashes the same across runs
+    let mut hasher = DefaultHasher::new();
+    (&entry.title, &entry.tags, entry.created_at.as_u64()).hash(&mut hasher);
+    hasher.finish()
+}
+
+fn index_entry(note: &Note) -> SearchIndexEntry {
+    SearchIndexEntry {
+        id: note.id,
+        title: summary(&note.text, MAX_TITLE_CHARS),
+        tags: note.tags.clone(),
+        created_at: note.created_at,
+        modified_at: note.created_at,
+    }
+}
+
+impl Dialog {
+    /// Search index entries that changed since `since`, the cursor returned
+    /// by the previous call; None for everything. Locked notes are never
+    /// indexed, even while unlocked.
+    pub async fn search_index_changes(
+        &self,
+        since: Option<Timestamp>,
+    ) -> Result<SearchIndexChanges> {
+        let filter = self.notes_filter();
+        let events = self
+            .client
+            .database()
+            .query(vec![filter])
+            .await
+            .map_err(|e| DialogError::Database(e.to_string()))?;
+        let entries = self
+            .notes_from_events(events)
+            .await
+            .iter()
+            .filter(|note| !note.is_locked)
+            .map(index_entry)
+            .collect();
+        self.search_journal.record(entries, since, self.now())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(id: u8, title: &str) -> SearchIndexEntry {
+        SearchIndexEntry {
+            id: EventId::from_hex(&format!("{id:02x}").repeat(32)).unwrap(),
+            title: title.into(),
+            tags: Vec::new(),
+            created_at: Timestamp::from(1_000),
+            modified_at: Timestamp::from(1_000),
+        }
+    }
+
+    #[test]
+    fn test_search_journal() {
+        let journal = SearchJournal::default();
+        let t = Timestamp::from;
+
+        let first = journal
+            .record(vec![entry(1, "a"), entry(2, "b")], None, t(10_000))
+            .unwrap();
+        assert!(first.reset);
+        assert_eq!(first.entries.len(), 2);
+        assert_eq!(first.entries[0].modified_at, t(10_000));
+
+        let second = journal
+            .record(
+                vec![entry(1, "a"), entry(2, "b2")],
+                Some(t(first.cursor.as_u64() + 1)),
+                t(10_050),
+            )
+            .unwrap();
+        assert!(!second.reset);
+        assert_eq!(
+            second.entries,
+            vec![SearchIndexEntry {
+                modified_at: t(10_050),
+                ..entry(2, "b2")
+            }]
+        );
+        assert!(second.removed.is_empty());
+
+        let third = journal
+            .record(
+                vec![entry(2, "b2")],
+                Some(t(second.cursor.as_u64() + 1)),
+                t(10_100),
+            )
+            .unwrap();
+        assert!(third.entries.is_empty());
+        assert_eq!(third.removed, vec![entry(1, "").id]);
+
+        let stale = journal
+            .record(vec![entry(2, "b2")], Some(t(5_000)), t(10_200))
+            .unwrap();
+        assert!(
+            stale.reset,
+            "changes before the journal started are unknown"
+        );
+        assert_eq!(stale.entries.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_search_index_skips_locked_notes() {
+        let dialog = crate::test_support::memory_dialog();
+        dialog
+            .save_note("Groceries\nmilk, eggs #home")
+            .await
+            .unwrap();
+        dialog
+            .create_locked_note("Secret plans", "hunter2")
+            .await
+            .unwrap();
+
+        let changes = dialog.search_index_changes(None).await.unwrap();
+        assert_eq!(changes.entries.len(), 1);
+        assert_eq!(changes.entries[0].title, "Groceries");
+        assert_eq!(changes.entries[0].tags, vec!["home"]);
+    }
+}
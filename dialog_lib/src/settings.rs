@@ -0,0 +1,191 @@
+use crate::app_state::{Lww, TagMetadata};
+use crate::relay_policy::RelayPolicy;
+use crate::retention::{RetentionPolicy, RetentionRule};
+use crate::rules::TagRule;
+use crate::{Dialog, DialogError, Result};
+use nostr_sdk::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+const SETTINGS_VERSION: u32 = 1;
+
+/// Everything about a setup that isn't notes, as moved between devices by
+/// [`Dialog::export_settings`] and [`Dialog::import_settings`]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Settings {
+    pub version: u32,
+    #[serde(default)]
+    pub relays: Vec<String>,
+    /// Kept with their timestamps so importing never overrides newer edits
+    #[serde(default)]
+    pub tags: BTreeMap<String, Lww<TagMetadata>>,
+    /// Name to query, as passed to [`Dialog::search_notes`]
+    #[serde(default)]
+    pub saved_searches: BTreeMap<String, String>,
+    #[serde(default)]
+    pub retention: Vec<RetentionRule>,
+    #[serde(default)]
+    pub tag_rules: Vec<TagRule>,
+    /// Kinds each relay is sent, by URL; see [`Dialog::set_relay_policy`]
+    #[serde(default)]
+    pub relay_policies: BTreeMap<String, RelayPolicy>,
+    /// See [`Dialog::set_max_privacy`]
+    #[serde(default)]
+    pub max_privacy: bool,
+}
+
+impl Dialog {
+    /// Remember `query` under `name`, replacing any search with that name
+    pub fn save_search(&self, name: &str, query: &str) {
+        self.saved_searches
+            .write()
+            .unwrap()
+            .insert(name.to_string(), query.to_string());
+    }
+
+    /// Returns false if no search had that name
+    pub fn remove_saved_search(&self, name: &str) -> bool {
+        self.saved_searches.write().unwrap().remove(name).is_some()
+    }
+
+    pub fn saved_searches(&self) -> BTreeMap<String, String> {
+        self.saved_searches.read().unwrap().clone()
+    }
+
+    /// Relays and their policies, tag metadata, saved searches, retention,
+    /// auto-tagging rules and max privacy as one blob encrypted to this key (NIP-44). Only the same key
+    /// can import it.
+    pub async fn export_settings(&self) -> Result<String> {
+        let mut relays: Vec<String> = self
+            .client
+            .relays()
+            .await
+            .into_keys()
+            .map(|url| url.to_string())
+            .collect();
+        relays.sort();
+        let state = self.app_state().await?;
+        let settings = Settings {
+            version: SETTINGS_VERSION,
+            relays,
+            tags: state.tags,
+            saved_searches: self.saved_searches(),
+            retention: self.retention().rules,
+            tag_rules: state.tag_rules.map(|rules| rules.value).unwrap_or_default(),
+            relay_policies: self.relay_policies()?,
+            max_privacy: self.max_privacy(),
+        };
+        let json =
+            serde_json::to_string(&settings).map_err(|e| DialogError::Import(e.to_string()))?;
+        Ok(nip44::encrypt(
+            self.keys.secret_key(),
+            &self.keys.public_key(),
+            json,
+            nip44::Version::default(),
+        )?)
+    }
+
+    /// Restore a blob from [`Dialog::export_settings`]. Relays are added to
+    /// the ones already configured, their policies replace any set here, tag metadata merges last-write-wins,
+    /// saved searches with the same name are replaced, a non-empty
+    /// retention policy replaces the current one and auto-tagging rules not
+    /// already set are added. Max privacy is turned on if it was on in the
+    /// export; an import never turns it off.
+    pub async fn import_settings(&self, blob: &str) -> Result<Settings> {
+        let json = nip44::decrypt(self.keys.secret_key(), &self.keys.public_key(), blob.trim())
+            .map_err(|_| DialogError::Import("settings were exported with another key".into()))?;
+        let settings: Settings =
+            serde_json::from_str(&json).map_err(|e| DialogError::Import(e.to_string()))?;
+        if settings.version > SETTINGS_VERSION {
+            return Err(DialogError::Import(format!(
+                "settings version {} is newer than this app supports",
+                settings.version
+            )));
+        }
+
+        for url in &settings.relays {
+            self.connect_relay(url).await?;
+        }
+        for (url, policy) in &settings.relay_policies {
+            self.set_relay_policy(url, Some(policy.clone()))?;
+        }
+        self.merge_tag_metadata(&settings.tags).await?;
+        self.saved_searches
+            .write()
+            .unwrap()
+            .extend(settings.saved_searches.clone());
+        if !settings.retention.is_empty() {
+            self.set_retention(RetentionPolicy {
+                rules: settings.retention.clone(),
+            })?;
+        }
+        let mut tag_rules = self.tag_rules().await?;
+        let before = tag_rules.len();
+        for rule in &settings.tag_rules {
+            if !tag_rules.contains(rule) {
+                tag_rules.push(rule.clone());
+            }
+        }
+        if tag_rules.len() > before {
+            self.set_tag_rules(tag_rules).await?;
+        }
+        if settings.max_privacy {
+            self.set_max_privacy(true)?;
+        }
+        Ok(settings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::retention::RetentionAction;
+    use crate::test_support::memory_dialog;
+
+    #[tokio::test]
+    async fn test_settings_round_trip() {
+        let keys = Keys::generate();
+        let old = Dialog::in_memory(keys.clone());
+        old.set_tag_metadata(
+            "work",
+            TagMetadata {
+                color: Some("#ff0000".into()),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        old.save_search("Groceries", "#shopping milk");
+        old.set_retention(RetentionPolicy {
+            rules: vec![RetentionRule {
+                tag: Some("fleeting".into()),
+                older_than_days: 30,
+                action: RetentionAction::Purge,
+                keep_pinned: true,
+            }],
+        })
+        .unwrap();
+        let policy = RelayPolicy::new([Kind::Metadata]);
+        old.set_relay_policy("wss://public.example.com", Some(policy))
+            .unwrap();
+        old.set_max_privacy(true).unwrap();
+        let blob = old.export_settings().await.unwrap();
+        assert!(!blob.contains("Groceries"), "blob must be encrypted");
+
+        let new = Dialog::in_memory(keys);
+        let imported = new.import_settings(&blob).await.unwrap();
+        assert_eq!(imported.saved_searches.len(), 1);
+        assert_eq!(new.saved_searches(), old.saved_searches());
+        assert_eq!(new.retention(), old.retention());
+        assert_eq!(new.relay_policies().unwrap(), old.relay_policies().unwrap());
+        assert!(new.max_privacy());
+        let tags = new.app_state().await.unwrap().tags;
+        assert_eq!(tags["work"].value.color.as_deref(), Some("#ff0000"));
+
+        let stranger = memory_dialog();
+        assert!(matches!(
+            stranger.import_settings(&blob).await,
+            Err(DialogError::Import(_))
+        ));
+    }
+}
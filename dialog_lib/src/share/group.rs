@@ -0,0 +1,234 @@
+use crate::{Dialog, DialogError, Result};
+use nostr_sdk::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// d-tag prefix of our local record of a group share
+const GROUP_SHARE_D_TAG: &str = "dialog_group_share";
+
+/// One person a note was shared with, and whether it reached them
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ShareRecipient {
+    pub pubkey: PublicKey,
+    /// Relays that acknowledged the gift wrap sent to them
+    #[serde(default)]
+    pub acked_by: Vec<String>,
+    /// Why the last attempt failed, if it did
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+impl ShareRecipient {
+    pub fn is_delivered(&self) -> bool {
+        !self.acked_by.is_empty()
+    }
+}
+
+/// A note sent privately to several people (NIP-17), one gift wrap each
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GroupShare {
+    pub note_id: EventId,
+    /// When it was shared; identifies the share for
+    /// [`Dialog::resend_group_share`]
+    pub shared_at: Timestamp,
+    pub recipients: Vec<ShareRecipient>,
+}
+
+impl GroupShare {
+    pub fn is_delivered(&self) -> bool {
+        self.recipients.iter().all(ShareRecipient::is_delivered)
+    }
+}
+
+/// Kept with the share so a resend delivers exactly what the others got
+#[derive(Serialize, Deserialize)]
+struct GroupShareRecord {
+    share: GroupShare,
+    rumor: UnsignedEvent,
+}
+
+impl Dialog {
+    /// Send a note privately to `recipients`: one message naming all of
+    /// them, gift-wrapped once per recipient. Each recipient's delivery is
+    /// recorded separately, so a relay failure for one doesn't fail the
+    /// share; retry those with [`Dialog::resend_group_share`].
+    pub async fn share_note_with(
+        &self,
+        note_id: &EventId,
+        recipients: Vec<PublicKey>,
+    ) -> Result<GroupShare> {
+        let mut pubkeys: Vec<PublicKey> = Vec::new();
+        for pubkey in recipients {
+            if pubkey != self.keys.public_key() && !pubkeys.contains(&pubkey) {
+                pubkeys.push(pubkey);
+            }
+        }
+        if pubkeys.is_empty() {
+            return Err(DialogError::Share("no one to share with".into()));
+        }
+        let note = self.hydrate_note(note_id).await?;
+        if note.is_locked {
+            return Err(DialogError::Share("unlock the note to share it".into()));
+        }
+
+        let shared_at = self.created_at.next(self.now());
+        let rumor = EventBuilder::new(Kind::PrivateDirectMessage, &note.text)
+            .tags(pubkeys.iter().map(|pubkey| Tag::public_key(*pubkey)))
+            .custom_created_at(shared_at)
+            .build(self.keys.public_key());
+        let mut record = GroupShareRecord {
+            share: GroupShare {
+                note_id: *note_id,
+                shared_at,
+                recipients: pubkeys
+                    .into_iter()
+                    .map(|pubkey| ShareRecipient {
+                        pubkey,
+                        acked_by: Vec::new(),
+                        error: None,
+                    })
+                    .collect(),
+            },
+            rumor,
+        };
+        // Recorded before sending, so an interrupted share can be resent
+        let recorded_at = self.store_group_share(&record, None).await?;
+        self.deliver_group_share(&mut record, recorded_at).await?;
+        Ok(record.share)
+    }
+
+    /// Send the note again to recipients no relay acknowledged yet
+    pub async fn resend_group_share(
+        &self,
+        note_id: &EventId,
+        shared_at: Timestamp,
+    ) -> Result<GroupShare> {
+        let (mut record, recorded_at) = self
+            .group_share_record(note_id, shared_at)
+            .await?
+            .ok_or_else(|| DialogError::Share(format!("no share of {note_id} at {shared_at}")))?;
+        self.deliver_group_share(&mut record, recorded_at).await?;
+        Ok(record.share)
+    }
+
+    /// Group shares of a note, newest first
+    pub async fn group_shares(&self, note_id: &EventId) -> Result<Vec<GroupShare>> {
+        let mut shares: Vec<GroupShare> = self
+            .private_records(&group_share_d_tag_prefix(note_id))
+            .await?
+            .into_iter()
+            .map(|record: GroupShareRecord| record.share)
+            .collect();
+        shares.sort_by_key(|share| std::cmp::Reverse(share.shared_at));
+        Ok(shares)
+    }
+
+    /// Gift-wrap and send to each undelivered recipient, then record how
+    /// it went. `recorded_at` is the created_at of the record to replace.
+    async fn deliver_group_share(
+        &self,
+        record: &mut GroupShareRecord,
+        recorded_at: Timestamp,
+    ) -> Result<()> {
+        for recipient in record.share.recipients.iter_mut() {
+            if recipient.is_delivered() {
+                continue;
+            }
+            let wrap =
+                EventBuilder::gift_wrap(&self.keys, &recipient.pubkey, record.rumor.clone(), [])
+                    .await?;
+            // Wraps are for the recipient; we keep the record, not the wrap
+            match self.send_to_relays(wrap).await {
+                Ok(output) => {
+                    recipient.acked_by = output.success.iter().map(|url| url.to_string()).collect();
+                    recipient.error = None;
+                }
+                Err(e) => recipient.error = Some(e.to_string()),
+            }
+        }
+        self.store_group_share(record, Some(recorded_at)).await?;
+        let delivered = record
+            .share
+            .recipients
+            .iter()
+            .filter(|r| r.is_delivered())
+            .count();
+        eprintln!(
+            "[lib] group share of {}: delivered to {delivered}/{}",
+            record.share.note_id,
+            record.share.recipients.len()
+        );
+        Ok(())
+    }
+
+    async fn group_share_record(
+        &self,
+        note_id: &EventId,
+        shared_at: Timestamp,
+    ) -> Result<Option<(GroupShareRecord, Timestamp)>> {
+        self.private_record(&group_share_d_tag(note_id, shared_at))
+            .await
+    }
+
+    /// Records stay on this device, like share link records. Returns the
+    /// new record's created_at.
+    async fn store_group_share(
+        &self,
+        record: &GroupShareRecord,
+        previous: Option<Timestamp>,
+    ) -> Result<Timestamp> {
+        let d_tag = group_share_d_tag(&record.share.note_id, record.share.shared_at);
+        self.save_private_record(d_tag, record, previous).await
+    }
+}
+
+fn group_share_d_tag_prefix(note_id: &EventId) -> String {
+    format!("{GROUP_SHARE_D_TAG}:{}:", note_id.to_hex())
+}
+
+fn group_share_d_tag(note_id: &EventId, shared_at: Timestamp) -> String {
+    format!(
+        "{}{}",
+        group_share_d_tag_prefix(note_id),
+        shared_at.as_u64()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::memory_dialog;
+
+    #[tokio::test]
+    async fn test_group_share_records_each_recipient() {
+        let dialog = memory_dialog();
+        let note = dialog.save_note("Trip plan #travel").await.unwrap();
+        let (alice, bob) = (Keys::generate().public_key(), Keys::generate().public_key());
+
+        assert!(dialog.share_note_with(&note.id, vec![]).await.is_err());
+        let own = dialog.keys.public_key();
+        assert!(dialog.share_note_with(&note.id, vec![own]).await.is_err());
+
+        // Without relays nothing is delivered, but the share is recorded
+        let share = dialog
+            .share_note_with(&note.id, vec![alice, bob, alice, own])
+            .await
+            .unwrap();
+        assert_eq!(share.note_id, note.id);
+        let pubkeys: Vec<PublicKey> = share.recipients.iter().map(|r| r.pubkey).collect();
+        assert_eq!(pubkeys, vec![alice, bob]);
+        assert!(!share.is_delivered());
+        assert!(share.recipients.iter().all(|r| r.error.is_some()));
+
+        let shares = dialog.group_shares(&note.id).await.unwrap();
+        assert_eq!(shares, vec![share.clone()]);
+        let resent = dialog
+            .resend_group_share(&note.id, share.shared_at)
+            .await
+            .unwrap();
+        assert_eq!(resent.shared_at, share.shared_at);
+        assert_eq!(dialog.group_shares(&note.id).await.unwrap().len(), 1);
+
+        let other = dialog.save_note("Not shared").await.unwrap();
+        assert!(dialog.group_shares(&other.id).await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,248 @@
+mod group;
+
+pub use group::{GroupShare, ShareRecipient};
+
+use crate::kinds::APP_DATA_KIND;
+use crate::{Dialog, DialogError, Result};
+use nostr_sdk::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// d-tag of the shared copy, published under a throwaway key
+const SHARE_D_TAG: &str = "dialog_share";
+/// d-tag prefix of our local record of a share, for listing and revoking
+const SHARE_RECORD_D_TAG: &str = "dialog_share_record";
+
+/// A link to an encrypted, expiring copy of a note
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ShareLink {
+    pub note_id: EventId,
+    /// Id of the shared copy; revoke with this
+    pub event_id: EventId,
+    /// `note1...#<key>`: the part after `#` decrypts the copy
+    pub url: String,
+    pub expires_at: Timestamp,
+    #[serde(default)]
+    pub revoked: bool,
+}
+
+impl ShareLink {
+    pub fn is_expired(&self, now: Timestamp) -> bool {
+        self.expires_at <= now
+    }
+}
+
+impl Dialog {
+    /// Publish a copy of a note encrypted with a fresh random key, set to
+    /// expire after `ttl` (NIP-40), and return a link carrying the key.
+    ///
+    /// The copy is signed by a throwaway key, so relays can't tie it to
+    /// you. Anyone with the link can read the note until it expires or is
+    /// revoked with [`Dialog::revoke_share_link`].
+    pub async fn create_share_link(&self, note_id: &EventId, ttl: Duration) -> Result<ShareLink> {
+        if ttl.is_zero() {
+            return Err(DialogError::Share("ttl must be positive".into()));
+        }
+        let note = self
+            .get_note(note_id)
+            .await?
+            .ok_or(DialogError::NoteNotFound(*note_id))?;
+
+        let share_keys = Keys::generate();
+        let expires_at = Timestamp::from(self.now().as_u64() + ttl.as_secs().max(1));
+        let event = seal(&share_keys, &note.text, expires_at).await?;
+        let link = ShareLink {
+            note_id: *note_id,
+            event_id: event.id,
+            url: share_url(&event.id, share_keys.secret_key())?,
+            expires_at,
+            revoked: false,
+        };
+        // A link to a copy no relay has is useless, so failures are errors
+        self.publish(event).await?;
+        self.store_share_record(&link, None).await?;
+        eprintln!(
+            "[lib] create_share_link: {} expires {expires_at}",
+            link.event_id
+        );
+        Ok(link)
+    }
+
+    /// Links made for a note, newest first, including expired and revoked
+    pub async fn share_links(&self, note_id: &EventId) -> Result<Vec<ShareLink>> {
+        let mut links: Vec<ShareLink> = self
+            .private_records(&format!("{SHARE_RECORD_D_TAG}:"))
+            .await?
+            .into_iter()
+            .filter(|link: &ShareLink| link.note_id == *note_id)
+            .collect();
+        links.sort_by_key(|link| std::cmp::Reverse(link.expires_at));
+        Ok(links)
+    }
+
+    /// Ask relays to delete a shared copy before it expires. The deletion
+    /// is signed with the link's own key, which we kept for this.
+    pub async fn revoke_share_link(&self, event_id: &EventId) -> Result<()> {
+        let (mut link, recorded_at) = self
+            .share_record(event_id)
+            .await?
+            .ok_or_else(|| DialogError::Share(format!("no share link {event_id}")))?;
+        if link.revoked {
+            return Ok(());
+        }
+
+        let (_, share_keys) = parse_share_url(&link.url)?;
+        let deletion = EventBuilder::delete(vec![*event_id]).sign_with_keys(&share_keys)?;
+        self.publish(deletion).await?;
+
+        link.revoked = true;
+        self.store_share_record(&link, Some(recorded_at)).await?;
+        eprintln!("[lib] revoke_share_link: revoked {event_id}");
+        Ok(())
+    }
+
+    /// Fetch and decrypt the note behind a share link
+    pub async fn open_share_link(&self, url: &str) -> Result<String> {
+        let (event_id, share_keys) = parse_share_url(url)?;
+        let filter = Filter::new().id(event_id);
+        let mut events = self
+            .client
+            .database()
+            .query(vec![filter.clone()])
+            .await
+            .map_err(|e| DialogError::Database(e.to_string()))?
+            .into_iter()
+            .collect::<Vec<_>>();
+        if events.is_empty() {
+            events = self
+                .client
+                .fetch_events(vec![filter], Some(self.fetch_timeout))
+                .await?
+                .into_iter()
+                .collect();
+        }
+        let event = events.into_iter().next().ok_or_else(|| {
+            DialogError::Share("shared note not found; it may have expired".into())
+        })?;
+        if event.is_expired() {
+            return Err(DialogError::Share("share link has expired".into()));
+        }
+        unseal(&event, &share_keys)
+    }
+
+    async fn share_record(&self, event_id: &EventId) -> Result<Option<(ShareLink, Timestamp)>> {
+        self.private_record(&share_record_d_tag(event_id)).await
+    }
+
+    /// Records stay on this device: the key in them is only needed here.
+    /// `previous` is the created_at of the record being replaced.
+    async fn store_share_record(
+        &self,
+        link: &ShareLink,
+        previous: Option<Timestamp>,
+    ) -> Result<()> {
+        self.save_private_record(share_record_d_tag(&link.event_id), link, previous)
+            .await?;
+        Ok(())
+    }
+}
+
+/// The shared copy: the text encrypted to the throwaway key itself, so the
+/// key alone decrypts it
+async fn seal(keys: &Keys, text: &str, expires_at: Timestamp) -> Result<Event> {
+    let encrypted = nip44::encrypt(
+        keys.secret_key(),
+        &keys.public_key(),
+        text,
+        nip44::Version::default(),
+    )?;
+    Ok(EventBuilder::new(Kind::from(APP_DATA_KIND), encrypted)
+        .tag(Tag::identifier(SHARE_D_TAG))
+        .tag(Tag::expiration(expires_at))
+        .sign(keys)
+        .await?)
+}
+
+fn unseal(event: &Event, keys: &Keys) -> Result<String> {
+    if keys.public_key() != event.pubkey {
+        return Err(DialogError::Share("link key doesn't match the note".into()));
+    }
+    Ok(nip44::decrypt(
+        keys.secret_key(),
+        &event.pubkey,
+        &event.content,
+    )?)
+}
+
+fn share_url(event_id: &EventId, secret_key: &SecretKey) -> Result<String> {
+    let note = event_id
+        .to_bech32()
+        .map_err(|e| DialogError::Share(e.to_string()))?;
+    Ok(format!("{note}#{}", secret_key.to_secret_hex()))
+}
+
+/// The shared copy's id and the keys that decrypt it. Accepts the link
+/// with or without a `nostr:` prefix.
+pub fn parse_share_url(url: &str) -> Result<(EventId, Keys)> {
+    let url = url.trim();
+    let url = url.strip_prefix("nostr:").unwrap_or(url);
+    let (note, key) = url
+        .split_once('#')
+        .ok_or_else(|| DialogError::Share("link has no key".into()))?;
+    let event_id = EventId::parse(note).map_err(|e| DialogError::Share(e.to_string()))?;
+    let keys = Keys::parse(key).map_err(|e| DialogError::Share(e.to_string()))?;
+    Ok((event_id, keys))
+}
+
+fn share_record_d_tag(event_id: &EventId) -> String {
+    format!("{SHARE_RECORD_D_TAG}:{}", event_id.to_hex())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::memory_dialog;
+
+    #[tokio::test]
+    async fn test_seal_and_open_share() {
+        let keys = Keys::generate();
+        let expires_at = Timestamp::from(Timestamp::now().as_u64() + 60);
+        let event = seal(&keys, "Shared plan", expires_at).await.unwrap();
+        assert!(!event.content.contains("Shared plan"));
+        assert!(!event.is_expired());
+
+        let url = share_url(&event.id, keys.secret_key()).unwrap();
+        assert!(url.starts_with("note1"));
+        let (id, share_keys) = parse_share_url(&format!("nostr:{url}")).unwrap();
+        assert_eq!(id, event.id);
+        assert_eq!(unseal(&event, &share_keys).unwrap(), "Shared plan");
+        assert!(unseal(&event, &Keys::generate()).is_err());
+        assert!(parse_share_url(&event.id.to_bech32().unwrap()).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_share_records_are_listed_per_note() {
+        let dialog = memory_dialog();
+        let note = dialog.save_note("Share me").await.unwrap();
+        // Without relays the copy can't be published
+        assert!(dialog
+            .create_share_link(&note.id, Duration::from_secs(3600))
+            .await
+            .is_err());
+        assert!(dialog.share_links(&note.id).await.unwrap().is_empty());
+
+        let link = ShareLink {
+            note_id: note.id,
+            event_id: EventId::all_zeros(),
+            url: share_url(&EventId::all_zeros(), Keys::generate().secret_key()).unwrap(),
+            expires_at: Timestamp::from(1),
+            revoked: false,
+        };
+        dialog.store_share_record(&link, None).await.unwrap();
+        let links = dialog.share_links(&note.id).await.unwrap();
+        assert_eq!(links, vec![link.clone()]);
+        assert!(links[0].is_expired(Timestamp::now()));
+        let (stored, _) = dialog.share_record(&link.event_id).await.unwrap().unwrap();
+        assert_eq!(stored, link);
+    }
+}
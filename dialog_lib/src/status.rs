@@ -0,0 +1,137 @@
+use crate::outbox::read_outbox;
+use crate::{Dialog, RelayEventCount, Result, SyncReport};
+use nostr_sdk::prelude::*;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A relay in the pool and how its connection is doing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RelayHealth {
+    pub url: RelayUrl,
+    pub connected: bool,
+    /// e.g. "Connected", "Disconnected", "Pending"
+    pub status: String,
+}
+
+/// How the most recent sync ended
+#[derive(Debug, Clone)]
+pub struct LastSync {
+    pub finished_at: Timestamp,
+    pub result: std::result::Result<SyncReport, String>,
+}
+
+/// Everything a status screen shows, read in one go
+#[derive(Debug, Clone)]
+pub struct DialogStatus {
+    pub relays: Vec<RelayHealth>,
+    /// Entries waiting in the outbox; 0 if no outbox is known
+    pub pending_outbox: usize,
+    pub syncing: bool,
+    /// None until a sync has finished this session
+    pub last_sync: Option<LastSync>,
+    /// Bytes used by the note database; None for in-memory storage
+    pub db_size_bytes: Option<u64>,
+    /// Names of running watches
+    pub watches: Vec<String>,
+    /// Events per relay, as last counted; see [`Dialog::count_remote_events`]
+    pub event_counts: Vec<RelayEventCount>,
+}
+
+impl Dialog {
+    pub async fn status(&self) -> Result<DialogStatus> {
+        let mut relays: Vec<RelayHealth> = self
+            .client
+            .relays()
+            .await
+            .into_iter()
+            .map(|(url, relay)| {
+                let status = relay.status();
+                RelayHealth {
+                    url,
+                    connected: status == RelayStatus::Connected,
+                    status: status.to_string(),
+                }
+            })
+            .collect();
+        relays.sort_by(|a, b| a.url.as_str().cmp(b.url.as_str()));
+
+        let pending_outbox = match self.outbox_path() {
+            Some(path) => read_outbox(&path)?.len(),
+            None => 0,
+        };
+        let db_size_bytes = match &self.db_path {
+            Some(path) => Some(dir_size(path)?),
+            None => None,
+        };
+
+        Ok(DialogStatus {
+            relays,
+            pending_outbox,
+            syncing: self.is_syncing(),
+            last_sync: self.last_sync(),
+            db_size_bytes,
+            watches: self.watches().into_iter().map(|(name, _)| name).collect(),
+            event_counts: self.event_counts(),
+        })
+    }
+
+    /// Count entries in the outbox at `path` in [`Dialog::status`]. Set by
+    /// [`Dialog::drain_outbox`] too.
+    pub fn set_outbox_path(&self, path: Option<PathBuf>) {
+        *self.outbox_path.write().unwrap() = path;
+    }
+
+    pub fn outbox_path(&self) -> Option<PathBuf> {
+        self.outbox_path.read().unwrap().clone()
+    }
+}
+
+/// Size of a file, or of everything under a directory
+fn dir_size(path: &Path) -> Result<u64> {
+    let metadata = match fs::metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e.into()),
+    };
+    if !metadata.is_dir() {
+        return Ok(metadata.len());
+    }
+    let mut size = 0;
+    for entry in fs::read_dir(path)? {
+        size += dir_size(&entry?.path())?;
+    }
+    Ok(size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::append_to_outbox;
+    use crate::test_support::memory_dialog;
+
+    #[tokio::test]
+    async fn test_status_counts_outbox() {
+        let dialog = memory_dialog();
+        let status = dialog.status().await.unwrap();
+        assert!(status.relays.is_empty());
+        assert_eq!(status.pending_outbox, 0);
+        assert!(status.last_sync.is_none());
+        assert_eq!(status.db_size_bytes, None);
+
+        let dir = std::env::temp_dir().join(format!("dialog-status-{}", dialog.public_key()));
+        let path = dir.join("outbox.jsonl");
+        append_to_outbox(&path, "one").unwrap();
+        append_to_outbox(&path, "two").unwrap();
+        dialog.set_outbox_path(Some(path));
+        assert_eq!(dialog.status().await.unwrap().pending_outbox, 2);
+        assert_eq!(
+            dir_size(&dir).unwrap(),
+            fs::metadata(dir.join("outbox.jsonl")).unwrap().len()
+        );
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(dialog.sync().await.is_err(), "no relays connected");
+        let last = dialog.status().await.unwrap().last_sync.unwrap();
+        assert!(last.result.is_err());
+    }
+}
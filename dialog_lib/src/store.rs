@@ -0,0 +1,138 @@
+use crate::{Dialog, DialogError, Result};
+use nostr_sdk::prelude::*;
+use std::collections::HashSet;
+use std::time::Instant;
+use tokio::task::JoinSet;
+
+/// Number of events written to the database concurrently
+const SAVE_BATCH_SIZE: usize = 64;
+
+impl Dialog {
+    /// Save many events to the local database at once.
+    ///
+    /// Duplicates and events already stored are skipped; the rest are written
+    /// in concurrent batches, each checked with a single query, instead of
+    /// one roundtrip at a time. Returns how many events were newly saved.
+    pub async fn save_events<I>(&self, events: I) -> Result<usize>
+    where
+        I: IntoIterator<Item = Event>,
+    {
+        let started = Instant::now();
+        let database = self.client.database().clone();
+
+        let mut seen = HashSet::new();
+        let mut unique = Vec::new();
+        for event in events {
+            if seen.insert(event.id) {
+                unique.push(event);
+            }
+        }
+
+        let mut saved = 0;
+        for batch in unique.chunks(SAVE_BATCH_SIZE) {
+            // One lookup per batch for the ids that are already stored.
+            // Deleted ids aren't returned, but saving them is refused anyway.
+            let filter = Filter::new().ids(batch.iter().map(|event| event.id));
+            let stored: HashSet<EventId> = database
+                .query(vec![filter])
+                .await
+                .map_err(|e| DialogError::Database(e.to_string()))?
+                .into_iter()
+                .map(|event| event.id)
+                .collect();
+
+            let mut tasks = JoinSet::new();
+            for event in batch.iter().filter(|event| !stored.contains(&event.id)) {
+                let event = event.clone();
+                let database = database.clone();
+                tasks.spawn(async move { database.save_event(&event).await });
+            }
+            while let Some(result) = tasks.join_next().await {
+                match result {
+                    Ok(Ok(true)) => saved += 1,
+                    Ok(Ok(false)) => {}
+                    Ok(Err(e)) => return Err(DialogError::Database(e.to_string())),
+                    Err(e) => return Err(DialogError::Database(e.to_string())),
+                }
+            }
+        }
+
+        let elapsed = started.elapsed();
+        eprintln!(
+            "[lib] save_events: saved {saved}/{} new events in {}ms ({:.0} events/s)",
+            seen.len(),
+            elapsed.as_millis(),
+            saved as f64 / elapsed.as_secs_f64().max(0.001)
+        );
+        Ok(saved)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::memory_dialog;
+
+    fn signed_events(keys: &Keys, count: usize) -> Vec<Event> {
+        (0..count)
+            .map(|i| {
+                EventBuilder::new(Kind::from(1059), format!("note {i}"))
+                    .sign_with_keys(keys)
+                    .unwrap()
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_save_events_skips_duplicates() {
+        let dialog = memory_dialog();
+        let events = signed_events(&dialog.keys, 150);
+
+        let mut with_dupes = events.clone();
+        with_dupes.extend(events.iter().take(10).cloned());
+        assert_eq!(dialog.save_events(with_dupes).await.unwrap(), 150);
+
+        // Everything is already stored the second time around
+        assert_eq!(dialog.save_events(events).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_save_events_skips_deleted() {
+        let dialog = memory_dialog();
+        let events = signed_events(&dialog.keys, 3);
+        dialog.save_events(events.clone()).await.unwrap();
+        dialog
+            .local_store
+            .delete(Filter::new().id(events[0].id))
+            .await
+            .unwrap();
+
+        assert_eq!(dialog.save_events(events).await.unwrap(), 0);
+    }
+
+    /// Compare against saving one event at a time:
+    /// `cargo test -p dialog_lib bench_save_events -- --ignored --nocapture`
+    #[tokio::test]
+    #[ignore]
+    async fn bench_save_events() {
+        let events = signed_events(&Keys::generate(), 5000);
+
+        let dialog = memory_dialog();
+        let started = Instant::now();
+        for event in &events {
+            let database = dialog.client.database();
+            if database.check_id(&event.id).await.unwrap() == DatabaseEventStatus::NotExistent {
+                database.save_event(event).await.unwrap();
+            }
+        }
+        let one_by_one = started.elapsed();
+
+        let dialog = memory_dialog();
+        let started = Instant::now();
+        assert_eq!(dialog.save_events(events).await.unwrap(), 5000);
+        println!(
+            "one at a time: {one_by_one:?}, save_events: {:?}",
+            started.elapsed()
+        );
+    }
+}
@@ -0,0 +1,118 @@
+use crate::query::SEARCH_SCAN_LIMIT;
+use crate::tags::{parse_hashtags, strip_hashtags};
+use crate::{Dialog, Result};
+use std::collections::{HashMap, HashSet};
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Shorter words ("a", "of", "to") say little about a note
+const MIN_WORD_CHARS: usize = 3;
+
+/// Distinct lowercase words of `text`, hashtags left out
+fn words(text: &str) -> HashSet<String> {
+    strip_hashtags(text)
+        .unicode_words()
+        .filter(|word| word.chars().count() >= MIN_WORD_CHARS)
+        .map(str::to_lowercase)
+        .collect()
+}
+
+impl Dialog {
+    /// Existing tags that suit `text`, best first, for a composer's
+    /// suggestions. Each tag scores the words it shares with `text`,
+    /// weighted by how many of the tag's notes use the word and how rare
+    /// the word is across all notes (TF-IDF). Tags already in `text` are
+    /// left out. Only the most recent [`SEARCH_SCAN_LIMIT`] notes are read,
+    /// and nothing leaves the device.
+    pub async fn suggest_tags_for_text(&self, text: &str, limit: usize) -> Result<Vec<String>> {
+        let query = words(text);
+        if query.is_empty() || limit == 0 {
+            return Ok(Vec::new());
+        }
+        let present: HashSet<String> = parse_hashtags(text).into_iter().collect();
+
+        let notes = self.list_notes(SEARCH_SCAN_LIMIT).await?;
+        let notes: Vec<_> = notes
+            .into_iter()
+            .filter(|note| note.is_hydrated && !note.is_locked)
+            .map(|note| (words(&note.text), note.tags))
+            .collect();
+
+        // Notes each query word appears in, overall and per tag
+        let mut document_frequency: HashMap<&str, usize> = HashMap::new();
+        let mut tag_notes: HashMap<&str, usize> = HashMap::new();
+        let mut tag_frequency: HashMap<(&str, &str), usize> = HashMap::new();
+        for (note_words, tags) in &notes {
+            let shared: Vec<&str> = query
+                .iter()
+                .filter(|word| note_words.contains(*word))
+                .map(String::as_str)
+                .collect();
+            for &word in &shared {
+                *document_frequency.entry(word).or_default() += 1;
+            }
+            let tags = tags.iter().map(String::as_str);
+            for tag in tags.filter(|tag| !present.contains(*tag)) {
+                *tag_notes.entry(tag).or_default() += 1;
+                for &word in &shared {
+                    *tag_frequency.entry((tag, word)).or_default() += 1;
+                }
+            }
+        }
+
+        let total = notes.len() as f64;
+        let mut scores: HashMap<&str, f64> = HashMap::new();
+        for ((tag, word), count) in tag_frequency {
+            let idf = (total / document_frequency[&word] as f64).ln();
+            let tf = count as f64 / tag_notes[&tag] as f64;
+            *scores.entry(tag).or_default() += tf * idf;
+        }
+
+        let mut ranked: Vec<(&str, f64)> = scores
+            .into_iter()
+            .filter(|(_, score)| *score > 0.0)
+            .collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        Ok(ranked
+            .into_iter()
+            .take(limit)
+            .map(|(tag, _)| tag.to_string())
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::memory_dialog;
+
+    #[tokio::test]
+    async fn test_tags_are_suggested_by_shared_words() {
+        let dialog = memory_dialog();
+        for text in [
+            "Buy flour and eggs #groceries",
+            "Eggs, milk and bread #groceries",
+            "Standup notes: deploy the backend #work",
+            "Backend deploy failed again #work #urgent",
+            "Call mum about the weekend",
+        ] {
+            dialog.save_note(text).await.unwrap();
+        }
+
+        let suggested = dialog
+            .suggest_tags_for_text("need eggs and flour", 3)
+            .await
+            .unwrap();
+        assert_eq!(suggested, vec!["groceries"]);
+
+        let suggested = dialog
+            .suggest_tags_for_text("the backend deploy is stuck #work", 3)
+            .await
+            .unwrap();
+        assert_eq!(suggested, vec!["urgent"]);
+        assert!(dialog
+            .suggest_tags_for_text("a to of", 3)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,154 @@
+use crate::tags::strip_hashtags;
+use crate::{Dialog, DialogError, Note, Result};
+use nostr_sdk::prelude::*;
+use std::future::Future;
+use std::ops::RangeBounds;
+use std::pin::Pin;
+use std::sync::Arc;
+
+/// Tag put on digest notes; notes with it are never summarized again
+pub const SUMMARY_TAG: &str = "summary";
+/// Most notes fed to the summarizer for one digest
+const MAX_SUMMARIZED_NOTES: usize = 500;
+
+pub type SummaryFuture<'a> = Pin<Box<dyn Future<Output = Result<String>> + Send + 'a>>;
+
+/// Turns the text of a batch of notes into a short summary. Apps plug in
+/// their own, e.g. one backed by an on-device or hosted LLM, with
+/// [`Dialog::set_summarizer`].
+pub trait Summarizer: Send + Sync {
+    fn summarize<'a>(&'a self, text: &'a str) -> SummaryFuture<'a>;
+}
+
+/// Default summarizer: returns the notes unchanged, so a digest is just
+/// the notes collected in one place
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NoopSummarizer;
+
+impl Summarizer for NoopSummarizer {
+    fn summarize<'a>(&'a self, text: &'a str) -> SummaryFuture<'a> {
+        Box::pin(async move { Ok(text.to_string()) })
+    }
+}
+
+impl Dialog {
+    pub fn set_summarizer(&self, summarizer: Arc<dyn Summarizer>) {
+        *self.summarizer.write().unwrap() = summarizer;
+    }
+
+    /// Summarize the notes tagged `tag` created in `range` and save the
+    /// result as a new `#summary` note. Returns None when there is nothing
+    /// to summarize. The digest is saved even if no relay takes it; it then
+    /// comes back with `is_synced` false.
+    pub async fn summarize_tag(
+        &self,
+        tag: &str,
+        range: impl RangeBounds<Timestamp>,
+    ) -> Result<Option<Note>> {
+        let mut notes: Vec<Note> = self
+            .list_by_tag(tag, MAX_SUMMARIZED_NOTES)
+            .await?
+            .into_iter()
+            .filter(|note| range.contains(&note.created_at))
+            .filter(|note| !note.tags.iter().any(|t| t == SUMMARY_TAG))
+            .collect();
+        if notes.is_empty() {
+            return Ok(None);
+        }
+        notes.sort_by_key(|note| note.created_at);
+
+        let summarizer = self.summarizer.read().unwrap().clone();
+        let summary = summarizer.summarize(&summarizer_input(&notes)).await?;
+        if summary.trim().is_empty() {
+            return Err(DialogError::Summarize("summarizer returned nothing".into()));
+        }
+
+        let text = digest_text(tag, &notes, summary.trim());
+        let note = self.save_note(&text).await?;
+        if let Err(e) = self.publish_stored_note(&note.id).await {
+            eprintln!("[lib] summarize_tag: {} not published: {e}", note.id);
+        }
+        eprintln!(
+            "[lib] summarize_tag: summarized {} notes into {}",
+            notes.len(),
+            note.id
+        );
+        Ok(Some(note))
+    }
+}
+
+/// Notes oldest first, each headed by its date. Hashtags are dropped so a
+/// digest that quotes them doesn't get filed under every tag it mentions.
+fn summarizer_input(notes: &[Note]) -> String {
+    notes
+        .iter()
+        .map(|note| {
+            let text = strip_hashtags(&note.text);
+            format!("[{}]\n{}", day(note.created_at), text.trim())
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// The tag is named without `#` so the digest doesn't land in it
+fn digest_text(tag: &str, notes: &[Note], summary: &str) -> String {
+    let first = day(notes[0].created_at);
+    let last = day(notes[notes.len() - 1].created_at);
+    let span = if first == last {
+        first
+    } else {
+        format!("{first} to {last}")
+    };
+    format!(
+        "Summary of {tag} ({} notes, {span})\n\n{summary}\n\n#{SUMMARY_TAG}",
+        notes.len()
+    )
+}
+
+fn day(timestamp: Timestamp) -> String {
+    chrono::DateTime::from_timestamp(timestamp.as_u64() as i64, 0)
+        .unwrap_or_default()
+        .format("%Y-%m-%d")
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::memory_dialog;
+
+    struct CountingSummarizer;
+
+    impl Summarizer for CountingSummarizer {
+        fn summarize<'a>(&'a self, text: &'a str) -> SummaryFuture<'a> {
+            Box::pin(async move { Ok(format!("{} entries", text.matches('[').count())) })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_summarize_tag() {
+        let dialog = memory_dialog();
+        assert!(dialog.summarize_tag("work", ..).await.unwrap().is_none());
+
+        dialog.save_note("Ship the beta #work").await.unwrap();
+        dialog.save_note("Plan offsite #work").await.unwrap();
+        dialog.save_note("Buy milk #home").await.unwrap();
+
+        let digest = dialog.summarize_tag("work", ..).await.unwrap().unwrap();
+        assert!(digest.text.starts_with("Summary of work (2 notes, "));
+        assert!(digest.text.contains("Ship the beta"));
+        assert_eq!(digest.tags, vec![SUMMARY_TAG]);
+        assert!(!digest.is_synced);
+
+        dialog.set_summarizer(Arc::new(CountingSummarizer));
+        let digest = dialog.summarize_tag("work", ..).await.unwrap().unwrap();
+        assert!(digest.text.contains("\n\n2 entries\n\n"));
+
+        let future = Timestamp::now().as_u64() + 3600;
+        let none = dialog
+            .summarize_tag("work", Timestamp::from(future)..)
+            .await
+            .unwrap();
+        assert!(none.is_none());
+    }
+}
@@ -0,0 +1,373 @@
+use crate::audit::AuditAction;
+use crate::kinds::KindFeature;
+use crate::validate::validate_event;
+use crate::{CancellationToken, Dialog, DialogError, LastSync, Result};
+use nostr_sdk::prelude::*;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tokio::task::JoinSet;
+
+/// Maximum number of events pulled by a plain (non-negentropy) sync
+const PLAIN_SYNC_LIMIT: usize = 500;
+/// Ids per REQ when fetching the events a negentropy sync found missing
+const SYNC_FETCH_BATCH_SIZE: usize = 500;
+
+/// Mechanism a sync ended up using
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncMode {
+    Negentropy,
+    Plain,
+}
+
+/// Summary of a completed sync
+#[derive(Debug, Clone)]
+pub struct SyncReport {
+    pub mode: SyncMode,
+    /// Events pulled from relays that were new locally
+    pub received: usize,
+    /// Local events uploaded to relays
+    pub sent: usize,
+    pub duration: Duration,
+    /// Negentropy failed and the plain fetch was used instead
+    pub fell_back: bool,
+    /// Read/pin/archive state from other devices changed local state
+    pub app_state_changed: bool,
+    /// What each relay returned to the plain fetch; empty if every relay
+    /// synced with negentropy
+    pub relays: Vec<RelaySyncStats>,
+}
+
+/// One relay's part in a plain sync. Relays are queried together and
+/// their events merged, so a note only one of them kept is still found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RelaySyncStats {
+    pub url: RelayUrl,
+    /// Valid note events it returned
+    pub fetched: usize,
+    /// Of those, events no other relay returned
+    pub exclusive: usize,
+    /// Why the fetch failed; the other relays' events are still used
+    pub error: Option<String>,
+}
+
+/// Single-flight bookkeeping for [`Dialog::sync`]
+#[derive(Debug, Default)]
+pub(crate) struct SyncState {
+    lock: tokio::sync::Mutex<()>,
+    running: AtomicBool,
+    // Bumped after every finished sync, so waiters can tell one completed
+    completed: AtomicU64,
+    last: std::sync::Mutex<Option<std::result::Result<SyncReport, String>>>,
+    finished_at: AtomicU64,
+}
+
+impl Dialog {
+    /// Sync notes with the connected relays, using negentropy where the
+    /// relay supports it and a plain fetch elsewhere.
+    ///
+    /// Overlapping calls share a single run: a caller arriving while a sync
+    /// is in progress waits for it and receives the same result.
+    pub async fn sync(&self) -> Result<SyncReport> {
+        self.sync_cancellable(&CancellationToken::new()).await
+    }
+
+    /// Like [`Dialog::sync`], but gives up with [`DialogError::Cancelled`]
+    /// once `cancel` fires. Callers that joined a cancelled run see it fail.
+    pub async fn sync_cancellable(&self, cancel: &CancellationToken) -> Result<SyncReport> {
+        if cancel.is_cancelled() {
+            return Err(DialogError::Cancelled);
+        }
+        let state = &self.sync_state;
+        let completed_before = state.completed.load(Ordering::SeqCst);
+        let _guard = tokio::select! {
+            guard = state.lock.lock() => guard,
+            _ = cancel.cancelled() => return Err(DialogError::Cancelled),
+        };
+
+        if state.completed.load(Ordering::SeqCst) != completed_before {
+            eprintln!("[lib] sync: joined in-progress sync");
+            let last = state.last.lock().unwrap().clone();
+            return match last {
+                Some(result) => result.map_err(DialogError::Sync),
+                None => Err(DialogError::Sync("previous sync left no result".into())),
+            };
+        }
+
+        state.running.store(true, Ordering::SeqCst);
+        let result = tokio::select! {
+            result = self.run_sync() => result,
+            _ = cancel.cancelled() => Err(DialogError::Cancelled),
+        };
+        *state.last.lock().unwrap() = Some(match &result {
+            Ok(report) => Ok(report.clone()),
+            Err(e) => Err(e.to_string()),
+        });
+        state
+            .finished_at
+            .store(self.now().as_u64(), Ordering::SeqCst);
+        state.completed.fetch_add(1, Ordering::SeqCst);
+        state.running.store(false, Ordering::SeqCst);
+        result
+    }
+
+    /// Whether a [`Dialog::sync`] is currently running
+    pub fn is_syncing(&self) -> bool {
+        self.sync_state.running.load(Ordering::SeqCst)
+    }
+
+    /// When the last [`Dialog::sync`] finished and how it went
+    pub fn last_sync(&self) -> Option<LastSync> {
+        let result = self.sync_state.last.lock().unwrap().clone()?;
+        Some(LastSync {
+            finished_at: Timestamp::from(self.sync_state.finished_at.load(Ordering::SeqCst)),
+            result,
+        })
+    }
+
+    async fn run_sync(&self) -> Result<SyncReport> {
+        let _span = self.span("sync");
+        self.chaos.delay(&*self.clock).await;
+        let mut report = self.run_note_sync().await?;
+        // App state and voice data are best-effort; a failure here
+        // shouldn't fail the sync
+        if self.kind_set().contains(KindFeature::AppData) {
+            match self.sync_app_state().await {
+                Ok(changed) => report.app_state_changed = changed,
+                Err(e) => eprintln!("[lib] sync: app state sync failed: {e}"),
+            }
+            if let Err(e) = self.sync_voice_data().await {
+                eprintln!("[lib] sync: voice data sync failed: {e}");
+            }
+        }
+        if let Err(e) = self.enforce_cache_limit().await {
+            eprintln!("[lib] sync: cache limit not enforced: {e}");
+        }
+        if report.received > 0 || report.sent > 0 || report.app_state_changed {
+            let detail = format!(
+                "{:?}: received {}, sent {}, app state changed: {}",
+                report.mode, report.received, report.sent, report.app_state_changed
+            );
+            self.audit(AuditAction::Sync, None, detail);
+        }
+        Ok(report)
+    }
+
+    /// Each relay is synced the way its NIP-11 document says it can be.
+    /// Relays that don't say try negentropy first and fall back to plain.
+    /// A sync mode set through the builder overrides both.
+    async fn run_note_sync(&self) -> Result<SyncReport> {
+        let started = Instant::now();
+        let mut negentropy = Vec::new();
+        let mut plain = Vec::new();
+        for url in self.client.relays().await.into_keys() {
+            let mode = match self.sync_mode {
+                Some(mode) => Some(mode),
+                None => self
+                    .probed_capabilities(&url)
+                    .await
+                    .and_then(|capabilities| capabilities.sync_mode()),
+            };
+            match mode {
+                Some(SyncMode::Plain) => plain.push(url),
+                Some(SyncMode::Negentropy) | None => negentropy.push(url),
+            }
+        }
+
+        if negentropy.is_empty() && plain.is_empty() {
+            return Err(DialogError::Sync("no relays connected".into()));
+        }
+
+        let mut report = SyncReport {
+            mode: SyncMode::Negentropy,
+            received: 0,
+            sent: 0,
+            duration: Duration::ZERO,
+            fell_back: false,
+            app_state_changed: false,
+            relays: Vec::new(),
+        };
+        if !self.kind_set().contains(KindFeature::Notes) {
+            return Ok(report);
+        }
+        if !negentropy.is_empty() {
+            match self.sync_notes_with(negentropy.clone()).await {
+                Ok(reconciliation) => {
+                    report.received += reconciliation.received.len();
+                    report.sent += reconciliation.sent.len();
+                }
+                Err(e) => {
+                    eprintln!("[lib] sync: negentropy failed ({e}); falling back to plain sync");
+                    report.fell_back = true;
+                    plain.extend(negentropy.drain(..));
+                }
+            }
+        }
+        if !plain.is_empty() {
+            if negentropy.is_empty() {
+                report.mode = SyncMode::Plain;
+            }
+            let (received, relays) = self.sync_notes_plain_from(plain).await?;
+            report.received += received;
+            report.relays = relays;
+        }
+        report.duration = started.elapsed();
+        Ok(report)
+    }
+
+    pub async fn sync_notes(&self) -> Result<Reconciliation> {
+        let urls = self.client.relays().await.into_keys();
+        self.sync_notes_with(urls).await
+    }
+
+    /// Reconcile with negentropy, letting the pool upload what relays lack.
+    /// What we lack is fetched by id and stored through
+    /// [`Dialog::save_events`] rather than one event at a time, which matters
+    /// most on the first sync of a fresh install.
+    async fn sync_notes_with(
+        &self,
+        urls: impl IntoIterator<Item = RelayUrl>,
+    ) -> Result<Reconciliation> {
+        let urls: Vec<RelayUrl> = urls.into_iter().collect();
+        let filter = self.notes_filter();
+
+        let output = self
+            .client
+            .sync_with(
+                urls.clone(),
+                filter,
+                &SyncOptions::default()
+                    .initial_timeout(self.fetch_timeout)
+                    .direction(SyncDirection::Up),
+            )
+            .await?;
+        let mut reconciliation = output.val;
+
+        let missing: Vec<EventId> = reconciliation.remote.iter().copied().collect();
+        let now = self.now();
+        let mut events = Vec::new();
+        for ids in missing.chunks(SYNC_FETCH_BATCH_SIZE) {
+            let filter = Filter::new().ids(ids.iter().copied());
+            let fetched = self
+                .client
+                .fetch_events_from(urls.clone(), vec![filter], Some(self.fetch_timeout))
+                .await?;
+            for event in fetched {
+                match validate_event(&event, &self.keys.public_key(), now) {
+                    Ok(()) => events.push(event),
+                    Err(reason) => self.quarantine.add(&event, reason, now),
+                }
+            }
+        }
+        reconciliation.received = events.iter().map(|event| event.id).collect();
+        let saved = self.save_events(events).await?;
+        eprintln!(
+            "[lib] sync_notes: {} missing locally, saved {saved}",
+            missing.len()
+        );
+        Ok(reconciliation)
+    }
+
+    /// Fetch recent notes with a regular REQ and store them locally.
+    /// Fallback for relays without negentropy support; returns the number
+    /// of events that were new to the local database.
+    pub async fn sync_notes_plain(&self) -> Result<usize> {
+        let urls = self.client.relays().await.into_keys();
+        Ok(self.sync_notes_plain_from(urls).await?.0)
+    }
+
+    /// Query every relay in `urls` at once and store the merged events.
+    /// Fails only if every relay does.
+    async fn sync_notes_plain_from(
+        &self,
+        urls: impl IntoIterator<Item = RelayUrl>,
+    ) -> Result<(usize, Vec<RelaySyncStats>)> {
+        let filter = self.notes_filter().limit(PLAIN_SYNC_LIMIT);
+
+        let mut tasks = JoinSet::new();
+        for url in urls {
+            let client = self.client.clone();
+            let filter = filter.clone();
+            let timeout = self.fetch_timeout;
+            tasks.spawn(async move {
+                let result = client
+                    .fetch_events_from([url.clone()], vec![filter], Some(timeout))
+                    .await;
+                (url, result)
+            });
+        }
+
+        let now = self.now();
+        // Each distinct event, with the number of relays that returned it
+        let mut merged: HashMap<EventId, (Event, usize)> = HashMap::new();
+        let mut fetched: Vec<(RelayUrl, std::result::Result<Vec<EventId>, String>)> = Vec::new();
+        while let Some(joined) = tasks.join_next().await {
+            let (url, result) = match joined {
+                Ok(joined) => joined,
+                Err(e) => {
+                    eprintln!("[lib] sync_notes_plain: relay task failed: {e}");
+                    continue;
+                }
+            };
+            let events = match result {
+                Ok(events) => events,
+                Err(e) => {
+                    eprintln!("[lib] sync_notes_plain: {url} failed: {e}");
+                    fetched.push((url, Err(e.to_string())));
+                    continue;
+                }
+            };
+            let mut ids = Vec::new();
+            for event in events {
+                if let Some((_, relays)) = merged.get_mut(&event.id) {
+                    *relays += 1;
+                    ids.push(event.id);
+                    continue;
+                }
+                match validate_event(&event, &self.keys.public_key(), now) {
+                    Ok(()) => {
+                        ids.push(event.id);
+                        merged.insert(event.id, (event, 1));
+                    }
+                    Err(reason) => self.quarantine.add(&event, reason, now),
+                }
+            }
+            fetched.push((url, Ok(ids)));
+        }
+
+        if !fetched.is_empty() && fetched.iter().all(|(_, result)| result.is_err()) {
+            let errors: Vec<String> = fetched
+                .into_iter()
+                .filter_map(|(url, result)| Some(format!("{url}: {}", result.err()?)))
+                .collect();
+            return Err(DialogError::Sync(errors.join("; ")));
+        }
+
+        let mut stats: Vec<RelaySyncStats> = fetched
+            .into_iter()
+            .map(|(url, result)| match result {
+                Ok(ids) => RelaySyncStats {
+                    url,
+                    fetched: ids.len(),
+                    exclusive: ids.iter().filter(|id| merged[*id].1 == 1).count(),
+                    error: None,
+                },
+                Err(error) => RelaySyncStats {
+                    url,
+                    fetched: 0,
+                    exclusive: 0,
+                    error: Some(error),
+                },
+            })
+            .collect();
+        stats.sort_by_key(|relay| relay.url.to_string());
+        eprintln!(
+            "[lib] sync_notes_plain: {} distinct events from {} relays",
+            merged.len(),
+            stats.len()
+        );
+
+        let events = merged.into_values().map(|(event, _)| event);
+        Ok((self.save_events(events).await?, stats))
+    }
+}
@@ -0,0 +1,226 @@
+use crate::{parse_hashtags, Dialog, DialogError, Result};
+use chrono::{DateTime, Local, Timelike};
+use nostr_sdk::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// What a new note gets for carrying a tag, e.g. `#fleeting` notes expire
+/// after 30 days. Stored with the tag's metadata, so it syncs with the rest
+/// of the app state and travels in [`crate::Settings`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct TagDefaults {
+    /// Relays drop the note this many days after it was written (NIP-40),
+    /// and it is hidden here from then on
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expire_after_days: Option<u32>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub pin: bool,
+    /// Only pin notes written between these local hours, start included
+    /// and end not, e.g. (9, 17). Wraps past midnight when start > end.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pin_hours: Option<(u8, u8)>,
+}
+
+impl TagDefaults {
+    pub fn is_empty(&self) -> bool {
+        *self == TagDefaults::default()
+    }
+
+    fn pins_at(&self, created_at: Timestamp) -> bool {
+        if !self.pin {
+            return false;
+        }
+        let Some((start, end)) = self.pin_hours else {
+            return true;
+        };
+        let Some(hour) = DateTime::from_timestamp(created_at.as_u64() as i64, 0)
+            .map(|at| at.with_timezone(&Local).hour() as u8)
+        else {
+            return false;
+        };
+        if start <= end {
+            (start..end).contains(&hour)
+        } else {
+            hour >= start || hour < end
+        }
+    }
+}
+
+/// The defaults of all a new note's tags, combined
+#[derive(Debug, Default)]
+pub(crate) struct CreationDefaults {
+    /// The soonest expiry of any tag
+    pub(crate) expires_at: Option<Timestamp>,
+    pub(crate) pin: bool,
+}
+
+/// When `event` expires (NIP-40), if ever
+pub(crate) fn expires_at(event: &Event) -> Option<Timestamp> {
+    event
+        .tags
+        .iter()
+        .find_map(|tag| match tag.as_standardized() {
+            Some(TagStandard::Expiration(at)) => Some(*at),
+            _ => None,
+        })
+}
+
+impl Dialog {
+    /// Set what new notes tagged `tag` get; empty defaults remove them.
+    /// Other tag metadata is kept.
+    pub async fn set_tag_defaults(&self, tag: &str, defaults: TagDefaults) -> Result<()> {
+        let Some(tag) = crate::normalize_tag(tag) else {
+            return Ok(());
+        };
+        if let Some((start, end)) = defaults.pin_hours {
+            if start > 23 || end > 24 || start == end {
+                return Err(DialogError::Config(format!(
+                    "invalid pin hours {start}-{end}"
+                )));
+            }
+        }
+        if defaults.expire_after_days == Some(0) {
+            return Err(DialogError::Config("expiry must be at least a day".into()));
+        }
+        let mut metadata = self
+            .app_state()
+            .await?
+            .tags
+            .get(&tag)
+            .map(|lww| lww.value.clone())
+            .unwrap_or_default();
+        metadata.defaults = defaults;
+        self.set_tag_metadata(&tag, metadata).await
+    }
+
+    /// Tags with defaults set
+    pub async fn tag_defaults(&self) -> Result<BTreeMap<String, TagDefaults>> {
+        Ok(self
+            .app_state()
+            .await?
+            .tags
+            .into_iter()
+            .filter(|(_, lww)| !lww.value.defaults.is_empty())
+            .map(|(tag, lww)| (tag, lww.value.defaults))
+            .collect())
+    }
+
+    /// What a note with `text` written at `created_at` gets from its tags.
+    /// Defaults that can't be read are skipped rather than failing the note.
+    pub(crate) async fn creation_defaults(
+        &self,
+        text: &str,
+        created_at: Timestamp,
+    ) -> CreationDefaults {
+        let defaults = match self.tag_defaults().await {
+            Ok(defaults) => defaults,
+            Err(e) => {
+                eprintln!("[lib] tag defaults unavailable: {e}");
+                return CreationDefaults::default();
+            }
+        };
+        let mut applied = CreationDefaults::default();
+        for tag in parse_hashtags(text) {
+            let Some(defaults) = defaults.get(&tag) else {
+                continue;
+            };
+            if let Some(days) = defaults.expire_after_days {
+                let at = Timestamp::from(created_at.as_u64() + u64::from(days) * 86_400);
+                applied.expires_at = Some(applied.expires_at.map_or(at, |soonest| soonest.min(at)));
+            }
+            applied.pin |= defaults.pins_at(created_at);
+        }
+        applied
+    }
+
+    /// Build a new note's event with its tags' defaults. It is pinned up
+    /// front, so a note that doesn't reach a relay is pinned too; returns
+    /// whether it was.
+    pub(crate) async fn build_new_note_event(
+        &self,
+        text: &str,
+        created_at: Timestamp,
+    ) -> Result<(Event, bool)> {
+        let defaults = self.creation_defaults(text, created_at).await;
+        let expiration = defaults.expires_at.map(Tag::expiration);
+        let event = self
+            .build_note_event_tagged(text, created_at, expiration)
+            .await?;
+        if defaults.pin {
+            self.set_pinned(&event.id, true).await?;
+        }
+        Ok((event, defaults.pin))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::memory_dialog;
+    use chrono::TimeZone;
+
+    #[tokio::test]
+    async fn test_tag_defaults_apply_at_creation() {
+        let dialog = memory_dialog();
+        let fleeting = TagDefaults {
+            expire_after_days: Some(30),
+            ..Default::default()
+        };
+        dialog
+            .set_tag_defaults("#Fleeting", fleeting)
+            .await
+            .unwrap();
+        let pinned = TagDefaults {
+            pin: true,
+            ..Default::default()
+        };
+        dialog.set_tag_defaults("work", pinned).await.unwrap();
+        assert_eq!(dialog.tag_defaults().await.unwrap().len(), 2);
+
+        let note = dialog.save_note("Passing thought #fleeting").await.unwrap();
+        let event = dialog.note_event(&note.id).await.unwrap();
+        let expected = Timestamp::from(note.created_at.as_u64() + 30 * 86_400);
+        assert_eq!(expires_at(&event), Some(expected));
+        assert!(!note.is_pinned);
+
+        let note = dialog.save_note("Standup notes #work").await.unwrap();
+        assert!(note.is_pinned);
+        assert!(dialog.note_state(&note.id).await.unwrap().is_pinned());
+        let event = dialog.note_event(&note.id).await.unwrap();
+        assert_eq!(expires_at(&event), None);
+
+        let nine_to_five = TagDefaults {
+            pin: true,
+            pin_hours: Some((9, 17)),
+            ..Default::default()
+        };
+        let at = |hour| {
+            let local = Local.with_ymd_and_hms(2024, 3, 4, hour, 30, 0).unwrap();
+            Timestamp::from(local.timestamp() as u64)
+        };
+        assert!(nine_to_five.pins_at(at(10)));
+        assert!(!nine_to_five.pins_at(at(20)));
+        let night_shift = TagDefaults {
+            pin_hours: Some((22, 6)),
+            ..nine_to_five.clone()
+        };
+        assert!(night_shift.pins_at(at(23)) && night_shift.pins_at(at(2)));
+        assert!(!night_shift.pins_at(at(12)));
+        assert!(dialog
+            .set_tag_defaults(
+                "work",
+                TagDefaults {
+                    pin_hours: Some((9, 9)),
+                    ..nine_to_five
+                }
+            )
+            .await
+            .is_err());
+
+        dialog
+            .set_tag_defaults("work", TagDefaults::default())
+            .await
+            .unwrap();
+        assert_eq!(dialog.tag_defaults().await.unwrap().len(), 1);
+    }
+}
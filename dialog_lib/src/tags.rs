@@ -0,0 +1,226 @@
+/// Longest tag (in characters) that will be extracted from note text
+pub const MAX_TAG_LEN: usize = 64;
+
+/// Extract hashtags from note text.
+///
+/// Rules:
+/// - a tag is a whitespace-separated word starting with `#`
+/// - the tag ends at the first punctuation character other than `_` or `-`,
+///   so `#tag,` and `#tag.` both yield `tag`
+/// - any unicode letters, digits and emoji are allowed; all-digit tags
+///   (`#1`) are ignored
+/// - tags are lowercased, de-duplicated (first occurrence wins) and
+///   dropped when longer than [`MAX_TAG_LEN`] characters
+pub fn parse_hashtags(text: &str) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+    for word in text.split_whitespace() {
+        let Some(rest) = word.strip_prefix('#') else {
+            continue;
+        };
+        let Some(tag) = clean_tag(rest) else {
+            continue;
+        };
+        if !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    tags
+}
+
+/// Normalize user-provided tag input (`#Work`, `work`, ` WORK `) to the
+/// stored form. Returns None if nothing tag-like remains.
+pub fn normalize_tag(input: &str) -> Option<String> {
+    let trimmed = input.trim();
+    clean_tag(trimmed.strip_prefix('#').unwrap_or(trimmed))
+}
+
+/// Remove hashtag words from text, keeping line breaks. Lines left empty
+/// only because they held tags are dropped.
+pub fn strip_hashtags(text: &str) -> String {
+    let mut lines = Vec::new();
+    for line in text.lines() {
+        let kept: Vec<&str> = line
+            .split_whitespace()
+            .filter(|word| word.strip_prefix('#').and_then(clean_tag).is_none())
+            .collect();
+        if kept.is_empty() && !line.trim().is_empty() {
+            continue;
+        }
+        lines.push(kept.join(" "));
+    }
+    lines.join("\n").trim().to_string()
+}
+
+/// Append the `tags` the text doesn't have yet. They join a trailing line
+/// of hashtags, or go on a line of their own.
+pub fn add_hashtags(text: &str, tags: &[String]) -> String {
+    let existing = parse_hashtags(text);
+    let mut missing: Vec<&String> = Vec::new();
+    for tag in tags {
+        if !existing.contains(tag) && !missing.contains(&tag) {
+            missing.push(tag);
+        }
+    }
+    if missing.is_empty() {
+        return text.to_string();
+    }
+    let text = text.trim_end();
+    let added: Vec<String> = missing.iter().map(|tag| format!("#{tag}")).collect();
+    let last_line = text.lines().next_back().unwrap_or_default();
+    let separator = if text.is_empty() {
+        ""
+    } else if is_tag_line(last_line) {
+        " "
+    } else {
+        "\n"
+    };
+    format!("{text}{separator}{}", added.join(" "))
+}
+
+/// Remove the words for `tags` from the text. Other lines are left exactly
+/// as they were; lines left empty by the removal are dropped.
+pub fn remove_hashtags(text: &str, tags: &[String]) -> String {
+    let is_removed = |word: &str| {
+        word.strip_prefix('#')
+            .and_then(clean_tag)
+            .is_some_and(|tag| tags.contains(&tag))
+    };
+    let mut lines = Vec::new();
+    for line in text.lines() {
+        if !line.split_whitespace().any(is_removed) {
+            lines.push(line.to_string());
+            continue;
+        }
+        let kept: Vec<&str> = line
+            .split_whitespace()
+            .filter(|&word| !is_removed(word))
+            .collect();
+        if !kept.is_empty() {
+            lines.push(kept.join(" "));
+        }
+    }
+    lines.join("\n").trim().to_string()
+}
+
+fn is_tag_line(line: &str) -> bool {
+    let mut words = line.split_whitespace().peekable();
+    words.peek().is_some() && words.all(|word| word.strip_prefix('#').and_then(clean_tag).is_some())
+}
+
+fn clean_tag(raw: &str) -> Option<String> {
+    let body: String = raw.chars().take_while(|c| !is_terminator(*c)).collect();
+    let body = body.trim_end_matches(['-', '_']);
+
+    if body.is_empty()
+        || body.chars().all(|c| c.is_ascii_digit())
+        || body.chars().count() > MAX_TAG_LEN
+    {
+        return None;
+    }
+    Some(body.to_lowercase())
+}
+
+// Non-ASCII punctuation that commonly follows a tag in prose
+const UNICODE_TERMINATORS: &[char] = &[
+    '…', '“', '”', '‘', '’', '«', '»', '、', '。', '，', '！', '？', '：', '；',
+];
+
+fn is_terminator(c: char) -> bool {
+    if c == '_' || c == '-' {
+        return false;
+    }
+    c.is_whitespace() || c.is_ascii_punctuation() || UNICODE_TERMINATORS.contains(&c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_hashtags() {
+        let text = "This is a #Test note with #Multiple #TAGS";
+        let tags = parse_hashtags(text);
+        assert_eq!(tags, vec!["test", "multiple", "tags"]);
+    }
+
+    #[test]
+    fn test_parse_hashtags_empty() {
+        let text = "This has no hashtags";
+        let tags = parse_hashtags(text);
+        assert!(tags.is_empty());
+    }
+
+    #[test]
+    fn test_parse_hashtags_just_hash() {
+        let text = "This has just # and nothing else";
+        let tags = parse_hashtags(text);
+        assert!(tags.is_empty());
+    }
+
+    #[test]
+    fn test_parse_hashtags_strips_punctuation() {
+        let tags = parse_hashtags("Buy #milk, #eggs. and (#bread) #well-known #snake_case! #tag-");
+        assert_eq!(
+            tags,
+            vec!["milk", "eggs", "well-known", "snake_case", "tag"]
+        );
+        // Only the leading hash counts; mid-word hashes are not tags
+        assert!(parse_hashtags("(#bread) issue#42").is_empty());
+    }
+
+    #[test]
+    fn test_parse_hashtags_unicode() {
+        let tags = parse_hashtags("#Café #日本語。 #Ünïcödé #🔥 #über…");
+        assert_eq!(tags, vec!["café", "日本語", "ünïcödé", "🔥", "über"]);
+    }
+
+    #[test]
+    fn test_parse_hashtags_dedupes_and_skips_numbers() {
+        let tags = parse_hashtags("#Work #work #WORK #1 #2024 #q3");
+        assert_eq!(tags, vec!["work", "q3"]);
+    }
+
+    #[test]
+    fn test_parse_hashtags_max_length() {
+        let ok = format!("#{}", "a".repeat(MAX_TAG_LEN));
+        let too_long = format!("#{}", "b".repeat(MAX_TAG_LEN + 1));
+        let tags = parse_hashtags(&format!("{ok} {too_long}"));
+        assert_eq!(tags, vec!["a".repeat(MAX_TAG_LEN)]);
+    }
+
+    #[test]
+    fn test_strip_hashtags() {
+        assert_eq!(
+            strip_hashtags("Ship it #work #q3\n\nMore soon #1\n#draft"),
+            "Ship it\n\nMore soon #1"
+        );
+        assert_eq!(strip_hashtags("no tags here"), "no tags here");
+    }
+
+    #[test]
+    fn test_add_and_remove_hashtags() {
+        let tags = |tags: &[&str]| tags.iter().map(|t| t.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            add_hashtags("Ship it\n#work", &tags(&["q3", "work"])),
+            "Ship it\n#work #q3"
+        );
+        assert_eq!(add_hashtags("Ship it", &tags(&["q3"])), "Ship it\n#q3");
+        assert_eq!(add_hashtags("Ship it #q3", &tags(&["q3"])), "Ship it #q3");
+
+        assert_eq!(
+            remove_hashtags("Ship  it #Work, now\n\n#q3 #work", &tags(&["work"])),
+            "Ship it now\n\n#q3"
+        );
+        assert_eq!(
+            remove_hashtags("Keep  spacing\n#work", &tags(&["work"])),
+            "Keep  spacing"
+        );
+    }
+
+    #[test]
+    fn test_normalize_tag() {
+        assert_eq!(normalize_tag(" #Work "), Some("work".to_string()));
+        assert_eq!(normalize_tag("work,"), Some("work".to_string()));
+        assert_eq!(normalize_tag("#"), None);
+    }
+}
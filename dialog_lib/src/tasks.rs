@@ -0,0 +1,122 @@
+use crate::app_state::Lww;
+use crate::audit::AuditAction;
+use crate::{Dialog, DialogError, Note, Result};
+use nostr_sdk::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Where a note stands as a task. Notes without a status are just notes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskStatus {
+    Todo,
+    Doing,
+    Done,
+}
+
+impl TaskStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TaskStatus::Todo => "todo",
+            TaskStatus::Doing => "doing",
+            TaskStatus::Done => "done",
+        }
+    }
+}
+
+impl fmt::Display for TaskStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Dialog {
+    /// Turn a note into a task, move it along, or make it a plain note
+    /// again with None. Kept with the note's other flags, so it follows
+    /// edits and syncs when app state sync is on.
+    pub async fn set_task_status(
+        &self,
+        note_id: &EventId,
+        status: Option<TaskStatus>,
+    ) -> Result<()> {
+        self.update_note_state(note_id, |state, now| {
+            state.task = Some(Lww::new(status, now))
+        })
+        .await?;
+        let detail = status.map_or("cleared", TaskStatus::as_str);
+        self.audit(AuditAction::SetTask, Some(note_id), detail);
+        Ok(())
+    }
+
+    /// Up to `limit` notes that are tasks, newest first; only those with
+    /// `status` when given. Archived notes are left out.
+    pub async fn list_tasks(&self, status: Option<TaskStatus>, limit: usize) -> Result<Vec<Note>> {
+        let events = self
+            .client
+            .database()
+            .query(vec![self.notes_filter()])
+            .await
+            .map_err(|e| DialogError::Database(e.to_string()))?;
+        Ok(self
+            .notes_from_events(events)
+            .await
+            .into_iter()
+            .filter(|note| !note.is_archived)
+            .filter(|note| match (note.task_status, status) {
+                (Some(task), Some(wanted)) => task == wanted,
+                (task, None) => task.is_some(),
+                (None, _) => false,
+            })
+            .take(limit)
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::memory_dialog;
+
+    #[tokio::test]
+    async fn test_task_status_lifecycle() {
+        let dialog = memory_dialog();
+        let call = dialog.save_note("Call the plumber").await.unwrap();
+        let report = dialog.save_note("Write the report #work").await.unwrap();
+        dialog.save_note("Just a thought").await.unwrap();
+
+        dialog
+            .set_task_status(&call.id, Some(TaskStatus::Todo))
+            .await
+            .unwrap();
+        dialog
+            .set_task_status(&report.id, Some(TaskStatus::Doing))
+            .await
+            .unwrap();
+        let tasks = dialog.list_tasks(None, 10).await.unwrap();
+        assert_eq!(tasks.len(), 2);
+        let doing = dialog
+            .list_tasks(Some(TaskStatus::Doing), 10)
+            .await
+            .unwrap();
+        assert_eq!(doing.len(), 1);
+        assert_eq!(doing[0].id, report.id);
+        assert_eq!(doing[0].task_status, Some(TaskStatus::Doing));
+
+        // Copies replacing a note keep its status
+        let retagged = dialog.add_tags(&[report.id], &["q3".into()]).await;
+        let (_, edited) = retagged.updated.into_iter().next().unwrap();
+        assert_eq!(edited.task_status, Some(TaskStatus::Doing));
+        dialog
+            .set_task_status(&edited.id, Some(TaskStatus::Done))
+            .await
+            .unwrap();
+        let done = dialog.list_tasks(Some(TaskStatus::Done), 10).await.unwrap();
+        assert_eq!(done.len(), 1);
+        assert_eq!(done[0].id, edited.id);
+
+        dialog.set_task_status(&call.id, None).await.unwrap();
+        let tasks = dialog.list_tasks(None, 10).await.unwrap();
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(dialog.note_state(&call.id).await.unwrap().task(), None);
+    }
+}
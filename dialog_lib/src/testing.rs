@@ -0,0 +1,52 @@
+//! An in-process relay for tests, so `cargo test` needs no external relay
+//! binary, fixed port or test key. Enabled with the `testing` feature.
+//!
+//! ```ignore
+//! let relay = TestRelay::run().await?;
+//! let keys = Keys::generate();
+//! let phone = relay.dialog_with_keys(keys.clone()).await?;
+//! let laptop = relay.dialog_with_keys(keys).await?;
+//! phone.create_note("Hello #test").await?;
+//! laptop.sync_notes().await?;
+//! ```
+
+use crate::{Dialog, DialogError, Result};
+use nostr_relay_builder::MockRelay;
+use nostr_sdk::prelude::*;
+
+/// A relay (with negentropy) on a free localhost port, shut down on drop
+pub struct TestRelay {
+    relay: MockRelay,
+}
+
+impl TestRelay {
+    pub async fn run() -> Result<Self> {
+        let relay = MockRelay::run()
+            .await
+            .map_err(|e| DialogError::Io(std::io::Error::other(e.to_string())))?;
+        Ok(Self { relay })
+    }
+
+    pub fn url(&self) -> String {
+        self.relay.url()
+    }
+
+    /// A Dialog with a fresh key and in-memory storage, connected to this relay
+    pub async fn dialog(&self) -> Result<Dialog> {
+        self.dialog_with_keys(Keys::generate()).await
+    }
+
+    /// Like [`TestRelay::dialog`] with a given key. Two of these with the
+    /// same key act as two devices of one user.
+    pub async fn dialog_with_keys(&self, keys: Keys) -> Result<Dialog> {
+        let dialog = Dialog::in_memory(keys);
+        dialog.connect_relay(&self.url()).await?;
+        Ok(dialog)
+    }
+}
+
+impl Drop for TestRelay {
+    fn drop(&mut self) {
+        self.relay.shutdown();
+    }
+}
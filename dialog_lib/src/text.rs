@@ -0,0 +1,126 @@
+use crate::tags::strip_hashtags;
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Length of [`preview`] in user-perceived characters, enough for a list row
+pub const PREVIEW_GRAPHEMES: usize = 200;
+
+/// Reading speed [`reading_minutes`] assumes
+pub const WORDS_PER_MINUTE: usize = 200;
+
+/// The first [`PREVIEW_GRAPHEMES`] characters of `text`, ending in "…" if
+/// anything was cut. Never splits an emoji or a combining sequence.
+pub fn preview(text: &str) -> String {
+    truncate_graphemes(text, PREVIEW_GRAPHEMES)
+}
+
+/// `text` cut to at most `max` grapheme clusters, plus "…" if shortened
+pub fn truncate_graphemes(text: &str, max: usize) -> String {
+    match text.grapheme_indices(true).nth(max) {
+        Some((end, _)) => format!("{}…", text[..end].trim_end()),
+        None => text.to_string(),
+    }
+}
+
+/// Number of user-perceived characters in `text`
+pub fn grapheme_len(text: &str) -> usize {
+    text.graphemes(true).count()
+}
+
+/// Words in `text`, hashtags left out. Each CJK character counts as a
+/// word.
+pub fn word_count(text: &str) -> usize {
+    strip_hashtags(text).unicode_words().count()
+}
+
+/// Minutes it takes to read `words` words, rounded up
+pub fn reading_minutes(words: usize) -> usize {
+    words.div_ceil(WORDS_PER_MINUTE)
+}
+
+/// `text` reduced for matching: compatibility forms unified (full-width
+/// letters, ligatures), accents dropped and case folded, so "cafe" finds
+/// "Café" and "strasse" finds "Straße".
+pub fn fold_for_search(text: &str) -> String {
+    let mut folded = String::with_capacity(text.len());
+    for c in text
+        .nfkd()
+        .filter(|c| !is_combining_mark(*c))
+        .flat_map(char::to_lowercase)
+    {
+        match c {
+            'ß' => folded.push_str("ss"),
+            'ς' => folded.push('σ'),
+            c => folded.push(c),
+        }
+    }
+    folded
+}
+
+/// A search string folded once, for matching against many notes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchQuery(String);
+
+impl SearchQuery {
+    pub fn new(query: &str) -> Self {
+        Self(fold_for_search(query.trim()))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Whether `text` contains the query, ignoring case and accents
+    pub fn matches(&self, text: &str) -> bool {
+        fold_for_search(text).contains(&self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_truncate_keeps_graphemes_whole() {
+        assert_eq!(truncate_graphemes("short", 10), "short");
+        assert_eq!(truncate_graphemes("hello world", 6), "hello…");
+        // Family emoji is one grapheme made of several code points
+        let family = "👨‍👩‍👧‍👦";
+        assert_eq!(
+            truncate_graphemes(&family.repeat(3), 2),
+            family.repeat(2) + "…"
+        );
+        assert_eq!(truncate_graphemes("e\u{301}e\u{301}", 1), "e\u{301}…");
+        assert_eq!(
+            preview(&"x".repeat(1000)).chars().count(),
+            PREVIEW_GRAPHEMES + 1
+        );
+    }
+
+    #[test]
+    fn test_search_ignores_case_and_accents() {
+        let query = SearchQuery::new(" cafe ");
+        assert!(query.matches("Meet at the Café"));
+        // Same word with a combining accent instead of a precomposed one
+        assert!(query.matches("CAFE\u{301} tomorrow"));
+        assert!(!query.matches("caffeine"));
+        assert!(SearchQuery::new("ÉCOLE").matches("école primaire"));
+        assert!(SearchQuery::new("strasse").matches("Hauptstraße 5"));
+        assert!(SearchQuery::new("ｎｏｔｅ").matches("note"));
+        assert!(SearchQuery::new("🎉").matches("Party 🎉 tonight"));
+        assert!(SearchQuery::new("Σοφία").matches("σοφια"));
+        assert!(SearchQuery::new("  ").is_empty());
+        assert_eq!(grapheme_len("e\u{301}👨‍👩‍👧‍👦"), 2);
+    }
+
+    #[test]
+    fn test_word_count_and_reading_time() {
+        assert_eq!(word_count("Call the plumber, then Anna's dentist #todo"), 6);
+        assert_eq!(word_count("#just #tags"), 0);
+        assert_eq!(word_count("東京に行く"), 5);
+        assert_eq!(reading_minutes(0), 0);
+        assert_eq!(reading_minutes(1), 1);
+        assert_eq!(reading_minutes(WORDS_PER_MINUTE * 3 + 1), 4);
+    }
+}
@@ -0,0 +1,171 @@
+use crate::Dialog;
+use nostr_sdk::prelude::*;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use thiserror::Error;
+
+/// Largest encrypted payload accepted from a relay
+pub const MAX_CONTENT_BYTES: usize = 256 * 1024;
+/// How far in the future an event may be dated before it is rejected
+pub const MAX_FUTURE_SECS: u64 = 24 * 60 * 60;
+/// Anything older than this (1990-01-01) is bogus. Imported notes keep
+/// their original dates, so this is well before nostr existed.
+pub const MIN_CREATED_AT: u64 = 631_152_000;
+/// Number of rejected events kept around for inspection
+const QUARANTINE_CAPACITY: usize = 256;
+
+/// Why an incoming event was not turned into a note
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum Rejection {
+    #[error("invalid id or signature")]
+    InvalidSignature,
+    #[error("unexpected author {0}")]
+    WrongAuthor(PublicKey),
+    #[error("content is {size} bytes (max {max})")]
+    ContentTooLarge { size: usize, max: usize },
+    #[error("created_at {0} is out of range")]
+    TimestampOutOfRange(Timestamp),
+    #[error("could not decrypt: {0}")]
+    Undecryptable(String),
+}
+
+/// An event set aside by validation, kept for inspection
+#[derive(Debug, Clone)]
+pub struct QuarantinedEvent {
+    pub event: Event,
+    pub reason: Rejection,
+    pub quarantined_at: Timestamp,
+}
+
+/// Check an incoming note event before it is decrypted or ingested
+pub fn validate_event(event: &Event, author: &PublicKey, now: Timestamp) -> Result<(), Rejection> {
+    if event.pubkey != *author {
+        return Err(Rejection::WrongAuthor(event.pubkey));
+    }
+    if event.content.len() > MAX_CONTENT_BYTES {
+        return Err(Rejection::ContentTooLarge {
+            size: event.content.len(),
+            max: MAX_CONTENT_BYTES,
+        });
+    }
+    let created_at = event.created_at.as_u64();
+    if created_at < MIN_CREATED_AT || created_at > now.as_u64() + MAX_FUTURE_SECS {
+        return Err(Rejection::TimestampOutOfRange(event.created_at));
+    }
+    event.verify().map_err(|_| Rejection::InvalidSignature)
+}
+
+/// Bounded, de-duplicated store of rejected events (oldest dropped first)
+#[derive(Debug, Default)]
+pub(crate) struct Quarantine {
+    events: Mutex<VecDeque<QuarantinedEvent>>,
+}
+
+impl Quarantine {
+    pub(crate) fn add(&self, event: &Event, reason: Rejection, now: Timestamp) {
+        let mut events = self.events.lock().unwrap();
+        if events.iter().any(|q| q.event.id == event.id) {
+            return;
+        }
+        eprintln!("[lib] quarantining event {}: {reason}", event.id);
+        events.push_back(QuarantinedEvent {
+            event: event.clone(),
+            reason,
+            quarantined_at: now,
+        });
+        if events.len() > QUARANTINE_CAPACITY {
+            events.pop_front();
+        }
+    }
+
+    pub(crate) fn list(&self) -> Vec<QuarantinedEvent> {
+        self.events.lock().unwrap().iter().cloned().collect()
+    }
+}
+
+impl Dialog {
+    /// Events that failed validation or decryption, oldest first
+    pub fn quarantined(&self) -> Vec<QuarantinedEvent> {
+        self.quarantine.list()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signed(keys: &Keys, content: &str, created_at: u64) -> Event {
+        EventBuilder::new(Kind::from(1059), content)
+            .custom_created_at(Timestamp::from(created_at))
+            .sign_with_keys(keys)
+            .unwrap()
+    }
+
+    const NOW: u64 = 1_700_000_000;
+
+    #[test]
+    fn test_validate_event_accepts_valid() {
+        let keys = Keys::generate();
+        let event = signed(&keys, "ciphertext", NOW);
+        assert_eq!(
+            validate_event(&event, &keys.public_key(), Timestamp::from(NOW)),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_validate_event_rejections() {
+        let keys = Keys::generate();
+        let now = Timestamp::from(NOW);
+
+        let other = Keys::generate();
+        let event = signed(&other, "ciphertext", NOW);
+        assert_eq!(
+            validate_event(&event, &keys.public_key(), now),
+            Err(Rejection::WrongAuthor(other.public_key()))
+        );
+
+        let event = signed(&keys, &"x".repeat(MAX_CONTENT_BYTES + 1), NOW);
+        assert!(matches!(
+            validate_event(&event, &keys.public_key(), now),
+            Err(Rejection::ContentTooLarge { .. })
+        ));
+
+        for created_at in [1_000, NOW + MAX_FUTURE_SECS + 1] {
+            let event = signed(&keys, "ciphertext", created_at);
+            assert!(matches!(
+                validate_event(&event, &keys.public_key(), now),
+                Err(Rejection::TimestampOutOfRange(_))
+            ));
+        }
+
+        // Same id and signature, different content
+        let mut event = signed(&keys, "ciphertext", NOW);
+        event.content = "tampered".to_string();
+        assert_eq!(
+            validate_event(&event, &keys.public_key(), now),
+            Err(Rejection::InvalidSignature)
+        );
+    }
+
+    #[test]
+    fn test_quarantine_dedupes_and_is_bounded() {
+        let keys = Keys::generate();
+        let quarantine = Quarantine::default();
+        let first = signed(&keys, "first", NOW);
+        quarantine.add(&first, Rejection::InvalidSignature, Timestamp::from(NOW));
+        quarantine.add(&first, Rejection::InvalidSignature, Timestamp::from(NOW));
+        assert_eq!(quarantine.list().len(), 1);
+
+        for i in 0..QUARANTINE_CAPACITY as u64 {
+            quarantine.add(
+                &signed(&keys, "more", NOW + i + 1),
+                Rejection::InvalidSignature,
+                Timestamp::from(NOW),
+            );
+        }
+        let list = quarantine.list();
+        assert_eq!(list.len(), QUARANTINE_CAPACITY);
+        assert!(list.iter().all(|q| q.event.id != first.id));
+    }
+}
@@ -0,0 +1,247 @@
+use crate::audit::AuditAction;
+use crate::kinds::APP_DATA_KIND;
+use crate::{Attachment, Dialog, DialogError, Note, Result};
+use nostr_sdk::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// d-tag prefix of the encrypted audio/transcript event for one note
+const VOICE_D_TAG: &str = "dialog_voice";
+/// NIP-78 events scanned for voice data per sync
+const VOICE_SYNC_LIMIT: usize = 500;
+
+/// Audio and transcript for a voice note, kept in their own encrypted
+/// NIP-78 event so the transcript can arrive after the note was created
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct VoiceData {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audio: Option<Attachment>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transcript: Option<String>,
+}
+
+impl Dialog {
+    /// Create a note for an uploaded voice memo. `text` is the caption shown
+    /// until (and alongside) the transcript; hashtags in it apply as usual.
+    pub async fn create_voice_note(&self, text: &str, audio: Attachment) -> Result<Note> {
+        let mut note = self.create_note(text).await?;
+        let data = VoiceData {
+            audio: Some(audio),
+            transcript: None,
+        };
+        self.store_voice_data(&note.id, &data, None).await?;
+        note.audio = data.audio;
+        Ok(note)
+    }
+
+    /// Attach a transcript to a note, making it searchable. Called by the
+    /// app once its transcription service is done with the note's audio.
+    pub async fn set_transcript(&self, note_id: &EventId, transcript: &str) -> Result<()> {
+        if self.get_note(note_id).await?.is_none() {
+            return Err(DialogError::NoteNotFound(*note_id));
+        }
+        let (mut data, previous) = match self.voice_event(note_id).await? {
+            Some((data, created_at)) => (data, Some(created_at)),
+            None => (VoiceData::default(), None),
+        };
+        let transcript = transcript.trim();
+        data.transcript = (!transcript.is_empty()).then(|| transcript.to_string());
+        self.store_voice_data(note_id, &data, previous).await?;
+        self.audit(
+            AuditAction::SetTranscript,
+            Some(note_id),
+            format!("{} chars", transcript.chars().count()),
+        );
+        Ok(())
+    }
+
+    /// Voice data for a single note
+    pub async fn voice_data(&self, note_id: &EventId) -> Result<Option<VoiceData>> {
+        Ok(self.voice_event(note_id).await?.map(|(data, _)| data))
+    }
+
+    /// Voice data for many notes in one query, keyed by note id
+    pub(crate) async fn voice_data_for(
+        &self,
+        note_ids: &[EventId],
+    ) -> Result<HashMap<EventId, VoiceData>> {
+        if note_ids.is_empty() {
+            return Ok(HashMap::new());
+        }
+        let filter = Filter::new()
+            .author(self.keys.public_key())
+            .kind(Kind::from(APP_DATA_KIND))
+            .identifiers(note_ids.iter().map(voice_d_tag));
+        let events = self
+            .client
+            .database()
+            .query(vec![filter])
+            .await
+            .map_err(|e| DialogError::Database(e.to_string()))?;
+
+        let mut newest: HashMap<EventId, (Timestamp, VoiceData)> = HashMap::new();
+        for event in events {
+            let Some(note_id) = voice_note_id(&event) else {
+                continue;
+            };
+            if newest
+                .get(&note_id)
+                .is_some_and(|(at, _)| *at >= event.created_at)
+            {
+                continue;
+            }
+            match self.decode_private_record(&event) {
+                Ok(data) => {
+                    newest.insert(note_id, (event.created_at, data));
+                }
+                Err(e) => eprintln!("[lib] voice event {} unreadable: {e}", event.id),
+            }
+        }
+        Ok(newest
+            .into_iter()
+            .map(|(id, (_, data))| (id, data))
+            .collect())
+    }
+
+    /// Pull voice events published by other devices into the local
+    /// database. Returns how many were new.
+    pub(crate) async fn sync_voice_data(&self) -> Result<usize> {
+        let filter = Filter::new()
+            .author(self.keys.public_key())
+            .kind(Kind::from(APP_DATA_KIND))
+            .limit(VOICE_SYNC_LIMIT);
+        let events = self
+            .client
+            .fetch_events(vec![filter], Some(self.fetch_timeout))
+            .await?;
+        self.save_events(events.into_iter().filter(|e| voice_note_id(e).is_some()))
+            .await
+    }
+
+    /// Remove a note's voice data locally and ask relays to delete it,
+    /// e.g. once the note was replaced by a copy that mustn't carry it
+    pub(crate) async fn remove_voice_data(&self, note_id: &EventId) -> Result<()> {
+        let filter = Filter::new()
+            .author(self.keys.public_key())
+            .kind(Kind::from(APP_DATA_KIND))
+            .identifier(voice_d_tag(note_id));
+        let events = self
+            .client
+            .database()
+            .query(vec![filter.clone()])
+            .await
+            .map_err(|e| DialogError::Database(e.to_string()))?;
+        if events.is_empty() {
+            return Ok(());
+        }
+        self.client
+            .database()
+            .delete(filter)
+            .await
+            .map_err(|e| DialogError::Database(e.to_string()))?;
+        let ids: Vec<EventId> = events.iter().map(|event| event.id).collect();
+        let request = self.deletion_request(&ids).await?;
+        if let Err(e) = self.publish(request).await {
+            eprintln!("[lib] voice data for {note_id}: deletion request not sent: {e}");
+        }
+        Ok(())
+    }
+
+    async fn voice_event(&self, note_id: &EventId) -> Result<Option<(VoiceData, Timestamp)>> {
+        self.private_record(&voice_d_tag(note_id)).await
+    }
+
+    /// Save the voice event locally and publish it so other devices get the
+    /// audio and transcript. `previous` is the created_at of the event being
+    /// replaced, so the replacement always sorts after it.
+    pub(crate) async fn store_voice_data(
+        &self,
+        note_id: &EventId,
+        data: &VoiceData,
+        previous: Option<Timestamp>,
+    ) -> Result<()> {
+        let event = self
+            .private_record_event(voice_d_tag(note_id), data, previous)
+            .await?;
+
+        match self.publish(event).await {
+            Ok(_) => Ok(()),
+            Err(DialogError::Database(e)) => Err(DialogError::Database(e)),
+            // Saved locally; the next sync sends it
+            Err(e) => {
+                eprintln!("[lib] voice data for {note_id} not published: {e}");
+                Ok(())
+            }
+        }
+    }
+}
+
+fn voice_d_tag(note_id: &EventId) -> String {
+    format!("{VOICE_D_TAG}:{}", note_id.to_hex())
+}
+
+/// The note a voice event belongs to, if it is one
+fn voice_note_id(event: &Event) -> Option<EventId> {
+    event.tags.iter().find_map(|tag| {
+        let Some(TagStandard::Identifier(d)) = tag.as_standardized() else {
+            return None;
+        };
+        EventId::from_hex(d.strip_prefix(VOICE_D_TAG)?.strip_prefix(':')?).ok()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::memory_dialog;
+
+    fn memo() -> Attachment {
+        Attachment {
+            url: "https://blossom.example/abc.m4a".into(),
+            mime_type: "audio/m4a".into(),
+            size: 48_000,
+            sha256: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_transcript_makes_voice_note_searchable() {
+        let dialog = memory_dialog();
+        let note = dialog.save_note("Voice memo #ideas").await.unwrap();
+        let data = VoiceData {
+            audio: Some(memo()),
+            transcript: None,
+        };
+        dialog
+            .store_voice_data(&note.id, &data, None)
+            .await
+            .unwrap();
+        assert!(dialog
+            .search_notes("groceries", 10)
+            .await
+            .unwrap()
+            .is_empty());
+
+        dialog
+            .set_transcript(&note.id, " remember the groceries ")
+            .await
+            .unwrap();
+        let found = dialog.search_notes("groceries", 10).await.unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].audio, Some(memo()));
+        assert_eq!(
+            found[0].transcript.as_deref(),
+            Some("remember the groceries")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_set_transcript_requires_note() {
+        let dialog = memory_dialog();
+        let missing = EventId::all_zeros();
+        assert!(matches!(
+            dialog.set_transcript(&missing, "hello").await,
+            Err(DialogError::NoteNotFound(id)) if id == missing
+        ));
+    }
+}
@@ -1,123 +1,624 @@
-use crate::{Dialog, Note, Result};
+use crate::debug::DebugFeed;
+use crate::kinds::KindFeature;
+use crate::lock::mask_locked;
+use crate::messages::{gift_wrap_filter, unwrap_message, GIFT_WRAP_BACKDATE_SECS};
+use crate::migrate::{is_edited, migrated_from, superseded_at};
+use crate::note::shown_word_count;
+use crate::privacy::{open_note, MAX_TIMESTAMP_FUZZ_SECS};
+use crate::validate::{validate_event, Quarantine, Rejection};
+use crate::{normalize_tag, Clock, Dialog, DirectMessage, Note, Result};
 use nostr_sdk::prelude::*;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
 use tokio::sync::mpsc;
 
+/// Number of recent event ids remembered for de-duplication
+const SEEN_CAPACITY: usize = 4096;
+/// Initial and maximum delay between resubscribe attempts
+const RESUBSCRIBE_DELAY: Duration = Duration::from_secs(1);
+const MAX_RESUBSCRIBE_DELAY: Duration = Duration::from_secs(60);
+/// Look-back applied to catch-up queries to cover relay clock differences
+const CATCH_UP_MARGIN_SECS: u64 = 60;
+const CATCH_UP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// What a watch follows
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WatchSpec {
+    /// Every new note of ours
+    Notes,
+    /// New notes carrying this tag
+    Tag(String),
+    /// Private messages from other people
+    Messages,
+}
+
+/// Something a watch delivered
+#[derive(Debug, Clone)]
+pub enum WatchItem {
+    Note(Note),
+    Message(DirectMessage),
+}
+
+/// A running watch. Dropping it (or [`Dialog::cancel_watch`] with its name)
+/// stops the watch and closes its relay subscription.
+pub struct WatchHandle {
+    name: Option<String>,
+    spec: WatchSpec,
+    sub_id: SubscriptionId,
+    rx: mpsc::Receiver<WatchItem>,
+    task: tokio::task::JoinHandle<()>,
+    client: Client,
+    registry: Option<Arc<WatchRegistry>>,
+}
+
+impl WatchHandle {
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn spec(&self) -> &WatchSpec {
+        &self.spec
+    }
+
+    /// Next delivered item; None once the watch has been cancelled
+    pub async fn recv(&mut self) -> Option<WatchItem> {
+        self.rx.recv().await
+    }
+}
+
+impl Drop for WatchHandle {
+    fn drop(&mut self) {
+        self.task.abort();
+        if let (Some(registry), Some(name)) = (&self.registry, &self.name) {
+            registry.remove(name, &self.sub_id);
+        }
+        if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+            let client = self.client.clone();
+            let sub_id = self.sub_id.clone();
+            runtime.spawn(async move {
+                let _ = client.unsubscribe(sub_id).await;
+            });
+        }
+    }
+}
+
+struct RegisteredWatch {
+    spec: WatchSpec,
+    sub_id: SubscriptionId,
+    task: tokio::task::AbortHandle,
+}
+
+/// Named watches currently running
+#[derive(Default)]
+pub(crate) struct WatchRegistry {
+    watches: Mutex<HashMap<String, RegisteredWatch>>,
+    // Ids remembered by every running watch, named or not
+    pub(crate) seen_ids: Arc<AtomicUsize>,
+}
+
+impl WatchRegistry {
+    /// Forget `name` if it still refers to the watch with `sub_id`
+    fn remove(&self, name: &str, sub_id: &SubscriptionId) {
+        let mut watches = self.watches.lock().unwrap();
+        if watches.get(name).is_some_and(|w| w.sub_id == *sub_id) {
+            watches.remove(name);
+        }
+    }
+}
+
 impl Dialog {
+    /// Start a named watch. Any number can run side by side; starting one
+    /// under a name already in use replaces the old watch.
+    ///
+    /// Each watch keeps its own relay subscription and resubscribes (and
+    /// back-fills what it missed) when the connection drops.
+    pub async fn watch(&self, name: &str, spec: WatchSpec) -> Result<WatchHandle> {
+        self.cancel_watch(name).await;
+        let mut handle = self.start_watch(spec).await?;
+        handle.name = Some(name.to_string());
+        handle.registry = Some(self.watches.clone());
+        self.watches.watches.lock().unwrap().insert(
+            name.to_string(),
+            RegisteredWatch {
+                spec: handle.spec.clone(),
+                sub_id: handle.sub_id.clone(),
+                task: handle.task.abort_handle(),
+            },
+        );
+        eprintln!("[lib] watch: started {name} ({:?})", handle.spec);
+        Ok(handle)
+    }
+
+    /// Running named watches, by name
+    pub fn watches(&self) -> Vec<(String, WatchSpec)> {
+        let mut watches: Vec<(String, WatchSpec)> = self
+            .watches
+            .watches
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(name, watch)| (name.clone(), watch.spec.clone()))
+            .collect();
+        watches.sort_by(|a, b| a.0.cmp(&b.0));
+        watches
+    }
+
+    /// Stop a named watch; its handle then receives None.
+    /// Returns false if no watch has that name.
+    pub async fn cancel_watch(&self, name: &str) -> bool {
+        let Some(watch) = self.watches.watches.lock().unwrap().remove(name) else {
+            return false;
+        };
+        watch.task.abort();
+        let _ = self.client.unsubscribe(watch.sub_id).await;
+        eprintln!("[lib] watch: cancelled {name}");
+        true
+    }
+
     pub async fn watch_notes(&self) -> Result<mpsc::Receiver<Note>> {
+        let mut handle = self.start_watch(WatchSpec::Notes).await?;
         let (tx, rx) = mpsc::channel(100);
+        tokio::spawn(async move {
+            while let Some(item) = handle.recv().await {
+                if let WatchItem::Note(note) = item {
+                    if tx.send(note).await.is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+        Ok(rx)
+    }
 
-        let client = self.client.clone();
-        let keys = self.keys.clone();
-        let pubkey = self.keys.public_key();
+    /// Stream private messages as they arrive
+    pub async fn watch_messages(&self) -> Result<mpsc::Receiver<DirectMessage>> {
+        let mut handle = self.start_watch(WatchSpec::Messages).await?;
+        let (tx, rx) = mpsc::channel(100);
+        tokio::spawn(async move {
+            while let Some(item) = handle.recv().await {
+                if let WatchItem::Message(message) = item {
+                    if tx.send(message).await.is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+        Ok(rx)
+    }
 
-        // Set up subscription
-        let filter = Filter::new()
-            .author(pubkey)
-            .kind(Kind::from(1059))
-            .since(Timestamp::now());
+    async fn start_watch(&self, spec: WatchSpec) -> Result<WatchHandle> {
+        self.require_feature(match spec {
+            WatchSpec::Messages => KindFeature::Messages,
+            WatchSpec::Notes | WatchSpec::Tag(_) => KindFeature::Notes,
+        })?;
+        let (tx, rx) = mpsc::channel(100);
+        let spec = match spec {
+            WatchSpec::Tag(tag) => WatchSpec::Tag(normalize_tag(&tag).unwrap_or(tag)),
+            spec => spec,
+        };
 
-        eprintln!("DEBUG: Creating subscription with filter: {filter:?}");
-        let output = self.client.subscribe(vec![filter], None).await?;
-        let sub_id = output.val;
-        eprintln!("DEBUG: Subscription created with id: {sub_id}");
+        let now = self.now();
+        let watcher = Watcher {
+            client: self.client.clone(),
+            keys: self.keys.clone(),
+            spec: spec.clone(),
+            kinds: self.kinds_for(KindFeature::Notes),
+            sub_id: SubscriptionId::generate(),
+            seen: RecentIds::new(SEEN_CAPACITY).counted(self.watches.seen_ids.clone()),
+            last_seen: now,
+            started_at: now,
+            quarantine: self.quarantine.clone(),
+            clock: self.clock.clone(),
+            debug: self.debug_feed.clone(),
+            tx,
+        };
+        watcher.subscribe().await?;
+        eprintln!("DEBUG: Subscription created with id: {}", watcher.sub_id);
 
-        tokio::spawn(async move {
-            let mut notifications = client.notifications();
-            let mut seen_ids = HashSet::new();
+        let sub_id = watcher.sub_id.clone();
+        let task = tokio::spawn(watcher.run());
+        Ok(WatchHandle {
+            name: None,
+            spec,
+            sub_id,
+            rx,
+            task,
+            client: self.client.clone(),
+            registry: None,
+        })
+    }
+}
+
+/// Background task feeding new notes or messages into a watch channel.
+///
+/// Survives notification stream failures by resubscribing and back-filling
+/// anything published while it was disconnected.
+struct Watcher {
+    client: Client,
+    keys: Keys,
+    spec: WatchSpec,
+    kinds: Vec<Kind>,
+    sub_id: SubscriptionId,
+    seen: RecentIds,
+    // Newest created_at delivered so far; catch-up queries start here
+    last_seen: Timestamp,
+    // Notes written before the watch started are not delivered
+    started_at: Timestamp,
+    quarantine: Arc<Quarantine>,
+    clock: Arc<dyn Clock>,
+    debug: Arc<DebugFeed>,
+    tx: mpsc::Sender<WatchItem>,
+}
+
+impl Watcher {
+    fn filter(&self, since: Timestamp) -> Filter {
+        if self.spec == WatchSpec::Messages {
+            let since = since.as_u64().saturating_sub(GIFT_WRAP_BACKDATE_SECS);
+            return gift_wrap_filter(&self.keys).since(Timestamp::from(since));
+        }
+        // Private notes may be dated up to the fuzz window in the past
+        let since = since.as_u64().saturating_sub(MAX_TIMESTAMP_FUZZ_SECS);
+        Filter::new()
+            .author(self.keys.public_key())
+            .kinds(self.kinds.clone())
+            .since(Timestamp::from(since))
+    }
 
-            eprintln!("DEBUG: Watch task started, entering loop");
+    async fn subscribe(&self) -> Result<()> {
+        let filter = self.filter(self.last_seen);
+        eprintln!("DEBUG: Creating subscription with filter: {filter:?}");
+        let req = ClientMessage::req(self.sub_id.clone(), vec![filter.clone()]);
+        self.debug.sent(self.clock.now(), None, &req);
+        self.client
+            .subscribe_with_id(self.sub_id.clone(), vec![filter], None)
+            .await?;
+        Ok(())
+    }
+
+    async fn run(mut self) {
+        let mut delay = RESUBSCRIBE_DELAY;
+        eprintln!("DEBUG: Watch task started, entering loop");
+        loop {
+            let mut notifications = self.client.notifications();
             loop {
-                eprintln!("DEBUG: Waiting for notification...");
-                match notifications.recv().await {
-                    Ok(RelayPoolNotification::Message { message, .. }) => {
-                        if let RelayMessage::Event {
-                            subscription_id,
-                            event,
-                        } = message
-                        {
-                            eprintln!(
-                                "DEBUG: Got event from subscription: {subscription_id} (our id: {sub_id})",
-                            );
-                            if subscription_id == sub_id
-                                && event.kind == Kind::from(1059)
-                                && event.pubkey == pubkey
-                                && !seen_ids.contains(&event.id)
-                            {
-                                if let Ok(decrypted) = decrypt_event(&keys, &event) {
-                                    let note = Note {
-                                        id: event.id,
-                                        text: decrypted,
-                                        tags: extract_tags(&event),
-                                        created_at: event.created_at,
-                                        is_read: false,  // New notes are unread
-                                        is_synced: true, // If we got it from relay, it's synced
-                                    };
-
-                                    seen_ids.insert(event.id);
-                                    let _ = tx.send(note).await;
-                                    eprintln!("DEBUG: Sent note to channel");
-                                }
-                            }
-                        }
+                let event = match notifications.recv().await {
+                    Ok(RelayPoolNotification::Message {
+                        message:
+                            RelayMessage::Event {
+                                subscription_id,
+                                event,
+                            },
+                        ..
+                    }) if subscription_id == self.sub_id => event,
+                    Ok(RelayPoolNotification::Event { event, .. }) => event,
+                    Ok(RelayPoolNotification::Shutdown) => {
+                        eprintln!("DEBUG: Relay pool shut down; watch loop exiting");
+                        return;
                     }
-                    Ok(RelayPoolNotification::Event { event, .. }) => {
-                        // Try the old pattern too just in case
-                        eprintln!("DEBUG: Got direct event notification");
-                        if event.kind == Kind::from(1059)
-                            && event.pubkey == pubkey
-                            && !seen_ids.contains(&event.id)
-                        {
-                            if let Ok(decrypted) = decrypt_event(&keys, &event) {
-                                let note = Note {
-                                    id: event.id,
-                                    text: decrypted,
-                                    tags: extract_tags(&event),
-                                    created_at: event.created_at,
-                                    is_read: false,  // New notes are unread
-                                    is_synced: true, // If we got it from relay, it's synced
-                                };
-
-                                seen_ids.insert(event.id);
-                                let _ = tx.send(note).await;
-                            }
+                    Ok(_) => continue,
+                    Err(RecvError::Lagged(skipped)) => {
+                        // Stream is still alive, but we may have missed notes
+                        eprintln!("DEBUG: Watch lagged by {skipped} notifications; catching up");
+                        if !self.catch_up().await {
+                            return;
                         }
-                    }
-                    Ok(other) => {
-                        eprintln!("DEBUG: Got other notification: {other:?}");
                         continue;
                     }
-                    Err(e) => {
-                        eprintln!("DEBUG: Error receiving notification: {e:?}");
+                    Err(RecvError::Closed) => {
+                        eprintln!("DEBUG: Notification stream closed");
                         break;
                     }
+                };
+
+                if !self.deliver(&event).await {
+                    eprintln!("DEBUG: Watch receiver dropped; exiting");
+                    return;
                 }
+                delay = RESUBSCRIBE_DELAY;
             }
-            eprintln!("DEBUG: Watch loop exited!");
-        });
 
-        eprintln!("DEBUG: Returning receiver");
-        Ok(rx)
+            if self.tx.is_closed() {
+                return;
+            }
+            self.clock.sleep(delay).await;
+            delay = (delay * 2).min(MAX_RESUBSCRIBE_DELAY);
+
+            if let Err(e) = self.subscribe().await {
+                eprintln!("DEBUG: Resubscribe failed: {e}; retrying in {delay:?}");
+                continue;
+            }
+            if !self.catch_up().await {
+                return;
+            }
+        }
+    }
+
+    /// Query for notes published since the last one we delivered.
+    /// Returns false once the receiving side has gone away.
+    async fn catch_up(&mut self) -> bool {
+        let since = Timestamp::from(self.last_seen.as_u64().saturating_sub(CATCH_UP_MARGIN_SECS));
+        let events = match self
+            .client
+            .fetch_events(vec![self.filter(since)], Some(CATCH_UP_TIMEOUT))
+            .await
+        {
+            Ok(events) => events,
+            Err(e) => {
+                eprintln!("DEBUG: Catch-up query failed: {e}");
+                return !self.tx.is_closed();
+            }
+        };
+
+        let mut events: Vec<Event> = events.into_iter().collect();
+        events.sort_by_key(|e| e.created_at);
+        for event in events {
+            if !self.deliver(&event).await {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Forward an event to the channel if it's new and matches the watch.
+    /// Returns false once the receiving side has gone away.
+    async fn deliver(&mut self, event: &Event) -> bool {
+        if self.spec == WatchSpec::Messages {
+            return self.deliver_message(event).await;
+        }
+        if !self.kinds.contains(&event.kind)
+            || event.pubkey != self.keys.public_key()
+            || self.seen.contains(&event.id)
+        {
+            return true;
+        }
+
+        if let Err(reason) = validate_event(event, &self.keys.public_key(), self.clock.now()) {
+            self.quarantine.add(event, reason, self.clock.now());
+            return true;
+        }
+        let opened = match open_note(&self.keys, event) {
+            Ok(opened) => opened,
+            Err(e) => {
+                self.quarantine.add(
+                    event,
+                    Rejection::Undecryptable(e.to_string()),
+                    self.clock.now(),
+                );
+                return true;
+            }
+        };
+        // Edited copies keep their note's date, so go by when they were made
+        let written_at = superseded_at(event).unwrap_or(opened.created_at);
+        if written_at < self.started_at {
+            return true;
+        }
+        if let WatchSpec::Tag(tag) = &self.spec {
+            if !opened.tags.contains(tag) {
+                return true;
+            }
+        }
+        let (text, tags, is_locked) = mask_locked(opened.text, opened.tags);
+        let word_count = shown_word_count(&text);
+        let edit_of = migrated_from(event);
+        let edited = edit_of.is_some() && self.is_edited(event).await;
+        let note = Note {
+            id: event.id,
+            text,
+            tags,
+            created_at: opened.created_at,
+            is_read: false, // New notes are unread
+            is_pinned: false,
+            is_archived: false,
+            public_id: None,
+            audio: None,
+            transcript: None,
+            is_locked,
+            is_synced: true, // If we got it from relay, it's synced
+            is_hydrated: true,
+            edited,
+            edit_of,
+            task_status: None,
+            word_count,
+        };
+
+        self.seen.insert(event.id);
+        self.last_seen = self.last_seen.max(event.created_at);
+        if self.tx.send(WatchItem::Note(note)).await.is_err() {
+            return false;
+        }
+        eprintln!("DEBUG: Sent note to channel");
+        true
+    }
+
+    /// [`is_edited`] with the earlier copies from the local database
+    async fn is_edited(&self, event: &Event) -> bool {
+        let mut chain: Vec<Event> = Vec::new();
+        let mut next = migrated_from(event);
+        while let Some(id) = next {
+            let filter = Filter::new().id(id);
+            let Ok(events) = self.client.database().query(vec![filter]).await else {
+                break;
+            };
+            let Some(original) = events.into_iter().next() else {
+                break;
+            };
+            next = migrated_from(&original);
+            chain.push(original);
+        }
+        is_edited(event, |id| chain.iter().find(|original| original.id == *id))
+    }
+
+    async fn deliver_message(&mut self, event: &Event) -> bool {
+        if !self.seen.insert(event.id) {
+            return true;
+        }
+        let Some(message) =
+            unwrap_message(&self.keys, &self.quarantine, event, self.clock.now()).await
+        else {
+            return true;
+        };
+        self.last_seen = self.last_seen.max(event.created_at);
+        self.tx.send(WatchItem::Message(message)).await.is_ok()
     }
 }
 
-// Helper function to decrypt events
-fn decrypt_event(keys: &Keys, event: &Event) -> Result<String> {
-    let decrypted = nip44::decrypt(keys.secret_key(), &keys.public_key(), &event.content)?;
-    Ok(decrypted)
+/// Fixed-capacity set of recently seen ids; the oldest id is forgotten first
+pub(crate) struct RecentIds {
+    ids: HashSet<EventId>,
+    order: VecDeque<EventId>,
+    capacity: usize,
+    // Shared with other sets, for runtime stats
+    live: Option<Arc<AtomicUsize>>,
 }
 
-// Helper function to extract tags
-fn extract_tags(event: &Event) -> Vec<String> {
-    event
-        .tags
-        .iter()
-        .filter_map(|tag| {
-            if let Some(TagStandard::Hashtag(t)) = tag.as_standardized() {
-                Some(t.to_string())
-            } else {
-                None
+impl RecentIds {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            ids: HashSet::with_capacity(capacity),
+            order: VecDeque::with_capacity(capacity),
+            capacity,
+            live: None,
+        }
+    }
+
+    /// Keep `live` up to date with the number of ids held
+    pub(crate) fn counted(mut self, live: Arc<AtomicUsize>) -> Self {
+        live.fetch_add(self.ids.len(), Ordering::Relaxed);
+        self.live = Some(live);
+        self
+    }
+
+    pub(crate) fn contains(&self, id: &EventId) -> bool {
+        self.ids.contains(id)
+    }
+
+    /// Returns false if the id was already present
+    pub(crate) fn insert(&mut self, id: EventId) -> bool {
+        if !self.ids.insert(id) {
+            return false;
+        }
+        self.order.push_back(id);
+        if self.order.len() > self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.ids.remove(&oldest);
             }
-        })
-        .collect()
+        } else if let Some(live) = &self.live {
+            live.fetch_add(1, Ordering::Relaxed);
+        }
+        true
+    }
+}
+
+impl Drop for RecentIds {
+    fn drop(&mut self) {
+        if let Some(live) = &self.live {
+            live.fetch_sub(self.ids.len(), Ordering::Relaxed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{memory_dialog, note_text};
+    use proptest::prelude::*;
+
+    fn id(n: u8) -> EventId {
+        EventId::from_byte_array([n; 32])
+    }
+
+    #[test]
+    fn test_recent_ids_is_bounded() {
+        let mut seen = RecentIds::new(3);
+        assert!(seen.insert(id(1)));
+        assert!(!seen.insert(id(1)));
+        seen.insert(id(2));
+        seen.insert(id(3));
+        seen.insert(id(4));
+
+        assert!(!seen.contains(&id(1)), "oldest id should be evicted");
+        assert!(seen.contains(&id(2)));
+        assert!(seen.contains(&id(4)));
+
+        let live = Arc::new(AtomicUsize::new(0));
+        let mut counted = RecentIds::new(2).counted(live.clone());
+        for n in 1..=4 {
+            counted.insert(id(n));
+        }
+        assert_eq!(live.load(Ordering::Relaxed), 2);
+        drop(counted);
+        assert_eq!(live.load(Ordering::Relaxed), 0);
+    }
+
+    #[tokio::test]
+    async fn test_tag_watch_filters_notes() {
+        let dialog = memory_dialog();
+        let (tx, mut rx) = mpsc::channel(10);
+        let mut watcher = Watcher {
+            client: dialog.client.clone(),
+            keys: dialog.keys.clone(),
+            spec: WatchSpec::Tag("work".into()),
+            kinds: dialog.note_kinds(),
+            sub_id: SubscriptionId::generate(),
+            seen: RecentIds::new(SEEN_CAPACITY),
+            last_seen: Timestamp::from(0),
+            started_at: Timestamp::from(0),
+            quarantine: dialog.quarantine.clone(),
+            clock: dialog.clock.clone(),
+            debug: dialog.debug_feed.clone(),
+            tx,
+        };
+
+        let other = dialog.build_note_event("Groceries #home").await.unwrap();
+        let work = dialog.build_note_event("Standup #work").await.unwrap();
+        assert!(watcher.deliver(&other).await);
+        assert!(watcher.deliver(&work).await);
+        let Some(WatchItem::Note(note)) = rx.recv().await else {
+            panic!("expected a note");
+        };
+        assert_eq!(note.id, work.id);
+        assert!(rx.try_recv().is_err());
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(32))]
+
+        #[test]
+        fn prop_watch_delivers_note_once(text in note_text()) {
+            let rt = tokio::runtime::Runtime::new().unwrap();
+            rt.block_on(async {
+                let dialog = memory_dialog();
+                let (tx, mut rx) = mpsc::channel(10);
+                let mut watcher = Watcher {
+                    client: dialog.client.clone(),
+                    keys: dialog.keys.clone(),
+                    spec: WatchSpec::Notes,
+                    kinds: dialog.note_kinds(),
+                    sub_id: SubscriptionId::generate(),
+                    seen: RecentIds::new(SEEN_CAPACITY),
+                    last_seen: Timestamp::from(0),
+                    started_at: Timestamp::from(0),
+                    quarantine: dialog.quarantine.clone(),
+                    clock: dialog.clock.clone(),
+                    debug: dialog.debug_feed.clone(),
+                    tx,
+                };
+
+                let event = dialog.build_note_event(&text).await.unwrap();
+                assert!(watcher.deliver(&event).await);
+                let Some(WatchItem::Note(note)) = rx.recv().await else {
+                    panic!("expected a note");
+                };
+                assert_eq!(note.text, text);
+                assert_eq!(note.tags, crate::parse_hashtags(&text));
+
+                // Redelivery (e.g. from a catch-up query) is de-duplicated
+                assert!(watcher.deliver(&event).await);
+                assert!(rx.try_recv().is_err());
+            });
+        }
+    }
 }
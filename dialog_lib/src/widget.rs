@@ -0,0 +1,155 @@
+use crate::file_store::write_atomic;
+use crate::ics::summary;
+use crate::{Dialog, DialogError, Note, Result, LOCKED_PLACEHOLDER};
+use nostr_sdk::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// Notes looked at when building a snapshot
+const WIDGET_SCAN_LIMIT: usize = 1000;
+const WIDGET_RECENT_NOTES: usize = 3;
+const MAX_WIDGET_TITLE_CHARS: usize = 60;
+
+/// Widget sizes, each showing a little more of the snapshot
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WidgetKind {
+    /// Unread count and the newest note
+    Small,
+    /// Unread count and the three newest notes
+    Medium,
+    /// Everything, including the pinned note
+    Large,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WidgetNote {
+    pub id: EventId,
+    pub title: String,
+    pub created_at: Timestamp,
+}
+
+/// What widgets show, written to a file by the app so widget processes can
+/// read it without opening the note database. Titles are plaintext, so the
+/// file should live in the app's own (group) container.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WidgetSnapshot {
+    pub unread_count: u32,
+    /// Newest first, archived notes left out
+    pub recent: Vec<WidgetNote>,
+    /// The most recently written pinned note
+    pub pinned: Option<WidgetNote>,
+    pub updated_at: Timestamp,
+}
+
+impl WidgetSnapshot {
+    /// The part of the snapshot a widget of `kind` shows
+    pub fn for_kind(mut self, kind: WidgetKind) -> Self {
+        match kind {
+            WidgetKind::Small => {
+                self.recent.truncate(1);
+                self.pinned = None;
+            }
+            WidgetKind::Medium => self.pinned = None,
+            WidgetKind::Large => {}
+        }
+        self
+    }
+}
+
+impl Dialog {
+    pub async fn widget_snapshot(&self) -> Result<WidgetSnapshot> {
+        let notes: Vec<Note> = self
+            .list_notes(WIDGET_SCAN_LIMIT)
+            .await?
+            .into_iter()
+            .filter(|note| !note.is_archived)
+            .collect();
+        Ok(WidgetSnapshot {
+            unread_count: notes.iter().filter(|note| !note.is_read).count() as u32,
+            recent: notes
+                .iter()
+                .take(WIDGET_RECENT_NOTES)
+                .map(widget_note)
+                .collect(),
+            pinned: notes.iter().find(|note| note.is_pinned).map(widget_note),
+            updated_at: self.now(),
+        })
+    }
+
+    /// Build a snapshot and write it to `path` for widgets to read
+    pub async fn write_widget_snapshot(&self, path: &Path) -> Result<WidgetSnapshot> {
+        let snapshot = self.widget_snapshot().await?;
+        let json =
+            serde_json::to_string(&snapshot).map_err(|e| DialogError::Database(e.to_string()))?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        write_atomic(path, json)?;
+        *self.widget_path.write().unwrap() = Some(path.to_path_buf());
+        Ok(snapshot)
+    }
+}
+
+/// The snapshot last written to `path`, or None if there isn't one yet.
+/// Only reads the file, so it is cheap enough for a widget extension.
+pub fn read_widget_snapshot(path: &Path) -> Result<Option<WidgetSnapshot>> {
+    match fs::read_to_string(path) {
+        Ok(json) => serde_json::from_str(&json)
+            .map(Some)
+            .map_err(|e| DialogError::Database(e.to_string())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn widget_note(note: &Note) -> WidgetNote {
+    // A locked note's text must not end up in the file
+    let title = if note.is_locked {
+        LOCKED_PLACEHOLDER.to_string()
+    } else {
+        summary(&note.text, MAX_WIDGET_TITLE_CHARS)
+    };
+    WidgetNote {
+        id: note.id,
+        title,
+        created_at: note.created_at,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::memory_dialog;
+
+    #[tokio::test]
+    async fn test_widget_snapshot() {
+        let dialog = memory_dialog();
+        let pinned = dialog.save_note("Packing list #trip").await.unwrap();
+        dialog.set_pinned(&pinned.id, true).await.unwrap();
+        let archived = dialog.save_note("Old idea").await.unwrap();
+        dialog.set_archived(&archived.id, true).await.unwrap();
+        for text in ["One", "Two", "Three"] {
+            dialog.save_note(text).await.unwrap();
+        }
+        let read = dialog.save_note("Read already").await.unwrap();
+        dialog.set_read(&read.id, true).await.unwrap();
+
+        let dir = std::env::temp_dir().join(format!("dialog-widget-{}", pinned.id));
+        let path = dir.join("widget.json");
+        assert_eq!(read_widget_snapshot(&path).unwrap(), None);
+        let snapshot = dialog.write_widget_snapshot(&path).await.unwrap();
+        assert_eq!(read_widget_snapshot(&path).unwrap(), Some(snapshot.clone()));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(snapshot.unread_count, 4);
+        let titles: Vec<&str> = snapshot.recent.iter().map(|n| n.title.as_str()).collect();
+        assert_eq!(titles, vec!["Read already", "Three", "Two"]);
+        assert_eq!(snapshot.pinned.as_ref().unwrap().title, "Packing list");
+
+        let small = snapshot.clone().for_kind(WidgetKind::Small);
+        assert_eq!(small.recent.len(), 1);
+        assert_eq!(small.pinned, None);
+        assert_eq!(snapshot.clone().for_kind(WidgetKind::Large), snapshot);
+    }
+}
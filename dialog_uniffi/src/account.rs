@@ -0,0 +1,62 @@
+use crate::{DIALOG, DialogClient, Event};
+use dialog_lib::{DeleteAccountOptions, DialogError};
+
+// Deleting the account takes two steps, so a stray DeleteAccount can't wipe
+// anything: the app gets a token when the user starts the flow and sends it
+// back once they confirm.
+impl DialogClient {
+    /// A fresh token for DeleteAccount; asking again invalidates the last one
+    pub fn request_account_deletion_token(&self) -> String {
+        let token = uuid::Uuid::new_v4().to_string();
+        *self.deletion_token.lock().unwrap() = Some(token.clone());
+        token
+    }
+
+    pub(crate) async fn delete_account(&self, token: String, delete_remote: bool) {
+        // Single use, right or wrong
+        let expected = self.deletion_token.lock().unwrap().take();
+        if expected.as_deref() != Some(token.as_str()) {
+            let e = DialogError::Config("account deletion token doesn't match".into());
+            let message = "Account not deleted: confirm again".to_string();
+            self.emit(Event::failed(message, &e)).await;
+            return;
+        }
+
+        // No widget snapshot may be written once the account is gone
+        if let Some(handle) = self.widget_handle.lock().unwrap().take() {
+            handle.abort();
+        }
+        let widget_path = self.widget_path.write().unwrap().take();
+
+        let options = DeleteAccountOptions {
+            delete_remote,
+            keychain: self.keychain_entry.lock().unwrap().clone(),
+        };
+        match DIALOG.get().unwrap().delete_account(options).await {
+            Ok(report) => {
+                // The library removes snapshots it wrote; this catches one
+                // left by an earlier launch
+                if let Some(path) = widget_path {
+                    match std::fs::remove_file(&path) {
+                        Ok(()) => {}
+                        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                        Err(e) => eprintln!("[uniffi] widget snapshot not removed: {e}"),
+                    }
+                }
+                self.notes.write().await.clear();
+                self.emit(Event::AccountDeleted {
+                    remote_requested: report.remote_requested as u32,
+                    relays: report.relays.iter().map(|url| url.to_string()).collect(),
+                    keychain_cleared: report.keychain_cleared,
+                })
+                .await;
+            }
+            Err(e) => {
+                *self.widget_path.write().unwrap() = widget_path;
+                eprintln!("[uniffi] delete_account failed: {e}");
+                let message = format!("Couldn't delete account: {e}");
+                self.emit(Event::failed(message, &e)).await;
+            }
+        }
+    }
+}
@@ -0,0 +1,20 @@
+use crate::models::Event;
+use crate::{DIALOG, DialogClient};
+use chrono::Local;
+
+impl DialogClient {
+    /// Send ActivityUpdated with today's note count and the current streak
+    pub(crate) async fn emit_activity(&self) {
+        let today = Local::now().date_naive();
+        match DIALOG.get().unwrap().activity(today..=today).await {
+            Ok(activity) => {
+                self.emit(Event::ActivityUpdated {
+                    today_count: activity.today_count as u32,
+                    streak_days: activity.streak_days,
+                })
+                .await
+            }
+            Err(e) => eprintln!("[uniffi] activity failed: {e}"),
+        }
+    }
+}
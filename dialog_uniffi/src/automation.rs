@@ -0,0 +1,50 @@
+use crate::models::OfflineEditKind;
+use crate::{DIALOG, DialogClient, Event, Note, convert_lib_note_to_uniffi, rt};
+use std::sync::Arc;
+
+// Shortcuts / App Intents entry points. These block until the note is
+// stored and return plain values, so automations don't need a listener.
+impl DialogClient {
+    /// Store a note and return its id (hex), or an empty string if it
+    /// couldn't be saved. Publishing happens in the background; without a
+    /// connected relay the note is queued until `ConnectRelay` succeeds.
+    pub fn quick_create(self: Arc<Self>, text: String) -> String {
+        eprintln!("[uniffi] quick_create len={}", text.len());
+        let lib_note = match rt().block_on(DIALOG.get().unwrap().save_note(&text)) {
+            Ok(lib_note) => lib_note,
+            Err(e) => {
+                eprintln!("[uniffi] quick_create failed: {e}");
+                return String::new();
+            }
+        };
+        let note_id = lib_note.id;
+        let note = convert_lib_note_to_uniffi(lib_note);
+        let id = note.id.clone();
+
+        let this = self.clone();
+        rt().spawn(async move {
+            this.notes
+                .write()
+                .await
+                .insert(note.id.clone(), note.clone());
+            this.emit(Event::NoteAdded { note }).await;
+            this.publish_or_queue(note_id, OfflineEditKind::Create)
+                .await;
+        });
+        id
+    }
+
+    /// Notes containing `query` (ignoring case), newest first
+    pub fn quick_search(&self, query: String, limit: u32) -> Vec<Note> {
+        match rt().block_on(DIALOG.get().unwrap().search_notes(&query, limit as usize)) {
+            Ok(lib_notes) => lib_notes
+                .into_iter()
+                .map(convert_lib_note_to_uniffi)
+                .collect(),
+            Err(e) => {
+                eprintln!("[uniffi] quick_search failed: {e}");
+                Vec::new()
+            }
+        }
+    }
+}
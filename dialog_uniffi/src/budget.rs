@@ -0,0 +1,68 @@
+use crate::models::RelayEventCount;
+use crate::{DIALOG, DialogClient, Event, rt};
+use dialog_lib::RelayEventCount as LibCount;
+
+impl From<LibCount> for RelayEventCount {
+    fn from(count: LibCount) -> Self {
+        RelayEventCount {
+            near_limit: count.near_limit(),
+            url: count.url.to_string(),
+            count: count.count as u64,
+            budget: count.budget.map(|budget| budget as u64),
+        }
+    }
+}
+
+// Event budget for relays that cap events per pubkey. Relays nearing it
+// arrive as EventBudgetWarning, a hint to offer CompactRemote.
+impl DialogClient {
+    pub(crate) fn forward_budget_warnings(&self) {
+        let mut near = DIALOG.get().unwrap().watch_event_budget();
+        let event_tx = self.event_tx.clone();
+        rt().spawn(async move {
+            while near.changed().await.is_ok() {
+                let relays: Vec<RelayEventCount> = near
+                    .borrow_and_update()
+                    .iter()
+                    .cloned()
+                    .map(Into::into)
+                    .collect();
+                eprintln!("[uniffi] Relays near the event budget: {}", relays.len());
+                let _ = event_tx.send(Event::EventBudgetWarning { relays });
+            }
+        });
+    }
+
+    pub fn get_event_counts(&self) -> Vec<RelayEventCount> {
+        let counts = DIALOG.get().unwrap().event_counts();
+        counts.into_iter().map(Into::into).collect()
+    }
+
+    pub(crate) async fn set_event_budget(&self, budget: Option<u64>) {
+        let dialog = DIALOG.get().unwrap();
+        dialog.set_event_budget(budget.map(|budget| budget as usize));
+        if budget.is_some() {
+            // Counting asks every relay for all our events, so only when needed
+            if let Err(e) = dialog.count_remote_events().await {
+                eprintln!("[uniffi] count_remote_events failed: {e}");
+            }
+        }
+    }
+
+    pub(crate) async fn compact_remote(&self) {
+        match DIALOG.get().unwrap().compact_remote().await {
+            Ok(report) => {
+                let removed = report.superseded.len() + report.tombstoned.len();
+                self.emit(Event::RemoteCompacted {
+                    removed: removed as u32,
+                })
+                .await;
+            }
+            Err(e) => {
+                eprintln!("[uniffi] compact_remote failed: {e}");
+                let message = format!("Cleaning up relays failed: {e}");
+                self.emit(Event::failed(message, &e)).await;
+            }
+        }
+    }
+}
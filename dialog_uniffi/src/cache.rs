@@ -0,0 +1,122 @@
+use crate::models::AttachmentCacheStats;
+use crate::{DIALOG, DialogClient, Event, convert_lib_note_to_uniffi};
+use dialog_lib::AttachmentCacheStats as LibStats;
+use std::sync::Arc;
+
+impl From<LibStats> for AttachmentCacheStats {
+    fn from(stats: LibStats) -> Self {
+        AttachmentCacheStats {
+            files: stats.files as u32,
+            bytes: stats.bytes,
+            pinned_files: stats.pinned_files as u32,
+            pinned_bytes: stats.pinned_bytes,
+            budget: stats.budget,
+        }
+    }
+}
+
+// Keeps the local database small on phones: old note bodies are dropped
+// and the rows stay, marked as not hydrated.
+impl DialogClient {
+    pub(crate) async fn set_local_cache_limit(self: Arc<Self>, bytes: Option<u64>) {
+        let report = match DIALOG.get().unwrap().set_local_cache_limit(bytes).await {
+            Ok(report) => report,
+            Err(e) => {
+                eprintln!("[uniffi] set_local_cache_limit failed: {e}");
+                let message = format!("Couldn't free up space: {e}");
+                self.emit(Event::failed(message, &e)).await;
+                return;
+            }
+        };
+        for id in report.evicted {
+            self.update_note(&id.to_hex(), |note| {
+                note.text.clear();
+                note.preview.clear();
+                note.is_truncated = false;
+                note.is_hydrated = false;
+            })
+            .await;
+        }
+    }
+
+    pub(crate) async fn hydrate_note(self: Arc<Self>, id: String) {
+        let result = match dialog_lib::parse_note_id(&id) {
+            Ok(note_id) => DIALOG.get().unwrap().hydrate_note(&note_id).await,
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(lib_note) => {
+                let note = convert_lib_note_to_uniffi(lib_note);
+                self.notes
+                    .write()
+                    .await
+                    .insert(note.id.clone(), note.clone());
+                self.emit(Event::NoteUpdated { note }).await;
+            }
+            Err(e) => {
+                eprintln!("[uniffi] hydrate_note {id} failed: {e}");
+                let message = format!("Couldn't download note: {e}");
+                self.emit(Event::failed(message, &e).about(&id)).await;
+            }
+        }
+    }
+
+    // Attachment files work like photo originals: kept while there is room,
+    // and always for notes marked keep offline
+    pub fn get_attachment_cache_stats(&self) -> Option<AttachmentCacheStats> {
+        match DIALOG.get().unwrap().attachment_cache_stats() {
+            Ok(stats) => Some(stats.into()),
+            Err(e) => {
+                eprintln!("[uniffi] attachment_cache_stats failed: {e}");
+                None
+            }
+        }
+    }
+
+    pub(crate) async fn set_keep_offline(&self, id: String, keep: bool) {
+        let result = match dialog_lib::parse_note_id(&id) {
+            Ok(note_id) => DIALOG.get().unwrap().set_keep_offline(&note_id, keep).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            eprintln!("[uniffi] set_keep_offline {id} failed: {e}");
+            let message = format!("Couldn't download attachments: {e}");
+            self.emit(Event::failed(message, &e).about(&id)).await;
+        }
+    }
+
+    pub(crate) async fn set_attachment_cache_budget(&self, bytes: Option<u64>) {
+        if let Err(e) = DIALOG.get().unwrap().set_attachment_cache_budget(bytes) {
+            eprintln!("[uniffi] set_attachment_cache_budget failed: {e}");
+            let message = format!("Couldn't free up space: {e}");
+            self.emit(Event::failed(message, &e)).await;
+        }
+    }
+
+    // Recovery for when lists, flags or search look wrong: everything
+    // derived from the stored events is rebuilt and the list reloaded
+    pub(crate) async fn rebuild_index(self: Arc<Self>, from_relays: bool) {
+        let report = match DIALOG.get().unwrap().rebuild_index(from_relays).await {
+            Ok(report) => report,
+            Err(e) => {
+                eprintln!("[uniffi] rebuild_index failed: {e}");
+                let message = format!("Couldn't rebuild the local index: {e}");
+                self.emit(Event::failed(message, &e)).await;
+                return;
+            }
+        };
+        let limit = {
+            let mut notes = self.notes.write().await;
+            let limit = notes.len().max(100);
+            notes.clear();
+            limit
+        };
+        self.clone().reload_notes(limit).await;
+        self.emit(Event::IndexRebuilt {
+            notes: report.notes as u32,
+            indexed: report.indexed as u32,
+            fetched: report.fetched as u32,
+        })
+        .await;
+    }
+}
@@ -0,0 +1,39 @@
+#[cfg(feature = "chaos")]
+use crate::DIALOG;
+use crate::models::ChaosConfig;
+#[cfg(not(feature = "chaos"))]
+use crate::models::ErrorCode;
+use crate::{DialogClient, Event};
+use std::time::Duration;
+
+impl From<ChaosConfig> for dialog_lib::ChaosConfig {
+    fn from(config: ChaosConfig) -> Self {
+        dialog_lib::ChaosConfig {
+            latency: Duration::from_millis(config.latency_ms),
+            jitter: Duration::from_millis(config.jitter_ms),
+            drop_rate: config.drop_rate,
+            decrypt_failure_rate: config.decrypt_failure_rate,
+            seed: config.seed,
+        }
+    }
+}
+
+// QA builds only: slow relays, dropped publishes and undecryptable notes on
+// demand, to exercise the app's error and retry handling
+impl DialogClient {
+    pub(crate) async fn set_chaos(&self, config: Option<ChaosConfig>) {
+        #[cfg(feature = "chaos")]
+        if let Err(e) = DIALOG.get().unwrap().set_chaos(config.map(Into::into)) {
+            eprintln!("[uniffi] set_chaos failed: {e}");
+            let message = format!("Couldn't inject faults: {e}");
+            self.emit(Event::failed(message, &e)).await;
+        }
+        #[cfg(not(feature = "chaos"))]
+        {
+            let _ = config;
+            let message = "Fault injection needs a build with the chaos feature".to_string();
+            self.emit(Event::error(ErrorCode::InvalidInput, message))
+                .await;
+        }
+    }
+}
@@ -0,0 +1,127 @@
+use crate::DialogClient;
+use crate::ids::parse_note_id;
+use crate::models::DeepLink;
+
+const SCHEME: &str = "dialog://";
+
+// dialog:// URLs for notifications, Spotlight and widgets. Notes are linked
+// by hex id; tags are normalized and percent-encoded. nostr: note links, as
+// pasted into task managers, open the note too.
+impl DialogClient {
+    /// `dialog://note/<id>`, or None if `note_id` isn't a note id
+    pub fn get_deep_link(&self, note_id: String) -> Option<String> {
+        note_link(&note_id)
+    }
+
+    /// `dialog://tag/<tag>`, or None if `tag` isn't a valid tag
+    pub fn get_tag_deep_link(&self, tag: String) -> Option<String> {
+        tag_link(&tag)
+    }
+
+    /// The screen a dialog:// or nostr: URL points at. Note ids come back as
+    /// hex.
+    pub fn parse_deep_link(&self, url: String) -> Option<DeepLink> {
+        parse_link(&url)
+    }
+}
+
+pub(crate) fn note_link(note_id: &str) -> Option<String> {
+    let id = parse_note_id(note_id.to_string())?;
+    Some(format!("{SCHEME}note/{id}"))
+}
+
+fn tag_link(tag: &str) -> Option<String> {
+    let tag = dialog_lib::normalize_tag(tag)?;
+    Some(format!("{SCHEME}tag/{}", percent_encode(&tag)))
+}
+
+fn parse_link(url: &str) -> Option<DeepLink> {
+    if url.trim().starts_with("nostr:") {
+        let id = parse_note_id(url.to_string())?;
+        return Some(DeepLink::Note { id });
+    }
+    let path = url.trim().strip_prefix(SCHEME)?;
+    // Ignore any query or fragment added by whoever passed the link on
+    let path = path.split(['?', '#']).next()?.trim_end_matches('/');
+    let (kind, value) = path.split_once('/')?;
+    match kind {
+        "note" => Some(DeepLink::Note {
+            id: parse_note_id(value.to_string())?,
+        }),
+        "tag" => Some(DeepLink::Tag {
+            tag: dialog_lib::normalize_tag(&percent_decode(value)?)?,
+        }),
+        _ => None,
+    }
+}
+
+fn percent_encode(input: &str) -> String {
+    let mut encoded = String::with_capacity(input.len());
+    for byte in input.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-_.~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    encoded
+}
+
+fn percent_decode(input: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(input.len());
+    let mut iter = input.bytes();
+    while let Some(byte) = iter.next() {
+        if byte == b'%' {
+            let hex = [iter.next()?, iter.next()?];
+            bytes.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+        } else {
+            bytes.push(byte);
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deep_links_round_trip() {
+        let hex = "c".repeat(64);
+        let link = note_link(&hex).unwrap();
+        assert_eq!(link, format!("dialog://note/{hex}"));
+        assert_eq!(parse_link(&link), Some(DeepLink::Note { id: hex.clone() }));
+
+        let note1 = crate::ids::format_note_id(hex.clone(), crate::NoteIdFormat::Note).unwrap();
+        assert_eq!(
+            parse_link(&format!("dialog://note/{note1}/?from=spotlight")),
+            Some(DeepLink::Note { id: hex.clone() })
+        );
+
+        let nevent = crate::ids::format_note_id(hex.clone(), crate::NoteIdFormat::Nevent).unwrap();
+        assert_eq!(
+            parse_link(&format!("nostr:{nevent}")),
+            Some(DeepLink::Note { id: hex.clone() })
+        );
+
+        let link = tag_link("#Café").unwrap();
+        assert_eq!(link, "dialog://tag/caf%C3%A9");
+        assert_eq!(
+            parse_link(&link),
+            Some(DeepLink::Tag {
+                tag: "café".into()
+            })
+        );
+
+        for bad in [
+            "https://note/abc",
+            "dialog://note/xyz",
+            "dialog://tag/",
+            "dialog://inbox",
+            "nostr:npub1xyz",
+        ] {
+            assert_eq!(parse_link(bad), None, "{bad}");
+        }
+        assert_eq!(tag_link("#"), None);
+    }
+}
@@ -0,0 +1,44 @@
+use crate::models::Device;
+use crate::{DIALOG, DialogClient, Event, rt};
+use std::sync::Arc;
+
+// Devices writing with this key. Ignoring one hides its notes here and,
+// through app state sync, on the other devices.
+impl DialogClient {
+    pub fn get_devices(&self) -> Vec<Device> {
+        match rt().block_on(DIALOG.get().unwrap().devices()) {
+            Ok(devices) => devices
+                .into_iter()
+                .map(|device| Device {
+                    id: device.id,
+                    name: device.name,
+                    last_active: device.last_active.as_u64() as i64,
+                    notes: device.notes as u32,
+                    is_current: device.is_current,
+                    ignored: device.ignored,
+                })
+                .collect(),
+            Err(e) => {
+                eprintln!("[uniffi] devices failed: {e}");
+                Vec::new()
+            }
+        }
+    }
+
+    pub(crate) async fn set_device_ignored(self: Arc<Self>, id: String, ignored: bool) {
+        if let Err(e) = DIALOG.get().unwrap().set_device_ignored(&id, ignored).await {
+            eprintln!("[uniffi] set_device_ignored {id} failed: {e}");
+            let message = format!("Couldn't update device: {e}");
+            self.emit(Event::failed(message, &e)).await;
+            return;
+        }
+        // Notes from the device come and go, so rebuild the list
+        let limit = {
+            let mut notes = self.notes.write().await;
+            let limit = notes.len().max(100);
+            notes.clear();
+            limit
+        };
+        self.reload_notes(limit).await;
+    }
+}
@@ -0,0 +1,88 @@
+use crate::models::Note;
+use std::collections::HashMap;
+
+/// Difference between the last list sent to the client and a new one
+#[derive(Debug, Default)]
+pub(crate) struct NotesDiff {
+    pub added: Vec<Note>,
+    pub updated: Vec<Note>,
+    pub removed_ids: Vec<String>,
+}
+
+impl NotesDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.updated.is_empty() && self.removed_ids.is_empty()
+    }
+}
+
+/// Compute what changed between the previous snapshot and the next list.
+/// Output ordering follows `next` for added/updated and is sorted for removals.
+pub(crate) fn diff_notes(prev: &HashMap<String, Note>, next: &[Note]) -> NotesDiff {
+    let mut diff = NotesDiff::default();
+    for note in next {
+        match prev.get(&note.id) {
+            None => diff.added.push(note.clone()),
+            Some(old) if old != note => diff.updated.push(note.clone()),
+            Some(_) => {}
+        }
+    }
+
+    let next_ids: std::collections::HashSet<&str> = next.iter().map(|n| n.id.as_str()).collect();
+    diff.removed_ids = prev
+        .keys()
+        .filter(|id| !next_ids.contains(id.as_str()))
+        .cloned()
+        .collect();
+    diff.removed_ids.sort();
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note(id: &str, text: &str) -> Note {
+        Note {
+            id: id.to_string(),
+            text: text.to_string(),
+            tags: vec![],
+            created_at: 0,
+            is_read: false,
+            is_pinned: false,
+            is_archived: false,
+            public_id: None,
+            audio: None,
+            transcript: None,
+            is_locked: false,
+            is_synced: true,
+            preview: text.to_string(),
+            is_truncated: false,
+            is_hydrated: true,
+            edited: false,
+            edit_of: None,
+            task_status: None,
+            word_count: 1,
+            reading_minutes: 1,
+        }
+    }
+
+    #[test]
+    fn test_diff_notes() {
+        let prev: HashMap<String, Note> = [note("a", "one"), note("b", "two"), note("c", "three")]
+            .into_iter()
+            .map(|n| (n.id.clone(), n))
+            .collect();
+        let next = vec![note("a", "one"), note("b", "two edited"), note("d", "four")];
+
+        let diff = diff_notes(&prev, &next);
+        assert_eq!(diff.added, vec![note("d", "four")]);
+        assert_eq!(diff.updated, vec![note("b", "two edited")]);
+        assert_eq!(diff.removed_ids, vec!["c".to_string()]);
+    }
+
+    #[test]
+    fn test_diff_notes_unchanged() {
+        let prev: HashMap<String, Note> = [(String::from("a"), note("a", "one"))].into();
+        assert!(diff_notes(&prev, &[note("a", "one")]).is_empty());
+    }
+}
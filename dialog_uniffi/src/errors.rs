@@ -0,0 +1,109 @@
+use crate::models::{ErrorCode, Event, SuggestedAction};
+use dialog_lib::DialogError;
+
+impl From<&DialogError> for ErrorCode {
+    fn from(error: &DialogError) -> Self {
+        match error {
+            DialogError::Nostr(_)
+            | DialogError::Sync(_)
+            | DialogError::NotPublished(_)
+            | DialogError::Http(_)
+            | DialogError::Payment(_) => ErrorCode::Network,
+            DialogError::Database(_)
+            | DialogError::Io(_)
+            | DialogError::ProjectDirs
+            | DialogError::Keychain(_) => ErrorCode::Storage,
+            DialogError::NoteNotFound(_) | DialogError::InvalidNoteId(_) => ErrorCode::NotFound,
+            DialogError::NoteTooLarge { .. } => ErrorCode::TooLarge,
+            DialogError::WrongPassphrase => ErrorCode::WrongPassphrase,
+            DialogError::Keys(_)
+            | DialogError::Config(_)
+            | DialogError::Import(_)
+            | DialogError::Share(_)
+            | DialogError::NoteKind(_)
+            | DialogError::Lock(_) => ErrorCode::InvalidInput,
+            DialogError::Summarize(_) | DialogError::Clipper(_) | DialogError::Api(_) => {
+                ErrorCode::ServiceUnavailable
+            }
+            DialogError::Cancelled => ErrorCode::Cancelled,
+            DialogError::Nip44(_) | DialogError::EventBuilder(_) => ErrorCode::Internal,
+            _ => ErrorCode::Internal,
+        }
+    }
+}
+
+impl ErrorCode {
+    /// Whether sending the same command again may work
+    fn retryable(self) -> bool {
+        matches!(self, ErrorCode::Network | ErrorCode::ServiceUnavailable)
+    }
+
+    fn suggested_action(self) -> Option<SuggestedAction> {
+        match self {
+            ErrorCode::Network => Some(SuggestedAction::CheckConnection),
+            ErrorCode::ServiceUnavailable => Some(SuggestedAction::Retry),
+            ErrorCode::NotFound => Some(SuggestedAction::Refresh),
+            ErrorCode::TooLarge | ErrorCode::InvalidInput => Some(SuggestedAction::EditInput),
+            ErrorCode::WrongPassphrase => Some(SuggestedAction::EnterPassphrase),
+            ErrorCode::Storage | ErrorCode::Cancelled | ErrorCode::Internal => None,
+        }
+    }
+}
+
+impl Event {
+    /// An Error event not caused by a dialog_lib error
+    pub(crate) fn error(code: ErrorCode, message: String) -> Self {
+        Event::Error {
+            message,
+            code,
+            retryable: code.retryable(),
+            related_note_id: None,
+            suggested_action: code.suggested_action(),
+        }
+    }
+
+    /// An Error event for a failed dialog_lib call
+    pub(crate) fn failed(message: String, error: &DialogError) -> Self {
+        Self::error(ErrorCode::from(error), message)
+    }
+
+    /// Name the note an Error event is about
+    pub(crate) fn about(mut self, note_id: &str) -> Self {
+        if let Event::Error {
+            related_note_id, ..
+        } = &mut self
+        {
+            *related_note_id = Some(note_id.to_string());
+        }
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nostr_sdk::prelude::*;
+
+    #[test]
+    fn test_errors_are_classified() {
+        let offline = DialogError::NotPublished("no relay accepted it".into());
+        let Event::Error {
+            code,
+            retryable,
+            related_note_id,
+            suggested_action,
+            ..
+        } = Event::failed("Failed to save note".into(), &offline).about("abc")
+        else {
+            panic!("not an error event");
+        };
+        assert_eq!(code, ErrorCode::Network);
+        assert!(retryable);
+        assert_eq!(related_note_id.as_deref(), Some("abc"));
+        assert_eq!(suggested_action, Some(SuggestedAction::CheckConnection));
+
+        let missing = DialogError::NoteNotFound(EventId::all_zeros());
+        assert_eq!(ErrorCode::from(&missing), ErrorCode::NotFound);
+        assert!(!ErrorCode::from(&DialogError::WrongPassphrase).retryable());
+    }
+}
@@ -0,0 +1,22 @@
+use crate::{DIALOG, DialogClient, Note, convert_lib_note_to_uniffi, rt};
+use nostr_sdk::prelude::*;
+
+// The "edited" marker on a note opens its history: every earlier version,
+// newest first, for the detail view.
+impl DialogClient {
+    pub fn get_edit_history(&self, id: String) -> Vec<Note> {
+        let Ok(note_id) = EventId::from_hex(&id) else {
+            return Vec::new();
+        };
+        match rt().block_on(DIALOG.get().unwrap().edit_history(&note_id)) {
+            Ok(lib_notes) => lib_notes
+                .into_iter()
+                .map(|note| convert_lib_note_to_uniffi(note).for_list())
+                .collect(),
+            Err(e) => {
+                eprintln!("[uniffi] edit_history {id} failed: {e}");
+                Vec::new()
+            }
+        }
+    }
+}
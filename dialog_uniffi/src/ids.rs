@@ -0,0 +1,100 @@
+use crate::Command;
+use crate::models::NoteIdFormat;
+use dialog_lib::NoteIdFormat as LibFormat;
+
+impl From<NoteIdFormat> for LibFormat {
+    fn from(format: NoteIdFormat) -> Self {
+        match format {
+            NoteIdFormat::Hex => LibFormat::Hex,
+            NoteIdFormat::Note => LibFormat::Note,
+            NoteIdFormat::Nevent => LibFormat::Nevent,
+        }
+    }
+}
+
+/// Hex id for a note id in any accepted form, or None if it isn't one
+pub fn parse_note_id(id: String) -> Option<String> {
+    dialog_lib::parse_note_id(&id).ok().map(|id| id.to_hex())
+}
+
+/// `id` (in any accepted form) written as `format`, e.g. for a share sheet
+pub fn format_note_id(id: String, format: NoteIdFormat) -> Option<String> {
+    let id = dialog_lib::parse_note_id(&id).ok()?;
+    Some(dialog_lib::format_note_id(&id, format.into()))
+}
+
+/// Notes are keyed by hex id, so ids pasted as note1/nevent1 are converted
+/// on the way in. Anything unparseable is passed on and fails as before.
+fn to_hex(id: String) -> String {
+    parse_note_id(id.clone()).unwrap_or(id)
+}
+
+impl Command {
+    pub(crate) fn with_hex_ids(self) -> Self {
+        match self {
+            Command::SetTranscript { id, transcript } => Command::SetTranscript {
+                id: to_hex(id),
+                transcript,
+            },
+            Command::DeleteNote { id } => Command::DeleteNote { id: to_hex(id) },
+            Command::MarkAsRead { id } => Command::MarkAsRead { id: to_hex(id) },
+            Command::RecordView { id } => Command::RecordView { id: to_hex(id) },
+            Command::LockNote { id, passphrase } => Command::LockNote {
+                id: to_hex(id),
+                passphrase,
+            },
+            Command::UnlockNote { id, passphrase } => Command::UnlockNote {
+                id: to_hex(id),
+                passphrase,
+            },
+            Command::SetPinned { id, pinned } => Command::SetPinned {
+                id: to_hex(id),
+                pinned,
+            },
+            Command::SetArchived { id, archived } => Command::SetArchived {
+                id: to_hex(id),
+                archived,
+            },
+            Command::PublishPublic { id, strip_tags } => Command::PublishPublic {
+                id: to_hex(id),
+                strip_tags,
+            },
+            Command::EnrichNote { id } => Command::EnrichNote { id: to_hex(id) },
+            Command::BulkTag { ids, add, remove } => Command::BulkTag {
+                ids: ids.into_iter().map(to_hex).collect(),
+                add,
+                remove,
+            },
+            other => other,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_ids_become_hex() {
+        let hex = "b".repeat(64);
+        let nevent = format_note_id(hex.clone(), NoteIdFormat::Nevent).unwrap();
+        assert!(nevent.starts_with("nevent1"));
+
+        let cmd = Command::SetPinned {
+            id: format!("nostr:{nevent}"),
+            pinned: true,
+        };
+        match cmd.with_hex_ids() {
+            Command::SetPinned { id, pinned } => assert_eq!((id, pinned), (hex, true)),
+            other => panic!("unexpected {other:?}"),
+        }
+
+        let cmd = Command::DeleteNote {
+            id: "not-an-id".into(),
+        };
+        match cmd.with_hex_ids() {
+            Command::DeleteNote { id } => assert_eq!(id, "not-an-id"),
+            other => panic!("unexpected {other:?}"),
+        }
+    }
+}
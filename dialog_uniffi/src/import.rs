@@ -0,0 +1,103 @@
+use crate::models::{DuplicatePolicy, ImportFormat, ImportReport};
+use crate::{DIALOG, DialogClient, Event, convert_lib_note_to_uniffi};
+use dialog_lib::ImportReport as LibReport;
+use std::path::Path;
+use std::sync::Arc;
+
+impl From<ImportFormat> for dialog_lib::ImportFormat {
+    fn from(format: ImportFormat) -> Self {
+        match format {
+            ImportFormat::Eml => dialog_lib::ImportFormat::Eml,
+            ImportFormat::Keep => dialog_lib::ImportFormat::Keep,
+            ImportFormat::Simplenote => dialog_lib::ImportFormat::Simplenote,
+        }
+    }
+}
+
+impl From<DuplicatePolicy> for dialog_lib::DuplicatePolicy {
+    fn from(policy: DuplicatePolicy) -> Self {
+        match policy {
+            DuplicatePolicy::Skip => dialog_lib::DuplicatePolicy::Skip,
+            DuplicatePolicy::Merge => dialog_lib::DuplicatePolicy::Merge,
+            DuplicatePolicy::Duplicate => dialog_lib::DuplicatePolicy::Duplicate,
+        }
+    }
+}
+
+impl From<&LibReport> for ImportReport {
+    fn from(report: &LibReport) -> Self {
+        ImportReport {
+            created: report.created.len() as u32,
+            skipped: report.skipped.len() as u32,
+            merged: report.merged.len() as u32,
+            failed: report
+                .failed
+                .iter()
+                .map(|(source, reason)| format!("{}: {reason}", source.display()))
+                .collect(),
+        }
+    }
+}
+
+// Importing another app's export, e.g. from the Files app. Created notes
+// arrive as NoteAdded, merged ones replace the stored note like BulkTag.
+impl DialogClient {
+    pub(crate) async fn import_notes(
+        self: Arc<Self>,
+        path: String,
+        format: ImportFormat,
+        policy: DuplicatePolicy,
+    ) {
+        let report = match DIALOG
+            .get()
+            .unwrap()
+            .import_path_cancellable(
+                Path::new(&path),
+                format.into(),
+                policy.into(),
+                &Default::default(),
+            )
+            .await
+        {
+            Ok(report) => report,
+            Err(e) => {
+                eprintln!("[uniffi] import_notes failed: {e}");
+                let message = format!("Couldn't import notes: {e}");
+                self.emit(Event::failed(message, &e)).await;
+                return;
+            }
+        };
+        let summary = ImportReport::from(&report);
+
+        for lib_note in report.created {
+            let note = convert_lib_note_to_uniffi(lib_note);
+            self.notes
+                .write()
+                .await
+                .insert(note.id.clone(), note.clone());
+            self.emit(Event::NoteAdded { note }).await;
+        }
+        for (duplicate, lib_note) in report.merged {
+            let retagged = duplicate.existing != lib_note.id;
+            let note = convert_lib_note_to_uniffi(lib_note);
+            if !retagged {
+                // Only pinned or archived
+                self.notes
+                    .write()
+                    .await
+                    .insert(note.id.clone(), note.clone());
+                self.emit(Event::NoteUpdated { note }).await;
+                continue;
+            }
+            let old_id = duplicate.existing.to_hex();
+            {
+                let mut notes = self.notes.write().await;
+                notes.remove(&old_id);
+                notes.insert(note.id.clone(), note.clone());
+            }
+            self.emit(Event::NoteDeleted { id: old_id }).await;
+            self.emit(Event::NoteAdded { note }).await;
+        }
+        self.emit(Event::ImportFinished { report: summary }).await;
+    }
+}
@@ -0,0 +1,78 @@
+//! Keeps the nsec in the Keychain, so the app handles the raw string only
+//! once, at onboarding. Without the `keychain` feature nothing is stored.
+
+use crate::DialogClient;
+
+impl DialogClient {
+    /// Like `new`, with the nsec stored by `store_nsec_in_keychain`. Check
+    /// `has_nsec_in_keychain` first: a missing key is fatal, like a bad nsec.
+    pub fn new_from_keychain(service: String, account: String) -> Self {
+        eprintln!("[uniffi] DialogClient::new_from_keychain service={service}");
+        match load_nsec(&service, &account) {
+            Ok(nsec) => {
+                let client = Self::new(nsec);
+                *client.keychain_entry.lock().unwrap() = Some((service, account));
+                client
+            }
+            Err(e) => panic!("[uniffi] Failed to read nsec from keychain: {e}"),
+        }
+    }
+}
+
+#[cfg(feature = "keychain")]
+fn load_nsec(service: &str, account: &str) -> Result<String, dialog_lib::DialogError> {
+    use nostr_sdk::prelude::*;
+    let keys = dialog_lib::keychain::load_keys(service, account)?;
+    Ok(keys.secret_key().to_secret_hex())
+}
+
+#[cfg(not(feature = "keychain"))]
+fn load_nsec(_service: &str, _account: &str) -> Result<String, String> {
+    Err("built without the keychain feature".to_string())
+}
+
+/// Store `nsec` (nsec1... or hex) under `service`/`account`. Returns the
+/// npub, or None if the nsec is invalid or the keychain refused it.
+pub fn store_nsec_in_keychain(service: String, account: String, nsec: String) -> Option<String> {
+    #[cfg(feature = "keychain")]
+    {
+        use nostr_sdk::prelude::*;
+        match dialog_lib::keychain::store_nsec(&service, &account, &nsec) {
+            Ok(public_key) => public_key.to_bech32().ok(),
+            Err(e) => {
+                eprintln!("[uniffi] store_nsec_in_keychain failed: {e}");
+                None
+            }
+        }
+    }
+    #[cfg(not(feature = "keychain"))]
+    {
+        let _ = (service, account, nsec);
+        None
+    }
+}
+
+pub fn has_nsec_in_keychain(service: String, account: String) -> bool {
+    #[cfg(feature = "keychain")]
+    {
+        dialog_lib::keychain::has_nsec(&service, &account)
+    }
+    #[cfg(not(feature = "keychain"))]
+    {
+        let _ = (service, account);
+        false
+    }
+}
+
+/// Remove the stored nsec, e.g. on sign-out
+pub fn delete_nsec_from_keychain(service: String, account: String) -> bool {
+    #[cfg(feature = "keychain")]
+    {
+        dialog_lib::keychain::delete_nsec(&service, &account).is_ok()
+    }
+    #[cfg(not(feature = "keychain"))]
+    {
+        let _ = (service, account);
+        false
+    }
+}
@@ -1,11 +1,68 @@
+mod account;
+mod activity;
+mod automation;
+mod budget;
+mod cache;
+mod chaos;
+mod deep_link;
+mod devices;
+mod diff;
+mod errors;
+mod history;
+mod ids;
+mod import;
+mod keychain;
+mod messages;
 mod models;
+mod notifications;
+mod offline;
+mod profile;
+mod provenance;
+mod queue;
+mod quick_capture;
+mod relay_policy;
+mod retention;
+mod review;
+mod runtime_stats;
+mod settings;
+mod share;
+mod spotlight;
+mod status;
+mod summarize;
+mod tasks;
+mod undo;
+mod user_profile;
+mod widget;
 
-pub use models::{Note, Event, Command};
+pub use models::{
+    Attachment, AttachmentCacheStats, CaptureSource, ChaosConfig, ClientCapabilities, Command,
+    Contact, DeepLink, Device, DialogStatus, DirectMessage, DuplicatePolicy, ErrorCode, Event,
+    EventCategory, GroupShare, ImportFormat, ImportReport, LastSync, LinkPreview, Note, NoteFilter,
+    NoteIdFormat, NotificationRule, NotifyRule, OfflineEdit, OfflineEditKind, OpTiming,
+    PendingCommand, Profile, Provenance, RelayEventCount, RelayHealth, RelayPolicy, RelaySyncStats,
+    RetentionAction, RetentionReport, RetentionRule, RuntimeStats, SearchIndexChanges,
+    SearchIndexEntry, ShareRecipient, ShareStatus, SortOrder, SuggestedAction, SyncMode, TaskStatus,
+    ValidationReport, WidgetKind, WidgetNote, WidgetSnapshot,
+};
+pub use ids::{format_note_id, parse_note_id};
+pub use keychain::{delete_nsec_from_keychain, has_nsec_in_keychain, store_nsec_in_keychain};
+pub use quick_capture::QuickCapture;
+pub use summarize::NoteSummarizer;
+pub use widget::WidgetReader;
 
-use dialog_lib::{Dialog, Note as LibNote};
+use dialog_lib::{CancellationToken, Dialog, DialogError, IdempotencyKey, Note as LibNote};
+use undo::{Change, UndoStack};
 use nostr_sdk::prelude::*;
 use once_cell::sync::OnceCell;
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 use tokio::{
     runtime::Runtime,
     sync::{broadcast, RwLock},
@@ -25,14 +82,50 @@ fn rt() -> &'static Runtime {
     })
 }
 
-// Global Dialog instance
-static DIALOG: OnceCell<Dialog> = OnceCell::new();
+// Quiet period after the last keystroke before a search actually runs
+const SEARCH_DEBOUNCE: Duration = Duration::from_millis(150);
+
+// Global Dialog instance, in an Arc for tasks like retention that own it
+static DIALOG: OnceCell<Arc<Dialog>> = OnceCell::new();
 
 pub struct DialogClient {
     notes: Arc<RwLock<HashMap<String, Note>>>,
     current_filter: Arc<RwLock<Option<String>>>,
     event_tx: broadcast::Sender<Event>,
     watch_handle: Arc<RwLock<Option<tokio::task::JoinHandle<()>>>>,
+    capabilities: Arc<std::sync::RwLock<ClientCapabilities>>,
+    // Last list delivered to the client, used to compute NotesDiff
+    last_emitted: Arc<RwLock<HashMap<String, Note>>>,
+    // Bumped on every SearchNotes; results from older generations are dropped
+    search_generation: Arc<AtomicU64>,
+    search_handle: Arc<std::sync::Mutex<Option<tokio::task::JoinHandle<()>>>>,
+    clock_skew_warned: Arc<AtomicBool>,
+    // Inbox mode: private messages are synced and watched only when enabled
+    inbox_enabled: Arc<AtomicBool>,
+    messages_handle: Arc<RwLock<Option<tokio::task::JoinHandle<()>>>>,
+    // Sender profiles by hex pubkey
+    profiles: Arc<RwLock<HashMap<String, Metadata>>>,
+    contacts: Arc<RwLock<Vec<Contact>>>,
+    // Set once ConnectRelay succeeds; writes are queued until then
+    relay_connected: Arc<AtomicBool>,
+    // Held while the offline queue (kept by dialog_lib) is being sent
+    offline_replay: Arc<tokio::sync::Mutex<()>>,
+    // Delete, read, pin, archive and transcript changes the user can take back
+    undo: Arc<std::sync::Mutex<UndoStack>>,
+    // Reports the hourly retention passes, which stop when it is aborted
+    retention_handle: Arc<std::sync::Mutex<Option<tokio::task::JoinHandle<()>>>>,
+    // Snapshot file for widgets, rewritten (debounced) after note changes
+    widget_path: Arc<std::sync::RwLock<Option<PathBuf>>>,
+    widget_handle: Arc<std::sync::Mutex<Option<tokio::task::JoinHandle<()>>>>,
+    // Shared by running syncs; CancelSync fires it and puts in a fresh one
+    sync_cancel: Arc<std::sync::Mutex<CancellationToken>>,
+    command_queue: Arc<std::sync::Mutex<queue::CommandQueue>>,
+    // Issued by request_account_deletion_token, taken by DeleteAccount
+    deletion_token: Arc<std::sync::Mutex<Option<String>>>,
+    // (service, account) when the nsec came from the keychain
+    keychain_entry: Arc<std::sync::Mutex<Option<(String, String)>>>,
+    // Events listeners missed by falling behind, for get_runtime_stats
+    events_lagged: Arc<AtomicU64>,
 }
 
 impl DialogClient {
@@ -48,9 +141,10 @@ impl DialogClient {
                 Err(e) => panic!("[uniffi] Failed to initialize Dialog: {e}"),
             }
         });
-        if DIALOG.set(dialog).is_err() {
+        if DIALOG.set(Arc::new(dialog)).is_err() {
             panic!("[uniffi] Dialog already initialized");
         }
+        provenance::record_app_provenance();
         
         let (event_tx, _) = broadcast::channel(1024);
         let client = Self {
@@ -58,8 +152,41 @@ impl DialogClient {
             current_filter: Arc::new(RwLock::new(None)),
             event_tx,
             watch_handle: Arc::new(RwLock::new(None)),
+            capabilities: Arc::new(std::sync::RwLock::new(ClientCapabilities::default())),
+            last_emitted: Arc::new(RwLock::new(HashMap::new())),
+            search_generation: Arc::new(AtomicU64::new(0)),
+            search_handle: Arc::new(std::sync::Mutex::new(None)),
+            clock_skew_warned: Arc::new(AtomicBool::new(false)),
+            inbox_enabled: Arc::new(AtomicBool::new(false)),
+            messages_handle: Arc::new(RwLock::new(None)),
+            profiles: Arc::new(RwLock::new(HashMap::new())),
+            contacts: Arc::new(RwLock::new(Vec::new())),
+            relay_connected: Arc::new(AtomicBool::new(false)),
+            offline_replay: Arc::new(tokio::sync::Mutex::new(())),
+            undo: Arc::new(std::sync::Mutex::new(UndoStack::default())),
+            retention_handle: Arc::new(std::sync::Mutex::new(None)),
+            widget_path: Arc::new(std::sync::RwLock::new(None)),
+            widget_handle: Arc::new(std::sync::Mutex::new(None)),
+            sync_cancel: Arc::new(std::sync::Mutex::new(CancellationToken::new())),
+            command_queue: Arc::default(),
+            deletion_token: Arc::default(),
+            keychain_entry: Arc::default(),
+            events_lagged: Arc::default(),
         };
         
+        // Forward publish throttling so the UI can show "uploading slowly"
+        let mut throttling = DIALOG.get().unwrap().watch_throttling();
+        let event_tx_clone = client.event_tx.clone();
+        rt().spawn(async move {
+            while throttling.changed().await.is_ok() {
+                let throttled = *throttling.borrow_and_update();
+                eprintln!("[uniffi] Publish throttling changed: {throttled}");
+                let _ = event_tx_clone.send(Event::PublishThrottled { throttled });
+            }
+        });
+        client.forward_budget_warnings();
+        client.forward_slow_operations();
+
         // Load initial notes from dialog_lib
         eprintln!("[uniffi] Loading initial notes...");
         let notes_clone = client.notes.clone();
@@ -75,6 +202,12 @@ impl DialogClient {
                 // Send ready event
                 eprintln!("[uniffi] Sending Event::Ready");
                 let _ = event_tx_clone.send(Event::Ready);
+                // Writes queued before the app was last closed
+                let queue = DIALOG.get().unwrap().offline_queue().unwrap_or_default();
+                if !queue.is_empty() {
+                    let pending = queue.into_iter().map(offline::offline_edit).collect();
+                    let _ = event_tx_clone.send(Event::OfflineQueueChanged { pending });
+                }
             } else {
                 eprintln!("[uniffi] Failed to load initial notes");
             }
@@ -83,17 +216,49 @@ impl DialogClient {
         client
     }
     pub fn start(self: Arc<Self>, listener: Box<dyn DialogListener>) {
+        self.start_with_interests(listener, EventCategory::ALL.to_vec());
+    }
+
+    /// Like start, but the listener only gets events in `interests`, e.g.
+    /// just Sync for a status widget. Each call adds another listener.
+    pub fn start_with_interests(
+        self: Arc<Self>,
+        listener: Box<dyn DialogListener>,
+        interests: Vec<EventCategory>,
+    ) {
         eprintln!("[uniffi] start() called; wiring listener and watch loop");
+        eprintln!("[uniffi] listener interests: {interests:?}");
+        // Rules kept from the last session apply again once someone listens
+        if self.retention_handle.lock().unwrap().is_none() {
+            self.clone().restart_retention();
+        }
         // Set up event forwarding to Swift (non-blocking)
         let mut rx = self.event_tx.subscribe();
         
         // Convert Box to Arc for sharing between threads
         let listener: Arc<dyn DialogListener> = Arc::from(listener);
         let listener_clone = listener.clone();
+        let wants_notes = interests.contains(&EventCategory::Notes);
+        let events_lagged = self.events_lagged.clone();
         
         // Spawn listener on background thread
         rt().spawn(async move {
-            while let Ok(event) = rx.recv().await {
+            loop {
+                let event = match rx.recv().await {
+                    Ok(event) => event,
+                    // A slow listener misses events rather than stopping
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        eprintln!("[uniffi] Listener lagged; dropped {skipped} events");
+                        events_lagged.fetch_add(skipped, Ordering::Relaxed);
+                        continue;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+                // Filtered before crossing the FFI boundary
+                if !interests.contains(&event.category()) {
+                    continue;
+                }
+                let event = event.for_list();
                 eprintln!("[uniffi] Dispatching event to Swift: {event:?}");
                 // Callback to Swift happens on background thread
                 // Swift will handle @MainActor transition
@@ -105,105 +270,304 @@ impl DialogClient {
         let self_clone = self.clone();
         rt().spawn(async move {
             self_clone.maybe_start_watch().await;
+            self_clone.emit_activity().await;
         });
         
         // Send initial data
-        let notes = self.get_notes(100, None);
+        if !wants_notes {
+            return;
+        }
+        let notes = self.get_notes(100, None, None);
         eprintln!("[uniffi] Emitting initial Event::NotesLoaded count={}", notes.len());
+        if let Ok(mut snapshot) = self.last_emitted.try_write() {
+            *snapshot = notes.iter().map(|n| (n.id.clone(), n.clone())).collect();
+        }
         listener.on_event(Event::NotesLoaded { notes });
     }
     
     pub fn stop(&self) {
         // Cleanup if needed
     }
+
+    pub fn set_capabilities(&self, capabilities: ClientCapabilities) {
+        eprintln!("[uniffi] set_capabilities: {capabilities:?}");
+        if let Ok(mut caps) = self.capabilities.write() {
+            *caps = capabilities;
+        }
+    }
     
     pub fn send_command(self: Arc<Self>, cmd: Command) {
         // Fire-and-forget: spawn work on Tokio runtime
         let self_clone = self.clone();
-        eprintln!("[uniffi] send_command: {:?}", cmd);
+        let cmd = cmd.with_hex_ids();
+        eprintln!("[uniffi] send_command: {}", cmd.name());
+        let in_flight = self.track_command(&cmd);
         rt().spawn(async move {
+            let _in_flight = in_flight;
             match cmd {
                 Command::ConnectRelay { relay_url } => {
                     eprintln!("[uniffi] Connecting to relay: {relay_url}");
-                    if let Err(e) = DIALOG.get().unwrap().connect_relay(&relay_url).await {
-                        eprintln!("[uniffi] Failed to connect to relay: {e}");
-                    } else {
-                        eprintln!("[uniffi] Connected to relay: {relay_url}");
-                        // After connecting, sync recent data and refresh UI
-                        if let Err(e) = DIALOG.get().unwrap().sync_notes().await {
-                            eprintln!("[uniffi] sync_notes failed: {e}");
-                        } else {
-                            // Load updated notes and emit NotesLoaded
-                            if let Ok(lib_notes) = DIALOG.get().unwrap().list_notes(100).await {
-                                let mut notes_map = self_clone.notes.write().await;
-                                let mut notes = Vec::new();
-                                for lib_note in lib_notes {
-                                    let note = convert_lib_note_to_uniffi(lib_note);
-                                    notes_map.insert(note.id.clone(), note.clone());
-                                    notes.push(note);
-                                }
-                                // Apply filter if set
-                                let filter = self_clone.current_filter.read().await.clone();
-                                if let Some(tag) = filter {
-                                    notes.retain(|n| n.tags.contains(&tag));
-                                }
-                                let _ = self_clone.event_tx.send(Event::NotesLoaded { notes });
-                            }
-                        }
-                        // Ensure watch loop is running
-                        self_clone.maybe_start_watch().await;
-                    }
+                    self_clone.connect_relay(relay_url).await;
                 }
-                Command::CreateNote { text } => {
+                Command::CreateNote { text, send_key } => {
                     eprintln!("[uniffi] CreateNote len={}", text.len());
-                    self_clone.create_note(text).await;
+                    self_clone.create_note(text, send_key, true).await;
+                }
+                Command::EditNote { id, text } => {
+                    eprintln!("[uniffi] EditNote id={id} len={}", text.len());
+                    self_clone.edit_note(id, text).await;
+                }
+                Command::CreateNoteStructured { text, tags, created_at_override } => {
+                    eprintln!("[uniffi] CreateNoteStructured len={}", text.len());
+                    self_clone.create_note_structured(text, tags, created_at_override).await;
+                }
+                Command::CreateNoteAnyway { text, send_key } => {
+                    eprintln!("[uniffi] CreateNoteAnyway len={}", text.len());
+                    self_clone.create_note(text, send_key, false).await;
+                }
+                Command::CreateVoiceNote { text, audio } => {
+                    eprintln!("[uniffi] CreateVoiceNote url={}", audio.url);
+                    self_clone.create_voice_note(text, audio).await;
+                }
+                Command::SetTranscript { id, transcript } => {
+                    eprintln!("[uniffi] SetTranscript id={id} len={}", transcript.len());
+                    self_clone.set_transcript(id, transcript).await;
                 }
                 Command::SetTagFilter { tag } => {
                     eprintln!("[uniffi] SetTagFilter tag={tag:?}");
                     self_clone.set_filter(tag).await;
                 }
+                Command::BulkTag { ids, add, remove } => {
+                    eprintln!("[uniffi] BulkTag ids={} add={add:?} remove={remove:?}", ids.len());
+                    self_clone.bulk_tag(ids, add, remove).await;
+                }
+                Command::SetNotifyRule { tag, notify } => {
+                    eprintln!("[uniffi] SetNotifyRule tag={tag} notify={notify:?}");
+                    self_clone.set_notify_rule(tag, notify).await;
+                }
+                Command::SetRetention { rules } => {
+                    eprintln!("[uniffi] SetRetention rules={}", rules.len());
+                    self_clone.set_retention(rules).await;
+                }
+                Command::SetEventBudget { budget } => {
+                    eprintln!("[uniffi] SetEventBudget budget={budget:?}");
+                    self_clone.set_event_budget(budget).await;
+                }
+                Command::CompactRemote => {
+                    eprintln!("[uniffi] CompactRemote");
+                    self_clone.compact_remote().await;
+                }
+                Command::SetSlowThreshold { ms } => {
+                    eprintln!("[uniffi] SetSlowThreshold ms={ms:?}");
+                    self_clone.set_slow_threshold(ms);
+                }
+                Command::ImportSettings { blob } => {
+                    eprintln!("[uniffi] ImportSettings");
+                    self_clone.import_settings(blob).await;
+                }
+                Command::SetDeviceName { name } => {
+                    eprintln!("[uniffi] SetDeviceName {name:?}");
+                    DIALOG.get().unwrap().set_device_name(name);
+                }
+                Command::SetDeviceIgnored { id, ignored } => {
+                    eprintln!("[uniffi] SetDeviceIgnored id={id} ignored={ignored}");
+                    self_clone.set_device_ignored(id, ignored).await;
+                }
+                Command::SetWidgetSnapshotPath { path } => {
+                    eprintln!("[uniffi] SetWidgetSnapshotPath set={}", path.is_some());
+                    self_clone.set_widget_snapshot_path(path);
+                }
+                Command::SetLocalCacheLimit { bytes } => {
+                    eprintln!("[uniffi] SetLocalCacheLimit bytes={bytes:?}");
+                    self_clone.set_local_cache_limit(bytes).await;
+                }
+                Command::HydrateNote { id } => {
+                    eprintln!("[uniffi] HydrateNote id={id}");
+                    self_clone.hydrate_note(id).await;
+                }
+                Command::SetKeepOffline { id, keep } => {
+                    eprintln!("[uniffi] SetKeepOffline id={id} keep={keep}");
+                    self_clone.set_keep_offline(id, keep).await;
+                }
+                Command::SetAttachmentCacheBudget { bytes } => {
+                    eprintln!("[uniffi] SetAttachmentCacheBudget bytes={bytes:?}");
+                    self_clone.set_attachment_cache_budget(bytes).await;
+                }
+                Command::RebuildIndex { from_relays } => {
+                    eprintln!("[uniffi] RebuildIndex from_relays={from_relays}");
+                    self_clone.rebuild_index(from_relays).await;
+                }
+                Command::DeleteAccount { token, delete_remote } => {
+                    eprintln!("[uniffi] DeleteAccount delete_remote={delete_remote}");
+                    self_clone.delete_account(token, delete_remote).await;
+                }
+                Command::SetChaos { config } => {
+                    eprintln!("[uniffi] SetChaos {config:?}");
+                    self_clone.set_chaos(config).await;
+                }
+                Command::ImportNotes { path, format, policy } => {
+                    eprintln!("[uniffi] ImportNotes {format:?} policy={policy:?}");
+                    self_clone.import_notes(path, format, policy).await;
+                }
                 Command::MarkAsRead { id } => {
                     eprintln!("[uniffi] MarkAsRead id={id}");
                     self_clone.mark_as_read(id).await;
                 }
+                Command::RecordView { id } => {
+                    eprintln!("[uniffi] RecordView id={id}");
+                    self_clone.record_view(id).await;
+                }
+                Command::LockNote { id, passphrase } => {
+                    eprintln!("[uniffi] LockNote id={id}");
+                    self_clone.lock_note(id, passphrase).await;
+                }
+                Command::UnlockNote { id, passphrase } => {
+                    eprintln!("[uniffi] UnlockNote id={id}");
+                    self_clone.unlock_note(id, passphrase).await;
+                }
+                Command::RelockNotes => {
+                    eprintln!("[uniffi] RelockNotes");
+                    self_clone.relock_notes().await;
+                }
+                Command::SetPinned { id, pinned } => {
+                    eprintln!("[uniffi] SetPinned id={id} pinned={pinned}");
+                    self_clone.set_pinned(id, pinned).await;
+                }
+                Command::SetArchived { id, archived } => {
+                    eprintln!("[uniffi] SetArchived id={id} archived={archived}");
+                    self_clone.set_archived(id, archived).await;
+                }
+                Command::SetTaskStatus { id, status } => {
+                    eprintln!("[uniffi] SetTaskStatus id={id} status={status:?}");
+                    self_clone.set_task_status(id, status).await;
+                }
+                Command::PublishPublic { id, strip_tags } => {
+                    eprintln!("[uniffi] PublishPublic id={id} strip_tags={strip_tags}");
+                    self_clone.publish_public(id, strip_tags).await;
+                }
+                Command::SetAppStateSync { enabled } => {
+                    eprintln!("[uniffi] SetAppStateSync enabled={enabled}");
+                    DIALOG.get().unwrap().set_app_state_sync(enabled);
+                    if enabled {
+                        // Pull other devices' state and push ours right away
+                        self_clone.sync().await;
+                    }
+                }
+                Command::CancelSync => {
+                    eprintln!("[uniffi] CancelSync");
+                    let cancel = std::mem::take(&mut *self_clone.sync_cancel.lock().unwrap());
+                    cancel.cancel();
+                }
                 Command::LoadNotes { limit } => {
                     eprintln!("[uniffi] LoadNotes limit={limit} (sync from dialog_lib)");
-                    // Sync from dialog_lib
-                    if let Ok(lib_notes) = DIALOG.get().unwrap().list_notes(limit as usize).await {
-                        let mut notes_map = self_clone.notes.write().await;
-                        let mut notes = Vec::new();
-                        
-                        for lib_note in lib_notes {
-                            let note = convert_lib_note_to_uniffi(lib_note);
-                            notes_map.insert(note.id.clone(), note.clone());
-                            notes.push(note);
-                        }
-                        
-                        // Apply filter if set
-                        let filter = self_clone.current_filter.read().await.clone();
-                        if let Some(tag) = filter {
-                            notes.retain(|n| n.tags.contains(&tag));
-                        }
-                        
-                        let _ = self_clone.event_tx.send(Event::NotesLoaded { notes });
-                    } else {
-                        eprintln!("[uniffi] list_notes failed");
-                    }
+                    self_clone.reload_notes(limit as usize).await;
                 }
                 Command::DeleteNote { id } => {
                     eprintln!("[uniffi] DeleteNote id={id}");
                     self_clone.delete_note(id).await;
                 }
+                Command::Undo => {
+                    eprintln!("[uniffi] Undo");
+                    self_clone.undo().await;
+                }
+                Command::Redo => {
+                    eprintln!("[uniffi] Redo");
+                    self_clone.redo().await;
+                }
+                Command::SetInboxEnabled { enabled } => {
+                    eprintln!("[uniffi] SetInboxEnabled enabled={enabled}");
+                    self_clone.set_inbox_enabled(enabled).await;
+                }
+                Command::LoadMessages { limit } => {
+                    eprintln!("[uniffi] LoadMessages limit={limit}");
+                    self_clone.reload_messages(limit as usize).await;
+                }
+                Command::LoadContacts => {
+                    eprintln!("[uniffi] LoadContacts");
+                    self_clone.load_contacts().await;
+                }
+                Command::PublishProfile { name, about, picture } => {
+                    eprintln!("[uniffi] PublishProfile");
+                    self_clone.publish_profile(name, about, picture).await;
+                }
+                Command::ShareNote { id, recipients } => {
+                    eprintln!("[uniffi] ShareNote id={id} recipients={}", recipients.len());
+                    self_clone.share_note(id, recipients).await;
+                }
+                Command::ResendShare { id, shared_at } => {
+                    eprintln!("[uniffi] ResendShare id={id} shared_at={shared_at}");
+                    self_clone.resend_share(id, shared_at).await;
+                }
+                Command::EnrichNote { id } => {
+                    eprintln!("[uniffi] EnrichNote id={id}");
+                    self_clone.enrich_note(id).await;
+                }
+                Command::SetAutoEnrich { enabled, proxy } => {
+                    eprintln!("[uniffi] SetAutoEnrich enabled={enabled} proxy={}", proxy.is_some());
+                    DIALOG.get().unwrap().set_enrich_settings(dialog_lib::EnrichSettings {
+                        auto_enrich: enabled,
+                        proxy,
+                    });
+                }
+                Command::SetMaxPrivacy { enabled } => {
+                    eprintln!("[uniffi] SetMaxPrivacy enabled={enabled}");
+                    self_clone.set_max_privacy(enabled).await;
+                }
+                Command::SetRelayPolicy { relay_url, kinds } => {
+                    eprintln!("[uniffi] SetRelayPolicy {relay_url} kinds={kinds:?}");
+                    self_clone.set_relay_policy(relay_url, kinds).await;
+                }
+                Command::SetNormalizeNotes { enabled } => {
+                    eprintln!("[uniffi] SetNormalizeNotes enabled={enabled}");
+                    DIALOG.get().unwrap().set_normalize_notes(enabled);
+                }
+                Command::DrainOutbox { outbox_path } => {
+                    eprintln!("[uniffi] DrainOutbox");
+                    self_clone.drain_outbox(outbox_path).await;
+                }
+                Command::ConfigureNwc { uri, max_payment_sats, budget_sats, pay_relays } => {
+                    eprintln!(
+                        "[uniffi] ConfigureNwc set={} max={max_payment_sats} budget={budget_sats}",
+                        uri.is_some()
+                    );
+                    self_clone
+                        .configure_nwc(uri, max_payment_sats, budget_sats, pay_relays)
+                        .await;
+                }
+                Command::SummarizeTag { tag, since, until } => {
+                    eprintln!("[uniffi] SummarizeTag tag={tag} since={since:?} until={until:?}");
+                    self_clone.summarize_tag(tag, since, until).await;
+                }
                 Command::SearchNotes { query } => {
                     eprintln!("[uniffi] SearchNotes query='{query}'");
-                    self_clone.search_notes(query).await;
+                    self_clone.schedule_search(query);
                 }
             }
         });
     }
     
     // Fast synchronous queries
-    pub fn get_notes(&self, limit: u32, tag: Option<String>) -> Vec<Note> {
+    /// The first `limit` notes in `order`, Smart if None
+    pub fn get_notes(
+        &self,
+        limit: u32,
+        tag: Option<String>,
+        order: Option<SortOrder>,
+    ) -> Vec<Note> {
+        let filter = NoteFilter {
+            tag,
+            ..Default::default()
+        };
+        self.get_filtered_notes(filter, limit, order)
+    }
+
+    /// [`Self::get_notes`] narrowed down by `filter`
+    pub fn get_filtered_notes(
+        &self,
+        filter: NoteFilter,
+        limit: u32,
+        order: Option<SortOrder>,
+    ) -> Vec<Note> {
         // Use try_read to avoid blocking in async context
         let notes = match self.notes.try_read() {
             Ok(guard) => guard,
@@ -215,12 +579,19 @@ impl DialogClient {
         };
         let mut result: Vec<Note> = notes
             .values()
-            .filter(|n| tag.as_ref().is_none_or(|t| n.tags.contains(t)))
+            .filter(|n| filter.matches(n))
             .cloned()
             .collect();
         
-        result.sort_by(|a, b| a.created_at.cmp(&b.created_at));
-        result.into_iter().take(limit as usize).collect()
+        let order = dialog_lib::SortOrder::from(order.unwrap_or(SortOrder::Smart));
+        let key = |note: &Note| dialog_lib::SortKey {
+            is_pinned: note.is_pinned,
+            is_read: note.is_read,
+            created_at: Timestamp::from(note.created_at.max(0) as u64),
+            id: note.id.clone(),
+        };
+        result.sort_by(|a, b| order.compare(&key(a), &key(b)));
+        result.into_iter().take(limit as usize).map(|n| n.for_list()).collect()
     }
     
     pub fn get_all_tags(&self) -> Vec<String> {
@@ -238,9 +609,72 @@ impl DialogClient {
         result.sort();
         result
     }
+
+    /// Existing tags that fit the text being written, best first
+    pub fn suggest_tags_for_text(&self, text: String, limit: u32) -> Vec<String> {
+        let dialog = DIALOG.get().unwrap();
+        match rt().block_on(dialog.suggest_tags_for_text(&text, limit as usize)) {
+            Ok(tags) => tags,
+            Err(e) => {
+                eprintln!("[uniffi] suggest_tags_for_text failed: {e}");
+                Vec::new()
+            }
+        }
+    }
+
+    /// Size, relay limits, tags and secrets of a draft, before it is sent
+    pub fn validate_note(&self, text: String) -> Option<ValidationReport> {
+        let dialog = DIALOG.get().unwrap();
+        match rt().block_on(dialog.validate_note(&text)) {
+            Ok(report) => Some(ValidationReport {
+                estimated_bytes: report.estimated_bytes as u64,
+                too_large: report.too_large,
+                rejected_by: report.rejected_by.iter().map(|url| url.to_string()).collect(),
+                tag_count: report.tag_count as u32,
+                secrets: report
+                    .warnings
+                    .into_iter()
+                    .map(|warning| match warning {
+                        dialog_lib::LintWarning::PotentialSecretDetected { masked, .. } => masked,
+                    })
+                    .collect(),
+            }),
+            Err(e) => {
+                eprintln!("[uniffi] validate_note failed: {e}");
+                None
+            }
+        }
+    }
     
     pub fn get_note(&self, id: String) -> Option<Note> {
-        self.notes.try_read().ok()?.get(&id).cloned()
+        self.get_note_full(id).map(|note| note.for_list())
+    }
+
+    pub fn get_note_full(&self, id: String) -> Option<Note> {
+        let hex = parse_note_id(id.clone())?;
+        if let Some(note) = self.notes.try_read().ok()?.get(&hex) {
+            return Some(note.clone());
+        }
+        // Not loaded, or replaced by an edit since the link was made
+        match rt().block_on(DIALOG.get().unwrap().resolve_note_ref(&id)) {
+            Ok(lib_note) => Some(convert_lib_note_to_uniffi(lib_note)),
+            Err(e) => {
+                eprintln!("[uniffi] get_note {id} failed: {e}");
+                None
+            }
+        }
+    }
+
+    /// Stable `nostr:nevent1...` link for other apps, e.g. task managers
+    pub fn get_external_ref(&self, note_id: String) -> Option<String> {
+        let id = dialog_lib::parse_note_id(&note_id).ok()?;
+        match rt().block_on(DIALOG.get().unwrap().external_ref(&id)) {
+            Ok(uri) => Some(uri),
+            Err(e) => {
+                eprintln!("[uniffi] external_ref {note_id} failed: {e}");
+                None
+            }
+        }
     }
     
     pub fn get_unread_count(&self, tag: Option<String>) -> u32 {
@@ -251,83 +685,724 @@ impl DialogClient {
         notes
             .values()
             .filter(|n| !n.is_read)
-            .filter(|n| tag.as_ref().map_or(true, |t| n.tags.contains(t)))
+            .filter(|n| tag.as_ref().is_none_or(|t| n.tags.contains(t)))
             .count() as u32
     }
     
+    pub fn get_contacts(&self) -> Vec<Contact> {
+        match self.contacts.try_read() {
+            Ok(contacts) => contacts.clone(),
+            Err(_) => Vec::new(),
+        }
+    }
+    
     // Private async helpers
-    async fn create_note(self: Arc<Self>, text: String) {
+    async fn connect_relay(self: Arc<Self>, relay_url: String) {
+        let dialog = DIALOG.get().unwrap();
+        if let Err(e) = dialog.connect_relay(&relay_url).await {
+            eprintln!("[uniffi] Failed to connect to relay: {e}");
+            let message = format!("Couldn't connect to {relay_url}: {e}");
+            self.emit(Event::failed(message, &e)).await;
+            return;
+        }
+        eprintln!("[uniffi] Connected to relay: {relay_url}");
+        self.relay_connected.store(true, Ordering::SeqCst);
+        self.replay_offline_queue().await;
+
+        // After connecting, sync recent data and refresh UI
+        self.clone().sync().await;
+
+        // Ensure watch loop is running
+        self.clone().maybe_start_watch().await;
+
+        if self.inbox_enabled.load(Ordering::SeqCst) {
+            self.start_inbox().await;
+        }
+    }
+
+    /// Run (or join) a dialog_lib sync and reload the list if it succeeded
+    async fn sync(self: Arc<Self>) {
+        let _ = self.event_tx.send(Event::SyncStatusChanged { syncing: true });
+        let cancel = self.sync_cancel.lock().unwrap().clone();
+        match DIALOG.get().unwrap().sync_cancellable(&cancel).await {
+            Ok(report) => {
+                eprintln!("[uniffi] sync finished: {report:?}");
+                if !report.relays.is_empty() {
+                    let relays = report
+                        .relays
+                        .into_iter()
+                        .map(|relay| RelaySyncStats {
+                            url: relay.url.to_string(),
+                            fetched: relay.fetched as u32,
+                            exclusive: relay.exclusive as u32,
+                            error: relay.error,
+                        })
+                        .collect();
+                    self.emit(Event::SyncProgress { relays }).await;
+                }
+                self.emit(Event::SyncComplete {
+                    mode_used: report.mode.into(),
+                    received: report.received as u32,
+                    sent: report.sent as u32,
+                    duration_ms: report.duration.as_millis() as u64,
+                    fell_back: report.fell_back,
+                })
+                .await;
+                self.clone().reload_notes(100).await;
+                self.replay_offline_queue().await;
+            }
+            Err(DialogError::Cancelled) => eprintln!("[uniffi] sync cancelled"),
+            Err(e) => {
+                eprintln!("[uniffi] sync failed: {e}");
+                self.emit(Event::failed(format!("Sync failed: {e}"), &e)).await;
+            }
+        }
+        let _ = self.event_tx.send(Event::SyncStatusChanged { syncing: false });
+    }
+
+    /// Reload notes from dialog_lib into the cache and emit the filtered list
+    async fn reload_notes(self: Arc<Self>, limit: usize) {
+        let lib_notes = match DIALOG.get().unwrap().list_notes(limit).await {
+            Ok(lib_notes) => lib_notes,
+            Err(e) => {
+                eprintln!("[uniffi] list_notes failed: {e}");
+                return;
+            }
+        };
+
+        if let Some(newest) = lib_notes.iter().map(|n| n.created_at).max() {
+            self.check_clock_skew(newest);
+        }
+
+        let mut notes = Vec::new();
+        {
+            let mut notes_map = self.notes.write().await;
+            for lib_note in lib_notes {
+                let note = convert_lib_note_to_uniffi(lib_note);
+                notes_map.insert(note.id.clone(), note.clone());
+                notes.push(note);
+            }
+        }
+
+        // Apply filter if set
+        let filter = self.current_filter.read().await.clone();
+        if let Some(tag) = filter {
+            notes.retain(|n| n.tags.contains(&tag));
+        }
+        self.emit_notes(notes).await;
+    }
+
+    async fn create_note(self: Arc<Self>, text: String, send_key: String, check_secrets: bool) {
         // Create note via dialog_lib
         eprintln!("[uniffi] create_note() begin");
-        match DIALOG.get().unwrap().create_note(&text).await {
-            Ok(note_id) => {
-                eprintln!("[uniffi] create_note() saved id={}", note_id.to_hex());
-                // Construct a provisional Note immediately using the returned id
-                let tags: Vec<String> = text
-                    .split_whitespace()
-                    .filter(|w| w.starts_with('#') && w.len() > 1)
-                    .map(|t| t[1..].to_lowercase())
-                    .collect();
-                let note = Note {
-                    id: note_id.to_hex(),
-                    text: text.clone(),
-                    tags,
-                    created_at: nostr_sdk::prelude::Timestamp::now().as_u64() as i64,
-                    is_read: false,
-                    is_synced: false,
-                };
-                // Update state and emit event
-                self.notes.write().await.insert(note.id.clone(), note.clone());
+        let warnings = DIALOG.get().unwrap().lint_note(&text).await;
+        let secrets: Vec<String> = warnings
+            .into_iter()
+            .map(|warning| match warning {
+                dialog_lib::LintWarning::PotentialSecretDetected { masked, .. } => masked,
+            })
+            .collect();
+        if check_secrets && !secrets.is_empty() {
+            eprintln!("[uniffi] create_note() held back: {} possible secrets", secrets.len());
+            let event = Event::PotentialSecretDetected { text, secrets, send_key };
+            self.emit(event).await;
+            return;
+        }
+        // An empty key would make every keyless send look like one
+        let key = if send_key.is_empty() {
+            IdempotencyKey::from(uuid::Uuid::new_v4().to_string())
+        } else {
+            IdempotencyKey::from(send_key)
+        };
+        if self.writes_offline() {
+            let result = DIALOG.get().unwrap().save_note_idempotent(&text, &key).await;
+            let saved = result.as_ref().ok().map(|note| note.id);
+            self.note_created(result).await;
+            if let Some(note_id) = saved {
+                self.publish_or_queue(note_id, OfflineEditKind::Create).await;
+            }
+            return;
+        }
+        let result = DIALOG.get().unwrap().create_note_idempotent(&text, &key).await;
+        self.note_created(result).await;
+    }
+
+    /// From App Intents: no secret check, since nobody is there to confirm
+    async fn create_note_structured(
+        self: Arc<Self>,
+        text: String,
+        tags: Vec<String>,
+        created_at_override: Option<i64>,
+    ) {
+        eprintln!("[uniffi] create_note_structured() begin tags={}", tags.len());
+        let created_at = created_at_override.map(|secs| Timestamp::from(secs.max(0) as u64));
+        let result = DIALOG
+            .get()
+            .unwrap()
+            .create_note_with_tags(&text, &tags, created_at)
+            .await;
+        self.note_created(result).await;
+    }
+
+    async fn note_created(&self, result: dialog_lib::Result<LibNote>) {
+        match result {
+            Ok(lib_note) => {
+                eprintln!("[uniffi] create_note() saved id={}", lib_note.id.to_hex());
+                // Provisional note carries the signed event's created_at, so it
+                // keeps its position when the relay echo replaces it
+                let note = convert_lib_note_to_uniffi(lib_note);
+                // Update state and emit event; a deduplicated send is
+                // already listed
+                let existing = self.notes.write().await.insert(note.id.clone(), note.clone());
+                if existing.is_some() {
+                    return;
+                }
                 eprintln!("[uniffi] create_note() emitting NoteAdded id={}", note.id);
-                let _ = self.event_tx.send(Event::NoteAdded { note });
+                self.emit(Event::NoteAdded { note }).await;
+                self.emit_activity().await;
             }
             Err(e) => {
                 eprintln!("[uniffi] create_note() failed: {}", e);
+                let message = match &e {
+                    dialog_lib::DialogError::NoteTooLarge { max, actual } => format!(
+                        "Note is too long ({} KB, limit {} KB)",
+                        actual.div_ceil(1024),
+                        max / 1024
+                    ),
+                    e => format!("Failed to save note: {e}"),
+                };
+                self.emit(Event::failed(message, &e)).await;
             }
         }
     }
     
+    async fn configure_nwc(
+        &self,
+        uri: Option<String>,
+        max_payment_sats: u64,
+        budget_sats: u64,
+        pay_relays: Vec<String>,
+    ) {
+        let dialog = DIALOG.get().unwrap();
+        dialog.set_max_relay_payment(max_payment_sats);
+        let pay_relays: Vec<&str> = pay_relays.iter().map(String::as_str).collect();
+        let limits = dialog
+            .set_payable_relays(&pay_relays)
+            .and_then(|()| dialog.set_relay_payment_budget(budget_sats));
+        if let Err(e) = limits {
+            eprintln!("[uniffi] configure_nwc failed: {e}");
+            let message = format!("Couldn't set relay payment limits: {e}");
+            self.emit(Event::failed(message, &e)).await;
+            return;
+        }
+        let Some(uri) = uri else {
+            dialog.clear_nwc();
+            return;
+        };
+        if let Err(e) = dialog.configure_nwc(&uri) {
+            eprintln!("[uniffi] configure_nwc failed: {e}");
+            let message = format!("Couldn't connect wallet: {e}");
+            self.emit(Event::failed(message, &e)).await;
+        }
+    }
+
+    async fn create_voice_note(self: Arc<Self>, text: String, audio: Attachment) {
+        let lib_audio = dialog_lib::Attachment {
+            url: audio.url.clone(),
+            mime_type: audio.mime_type.clone(),
+            size: audio.size,
+            sha256: audio.sha256.clone(),
+        };
+        match DIALOG.get().unwrap().create_voice_note(&text, lib_audio).await {
+            Ok(lib_note) => {
+                let note = convert_lib_note_to_uniffi(lib_note);
+                let note_id = note.id.clone();
+                self.notes.write().await.insert(note.id.clone(), note.clone());
+                self.emit(Event::NoteAdded { note }).await;
+                let _ = self.event_tx.send(Event::TranscriptionRequested { note_id, audio });
+                self.emit_activity().await;
+            }
+            Err(e) => {
+                eprintln!("[uniffi] create_voice_note failed: {e}");
+                let message = format!("Failed to save voice note: {e}");
+                self.emit(Event::failed(message, &e)).await;
+            }
+        }
+    }
+
+    async fn set_transcript(self: Arc<Self>, id: String, transcript: String) {
+        let before = self.notes.read().await.get(&id).and_then(|note| note.transcript.clone());
+        let after = Some(transcript.trim().to_string()).filter(|t| !t.is_empty());
+        self.change(Change::SetTranscript { id, before, after }).await;
+    }
+
     async fn set_filter(self: Arc<Self>, tag: Option<String>) {
         *self.current_filter.write().await = tag.clone();
         let _ = self.event_tx.send(Event::TagFilterChanged { tag: tag.clone() });
         
         // Re-send filtered notes
-        let notes = self.get_notes(100, tag);
-        let _ = self.event_tx.send(Event::NotesLoaded { notes });
+        let notes = self.get_notes(100, tag, None);
+        self.emit_notes(notes).await;
     }
     
     async fn mark_as_read(self: Arc<Self>, id: String) {
-        // Mark as read via dialog_lib
-        if let Ok(event_id) = EventId::from_hex(&id) {
-            if let Ok(_) = DIALOG.get().unwrap().mark_as_read(&event_id).await {
-                let mut notes = self.notes.write().await;
-                if let Some(note) = notes.get_mut(&id) {
-                    note.is_read = true;
-                    let _ = self.event_tx.send(Event::NoteUpdated { note: note.clone() });
+        self.change(Change::SetRead { id, read: true }).await;
+    }
+
+    async fn set_pinned(self: Arc<Self>, id: String, pinned: bool) {
+        self.change(Change::SetPinned { id, pinned }).await;
+    }
+
+    async fn set_archived(self: Arc<Self>, id: String, archived: bool) {
+        self.change(Change::SetArchived { id, archived }).await;
+    }
+
+    async fn lock_note(self: Arc<Self>, id: String, passphrase: String) {
+        let Ok(event_id) = EventId::from_hex(&id) else {
+            return;
+        };
+        match DIALOG.get().unwrap().lock_note(&event_id, &passphrase).await {
+            Ok(lib_note) => {
+                // The locked copy replaces the note under a new id
+                let note = convert_lib_note_to_uniffi(lib_note);
+                {
+                    let mut notes = self.notes.write().await;
+                    notes.remove(&id);
+                    notes.insert(note.id.clone(), note.clone());
                 }
+                self.emit(Event::NoteDeleted { id }).await;
+                self.emit(Event::NoteAdded { note }).await;
+            }
+            Err(e) => {
+                eprintln!("[uniffi] lock_note failed: {e}");
+                let message = format!("Failed to lock note: {e}");
+                self.emit(Event::failed(message, &e).about(&id)).await;
             }
         }
     }
+
+    async fn bulk_tag(self: Arc<Self>, ids: Vec<String>, add: Vec<String>, remove: Vec<String>) {
+        let mut ids: Vec<EventId> = ids.iter().filter_map(|id| EventId::from_hex(id).ok()).collect();
+        let dialog = DIALOG.get().unwrap();
+        let mut failed = 0;
+        for (tags, adding) in [(add, true), (remove, false)] {
+            if tags.is_empty() {
+                continue;
+            }
+            let report = if adding {
+                dialog.add_tags(&ids, &tags).await
+            } else {
+                dialog.remove_tags(&ids, &tags).await
+            };
+            failed += report.failed.len();
+            for (old_id, lib_note) in report.updated {
+                // Later passes work on the copies
+                if let Some(id) = ids.iter_mut().find(|id| **id == old_id) {
+                    *id = lib_note.id;
+                }
+                let note = convert_lib_note_to_uniffi(lib_note);
+                let old_id = old_id.to_hex();
+                {
+                    let mut notes = self.notes.write().await;
+                    notes.remove(&old_id);
+                    notes.insert(note.id.clone(), note.clone());
+                }
+                self.emit(Event::NoteDeleted { id: old_id }).await;
+                self.emit(Event::NoteAdded { note }).await;
+            }
+        }
+        if failed > 0 {
+            let message = format!("Couldn't update tags on {failed} notes");
+            self.emit(Event::error(ErrorCode::Network, message)).await;
+        }
+    }
+
+    async fn unlock_note(self: Arc<Self>, id: String, passphrase: String) {
+        let Ok(event_id) = EventId::from_hex(&id) else {
+            return;
+        };
+        match DIALOG.get().unwrap().unlock_note(&event_id, &passphrase).await {
+            Ok(lib_note) => {
+                let note = convert_lib_note_to_uniffi(lib_note);
+                self.notes.write().await.insert(note.id.clone(), note.clone());
+                self.emit(Event::NoteUpdated { note }).await;
+            }
+            Err(e) => {
+                eprintln!("[uniffi] unlock_note failed: {e}");
+                let message = match &e {
+                    dialog_lib::DialogError::WrongPassphrase => "Wrong passphrase".to_string(),
+                    e => format!("Failed to unlock note: {e}"),
+                };
+                self.emit(Event::failed(message, &e).about(&id)).await;
+            }
+        }
+    }
+
+    async fn relock_notes(self: Arc<Self>) {
+        DIALOG.get().unwrap().relock_notes();
+        let relocked: Vec<Note> = self
+            .notes
+            .write()
+            .await
+            .values_mut()
+            .filter(|note| note.is_locked && note.text != dialog_lib::LOCKED_PLACEHOLDER)
+            .map(|note| {
+                note.text = dialog_lib::LOCKED_PLACEHOLDER.to_string();
+                note.tags.clear();
+                note.clone()
+            })
+            .collect();
+        for note in relocked {
+            self.emit(Event::NoteUpdated { note }).await;
+        }
+    }
+
+    async fn publish_public(self: Arc<Self>, id: String, strip_tags: bool) {
+        let Ok(event_id) = EventId::from_hex(&id) else {
+            return;
+        };
+        let options = dialog_lib::PublishOptions { strip_tags };
+        match DIALOG.get().unwrap().publish_public(&event_id, options).await {
+            Ok(public_id) => {
+                let public_id = public_id.to_hex();
+                self.update_note(&id, |note| note.public_id = Some(public_id)).await;
+            }
+            Err(e) => {
+                eprintln!("[uniffi] publish_public failed: {e}");
+                let message = format!("Failed to publish note: {e}");
+                self.emit(Event::failed(message, &e).about(&id)).await;
+            }
+        }
+    }
+
+    async fn load_contacts(self: Arc<Self>) {
+        let lib_contacts = match DIALOG.get().unwrap().fetch_contacts().await {
+            Ok(lib_contacts) => lib_contacts,
+            Err(e) => {
+                eprintln!("[uniffi] fetch_contacts failed: {e}");
+                return;
+            }
+        };
+        let contacts: Vec<Contact> = lib_contacts
+            .into_iter()
+            .map(|c| Contact {
+                name: c.display_name().map(str::to_string),
+                picture: c.profile.and_then(|p| p.picture),
+                pubkey: c.pubkey.to_bech32().unwrap_or_else(|_| c.pubkey.to_hex()),
+            })
+            .collect();
+        *self.contacts.write().await = contacts.clone();
+        let _ = self.event_tx.send(Event::ContactsLoaded { contacts });
+    }
+
+    async fn enrich_note(self: Arc<Self>, id: String) {
+        let Ok(event_id) = EventId::from_hex(&id) else {
+            return;
+        };
+        match DIALOG.get().unwrap().enrich_note(&event_id).await {
+            Ok(previews) => {
+                let previews = previews
+                    .into_iter()
+                    .map(|p| LinkPreview {
+                        url: p.url,
+                        title: p.title,
+                        description: p.description,
+                    })
+                    .collect();
+                let _ = self.event_tx.send(Event::LinkPreviewsLoaded { note_id: id, previews });
+            }
+            Err(e) => eprintln!("[uniffi] enrich_note failed: {e}"),
+        }
+    }
+
+    /// Apply a change to a cached note and emit NoteUpdated
+    async fn update_note(&self, id: &str, update: impl FnOnce(&mut Note)) {
+        let updated = self.notes.write().await.get_mut(id).map(|note| {
+            update(note);
+            note.clone()
+        });
+        if let Some(note) = updated {
+            self.emit(Event::NoteUpdated { note }).await;
+        }
+    }
     
     async fn delete_note(self: Arc<Self>, id: String) {
-        let mut notes = self.notes.write().await;
-        if notes.remove(&id).is_some() {
-            let _ = self.event_tx.send(Event::NoteDeleted { id });
+        let note = self.notes.read().await.get(&id).cloned();
+        if let Some(note) = note {
+            self.change(Change::Delete { note }).await;
         }
     }
+
+    /// Make a change the user asked for and put it on the undo stack
+    async fn change(&self, change: Change) {
+        // Notes the app hasn't loaded and no-op changes leave no undo step
+        let undoable = self
+            .notes
+            .read()
+            .await
+            .get(change.note_id())
+            .is_some_and(|note| change.changes(note));
+        if self.apply(&change).await && undoable {
+            self.undo.lock().unwrap().record(change);
+            self.emit_undo_state().await;
+        }
+    }
+
+    async fn undo(self: Arc<Self>) {
+        let change = self.undo.lock().unwrap().pop_undo();
+        let Some(change) = change else {
+            return;
+        };
+        if self.apply(&change.inverse()).await {
+            self.undo.lock().unwrap().push_redo(change);
+        } else {
+            let message = "Couldn't undo the last change".to_string();
+            self.emit(Event::error(ErrorCode::Internal, message)).await;
+        }
+        self.emit_undo_state().await;
+    }
+
+    async fn redo(self: Arc<Self>) {
+        let change = self.undo.lock().unwrap().pop_redo();
+        let Some(change) = change else {
+            return;
+        };
+        if self.apply(&change).await {
+            self.undo.lock().unwrap().push_undo(change);
+        } else {
+            let message = "Couldn't redo the change".to_string();
+            self.emit(Event::error(ErrorCode::Internal, message)).await;
+        }
+        self.emit_undo_state().await;
+    }
+
+    /// Carry out a change in dialog_lib and the cache, returning whether it took
+    async fn apply(&self, change: &Change) -> bool {
+        let dialog = DIALOG.get().unwrap();
+        let event_id = EventId::from_hex(change.note_id());
+        match change {
+            // Removed locally at once; relays are asked through the queue
+            Change::Delete { note } => {
+                let Ok(event_id) = event_id else {
+                    return false;
+                };
+                let removed = dialog.with_batch_offline(|batch| batch.delete(event_id)).await;
+                if let Err(e) = removed {
+                    eprintln!("[uniffi] delete failed: {e}");
+                    return false;
+                }
+                if self.notes.write().await.remove(&note.id).is_some() {
+                    self.emit(Event::NoteDeleted { id: note.id.clone() }).await;
+                }
+                self.publish_or_queue(event_id, OfflineEditKind::Delete).await;
+                true
+            }
+            Change::Restore { note } => {
+                let Ok(event_id) = event_id else {
+                    return false;
+                };
+                let unsent = match dialog.restore_note(&event_id) {
+                    Ok(unsent) => unsent,
+                    Err(e) => {
+                        eprintln!("[uniffi] restore failed: {e}");
+                        return false;
+                    }
+                };
+                self.notes.write().await.insert(note.id.clone(), note.clone());
+                self.emit(Event::NoteAdded { note: note.clone() }).await;
+                if unsent {
+                    self.queue_changed(dialog.offline_queue());
+                } else {
+                    // Relays that honored the deletion keep it deleted
+                    self.publish_or_queue(event_id, OfflineEditKind::Create).await;
+                }
+                true
+            }
+            Change::SetRead { id, read } => {
+                let read = *read;
+                let Ok(event_id) = event_id else {
+                    return false;
+                };
+                if let Err(e) = dialog.set_read(&event_id, read).await {
+                    eprintln!("[uniffi] set_read failed: {e}");
+                    return false;
+                }
+                self.update_note(id, |note| note.is_read = read).await;
+                true
+            }
+            Change::SetPinned { id, pinned } => {
+                let pinned = *pinned;
+                let Ok(event_id) = event_id else {
+                    return false;
+                };
+                if let Err(e) = dialog.set_pinned(&event_id, pinned).await {
+                    eprintln!("[uniffi] set_pinned failed: {e}");
+                    return false;
+                }
+                self.update_note(id, |note| note.is_pinned = pinned).await;
+                true
+            }
+            Change::SetArchived { id, archived } => {
+                let archived = *archived;
+                let Ok(event_id) = event_id else {
+                    return false;
+                };
+                if let Err(e) = dialog.set_archived(&event_id, archived).await {
+                    eprintln!("[uniffi] set_archived failed: {e}");
+                    return false;
+                }
+                self.update_note(id, |note| note.is_archived = archived).await;
+                true
+            }
+            Change::SetTaskStatus { id, after, .. } => {
+                let Ok(event_id) = event_id else {
+                    return false;
+                };
+                let status = after.map(Into::into);
+                if let Err(e) = dialog.set_task_status(&event_id, status).await {
+                    eprintln!("[uniffi] set_task_status failed: {e}");
+                    return false;
+                }
+                let after = *after;
+                self.update_note(id, |note| note.task_status = after).await;
+                true
+            }
+            Change::SetTranscript { id, after, .. } => {
+                let Ok(event_id) = event_id else {
+                    return false;
+                };
+                // An empty transcript clears it
+                let transcript = after.as_deref().unwrap_or("");
+                if let Err(e) = dialog.set_transcript(&event_id, transcript).await {
+                    eprintln!("[uniffi] set_transcript failed: {e}");
+                    return false;
+                }
+                let after = after.clone();
+                self.update_note(id, |note| note.transcript = after).await;
+                true
+            }
+        }
+    }
+
+    async fn emit_undo_state(&self) {
+        let (can_undo, can_redo) = {
+            let stack = self.undo.lock().unwrap();
+            (stack.can_undo(), stack.can_redo())
+        };
+        self.emit(Event::UndoStateChanged { can_undo, can_redo }).await;
+    }
     
-    async fn search_notes(self: Arc<Self>, query: String) {
-        let notes = self.notes.read().await;
-        let query_lower = query.to_lowercase();
-        let results: Vec<Note> = notes
+    /// Debounce and run a search, cancelling any search still in flight.
+    /// Only the newest query's results ever reach the client.
+    fn schedule_search(self: Arc<Self>, query: String) {
+        // Hold the slot while bumping so generation order matches handle order
+        let mut slot = self.search_handle.lock().unwrap();
+        let generation = self.search_generation.fetch_add(1, Ordering::SeqCst) + 1;
+        let this = self.clone();
+        let handle = rt().spawn(async move {
+            tokio::time::sleep(SEARCH_DEBOUNCE).await;
+            if this.search_generation.load(Ordering::SeqCst) != generation {
+                return;
+            }
+            this.search_notes(query, generation).await;
+        });
+
+        if let Some(previous) = slot.replace(handle) {
+            previous.abort();
+        }
+    }
+
+    async fn search_notes(&self, query: String, generation: u64) {
+        let search = dialog_lib::SearchQuery::new(&query);
+        let results: Vec<Note> = self
+            .notes
+            .read()
+            .await
             .values()
-            .filter(|n| n.text.to_lowercase().contains(&query_lower))
+            .filter(|n| {
+                search.matches(&n.text)
+                    || n.transcript.as_ref().is_some_and(|t| search.matches(t))
+            })
             .cloned()
             .collect();
-        let _ = self.event_tx.send(Event::NotesLoaded { notes: results });
+        if self.search_generation.load(Ordering::SeqCst) != generation {
+            eprintln!("[uniffi] Dropping stale search results for '{query}'");
+            return;
+        }
+        self.emit_notes(results).await;
+    }
+
+    /// Send a list update, as a NotesDiff against the last snapshot if the
+    /// client opted in, otherwise as a full NotesLoaded.
+    async fn emit_notes(&self, notes: Vec<Note>) {
+        let diff_enabled = self.capabilities.read().is_ok_and(|c| c.notes_diff);
+        let mut snapshot = self.last_emitted.write().await;
+        let next: HashMap<String, Note> = notes.iter().map(|n| (n.id.clone(), n.clone())).collect();
+        if diff_enabled {
+            let diff = diff::diff_notes(&snapshot, &notes);
+            *snapshot = next;
+            if diff.is_empty() {
+                return;
+            }
+            eprintln!(
+                "[uniffi] Emitting Event::NotesDiff added={} updated={} removed={}",
+                diff.added.len(),
+                diff.updated.len(),
+                diff.removed_ids.len()
+            );
+            let _ = self.event_tx.send(Event::NotesDiff {
+                added: diff.added,
+                updated: diff.updated,
+                removed_ids: diff.removed_ids,
+            });
+        } else {
+            *snapshot = next;
+            let _ = self.event_tx.send(Event::NotesLoaded { notes });
+        }
+        self.refresh_widget_snapshot();
+    }
+
+    /// Warn the client (once per session) if a note is dated well in the
+    /// future, which means this device's clock or the author's is off
+    fn check_clock_skew(&self, created_at: Timestamp) {
+        let Some(skew_secs) = dialog_lib::detect_clock_skew(created_at, Timestamp::now()) else {
+            return;
+        };
+        if !self.clock_skew_warned.swap(true, Ordering::SeqCst) {
+            eprintln!("[uniffi] Clock skew detected: note dated {skew_secs}s in the future");
+            let _ = self.event_tx.send(Event::ClockSkewWarning { skew_secs });
+        }
+    }
+
+    /// Send a single-note event, keeping the diff snapshot in step with it
+    async fn emit(&self, event: Event) {
+        {
+            let mut snapshot = self.last_emitted.write().await;
+            match &event {
+                Event::NoteAdded { note } | Event::NoteUpdated { note } => {
+                    snapshot.insert(note.id.clone(), note.clone());
+                }
+                Event::NoteDeleted { id } => {
+                    snapshot.remove(id);
+                }
+                _ => {}
+            }
+        }
+        if matches!(
+            event,
+            Event::NoteAdded { .. } | Event::NoteUpdated { .. } | Event::NoteDeleted { .. }
+        ) {
+            self.refresh_widget_snapshot();
+        }
+        let _ = self.event_tx.send(event);
     }
 }
 
+/// Tags that would be extracted from `text`, for the composer preview
+pub fn parse_hashtags(text: String) -> Vec<String> {
+    dialog_lib::parse_hashtags(&text)
+}
+
 pub trait DialogListener: Send + Sync {
     fn on_event(&self, event: Event);
 }
@@ -336,11 +1411,30 @@ pub trait DialogListener: Send + Sync {
 fn convert_lib_note_to_uniffi(lib_note: LibNote) -> Note {
     Note {
         id: lib_note.id.to_hex(),
-        text: lib_note.text,
         tags: lib_note.tags,
         created_at: lib_note.created_at.as_u64() as i64,
         is_read: lib_note.is_read,
+        is_pinned: lib_note.is_pinned,
+        is_archived: lib_note.is_archived,
+        public_id: lib_note.public_id.map(|id| id.to_hex()),
+        audio: lib_note.audio.map(|audio| Attachment {
+            url: audio.url,
+            mime_type: audio.mime_type,
+            size: audio.size,
+            sha256: audio.sha256,
+        }),
+        transcript: lib_note.transcript,
+        is_locked: lib_note.is_locked,
         is_synced: lib_note.is_synced,
+        preview: dialog_lib::preview(&lib_note.text),
+        text: lib_note.text,
+        is_truncated: false,
+        is_hydrated: lib_note.is_hydrated,
+        edited: lib_note.edited,
+        edit_of: lib_note.edit_of.map(|id| id.to_hex()),
+        task_status: lib_note.task_status.map(Into::into),
+        word_count: lib_note.word_count as u32,
+        reading_minutes: lib_note.reading_minutes() as u32,
     }
 }
 
@@ -351,22 +1445,63 @@ impl DialogClient {
             return;
         }
         // Try to acquire a receiver
-        match DIALOG.get().unwrap().watch_notes().await {
+        let dialog = DIALOG.get().unwrap();
+        match dialog.watch_notes().await {
             Ok(mut receiver) => {
                 eprintln!("[uniffi] watch_notes receiver acquired; entering loop");
+                // Read/pin/archive changes made on other devices
+                let mut states = match dialog.watch_state_changes().await {
+                    Ok(states) => states,
+                    Err(e) => {
+                        eprintln!("[uniffi] watch_state_changes() failed to start: {}", e);
+                        tokio::sync::mpsc::channel(1).1
+                    }
+                };
                 let this = self.clone();
                 let handle = rt().spawn(async move {
-                    while let Some(lib_note) = receiver.recv().await {
+                    loop {
+                        let lib_note = tokio::select! {
+                            Some(change) = states.recv() => {
+                                let state = change.state;
+                                this.update_note(&change.note_id.to_hex(), |note| {
+                                    note.is_read = state.is_read();
+                                    note.is_pinned = state.is_pinned();
+                                    note.is_archived = state.is_archived();
+                                })
+                                .await;
+                                continue;
+                            }
+                            lib_note = receiver.recv() => match lib_note {
+                                Some(lib_note) => lib_note,
+                                None => break,
+                            },
+                        };
+                        this.check_clock_skew(lib_note.created_at);
+                        let rule = this.notification_rule(&lib_note).await;
                         let note = convert_lib_note_to_uniffi(lib_note);
-                        let mut notes_guard = this.notes.write().await;
-                        if notes_guard.contains_key(&note.id) {
-                            notes_guard.insert(note.id.clone(), note.clone());
+                        // An edit from another device takes its original's place
+                        if let Some(old_id) = note.edit_of.clone() {
+                            if this.notes.write().await.remove(&old_id).is_some() {
+                                this.emit(Event::NoteDeleted { id: old_id }).await;
+                            }
+                        }
+                        let existed = this
+                            .notes
+                            .write()
+                            .await
+                            .insert(note.id.clone(), note.clone())
+                            .is_some();
+                        if existed {
                             eprintln!("[uniffi] Emitting Event::NoteUpdated {{ id={} }}", note.id);
-                            let _ = this.event_tx.send(Event::NoteUpdated { note });
+                            this.emit(Event::NoteUpdated { note }).await;
                         } else {
-                            notes_guard.insert(note.id.clone(), note.clone());
                             eprintln!("[uniffi] Emitting Event::NoteAdded {{ id={} }}", note.id);
-                            let _ = this.event_tx.send(Event::NoteAdded { note });
+                            this.emit(Event::NoteAdded { note: note.clone() }).await;
+                            // Only notes new to this device notify, not edits
+                            if let Some(rule) = rule.filter(|_| note.edit_of.is_none()) {
+                                eprintln!("[uniffi] Emitting Event::NotificationWorthy {{ tag={} }}", rule.tag);
+                                this.emit(Event::NotificationWorthy { note, rule }).await;
+                            }
                         }
                     }
                 });
@@ -374,6 +1509,8 @@ impl DialogClient {
             }
             Err(e) => {
                 eprintln!("[uniffi] watch_notes() failed to start: {}", e);
+                let message = format!("New notes won't appear until restart: {e}");
+                self.emit(Event::failed(message, &e)).await;
             }
         }
     }
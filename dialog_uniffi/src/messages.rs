@@ -0,0 +1,110 @@
+use crate::{DIALOG, DialogClient, DirectMessage, Event, rt};
+use dialog_lib::DirectMessage as LibMessage;
+use nostr_sdk::prelude::*;
+use std::sync::{Arc, atomic::Ordering};
+
+impl DialogClient {
+    pub(crate) async fn set_inbox_enabled(self: Arc<Self>, enabled: bool) {
+        self.inbox_enabled.store(enabled, Ordering::SeqCst);
+        if enabled {
+            self.start_inbox().await;
+            return;
+        }
+        if let Some(handle) = self.messages_handle.write().await.take() {
+            handle.abort();
+        }
+        let _ = self.event_tx.send(Event::MessagesLoaded { messages: Vec::new() });
+    }
+
+    /// Pull messages from relays, emit what's stored and keep watching for more
+    pub(crate) async fn start_inbox(self: Arc<Self>) {
+        let dialog = DIALOG.get().unwrap();
+        match dialog.sync_messages().await {
+            Ok(received) => eprintln!("[uniffi] sync_messages: {received} new"),
+            Err(e) => eprintln!("[uniffi] sync_messages failed: {e}"),
+        }
+        self.clone().reload_messages(100).await;
+
+        let mut slot = self.messages_handle.write().await;
+        if slot.is_some() {
+            return;
+        }
+        match dialog.watch_messages().await {
+            Ok(mut receiver) => {
+                eprintln!("[uniffi] watch_messages receiver acquired");
+                let this = self.clone();
+                *slot = Some(rt().spawn(async move {
+                    while let Some(lib_message) = receiver.recv().await {
+                        let message = this.convert_message(lib_message).await;
+                        let _ = this.event_tx.send(Event::MessageReceived { message });
+                    }
+                }));
+            }
+            Err(e) => eprintln!("[uniffi] watch_messages failed: {e}"),
+        }
+    }
+
+    pub(crate) async fn reload_messages(self: Arc<Self>, limit: usize) {
+        if !self.inbox_enabled.load(Ordering::SeqCst) {
+            return;
+        }
+        let lib_messages = match DIALOG.get().unwrap().list_messages(limit).await {
+            Ok(lib_messages) => lib_messages,
+            Err(e) => {
+                eprintln!("[uniffi] list_messages failed: {e}");
+                return;
+            }
+        };
+
+        // Fetch every sender's profile in one request before converting
+        self.load_profiles(lib_messages.iter().map(|m| m.sender)).await;
+        let mut messages = Vec::with_capacity(lib_messages.len());
+        for lib_message in lib_messages {
+            messages.push(self.convert_message(lib_message).await);
+        }
+        let _ = self.event_tx.send(Event::MessagesLoaded { messages });
+    }
+
+    /// Make sure profiles for `pubkeys` are cached. Senders without a
+    /// profile are cached as empty so they aren't fetched again.
+    pub(crate) async fn load_profiles(&self, pubkeys: impl IntoIterator<Item = PublicKey>) {
+        let missing: Vec<PublicKey> = {
+            let profiles = self.profiles.read().await;
+            pubkeys
+                .into_iter()
+                .filter(|pk| !profiles.contains_key(&pk.to_hex()))
+                .collect()
+        };
+        if missing.is_empty() {
+            return;
+        }
+
+        let mut fetched = match DIALOG.get().unwrap().fetch_profiles(missing.clone()).await {
+            Ok(fetched) => fetched,
+            Err(e) => {
+                eprintln!("[uniffi] fetch_profiles failed: {e}");
+                return;
+            }
+        };
+        let mut profiles = self.profiles.write().await;
+        for pubkey in missing {
+            let metadata = fetched.remove(&pubkey).unwrap_or_default();
+            profiles.insert(pubkey.to_hex(), metadata);
+        }
+    }
+
+    async fn convert_message(&self, lib_message: LibMessage) -> DirectMessage {
+        let sender = lib_message.sender;
+        self.load_profiles([sender]).await;
+        let profiles = self.profiles.read().await;
+        let profile = profiles.get(&sender.to_hex());
+        DirectMessage {
+            id: lib_message.id.to_hex(),
+            sender_pubkey: sender.to_bech32().unwrap_or_else(|_| sender.to_hex()),
+            sender_name: profile.and_then(|p| p.display_name.clone().or_else(|| p.name.clone())),
+            sender_picture: profile.and_then(|p| p.picture.clone()),
+            text: lib_message.text,
+            created_at: lib_message.created_at.as_u64() as i64,
+        }
+    }
+}
@@ -1,55 +1,819 @@
 use chrono::Utc;
 use uuid::Uuid;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Note {
     pub id: String,
     pub text: String,
     pub tags: Vec<String>,
     pub created_at: i64,  // Changed to i64 to match Swift expectations
     pub is_read: bool,
+    pub is_pinned: bool,
+    pub is_archived: bool,
+    pub public_id: Option<String>,  // Set once published as a public kind-1
+    pub audio: Option<Attachment>,  // Voice notes only
+    pub transcript: Option<String>,
+    pub is_locked: bool,  // text is a placeholder until UnlockNote
     pub is_synced: bool,
+    pub preview: String,  // First ~200 characters, for list rows
+    pub is_truncated: bool,  // text is only the preview; see get_note_full
+    pub is_hydrated: bool,  // false once evicted from the local cache; text is empty
+    pub edited: bool,  // text changed since it was written; see get_edit_history
+    pub edit_of: Option<String>,  // the note this copy replaced
+    pub task_status: Option<TaskStatus>,  // None unless made a task
+    pub word_count: u32,  // hashtags left out; kept while evicted, 0 while locked
+    pub reading_minutes: u32,  // at 200 words a minute, rounded up
+}
+
+/// Which notes get_filtered_notes returns; unset fields match every note
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct NoteFilter {
+    pub tag: Option<String>,
+    pub min_words: Option<u32>,  // e.g. 300 for a long-form reading mode
+    pub max_words: Option<u32>,
+}
+
+impl NoteFilter {
+    pub fn matches(&self, note: &Note) -> bool {
+        self.tag.as_ref().is_none_or(|t| note.tags.contains(t))
+            && self.min_words.is_none_or(|min| note.word_count >= min)
+            && self.max_words.is_none_or(|max| note.word_count <= max)
+    }
+}
+
+/// Where a note stands as a task
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TaskStatus {
+    Todo,
+    Doing,
+    Done,
+}
+
+/// Notes longer than this cross the FFI as their preview, except from
+/// get_note_full
+pub const LIST_TEXT_MAX_BYTES: usize = 4096;
+
+/// Uploaded file referenced by a note, e.g. a voice memo recording
+#[derive(Clone, Debug, PartialEq)]
+pub struct Attachment {
+    pub url: String,
+    pub mime_type: String,
+    pub size: u64,
+    pub sha256: Option<String>,
 }
 
 impl Note {
     pub fn from_text(text: String) -> Self {
-        // Parse hashtags
-        let tags = text
-            .split_whitespace()
-            .filter(|word| word.starts_with('#') && word.len() > 1)
-            .map(|tag| tag[1..].to_lowercase())
-            .collect();
-        
+        let tags = dialog_lib::parse_hashtags(&text);
+        let words = dialog_lib::word_count(&text);
+
         Self {
             id: Uuid::new_v4().to_string(),
+            preview: dialog_lib::preview(&text),
             text,
             tags,
             created_at: Utc::now().timestamp(),
             is_read: false,
+            is_pinned: false,
+            is_archived: false,
+            public_id: None,
+            audio: None,
+            transcript: None,
+            is_locked: false,
             is_synced: false,
+            is_truncated: false,
+            is_hydrated: true,
+            edited: false,
+            edit_of: None,
+            task_status: None,
+            word_count: words as u32,
+            reading_minutes: dialog_lib::reading_minutes(words) as u32,
+        }
+    }
+
+    /// The note as list views get it: a fresh preview, and very long text
+    /// swapped for that preview
+    pub fn for_list(&self) -> Self {
+        let mut note = self.clone();
+        note.preview = dialog_lib::preview(&note.text);
+        if note.text.len() > LIST_TEXT_MAX_BYTES {
+            note.text = note.preview.clone();
+            note.is_truncated = true;
+        }
+        note
+    }
+}
+
+/// A private (NIP-17) message from someone else
+#[derive(Clone, Debug, PartialEq)]
+pub struct DirectMessage {
+    pub id: String,
+    pub sender_pubkey: String,  // npub
+    pub sender_name: Option<String>,
+    pub sender_picture: Option<String>,
+    pub text: String,
+    pub created_at: i64,
+}
+
+/// Someone from our follow list, for the share sheet picker
+#[derive(Clone, Debug, PartialEq)]
+pub struct Contact {
+    pub pubkey: String,  // npub
+    pub name: Option<String>,
+    pub picture: Option<String>,
+}
+
+/// Our own profile (kind 0), for the profile editor
+#[derive(Clone, Debug, PartialEq)]
+pub struct Profile {
+    pub pubkey: String,  // npub
+    pub name: Option<String>,
+    pub about: Option<String>,
+    pub picture: Option<String>,
+}
+
+/// How far a shared note got to one recipient
+#[derive(Clone, Debug, PartialEq)]
+pub enum ShareStatus {
+    Pending,  // not sent yet, e.g. the app quit mid-share
+    Delivered { relays: Vec<String> },  // relays that acknowledged it
+    Failed { reason: String },
+}
+
+/// Someone a note was shared with, for the detail view
+#[derive(Clone, Debug, PartialEq)]
+pub struct ShareRecipient {
+    pub pubkey: String,  // npub
+    pub name: Option<String>,  // from cached profiles
+    pub status: ShareStatus,
+}
+
+/// A note sent privately (NIP-17) to several people at once
+#[derive(Clone, Debug, PartialEq)]
+pub struct GroupShare {
+    pub note_id: String,
+    pub shared_at: i64,  // identifies the share for ResendShare
+    pub recipients: Vec<ShareRecipient>,
+}
+
+/// When new notes under a tag should notify
+#[derive(Clone, Debug, PartialEq)]
+pub enum NotifyRule {
+    Off,  // watch only
+    All,
+    Keyword { keyword: String },  // only notes mentioning it, ignoring case
+}
+
+/// A tag's notification rule
+#[derive(Clone, Debug, PartialEq)]
+pub struct NotificationRule {
+    pub tag: String,
+    pub notify: NotifyRule,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RetentionAction {
+    Archive,
+    Purge,  // deleted here and on relays that honour deletion requests
+}
+
+/// A retention setting, e.g. archive everything older than 365 days
+/// unless pinned; tag None applies to every note
+#[derive(Clone, Debug, PartialEq)]
+pub struct RetentionRule {
+    pub tag: Option<String>,
+    pub older_than_days: u32,
+    pub action: RetentionAction,
+    pub keep_pinned: bool,
+}
+
+/// Note ids (hex) a retention pass changed, or would change if dry_run
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RetentionReport {
+    pub dry_run: bool,
+    pub archived: Vec<String>,
+    pub purged: Vec<String>,
+    pub failed: Vec<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ImportFormat {
+    Eml,         // email messages, tagged #email
+    Keep,        // Google Keep from a Takeout .zip
+    Simplenote,  // export .zip or its notes.json
+}
+
+/// What to do with imported notes that look like ones already stored
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DuplicatePolicy {
+    Skip,
+    Merge,      // add their tags and pin/archive state to the stored note
+    Duplicate,  // import them anyway
+}
+
+/// Counts from ImportNotes; `failed` reads "<file>: <reason>"
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ImportReport {
+    pub created: u32,
+    pub skipped: u32,
+    pub merged: u32,
+    pub failed: Vec<String>,
+}
+
+/// How to write a note id for people, e.g. nevent1... for sharing
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NoteIdFormat {
+    Hex,  // what Note.id and events use
+    Note,
+    Nevent,
+}
+
+/// Order get_notes returns notes in
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SortOrder {
+    NewestFirst,
+    OldestFirst,
+    Smart,  // pinned, then unread, then the rest; newest first in each
+}
+
+impl From<SortOrder> for dialog_lib::SortOrder {
+    fn from(order: SortOrder) -> Self {
+        match order {
+            SortOrder::NewestFirst => dialog_lib::SortOrder::NewestFirst,
+            SortOrder::OldestFirst => dialog_lib::SortOrder::OldestFirst,
+            SortOrder::Smart => dialog_lib::SortOrder::Smart,
+        }
+    }
+}
+
+/// How a sync reached the relays
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SyncMode {
+    Negentropy,  // only the differences were exchanged
+    Plain,       // recent notes were fetched whole
+}
+
+impl From<dialog_lib::SyncMode> for SyncMode {
+    fn from(mode: dialog_lib::SyncMode) -> Self {
+        match mode {
+            dialog_lib::SyncMode::Negentropy => SyncMode::Negentropy,
+            dialog_lib::SyncMode::Plain => SyncMode::Plain,
         }
     }
 }
 
+/// A note as Spotlight indexes it
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchIndexEntry {
+    pub id: String,
+    pub title: String,  // first line without hashtags, shortened
+    pub tags: Vec<String>,
+    pub created_at: i64,
+    pub modified_at: i64,
+}
+
+/// Index changes since the cursor passed to get_search_index_entries
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchIndexChanges {
+    pub entries: Vec<SearchIndexEntry>,  // add or replace
+    pub removed_ids: Vec<String>,
+    pub reset: bool,  // delete everything indexed before applying entries
+    pub cursor: i64,  // pass as `since` next time
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WidgetKind {
+    Small,  // unread count and the newest note
+    Medium,  // unread count and the three newest notes
+    Large,  // also the pinned note
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct WidgetNote {
+    pub id: String,
+    pub title: String,
+    pub created_at: i64,
+    pub link: String,  // dialog://note/<id> for widgetURL
+}
+
+/// What a widget shows, read from the snapshot file the app keeps
+#[derive(Clone, Debug, PartialEq)]
+pub struct WidgetSnapshot {
+    pub unread_count: u32,
+    pub recent: Vec<WidgetNote>,
+    pub pinned: Option<WidgetNote>,
+    pub updated_at: i64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RelayHealth {
+    pub url: String,
+    pub connected: bool,
+    pub status: String,  // e.g. "Connected", "Disconnected"
+}
+
+/// How the most recent sync ended
+#[derive(Clone, Debug, PartialEq)]
+pub struct LastSync {
+    pub finished_at: i64,
+    pub received: u32,
+    pub sent: u32,
+    pub error: Option<String>,  // set if the sync failed
+}
+
+/// What one relay returned to a plain sync
+#[derive(Clone, Debug, PartialEq)]
+pub struct RelaySyncStats {
+    pub url: String,
+    pub fetched: u32,
+    pub exclusive: u32,  // events no other relay returned
+    pub error: Option<String>,
+}
+
+/// What sending a draft would run into, for warnings in the composer
+#[derive(Clone, Debug, PartialEq)]
+pub struct ValidationReport {
+    pub estimated_bytes: u64,  // the encrypted note as sent to relays
+    pub too_large: bool,  // over the note size limit; sending would fail
+    pub rejected_by: Vec<String>,  // relays whose message limit it exceeds
+    pub tag_count: u32,
+    pub secrets: Vec<String>,  // masked, as in PotentialSecretDetected
+}
+
+/// Everything the status screen shows
+#[derive(Clone, Debug, PartialEq)]
+pub struct DialogStatus {
+    pub relays: Vec<RelayHealth>,
+    pub pending_outbox: u32,  // notes queued by extensions, not yet saved
+    pub syncing: bool,
+    pub last_sync: Option<LastSync>,  // None until a sync has finished
+    pub db_size_bytes: Option<u64>,
+    pub watches: Vec<String>,
+    pub event_counts: Vec<RelayEventCount>,  // empty until a budget is set
+    pub error: Option<String>,  // the status itself couldn't be read
+}
+
+/// Event kinds published to a relay; relays without one get every kind
+#[derive(Clone, Debug, PartialEq)]
+pub struct RelayPolicy {
+    pub relay_url: String,
+    pub kinds: Vec<u16>,
+}
+
+/// Events a relay holds for this key, against the event budget
+#[derive(Clone, Debug, PartialEq)]
+pub struct RelayEventCount {
+    pub url: String,
+    pub count: u64,
+    pub budget: Option<u64>,
+    pub near_limit: bool,  // within 10% of the budget, or over it
+}
+
+/// Attachment files stored on this device
+#[derive(Clone, Debug, PartialEq)]
+pub struct AttachmentCacheStats {
+    pub files: u32,
+    pub bytes: u64,
+    pub pinned_files: u32,  // of notes kept offline, never evicted
+    pub pinned_bytes: u64,
+    pub budget: Option<u64>,
+}
+
+/// Faults to inject with SetChaos; rates are 0.0 to 1.0
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChaosConfig {
+    pub latency_ms: u64,  // before every publish to a relay and every sync
+    pub jitter_ms: u64,   // up to this much more, at random
+    pub drop_rate: f64,
+    pub decrypt_failure_rate: f64,
+    pub seed: u64,        // same seed, same failures
+}
+
+/// How a note was captured
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CaptureSource {
+    App,
+    Cli,
+    ShareExtension,  // queued by QuickCapture and picked up by the app
+    Import,
+}
+
+/// Where a note came from, for the detail view
+#[derive(Clone, Debug, PartialEq)]
+pub struct Provenance {
+    pub device_id: String,
+    pub device_name: Option<String>,
+    pub app_version: Option<String>,
+    pub source: Option<CaptureSource>,  // None on notes from before it was recorded
+}
+
+/// How long a list, search, sync or decrypt took
+#[derive(Clone, Debug, PartialEq)]
+pub struct OpTiming {
+    pub name: String,
+    pub ms: u64,
+    pub at: u64,  // when it finished, unix seconds
+}
+
+/// Memory and handle usage, for leak hunting in long sessions
+#[derive(Clone, Debug, PartialEq)]
+pub struct RuntimeStats {
+    pub notes_cached: u32,
+    pub notes_cached_bytes: u64,  // estimate: struct sizes plus text and tags
+    pub profiles_cached: u32,
+    pub subscriptions: u32,  // open relay subscriptions
+    pub watches: u32,
+    pub seen_ids: u64,  // event ids remembered by watches for de-duplication
+    pub unlocked_notes: u32,
+    pub relay_info_cached: u32,
+    pub tokio_workers: u32,
+    pub tokio_alive_tasks: u64,
+    pub tokio_queue_depth: u64,  // tasks waiting in the runtime's global queue
+    pub event_listeners: u32,
+    pub events_queued: u64,  // events not yet taken by the slowest listener
+    pub events_lagged: u64,  // events listeners missed by falling behind
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OfflineEditKind {
+    Create,
+    Edit,
+    Delete,  // removed locally; relays are asked to delete it
+}
+
+/// A change made on this device that relays haven't received yet
+#[derive(Clone, Debug, PartialEq)]
+pub struct OfflineEdit {
+    pub note_id: String,  // hex; an edit's new copy
+    pub kind: OfflineEditKind,
+    pub queued_at: i64,
+}
+
+/// A command sent with send_command that is still running
+#[derive(Clone, Debug, PartialEq)]
+pub struct PendingCommand {
+    pub id: u64,
+    pub name: String,  // Command variant, e.g. "CreateNote"
+    pub started_at: i64,
+    pub writes: bool,  // publishing a change; quitting now could lose it
+}
+
+/// A device writing notes with this key
+#[derive(Clone, Debug, PartialEq)]
+pub struct Device {
+    pub id: String,
+    pub name: Option<String>,
+    pub last_active: i64,  // 0 if nothing from it is stored yet
+    pub notes: u32,
+    pub is_current: bool,
+    pub ignored: bool,  // its notes are hidden
+}
+
+/// Where a dialog:// URL leads
+#[derive(Clone, Debug, PartialEq)]
+pub enum DeepLink {
+    Note { id: String },  // hex
+    Tag { tag: String },
+}
+
+/// Stored title/description for a link in a note
+#[derive(Clone, Debug, PartialEq)]
+pub struct LinkPreview {
+    pub url: String,
+    pub title: Option<String>,
+    pub description: Option<String>,
+}
+
+/// What kind of failure an Error event reports, so the app can react
+/// without matching on the message
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ErrorCode {
+    Network,  // relays unreachable or refusing; usually passes
+    Storage,  // the local database or disk
+    NotFound,  // the note is gone or was replaced
+    TooLarge,
+    WrongPassphrase,
+    InvalidInput,  // something the user entered or imported
+    ServiceUnavailable,  // an outside service, e.g. the summarizer
+    Cancelled,
+    Internal,
+}
+
+/// What the app could offer the user after an Error event
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SuggestedAction {
+    Retry,
+    CheckConnection,
+    EditInput,
+    EnterPassphrase,
+    Refresh,
+}
+
 #[derive(Clone, Debug)]
 pub enum Event {
     Ready,  // Sent when Dialog is initialized
     NotesLoaded { notes: Vec<Note> },
+    NotesDiff { added: Vec<Note>, updated: Vec<Note>, removed_ids: Vec<String> },
     NoteAdded { note: Note },
     NoteUpdated { note: Note },
     NoteDeleted { id: String },
     TagFilterChanged { tag: Option<String> },
     SyncStatusChanged { syncing: bool },
-    Error { message: String },
+    // Relays were fetched from concurrently and their notes merged; sent
+    // before SyncStatusChanged(false)
+    SyncProgress { relays: Vec<RelaySyncStats> },
+    // A sync finished. Plain with fell_back means negentropy was tried and
+    // failed; sent before SyncStatusChanged(false)
+    SyncComplete {
+        mode_used: SyncMode,
+        received: u32,
+        sent: u32,
+        duration_ms: u64,
+        fell_back: bool,
+    },
+    // A note is dated further in the future than clock drift allows
+    ClockSkewWarning { skew_secs: u64 },
+    // Publishing is being rate limited by us or a relay
+    PublishThrottled { throttled: bool },
+    // Relays within 10% of the event budget, e.g. to offer CompactRemote;
+    // sent again with an empty list once none are
+    EventBudgetWarning { relays: Vec<RelayEventCount> },
+    // CompactRemote asked relays to delete `removed` stale events
+    RemoteCompacted { removed: u32 },
+    // A list, search, sync or decrypt took at least the slow threshold
+    // (500ms unless changed with SetSlowThreshold)
+    SlowOperation { name: String, ms: u64 },
+    // Inbox mode: private messages, kept separate from notes
+    MessagesLoaded { messages: Vec<DirectMessage> },
+    MessageReceived { message: DirectMessage },
+    ContactsLoaded { contacts: Vec<Contact> },
+    // PublishProfile reached a relay; also in get_my_profile()
+    ProfilePublished { profile: Profile },
+    // ShareNote or ResendShare finished; check each recipient's status
+    NoteShared { share: GroupShare },
+    LinkPreviewsLoaded { note_id: String, previews: Vec<LinkPreview> },
+    // A voice note was created; transcribe `audio` and reply with SetTranscript
+    TranscriptionRequested { note_id: String, audio: Attachment },
+    // A new note matched a tag's notification rule; sent after NoteAdded
+    NotificationWorthy { note: Note, rule: NotificationRule },
+    // The undo/redo stacks changed, e.g. to enable shake-to-undo
+    UndoStateChanged { can_undo: bool, can_redo: bool },
+    // A note was created (also sent on start), for a writing streak
+    ActivityUpdated { today_count: u32, streak_days: u32 },
+    // Writes saved while offline, in the order they will be sent; show a
+    // pending badge on these notes
+    OfflineQueueChanged { pending: Vec<OfflineEdit> },
+    // A command was sent or finished; see get_pending_commands()
+    QueueChanged { pending: Vec<PendingCommand> },
+    // A new note looks like it contains a private key and wasn't saved;
+    // `secrets` are masked. Confirm with CreateNoteAnyway.
+    PotentialSecretDetected { text: String, secrets: Vec<String>, send_key: String },
+    // DeleteAccount finished; the client can't be used any more.
+    // `relays` accepted every deletion request.
+    AccountDeleted { remote_requested: u32, relays: Vec<String>, keychain_cleared: bool },
+    // ImportNotes finished; sent after the NoteAdded events for its notes
+    ImportFinished { report: ImportReport },
+    // RebuildIndex finished; the notes were reloaded as NotesLoaded first
+    IndexRebuilt { notes: u32, indexed: u32, fetched: u32 },
+    // `message` is for people; `code` and the rest for retry/backoff logic
+    Error {
+        message: String,
+        code: ErrorCode,
+        retryable: bool,
+        related_note_id: Option<String>,
+        suggested_action: Option<SuggestedAction>,
+    },
+}
+
+/// Groups of events a listener can ask for with start_with_interests
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EventCategory {
+    Notes,     // notes, tags, drafts and undo
+    Messages,  // inbox messages, contacts and our profile
+    Relays,    // relay status: throttling, event budget, compaction
+    Sync,      // sync progress and queued writes
+    Errors,    // Error and warnings meant for the user
+    Logs,      // diagnostics such as SlowOperation
+}
+
+impl EventCategory {
+    pub const ALL: [EventCategory; 6] = [
+        EventCategory::Notes,
+        EventCategory::Messages,
+        EventCategory::Relays,
+        EventCategory::Sync,
+        EventCategory::Errors,
+        EventCategory::Logs,
+    ];
+}
+
+impl Event {
+    /// No wildcard, so a new event has to be given a category
+    pub fn category(&self) -> EventCategory {
+        match self {
+            Event::Ready
+            | Event::NotesLoaded { .. }
+            | Event::NotesDiff { .. }
+            | Event::NoteAdded { .. }
+            | Event::NoteUpdated { .. }
+            | Event::NoteDeleted { .. }
+            | Event::TagFilterChanged { .. }
+            | Event::LinkPreviewsLoaded { .. }
+            | Event::TranscriptionRequested { .. }
+            | Event::NotificationWorthy { .. }
+            | Event::UndoStateChanged { .. }
+            | Event::ActivityUpdated { .. }
+            | Event::PotentialSecretDetected { .. }
+            | Event::AccountDeleted { .. }
+            | Event::ImportFinished { .. }
+            | Event::IndexRebuilt { .. } => EventCategory::Notes,
+            Event::MessagesLoaded { .. }
+            | Event::MessageReceived { .. }
+            | Event::ContactsLoaded { .. }
+            | Event::ProfilePublished { .. }
+            | Event::NoteShared { .. } => EventCategory::Messages,
+            Event::PublishThrottled { .. }
+            | Event::EventBudgetWarning { .. }
+            | Event::RemoteCompacted { .. } => EventCategory::Relays,
+            Event::SyncStatusChanged { .. }
+            | Event::SyncProgress { .. }
+            | Event::SyncComplete { .. }
+            | Event::OfflineQueueChanged { .. }
+            | Event::QueueChanged { .. } => EventCategory::Sync,
+            Event::ClockSkewWarning { .. } | Event::Error { .. } => EventCategory::Errors,
+            Event::SlowOperation { .. } => EventCategory::Logs,
+        }
+    }
+
+    /// Apply [`Note::for_list`] to every note carried, so megabyte notes
+    /// aren't serialized for each list update
+    pub fn for_list(self) -> Self {
+        let list = |notes: Vec<Note>| notes.iter().map(Note::for_list).collect();
+        match self {
+            Event::NotesLoaded { notes } => Event::NotesLoaded { notes: list(notes) },
+            Event::NotesDiff { added, updated, removed_ids } => Event::NotesDiff {
+                added: list(added),
+                updated: list(updated),
+                removed_ids,
+            },
+            Event::NoteAdded { note } => Event::NoteAdded { note: note.for_list() },
+            Event::NoteUpdated { note } => Event::NoteUpdated { note: note.for_list() },
+            Event::NotificationWorthy { note, rule } => Event::NotificationWorthy {
+                note: note.for_list(),
+                rule,
+            },
+            other => other,
+        }
+    }
+}
+
+/// Optional behaviours a client can opt into via `DialogClient::set_capabilities`
+#[derive(Clone, Debug, Default)]
+pub struct ClientCapabilities {
+    /// Receive `Event::NotesDiff` instead of full `Event::NotesLoaded` lists
+    pub notes_diff: bool,
 }
 
+// Note ids may be hex, note1... or nevent1...
 #[derive(Clone, Debug)]
 pub enum Command {
     ConnectRelay { relay_url: String },
-    CreateNote { text: String },
+    // `send_key` identifies the compose action, e.g. a UUID made when the
+    // composer opens; sends repeated with it within 30s create one note.
+    // An empty key never matches another send.
+    CreateNote { text: String, send_key: String },
+    // Create a note PotentialSecretDetected held back, as written
+    CreateNoteAnyway { text: String, send_key: String },
+    // From Shortcuts: `tags` are added to the text's hashtags, and
+    // `created_at_override` (unix seconds) backdates an imported item
+    CreateNoteStructured { text: String, tags: Vec<String>, created_at_override: Option<i64> },
+    // `audio` has already been uploaded by the app
+    CreateVoiceNote { text: String, audio: Attachment },
+    // Replace a note's text; the copy arrives as NoteDeleted plus NoteAdded.
+    // Offline it is saved and sent once a relay is connected
+    EditNote { id: String, text: String },
+    SetTranscript { id: String, transcript: String },
     DeleteNote { id: String },
+    // Take back or repeat the last delete, read, pin, archive or
+    // transcript change
+    Undo,
+    Redo,
     MarkAsRead { id: String },
+    // The note was opened; keeps it out of the review queue for a while
+    RecordView { id: String },
+    // Replace a note with a copy only readable with the passphrase; it
+    // arrives as NoteDeleted for the old id plus NoteAdded
+    LockNote { id: String, passphrase: String },
+    // Reveal a locked note until RelockNotes; arrives as NoteUpdated
+    UnlockNote { id: String, passphrase: String },
+    RelockNotes,
+    SetPinned { id: String, pinned: bool },
+    SetArchived { id: String, archived: bool },
+    // Make a note a task or move it along; None makes it a plain note
+    // again. Arrives as NoteUpdated and can be undone
+    SetTaskStatus { id: String, status: Option<TaskStatus> },
+    // Post a note publicly; the app confirms with the user before sending
+    PublishPublic { id: String, strip_tags: bool },
+    // Opt in to syncing read/pin/archive state across devices via relays
+    SetAppStateSync { enabled: bool },
+    // Abort running syncs; they end with SyncStatusChanged(false)
+    CancelSync,
+    // Also receive private messages other people send us
+    SetInboxEnabled { enabled: bool },
+    LoadMessages { limit: u32 },
+    // Refresh the follow list and profiles; read back with get_contacts()
+    LoadContacts,
+    // Name, about and picture URL others see instead of our pubkey; None
+    // or blank clears a field. Answered with ProfilePublished
+    PublishProfile { name: Option<String>, about: Option<String>, picture: Option<String> },
+    // Send a note privately to each recipient (npub or hex); answered with
+    // NoteShared, also in get_note_shares()
+    ShareNote { id: String, recipients: Vec<String> },
+    // Try recipients of a share that weren't delivered again
+    ResendShare { id: String, shared_at: i64 },
+    // Load (fetching if needed) link previews for a note
+    EnrichNote { id: String },
+    // Privacy toggle: fetch previews for new notes' links automatically
+    SetAutoEnrich { enabled: bool, proxy: Option<String> },
+    // Privacy toggle: publish new notes without public tags and with a
+    // fuzzed timestamp
+    SetMaxPrivacy { enabled: bool },
+    // Only publish these kinds to a relay, as numbers or names (notes,
+    // app-data, messages, profile, contacts, relays, public, deletions);
+    // an empty list sends it every kind again
+    SetRelayPolicy { relay_url: String, kinds: Vec<String> },
+    // Trim trailing whitespace, extra blank lines and normalize Unicode in
+    // new notes
+    SetNormalizeNotes { enabled: bool },
+    // Save notes queued by app extensions through QuickCapture
+    DrainOutbox { outbox_path: String },
+    // Wallet (nostr+walletconnect://) that pays the pay_relays asking for
+    // payment, up to max_payment_sats per invoice and budget_sats in all;
+    // None removes it
+    ConfigureNwc {
+        uri: Option<String>,
+        max_payment_sats: u64,
+        budget_sats: u64,
+        pay_relays: Vec<String>,
+    },
+    // Save a #summary note digesting a tag's notes between two unix times
+    // (None: from the start / until now); arrives as NoteAdded
+    SummarizeTag { tag: String, since: Option<u64>, until: Option<u64> },
     SetTagFilter { tag: Option<String> },
+    // Multi-select: add and remove tags on many notes. Each changed note
+    // is replaced by a copy, arriving as NoteDeleted plus NoteAdded
+    BulkTag { ids: Vec<String>, add: Vec<String>, remove: Vec<String> },
+    // Off turns notifications for the tag off; read back with
+    // get_notification_rules()
+    SetNotifyRule { tag: String, notify: NotifyRule },
+    // Rules are applied in order, now and then hourly; an empty list
+    // turns retention off. Check with preview_retention() first
+    SetRetention { rules: Vec<RetentionRule> },
+    // Events each relay may hold for this key (for relays that cap them);
+    // counts what relays hold now. None removes the budget
+    SetEventBudget { budget: Option<u64> },
+    // Ask relays to delete notes replaced by edits and events deleted
+    // before that they still hold; answered with RemoteCompacted
+    CompactRemote,
+    // Report operations taking at least this long as SlowOperation; None
+    // stops reporting. Recent timings are in get_op_timings()
+    SetSlowThreshold { ms: Option<u64> },
+    // Restore relays, tag metadata and retention rules from a blob made by
+    // export_settings() with the same key
+    ImportSettings { blob: String },
+    // Shown for this device in get_devices() on the others, e.g. "Paul's iPad"
+    SetDeviceName { name: Option<String> },
+    // Hide (or show again) the notes a device wrote
+    SetDeviceIgnored { id: String, ignored: bool },
+    // Keep a snapshot for widgets at this path (in the app group
+    // container), rewritten as notes change; None stops it
+    SetWidgetSnapshotPath { path: Option<String> },
+    // Keep stored notes under this many bytes by evicting the oldest
+    // bodies; None keeps everything
+    SetLocalCacheLimit { bytes: Option<u64> },
+    // Fetch an evicted (or never downloaded) note from the relays;
+    // answered with NoteUpdated
+    HydrateNote { id: String },
+    // Keep a note's attachments on this device whatever the budget,
+    // downloading them now, or let them be evicted again
+    SetKeepOffline { id: String, keep: bool },
+    // Keep attachment files not kept offline under this many bytes,
+    // least recently used evicted first; None keeps everything
+    SetAttachmentCacheBudget { bytes: Option<u64> },
+    // Recovery: rebuild the search index and local caches from stored
+    // events, with from_relays fetching ours from the relays first.
+    // Answered with IndexRebuilt
+    RebuildIndex { from_relays: bool },
+    // Delete everything local and the keychain entry, and with
+    // delete_remote ask relays to delete every event of this key. `token`
+    // comes from request_account_deletion_token().
+    DeleteAccount { token: String, delete_remote: bool },
+    // QA: slow relays, drop publishes and fail decryption at random; None
+    // turns it off. Needs a build with the chaos feature.
+    SetChaos { config: Option<ChaosConfig> },
+    // Import a file, or the matching files in a directory, keeping the
+    // notes' dates; answered with ImportFinished
+    ImportNotes { path: String, format: ImportFormat, policy: DuplicatePolicy },
     LoadNotes { limit: u32 },
     SearchNotes { query: String },
 }
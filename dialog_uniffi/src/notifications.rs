@@ -0,0 +1,71 @@
+use crate::models::{NotificationRule, NotifyRule};
+use crate::{DIALOG, DialogClient, Event, rt};
+use dialog_lib::{Note as LibNote, NotifyRule as LibNotifyRule};
+
+impl From<LibNotifyRule> for NotifyRule {
+    fn from(rule: LibNotifyRule) -> Self {
+        match rule {
+            LibNotifyRule::Off => NotifyRule::Off,
+            LibNotifyRule::All => NotifyRule::All,
+            LibNotifyRule::Keyword(keyword) => NotifyRule::Keyword { keyword },
+        }
+    }
+}
+
+impl From<NotifyRule> for LibNotifyRule {
+    fn from(rule: NotifyRule) -> Self {
+        match rule {
+            NotifyRule::Off => LibNotifyRule::Off,
+            NotifyRule::All => LibNotifyRule::All,
+            NotifyRule::Keyword { keyword } => LibNotifyRule::Keyword(keyword),
+        }
+    }
+}
+
+// Per-tag notification rules. The watch loop checks new notes against
+// them and sends Event::NotificationWorthy for the ones that match.
+impl DialogClient {
+    /// Tags that notify, for the settings screen
+    pub fn get_notification_rules(&self) -> Vec<NotificationRule> {
+        match rt().block_on(DIALOG.get().unwrap().notify_rules()) {
+            Ok(rules) => rules
+                .into_iter()
+                .map(|(tag, notify)| NotificationRule {
+                    tag,
+                    notify: notify.into(),
+                })
+                .collect(),
+            Err(e) => {
+                eprintln!("[uniffi] notify_rules failed: {e}");
+                Vec::new()
+            }
+        }
+    }
+
+    pub(crate) async fn set_notify_rule(&self, tag: String, notify: NotifyRule) {
+        if let Err(e) = DIALOG
+            .get()
+            .unwrap()
+            .set_notify_rule(&tag, notify.into())
+            .await
+        {
+            eprintln!("[uniffi] set_notify_rule failed: {e}");
+            let message = format!("Failed to save notification setting: {e}");
+            self.emit(Event::failed(message, &e)).await;
+        }
+    }
+
+    /// The rule a newly arrived note matches, if any
+    pub(crate) async fn notification_rule(&self, note: &LibNote) -> Option<NotificationRule> {
+        match DIALOG.get().unwrap().notification_rule(note).await {
+            Ok(rule) => rule.map(|(tag, notify)| NotificationRule {
+                tag,
+                notify: notify.into(),
+            }),
+            Err(e) => {
+                eprintln!("[uniffi] notification_rule failed: {e}");
+                None
+            }
+        }
+    }
+}
@@ -0,0 +1,168 @@
+use crate::models::{OfflineEdit, OfflineEditKind};
+use crate::{DIALOG, DialogClient, Event, convert_lib_note_to_uniffi};
+use dialog_lib::{DialogError, QueuedWrite, QueuedWriteKind};
+use nostr_sdk::prelude::*;
+use std::sync::atomic::Ordering;
+
+// Writes made while offline are saved locally, shown right away and sent in
+// the order they were made once a relay is connected. The queue is kept by
+// dialog_lib on disk, so it is sent even after the app was killed. The app
+// shows a pending badge on notes listed in OfflineQueueChanged.
+impl DialogClient {
+    pub fn get_offline_queue(&self) -> Vec<OfflineEdit> {
+        match DIALOG.get().unwrap().offline_queue() {
+            Ok(queue) => queue.into_iter().map(offline_edit).collect(),
+            Err(e) => {
+                eprintln!("[uniffi] offline queue unreadable: {e}");
+                Vec::new()
+            }
+        }
+    }
+
+    /// Whether a write should be saved locally and queued: no relay yet, or
+    /// earlier writes still waiting, which it must not overtake
+    pub(crate) fn writes_offline(&self) -> bool {
+        !self.relay_connected.load(Ordering::SeqCst) || !self.get_offline_queue().is_empty()
+    }
+
+    /// Queue a write already made locally and send the queue if a relay is
+    /// connected
+    pub(crate) async fn publish_or_queue(&self, note_id: EventId, kind: OfflineEditKind) {
+        self.enqueue(note_id, kind, None).await;
+        self.replay_offline_queue().await;
+    }
+
+    /// Add a write to the queue; see Dialog::queue_write for how writes to
+    /// notes that haven't gone out yet are folded together
+    async fn enqueue(&self, note_id: EventId, kind: OfflineEditKind, replaces: Option<&EventId>) {
+        let queued = DIALOG
+            .get()
+            .unwrap()
+            .queue_write(note_id, kind.into(), replaces)
+            .await;
+        self.queue_changed(queued);
+    }
+
+    fn dequeue(&self, note_id: &EventId) {
+        self.queue_changed(DIALOG.get().unwrap().dequeue_write(note_id));
+    }
+
+    pub(crate) fn queue_changed(&self, queue: dialog_lib::Result<Vec<QueuedWrite>>) {
+        match queue {
+            Ok(queue) => {
+                let pending = queue.into_iter().map(offline_edit).collect();
+                let _ = self.event_tx.send(Event::OfflineQueueChanged { pending });
+            }
+            Err(e) => eprintln!("[uniffi] offline queue not saved: {e}"),
+        }
+    }
+
+    /// Send queued writes oldest first, stopping at the first that fails so
+    /// later ones never overtake it
+    pub(crate) async fn replay_offline_queue(&self) {
+        if !self.relay_connected.load(Ordering::SeqCst) {
+            return;
+        }
+        // A replay already running picks up whatever was queued since
+        let Ok(_replaying) = self.offline_replay.try_lock() else {
+            return;
+        };
+        let dialog = DIALOG.get().unwrap();
+        loop {
+            let Some(write) = dialog
+                .offline_queue()
+                .ok()
+                .and_then(|queue| queue.first().cloned())
+            else {
+                break;
+            };
+            let note_id = write.note_id;
+            match dialog.send_queued_write(&write).await {
+                Ok(()) => {
+                    eprintln!("[uniffi] sent queued {:?} {note_id}", write.kind);
+                    self.dequeue(&note_id);
+                    if write.kind != QueuedWriteKind::Delete {
+                        self.update_note(&note_id.to_hex(), |note| note.is_synced = true)
+                            .await;
+                    }
+                }
+                Err(DialogError::NoteNotFound(_)) => {
+                    eprintln!("[uniffi] queued note {note_id} is gone, dropping it");
+                    self.dequeue(&note_id);
+                }
+                Err(e) => {
+                    eprintln!("[uniffi] sending queued {note_id} failed, keeping the queue: {e}");
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Replace a note's text. The copy takes the note's place in the list,
+    /// arriving as NoteDeleted plus NoteAdded; offline it is queued.
+    pub(crate) async fn edit_note(&self, id: String, text: String) {
+        let Ok(original) = EventId::from_hex(&id) else {
+            eprintln!("[uniffi] edit_note: invalid id {id}");
+            return;
+        };
+        let dialog = DIALOG.get().unwrap();
+        let offline = self.writes_offline();
+        let stage = |batch: &mut dialog_lib::Batch| batch.edit(original, text);
+        let result = if offline {
+            dialog.with_batch_offline(stage).await
+        } else {
+            dialog.with_batch(stage).await
+        };
+        let report = match result {
+            Ok(report) => report,
+            Err(e) => {
+                eprintln!("[uniffi] edit_note failed: {e}");
+                let message = format!("Couldn't save your edit: {e}");
+                self.emit(Event::failed(message, &e).about(&id)).await;
+                return;
+            }
+        };
+        let Some(copy) = report.edited.into_iter().next() else {
+            return;
+        };
+        let copy_id = copy.id;
+        let unpublished = report.unpublished.iter().any(|(id, _)| *id == copy_id);
+
+        if self.notes.write().await.remove(&id).is_some() {
+            self.emit(Event::NoteDeleted { id }).await;
+        }
+        let note = convert_lib_note_to_uniffi(copy);
+        self.notes
+            .write()
+            .await
+            .insert(note.id.clone(), note.clone());
+        self.emit(Event::NoteAdded { note }).await;
+
+        if unpublished {
+            self.enqueue(copy_id, OfflineEditKind::Edit, Some(&original))
+                .await;
+        }
+    }
+}
+
+impl From<OfflineEditKind> for QueuedWriteKind {
+    fn from(kind: OfflineEditKind) -> Self {
+        match kind {
+            OfflineEditKind::Create => QueuedWriteKind::Create,
+            OfflineEditKind::Edit => QueuedWriteKind::Edit,
+            OfflineEditKind::Delete => QueuedWriteKind::Delete,
+        }
+    }
+}
+
+pub(crate) fn offline_edit(write: QueuedWrite) -> OfflineEdit {
+    OfflineEdit {
+        note_id: write.note_id.to_hex(),
+        kind: match write.kind {
+            QueuedWriteKind::Create => OfflineEditKind::Create,
+            QueuedWriteKind::Edit => OfflineEditKind::Edit,
+            QueuedWriteKind::Delete => OfflineEditKind::Delete,
+        },
+        queued_at: write.queued_at.as_u64() as i64,
+    }
+}
@@ -0,0 +1,52 @@
+use crate::models::OpTiming;
+use crate::{DIALOG, DialogClient, Event, rt};
+use dialog_lib::OpTiming as LibTiming;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+
+impl From<LibTiming> for OpTiming {
+    fn from(timing: LibTiming) -> Self {
+        OpTiming {
+            name: timing.name.to_string(),
+            ms: timing.duration.as_millis() as u64,
+            at: timing.at.as_u64(),
+        }
+    }
+}
+
+// Timings of listing, searching, syncing and decrypting. Operations over
+// the slow threshold arrive as SlowOperation, for the app's diagnostics.
+impl DialogClient {
+    pub(crate) fn forward_slow_operations(&self) {
+        let mut slow = DIALOG.get().unwrap().watch_slow_operations();
+        let event_tx = self.event_tx.clone();
+        rt().spawn(async move {
+            loop {
+                match slow.recv().await {
+                    Ok(timing) => {
+                        let _ = event_tx.send(Event::SlowOperation {
+                            name: timing.name.to_string(),
+                            ms: timing.duration.as_millis() as u64,
+                        });
+                    }
+                    Err(RecvError::Lagged(skipped)) => {
+                        eprintln!("[uniffi] Dropped {skipped} slow operation reports");
+                    }
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        });
+    }
+
+    pub fn get_op_timings(&self) -> Vec<OpTiming> {
+        let timings = DIALOG.get().unwrap().last_op_timings();
+        timings.into_iter().map(Into::into).collect()
+    }
+
+    pub(crate) fn set_slow_threshold(&self, ms: Option<u64>) {
+        DIALOG
+            .get()
+            .unwrap()
+            .set_slow_threshold(ms.map(Duration::from_millis));
+    }
+}
@@ -0,0 +1,51 @@
+use crate::models::{CaptureSource, Provenance};
+use crate::{DIALOG, DialogClient, rt};
+use dialog_lib::{CaptureSource as LibSource, Provenance as LibProvenance};
+
+impl From<LibSource> for CaptureSource {
+    fn from(source: LibSource) -> Self {
+        match source {
+            LibSource::App => CaptureSource::App,
+            LibSource::Cli => CaptureSource::Cli,
+            LibSource::ShareExtension => CaptureSource::ShareExtension,
+            LibSource::Import => CaptureSource::Import,
+        }
+    }
+}
+
+impl From<LibProvenance> for Provenance {
+    fn from(provenance: LibProvenance) -> Self {
+        Provenance {
+            device_id: provenance.device_id,
+            device_name: provenance.device_name,
+            app_version: provenance.app_version,
+            source: provenance.source.map(Into::into),
+        }
+    }
+}
+
+/// Notes written through this library come from the app until it says
+/// which version it is
+pub(crate) fn record_app_provenance() {
+    let dialog = DIALOG.get().unwrap();
+    dialog.set_capture_source(Some(LibSource::App));
+    dialog.set_app_version(Some(format!("dialog_uniffi {}", env!("CARGO_PKG_VERSION"))));
+}
+
+impl DialogClient {
+    pub fn set_app_version(&self, version: String) {
+        DIALOG.get().unwrap().set_app_version(Some(version));
+    }
+
+    pub fn get_note_provenance(&self, id: String) -> Option<Provenance> {
+        let note_id = dialog_lib::parse_note_id(&id).ok()?;
+        let result = rt().block_on(DIALOG.get().unwrap().note_provenance(&note_id));
+        match result {
+            Ok(provenance) => provenance.map(Into::into),
+            Err(e) => {
+                eprintln!("[uniffi] get_note_provenance {id} failed: {e}");
+                None
+            }
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use crate::models::{Command, PendingCommand};
+use crate::{DialogClient, Event};
+use chrono::Utc;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+
+/// Commands spawned by send_command that haven't finished
+#[derive(Default)]
+pub(crate) struct CommandQueue {
+    next_id: u64,
+    pending: Vec<PendingCommand>,
+}
+
+/// Held by a running command; takes it off the queue when dropped, so
+/// panics and early returns are covered too
+pub(crate) struct InFlight {
+    id: u64,
+    queue: Arc<Mutex<CommandQueue>>,
+    event_tx: broadcast::Sender<Event>,
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        let pending = {
+            let mut queue = self.queue.lock().unwrap();
+            queue.pending.retain(|command| command.id != self.id);
+            queue.pending.clone()
+        };
+        let _ = self.event_tx.send(Event::QueueChanged { pending });
+    }
+}
+
+impl Command {
+    /// The variant alone, safe to log: fields can hold passphrases, wallet
+    /// secrets and settings exports
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            Command::ConnectRelay { .. } => "ConnectRelay",
+            Command::CreateNote { .. } => "CreateNote",
+            Command::CreateNoteAnyway { .. } => "CreateNoteAnyway",
+            Command::CreateNoteStructured { .. } => "CreateNoteStructured",
+            Command::CreateVoiceNote { .. } => "CreateVoiceNote",
+            Command::EditNote { .. } => "EditNote",
+            Command::SetTranscript { .. } => "SetTranscript",
+            Command::DeleteNote { .. } => "DeleteNote",
+            Command::Undo => "Undo",
+            Command::Redo => "Redo",
+            Command::MarkAsRead { .. } => "MarkAsRead",
+            Command::RecordView { .. } => "RecordView",
+            Command::LockNote { .. } => "LockNote",
+            Command::UnlockNote { .. } => "UnlockNote",
+            Command::RelockNotes => "RelockNotes",
+            Command::SetPinned { .. } => "SetPinned",
+            Command::SetArchived { .. } => "SetArchived",
+            Command::SetTaskStatus { .. } => "SetTaskStatus",
+            Command::PublishPublic { .. } => "PublishPublic",
+            Command::SetAppStateSync { .. } => "SetAppStateSync",
+            Command::CancelSync => "CancelSync",
+            Command::SetInboxEnabled { .. } => "SetInboxEnabled",
+            Command::LoadMessages { .. } => "LoadMessages",
+            Command::LoadContacts => "LoadContacts",
+            Command::PublishProfile { .. } => "PublishProfile",
+            Command::ShareNote { .. } => "ShareNote",
+            Command::ResendShare { .. } => "ResendShare",
+            Command::EnrichNote { .. } => "EnrichNote",
+            Command::SetAutoEnrich { .. } => "SetAutoEnrich",
+            Command::SetMaxPrivacy { .. } => "SetMaxPrivacy",
+            Command::SetRelayPolicy { .. } => "SetRelayPolicy",
+            Command::SetNormalizeNotes { .. } => "SetNormalizeNotes",
+            Command::DrainOutbox { .. } => "DrainOutbox",
+            Command::ConfigureNwc { .. } => "ConfigureNwc",
+            Command::SummarizeTag { .. } => "SummarizeTag",
+            Command::SetTagFilter { .. } => "SetTagFilter",
+            Command::BulkTag { .. } => "BulkTag",
+            Command::SetNotifyRule { .. } => "SetNotifyRule",
+            Command::SetRetention { .. } => "SetRetention",
+            Command::SetEventBudget { .. } => "SetEventBudget",
+            Command::CompactRemote => "CompactRemote",
+            Command::SetSlowThreshold { .. } => "SetSlowThreshold",
+            Command::ImportSettings { .. } => "ImportSettings",
+            Command::SetDeviceName { .. } => "SetDeviceName",
+            Command::SetDeviceIgnored { .. } => "SetDeviceIgnored",
+            Command::SetWidgetSnapshotPath { .. } => "SetWidgetSnapshotPath",
+            Command::SetLocalCacheLimit { .. } => "SetLocalCacheLimit",
+            Command::HydrateNote { .. } => "HydrateNote",
+            Command::SetKeepOffline { .. } => "SetKeepOffline",
+            Command::SetAttachmentCacheBudget { .. } => "SetAttachmentCacheBudget",
+            Command::RebuildIndex { .. } => "RebuildIndex",
+            Command::DeleteAccount { .. } => "DeleteAccount",
+            Command::SetChaos { .. } => "SetChaos",
+            Command::ImportNotes { .. } => "ImportNotes",
+            Command::LoadNotes { .. } => "LoadNotes",
+            Command::SearchNotes { .. } => "SearchNotes",
+        }
+    }
+
+    /// Whether the command publishes notes or app state, so killing the
+    /// app mid-way could lose the change on other devices
+    fn writes(&self) -> bool {
+        matches!(
+            self,
+            Command::CreateNote { .. }
+                | Command::CreateNoteAnyway { .. }
+                | Command::CreateNoteStructured { .. }
+                | Command::CreateVoiceNote { .. }
+                | Command::EditNote { .. }
+                | Command::SetTranscript { .. }
+                | Command::DeleteNote { .. }
+                | Command::Undo
+                | Command::Redo
+                | Command::MarkAsRead { .. }
+                | Command::RecordView { .. }
+                | Command::LockNote { .. }
+                | Command::SetPinned { .. }
+                | Command::SetArchived { .. }
+                | Command::SetTaskStatus { .. }
+                | Command::PublishPublic { .. }
+                | Command::PublishProfile { .. }
+                | Command::ShareNote { .. }
+                | Command::ResendShare { .. }
+                | Command::DrainOutbox { .. }
+                | Command::SummarizeTag { .. }
+                | Command::BulkTag { .. }
+                | Command::SetNotifyRule { .. }
+                | Command::CompactRemote
+                | Command::ImportSettings { .. }
+                | Command::SetDeviceIgnored { .. }
+                | Command::DeleteAccount { .. }
+                | Command::ImportNotes { .. }
+        )
+    }
+}
+
+// "Still saving…": the app shows a spinner while anything is pending and
+// warns before quitting while a write is.
+impl DialogClient {
+    pub fn get_pending_commands(&self) -> Vec<PendingCommand> {
+        self.command_queue.lock().unwrap().pending.clone()
+    }
+
+    /// Put `cmd` on the queue until the returned guard is dropped
+    pub(crate) fn track_command(&self, cmd: &Command) -> InFlight {
+        let (id, pending) = {
+            let mut queue = self.command_queue.lock().unwrap();
+            queue.next_id += 1;
+            let id = queue.next_id;
+            queue.pending.push(PendingCommand {
+                id,
+                name: cmd.name().to_string(),
+                started_at: Utc::now().timestamp(),
+                writes: cmd.writes(),
+            });
+            (id, queue.pending.clone())
+        };
+        let _ = self.event_tx.send(Event::QueueChanged { pending });
+        InFlight {
+            id,
+            queue: self.command_queue.clone(),
+            event_tx: self.event_tx.clone(),
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use crate::{DIALOG, DialogClient, Event, convert_lib_note_to_uniffi};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Note capture for app extensions (e.g. the share sheet).
+///
+/// Extensions run in their own process and must not open the nostrdb
+/// database while the app may have it open, so captures are appended to an
+/// outbox file in the shared app group container instead. The app turns
+/// them into notes with `Command::DrainOutbox`.
+pub struct QuickCapture {
+    outbox_path: PathBuf,
+}
+
+impl QuickCapture {
+    pub fn new(outbox_path: String) -> Self {
+        Self {
+            outbox_path: PathBuf::from(outbox_path),
+        }
+    }
+
+    /// Queue a note; false if it was empty, too large or couldn't be written
+    pub fn capture(&self, text: String) -> bool {
+        match dialog_lib::append_to_outbox(&self.outbox_path, &text) {
+            Ok(_) => true,
+            Err(e) => {
+                eprintln!("[uniffi] QuickCapture::capture failed: {e}");
+                false
+            }
+        }
+    }
+
+    /// Notes captured but not drained by the app yet
+    pub fn pending_count(&self) -> u32 {
+        dialog_lib::outbox::read_outbox(&self.outbox_path)
+            .map(|entries| entries.len() as u32)
+            .unwrap_or(0)
+    }
+}
+
+impl DialogClient {
+    /// Turn notes queued by extensions into real notes
+    pub(crate) async fn drain_outbox(self: Arc<Self>, outbox_path: String) {
+        let lib_notes = match DIALOG
+            .get()
+            .unwrap()
+            .drain_outbox(&PathBuf::from(outbox_path))
+            .await
+        {
+            Ok(lib_notes) => lib_notes,
+            Err(e) => {
+                eprintln!("[uniffi] drain_outbox failed: {e}");
+                let message = format!("Failed to save shared notes: {e}");
+                self.emit(Event::failed(message, &e)).await;
+                return;
+            }
+        };
+        for lib_note in lib_notes {
+            let note = convert_lib_note_to_uniffi(lib_note);
+            self.notes
+                .write()
+                .await
+                .insert(note.id.clone(), note.clone());
+            self.emit(Event::NoteAdded { note }).await;
+        }
+    }
+}
@@ -0,0 +1,44 @@
+use crate::models::RelayPolicy;
+use crate::{DIALOG, DialogClient, Event};
+use std::sync::Arc;
+
+// What gets published where, e.g. notes only to a private relay and the
+// profile to public ones.
+impl DialogClient {
+    pub fn get_relay_policies(&self) -> Vec<RelayPolicy> {
+        match DIALOG.get().unwrap().relay_policies() {
+            Ok(policies) => policies
+                .into_iter()
+                .map(|(relay_url, policy)| RelayPolicy {
+                    relay_url,
+                    kinds: policy.kinds.into_iter().collect(),
+                })
+                .collect(),
+            Err(e) => {
+                eprintln!("[uniffi] relay_policies failed: {e}");
+                Vec::new()
+            }
+        }
+    }
+
+    pub(crate) async fn set_relay_policy(self: Arc<Self>, relay_url: String, kinds: Vec<String>) {
+        let dialog = DIALOG.get().unwrap();
+        let result = if kinds.is_empty() {
+            dialog.set_relay_policy(&relay_url, None)
+        } else {
+            kinds
+                .iter()
+                .map(|name| dialog.kinds_named(name))
+                .collect::<dialog_lib::Result<Vec<_>>>()
+                .and_then(|kinds| {
+                    let policy = dialog_lib::RelayPolicy::new(kinds.into_iter().flatten());
+                    dialog.set_relay_policy(&relay_url, Some(policy))
+                })
+        };
+        if let Err(e) = result {
+            eprintln!("[uniffi] set_relay_policy {relay_url} failed: {e}");
+            let message = format!("Couldn't set the policy for {relay_url}: {e}");
+            self.emit(Event::failed(message, &e)).await;
+        }
+    }
+}
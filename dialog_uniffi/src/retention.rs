@@ -0,0 +1,125 @@
+use crate::models::{RetentionAction, RetentionReport, RetentionRule};
+use crate::{DIALOG, DialogClient, Event, rt};
+use dialog_lib::{
+    RetentionAction as LibAction, RetentionPolicy, RetentionReport as LibReport,
+    RetentionRule as LibRule,
+};
+use std::sync::Arc;
+
+impl From<LibRule> for RetentionRule {
+    fn from(rule: LibRule) -> Self {
+        RetentionRule {
+            tag: rule.tag,
+            older_than_days: rule.older_than_days,
+            action: match rule.action {
+                LibAction::Archive => RetentionAction::Archive,
+                LibAction::Purge => RetentionAction::Purge,
+            },
+            keep_pinned: rule.keep_pinned,
+        }
+    }
+}
+
+impl From<RetentionRule> for LibRule {
+    fn from(rule: RetentionRule) -> Self {
+        LibRule {
+            tag: rule.tag,
+            older_than_days: rule.older_than_days,
+            action: match rule.action {
+                RetentionAction::Archive => LibAction::Archive,
+                RetentionAction::Purge => LibAction::Purge,
+            },
+            keep_pinned: rule.keep_pinned,
+        }
+    }
+}
+
+impl From<LibReport> for RetentionReport {
+    fn from(report: LibReport) -> Self {
+        RetentionReport {
+            dry_run: report.dry_run,
+            archived: report.archived.iter().map(|id| id.to_hex()).collect(),
+            purged: report.purged.iter().map(|id| id.to_hex()).collect(),
+            failed: report.failed.iter().map(|(id, _)| id.to_hex()).collect(),
+        }
+    }
+}
+
+// Retention settings, kept with the profile. While rules are set, a pass runs
+// right away and then hourly; archived notes arrive as NoteUpdated, purged
+// ones as NoteDeleted.
+impl DialogClient {
+    pub fn get_retention_rules(&self) -> Vec<RetentionRule> {
+        let policy = DIALOG.get().unwrap().retention();
+        policy.rules.into_iter().map(Into::into).collect()
+    }
+
+    pub fn preview_retention(&self) -> RetentionReport {
+        match rt().block_on(DIALOG.get().unwrap().enforce_retention(true)) {
+            Ok(report) => report.into(),
+            Err(e) => {
+                eprintln!("[uniffi] preview_retention failed: {e}");
+                RetentionReport {
+                    dry_run: true,
+                    ..Default::default()
+                }
+            }
+        }
+    }
+
+    pub(crate) async fn set_retention(self: Arc<Self>, rules: Vec<RetentionRule>) {
+        let policy = RetentionPolicy {
+            rules: rules.into_iter().map(Into::into).collect(),
+        };
+        if let Err(e) = DIALOG.get().unwrap().set_retention(policy) {
+            eprintln!("[uniffi] set_retention failed: {e}");
+            let message = format!("Couldn't save retention rules: {e}");
+            self.emit(Event::failed(message, &e)).await;
+            return;
+        }
+        self.restart_retention();
+    }
+
+    /// Start passes for the kept policy, replacing any running ones;
+    /// nothing runs while it has no rules
+    pub(crate) fn restart_retention(self: Arc<Self>) {
+        let mut slot = self.retention_handle.lock().unwrap();
+        if let Some(handle) = slot.take() {
+            handle.abort();
+        }
+        let dialog = DIALOG.get().unwrap();
+        if dialog.retention().rules.is_empty() {
+            return;
+        }
+        // Called from Swift threads too, outside the runtime
+        let _runtime = rt().enter();
+        let mut retention = dialog.clone().start_retention();
+        let this = self.clone();
+        *slot = Some(rt().spawn(async move {
+            while let Some(result) = retention.next_report().await {
+                this.apply_retention_report(result).await;
+            }
+        }));
+    }
+
+    async fn apply_retention_report(&self, result: dialog_lib::Result<LibReport>) {
+        let report = match result {
+            Ok(report) => report,
+            Err(e) => {
+                eprintln!("[uniffi] retention pass failed: {e}");
+                let message = format!("Retention cleanup failed: {e}");
+                self.emit(Event::failed(message, &e)).await;
+                return;
+            }
+        };
+        for id in &report.archived {
+            self.update_note(&id.to_hex(), |note| note.is_archived = true)
+                .await;
+        }
+        for id in report.purged.iter().map(|id| id.to_hex()) {
+            if self.notes.write().await.remove(&id).is_some() {
+                self.emit(Event::NoteDeleted { id }).await;
+            }
+        }
+    }
+}
@@ -0,0 +1,28 @@
+use crate::{DIALOG, DialogClient, Note, convert_lib_note_to_uniffi, rt};
+use nostr_sdk::prelude::*;
+
+// "Review your notes": the app sends RecordView when a note is opened and
+// shows get_review_queue() as a deck of long-unopened notes.
+impl DialogClient {
+    pub fn get_review_queue(&self, limit: u32) -> Vec<Note> {
+        match rt().block_on(DIALOG.get().unwrap().review_queue(limit as usize)) {
+            Ok(lib_notes) => lib_notes
+                .into_iter()
+                .map(|note| convert_lib_note_to_uniffi(note).for_list())
+                .collect(),
+            Err(e) => {
+                eprintln!("[uniffi] review_queue failed: {e}");
+                Vec::new()
+            }
+        }
+    }
+
+    pub(crate) async fn record_view(&self, id: String) {
+        let Ok(event_id) = EventId::from_hex(&id) else {
+            return;
+        };
+        if let Err(e) = DIALOG.get().unwrap().record_view(&event_id).await {
+            eprintln!("[uniffi] record_view {id} failed: {e}");
+        }
+    }
+}
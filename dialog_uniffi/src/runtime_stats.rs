@@ -0,0 +1,55 @@
+use crate::models::{Note, RuntimeStats};
+use crate::{DIALOG, DialogClient, rt};
+use std::mem::size_of;
+use std::sync::atomic::Ordering;
+
+/// Heap the note holds besides its own struct; attachments count as their
+/// struct only
+fn note_bytes(note: &Note) -> usize {
+    let tags: usize = note
+        .tags
+        .iter()
+        .map(|tag| size_of::<String>() + tag.len())
+        .sum();
+    let optional = [&note.public_id, &note.transcript, &note.edit_of];
+    let optional: usize = optional
+        .iter()
+        .filter_map(|s| s.as_ref())
+        .map(String::len)
+        .sum();
+    size_of::<Note>() + note.id.len() + note.text.len() + note.preview.len() + tags + optional
+}
+
+// Numbers to log periodically (e.g. with MetricKit) so caches or tasks
+// that only ever grow show up in production
+impl DialogClient {
+    pub fn get_runtime_stats(&self) -> RuntimeStats {
+        let metrics = rt().metrics();
+        let (lib, notes_cached, notes_cached_bytes, profiles_cached) = rt().block_on(async {
+            let notes = self.notes.read().await;
+            let bytes: usize = notes.values().map(note_bytes).sum();
+            (
+                DIALOG.get().unwrap().runtime_stats().await,
+                notes.len(),
+                bytes,
+                self.profiles.read().await.len(),
+            )
+        });
+        RuntimeStats {
+            notes_cached: notes_cached as u32,
+            notes_cached_bytes: notes_cached_bytes as u64,
+            profiles_cached: profiles_cached as u32,
+            subscriptions: lib.subscriptions as u32,
+            watches: lib.watches as u32,
+            seen_ids: lib.seen_ids as u64,
+            unlocked_notes: lib.unlocked_notes as u32,
+            relay_info_cached: lib.relay_info_cached as u32,
+            tokio_workers: metrics.num_workers() as u32,
+            tokio_alive_tasks: metrics.num_alive_tasks() as u64,
+            tokio_queue_depth: metrics.global_queue_depth() as u64,
+            event_listeners: self.event_tx.receiver_count() as u32,
+            events_queued: self.event_tx.len() as u64,
+            events_lagged: self.events_lagged.load(Ordering::Relaxed),
+        }
+    }
+}
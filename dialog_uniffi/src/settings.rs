@@ -0,0 +1,44 @@
+use crate::{DIALOG, DialogClient, Event, rt};
+use std::sync::Arc;
+
+// Moving to a new device: export_settings() on the old one, then
+// Command::ImportSettings with the blob on the new one (same nsec).
+impl DialogClient {
+    pub fn export_settings(&self) -> Option<String> {
+        match rt().block_on(DIALOG.get().unwrap().export_settings()) {
+            Ok(blob) => Some(blob),
+            Err(e) => {
+                eprintln!("[uniffi] export_settings failed: {e}");
+                None
+            }
+        }
+    }
+
+    pub fn get_max_privacy(&self) -> bool {
+        DIALOG.get().unwrap().max_privacy()
+    }
+
+    pub(crate) async fn set_max_privacy(&self, enabled: bool) {
+        if let Err(e) = DIALOG.get().unwrap().set_max_privacy(enabled) {
+            eprintln!("[uniffi] set_max_privacy failed: {e}");
+            let message = format!("Couldn't change max privacy: {e}");
+            self.emit(Event::failed(message, &e)).await;
+        }
+    }
+
+    pub(crate) async fn import_settings(self: Arc<Self>, blob: String) {
+        let settings = match DIALOG.get().unwrap().import_settings(&blob).await {
+            Ok(settings) => settings,
+            Err(e) => {
+                eprintln!("[uniffi] import_settings failed: {e}");
+                let message = format!("Couldn't import settings: {e}");
+                self.emit(Event::failed(message, &e)).await;
+                return;
+            }
+        };
+        if !settings.retention.is_empty() {
+            // Restart the retention task with the imported rules
+            self.restart_retention();
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use crate::models::{ErrorCode, GroupShare, ShareRecipient, ShareStatus};
+use crate::{DIALOG, DialogClient, Event, rt};
+use dialog_lib::GroupShare as LibShare;
+use nostr_sdk::prelude::*;
+use std::sync::Arc;
+
+// Sharing a note with a group: one private message per recipient, with
+// each recipient's delivery shown on the note's detail view.
+impl DialogClient {
+    pub fn get_note_shares(&self, id: String) -> Vec<GroupShare> {
+        let Ok(note_id) = EventId::from_hex(&id) else {
+            return Vec::new();
+        };
+        rt().block_on(async {
+            match DIALOG.get().unwrap().group_shares(&note_id).await {
+                Ok(shares) => {
+                    let mut converted = Vec::with_capacity(shares.len());
+                    for share in shares {
+                        converted.push(self.convert_share(share).await);
+                    }
+                    converted
+                }
+                Err(e) => {
+                    eprintln!("[uniffi] group_shares {id} failed: {e}");
+                    Vec::new()
+                }
+            }
+        })
+    }
+
+    pub(crate) async fn share_note(self: Arc<Self>, id: String, recipients: Vec<String>) {
+        let Ok(note_id) = EventId::from_hex(&id) else {
+            return;
+        };
+        let mut pubkeys = Vec::with_capacity(recipients.len());
+        for recipient in recipients {
+            match PublicKey::parse(&recipient) {
+                Ok(pubkey) => pubkeys.push(pubkey),
+                Err(e) => {
+                    let message = format!("Can't share with {recipient}: {e}");
+                    self.emit(Event::error(ErrorCode::InvalidInput, message).about(&id))
+                        .await;
+                    return;
+                }
+            }
+        }
+        let result = DIALOG
+            .get()
+            .unwrap()
+            .share_note_with(&note_id, pubkeys)
+            .await;
+        self.finish_share(id, result).await;
+    }
+
+    pub(crate) async fn resend_share(self: Arc<Self>, id: String, shared_at: i64) {
+        let Ok(note_id) = EventId::from_hex(&id) else {
+            return;
+        };
+        let shared_at = Timestamp::from(shared_at.max(0) as u64);
+        let result = DIALOG
+            .get()
+            .unwrap()
+            .resend_group_share(&note_id, shared_at)
+            .await;
+        self.finish_share(id, result).await;
+    }
+
+    async fn finish_share(&self, id: String, result: dialog_lib::Result<LibShare>) {
+        match result {
+            Ok(share) => {
+                self.load_profiles(share.recipients.iter().map(|r| r.pubkey))
+                    .await;
+                let share = self.convert_share(share).await;
+                self.emit(Event::NoteShared { share }).await;
+            }
+            Err(e) => {
+                eprintln!("[uniffi] share {id} failed: {e}");
+                let message = format!("Couldn't share the note: {e}");
+                self.emit(Event::failed(message, &e).about(&id)).await;
+            }
+        }
+    }
+
+    /// Names come from profiles already cached; nothing is fetched here
+    async fn convert_share(&self, share: LibShare) -> GroupShare {
+        let profiles = self.profiles.read().await;
+        let recipients = share
+            .recipients
+            .into_iter()
+            .map(|recipient| {
+                let profile = profiles.get(&recipient.pubkey.to_hex());
+                let status = if recipient.is_delivered() {
+                    ShareStatus::Delivered {
+                        relays: recipient.acked_by,
+                    }
+                } else if let Some(reason) = recipient.error {
+                    ShareStatus::Failed { reason }
+                } else {
+                    ShareStatus::Pending
+                };
+                ShareRecipient {
+                    pubkey: recipient
+                        .pubkey
+                        .to_bech32()
+                        .unwrap_or_else(|_| recipient.pubkey.to_hex()),
+                    name: profile.and_then(|p| p.display_name.clone().or_else(|| p.name.clone())),
+                    status,
+                }
+            })
+            .collect();
+        GroupShare {
+            note_id: share.note_id.to_hex(),
+            shared_at: share.shared_at.as_u64() as i64,
+            recipients,
+        }
+    }
+}
@@ -0,0 +1,40 @@
+use crate::models::{SearchIndexChanges, SearchIndexEntry};
+use crate::{DIALOG, DialogClient, rt};
+use nostr_sdk::prelude::*;
+
+// Feed for Core Spotlight: titles and tags only, as a change journal the
+// app polls with the cursor from its last call.
+impl DialogClient {
+    pub fn get_search_index_entries(&self, since: Option<i64>) -> SearchIndexChanges {
+        let since_ts = since.map(|since| Timestamp::from(since.max(0) as u64));
+        match rt().block_on(DIALOG.get().unwrap().search_index_changes(since_ts)) {
+            Ok(changes) => SearchIndexChanges {
+                entries: changes
+                    .entries
+                    .into_iter()
+                    .map(|entry| SearchIndexEntry {
+                        id: entry.id.to_hex(),
+                        title: entry.title,
+                        tags: entry.tags,
+                        created_at: entry.created_at.as_u64() as i64,
+                        modified_at: entry.modified_at.as_u64() as i64,
+                    })
+                    .collect(),
+                removed_ids: changes.removed.iter().map(|id| id.to_hex()).collect(),
+                reset: changes.reset,
+                cursor: changes.cursor.as_u64() as i64,
+            },
+            Err(e) => {
+                eprintln!("[uniffi] search_index_changes failed: {e}");
+                // Nothing changed as far as the app can tell; retry from the
+                // same cursor next time
+                SearchIndexChanges {
+                    entries: Vec::new(),
+                    removed_ids: Vec::new(),
+                    reset: false,
+                    cursor: since.unwrap_or(0),
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,58 @@
+use crate::models::{DialogStatus, LastSync, RelayHealth};
+use crate::{DIALOG, DialogClient, rt};
+
+// One snapshot for the app's status screen
+impl DialogClient {
+    pub fn get_status(&self) -> DialogStatus {
+        let status = match rt().block_on(DIALOG.get().unwrap().status()) {
+            Ok(status) => status,
+            Err(e) => {
+                eprintln!("[uniffi] get_status failed: {e}");
+                return DialogStatus {
+                    relays: Vec::new(),
+                    pending_outbox: 0,
+                    syncing: false,
+                    last_sync: None,
+                    db_size_bytes: None,
+                    watches: Vec::new(),
+                    event_counts: Vec::new(),
+                    error: Some(e.to_string()),
+                };
+            }
+        };
+        DialogStatus {
+            relays: status
+                .relays
+                .into_iter()
+                .map(|relay| RelayHealth {
+                    url: relay.url.to_string(),
+                    connected: relay.connected,
+                    status: relay.status,
+                })
+                .collect(),
+            pending_outbox: status.pending_outbox as u32,
+            syncing: status.syncing,
+            last_sync: status.last_sync.map(|last| {
+                let finished_at = last.finished_at.as_u64() as i64;
+                match last.result {
+                    Ok(report) => LastSync {
+                        finished_at,
+                        received: report.received as u32,
+                        sent: report.sent as u32,
+                        error: None,
+                    },
+                    Err(e) => LastSync {
+                        finished_at,
+                        received: 0,
+                        sent: 0,
+                        error: Some(e),
+                    },
+                }
+            }),
+            db_size_bytes: status.db_size_bytes,
+            watches: status.watches,
+            event_counts: status.event_counts.into_iter().map(Into::into).collect(),
+            error: None,
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use crate::{DIALOG, DialogClient, ErrorCode, Event, convert_lib_note_to_uniffi};
+use dialog_lib::{DialogError, Summarizer, SummaryFuture};
+use nostr_sdk::prelude::*;
+use std::sync::Arc;
+
+/// Summarizer implemented by the app (e.g. with an on-device LLM). Called
+/// off the main thread; may block. Returning "" means it failed.
+pub trait NoteSummarizer: Send + Sync {
+    fn summarize(&self, text: String) -> String;
+}
+
+struct ForeignSummarizer(Arc<dyn NoteSummarizer>);
+
+impl Summarizer for ForeignSummarizer {
+    fn summarize<'a>(&'a self, text: &'a str) -> SummaryFuture<'a> {
+        let summarizer = self.0.clone();
+        let text = text.to_string();
+        Box::pin(async move {
+            tokio::task::spawn_blocking(move || summarizer.summarize(text))
+                .await
+                .map_err(|e| DialogError::Summarize(e.to_string()))
+        })
+    }
+}
+
+impl DialogClient {
+    /// Replace the built-in summarizer, which just collects the notes
+    pub fn set_summarizer(&self, summarizer: Box<dyn NoteSummarizer>) {
+        eprintln!("[uniffi] set_summarizer");
+        DIALOG
+            .get()
+            .unwrap()
+            .set_summarizer(Arc::new(ForeignSummarizer(Arc::from(summarizer))));
+    }
+
+    pub(crate) async fn summarize_tag(&self, tag: String, since: Option<u64>, until: Option<u64>) {
+        let since = Timestamp::from(since.unwrap_or(0));
+        let until = until.map_or(Timestamp::now(), Timestamp::from);
+        match DIALOG
+            .get()
+            .unwrap()
+            .summarize_tag(&tag, since..=until)
+            .await
+        {
+            Ok(Some(lib_note)) => {
+                let note = convert_lib_note_to_uniffi(lib_note);
+                self.notes
+                    .write()
+                    .await
+                    .insert(note.id.clone(), note.clone());
+                self.emit(Event::NoteAdded { note }).await;
+            }
+            Ok(None) => {
+                let message = format!("No #{tag} notes to summarize");
+                self.emit(Event::error(ErrorCode::NotFound, message)).await;
+            }
+            Err(e) => {
+                eprintln!("[uniffi] summarize_tag failed: {e}");
+                let message = format!("Couldn't summarize #{tag}: {e}");
+                self.emit(Event::failed(message, &e)).await;
+            }
+        }
+    }
+}
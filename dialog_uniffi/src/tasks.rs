@@ -0,0 +1,58 @@
+use crate::models::TaskStatus;
+use crate::undo::Change;
+use crate::{DIALOG, DialogClient, Note, convert_lib_note_to_uniffi, rt};
+use dialog_lib::TaskStatus as LibStatus;
+use std::sync::Arc;
+
+impl From<LibStatus> for TaskStatus {
+    fn from(status: LibStatus) -> Self {
+        match status {
+            LibStatus::Todo => TaskStatus::Todo,
+            LibStatus::Doing => TaskStatus::Doing,
+            LibStatus::Done => TaskStatus::Done,
+        }
+    }
+}
+
+impl From<TaskStatus> for LibStatus {
+    fn from(status: TaskStatus) -> Self {
+        match status {
+            TaskStatus::Todo => LibStatus::Todo,
+            TaskStatus::Doing => LibStatus::Doing,
+            TaskStatus::Done => LibStatus::Done,
+        }
+    }
+}
+
+// Quick notes that turn into todos: the task list reads all stored notes,
+// not just the ones loaded into the list.
+impl DialogClient {
+    pub fn get_tasks(&self, status: Option<TaskStatus>, limit: u32) -> Vec<Note> {
+        let dialog = DIALOG.get().unwrap();
+        match rt().block_on(dialog.list_tasks(status.map(Into::into), limit as usize)) {
+            Ok(lib_notes) => lib_notes
+                .into_iter()
+                .map(|note| convert_lib_note_to_uniffi(note).for_list())
+                .collect(),
+            Err(e) => {
+                eprintln!("[uniffi] list_tasks failed: {e}");
+                Vec::new()
+            }
+        }
+    }
+
+    pub(crate) async fn set_task_status(self: Arc<Self>, id: String, status: Option<TaskStatus>) {
+        let before = self
+            .notes
+            .read()
+            .await
+            .get(&id)
+            .and_then(|note| note.task_status);
+        let change = Change::SetTaskStatus {
+            id,
+            before,
+            after: status,
+        };
+        self.change(change).await;
+    }
+}
@@ -0,0 +1,209 @@
+use crate::models::{Note, TaskStatus};
+
+/// Oldest changes are forgotten past this many undo steps
+const UNDO_LIMIT: usize = 50;
+
+/// A change the user made to a note, holding enough to reverse it
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Change {
+    /// Removed from the list
+    Delete {
+        note: Note,
+    },
+    /// Put back into the list, the reverse of a delete
+    Restore {
+        note: Note,
+    },
+    SetRead {
+        id: String,
+        read: bool,
+    },
+    SetPinned {
+        id: String,
+        pinned: bool,
+    },
+    SetArchived {
+        id: String,
+        archived: bool,
+    },
+    /// Task status change, with the status it replaced
+    SetTaskStatus {
+        id: String,
+        before: Option<TaskStatus>,
+        after: Option<TaskStatus>,
+    },
+    /// Transcript edit, with the transcript it replaced
+    SetTranscript {
+        id: String,
+        before: Option<String>,
+        after: Option<String>,
+    },
+}
+
+impl Change {
+    pub fn note_id(&self) -> &str {
+        match self {
+            Change::Delete { note } | Change::Restore { note } => &note.id,
+            Change::SetRead { id, .. }
+            | Change::SetPinned { id, .. }
+            | Change::SetArchived { id, .. }
+            | Change::SetTaskStatus { id, .. }
+            | Change::SetTranscript { id, .. } => id,
+        }
+    }
+
+    /// The change that puts things back the way they were
+    pub fn inverse(&self) -> Change {
+        match self {
+            Change::Delete { note } => Change::Restore { note: note.clone() },
+            Change::Restore { note } => Change::Delete { note: note.clone() },
+            Change::SetRead { id, read } => Change::SetRead {
+                id: id.clone(),
+                read: !read,
+            },
+            Change::SetPinned { id, pinned } => Change::SetPinned {
+                id: id.clone(),
+                pinned: !pinned,
+            },
+            Change::SetArchived { id, archived } => Change::SetArchived {
+                id: id.clone(),
+                archived: !archived,
+            },
+            Change::SetTaskStatus { id, before, after } => Change::SetTaskStatus {
+                id: id.clone(),
+                before: *after,
+                after: *before,
+            },
+            Change::SetTranscript { id, before, after } => Change::SetTranscript {
+                id: id.clone(),
+                before: after.clone(),
+                after: before.clone(),
+            },
+        }
+    }
+
+    /// Whether applying this to `note` would change anything
+    pub fn changes(&self, note: &Note) -> bool {
+        match self {
+            Change::Delete { .. } | Change::Restore { .. } => true,
+            Change::SetRead { read, .. } => note.is_read != *read,
+            Change::SetPinned { pinned, .. } => note.is_pinned != *pinned,
+            Change::SetArchived { archived, .. } => note.is_archived != *archived,
+            Change::SetTaskStatus { after, .. } => note.task_status != *after,
+            Change::SetTranscript { after, .. } => note.transcript != *after,
+        }
+    }
+}
+
+/// Undo and redo history, newest last
+#[derive(Debug, Default)]
+pub(crate) struct UndoStack {
+    undo: Vec<Change>,
+    redo: Vec<Change>,
+}
+
+impl UndoStack {
+    /// Record a change the user just made. A new change forgets whatever
+    /// could have been redone.
+    pub fn record(&mut self, change: Change) {
+        self.redo.clear();
+        self.push_undo(change);
+    }
+
+    /// The change to reverse; hand it to `push_redo` once reversed
+    pub fn pop_undo(&mut self) -> Option<Change> {
+        self.undo.pop()
+    }
+
+    /// The change to make again; hand it to `push_undo` once made
+    pub fn pop_redo(&mut self) -> Option<Change> {
+        self.redo.pop()
+    }
+
+    pub fn push_undo(&mut self, change: Change) {
+        self.undo.push(change);
+        if self.undo.len() > UNDO_LIMIT {
+            self.undo.remove(0);
+        }
+    }
+
+    pub fn push_redo(&mut self, change: Change) {
+        self.redo.push(change);
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(id: &str) -> Change {
+        Change::SetRead {
+            id: id.to_string(),
+            read: true,
+        }
+    }
+
+    #[test]
+    fn test_undo_redo_order() {
+        let mut stack = UndoStack::default();
+        stack.record(read("a"));
+        stack.record(read("b"));
+
+        let undone = stack.pop_undo().unwrap();
+        assert_eq!(undone, read("b"));
+        stack.push_redo(undone);
+        assert!(stack.can_undo() && stack.can_redo());
+
+        let redone = stack.pop_redo().unwrap();
+        assert_eq!(redone, read("b"));
+        stack.push_undo(redone);
+        assert!(!stack.can_redo());
+
+        // A fresh change drops the redo history
+        let undone = stack.pop_undo().unwrap();
+        stack.push_redo(undone);
+        stack.record(read("c"));
+        assert!(!stack.can_redo());
+        assert_eq!(stack.pop_undo(), Some(read("c")));
+        assert_eq!(stack.pop_undo(), Some(read("a")));
+        assert!(!stack.can_undo());
+    }
+
+    #[test]
+    fn test_inverse_round_trips() {
+        let edit = Change::SetTranscript {
+            id: "a".to_string(),
+            before: None,
+            after: Some("hello".to_string()),
+        };
+        assert_eq!(edit.inverse().inverse(), edit);
+        assert_eq!(
+            read("a").inverse(),
+            Change::SetRead {
+                id: "a".to_string(),
+                read: false
+            }
+        );
+    }
+
+    #[test]
+    fn test_undo_limit() {
+        let mut stack = UndoStack::default();
+        for i in 0..UNDO_LIMIT + 5 {
+            stack.record(read(&i.to_string()));
+        }
+        let mut count = 0;
+        while stack.pop_undo().is_some() {
+            count += 1;
+        }
+        assert_eq!(count, UNDO_LIMIT);
+    }
+}
@@ -0,0 +1,51 @@
+use crate::models::Profile;
+use crate::{DIALOG, DialogClient, Event, rt};
+use nostr_sdk::prelude::*;
+use std::sync::Arc;
+
+fn to_profile(metadata: Metadata) -> Profile {
+    let pubkey = DIALOG.get().unwrap().public_key();
+    Profile {
+        pubkey: pubkey.to_bech32().unwrap_or_else(|_| pubkey.to_hex()),
+        name: metadata.name,
+        about: metadata.about,
+        picture: metadata.picture,
+    }
+}
+
+// The profile editor: a fresh key shows up as a name and picture, not a
+// hex string, when notes are shared or messages sent.
+impl DialogClient {
+    pub fn get_my_profile(&self) -> Option<Profile> {
+        match rt().block_on(DIALOG.get().unwrap().get_my_profile()) {
+            Ok(metadata) => metadata.map(to_profile),
+            Err(e) => {
+                eprintln!("[uniffi] get_my_profile failed: {e}");
+                None
+            }
+        }
+    }
+
+    pub(crate) async fn publish_profile(
+        self: Arc<Self>,
+        name: Option<String>,
+        about: Option<String>,
+        picture: Option<String>,
+    ) {
+        let dialog = DIALOG.get().unwrap();
+        match dialog
+            .publish_profile(name.as_deref(), about.as_deref(), picture.as_deref())
+            .await
+        {
+            Ok(metadata) => {
+                let profile = to_profile(metadata);
+                self.emit(Event::ProfilePublished { profile }).await;
+            }
+            Err(e) => {
+                eprintln!("[uniffi] publish_profile failed: {e}");
+                let message = format!("Couldn't publish your profile: {e}");
+                self.emit(Event::failed(message, &e)).await;
+            }
+        }
+    }
+}
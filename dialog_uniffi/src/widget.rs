@@ -0,0 +1,90 @@
+use crate::deep_link::note_link;
+use crate::models::{WidgetKind, WidgetNote, WidgetSnapshot};
+use crate::{DIALOG, DialogClient, rt};
+use dialog_lib::{WidgetKind as LibKind, WidgetNote as LibWidgetNote};
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Note changes within this window share one snapshot write
+const WIDGET_REFRESH_DEBOUNCE: Duration = Duration::from_secs(2);
+
+/// Snapshot access for widget extensions.
+///
+/// Like `QuickCapture`, this never opens the note database: the app keeps
+/// a snapshot file in the shared app group container up to date (see
+/// `Command::SetWidgetSnapshotPath`) and widgets only read it.
+pub struct WidgetReader {
+    snapshot_path: PathBuf,
+}
+
+impl WidgetReader {
+    pub fn new(snapshot_path: String) -> Self {
+        Self {
+            snapshot_path: PathBuf::from(snapshot_path),
+        }
+    }
+
+    /// None until the app has written a snapshot
+    pub fn get_widget_snapshot(&self, kind: WidgetKind) -> Option<WidgetSnapshot> {
+        match dialog_lib::read_widget_snapshot(&self.snapshot_path) {
+            Ok(snapshot) => snapshot.map(|snapshot| {
+                let snapshot = snapshot.for_kind(kind.into());
+                WidgetSnapshot {
+                    unread_count: snapshot.unread_count,
+                    recent: snapshot.recent.into_iter().map(widget_note).collect(),
+                    pinned: snapshot.pinned.map(widget_note),
+                    updated_at: snapshot.updated_at.as_u64() as i64,
+                }
+            }),
+            Err(e) => {
+                eprintln!("[uniffi] WidgetReader::get_widget_snapshot failed: {e}");
+                None
+            }
+        }
+    }
+}
+
+impl From<WidgetKind> for LibKind {
+    fn from(kind: WidgetKind) -> Self {
+        match kind {
+            WidgetKind::Small => LibKind::Small,
+            WidgetKind::Medium => LibKind::Medium,
+            WidgetKind::Large => LibKind::Large,
+        }
+    }
+}
+
+fn widget_note(note: LibWidgetNote) -> WidgetNote {
+    let id = note.id.to_hex();
+    WidgetNote {
+        link: note_link(&id).unwrap_or_default(),
+        id,
+        title: note.title,
+        created_at: note.created_at.as_u64() as i64,
+    }
+}
+
+impl DialogClient {
+    /// Start (or with None, stop) keeping a widget snapshot at `path`
+    pub(crate) fn set_widget_snapshot_path(&self, path: Option<String>) {
+        *self.widget_path.write().unwrap() = path.map(PathBuf::from);
+        self.refresh_widget_snapshot();
+    }
+
+    /// Rewrite the widget snapshot shortly, once note changes settle
+    pub(crate) fn refresh_widget_snapshot(&self) {
+        let Some(path) = self.widget_path.read().unwrap().clone() else {
+            return;
+        };
+        let mut slot = self.widget_handle.lock().unwrap();
+        let handle = rt().spawn(async move {
+            tokio::time::sleep(WIDGET_REFRESH_DEBOUNCE).await;
+            if let Err(e) = DIALOG.get().unwrap().write_widget_snapshot(&path).await {
+                eprintln!("[uniffi] write_widget_snapshot failed: {e}");
+            }
+        });
+        if let Some(previous) = slot.replace(handle) {
+            previous.abort();
+        }
+    }
+}
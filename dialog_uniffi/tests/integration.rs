@@ -1,6 +1,6 @@
 mod common;
 
-use common::{TestServer, TEST_RELAY_URL};
+use common::TestServer;
 use dialog_uniffi::{DialogClient, Event, Command, DialogListener};
 use std::sync::{mpsc, Arc};
 use std::time::Duration;
@@ -18,13 +18,10 @@ impl DialogListener for TestListener {
 #[test]
 fn uniffi_end_to_end_note_flow() {
     // Start fresh relay
-    let _server = TestServer::new();
+    let server = TestServer::new();
 
     // Create client
-    let test_nsec = std::env::var("DIALOG_NSEC_TEST")
-        .or_else(|_| std::env::var("DIALOG_NSEC"))
-        .expect("Set DIALOG_NSEC_TEST or DIALOG_NSEC in CI/environment");
-    let client = Arc::new(DialogClient::new(test_nsec));
+    let client = Arc::new(DialogClient::new(server.nsec.clone()));
 
     // Wire listener
     let (tx, rx) = mpsc::channel();
@@ -34,7 +31,7 @@ fn uniffi_end_to_end_note_flow() {
 
     // Connect relay
     client.clone().send_command(Command::ConnectRelay {
-        relay_url: TEST_RELAY_URL.to_string(),
+        relay_url: server.url(),
     });
 
     // Wait for initial ready/notes
@@ -1,5 +1,7 @@
 import SwiftUI
 import Combine
+import UserNotifications
+import CoreSpotlight
 import Dialog
 
 // ViewModel using fire-and-forget pattern
@@ -10,19 +12,68 @@ class InboxViewModel: ObservableObject {
     @Published var allTags: [String] = []
     @Published var isLoading = false
     @Published var errorMessage: String?
+    @Published var errorAction: SuggestedAction?
+    @Published var isUploadingSlowly = false
+    @Published var messages: [DirectMessage] = []
+    @Published var isInboxEnabled = false
+    @Published var contacts: [Contact] = []
+    @Published var linkPreviews: [String: [LinkPreview]] = [:]
+    @Published var canUndo = false
+    @Published var canRedo = false
+    // Set by a dialog://note link; the inbox scrolls to it
+    @Published var focusedNoteId: String?
+    // Commands still running; a write among them means "still saving"
+    @Published var pendingCommands: [PendingCommand] = []
+    // Notes saved offline and not yet sent, for a pending badge per note
+    @Published var offlineNoteIds: Set<String> = []
+    // Notes written today and days in a row with a note
+    @Published var todayCount: UInt32 = 0
+    @Published var streakDays: UInt32 = 0
+    // What each relay returned to the last plain sync, for the settings screen
+    @Published var relaySyncStats: [RelaySyncStats] = []
+    // How the last sync ran, e.g. "Negentropy, 12 received, 0 sent in 840 ms"
+    @Published var lastSyncSummary: String?
+    // A note held back because it looks like it contains a private key
+    @Published var secretNoteText: String?
+    // Send key of the held-back note, so confirming it twice saves it once
+    private var secretNoteKey = ""
+    // Relays close to the event budget; non-empty shows "Clean up relays"
+    @Published var relaysNearEventLimit: [RelayEventCount] = []
+    // Set once DeleteAccount went through; the app goes back to onboarding
+    @Published var isAccountDeleted = false
+    // Outcome of the last import, for the "Imported 12 notes" sheet
+    @Published var lastImport: ImportReport?
+    // Our name and picture as others see them; nil until loaded or published
+    @Published var myProfile: Profile?
+    // Group shares by note id, for the detail view's "Shared with" section
+    @Published var noteShares: [String: [GroupShare]] = [:]
     
     private let client: DialogClient
     
     private let userDefaults = UserDefaults.standard
     private let scrollPositionKey = "dialog.scrollPosition"
+    private let spotlightCursorKey = "dialog.spotlightCursor"
+    
+    private static let keychainService = "com.dialog.app"
+    private static let keychainAccount = "nsec"
     
     init() {
-        // Read nsec from environment for development (set in Xcode scheme)
+        let service = Self.keychainService
+        let account = Self.keychainAccount
+        if hasNsecInKeychain(service: service, account: account) {
+            self.client = DialogClient.newFromKeychain(service: service, account: account)
+            return
+        }
+        // Onboarding: read nsec from environment for development (set in
+        // Xcode scheme) and keep it in the Keychain from then on
         let env = ProcessInfo.processInfo.environment
         guard let nsec = env["DIALOG_NSEC"], !nsec.isEmpty else {
             fatalError("DIALOG_NSEC not set. Configure in your Xcode Run scheme Environment Variables.")
         }
-        self.client = DialogClient(nsec: nsec)
+        guard storeNsecInKeychain(service: service, account: account, nsec: nsec) != nil else {
+            fatalError("DIALOG_NSEC is not a valid nsec, or the Keychain refused it.")
+        }
+        self.client = DialogClient.newFromKeychain(service: service, account: account)
     }
     
     var displayedNotes: [Note] {
@@ -47,9 +98,21 @@ class InboxViewModel: ObservableObject {
             }
         }
         
+        // Receive incremental list updates instead of full reloads
+        client.setCapabilities(capabilities: ClientCapabilities(notesDiff: true))
+        
         // Start the client with the listener (fire-and-forget)
         client.start(listener: listener)
         
+        // How this device appears in other devices' device lists
+        client.sendCommand(cmd: Command.setDeviceName(name: UIDevice.current.name))
+        
+        // Recorded on new notes, to tell which build wrote a note
+        let info = Bundle.main.infoDictionary
+        let version = info?["CFBundleShortVersionString"] as? String ?? "?"
+        let build = info?["CFBundleVersion"] as? String ?? "?"
+        client.setAppVersion(version: "Dialog \(version) (\(build))")
+        
         // Connect to a relay so create/list/watch work
         // Hardcode relay for reliability during development
         client.sendCommand(cmd: Command.connectRelay(relayUrl: "wss://relay.damus.io"))
@@ -59,6 +122,14 @@ class InboxViewModel: ObservableObject {
         self.notes = client.getNotes(limit: 100, tag: currentTag)
         self.allTags = client.getAllTags()
         print("[swift] initial notes count", self.notes.count)
+        
+        syncSpotlight()
+        
+        // Widgets read this instead of opening the note database
+        if let container = FileManager.default.containerURL(forSecurityApplicationGroupIdentifier: "group.com.dialog.app") {
+            let path = container.appendingPathComponent("widget.json").path
+            client.sendCommand(cmd: Command.setWidgetSnapshotPath(path: path))
+        }
     }
     
     func stop() {
@@ -80,6 +151,20 @@ class InboxViewModel: ObservableObject {
             self.allTags = client.getAllTags()
             self.isLoading = false
             
+        case .notesDiff(let added, let updated, let removedIds):
+            let removed = Set(removedIds)
+            notes.removeAll { removed.contains($0.id) }
+            for note in updated {
+                if let index = notes.firstIndex(where: { $0.id == note.id }) {
+                    notes[index] = note
+                }
+            }
+            for note in added where !notes.contains(where: { $0.id == note.id }) {
+                notes.append(note)
+            }
+            self.allTags = client.getAllTags()
+            self.isLoading = false
+            
         case .noteAdded(let note):
             if let idx = self.notes.firstIndex(where: { $0.id == note.id }) {
                 self.notes[idx] = note
@@ -106,18 +191,176 @@ class InboxViewModel: ObservableObject {
         case .syncStatusChanged(let syncing):
             self.isLoading = syncing
             
-        case .error(let message):
+        case .syncProgress(let relays):
+            self.relaySyncStats = relays
+            
+        case .syncComplete(let mode, let received, let sent, let durationMs, let fellBack):
+            let method = fellBack ? "Plain (negentropy failed)" : "\(mode)"
+            self.lastSyncSummary = "\(method), \(received) received, \(sent) sent in \(durationMs) ms"
+            
+        case .clockSkewWarning(let skewSecs):
+            self.errorMessage = "Your clock appears to be off by \(skewSecs / 60) minutes; note order may be wrong."
+            
+        case .publishThrottled(let throttled):
+            self.isUploadingSlowly = throttled
+            
+        case .eventBudgetWarning(let relays):
+            self.relaysNearEventLimit = relays
+            
+        case .remoteCompacted(let removed):
+            print("[swift] compaction removed \(removed) events")
+            
+        case .slowOperation(let name, let ms):
+            print("[swift] slow \(name): \(ms)ms")
+            
+        case .messagesLoaded(let messages):
+            self.messages = messages
+            
+        case .messageReceived(let message):
+            if !self.messages.contains(where: { $0.id == message.id }) {
+                self.messages.insert(message, at: 0)
+            }
+            
+        case .contactsLoaded(let contacts):
+            self.contacts = contacts
+            
+        case .profilePublished(let profile):
+            self.myProfile = profile
+            
+        case .noteShared(let share):
+            var shares = self.noteShares[share.noteId] ?? []
+            shares.removeAll { $0.sharedAt == share.sharedAt }
+            shares.insert(share, at: 0)
+            self.noteShares[share.noteId] = shares
+            
+        case .linkPreviewsLoaded(let noteId, let previews):
+            self.linkPreviews[noteId] = previews
+            
+        case .transcriptionRequested:
+            // No transcription service wired up yet; voice notes stay untranscribed
+            break
+            
+        case .notificationWorthy(let note, let rule):
+            postNotification(for: note, tag: rule.tag)
+            
+        case .undoStateChanged(let canUndo, let canRedo):
+            self.canUndo = canUndo
+            self.canRedo = canRedo
+            
+        case .activityUpdated(let todayCount, let streakDays):
+            self.todayCount = todayCount
+            self.streakDays = streakDays
+            
+        case .offlineQueueChanged(let pending):
+            self.offlineNoteIds = Set(pending.map { $0.noteId })
+            
+        case .queueChanged(let pending):
+            self.pendingCommands = pending
+            
+        case .potentialSecretDetected(let text, _, let sendKey):
+            self.secretNoteText = text
+            self.secretNoteKey = sendKey
+            
+        case .accountDeleted(_, _, _):
+            self.notes = []
+            self.isAccountDeleted = true
+            
+        case .importFinished(let report):
+            self.lastImport = report
+            
+        case .indexRebuilt(let notes, let indexed, let fetched):
+            print("[swift] rebuilt index: \(notes) notes, \(indexed) indexed, \(fetched) fetched")
+            self.isLoading = false
+            
+        case .error(let message, _, let retryable, _, let suggestedAction):
             self.errorMessage = message
+            // Drives the error banner's button, e.g. "Try Again"
+            self.errorAction = retryable ? .retry : suggestedAction
         }
     }
     
-    func createNote(text: String) {
+    // Call only after the user confirmed: public posts can't be taken back
+    func publishPublicly(_ note: Note, stripTags: Bool) {
+        client.sendCommand(cmd: Command.publishPublic(id: note.id, stripTags: stripTags))
+    }
+    
+    // Share sheet picker: show cached contacts now, refresh in the background
+    func loadContacts() {
+        contacts = client.getContacts()
+        client.sendCommand(cmd: Command.loadContacts)
+    }
+    
+    // Profile editor: fill the form with what relays know about us
+    func loadMyProfile() {
+        myProfile = client.getMyProfile()
+    }
+    
+    func publishProfile(name: String?, about: String?, picture: String?) {
+        client.sendCommand(cmd: Command.publishProfile(name: name, about: about, picture: picture))
+    }
+    
+    // Share sheet: send a note privately to the picked contacts
+    func shareNote(_ note: Note, with contacts: [Contact]) {
+        let recipients = contacts.map { $0.pubkey }
+        client.sendCommand(cmd: Command.shareNote(id: note.id, recipients: recipients))
+    }
+    
+    // Detail view: who the note went to and whether it reached them
+    func loadShares(for note: Note) {
+        noteShares[note.id] = client.getNoteShares(id: note.id)
+    }
+    
+    func resendShare(_ share: GroupShare) {
+        client.sendCommand(cmd: Command.resendShare(id: share.noteId, sharedAt: share.sharedAt))
+    }
+    
+    // Relay settings: which kinds each relay is sent; empty kinds sends all
+    func relayPolicies() -> [RelayPolicy] {
+        client.getRelayPolicies()
+    }
+    
+    func setRelayPolicy(relayUrl: String, kinds: [String]) {
+        client.sendCommand(cmd: Command.setRelayPolicy(relayUrl: relayUrl, kinds: kinds))
+    }
+    
+    func setInboxEnabled(_ enabled: Bool) {
+        isInboxEnabled = enabled
+        client.sendCommand(cmd: Command.setInboxEnabled(enabled: enabled))
+    }
+    
+    // `sendKey` is made when the composer opens, so a double tap or a
+    // resend of the same draft creates one note
+    func createNote(text: String, sendKey: String) {
         let trimmed = text.trimmingCharacters(in: .whitespacesAndNewlines)
         guard !trimmed.isEmpty else { return }
         
         // Fire-and-forget command
         print("[swift] createNote -> sendCommand .createNote text='\(trimmed)' len=\(trimmed.count)")
-        client.sendCommand(cmd: Command.createNote(text: trimmed))
+        client.sendCommand(cmd: Command.createNote(text: trimmed, sendKey: sendKey))
+    }
+    
+    // App Intents / Shortcuts capture: explicit tags and an optional
+    // original date for imported items
+    func createNote(text: String, tags: [String], createdAt: Date? = nil) {
+        let trimmed = text.trimmingCharacters(in: .whitespacesAndNewlines)
+        guard !trimmed.isEmpty || !tags.isEmpty else { return }
+        let override = createdAt.map { Int64($0.timeIntervalSince1970) }
+        client.sendCommand(cmd: Command.createNoteStructured(
+            text: trimmed, tags: tags, createdAtOverride: override))
+    }
+    
+    // Works offline too: the edit shows right away and is sent later
+    func editNote(_ note: Note, text: String) {
+        let trimmed = text.trimmingCharacters(in: .whitespacesAndNewlines)
+        guard !trimmed.isEmpty, trimmed != note.text else { return }
+        client.sendCommand(cmd: Command.editNote(id: note.id, text: trimmed))
+    }
+    
+    // The user confirmed the held-back note should be saved as written
+    func createSecretNoteAnyway() {
+        guard let text = secretNoteText else { return }
+        secretNoteText = nil
+        client.sendCommand(cmd: Command.createNoteAnyway(text: text, sendKey: secretNoteKey))
     }
     
     func setTagFilter(_ tag: String?) {
@@ -125,17 +368,262 @@ class InboxViewModel: ObservableObject {
         client.sendCommand(cmd: Command.setTagFilter(tag: tag))
     }
     
+    // Shown as a cancel button while isLoading
+    func cancelSync() {
+        client.sendCommand(cmd: Command.cancelSync)
+    }
+    
     func markAsRead(_ noteId: String) {
         // Fire-and-forget command
         client.sendCommand(cmd: Command.markAsRead(id: noteId))
     }
     
+    // Ping for new notes under `tag`, e.g. #urgent
+    func setNotifyRule(tag: String, notify: NotifyRule) {
+        if notify != .off {
+            UNUserNotificationCenter.current().requestAuthorization(options: [.alert, .sound]) { _, _ in }
+        }
+        client.sendCommand(cmd: Command.setNotifyRule(tag: tag, notify: notify))
+    }
+    
+    private func postNotification(for note: Note, tag: String) {
+        let content = UNMutableNotificationContent()
+        content.title = "#\(tag)"
+        content.body = note.text
+        content.sound = .default
+        if let link = client.getDeepLink(noteId: note.id) {
+            content.userInfo = ["link": link]
+        }
+        let request = UNNotificationRequest(identifier: note.id, content: content, trigger: nil)
+        UNUserNotificationCenter.current().add(request)
+    }
+    
+    // dialog:// links from notifications, Spotlight and widgets
+    func openDeepLink(_ url: URL) {
+        switch client.parseDeepLink(url: url.absoluteString) {
+        case .note(let id):
+            setTagFilter(nil)
+            focusedNoteId = id
+            markAsRead(id)
+        case .tag(let tag):
+            setTagFilter(tag)
+        case nil:
+            print("[swift] ignoring link \(url)")
+        }
+    }
+    
+    // Apply note changes since the last sync to the Spotlight index
+    func syncSpotlight() {
+        let cursor = userDefaults.object(forKey: spotlightCursorKey) as? Int64
+        let changes = client.getSearchIndexEntries(since: cursor)
+        let index = CSSearchableIndex.default()
+        let items = changes.entries.map { entry -> CSSearchableItem in
+            let attributes = CSSearchableItemAttributeSet(contentType: .text)
+            attributes.title = entry.title
+            attributes.keywords = entry.tags
+            attributes.contentModificationDate = Date(timeIntervalSince1970: TimeInterval(entry.modifiedAt))
+            return CSSearchableItem(uniqueIdentifier: entry.id, domainIdentifier: "notes", attributeSet: attributes)
+        }
+        let apply = { [userDefaults, spotlightCursorKey] in
+            index.deleteSearchableItems(withIdentifiers: changes.removedIds)
+            index.indexSearchableItems(items) { error in
+                if let error {
+                    print("[swift] spotlight index failed:", error.localizedDescription)
+                } else {
+                    userDefaults.set(changes.cursor, forKey: spotlightCursorKey)
+                }
+            }
+        }
+        if changes.reset {
+            index.deleteSearchableItems(withDomainIdentifiers: ["notes"]) { _ in apply() }
+        } else {
+            apply()
+        }
+    }
+    
+    // Settings: show previewRetention() before applying new rules
+    func setRetention(_ rules: [RetentionRule]) {
+        client.sendCommand(cmd: Command.setRetention(rules: rules))
+    }
+    
+    func previewRetention() -> RetentionReport {
+        client.previewRetention()
+    }
+    
+    // For relays that cap stored events per user; nil removes the budget
+    func setEventBudget(_ budget: UInt64?) {
+        client.sendCommand(cmd: Command.setEventBudget(budget: budget))
+    }
+    
+    func compactRelays() {
+        client.sendCommand(cmd: Command.compactRemote)
+    }
+    
+    // "Review your notes": long-unopened notes, longest-forgotten first
+    func reviewQueue(limit: UInt32 = 10) -> [Note] {
+        client.getReviewQueue(limit: limit)
+    }
+    
+    // Reading mode: long-form notes under the current tag, newest first
+    func longReads(minWords: UInt32 = 300, limit: UInt32 = 100) -> [Note] {
+        let filter = NoteFilter(tag: currentTag, minWords: minWords, maxWords: nil)
+        return client.getFilteredNotes(filter: filter, limit: limit, order: .newestFirst)
+    }
+    
+    // Tasks tab: notes made tasks, optionally only todo, doing or done
+    func tasks(status: TaskStatus? = nil, limit: UInt32 = 100) -> [Note] {
+        client.getTasks(status: status, limit: limit)
+    }
+    
+    // Swipe actions on a note: nil turns a task back into a plain note
+    func setTaskStatus(_ note: Note, _ status: TaskStatus?) {
+        client.sendCommand(cmd: Command.setTaskStatus(id: note.id, status: status))
+    }
+    
+    // Composer's "suggested tags" row for the draft being typed
+    func suggestedTags(for draft: String, limit: UInt32 = 3) -> [String] {
+        client.suggestTagsForText(text: draft, limit: limit)
+    }
+    
+    // Composer warnings: too large, rejected by a relay, possible secrets
+    func validate(draft: String) -> ValidationReport? {
+        client.validateNote(text: draft)
+    }
+    
+    // Status screen: relays, pending outbox, last sync and database size
+    func status() -> DialogStatus {
+        client.getStatus()
+    }
+    
+    // Logged with each MetricKit payload; steady growth across payloads
+    // points at a leak
+    func runtimeStats() -> RuntimeStats {
+        client.getRuntimeStats()
+    }
+    
+    // Warn before quitting while a change is still being published
+    var hasPendingWrites: Bool {
+        pendingCommands.contains { $0.writes }
+    }
+    
+    // "Copy link" for Things/OmniFocus; opening it comes back via parseDeepLink
+    func externalRef(for note: Note) -> String? {
+        client.getExternalRef(noteId: note.id)
+    }
+    
+    // Devices writing with this key; ignoring one hides its notes
+    func devices() -> [Device] {
+        client.getDevices()
+    }
+    
+    func setDeviceIgnored(_ deviceId: String, ignored: Bool) {
+        client.sendCommand(cmd: Command.setDeviceIgnored(id: deviceId, ignored: ignored))
+    }
+    
+    // "Written on iPad, Dialog 1.4 (52), via share extension" in the detail view
+    func provenance(for note: Note) -> Provenance? {
+        client.getNoteProvenance(id: note.id)
+    }
+    
+    // Old notes beyond the limit show a download button (isHydrated false)
+    func setLocalCacheLimit(bytes: UInt64?) {
+        client.sendCommand(cmd: Command.setLocalCacheLimit(bytes: bytes))
+    }
+    
+    // Like "Download originals": the note's recordings stay on the device
+    func setKeepOffline(_ noteId: String, keep: Bool) {
+        client.sendCommand(cmd: Command.setKeepOffline(id: noteId, keep: keep))
+    }
+    
+    func setAttachmentCacheBudget(bytes: UInt64?) {
+        client.sendCommand(cmd: Command.setAttachmentCacheBudget(bytes: bytes))
+    }
+    
+    // Settings > Troubleshooting: for when lists or search look wrong
+    func rebuildIndex(fromRelays: Bool) {
+        isLoading = true
+        client.sendCommand(cmd: Command.rebuildIndex(fromRelays: fromRelays))
+    }
+    
+    // Call from the confirmation alert's destructive button only
+    func deleteAccount(deleteRemote: Bool) {
+        let token = client.requestAccountDeletionToken()
+        client.sendCommand(cmd: Command.deleteAccount(token: token, deleteRemote: deleteRemote))
+    }
+    
+    // Debug menu: flaky network on demand (needs a CHAOS=1 package build)
+    func setChaos(_ config: ChaosConfig?) {
+        client.sendCommand(cmd: Command.setChaos(config: config))
+    }
+    
+    // Moving to a new device: export here, import there with the same key
+    func exportSettings() -> String? {
+        client.exportSettings()
+    }
+    
+    func importSettings(_ blob: String) {
+        client.sendCommand(cmd: Command.importSettings(blob: blob))
+    }
+    
+    // A file picked in the Files app; duplicates are skipped unless asked
+    func importNotes(at url: URL, format: ImportFormat, policy: DuplicatePolicy = .skip) {
+        client.sendCommand(cmd: Command.importNotes(
+            path: url.path, format: format, policy: policy))
+    }
+    
+    // Multi-select mode: tag or untag the selected notes
+    func bulkTag(_ noteIds: [String], add: [String], remove: [String]) {
+        client.sendCommand(cmd: Command.bulkTag(ids: noteIds, add: add, remove: remove))
+    }
+    
+    func lockNote(_ noteId: String, passphrase: String) {
+        client.sendCommand(cmd: Command.lockNote(id: noteId, passphrase: passphrase))
+    }
+    
+    func unlockNote(_ noteId: String, passphrase: String) {
+        client.sendCommand(cmd: Command.unlockNote(id: noteId, passphrase: passphrase))
+    }
+    
+    // Hide unlocked notes again, e.g. when the app goes to the background
+    func relockNotes() {
+        client.sendCommand(cmd: Command.relockNotes)
+    }
+    
+    // Shake-to-undo
+    func undo() {
+        client.sendCommand(cmd: Command.undo)
+    }
+    
+    func redo() {
+        client.sendCommand(cmd: Command.redo)
+    }
+    
     func selectNote(_ note: Note) {
         // Mark as read when selected
         markAsRead(note.id)
+        hydrate(note)
+        client.sendCommand(cmd: Command.recordView(id: note.id))
         print("Selected note: \(note.id)")
     }
     
+    // Evicted notes are fetched again when opened; NoteUpdated brings the text
+    func hydrate(_ note: Note) {
+        guard !note.isHydrated else { return }
+        client.sendCommand(cmd: Command.hydrateNote(id: note.id))
+    }
+    
+    // Long notes arrive as previews; the detail view needs the whole text
+    func fullNote(_ note: Note) -> Note {
+        guard note.isTruncated else { return note }
+        return client.getNoteFull(id: note.id) ?? note
+    }
+    
+    // Tapping the "edited" marker: earlier versions, newest first
+    func editHistory(_ note: Note) -> [Note] {
+        guard note.edited else { return [note] }
+        return client.getEditHistory(id: note.id)
+    }
+    
     func bubblePosition(for index: Int) -> BubblePosition {
         guard index >= 0 && index < displayedNotes.count else { return .solo }
         
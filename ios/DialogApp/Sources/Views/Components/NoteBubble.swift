@@ -89,6 +89,12 @@ struct NoteBubble: View {
             tags: ["preview"],
             createdAt: Int64(Date().timeIntervalSince1970),
             isRead: false,
+            isPinned: false,
+            isArchived: false,
+            publicId: nil,
+            audio: nil,
+            transcript: nil,
+            isLocked: false,
             isSynced: false
         ),
         position: .solo,
@@ -105,6 +111,12 @@ struct NoteBubble: View {
                 tags: ["work"],
                 createdAt: Int64(Date().timeIntervalSince1970),
                 isRead: true,
+                isPinned: false,
+                isArchived: false,
+                publicId: nil,
+                audio: nil,
+                transcript: nil,
+                isLocked: false,
                 isSynced: true
             ),
             position: .top,
@@ -117,6 +129,12 @@ struct NoteBubble: View {
                 tags: ["work"],
                 createdAt: Int64(Date().timeIntervalSince1970 + 30),
                 isRead: true,
+                isPinned: false,
+                isArchived: false,
+                publicId: nil,
+                audio: nil,
+                transcript: nil,
+                isLocked: false,
                 isSynced: true
             ),
             position: .middle,
@@ -129,6 +147,12 @@ struct NoteBubble: View {
                 tags: ["work", "important"],
                 createdAt: Int64(Date().timeIntervalSince1970 + 45),
                 isRead: false,
+                isPinned: false,
+                isArchived: false,
+                publicId: nil,
+                audio: nil,
+                transcript: nil,
+                isLocked: false,
                 isSynced: false
             ),
             position: .bottom,
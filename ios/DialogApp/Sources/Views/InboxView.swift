@@ -4,6 +4,8 @@ import Dialog
 struct InboxView: View {
     @StateObject private var viewModel = InboxViewModel()
     @State private var messageText = ""
+    // One per draft: made when typing starts in an empty composer
+    @State private var composeKey = UUID().uuidString
     @State private var showingTopicPicker = false
     @FocusState private var isInputFocused: Bool
     @State private var lastVisibleNoteId: String?
@@ -70,6 +72,12 @@ struct InboxView: View {
                             }
                         }
                     }
+                    .onChange(of: viewModel.focusedNoteId) { _, noteId in
+                        guard let noteId else { return }
+                        withAnimation {
+                            proxy.scrollTo(noteId, anchor: .center)
+                        }
+                    }
                     .onDisappear {
                         // Save scroll position when view disappears
                         viewModel.saveScrollPosition(for: lastVisibleNoteId)
@@ -80,13 +88,21 @@ struct InboxView: View {
                 InputBar(
                     text: $messageText,
                     onSend: {
-                        viewModel.createNote(text: messageText)
+                        viewModel.createNote(text: messageText, sendKey: composeKey)
                         messageText = ""
                     },
                     isEnabled: !messageText.trimmingCharacters(in: .whitespacesAndNewlines).isEmpty
                 )
+                .onChange(of: messageText) { oldText, newText in
+                    if oldText.isEmpty && !newText.isEmpty {
+                        composeKey = UUID().uuidString
+                    }
+                }
             }
         }
+        .onOpenURL { url in
+            viewModel.openDeepLink(url)
+        }
         .sheet(isPresented: $showingTopicPicker) {
             TopicPickerView(
                 selectedTag: $viewModel.currentTag,
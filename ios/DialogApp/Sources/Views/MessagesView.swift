@@ -0,0 +1,32 @@
+import SwiftUI
+import Dialog
+
+// Private messages other people sent us, shown separately from notes
+struct MessagesView: View {
+    @ObservedObject var viewModel: InboxViewModel
+    
+    var body: some View {
+        List(viewModel.messages, id: \.id) { message in
+            VStack(alignment: .leading, spacing: 4) {
+                HStack {
+                    Text(message.senderName ?? String(message.senderPubkey.prefix(16)) + "…")
+                        .font(.headline)
+                    Spacer()
+                    Text(Date(timeIntervalSince1970: TimeInterval(message.createdAt)), style: .relative)
+                        .font(.caption)
+                        .foregroundStyle(.secondary)
+                }
+                Text(message.text)
+                    .font(.body)
+            }
+            .padding(.vertical, 4)
+        }
+        .navigationTitle("Messages")
+        .overlay {
+            if viewModel.messages.isEmpty {
+                Text("No messages")
+                    .foregroundStyle(.secondary)
+            }
+        }
+    }
+}